@@ -565,6 +565,29 @@ fn bench_render_svg(c: &mut Criterion) {
     group.finish();
 }
 
+// ─── Shared synthetic corpus (fm_core::corpus) ──────────────────────────────
+
+fn bench_standard_corpus(c: &mut Criterion) {
+    let mut group = c.benchmark_group("corpus");
+    let config = fm_render_svg::SvgRenderConfig::default();
+
+    for (label, ir) in fm_core::corpus::standard_corpus() {
+        group.bench_with_input(BenchmarkId::new("layout", &label), &ir, |b, ir| {
+            b.iter(|| fm_layout::layout_diagram(ir));
+        });
+        let layout = fm_layout::layout_diagram(&ir);
+        group.bench_with_input(
+            BenchmarkId::new("render_svg", &label),
+            &(&ir, &layout),
+            |b, (ir, layout)| {
+                b.iter(|| fm_render_svg::render_svg_with_layout(ir, layout, &config));
+            },
+        );
+    }
+
+    group.finish();
+}
+
 // ─── Full pipeline benchmarks ───────────────────────────────────────────────
 
 fn bench_full_pipeline(c: &mut Criterion) {
@@ -1019,6 +1042,7 @@ criterion_group!(
     bench_full_pipeline_wide,
     bench_full_pipeline_mindmap,
     bench_render_svg,
+    bench_standard_corpus,
     bench_render_nonflowchart,
     bench_full_pipeline,
     bench_wide_stages,
@@ -26,8 +26,6 @@
 #[global_allocator]
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
-#[cfg(feature = "png")]
-use std::collections::BTreeMap;
 use std::io::{self, IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
 use std::time::Instant;
@@ -49,23 +47,31 @@ use fm_core::{
     MermaidLayoutDecisionExplanation, MermaidLayoutDecisionLedger, MermaidLinkMode,
     MermaidNativePressureSignals, MermaidParseMode, MermaidTier, StructuredDiagnostic,
     capability_matrix, capability_matrix_json_pretty, mermaid_layout_guard_observability,
+    validate_ir,
 };
 #[cfg(all(feature = "fnx-integration", not(target_arch = "wasm32")))]
 use fm_layout::fnx_diagnostics::{FnxAnalysisResults, FnxDiagnosticSeverity, analyze_structure};
 use fm_layout::{
-    CycleStrategy, EdgeRouting, LayoutAlgorithm, LayoutConfig, LayoutGuardrails, TracedLayout,
-    build_layout_decision_ledger, build_layout_guard_report_with_pressure,
-    layout_diagram_traced_with_config_and_guardrails, layout_source_map,
+    ComponentPacking, CycleStrategy, EdgeRouting, LayoutAlgorithm, LayoutConfig, LayoutGuardrails,
+    RankAlign, RankStrategy, TracedLayout, build_layout_decision_ledger,
+    build_layout_guard_report_with_pressure, layout_diagram_traced_with_config_and_guardrails,
+    layout_source_map,
 };
 use fm_parser::{
     ParserConfig, capture_format_complement, detect_type_with_confidence_and_config,
     first_significant_line, parse_evidence_json, parse_with_mode, parse_with_mode_and_config,
 };
 use fm_render_svg::{
-    A11yConfig, SvgRenderConfig, ThemePreset, describe_diagram_with_layout, render_svg_with_layout,
+    A11yConfig, SvgRenderConfig, ThemePreset, describe_diagram_with_layout, render_diff_svg,
+    render_svg_with_layout,
+};
+#[cfg(feature = "png")]
+use fm_render_svg::{
+    make_config_raster_safe, resolve_svg_custom_properties_for_rasterization, svg_to_png,
 };
 use fm_render_term::{
-    TermRenderConfig, diff_diagrams, render_diff_plain, render_diff_summary,
+    AutoGlyphMode, ColorMode, LegendPosition, TermRenderConfig, diff_diagrams, render_diff_diagram,
+    render_diff_plain, render_diff_side_by_side, render_diff_summary,
     render_diff_terminal_with_config, render_term_with_layout_and_config,
 };
 use serde::{Deserialize, Serialize};
@@ -173,6 +179,10 @@ enum Command {
         #[arg(long)]
         json: bool,
 
+        /// Draw terminal output with ASCII glyphs when stdout is not a terminal.
+        #[arg(long)]
+        ascii_on_pipe: bool,
+
         /// Embed source-span metadata attributes in SVG output.
         #[arg(long, default_value_t = false)]
         embed_source_spans: bool,
@@ -395,6 +405,12 @@ enum DiffOutputFormat {
     Summary,
     Plain,
     Terminal,
+    /// The new diagram with removed elements merged back in, colored or marked by status.
+    Overlay,
+    /// Both diagrams drawn next to each other, common nodes on the same rows.
+    SideBySide,
+    /// An SVG of the new diagram that animates the changes from the old one.
+    Svg,
     Json,
 }
 
@@ -437,6 +453,7 @@ enum LayoutAlgorithmArg {
     Auto,
     Sugiyama,
     Force,
+    Stress,
     Tree,
     Radial,
     Timeline,
@@ -452,6 +469,7 @@ impl LayoutAlgorithmArg {
             Self::Auto => LayoutAlgorithm::Auto,
             Self::Sugiyama => LayoutAlgorithm::Sugiyama,
             Self::Force => LayoutAlgorithm::Force,
+            Self::Stress => LayoutAlgorithm::Stress,
             Self::Tree => LayoutAlgorithm::Tree,
             Self::Radial => LayoutAlgorithm::Radial,
             Self::Timeline => LayoutAlgorithm::Timeline,
@@ -581,9 +599,14 @@ struct FrankenmermaidParserConfig {
 struct FrankenmermaidLayoutConfig {
     algorithm: Option<String>,
     cycle_strategy: Option<String>,
+    rank_strategy: Option<String>,
+    align: Option<String>,
     node_spacing: Option<f32>,
     rank_spacing: Option<f32>,
     edge_routing: Option<String>,
+    route_around_clusters: Option<bool>,
+    target_aspect_ratio: Option<f32>,
+    component_packing: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, Default)]
@@ -598,12 +621,18 @@ struct FrankenmermaidRenderConfig {
 #[serde(default, deny_unknown_fields)]
 struct FrankenmermaidSvgConfig {
     theme: Option<String>,
+    width: Option<f32>,
+    height: Option<f32>,
+    max_width: Option<f32>,
+    scale: Option<f32>,
     rounded_corners: Option<f32>,
     shadows: Option<bool>,
     gradients: Option<bool>,
     accessibility: Option<bool>,
+    focus_order: Option<bool>,
     enable_links: Option<bool>,
     link_mode: Option<String>,
+    layers: Option<bool>,
 }
 
 #[derive(Debug, Clone, Deserialize, Default)]
@@ -612,6 +641,8 @@ struct FrankenmermaidTermConfig {
     tier: Option<String>,
     unicode: Option<bool>,
     minimap: Option<bool>,
+    color: Option<String>,
+    legend: Option<String>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -975,6 +1006,7 @@ fn main() -> Result<()> {
             width,
             height,
             json,
+            ascii_on_pipe,
             embed_source_spans,
             no_embed_source_spans,
             source_map_out,
@@ -987,7 +1019,10 @@ fn main() -> Result<()> {
             let theme = resolve_theme_name(theme, &loaded_config.file);
             let layout_config = build_layout_config(&loaded_config.file, font_size)?;
             let svg_base_config = build_base_svg_render_config(&loaded_config.file)?;
-            let term_base_config = build_base_term_render_config(&loaded_config.file)?;
+            let mut term_base_config = build_base_term_render_config(&loaded_config.file)?;
+            if ascii_on_pipe {
+                term_base_config.auto_glyph_mode = AutoGlyphMode::LocaleOrPipe;
+            }
             let show_back_edges = resolve_show_back_edges(&loaded_config.file);
             let show_minimap = term_base_config.show_minimap;
             // Log FNX configuration at debug level
@@ -1304,6 +1339,7 @@ fn parse_layout_algorithm_name(value: &str) -> Result<LayoutAlgorithm> {
         "auto" => Ok(LayoutAlgorithm::Auto),
         "sugiyama" => Ok(LayoutAlgorithm::Sugiyama),
         "force" => Ok(LayoutAlgorithm::Force),
+        "stress" => Ok(LayoutAlgorithm::Stress),
         "tree" => Ok(LayoutAlgorithm::Tree),
         "radial" => Ok(LayoutAlgorithm::Radial),
         "sequence" => Ok(LayoutAlgorithm::Sequence),
@@ -1339,6 +1375,25 @@ fn parse_tier_name(value: &str) -> Result<MermaidTier> {
     }
 }
 
+fn parse_color_mode(value: &str) -> Result<ColorMode> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "none" | "off" => Ok(ColorMode::None),
+        "16" | "ansi16" => Ok(ColorMode::Ansi16),
+        "256" | "ansi256" => Ok(ColorMode::Ansi256),
+        "truecolor" | "24bit" => Ok(ColorMode::TrueColor),
+        other => anyhow::bail!("unknown term.color '{other}'"),
+    }
+}
+
+fn parse_legend_position(value: &str) -> Result<LegendPosition> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "off" | "none" => Ok(LegendPosition::Off),
+        "bottom" => Ok(LegendPosition::Bottom),
+        "right" => Ok(LegendPosition::Right),
+        other => anyhow::bail!("unknown term.legend '{other}'"),
+    }
+}
+
 fn parse_link_mode(value: &str) -> Result<MermaidLinkMode> {
     match value.trim().to_ascii_lowercase().as_str() {
         "off" | "disabled" => Ok(MermaidLinkMode::Off),
@@ -1444,6 +1499,15 @@ fn build_layout_config(
             anyhow::anyhow!("unknown layout.cycle_strategy '{}'", cycle_strategy.trim())
         })?;
     }
+    if let Some(rank_strategy) = config_file.layout.rank_strategy.as_deref() {
+        config.rank_strategy = RankStrategy::parse(rank_strategy).ok_or_else(|| {
+            anyhow::anyhow!("unknown layout.rank_strategy '{}'", rank_strategy.trim())
+        })?;
+    }
+    if let Some(align) = config_file.layout.align.as_deref() {
+        config.align = RankAlign::parse(align)
+            .ok_or_else(|| anyhow::anyhow!("unknown layout.align '{}'", align.trim()))?;
+    }
     if let Some(node_spacing) = config_file.layout.node_spacing {
         config.spacing.node_spacing = validate_positive_f32(node_spacing, "layout.node_spacing")?;
     }
@@ -1453,6 +1517,18 @@ fn build_layout_config(
     if let Some(edge_routing) = config_file.layout.edge_routing.as_deref() {
         config.edge_routing = parse_edge_routing_name(edge_routing)?;
     }
+    if let Some(route_around_clusters) = config_file.layout.route_around_clusters {
+        config.route_around_clusters = route_around_clusters;
+    }
+    if let Some(ratio) = config_file.layout.target_aspect_ratio {
+        config.target_aspect_ratio =
+            Some(validate_positive_f32(ratio, "layout.target_aspect_ratio")?);
+    }
+    if let Some(packing) = config_file.layout.component_packing.as_deref() {
+        config.component_packing = Some(ComponentPacking::parse(packing).ok_or_else(|| {
+            anyhow::anyhow!("unknown layout.component_packing '{}'", packing.trim())
+        })?);
+    }
 
     Ok(config)
 }
@@ -1481,6 +1557,18 @@ fn build_base_svg_render_config(config_file: &FrankenmermaidConfigFile) -> Resul
             .parse::<ThemePreset>()
             .map_err(|_| anyhow::anyhow!("unknown svg.theme '{}'", theme.trim()))?;
     }
+    if let Some(width) = config_file.svg.width {
+        config.width = Some(validate_positive_f32(width, "svg.width")?);
+    }
+    if let Some(height) = config_file.svg.height {
+        config.height = Some(validate_positive_f32(height, "svg.height")?);
+    }
+    if let Some(max_width) = config_file.svg.max_width {
+        config.max_width = Some(validate_positive_f32(max_width, "svg.max_width")?);
+    }
+    if let Some(scale) = config_file.svg.scale {
+        config.scale = validate_positive_f32(scale, "svg.scale")?;
+    }
     if let Some(rounded_corners) = config_file.svg.rounded_corners {
         config.rounded_corners = validate_non_negative_f32(rounded_corners, "svg.rounded_corners")?;
     }
@@ -1498,6 +1586,12 @@ fn build_base_svg_render_config(config_file: &FrankenmermaidConfigFile) -> Resul
             A11yConfig::none()
         };
     }
+    if let Some(focus_order) = config_file.svg.focus_order {
+        config.a11y.focus_order = focus_order;
+    }
+    if let Some(layers) = config_file.svg.layers {
+        config.layers = layers;
+    }
     if let Some(link_mode) = config_file.svg.link_mode.as_deref() {
         config.link_mode = parse_link_mode(link_mode)?;
     }
@@ -1516,7 +1610,15 @@ fn build_base_svg_render_config(config_file: &FrankenmermaidConfigFile) -> Resul
 fn build_base_term_render_config(
     config_file: &FrankenmermaidConfigFile,
 ) -> Result<TermRenderConfig> {
-    let mut config = TermRenderConfig::rich();
+    let mut config = TermRenderConfig {
+        // Fall back to ASCII in a non-UTF-8 locale unless the config pins the glyph set.
+        auto_glyph_mode: if config_file.term.unicode.is_some() {
+            AutoGlyphMode::Off
+        } else {
+            AutoGlyphMode::Locale
+        },
+        ..TermRenderConfig::rich()
+    };
 
     if let Some(tier) = config_file.term.tier.as_deref() {
         config.tier = parse_tier_name(tier)?;
@@ -1531,6 +1633,12 @@ fn build_base_term_render_config(
     if let Some(show_minimap) = config_file.term.minimap {
         config.show_minimap = show_minimap;
     }
+    if let Some(color) = config_file.term.color.as_deref() {
+        config.color_mode = parse_color_mode(color)?;
+    }
+    if let Some(legend) = config_file.term.legend.as_deref() {
+        config.legend = parse_legend_position(legend)?;
+    }
 
     Ok(config)
 }
@@ -2404,7 +2512,7 @@ fn render_format(
                 let mut svg_config =
                     build_svg_render_config(&svg_base_config, theme, font_size, embed_source_spans);
                 svg_config.apply_degradation(&degradation);
-                make_svg_render_config_raster_safe(&mut svg_config);
+                make_config_raster_safe(&mut svg_config);
                 let svg = render_svg_with_layout(ir, render_layout, &svg_config);
                 let svg = resolve_svg_custom_properties_for_rasterization(&svg);
                 let (png, px_width, px_height) = svg_to_png(&svg, width, height)?;
@@ -2509,20 +2617,6 @@ fn build_svg_render_config(
     svg_config
 }
 
-#[cfg(feature = "png")]
-fn make_svg_render_config_raster_safe(config: &mut SvgRenderConfig) {
-    // usvg/resvg only supports a browser-free subset of the CSS emitted for
-    // interactive SVG output. Prefer a static attribute-driven SVG for PNG so
-    // rasterization remains deterministic across theme presets.
-    config.responsive = false;
-    config.embed_theme_css = false;
-    config.animations_enabled = false;
-    config.print_optimized = false;
-    config.shadows = false;
-    config.glow_enabled = false;
-    config.a11y.accessibility_css = false;
-}
-
 fn normalize_positive_font_size(font_size: Option<f32>) -> Option<f32> {
     font_size.filter(|size| size.is_finite() && *size > 0.0)
 }
@@ -2621,152 +2715,6 @@ fn svg_root_tag(svg: &str) -> &str {
     &svg[start..end]
 }
 
-#[cfg(feature = "png")]
-fn resolve_svg_custom_properties_for_rasterization(svg: &str) -> String {
-    let mut custom_properties = BTreeMap::new();
-    if let Some(style_start) = svg.find("<style>") {
-        let style_content_start = style_start + "<style>".len();
-        if let Some(style_end_rel) = svg[style_content_start..].find("</style>") {
-            let style_content_end = style_content_start + style_end_rel;
-            let style_content = &svg[style_content_start..style_content_end];
-            custom_properties = extract_svg_custom_properties(style_content);
-        }
-    }
-    if custom_properties.is_empty() && !svg.contains("var(--fm-") {
-        return svg.to_string();
-    }
-
-    let mut resolved = svg.to_string();
-    for _ in 0..8 {
-        let next = substitute_svg_var_calls(&resolved, &custom_properties);
-        if next == resolved {
-            break;
-        }
-        resolved = next;
-    }
-    resolved
-}
-
-#[cfg(feature = "png")]
-fn extract_svg_custom_properties(style_content: &str) -> BTreeMap<String, String> {
-    let mut properties = BTreeMap::new();
-    for line in style_content.lines() {
-        let trimmed = line.trim();
-        if !trimmed.starts_with("--fm-") {
-            continue;
-        }
-        let Some((name, value)) = trimmed.split_once(':') else {
-            continue;
-        };
-        let value = value.trim().trim_end_matches(';').trim();
-        if !value.is_empty() {
-            properties.insert(name.trim().to_string(), value.to_string());
-        }
-    }
-    properties
-}
-
-#[cfg(feature = "png")]
-fn substitute_svg_var_calls(input: &str, custom_properties: &BTreeMap<String, String>) -> String {
-    let mut output = String::with_capacity(input.len());
-    let mut cursor = 0;
-
-    while let Some(rel_start) = input[cursor..].find("var(--fm-") {
-        let start = cursor + rel_start;
-        output.push_str(&input[cursor..start]);
-
-        let content_start = start + "var(".len();
-        let mut depth = 1_usize;
-        let mut end = None;
-        for (offset, ch) in input[content_start..].char_indices() {
-            match ch {
-                '(' => depth = depth.saturating_add(1),
-                ')' => {
-                    depth = depth.saturating_sub(1);
-                    if depth == 0 {
-                        end = Some(content_start + offset);
-                        break;
-                    }
-                }
-                _ => {}
-            }
-        }
-        let Some(end) = end else {
-            output.push_str(&input[start..]);
-            return output;
-        };
-        let body = &input[content_start..end];
-        let (property_name, fallback) = match body.split_once(',') {
-            Some((name, fallback)) => (name.trim(), Some(fallback.trim())),
-            None => (body.trim(), None),
-        };
-
-        if let Some(value) = custom_properties.get(property_name) {
-            output.push_str(value);
-        } else if let Some(fallback) = fallback.filter(|value| !value.is_empty()) {
-            output.push_str(fallback);
-        } else {
-            output.push_str(&input[start..=end]);
-        }
-
-        cursor = end + 1;
-    }
-
-    output.push_str(&input[cursor..]);
-    output
-}
-
-#[cfg(feature = "png")]
-fn svg_to_png(svg: &str, width: Option<u32>, height: Option<u32>) -> Result<(Vec<u8>, u32, u32)> {
-    use resvg::tiny_skia;
-    use usvg::{Options, Transform, Tree};
-
-    let opt = Options::default();
-    let tree = Tree::from_str(svg, &opt).context("Failed to parse SVG")?;
-
-    let size = tree.size();
-    let size_width = size.width();
-    let size_height = size.height();
-    if !size_width.is_finite()
-        || !size_height.is_finite()
-        || size_width <= 0.0
-        || size_height <= 0.0
-    {
-        anyhow::bail!("SVG dimensions must be greater than 0");
-    }
-
-    let (px_width, px_height) = match (width, height) {
-        (Some(w), Some(h)) => (w, h),
-        (Some(w), None) => {
-            let scale = w as f32 / size_width;
-            (w, (size_height * scale) as u32)
-        }
-        (None, Some(h)) => {
-            let scale = h as f32 / size_height;
-            ((size_width * scale) as u32, h)
-        }
-        (None, None) => (size_width as u32, size_height as u32),
-    };
-    if px_width == 0 || px_height == 0 {
-        anyhow::bail!("PNG dimensions must be greater than 0");
-    }
-
-    let mut pixmap =
-        tiny_skia::Pixmap::new(px_width, px_height).context("Failed to create pixmap")?;
-
-    let scale_x = px_width as f32 / size_width;
-    let scale_y = px_height as f32 / size_height;
-
-    resvg::render(
-        &tree,
-        Transform::from_scale(scale_x, scale_y),
-        &mut pixmap.as_mut(),
-    );
-
-    let bytes = pixmap.encode_png().context("Failed to encode PNG")?;
-    Ok((bytes, px_width, px_height))
-}
-
 // =============================================================================
 // Command: parse
 // =============================================================================
@@ -2996,6 +2944,25 @@ fn cmd_diff(old_input: &str, new_input: &str, options: DiffCommandOptions<'_>) -
                 use_colors,
             )
         }
+        DiffOutputFormat::Overlay => {
+            let (cols, rows) = terminal_size(width, height);
+            let config = TermRenderConfig {
+                color_mode: if use_colors {
+                    ColorMode::Ansi16
+                } else {
+                    ColorMode::None
+                },
+                ..TermRenderConfig::rich()
+            };
+            render_diff_diagram(&old_parsed.ir, &new_parsed.ir, &config, cols, rows).output
+        }
+        DiffOutputFormat::SideBySide => {
+            let (cols, rows) = terminal_size(width, height);
+            render_diff_side_by_side(&old_parsed.ir, &new_parsed.ir, cols, rows)
+        }
+        DiffOutputFormat::Svg => {
+            render_diff_svg(&old_parsed.ir, &new_parsed.ir, &SvgRenderConfig::default())
+        }
         DiffOutputFormat::Json => serde_json::to_string_pretty(&diff)?,
     };
 
@@ -3370,6 +3337,18 @@ fn collect_structural_diagnostics(parsed: &fm_parser::ParseResult) -> Vec<Valida
         });
     }
 
+    for diagnostic in validate_ir(&parsed.ir).diagnostics {
+        let mut payload =
+            StructuredDiagnostic::from_diagnostic(&diagnostic).with_confidence(parsed.confidence);
+        if let Some(rule_id) = diagnostic.rule_id {
+            payload = payload.with_rule_id(rule_id);
+        }
+        diagnostics.push(ValidationDiagnostic {
+            stage: "parse".to_string(),
+            payload,
+        });
+    }
+
     diagnostics
 }
 
@@ -4016,11 +3995,12 @@ mod render_tests {
 #[cfg(test)]
 mod config_tests {
     use super::{
-        FrankenmermaidConfigFile, LayoutAlgorithmArg, OutputFormat, build_base_svg_render_config,
-        build_layout_config, resolve_layout_algorithm, resolve_output_format,
-        resolve_show_back_edges, resolve_theme_name,
+        ColorMode, FrankenmermaidConfigFile, LayoutAlgorithmArg, LegendPosition, OutputFormat,
+        build_base_svg_render_config, build_base_term_render_config, build_layout_config,
+        resolve_layout_algorithm, resolve_output_format, resolve_show_back_edges,
+        resolve_theme_name,
     };
-    use fm_layout::{CycleStrategy, EdgeRouting};
+    use fm_layout::{CycleStrategy, EdgeRouting, RankStrategy};
     use fm_render_svg::ThemePreset;
 
     #[test]
@@ -4061,6 +4041,8 @@ mod config_tests {
                 tier = "compact"
                 unicode = false
                 minimap = true
+                color = "256"
+                legend = "right"
             "#,
         )
         .expect("parse documented config");
@@ -4070,6 +4052,9 @@ mod config_tests {
         assert_eq!(config.render.default_format.as_deref(), Some("term"));
         assert_eq!(config.svg.theme.as_deref(), Some("forest"));
         assert_eq!(config.term.tier.as_deref(), Some("compact"));
+        let term_config = build_base_term_render_config(&config).expect("term config");
+        assert_eq!(term_config.color_mode, ColorMode::Ansi256);
+        assert_eq!(term_config.legend, LegendPosition::Right);
     }
 
     #[test]
@@ -4110,9 +4095,14 @@ mod config_tests {
                 [layout]
                 algorithm = "sugiyama"
                 cycle_strategy = "cycle-aware"
+                rank_strategy = "network-simplex"
+                align = "center"
                 node_spacing = 90.0
                 rank_spacing = 150.0
                 edge_routing = "spline"
+                route_around_clusters = true
+                target_aspect_ratio = 4.0
+                component_packing = "bin-pack"
 
                 [render]
                 default_format = "svg"
@@ -4122,21 +4112,36 @@ mod config_tests {
                 theme = "dark"
                 shadows = false
                 gradients = false
+                width = 800.0
+                max_width = 640.0
+                layers = true
             "#,
         )
         .expect("parse config");
 
         let layout = build_layout_config(&config, None).expect("build layout config");
         assert_eq!(layout.cycle_strategy, CycleStrategy::CycleAware);
+        assert_eq!(layout.rank_strategy, RankStrategy::NetworkSimplex);
+        assert_eq!(layout.align, fm_layout::RankAlign::Center);
         assert_eq!(layout.edge_routing, EdgeRouting::Spline);
+        assert!(layout.route_around_clusters);
         assert_eq!(layout.spacing.node_spacing, 90.0);
         assert_eq!(layout.spacing.rank_spacing, 150.0);
+        assert_eq!(layout.target_aspect_ratio, Some(4.0));
+        assert_eq!(
+            layout.component_packing,
+            Some(fm_layout::ComponentPacking::BinPack)
+        );
 
         let svg = build_base_svg_render_config(&config).expect("build svg config");
         assert_eq!(svg.theme, ThemePreset::Dark);
         assert!(!svg.shadows);
         assert!(!svg.node_gradients);
         assert!(svg.animations_enabled);
+        assert_eq!(svg.width, Some(800.0));
+        assert_eq!(svg.height, None);
+        assert_eq!(svg.max_width, Some(640.0));
+        assert!(svg.layers);
     }
 
     #[test]
@@ -1427,19 +1427,19 @@ fn render_svg_writes_source_map_artifact() {
             .as_array()
             .expect("entries array")
             .iter()
-            .any(|entry| entry["element_id"] == "fm-node-a-0")
+            .any(|entry| entry["element_id"] == "fm-node-a-c40bf6cc")
     );
     assert!(
         source_map["entries"]
             .as_array()
             .expect("entries array")
             .iter()
-            .any(|entry| entry["element_id"] == "fm-edge-0")
+            .any(|entry| entry["element_id"] == "fm-edge-a-b-0")
     );
 
     let svg = std::fs::read_to_string(&output_path).expect("failed to read rendered svg");
-    assert!(svg.contains("id=\"fm-node-a-0\""));
-    assert!(svg.contains("id=\"fm-edge-0\""));
+    assert!(svg.contains("id=\"fm-node-a-c40bf6cc\""));
+    assert!(svg.contains("id=\"fm-edge-a-b-0\""));
 }
 
 #[test]
@@ -1545,31 +1545,31 @@ fn render_svg_source_map_tracks_sequence_mirror_headers_as_distinct_elements() {
             .as_array()
             .expect("entries array")
             .iter()
-            .any(|entry| entry["element_id"] == "fm-node-alice-0")
+            .any(|entry| entry["element_id"] == "fm-node-alice-2cdd8587")
     );
     assert!(
         source_map["entries"]
             .as_array()
             .expect("entries array")
             .iter()
-            .any(|entry| entry["element_id"] == "fm-node-alice-0-mirror-header")
+            .any(|entry| entry["element_id"] == "fm-node-alice-2cdd8587-mirror-header")
     );
     assert!(
         source_map["entries"]
             .as_array()
             .expect("entries array")
             .iter()
-            .any(|entry| entry["element_id"] == "fm-node-bob-1-mirror-header")
+            .any(|entry| entry["element_id"] == "fm-node-bob-ebcba174-mirror-header")
     );
 
     let svg = std::fs::read_to_string(&output_path).expect("failed to read rendered svg");
-    assert_eq!(svg.matches("id=\"fm-node-alice-0\"").count(), 1);
+    assert_eq!(svg.matches("id=\"fm-node-alice-2cdd8587\"").count(), 1);
     assert_eq!(
-        svg.matches("id=\"fm-node-alice-0-mirror-header\"").count(),
+        svg.matches("id=\"fm-node-alice-2cdd8587-mirror-header\"").count(),
         1
     );
-    assert_eq!(svg.matches("id=\"fm-node-bob-1\"").count(), 1);
-    assert_eq!(svg.matches("id=\"fm-node-bob-1-mirror-header\"").count(), 1);
+    assert_eq!(svg.matches("id=\"fm-node-bob-ebcba174\"").count(), 1);
+    assert_eq!(svg.matches("id=\"fm-node-bob-ebcba174-mirror-header\"").count(), 1);
 }
 
 #[test]
@@ -1585,7 +1585,7 @@ fn interactive_edit_session_keeps_cli_pipeline_in_sync() {
     );
 
     let visual_edit = MermaidLensEdit {
-        element_id: "fm-edge-0".to_string(),
+        element_id: "fm-edge-a-b-0".to_string(),
         replacement: "A[Alpha]-.->B[Beta]".to_string(),
     };
     let visual_result = apply_lens_edit(source, &initial_source_map, &visual_edit)
@@ -1620,7 +1620,7 @@ fn interactive_edit_session_rebases_visual_edit_on_latest_text_source() {
     );
 
     let visual_edit = MermaidLensEdit {
-        element_id: "fm-edge-0".to_string(),
+        element_id: "fm-edge-a-b-0".to_string(),
         replacement: "A[Atlas]-.->B[Beta]".to_string(),
     };
     let visual_result = apply_lens_edit(latest_text_source, &latest_source_map, &visual_edit)
@@ -1647,7 +1647,7 @@ fn parse_lens_snapshot_preserves_directives_and_comments_across_visual_edit() {
     let response = apply_parse_lens_edit(
         source,
         &MermaidLensEdit {
-            element_id: "fm-edge-0".to_string(),
+            element_id: "fm-edge-a-b-0".to_string(),
             replacement: "A[Alpha] -.-> B[Beta]".to_string(),
         },
     )
@@ -0,0 +1,348 @@
+//! Synthetic benchmark corpus.
+//!
+//! [`generate`] builds representative flowchart IRs for layout and render performance work: deep
+//! chains, wide fans, dense bipartite graphs, many clusters, and big strongly connected
+//! components. Every random choice comes from a fixed per-profile seed, so the same profile always
+//! yields the same IR across machines and runs.
+
+use crate::{
+    ArrowType, DiagramType, GraphDirection, IrCluster, IrClusterId, IrEdge, IrEndpoint,
+    IrGraphCluster, IrGraphEdge, IrGraphNode, IrLabel, IrLabelId, IrNode, IrNodeId,
+    MermaidDiagramIr, NodeShape,
+};
+
+/// Shape and size of one synthetic corpus diagram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CorpusProfile {
+    /// A single path of `length` nodes: maximal rank count, one node per rank.
+    DeepChain { length: usize },
+    /// One root with `width` children: a single very wide rank.
+    WideFan { width: usize },
+    /// Two layers where each left/right pair is connected with probability `density_permille`.
+    DenseBipartite {
+        left: usize,
+        right: usize,
+        density_permille: u16,
+    },
+    /// `clusters` subgraph-style clusters of `nodes_per_cluster` chained nodes, with random
+    /// cross-cluster edges.
+    ManyClusters {
+        clusters: usize,
+        nodes_per_cluster: usize,
+    },
+    /// A ring of `nodes` plus `chords` random chords, so the whole graph is one SCC.
+    BigScc { nodes: usize, chords: usize },
+}
+
+impl CorpusProfile {
+    /// The default benchmark set, one medium-sized diagram per shape.
+    pub const STANDARD: [Self; 5] = [
+        Self::DeepChain { length: 200 },
+        Self::WideFan { width: 200 },
+        Self::DenseBipartite {
+            left: 30,
+            right: 30,
+            density_permille: 300,
+        },
+        Self::ManyClusters {
+            clusters: 20,
+            nodes_per_cluster: 8,
+        },
+        Self::BigScc {
+            nodes: 100,
+            chords: 150,
+        },
+    ];
+
+    /// Stable profile name, e.g. `deep_chain_200`, for benchmark ids and reports.
+    #[must_use]
+    pub fn name(&self) -> String {
+        match *self {
+            Self::DeepChain { length } => format!("deep_chain_{length}"),
+            Self::WideFan { width } => format!("wide_fan_{width}"),
+            Self::DenseBipartite {
+                left,
+                right,
+                density_permille,
+            } => format!("dense_bipartite_{left}x{right}_d{density_permille}"),
+            Self::ManyClusters {
+                clusters,
+                nodes_per_cluster,
+            } => format!("many_clusters_{clusters}x{nodes_per_cluster}"),
+            Self::BigScc { nodes, chords } => format!("big_scc_{nodes}_c{chords}"),
+        }
+    }
+
+    const fn seed(&self) -> u64 {
+        match self {
+            Self::DeepChain { .. } => 0x5eed_0001,
+            Self::WideFan { .. } => 0x5eed_0002,
+            Self::DenseBipartite { .. } => 0x5eed_0003,
+            Self::ManyClusters { .. } => 0x5eed_0004,
+            Self::BigScc { .. } => 0x5eed_0005,
+        }
+    }
+}
+
+/// Generate the synthetic diagram for `profile`.
+///
+/// Output is deterministic: identical profiles produce identical IRs. Node ids are `N0`, `N1`, …
+/// with short labels of varying length, and `ir.graph` mirrors the nodes, edges, and clusters the
+/// way the parser fills it.
+#[must_use]
+pub fn generate(profile: CorpusProfile) -> MermaidDiagramIr {
+    let mut rng = CorpusRng::new(profile.seed());
+    let mut builder = CorpusBuilder::new();
+    match profile {
+        CorpusProfile::DeepChain { length } => {
+            builder.add_nodes(length, &mut rng);
+            for index in 1..length {
+                builder.add_edge(index - 1, index);
+            }
+        }
+        CorpusProfile::WideFan { width } => {
+            builder.add_nodes(width + 1, &mut rng);
+            for child in 1..=width {
+                builder.add_edge(0, child);
+            }
+        }
+        CorpusProfile::DenseBipartite {
+            left,
+            right,
+            density_permille,
+        } => {
+            builder.add_nodes(left + right, &mut rng);
+            for source in 0..left {
+                for target in left..left + right {
+                    if rng.below(1000) < u64::from(density_permille) {
+                        builder.add_edge(source, target);
+                    }
+                }
+            }
+        }
+        CorpusProfile::ManyClusters {
+            clusters,
+            nodes_per_cluster,
+        } => {
+            builder.add_nodes(clusters * nodes_per_cluster, &mut rng);
+            for cluster in 0..clusters {
+                let first = cluster * nodes_per_cluster;
+                for index in first + 1..first + nodes_per_cluster {
+                    builder.add_edge(index - 1, index);
+                }
+                builder.add_cluster(first..first + nodes_per_cluster);
+            }
+            if nodes_per_cluster > 0 {
+                // Forward edges between clusters keep the graph acyclic but tangled.
+                for cluster in 1..clusters {
+                    let source_cluster = rng.below(cluster as u64) as usize;
+                    let source = source_cluster * nodes_per_cluster
+                        + rng.below(nodes_per_cluster as u64) as usize;
+                    let target =
+                        cluster * nodes_per_cluster + rng.below(nodes_per_cluster as u64) as usize;
+                    builder.add_edge(source, target);
+                }
+            }
+        }
+        CorpusProfile::BigScc { nodes, chords } => {
+            builder.add_nodes(nodes, &mut rng);
+            if nodes > 1 {
+                for index in 0..nodes {
+                    builder.add_edge(index, (index + 1) % nodes);
+                }
+                for _ in 0..chords {
+                    let source = rng.below(nodes as u64) as usize;
+                    let offset = 2 + rng.below((nodes as u64).saturating_sub(2).max(1)) as usize;
+                    builder.add_edge(source, (source + offset) % nodes);
+                }
+            }
+        }
+    }
+    builder.ir
+}
+
+/// Generate every profile of [`CorpusProfile::STANDARD`], paired with its name.
+#[must_use]
+pub fn standard_corpus() -> Vec<(String, MermaidDiagramIr)> {
+    CorpusProfile::STANDARD
+        .iter()
+        .map(|profile| (profile.name(), generate(*profile)))
+        .collect()
+}
+
+struct CorpusBuilder {
+    ir: MermaidDiagramIr,
+}
+
+impl CorpusBuilder {
+    fn new() -> Self {
+        let mut ir = MermaidDiagramIr::empty(DiagramType::Flowchart);
+        ir.direction = GraphDirection::TB;
+        Self { ir }
+    }
+
+    fn add_nodes(&mut self, count: usize, rng: &mut CorpusRng) {
+        const WORDS: [&str; 8] = [
+            "load", "parse", "validate", "route", "render", "cache", "emit", "retry",
+        ];
+        const SHAPES: [NodeShape; 4] = [
+            NodeShape::Rect,
+            NodeShape::Rounded,
+            NodeShape::Diamond,
+            NodeShape::Stadium,
+        ];
+        self.ir.nodes.reserve(count);
+        for _ in 0..count {
+            let index = self.ir.nodes.len();
+            let word_count = 1 + rng.below(3) as usize;
+            let text = (0..word_count)
+                .map(|_| WORDS[rng.below(WORDS.len() as u64) as usize])
+                .collect::<Vec<_>>()
+                .join(" ");
+            let label = IrLabelId(self.ir.labels.len());
+            self.ir.labels.push(IrLabel {
+                text,
+                ..IrLabel::default()
+            });
+            self.ir.nodes.push(IrNode {
+                id: format!("N{index}"),
+                label: Some(label),
+                shape: SHAPES[rng.below(SHAPES.len() as u64) as usize],
+                ..IrNode::default()
+            });
+            self.ir.graph.nodes.push(IrGraphNode {
+                node_id: IrNodeId(index),
+                ..IrGraphNode::default()
+            });
+        }
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize) {
+        let edge_id = self.ir.edges.len();
+        let (from, to) = (
+            IrEndpoint::Node(IrNodeId(from)),
+            IrEndpoint::Node(IrNodeId(to)),
+        );
+        self.ir.edges.push(IrEdge {
+            from,
+            to,
+            arrow: ArrowType::Arrow,
+            ..IrEdge::default()
+        });
+        self.ir.graph.edges.push(IrGraphEdge {
+            edge_id,
+            from,
+            to,
+            ..IrGraphEdge::default()
+        });
+    }
+
+    fn add_cluster(&mut self, members: std::ops::Range<usize>) {
+        let cluster_id = IrClusterId(self.ir.clusters.len());
+        let title = IrLabelId(self.ir.labels.len());
+        self.ir.labels.push(IrLabel {
+            text: format!("Group {}", cluster_id.0),
+            ..IrLabel::default()
+        });
+        let members: Vec<IrNodeId> = members.map(IrNodeId).collect();
+        for member in &members {
+            self.ir.graph.nodes[member.0].clusters.push(cluster_id);
+        }
+        self.ir.clusters.push(IrCluster {
+            id: cluster_id,
+            title: Some(title),
+            members: members.clone(),
+            ..IrCluster::default()
+        });
+        self.ir.graph.clusters.push(IrGraphCluster {
+            cluster_id,
+            title: Some(title),
+            members,
+            ..IrGraphCluster::default()
+        });
+    }
+}
+
+/// SplitMix64: tiny, fast, and stable across platforms.
+struct CorpusRng(u64);
+
+impl CorpusRng {
+    const fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform-ish value in `0..bound` (`0` when `bound` is `0`).
+    fn below(&mut self, bound: u64) -> u64 {
+        if bound == 0 {
+            return 0;
+        }
+        self.next_u64() % bound
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generation_is_deterministic_and_sized() {
+        for profile in CorpusProfile::STANDARD {
+            let ir = generate(profile);
+            assert_eq!(ir, generate(profile), "{}", profile.name());
+            assert_eq!(ir.graph.nodes.len(), ir.nodes.len());
+            assert_eq!(ir.graph.edges.len(), ir.edges.len());
+        }
+        assert_eq!(
+            generate(CorpusProfile::DeepChain { length: 10 })
+                .edges
+                .len(),
+            9
+        );
+        let clusters = generate(CorpusProfile::ManyClusters {
+            clusters: 4,
+            nodes_per_cluster: 3,
+        });
+        assert_eq!(clusters.clusters.len(), 4);
+        assert_eq!(clusters.edges.len(), 4 * 2 + 3);
+    }
+
+    #[test]
+    fn big_scc_and_bipartite_edge_counts_follow_profile() {
+        let ir = generate(CorpusProfile::BigScc {
+            nodes: 12,
+            chords: 5,
+        });
+        assert_eq!(ir.edges.len(), 17);
+        let bipartite = generate(CorpusProfile::DenseBipartite {
+            left: 10,
+            right: 10,
+            density_permille: 1000,
+        });
+        assert_eq!(bipartite.edges.len(), 100);
+        assert!(bipartite.edges.iter().all(|edge| matches!(
+            (edge.from, edge.to),
+            (IrEndpoint::Node(from), IrEndpoint::Node(to)) if from.0 < 10 && to.0 >= 10
+        )));
+        assert_eq!(
+            standard_corpus()
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "deep_chain_200",
+                "wide_fan_200",
+                "dense_bipartite_30x30_d300",
+                "many_clusters_20x8",
+                "big_scc_100_c150",
+            ]
+        );
+    }
+}
@@ -0,0 +1,709 @@
+//! Mermaid source from a [`MermaidDiagramIr`].
+//!
+//! [`emit_mermaid`] writes a diagram back out in one canonical layout: the header, every node
+//! declared once (inside its innermost subgraph), the edges in IR order, then `class`, style and
+//! `click` directives. Parsing the output gives back the same nodes, edges, subgraphs and styles,
+//! so `parse → transform → emit` round-trips, diagrams assembled with
+//! [`IrBuilder`](crate::IrBuilder) can be saved as `.mmd` files, and a formatter only has to parse
+//! and re-emit. Diagram types it cannot write are refused rather than approximated.
+
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+
+use crate::{
+    ArrowType, DiagramType, GraphDirection, IrAutonumberChange, IrEndpoint, IrLabelId, IrNode,
+    IrSequenceNote, IrStyleTarget, MermaidDiagramIr, MermaidError, NodeShape, NotePosition, Span,
+};
+
+const INDENT: &str = "    ";
+
+/// Class the sequence parser gives participants declared with `actor`.
+const SEQUENCE_ACTOR_CLASS: &str = "sequence-participant-actor";
+
+/// Canonical Mermaid source for `ir`.
+///
+/// Flowcharts are written in full: node labels, shapes and `click` targets, subgraphs and their
+/// directions, edge labels and `id@` names, and `class`/`classDef`/`style`/`linkStyle`
+/// directives. Sequence diagrams get their participants, messages, notes and `autonumber`
+/// statements; fragments and activations are not written yet. A title becomes front matter.
+///
+/// Node ids the parser would not read back as one id (spaces, punctuation, as DOT and PlantUML
+/// imports and [`merge_irs`](crate::merge_irs) produce) are written as a safe stand-in id, with
+/// the original kept as the node's label; subgraph keys get the same treatment.
+///
+/// # Errors
+///
+/// [`MermaidError::Unsupported`] for any other diagram type, since writing it as a flowchart
+/// would lose what makes it that type.
+pub fn emit_mermaid(ir: &MermaidDiagramIr) -> Result<String, MermaidError> {
+    let mut out = String::new();
+    if let Some(title) = &ir.meta.title {
+        let _ = writeln!(out, "---\ntitle: {title}\n---");
+    }
+    match ir.diagram_type {
+        DiagramType::Flowchart => emit_flowchart(ir, &mut out),
+        DiagramType::Sequence => emit_sequence(ir, &mut out),
+        other => {
+            return Err(MermaidError::Unsupported {
+                message: format!("cannot write {} diagrams as Mermaid source", other.as_str()),
+                span: Span::default(),
+            });
+        }
+    }
+    Ok(out)
+}
+
+/// Ids to write for the nodes and groups of `ir`: the id itself when the parser reads it back
+/// as one, otherwise `_`-joined words of it made unique among every id written.
+struct SourceIds<'a> {
+    nodes: Vec<Cow<'a, str>>,
+    groups: Vec<Cow<'a, str>>,
+}
+
+impl<'a> SourceIds<'a> {
+    fn new(ir: &'a MermaidDiagramIr, groups: &'a [Group]) -> Self {
+        let keys = ir
+            .nodes
+            .iter()
+            .map(|node| node.id.as_str())
+            .chain(groups.iter().map(|group| group.key.as_str()));
+        let mut taken: BTreeSet<String> = keys
+            .clone()
+            .filter(|id| is_source_id(id))
+            .map(str::to_string)
+            .collect();
+        let mut ids: Vec<Cow<'a, str>> = keys
+            .map(|id| {
+                if is_source_id(id) {
+                    return Cow::Borrowed(id);
+                }
+                let base = source_id_base(id);
+                let mut candidate = base.clone();
+                let mut suffix = 1;
+                while taken.contains(&candidate) {
+                    suffix += 1;
+                    candidate = format!("{base}_{suffix}");
+                }
+                taken.insert(candidate.clone());
+                Cow::Owned(candidate)
+            })
+            .collect();
+        let groups = ids.split_off(ir.nodes.len());
+        Self { nodes: ids, groups }
+    }
+
+    fn node(&self, index: usize) -> &str {
+        &self.nodes[index]
+    }
+
+    fn endpoint(&self, ir: &MermaidDiagramIr, endpoint: IrEndpoint) -> Option<&str> {
+        let node = ir.resolve_endpoint_node(endpoint)?;
+        self.nodes.get(node.0).map(AsRef::as_ref)
+    }
+}
+
+/// A `subgraph` block: an IR subgraph, or a cluster no subgraph stands for.
+struct Group {
+    key: String,
+    title: Option<IrLabelId>,
+    parent: Option<usize>,
+    members: Vec<usize>,
+    direction: Option<GraphDirection>,
+}
+
+fn groups(ir: &MermaidDiagramIr) -> Vec<Group> {
+    let subgraph_count = ir.graph.subgraphs.len();
+    let mut groups: Vec<Group> = ir
+        .graph
+        .subgraphs
+        .iter()
+        .map(|subgraph| Group {
+            key: subgraph.key.clone(),
+            title: subgraph.title,
+            parent: subgraph
+                .parent
+                .map(|parent| parent.0)
+                .filter(|&parent| parent < subgraph_count),
+            members: subgraph.members.iter().map(|member| member.0).collect(),
+            direction: subgraph.direction,
+        })
+        .collect();
+    for (index, cluster) in ir.clusters.iter().enumerate() {
+        let linked = ir
+            .graph
+            .clusters
+            .get(index)
+            .is_some_and(|graph_cluster| graph_cluster.subgraph.is_some());
+        if !linked {
+            groups.push(Group {
+                key: format!("cluster_{index}"),
+                title: cluster.title,
+                parent: None,
+                members: cluster.members.iter().map(|member| member.0).collect(),
+                direction: None,
+            });
+        }
+    }
+
+    // A cycle of parent links would keep its groups from ever being written; cut it.
+    for index in 0..groups.len() {
+        let mut ancestor = groups[index].parent;
+        for _ in 0..groups.len() {
+            ancestor = ancestor.and_then(|parent| groups[parent].parent);
+        }
+        if ancestor.is_some() {
+            groups[index].parent = None;
+        }
+    }
+    groups
+}
+
+fn emit_flowchart(ir: &MermaidDiagramIr, out: &mut String) {
+    let _ = writeln!(out, "flowchart {}", ir.direction.as_str());
+
+    let groups = groups(ir);
+    let ids = SourceIds::new(ir, &groups);
+    let depth = |mut index: usize| {
+        let mut depth = 0;
+        while let Some(parent) = groups[index].parent {
+            depth += 1;
+            index = parent;
+        }
+        depth
+    };
+    // Each node is declared in the deepest group that lists it.
+    let mut home: Vec<Option<(usize, usize)>> = vec![None; ir.nodes.len()];
+    for (index, group) in groups.iter().enumerate() {
+        let group_depth = depth(index);
+        for &member in &group.members {
+            if let Some(slot) = home.get_mut(member)
+                && slot.is_none_or(|(deepest, _)| group_depth > deepest)
+            {
+                *slot = Some((group_depth, index));
+            }
+        }
+    }
+    let mut group_nodes = vec![Vec::new(); groups.len()];
+    for (node, slot) in home.iter().enumerate() {
+        match slot {
+            Some((_, group)) => group_nodes[*group].push(node),
+            None => {
+                let _ = writeln!(out, "{INDENT}{}", node_declaration(ir, &ids, node));
+            }
+        }
+    }
+    for index in 0..groups.len() {
+        if groups[index].parent.is_none() {
+            write_group(ir, &groups, &ids, &group_nodes, index, 1, out);
+        }
+    }
+
+    // `linkStyle` counts the edges written, which skips any with an unresolved endpoint.
+    let mut link_index = vec![None; ir.edges.len()];
+    let mut written = 0;
+    for (index, edge) in ir.edges.iter().enumerate() {
+        let (Some(from), Some(to)) = (ids.endpoint(ir, edge.from), ids.endpoint(ir, edge.to))
+        else {
+            continue;
+        };
+        let id = edge
+            .user_id()
+            .filter(|id| is_edge_id(id))
+            .map(|id| format!("{id}@"))
+            .unwrap_or_default();
+        let label = label_text(ir, edge.label)
+            .map(|label| format!("|{}|", edge_label_source(label)))
+            .unwrap_or_default();
+        let arrow = flowchart_arrow(edge.arrow);
+        let _ = writeln!(out, "{INDENT}{from} {id}{arrow}{label} {to}");
+        link_index[index] = Some(written);
+        written += 1;
+    }
+
+    let mut classes: Vec<(&str, Vec<&str>)> = Vec::new();
+    for (index, node) in ir.nodes.iter().enumerate() {
+        for class in &node.classes {
+            match classes.iter_mut().find(|(name, _)| name == class) {
+                Some((_, nodes)) => nodes.push(ids.node(index)),
+                None => classes.push((class.as_str(), vec![ids.node(index)])),
+            }
+        }
+    }
+    for (class, nodes) in classes {
+        let _ = writeln!(out, "{INDENT}class {} {class}", nodes.join(","));
+    }
+
+    let mut class_defs = Vec::new();
+    for style_ref in &ir.style_refs {
+        let style = &style_ref.style;
+        match &style_ref.target {
+            IrStyleTarget::Class(name) => {
+                let _ = writeln!(out, "{INDENT}classDef {name} {style}");
+                class_defs.push(name.as_str());
+            }
+            IrStyleTarget::Node(node) => {
+                if node.0 < ir.nodes.len() {
+                    let _ = writeln!(out, "{INDENT}style {} {style}", ids.node(node.0));
+                }
+            }
+            IrStyleTarget::Link(index) => {
+                if let Some(Some(index)) = link_index.get(*index) {
+                    let _ = writeln!(out, "{INDENT}linkStyle {index} {style}");
+                }
+            }
+            IrStyleTarget::LinkDefault => {
+                let _ = writeln!(out, "{INDENT}linkStyle default {style}");
+            }
+        }
+    }
+    // Definitions with no `classDef` reference behind them, as an IR built in code has.
+    for style_def in &ir.style_defs {
+        if !class_defs.contains(&style_def.name.as_str()) && !style_def.properties.is_empty() {
+            let properties: Vec<String> = style_def
+                .properties
+                .iter()
+                .map(|(key, value)| format!("{key}:{value}"))
+                .collect();
+            let _ = writeln!(
+                out,
+                "{INDENT}classDef {} {}",
+                style_def.name,
+                properties.join(",")
+            );
+        }
+    }
+
+    for (index, node) in ir.nodes.iter().enumerate() {
+        let tooltip = node
+            .tooltip()
+            .map(|tooltip| format!(" \"{tooltip}\""))
+            .unwrap_or_default();
+        let id = ids.node(index);
+        if let Some(href) = node.href() {
+            let _ = writeln!(out, "{INDENT}click {id} href \"{href}\"{tooltip}");
+        } else if let Some(callback) = node.callback() {
+            let _ = writeln!(out, "{INDENT}click {id} call {callback}{tooltip}");
+        }
+    }
+}
+
+fn write_group(
+    ir: &MermaidDiagramIr,
+    groups: &[Group],
+    ids: &SourceIds<'_>,
+    group_nodes: &[Vec<usize>],
+    index: usize,
+    depth: usize,
+    out: &mut String,
+) {
+    let indent = INDENT.repeat(depth);
+    let group = &groups[index];
+    let key = ids.groups[index].as_ref();
+    // A group written under a stand-in key keeps its original key as the title.
+    let title = label_text(ir, group.title).unwrap_or(&group.key);
+    if title == key {
+        let _ = writeln!(out, "{indent}subgraph {key}");
+    } else {
+        let _ = writeln!(out, "{indent}subgraph {key}[{}]", label_source(title));
+    }
+    if let Some(direction) = group.direction {
+        let _ = writeln!(out, "{indent}{INDENT}direction {}", direction.as_str());
+    }
+    for &node in &group_nodes[index] {
+        let _ = writeln!(out, "{indent}{INDENT}{}", node_declaration(ir, ids, node));
+    }
+    for child in (0..groups.len()).filter(|&child| groups[child].parent == Some(index)) {
+        write_group(ir, groups, ids, group_nodes, child, depth + 1, out);
+    }
+    let _ = writeln!(out, "{indent}end");
+}
+
+/// `id`, or `id` wrapped in its shape's delimiters around the label. A node written under a
+/// stand-in id keeps its original id as the label.
+fn node_declaration(ir: &MermaidDiagramIr, ids: &SourceIds<'_>, index: usize) -> String {
+    let node: &IrNode = &ir.nodes[index];
+    let id = ids.node(index);
+    let label = label_text(ir, node.label);
+    if label.is_none() && node.shape == NodeShape::Rect && id == node.id {
+        return node.id.clone();
+    }
+    let (open, close) = shape_delimiters(node.shape);
+    let label = label_source(label.unwrap_or(&node.id));
+    format!("{id}{open}{label}{close}")
+}
+
+/// Delimiters the flowchart parser reads back as `shape`. Shapes flowchart syntax cannot spell
+/// get the nearest one it can.
+const fn shape_delimiters(shape: NodeShape) -> (&'static str, &'static str) {
+    match shape {
+        NodeShape::Rounded | NodeShape::Stadium => ("(", ")"),
+        NodeShape::Subroutine => ("[[", "]]"),
+        NodeShape::Cylinder => ("[(", ")]"),
+        NodeShape::Circle
+        | NodeShape::DoubleCircle
+        | NodeShape::FilledCircle
+        | NodeShape::CrossedCircle => ("((", "))"),
+        NodeShape::Asymmetric | NodeShape::Tag => (">", "]"),
+        NodeShape::Diamond => ("{", "}"),
+        NodeShape::Hexagon => ("{{", "}}"),
+        NodeShape::Parallelogram => ("[/", "/]"),
+        NodeShape::InvParallelogram => ("[\\", "\\]"),
+        NodeShape::Trapezoid => ("[/", "\\]"),
+        NodeShape::InvTrapezoid => ("[\\", "/]"),
+        NodeShape::Rect
+        | NodeShape::HorizontalBar
+        | NodeShape::Note
+        | NodeShape::Triangle
+        | NodeShape::Pentagon
+        | NodeShape::Star
+        | NodeShape::Cloud => ("[", "]"),
+    }
+}
+
+const fn flowchart_arrow(arrow: ArrowType) -> &'static str {
+    match arrow {
+        ArrowType::Line => "---",
+        ArrowType::ThickArrow => "==>",
+        ArrowType::ThickLine => "===",
+        ArrowType::DottedArrow => "-.->",
+        ArrowType::DottedLine => "-.-",
+        ArrowType::Circle => "--o",
+        ArrowType::Cross | ArrowType::DottedCross => "--x",
+        ArrowType::DoubleArrow => "<-->",
+        ArrowType::DoubleThickArrow => "<==>",
+        ArrowType::DoubleDottedArrow => "<-.->",
+        _ => "-->",
+    }
+}
+
+fn emit_sequence(ir: &MermaidDiagramIr, out: &mut String) {
+    let _ = writeln!(out, "sequenceDiagram");
+    let ids = SourceIds::new(ir, &[]);
+    let meta = ir.sequence_meta.as_ref();
+    let (notes, changes) = meta.map_or((&[][..], &[][..]), |meta| {
+        (meta.notes.as_slice(), meta.autonumber_changes.as_slice())
+    });
+    if let Some(meta) = meta {
+        if meta.autonumber && changes.is_empty() {
+            if (meta.autonumber_start, meta.autonumber_increment) == (1, 1) {
+                let _ = writeln!(out, "{INDENT}autonumber");
+            } else {
+                let _ = writeln!(
+                    out,
+                    "{INDENT}autonumber {} {}",
+                    meta.autonumber_start, meta.autonumber_increment
+                );
+            }
+        }
+        if meta.hide_footbox {
+            let _ = writeln!(out, "{INDENT}hide footbox");
+        }
+    }
+
+    for (index, node) in ir.nodes.iter().enumerate() {
+        let keyword = if node
+            .classes
+            .iter()
+            .any(|class| class == SEQUENCE_ACTOR_CLASS)
+        {
+            "actor"
+        } else {
+            "participant"
+        };
+        let id = ids.node(index);
+        let label = label_text(ir, node.label).unwrap_or(&node.id);
+        if label == id {
+            let _ = writeln!(out, "{INDENT}{keyword} {id}");
+        } else {
+            let _ = writeln!(out, "{INDENT}{keyword} {id} as {}", escaped(label));
+        }
+    }
+
+    for (index, edge) in ir.edges.iter().enumerate() {
+        for change in changes.iter().filter(|change| change.edge_index == index) {
+            write_autonumber(change, out);
+        }
+        if let (Some(from), Some(to)) = (ids.endpoint(ir, edge.from), ids.endpoint(ir, edge.to)) {
+            let arrow = sequence_arrow(edge.arrow);
+            match label_text(ir, edge.label) {
+                Some(text) => {
+                    let _ = writeln!(out, "{INDENT}{from}{arrow}{to}: {}", escaped(text));
+                }
+                None => {
+                    let _ = writeln!(out, "{INDENT}{from}{arrow}{to}");
+                }
+            }
+        }
+        for note in notes.iter().filter(|note| note.after_edge == index) {
+            write_note(&ids, note, out);
+        }
+    }
+    let last = ir.edges.len();
+    for change in changes.iter().filter(|change| change.edge_index >= last) {
+        write_autonumber(change, out);
+    }
+    for note in notes.iter().filter(|note| note.after_edge >= last) {
+        write_note(&ids, note, out);
+    }
+}
+
+fn write_autonumber(change: &IrAutonumberChange, out: &mut String) {
+    let _ = match (change.enabled, change.start, change.increment) {
+        (false, ..) => writeln!(out, "{INDENT}autonumber off"),
+        (true, Some(start), Some(increment)) => {
+            writeln!(out, "{INDENT}autonumber {start} {increment}")
+        }
+        (true, Some(start), None) => writeln!(out, "{INDENT}autonumber {start}"),
+        (true, None, _) => writeln!(out, "{INDENT}autonumber"),
+    };
+}
+
+fn write_note(ids: &SourceIds<'_>, note: &IrSequenceNote, out: &mut String) {
+    let participants: Vec<&str> = note
+        .participants
+        .iter()
+        .filter_map(|participant| ids.nodes.get(participant.0))
+        .map(AsRef::as_ref)
+        .collect();
+    if participants.is_empty() {
+        return;
+    }
+    let position = match note.position {
+        NotePosition::LeftOf => "left of",
+        NotePosition::RightOf => "right of",
+        NotePosition::Over => "over",
+    };
+    let _ = writeln!(
+        out,
+        "{INDENT}Note {position} {}: {}",
+        participants.join(","),
+        escaped(&note.text)
+    );
+}
+
+/// The sequence operator the parser reads back as `arrow`; flowchart-only arrows become `->>`.
+const fn sequence_arrow(arrow: ArrowType) -> &'static str {
+    match arrow {
+        ArrowType::Line => "->",
+        ArrowType::DottedLine => "-->",
+        ArrowType::DottedArrow => "-->>",
+        ArrowType::DoubleArrow => "<<->>",
+        ArrowType::DoubleDottedArrow => "<<-->>",
+        ArrowType::OpenArrow
+        | ArrowType::DottedOpenArrow
+        | ArrowType::Cross
+        | ArrowType::DottedCross
+        | ArrowType::HalfArrowTop
+        | ArrowType::HalfArrowBottom
+        | ArrowType::HalfArrowTopReverse
+        | ArrowType::HalfArrowBottomReverse
+        | ArrowType::StickArrowTop
+        | ArrowType::StickArrowBottom
+        | ArrowType::StickArrowTopReverse
+        | ArrowType::StickArrowBottomReverse
+        | ArrowType::HalfArrowTopDotted
+        | ArrowType::HalfArrowBottomDotted
+        | ArrowType::HalfArrowTopReverseDotted
+        | ArrowType::HalfArrowBottomReverseDotted
+        | ArrowType::StickArrowTopDotted
+        | ArrowType::StickArrowBottomDotted
+        | ArrowType::StickArrowTopReverseDotted
+        | ArrowType::StickArrowBottomReverseDotted => arrow.as_str(),
+        ArrowType::Arrow
+        | ArrowType::ThickArrow
+        | ArrowType::ThickLine
+        | ArrowType::Circle
+        | ArrowType::DoubleThickArrow => "->>",
+    }
+}
+
+fn label_text(ir: &MermaidDiagramIr, label: Option<IrLabelId>) -> Option<&str> {
+    label
+        .and_then(|label| ir.labels.get(label.0))
+        .map(|label| label.text.as_str())
+        .filter(|text| !text.is_empty())
+}
+
+/// Node ids and subgraph keys the parser reads back whole: words of letters, digits and `_`
+/// joined by single `-` or `.`, and not the `end` keyword.
+fn is_source_id(id: &str) -> bool {
+    id != "end"
+        && id.split(['-', '.']).all(|word| {
+            !word.is_empty()
+                && word
+                    .chars()
+                    .all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+        })
+}
+
+/// `id` as `_`-joined runs of its letters, digits and `_`, prefixed with `n` when that leaves
+/// nothing or the `end` keyword.
+fn source_id_base(id: &str) -> String {
+    let base = id
+        .split(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_'))
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("_");
+    if base.is_empty() || base == "end" {
+        format!("n{base}")
+    } else {
+        base
+    }
+}
+
+/// Ids the `id@-->` syntax accepts.
+fn is_edge_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+/// A node label as written between shape delimiters: bare when it is plain words, quoted
+/// otherwise.
+fn label_source(text: &str) -> String {
+    let plain = text.trim() == text
+        && text.chars().all(|ch| {
+            ch.is_alphanumeric() || matches!(ch, ' ' | '_' | '-' | '.' | ',' | '!' | '?')
+        });
+    if plain {
+        text.to_string()
+    } else {
+        format!("\"{}\"", escaped(text))
+    }
+}
+
+/// An edge label as written between `|` pipes. The parser takes it verbatim apart from
+/// surrounding quotes, so only a label that would end the pipes early is quoted and escaped.
+fn edge_label_source(text: &str) -> String {
+    if text.contains(['|', '\n']) {
+        format!("\"{}\"", escaped(text))
+    } else {
+        text.to_string()
+    }
+}
+
+/// `text` with the characters that would end a quoted label or a statement written as numeric
+/// entities (`#34;`), which the parser decodes.
+fn escaped(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        if matches!(ch, '"' | '#' | '&' | ';' | '|' | '\n') {
+            let _ = write!(escaped, "#{};", u32::from(ch));
+        } else {
+            escaped.push(ch);
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IrBuilder, IrStyleRef, Span};
+
+    #[test]
+    fn flowcharts_are_written_canonically() {
+        let mut builder = IrBuilder::new(DiagramType::Flowchart);
+        builder.direction(GraphDirection::LR);
+        builder.node("A").label("Start").shape(NodeShape::Rounded);
+        builder
+            .node("B")
+            .label("Is it \"ok\"?")
+            .shape(NodeShape::Diamond);
+        builder.node("C").class("done");
+        builder.edge("A", "B").id("e1");
+        builder
+            .edge("B", "C")
+            .label("yes")
+            .arrow(ArrowType::DottedArrow);
+        builder.cluster("Checks").member("B");
+        let mut ir = builder.build().unwrap();
+        ir.style_refs.push(IrStyleRef {
+            target: IrStyleTarget::Link(1),
+            style: "stroke:red".to_string(),
+            span: Span::default(),
+        });
+
+        assert_eq!(
+            emit_mermaid(&ir).unwrap(),
+            "flowchart LR\n\
+             \x20   A(Start)\n\
+             \x20   C\n\
+             \x20   subgraph cluster_0[Checks]\n\
+             \x20       B{\"Is it #34;ok#34;?\"}\n\
+             \x20   end\n\
+             \x20   A e1@--> B\n\
+             \x20   B -.->|yes| C\n\
+             \x20   class C done\n\
+             \x20   linkStyle 1 stroke:red\n"
+        );
+    }
+
+    #[test]
+    fn sequence_diagrams_keep_participants_messages_and_notes() {
+        let mut builder = IrBuilder::new(DiagramType::Sequence);
+        builder.node("Alice").class(SEQUENCE_ACTOR_CLASS);
+        builder.node("Bob").label("Bob the builder");
+        builder.edge("Alice", "Bob").label("Hi; how are you?");
+        builder.edge("Bob", "Alice").arrow(ArrowType::DottedArrow);
+        let mut ir = builder.build().unwrap();
+        ir.meta.title = Some("Greeting".to_string());
+        let meta = ir.sequence_meta.get_or_insert_with(Default::default);
+        meta.autonumber = true;
+        meta.notes.push(IrSequenceNote {
+            position: NotePosition::Over,
+            participants: vec![crate::IrNodeId(0), crate::IrNodeId(1)],
+            text: "friends".to_string(),
+            after_edge: 0,
+        });
+
+        assert_eq!(
+            emit_mermaid(&ir).unwrap(),
+            "---\ntitle: Greeting\n---\n\
+             sequenceDiagram\n\
+             \x20   autonumber\n\
+             \x20   actor Alice\n\
+             \x20   participant Bob as Bob the builder\n\
+             \x20   Alice->>Bob: Hi#59; how are you?\n\
+             \x20   Note over Alice,Bob: friends\n\
+             \x20   Bob-->>Alice\n"
+        );
+    }
+
+    #[test]
+    fn ids_the_parser_would_split_get_stand_ins() {
+        let mut builder = IrBuilder::new(DiagramType::Flowchart);
+        builder.node("web server").class("edge");
+        builder.node("web_server").label("Taken");
+        builder.node("end");
+        builder.edge("web server", "end");
+        builder.edge("web_server", "end");
+        let mut ir = builder.build().unwrap();
+        ir.graph.subgraphs.push(crate::IrSubgraph {
+            key: "1:backend".to_string(),
+            members: vec![crate::IrNodeId(1)],
+            ..crate::IrSubgraph::default()
+        });
+
+        assert_eq!(
+            emit_mermaid(&ir).unwrap(),
+            "flowchart TB\n\
+             \x20   web_server_2[web server]\n\
+             \x20   nend[end]\n\
+             \x20   subgraph 1_backend[\"1:backend\"]\n\
+             \x20       web_server[Taken]\n\
+             \x20   end\n\
+             \x20   web_server_2 --> nend\n\
+             \x20   web_server --> nend\n\
+             \x20   class web_server_2 edge\n"
+        );
+    }
+
+    #[test]
+    fn other_diagram_types_are_refused() {
+        let mut builder = IrBuilder::new(DiagramType::Class);
+        builder.node("Animal");
+        let error = emit_mermaid(&builder.build().unwrap()).unwrap_err();
+        assert_eq!(error.code(), crate::MermaidErrorCode::Unsupported);
+        assert!(error.to_string().contains("class"));
+    }
+}
@@ -112,6 +112,37 @@ impl FontPreset {
         }
     }
 
+    /// Like [`Self::from_family`], but `None` unless some family in the comma-separated stack is
+    /// one the presets were fitted to (a generic family or a common UI, serif or monospace
+    /// font), so callers can fall back to a coarser estimate for fonts they know nothing about.
+    #[must_use]
+    pub fn from_known_family(family: &str) -> Option<Self> {
+        const SANS_FAMILIES: &[&str] = &[
+            "sans-serif",
+            "system-ui",
+            "ui-sans-serif",
+            "-apple-system",
+            "blinkmacsystemfont",
+            "segoe ui",
+            "roboto",
+            "helvetica",
+            "helvetica neue",
+            "arial",
+            "inter",
+            "verdana",
+            "tahoma",
+            "trebuchet ms",
+            "open sans",
+            "noto sans",
+        ];
+        let known = family.split(',').any(|name| {
+            let name = name.trim().trim_matches(['\'', '"']).to_lowercase();
+            SANS_FAMILIES.contains(&name.as_str())
+                || (!name.is_empty() && Self::from_family(&name) != Self::SansSerif)
+        });
+        known.then(|| Self::from_family(family))
+    }
+
     /// Get the average character width ratio for this preset (relative to em-size).
     #[must_use]
     pub const fn avg_char_ratio(&self) -> f32 {
@@ -589,6 +620,16 @@ mod tests {
             FontPreset::from_family("Arial Narrow"),
             FontPreset::Condensed
         );
+        assert_eq!(
+            FontPreset::from_known_family("'Inter', Helvetica, sans-serif"),
+            Some(FontPreset::SansSerif)
+        );
+        assert_eq!(
+            FontPreset::from_known_family("\"Fira Code\", ui-monospace"),
+            Some(FontPreset::Monospace)
+        );
+        assert_eq!(FontPreset::from_known_family("Comic Neue"), None);
+        assert_eq!(FontPreset::from_known_family(""), None);
     }
 
     #[test]
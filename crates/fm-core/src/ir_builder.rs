@@ -0,0 +1,373 @@
+//! Programmatic construction of a [`MermaidDiagramIr`].
+//!
+//! [`IrBuilder`] lets code assemble a diagram without hand-wiring indices: nodes and edges are
+//! named by their Mermaid ids, label text is interned into [`MermaidDiagramIr::labels`], and the
+//! parallel [`MermaidGraphIr`](crate::MermaidGraphIr) tables are filled in. Edges and cluster
+//! members may name nodes declared later; [`IrBuilder::build`] resolves every reference and
+//! rejects the diagram with a [`MermaidError::Validation`] when one does not resolve.
+//!
+//! ```
+//! use fm_core::{ArrowType, DiagramType, IrBuilder, NodeShape};
+//!
+//! let mut builder = IrBuilder::new(DiagramType::Flowchart);
+//! builder.node("A").label("Start").shape(NodeShape::Diamond);
+//! builder.node("B");
+//! builder.edge("A", "B").arrow(ArrowType::DottedArrow).label("next");
+//! let ir = builder.build().expect("every edge names a node");
+//! assert_eq!(ir.nodes.len(), 2);
+//! ```
+
+use std::collections::BTreeMap;
+
+use crate::{
+    ArrowType, DiagramType, GraphDirection, IrCluster, IrClusterId, IrEdge, IrEdgeKind, IrEndpoint,
+    IrGraphCluster, IrGraphEdge, IrGraphNode, IrLabel, IrLabelId, IrNode, IrNodeId, IrNodeKind,
+    MermaidDiagramIr, MermaidError, NodeShape, Span,
+};
+
+/// Assembles a [`MermaidDiagramIr`] from node ids, checking references when it is built.
+#[derive(Debug, Clone)]
+pub struct IrBuilder {
+    ir: MermaidDiagramIr,
+    node_ids: BTreeMap<String, IrNodeId>,
+    label_ids: BTreeMap<String, IrLabelId>,
+    /// `(from, to)` node ids of each edge, indexed like `ir.edges`.
+    edge_endpoints: Vec<(String, String)>,
+    /// Member node ids of each cluster, indexed like `ir.clusters`.
+    cluster_members: Vec<Vec<String>>,
+}
+
+impl IrBuilder {
+    #[must_use]
+    pub fn new(diagram_type: DiagramType) -> Self {
+        Self {
+            ir: MermaidDiagramIr::empty(diagram_type),
+            node_ids: BTreeMap::new(),
+            label_ids: BTreeMap::new(),
+            edge_endpoints: Vec::new(),
+            cluster_members: Vec::new(),
+        }
+    }
+
+    /// Set the layout direction.
+    pub fn direction(&mut self, direction: GraphDirection) -> &mut Self {
+        self.ir.direction = direction;
+        self.ir.meta.direction = direction;
+        self
+    }
+
+    /// The node `id`, declaring it on first use. Calling this again for the same id returns the
+    /// same node, so its label and shape can be set in several places.
+    pub fn node(&mut self, id: &str) -> NodeBuilder<'_> {
+        let node = match self.node_ids.get(id) {
+            Some(&node) => node,
+            None => {
+                let node = IrNodeId(self.ir.nodes.len());
+                self.ir.nodes.push(IrNode {
+                    id: id.to_string(),
+                    ..IrNode::default()
+                });
+                self.ir.graph.nodes.push(IrGraphNode {
+                    node_id: node,
+                    kind: node_kind(self.ir.diagram_type),
+                    clusters: Vec::new(),
+                    subgraphs: Vec::new(),
+                });
+                self.node_ids.insert(id.to_string(), node);
+                node
+            }
+        };
+        NodeBuilder {
+            builder: self,
+            node,
+        }
+    }
+
+    /// A new edge from node `from` to node `to`, drawn as a plain arrow unless
+    /// [`EdgeBuilder::arrow`] says otherwise. The nodes need not be declared yet.
+    pub fn edge(&mut self, from: &str, to: &str) -> EdgeBuilder<'_> {
+        let edge = self.ir.edges.len();
+        self.ir.edges.push(IrEdge {
+            arrow: ArrowType::Arrow,
+            ..IrEdge::default()
+        });
+        self.ir.graph.edges.push(IrGraphEdge {
+            edge_id: edge,
+            kind: edge_kind(self.ir.diagram_type),
+            ..IrGraphEdge::default()
+        });
+        self.edge_endpoints.push((from.to_string(), to.to_string()));
+        EdgeBuilder {
+            builder: self,
+            edge,
+        }
+    }
+
+    /// A new cluster (a flowchart subgraph box) titled `title`.
+    pub fn cluster(&mut self, title: &str) -> ClusterBuilder<'_> {
+        let cluster = IrClusterId(self.ir.clusters.len());
+        let title = self.intern_label(title);
+        self.ir.clusters.push(IrCluster {
+            id: cluster,
+            title: Some(title),
+            grid_span: 1,
+            ..IrCluster::default()
+        });
+        self.ir.graph.clusters.push(IrGraphCluster {
+            cluster_id: cluster,
+            title: Some(title),
+            grid_span: 1,
+            ..IrGraphCluster::default()
+        });
+        self.cluster_members.push(Vec::new());
+        ClusterBuilder {
+            builder: self,
+            cluster,
+        }
+    }
+
+    /// Resolve every edge endpoint and cluster member and return the diagram.
+    ///
+    /// # Errors
+    ///
+    /// [`MermaidError::Validation`] when a node id is empty, an edge or cluster names a node that
+    /// was never declared, or two edges share an id.
+    pub fn build(mut self) -> Result<MermaidDiagramIr, MermaidError> {
+        if self.node_ids.contains_key("") {
+            return Err(validation_error("node ids must not be empty".to_string()));
+        }
+
+        let mut edge_user_ids = BTreeMap::new();
+        for (index, (from, to)) in self.edge_endpoints.iter().enumerate() {
+            let resolve = |id: &str| {
+                self.node_ids.get(id).copied().ok_or_else(|| {
+                    validation_error(format!("edge {index} references unknown node '{id}'"))
+                })
+            };
+            let (from, to) = (
+                IrEndpoint::Node(resolve(from)?),
+                IrEndpoint::Node(resolve(to)?),
+            );
+            let edge = &mut self.ir.edges[index];
+            (edge.from, edge.to) = (from, to);
+            let graph_edge = &mut self.ir.graph.edges[index];
+            (graph_edge.from, graph_edge.to) = (from, to);
+            if let Some(user_id) = edge.user_id()
+                && let Some(first) = edge_user_ids.insert(user_id.to_string(), index)
+            {
+                return Err(validation_error(format!(
+                    "edges {first} and {index} share the id '{user_id}'"
+                )));
+            }
+        }
+
+        for (index, members) in self.cluster_members.iter().enumerate() {
+            for id in members {
+                let Some(&node) = self.node_ids.get(id.as_str()) else {
+                    return Err(validation_error(format!(
+                        "cluster {index} references unknown node '{id}'"
+                    )));
+                };
+                let cluster = IrClusterId(index);
+                if self.ir.clusters[index].members.contains(&node) {
+                    continue;
+                }
+                self.ir.clusters[index].members.push(node);
+                self.ir.graph.clusters[index].members.push(node);
+                self.ir.graph.nodes[node.0].clusters.push(cluster);
+            }
+        }
+
+        Ok(self.ir)
+    }
+
+    fn intern_label(&mut self, text: &str) -> IrLabelId {
+        if let Some(&label) = self.label_ids.get(text) {
+            return label;
+        }
+        let label = IrLabelId(self.ir.labels.len());
+        self.ir.labels.push(IrLabel {
+            text: text.to_string(),
+            span: Span::default(),
+        });
+        self.label_ids.insert(text.to_string(), label);
+        label
+    }
+}
+
+/// A node being configured; see [`IrBuilder::node`].
+pub struct NodeBuilder<'a> {
+    builder: &'a mut IrBuilder,
+    node: IrNodeId,
+}
+
+impl NodeBuilder<'_> {
+    #[must_use]
+    pub fn id(&self) -> IrNodeId {
+        self.node
+    }
+
+    pub fn label(self, text: &str) -> Self {
+        let label = self.builder.intern_label(text);
+        self.builder.ir.nodes[self.node.0].label = Some(label);
+        self
+    }
+
+    pub fn shape(self, shape: NodeShape) -> Self {
+        self.builder.ir.nodes[self.node.0].shape = shape;
+        self
+    }
+
+    /// Add the `classDef` class `name`, as `A:::name` does.
+    pub fn class(self, name: &str) -> Self {
+        let classes = &mut self.builder.ir.nodes[self.node.0].classes;
+        if !classes.iter().any(|class| class == name) {
+            classes.push(name.to_string());
+        }
+        self
+    }
+}
+
+/// An edge being configured; see [`IrBuilder::edge`].
+pub struct EdgeBuilder<'a> {
+    builder: &'a mut IrBuilder,
+    edge: usize,
+}
+
+impl EdgeBuilder<'_> {
+    /// Index of the edge in [`MermaidDiagramIr::edges`].
+    #[must_use]
+    pub fn index(&self) -> usize {
+        self.edge
+    }
+
+    pub fn arrow(self, arrow: ArrowType) -> Self {
+        self.builder.ir.edges[self.edge].arrow = arrow;
+        self
+    }
+
+    pub fn label(self, text: &str) -> Self {
+        let label = self.builder.intern_label(text);
+        self.builder.ir.edges[self.edge].label = Some(label);
+        self
+    }
+
+    /// Give the edge a stable id, as `A e1@--> B` does.
+    pub fn id(self, id: &str) -> Self {
+        self.builder.ir.edges[self.edge].extras_mut().user_id = Some(Box::from(id));
+        self
+    }
+}
+
+/// A cluster being configured; see [`IrBuilder::cluster`].
+pub struct ClusterBuilder<'a> {
+    builder: &'a mut IrBuilder,
+    cluster: IrClusterId,
+}
+
+impl ClusterBuilder<'_> {
+    #[must_use]
+    pub fn id(&self) -> IrClusterId {
+        self.cluster
+    }
+
+    /// Put node `id` in the cluster. The node need not be declared yet.
+    pub fn member(self, id: &str) -> Self {
+        self.builder.cluster_members[self.cluster.0].push(id.to_string());
+        self
+    }
+}
+
+fn validation_error(message: String) -> MermaidError {
+    MermaidError::Validation {
+        message,
+        span: Span::default(),
+    }
+}
+
+const fn node_kind(diagram_type: DiagramType) -> IrNodeKind {
+    match diagram_type {
+        DiagramType::Er => IrNodeKind::Entity,
+        DiagramType::Sequence => IrNodeKind::Participant,
+        DiagramType::State => IrNodeKind::State,
+        DiagramType::Gantt => IrNodeKind::Task,
+        DiagramType::Timeline | DiagramType::Journey => IrNodeKind::Event,
+        DiagramType::GitGraph => IrNodeKind::Commit,
+        DiagramType::Requirement => IrNodeKind::Requirement,
+        DiagramType::Pie => IrNodeKind::Slice,
+        DiagramType::QuadrantChart | DiagramType::XyChart => IrNodeKind::Point,
+        _ => IrNodeKind::Generic,
+    }
+}
+
+const fn edge_kind(diagram_type: DiagramType) -> IrEdgeKind {
+    match diagram_type {
+        DiagramType::Er => IrEdgeKind::Relationship,
+        DiagramType::Sequence => IrEdgeKind::Message,
+        DiagramType::Timeline | DiagramType::Journey => IrEdgeKind::Timeline,
+        DiagramType::Gantt => IrEdgeKind::Dependency,
+        DiagramType::GitGraph => IrEdgeKind::Commit,
+        _ => IrEdgeKind::Generic,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_a_checked_flowchart() {
+        let mut builder = IrBuilder::new(DiagramType::Flowchart);
+        builder.direction(GraphDirection::LR);
+        builder
+            .node("A")
+            .label("Start")
+            .shape(NodeShape::Diamond)
+            .class("hot");
+        builder
+            .edge("A", "B")
+            .arrow(ArrowType::ThickArrow)
+            .label("Start")
+            .id("e1");
+        builder.cluster("Group").member("B").member("B");
+        builder.node("B").label("End");
+        let ir = builder.build().unwrap();
+
+        assert_eq!(ir.direction, GraphDirection::LR);
+        assert_eq!(ir.nodes.len(), 2);
+        assert_eq!(ir.nodes[0].shape, NodeShape::Diamond);
+        assert_eq!(ir.nodes[0].classes, ["hot"]);
+        // "Start" is interned once for the node and the edge.
+        assert_eq!(ir.nodes[0].label, ir.edges[0].label);
+        let texts: Vec<_> = ir.labels.iter().map(|label| label.text.as_str()).collect();
+        assert_eq!(texts, ["Start", "Group", "End"]);
+        assert_eq!(ir.edges[0].from, IrEndpoint::Node(IrNodeId(0)));
+        assert_eq!(ir.edges[0].to, IrEndpoint::Node(IrNodeId(1)));
+        assert_eq!(ir.edges[0].user_id(), Some("e1"));
+        assert_eq!(ir.graph.edges[0].to, ir.edges[0].to);
+        assert_eq!(ir.clusters[0].members, [IrNodeId(1)]);
+        assert_eq!(ir.graph.nodes[1].clusters, [IrClusterId(0)]);
+    }
+
+    #[test]
+    fn rejects_unresolved_references() {
+        let mut builder = IrBuilder::new(DiagramType::Flowchart);
+        builder.node("A");
+        builder.edge("A", "Missing");
+        let error = builder.build().unwrap_err();
+        assert!(matches!(error, MermaidError::Validation { .. }));
+        assert!(error.to_string().contains("'Missing'"), "{error}");
+
+        let mut builder = IrBuilder::new(DiagramType::Flowchart);
+        builder.cluster("Group").member("Nobody");
+        assert!(builder.build().is_err());
+
+        let mut builder = IrBuilder::new(DiagramType::Flowchart);
+        builder.edge("A", "B").id("e1");
+        builder.edge("B", "A").id("e1");
+        builder.node("A");
+        builder.node("B");
+        let error = builder.build().unwrap_err();
+        assert!(error.to_string().contains("share the id 'e1'"), "{error}");
+    }
+}
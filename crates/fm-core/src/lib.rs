@@ -4,15 +4,25 @@ pub mod art;
 pub mod canary;
 pub mod cga;
 pub mod constraints;
+pub mod corpus;
+mod emit;
 pub mod epoch;
 pub mod evidence;
 mod font_metrics;
+mod ir_builder;
 pub mod leapfrog;
 #[cfg(test)]
 mod lens_tests;
+mod markdown;
 pub mod quotient_filter;
+mod shape_registry;
 pub mod succinct;
+mod theme_vars;
+pub mod transform;
+mod validate;
+mod wrap;
 
+pub use emit::emit_mermaid;
 pub use evidence::{
     DecisionMode, EvidenceBundle, EvidenceLogEntry, EvidenceSummary, FallbackReason, FnxFeatures,
     FnxMode, PassFailReason, ProjectionMode, fnv1a_hex,
@@ -21,6 +31,18 @@ pub use font_metrics::{
     CharWidthClass, DiagnosticLevel, FontMetrics, FontMetricsConfig, FontMetricsDiagnostic,
     FontPreset, is_east_asian_wide,
 };
+pub use ir_builder::{ClusterBuilder, EdgeBuilder, IrBuilder, NodeBuilder};
+pub use markdown::{label_segments_text, parse_label_markdown};
+pub use shape_registry::{NodeShapeDrawer, NodeShapeRegistry};
+pub use theme_vars::{
+    ThemeVariableKind, is_css_named_color, is_valid_css_color, theme_variable_kind,
+    validate_theme_variable,
+};
+pub use validate::validate_ir;
+pub use wrap::{
+    WRAP_ELLIPSIS, WRAP_ELLIPSIS_ASCII, ellipsize, ellipsize_with_marker, wrap_label,
+    wrap_label_with_marker,
+};
 
 use std::borrow::Cow;
 use std::collections::{BTreeMap, BTreeSet};
@@ -994,7 +1016,9 @@ pub struct IrPortId(pub usize);
 )]
 pub struct IrLabelId(pub usize);
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Default)]
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Default,
+)]
 pub struct IrClusterId(pub usize);
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Default)]
@@ -1185,14 +1209,27 @@ pub enum ClassMemberKind {
     Method,
 }
 
+/// One parameter of a class method: `amount: int`, `int amount` or a bare `amount`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct IrClassParameter {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub type_name: Option<String>,
+}
+
 /// A single class member (attribute or method).
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
 pub struct IrClassMember {
     pub visibility: ClassVisibility,
     pub kind: ClassMemberKind,
+    /// The member as written, without its visibility, `$`/`*` marker or return type: `String name`
+    /// or `deposit(amount: int)`.
     pub name: String,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub return_type: Option<String>,
+    /// A method's parameters, parsed from the list between the parentheses of `name`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parameters: Vec<IrClassParameter>,
     pub is_static: bool,
     pub is_abstract: bool,
 }
@@ -1221,6 +1258,43 @@ pub struct IrClassNodeMeta {
     pub generics: Vec<String>,
 }
 
+/// UML relation drawn by a class-diagram edge operator.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum ClassRelationKind {
+    /// `<|--` / `--|>`
+    Inheritance,
+    /// `<|..` / `..|>`
+    Realization,
+    /// `*--` / `--*`
+    Composition,
+    /// `o--` / `--o`
+    Aggregation,
+    /// `-->`
+    Association,
+    /// `..>` / `<..`
+    Dependency,
+    /// `--`
+    Link,
+}
+
+impl ClassRelationKind {
+    /// Inheritance and realization: the edge points from a subtype to its supertype.
+    #[must_use]
+    pub const fn is_generalization(self) -> bool {
+        matches!(self, Self::Inheritance | Self::Realization)
+    }
+}
+
+/// Typed class relation attached to a class-diagram edge.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct IrClassRelation {
+    pub kind: ClassRelationKind,
+    /// True when the operator's marker (triangle, diamond, arrowhead) sits at the edge's `from`
+    /// end, as in `Animal <|-- Duck`; false for `Duck --|> Animal`. For generalizations the marked
+    /// end is the supertype.
+    pub marker_at_from: bool,
+}
+
 /// C4-diagram-specific metadata for a node.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
 pub struct IrC4NodeMeta {
@@ -1274,6 +1348,10 @@ pub struct IrNode {
     /// Parsed inline style from `style nodeId ...` directives.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub inline_style: Option<Box<IrInlineStyle>>,
+    /// Name of a custom shape drawn through a renderer's [`NodeShapeRegistry`]; renderers
+    /// without a matching drawer fall back to `shape`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_shape: Option<String>,
 }
 
 /// Rarely-populated icon/link/interaction fields split off `IrNode` (see [`IrNode::interaction`])
@@ -1385,6 +1463,9 @@ pub struct IrEdge {
     /// Parsed inline style from `linkStyle N ...` directives.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub inline_style: Option<Box<IrInlineStyle>>,
+    /// Explicit layout weight. `None` uses the arrow's default; see [`IrEdge::layout_weight`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<u32>,
 }
 
 /// Rarely-populated, diagram-specific fields split off `IrEdge` (see [`IrEdge::extras`]) so the
@@ -1406,9 +1487,65 @@ pub struct IrEdgeExtras {
     /// Action on a state transition (e.g., `cleanup()`).
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub action: Option<Box<str>>,
+    /// UML relation kind for class diagrams, derived from the edge operator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class_relation: Option<IrClassRelation>,
+    /// Id the source gave the edge, as in `A e1@--> B`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<Box<str>>,
+}
+
+/// Stable identity of an edge, independent of its position in [`MermaidDiagramIr::edges`], so
+/// inserting or removing an unrelated edge leaves every other edge's id alone. See
+/// [`MermaidDiagramIr::edge_ids`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IrEdgeId {
+    /// The id the source gave the edge (`A e1@--> B`).
+    User(String),
+    /// The `occurrence`-th edge (from 0, in source order) between the nodes `from` and `to`
+    /// among those without a user id.
+    Endpoints {
+        from: String,
+        to: String,
+        occurrence: usize,
+    },
+}
+
+impl std::fmt::Display for IrEdgeId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::User(id) => f.write_str(id),
+            Self::Endpoints {
+                from,
+                to,
+                occurrence: 0,
+            } => write!(f, "{from}->{to}"),
+            Self::Endpoints {
+                from,
+                to,
+                occurrence,
+            } => write!(f, "{from}->{to}#{occurrence}"),
+        }
+    }
 }
 
 impl IrEdge {
+    /// Weight of a thick edge (`==>`, `===`, `<==>`) without an explicit weight.
+    pub const THICK_EDGE_WEIGHT: u32 = 2;
+
+    /// Weight the layered layout gives this edge: the explicit [`IrEdge::weight`], otherwise
+    /// [`IrEdge::THICK_EDGE_WEIGHT`] for thick edges and 1 for everything else. As in dagre,
+    /// heavier edges are kept short and straight in preference to lighter ones.
+    #[must_use]
+    pub fn layout_weight(&self) -> u32 {
+        self.weight.unwrap_or(match self.arrow {
+            ArrowType::ThickArrow | ArrowType::ThickLine | ArrowType::DoubleThickArrow => {
+                Self::THICK_EDGE_WEIGHT
+            }
+            _ => 1,
+        })
+    }
+
     /// Raw ER cardinality operator, if any.
     #[must_use]
     pub fn er_notation(&self) -> Option<&str> {
@@ -1438,6 +1575,16 @@ impl IrEdge {
     pub fn action(&self) -> Option<&str> {
         self.extras.as_ref().and_then(|e| e.action.as_deref())
     }
+    /// Class-diagram relation kind, if any.
+    #[must_use]
+    pub fn class_relation(&self) -> Option<IrClassRelation> {
+        self.extras.as_ref().and_then(|e| e.class_relation)
+    }
+    /// Id the source gave this edge, if any.
+    #[must_use]
+    pub fn user_id(&self) -> Option<&str> {
+        self.extras.as_ref().and_then(|e| e.user_id.as_deref())
+    }
     /// Mutable access to the diagram-specific extras, allocating the box on first use.
     pub fn extras_mut(&mut self) -> &mut IrEdgeExtras {
         self.extras
@@ -1713,6 +1860,9 @@ pub enum IrStyleTarget {
     LinkDefault,
 }
 
+/// Name of the `classDef` that styles every node before its own classes: `classDef default ...`.
+pub const DEFAULT_STYLE_CLASS: &str = "default";
+
 /// A style reference from a `classDef`, `style`, or `linkStyle` directive.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct IrStyleRef {
@@ -1772,6 +1922,18 @@ impl IrInlineStyle {
         self.properties.is_empty()
     }
 
+    /// A length property such as `stroke-width` in pixels: `4px` and a bare `4` both read as
+    /// `4.0`. `None` when the property is unset or not a pixel length.
+    #[must_use]
+    pub fn length_px(&self, property: &str) -> Option<f32> {
+        let value = self.properties.get(property)?.trim();
+        let number = value.strip_suffix("px").unwrap_or(value).trim_end();
+        number
+            .parse::<f32>()
+            .ok()
+            .filter(|length| length.is_finite())
+    }
+
     /// Render as a CSS-style string for the `style` attribute.
     #[must_use]
     pub fn to_css_string(&self) -> String {
@@ -1950,6 +2112,34 @@ pub fn is_safe_link_target(target: &str, sanitize_mode: MermaidSanitizeMode) ->
     }
 }
 
+/// The function a `click nodeId call ...` callback names, with any argument list (`fn(a, b)`)
+/// dropped. `None` unless the name is a plain JavaScript identifier path such as `handlers.open`,
+/// so nothing but a name ever reaches a renderer's data attributes.
+#[must_use]
+pub fn callback_function_name(callback: &str) -> Option<&str> {
+    let callback = callback.trim();
+    let name = match callback.split_once('(') {
+        Some((name, args))
+            if args
+                .trim_end()
+                .strip_suffix(')')
+                .is_some_and(|inner| !inner.contains(['(', ')'])) =>
+        {
+            name.trim_end()
+        }
+        Some(_) => return None,
+        None => callback,
+    };
+    let is_identifier = |part: &str| {
+        let mut chars = part.chars();
+        chars
+            .next()
+            .is_some_and(|first| first.is_ascii_alphabetic() || matches!(first, '_' | '$'))
+            && chars.all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '_' | '$'))
+    };
+    name.split('.').all(is_identifier).then_some(name)
+}
+
 fn decode_percent_triplets(input: &str) -> String {
     let bytes = input.as_bytes();
     if !bytes.contains(&b'%') {
@@ -2105,6 +2295,8 @@ pub enum MermaidRenderMode {
     Braille,
     Block,
     HalfBlock,
+    /// Inline image (Kitty graphics, iTerm2 or Sixel) where the terminal supports one.
+    Pixels,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
@@ -2306,10 +2498,22 @@ pub fn parse_mermaid_js_config_value(value: &Value) -> MermaidConfigParse {
                 if let Some(theme_vars) = raw_value.as_object() {
                     for (var_key, var_value) in theme_vars {
                         if let Some(value_text) = json_scalar_to_string(var_value) {
-                            parsed
-                                .config
-                                .theme_variables
-                                .insert(var_key.clone(), value_text);
+                            // Invalid values are reported and dropped so the theme default
+                            // applies instead of a broken CSS declaration.
+                            match validate_theme_variable(var_key, &value_text) {
+                                Ok(normalized) => {
+                                    parsed
+                                        .config
+                                        .theme_variables
+                                        .insert(var_key.clone(), normalized);
+                                }
+                                Err(message) => push_type_error(
+                                    &mut parsed,
+                                    &format!("themeVariables.{var_key}"),
+                                    var_value,
+                                    &message,
+                                ),
+                            }
                         } else {
                             push_type_error(
                                 &mut parsed,
@@ -2540,6 +2744,30 @@ pub struct MermaidComplexity {
     pub score: usize,
 }
 
+impl MermaidComplexity {
+    /// Element counts of `ir` and their weighted sum: clusters weigh most, then nodes, edges,
+    /// labels and ports.
+    #[must_use]
+    pub fn of(ir: &MermaidDiagramIr) -> Self {
+        Self {
+            nodes: ir.nodes.len(),
+            edges: ir.edges.len(),
+            labels: ir.labels.len(),
+            clusters: ir.clusters.len(),
+            ports: ir.ports.len(),
+            style_refs: ir.nodes.iter().map(|node| node.classes.len()).sum(),
+            score: ir
+                .nodes
+                .len()
+                .saturating_mul(4)
+                .saturating_add(ir.edges.len().saturating_mul(3))
+                .saturating_add(ir.labels.len().saturating_mul(2))
+                .saturating_add(ir.clusters.len().saturating_mul(5))
+                .saturating_add(ir.ports.len()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
 pub enum MermaidPressureSource {
     #[default]
@@ -3802,6 +4030,17 @@ impl Default for MermaidFallbackPolicy {
 pub struct MermaidValidation {
     pub warnings: Vec<MermaidWarning>,
     pub errors: Vec<MermaidError>,
+    /// Findings of [`validate_ir`], errors and warnings alike, with the spans they point at.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl MermaidValidation {
+    /// Whether nothing at error level was found.
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
@@ -4385,6 +4624,25 @@ pub struct IrSequenceMeta {
     pub participant_groups: Vec<IrParticipantGroup>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub lifecycle_events: Vec<IrLifecycleEvent>,
+    /// `autonumber` statements in source order, when numbering does not simply cover every
+    /// message: a statement after the first message, `autonumber off`, or a restart. Empty means
+    /// `autonumber`, `autonumber_start` and `autonumber_increment` apply to all messages.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub autonumber_changes: Vec<IrAutonumberChange>,
+}
+
+/// One `autonumber` statement, applying from the message at `edge_index` on.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IrAutonumberChange {
+    /// Index of the first message (edge) the statement applies to.
+    pub edge_index: usize,
+    /// `autonumber N` restarts the count at `N`; without it the count carries on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub increment: Option<u32>,
+    /// `false` for `autonumber off`.
+    pub enabled: bool,
 }
 
 const fn default_sequence_autonumber_start() -> u32 {
@@ -4417,21 +4675,45 @@ impl Default for IrSequenceMeta {
             fragments: Vec::new(),
             participant_groups: Vec::new(),
             lifecycle_events: Vec::new(),
+            autonumber_changes: Vec::new(),
         }
     }
 }
 
 impl IrSequenceMeta {
+    /// Sequence number shown on the message at `edge_index`, if numbering is on for it.
+    ///
+    /// With [`Self::autonumber_changes`] the count works like Mermaid's: it starts at 1, steps
+    /// by 1, and advances on every message, numbered or not, so a bare `autonumber` after
+    /// `autonumber off` carries on where the count stands.
     #[must_use]
     pub fn autonumber_value(&self, edge_index: usize) -> Option<u64> {
         if !self.autonumber {
             return None;
         }
+        if self.autonumber_changes.is_empty() {
+            return Some(
+                u64::from(self.autonumber_start)
+                    + (edge_index as u64) * u64::from(self.autonumber_increment),
+            );
+        }
 
-        Some(
-            u64::from(self.autonumber_start)
-                + (edge_index as u64) * u64::from(self.autonumber_increment),
-        )
+        let (mut number, mut step, mut enabled, mut at) = (1_u64, 1_u64, false, 0_usize);
+        for change in &self.autonumber_changes {
+            if change.edge_index > edge_index {
+                break;
+            }
+            number += (change.edge_index - at) as u64 * step;
+            at = change.edge_index;
+            if let Some(start) = change.start {
+                number = u64::from(start);
+            }
+            if let Some(increment) = change.increment {
+                step = u64::from(increment);
+            }
+            enabled = change.enabled;
+        }
+        enabled.then(|| number + (edge_index - at) as u64 * step)
     }
 }
 
@@ -4453,6 +4735,17 @@ pub struct IrPieMeta {
     pub slices: Vec<IrPieSlice>,
 }
 
+/// The numeric data of a chart diagram, borrowed from its typed metadata. Charts keep their
+/// values here rather than in node labels: renderers draw wedges, bars and lines from it, and the
+/// nodes the parser also creates only carry display text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChartData<'a> {
+    /// Pie slices, each a label and its value.
+    Pie(&'a IrPieMeta),
+    /// xyChart axes and series.
+    XyChart(&'a IrXyChartMeta),
+}
+
 /// Requirement-diagram-specific metadata for a node.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
 pub struct IrRequirementNodeMeta {
@@ -4501,6 +4794,51 @@ pub struct IrQuadrantMeta {
     pub points: Vec<IrQuadrantPoint>,
 }
 
+/// The branch a git-graph commit was made on.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IrGitCommit {
+    pub node: IrNodeId,
+    /// Index into [`IrGitGraphMeta::branches`].
+    pub branch: usize,
+}
+
+/// Git-graph-specific metadata: branches in creation order and the branch of every commit.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct IrGitGraphMeta {
+    /// Branch names in creation order; `main` is always first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub branches: Vec<String>,
+    /// Commits in chronological order, including merge and cherry-pick commits.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub commits: Vec<IrGitCommit>,
+}
+
+impl IrGitGraphMeta {
+    /// Number of distinct branch colors; branch `i` uses color `i % BRANCH_COLOR_COUNT`, matching
+    /// the parser's `git-branch-N` classes.
+    pub const BRANCH_COLOR_COUNT: usize = 8;
+
+    #[must_use]
+    pub const fn branch_color_index(branch: usize) -> usize {
+        branch % Self::BRANCH_COLOR_COUNT
+    }
+
+    /// Branch index of the commit at `node`, if it is a recorded commit.
+    #[must_use]
+    pub fn branch_of(&self, node: IrNodeId) -> Option<usize> {
+        // Commits are recorded in node creation order, so node `n` is usually commit `n`.
+        if let Some(commit) = self.commits.get(node.0)
+            && commit.node == node
+        {
+            return Some(commit.branch);
+        }
+        self.commits
+            .iter()
+            .find(|commit| commit.node == node)
+            .map(|commit| commit.branch)
+    }
+}
+
 // ── State diagram note ────────────────────────────────────────────────
 
 /// A note attached to a state node.
@@ -4515,6 +4853,18 @@ pub struct IrStateNote {
     pub span: Span,
 }
 
+impl IrStateNote {
+    /// Side of the state the note sits on; anything but `"left"` is the right side.
+    #[must_use]
+    pub fn note_position(&self) -> NotePosition {
+        if self.position.eq_ignore_ascii_case("left") {
+            NotePosition::LeftOf
+        } else {
+            NotePosition::RightOf
+        }
+    }
+}
+
 // ── Main IR container ──────────────────────────────────────────────────
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
@@ -4547,10 +4897,26 @@ pub struct MermaidDiagramIr {
     pub pie_meta: Option<IrPieMeta>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub quadrant_meta: Option<IrQuadrantMeta>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gitgraph_meta: Option<IrGitGraphMeta>,
     /// Notes attached to state diagram nodes.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub state_notes: Vec<IrStateNote>,
     pub diagnostics: Vec<Diagnostic>,
+    /// Which source diagram each element came from, set on diagrams built by
+    /// [`transform::merge_irs`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<IrProvenance>,
+}
+
+/// Source of every element of a merged diagram, as indices into the slice passed to
+/// [`transform::merge_irs`]. Each table is indexed like the IR table it describes.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct IrProvenance {
+    /// Every source that declared the node, in order; a node several diagrams share lists each.
+    pub nodes: Vec<Vec<usize>>,
+    pub edges: Vec<usize>,
+    pub clusters: Vec<usize>,
 }
 
 impl MermaidDiagramIr {
@@ -4588,8 +4954,10 @@ impl MermaidDiagramIr {
             xy_chart_meta: None,
             pie_meta: None,
             quadrant_meta: None,
+            gitgraph_meta: None,
             state_notes: Vec::new(),
             diagnostics: Vec::new(),
+            provenance: None,
         }
     }
 
@@ -4661,6 +5029,24 @@ impl MermaidDiagramIr {
             .collect()
     }
 
+    /// The chart data of a pie chart with slices or an xyChart with series, if this is one.
+    #[must_use]
+    pub fn chart_data(&self) -> Option<ChartData<'_>> {
+        match self.diagram_type {
+            DiagramType::Pie => self
+                .pie_meta
+                .as_ref()
+                .filter(|meta| !meta.slices.is_empty())
+                .map(ChartData::Pie),
+            DiagramType::XyChart => self
+                .xy_chart_meta
+                .as_ref()
+                .filter(|meta| !meta.series.is_empty())
+                .map(ChartData::XyChart),
+            _ => None,
+        }
+    }
+
     /// Find a node by ID, returning its index.
     #[must_use]
     pub fn find_node_index(&self, id: &str) -> Option<usize> {
@@ -4769,10 +5155,16 @@ impl MermaidDiagramIr {
         }
         self.style_defs = defs.into_values().collect();
 
-        // 2. Apply per-node styles (cascade: classDef → style directive).
+        // 2. Apply per-node styles (cascade: `classDef default` → classDef → style directive).
+        let default_class = self
+            .style_defs
+            .iter()
+            .find(|def| def.name == DEFAULT_STYLE_CLASS)
+            .map(|def| def.properties.clone())
+            .unwrap_or_default();
         for (node_idx, node) in self.nodes.iter_mut().enumerate() {
             let node_id = IrNodeId(node_idx);
-            let mut merged = BTreeMap::new();
+            let mut merged = default_class.clone();
 
             // Layer 1: classDef properties via node.classes.
             for class_name in &node.classes {
@@ -4823,6 +5215,68 @@ impl MermaidDiagramIr {
         }
     }
 
+    /// Stable identities of every edge, indexed like `edges`: the id the source gave an edge, or
+    /// its endpoints and how many earlier unnamed edges share them. Diffs and renderers key edges
+    /// by these rather than by index.
+    #[must_use]
+    pub fn edge_ids(&self) -> Vec<IrEdgeId> {
+        let mut occurrences: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+        self.edges
+            .iter()
+            .map(|edge| {
+                if let Some(user_id) = edge.user_id() {
+                    return IrEdgeId::User(user_id.to_string());
+                }
+                let endpoints = (
+                    self.endpoint_node_id(edge.from),
+                    self.endpoint_node_id(edge.to),
+                );
+                let occurrence = occurrences.entry(endpoints).or_insert(0);
+                let id = IrEdgeId::Endpoints {
+                    from: endpoints.0.to_string(),
+                    to: endpoints.1.to_string(),
+                    occurrence: *occurrence,
+                };
+                *occurrence += 1;
+                id
+            })
+            .collect()
+    }
+
+    /// Id of the node `endpoint` resolves to, or `""` when it resolves to none.
+    fn endpoint_node_id(&self, endpoint: IrEndpoint) -> &str {
+        self.resolve_endpoint_node(endpoint)
+            .and_then(|id| self.nodes.get(id.0))
+            .map_or("", |node| node.id.as_str())
+    }
+
+    /// Element ids of every edge, indexed like `edges`: [`mermaid_user_edge_element_id`] for an
+    /// edge the source named, otherwise [`mermaid_edge_element_id`]. The occurrence counter makes
+    /// them unique within the diagram, and an edge keeps its id as long as no earlier unnamed edge
+    /// between the same endpoints is added or removed.
+    #[must_use]
+    pub fn edge_element_ids(&self) -> Vec<String> {
+        let mut occurrences: BTreeMap<String, usize> = BTreeMap::new();
+        self.edges
+            .iter()
+            .map(|edge| {
+                if let Some(user_id) = edge.user_id() {
+                    return mermaid_user_edge_element_id(user_id);
+                }
+                let (from, to) = (
+                    self.endpoint_node_id(edge.from),
+                    self.endpoint_node_id(edge.to),
+                );
+                let mut fragment = String::with_capacity(from.len() + to.len() + 1);
+                write_edge_element_fragment_into(&mut fragment, from, to);
+                let occurrence = occurrences.entry(fragment).or_insert(0);
+                let id = mermaid_edge_element_id(from, to, *occurrence);
+                *occurrence += 1;
+                id
+            })
+            .collect()
+    }
+
     #[must_use]
     pub fn source_map(&self) -> MermaidSourceMap {
         let entry_capacity = self.nodes.len() + self.edges.len() + self.clusters.len();
@@ -4840,13 +5294,14 @@ impl MermaidDiagramIr {
             entries.push(MermaidSourceMapEntry {
                 kind: MermaidSourceMapKind::Node,
                 index,
-                element_id: mermaid_node_element_id(&node.id, index),
+                element_id: mermaid_node_element_id(&node.id),
                 source_id: (!node.id.is_empty()).then(|| node.id.clone()),
                 span: node.span_primary,
             });
         }
 
-        for (index, edge) in self.edges.iter().enumerate() {
+        let edge_element_ids = self.edge_element_ids();
+        for ((index, edge), element_id) in self.edges.iter().enumerate().zip(edge_element_ids) {
             if edge.span.is_unknown() {
                 continue;
             }
@@ -4854,8 +5309,8 @@ impl MermaidDiagramIr {
             entries.push(MermaidSourceMapEntry {
                 kind: MermaidSourceMapKind::Edge,
                 index,
-                element_id: mermaid_edge_element_id(index),
-                source_id: None,
+                element_id,
+                source_id: edge.user_id().map(str::to_string),
                 span: edge.span,
             });
         }
@@ -5227,47 +5682,35 @@ pub fn push_usize_decimal(out: &mut String, value: usize) {
     }
 }
 
+/// Element id of a node: `fm-node-[{fragment}-]{hash}`, where `{fragment}` is the sanitized Mermaid
+/// id and `{hash}` is the 8-hex-digit FNV-1a hash of the raw id. It depends only on the node's own id,
+/// so it survives nodes being added or reordered and successive renders can be DOM-diffed.
 #[must_use]
-pub fn mermaid_node_element_id(node_id: &str, index: usize) -> String {
-    let mut id = String::with_capacity("fm-node-".len() + node_id.len() + 21);
-    write_mermaid_node_element_id_into(&mut id, node_id, index);
+pub fn mermaid_node_element_id(node_id: &str) -> String {
+    let mut id = String::with_capacity("fm-node-".len() + node_id.len() + 9);
+    write_mermaid_node_element_id_into(&mut id, node_id);
     id
 }
 
 /// Write the (no-variant) node element id directly into `out` — **byte-identical** to
 /// [`mermaid_node_element_id`] but allocating nothing: it streams the sanitized `node_id` fragment in
 /// place (no intermediate `sanitize_render_element_fragment` String, which itself allocates twice) and
-/// writes the index decimal directly. The result is `fm-node-[{fragment}-]{index}` where `{fragment}`
-/// contains only `[a-z0-9-]`, so a caller may write it without XML-escaping (the id can never contain
-/// an escapable byte). Used on the per-node render fast path.
-pub fn write_mermaid_node_element_id_into(out: &mut String, node_id: &str, index: usize) {
+/// writes the hash digits directly. The result contains only `[a-z0-9-]`, so a caller may write it
+/// without XML-escaping (the id can never contain an escapable byte). Used on the per-node render fast
+/// path.
+pub fn write_mermaid_node_element_id_into(out: &mut String, node_id: &str) {
     out.push_str("fm-node-");
     let before = out.len();
     write_sanitized_render_element_fragment_into(out, node_id);
     if out.len() > before {
         out.push('-');
     }
-    push_usize_decimal(out, index);
+    push_element_id_hash(out, node_id);
 }
 
 #[must_use]
-pub fn mermaid_node_element_id_with_variant(
-    node_id: &str,
-    index: usize,
-    variant: Option<&str>,
-) -> String {
-    let fragment = sanitize_render_element_fragment(node_id);
-    // Build the id with direct pushes instead of `format!` (whose `format_inner` machinery is a
-    // measurable render cost across hundreds of elements). Byte-identical to the prior
-    // `fm-node-[{fragment}-]{index}` formatting.
-    let mut id = String::with_capacity("fm-node-".len() + fragment.len() + 21);
-    id.push_str("fm-node-");
-    if !fragment.is_empty() {
-        id.push_str(&fragment);
-        id.push('-');
-    }
-    push_usize_decimal(&mut id, index);
-
+pub fn mermaid_node_element_id_with_variant(node_id: &str, variant: Option<&str>) -> String {
+    let mut id = mermaid_node_element_id(node_id);
     if let Some(variant) = variant
         .map(sanitize_render_element_fragment)
         .filter(|v| !v.is_empty())
@@ -5279,14 +5722,61 @@ pub fn mermaid_node_element_id_with_variant(
     id
 }
 
+/// Append the 32-bit FNV-1a hash of `raw` as 8 lowercase hex digits. FNV is fixed by definition, so
+/// the digits are identical across platforms, builds, and runs.
+fn push_element_id_hash(out: &mut String, raw: &str) {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    let mut hash: u32 = 0x811c_9dc5;
+    for &byte in raw.as_bytes() {
+        hash ^= u32::from(byte);
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    for shift in (0..8).rev() {
+        out.push(HEX[((hash >> (shift * 4)) & 0xf) as usize] as char);
+    }
+}
+
+/// Element id of an edge: `fm-edge-[{from}-][{to}-]{occurrence}`, where `{from}`/`{to}` are the
+/// sanitized endpoint node ids and `{occurrence}` counts earlier edges with the same endpoint
+/// fragments. Use [`MermaidDiagramIr::edge_element_ids`] to get the ids of a whole diagram.
 #[must_use]
-pub fn mermaid_edge_element_id(index: usize) -> String {
-    let mut id = String::with_capacity("fm-edge-".len() + 20);
+pub fn mermaid_edge_element_id(from: &str, to: &str, occurrence: usize) -> String {
+    let mut id = String::with_capacity("fm-edge-".len() + from.len() + to.len() + 22);
     id.push_str("fm-edge-");
-    push_usize_decimal(&mut id, index);
+    write_edge_element_fragment_into(&mut id, from, to);
+    if id.len() > "fm-edge-".len() {
+        id.push('-');
+    }
+    push_usize_decimal(&mut id, occurrence);
+    id
+}
+
+/// Element id of an edge the source named (`A e1@--> B`): `fm-edge-[{id}-]{hash}`, built like
+/// [`mermaid_node_element_id`] so ids that sanitize to the same fragment stay distinct.
+#[must_use]
+pub fn mermaid_user_edge_element_id(user_id: &str) -> String {
+    let mut id = String::with_capacity("fm-edge-".len() + user_id.len() + 9);
+    id.push_str("fm-edge-");
+    let before = id.len();
+    write_sanitized_render_element_fragment_into(&mut id, user_id);
+    if id.len() > before {
+        id.push('-');
+    }
+    push_element_id_hash(&mut id, user_id);
     id
 }
 
+/// `{from}-{to}` with empty fragments (unresolved endpoints, punctuation-only ids) left out.
+fn write_edge_element_fragment_into(out: &mut String, from: &str, to: &str) {
+    let before = out.len();
+    write_sanitized_render_element_fragment_into(out, from);
+    let after_from = out.len();
+    write_sanitized_render_element_fragment_into(out, to);
+    if after_from > before && out.len() > after_from {
+        out.insert(after_from, '-');
+    }
+}
+
 #[must_use]
 pub fn mermaid_cluster_element_id(index: usize) -> String {
     let mut id = String::with_capacity("fm-cluster-".len() + 20);
@@ -5527,49 +6017,132 @@ mod tests {
                 sanitize_reference(raw),
                 "fragment sanitize diverged for {raw:?}"
             );
-            // direct node-id writer == the String-building (no-variant) builder, for several indices
-            for index in [0usize, 7, 42, 1000, 999_999] {
-                let mut written = String::new();
-                super::write_mermaid_node_element_id_into(&mut written, raw, index);
-                let built = super::mermaid_node_element_id(raw, index);
-                let with_variant_none =
-                    super::mermaid_node_element_id_with_variant(raw, index, None);
-                assert_eq!(
-                    written, built,
-                    "id writer != builder for ({raw:?}, {index})"
-                );
-                assert_eq!(
-                    written, with_variant_none,
-                    "id writer != variant(None) for ({raw:?}, {index})"
-                );
+            // direct node-id writer == the String-building (no-variant) builder
+            let mut written = String::new();
+            super::write_mermaid_node_element_id_into(&mut written, raw);
+            let built = super::mermaid_node_element_id(raw);
+            let with_variant_none = super::mermaid_node_element_id_with_variant(raw, None);
+            assert_eq!(written, built, "id writer != builder for {raw:?}");
+            assert_eq!(
+                written, with_variant_none,
+                "id writer != variant(None) for {raw:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn element_ids_survive_reordering_and_insertions() {
+        assert_eq!(super::mermaid_node_element_id("A"), "fm-node-a-c40bf6cc");
+        assert_eq!(super::mermaid_node_element_id("!!!"), "fm-node-2d53a722");
+        assert_ne!(
+            super::mermaid_node_element_id("a b"),
+            super::mermaid_node_element_id("a-b")
+        );
+
+        let graph = |ids: &[&str], edges: &[(usize, usize)]| {
+            let mut ir = MermaidDiagramIr::empty(DiagramType::Flowchart);
+            for id in ids {
+                ir.nodes.push(IrNode {
+                    id: (*id).to_string(),
+                    ..IrNode::default()
+                });
             }
+            for &(from, to) in edges {
+                ir.edges.push(IrEdge {
+                    from: IrEndpoint::Node(IrNodeId(from)),
+                    to: IrEndpoint::Node(IrNodeId(to)),
+                    ..IrEdge::default()
+                });
+            }
+            ir
+        };
+        let before = graph(&["A", "B"], &[(0, 1), (0, 1)]);
+        assert_eq!(
+            before.edge_element_ids(),
+            ["fm-edge-a-b-0", "fm-edge-a-b-1"]
+        );
+        // A new node and edge in front leave the existing ids untouched.
+        let after = graph(&["Z", "A", "B"], &[(0, 1), (1, 2), (1, 2)]);
+        assert_eq!(
+            after.edge_element_ids(),
+            ["fm-edge-z-a-0", "fm-edge-a-b-0", "fm-edge-a-b-1"]
+        );
+    }
+
+    #[test]
+    fn edge_ids_prefer_the_user_id_and_count_only_unnamed_edges() {
+        let mut ir = MermaidDiagramIr::empty(DiagramType::Flowchart);
+        for id in ["A", "B"] {
+            ir.nodes.push(IrNode {
+                id: id.to_string(),
+                ..IrNode::default()
+            });
         }
+        for user_id in [Some("e1"), None, None] {
+            let mut edge = IrEdge {
+                from: IrEndpoint::Node(IrNodeId(0)),
+                to: IrEndpoint::Node(IrNodeId(1)),
+                span: sample_span(2, 1, 10),
+                ..IrEdge::default()
+            };
+            if let Some(user_id) = user_id {
+                edge.extras_mut().user_id = Some(Box::from(user_id));
+            }
+            ir.edges.push(edge);
+        }
+
+        let ids = ir.edge_ids();
+        assert_eq!(ids[0], IrEdgeId::User("e1".to_string()));
+        assert_eq!(
+            ids[2],
+            IrEdgeId::Endpoints {
+                from: "A".to_string(),
+                to: "B".to_string(),
+                occurrence: 1,
+            }
+        );
+        let shown: Vec<String> = ids.iter().map(ToString::to_string).collect();
+        assert_eq!(shown, ["e1", "A->B", "A->B#1"]);
+
+        let element_ids = ir.edge_element_ids();
+        assert_eq!(element_ids[0], super::mermaid_user_edge_element_id("e1"));
+        assert!(element_ids[0].starts_with("fm-edge-e1-"));
+        assert_eq!(element_ids[1..], ["fm-edge-a-b-0", "fm-edge-a-b-1"]);
+        let source_map = ir.source_map();
+        let edge_entry = source_map
+            .entries
+            .iter()
+            .find(|entry| entry.kind == MermaidSourceMapKind::Edge)
+            .unwrap();
+        assert_eq!(edge_entry.source_id.as_deref(), Some("e1"));
     }
 
     use std::borrow::Cow;
     use std::collections::BTreeMap;
 
     use super::{
-        ALLOWED_STYLE_PROPERTIES_REFERENCE, ArrowType, DegradationContext, DegradationOperator,
-        Diagnostic, DiagnosticCategory, DiagnosticSeverity, DiagramPalettePreset, DiagramType,
-        EdgeMap, FragmentAlternative, FragmentKind, GanttDate, GanttExclude, GanttTaskType,
-        GanttTickInterval, GraphDirection, IrActivation, IrAttributeKey, IrCluster, IrClusterId,
-        IrEdge, IrEdgeKind, IrEndpoint, IrEntityAttribute, IrGanttMeta, IrGanttSection,
-        IrGanttTask, IrGraphCluster, IrGraphEdge, IrGraphNode, IrInlineStyle, IrLabel, IrLabelId,
-        IrLifecycleEvent, IrNode, IrNodeId, IrNodeKind, IrParticipantGroup, IrPort, IrPortId,
-        IrPortSideHint, IrSequenceFragment, IrSequenceMeta, IrSequenceNote, IrStyleDef, IrStyleRef,
-        IrStyleTarget, IrSubgraph, IrSubgraphId, IrXyAxis, IrXyChartMeta, IrXySeries,
-        IrXySeriesKind, LifecycleEventKind, MERMAID_SCHEMA_VERSION, MermaidBudgetLedger,
-        MermaidConfig, MermaidDecisionWeight, MermaidDegradationPlan, MermaidDiagramIr,
-        MermaidError, MermaidErrorCode, MermaidFallbackAction, MermaidFallbackPolicy,
-        MermaidFidelity, MermaidGlyphMode, MermaidGuardReport, MermaidLayoutDecisionAlternative,
-        MermaidLayoutDecisionLedger, MermaidLayoutDecisionRecord, MermaidLensBinding,
-        MermaidLensEdit, MermaidLensEditResult, MermaidLensError, MermaidNativePressureSignals,
-        MermaidPressureReport, MermaidPressureTier, MermaidQualityMode, MermaidSanitizeMode,
-        MermaidSourceMap, MermaidSourceMapEntry, MermaidSourceMapKind, MermaidSupportLevel,
-        MermaidTextRange, MermaidWarningCode, MermaidWasmPressureSignals, NodeMap, NodeSet,
-        NodeShape, NotePosition, Position, Span, StructuredDiagnostic, apply_lens_edit,
-        build_lens_bindings, capability_matrix, capability_matrix_json_pretty,
+        ALLOWED_STYLE_PROPERTIES_REFERENCE, ArrowType, ChartData, DEFAULT_STYLE_CLASS,
+        DegradationContext, DegradationOperator, Diagnostic, DiagnosticCategory,
+        DiagnosticSeverity, DiagramPalettePreset, DiagramType, EdgeMap, FragmentAlternative,
+        FragmentKind, GanttDate, GanttExclude, GanttTaskType, GanttTickInterval, GraphDirection,
+        IrActivation, IrAttributeKey, IrAutonumberChange, IrCluster, IrClusterId, IrEdge, IrEdgeId,
+        IrEdgeKind, IrEndpoint, IrEntityAttribute, IrGanttMeta, IrGanttSection, IrGanttTask,
+        IrGraphCluster, IrGraphEdge, IrGraphNode, IrInlineStyle, IrLabel, IrLabelId,
+        IrLifecycleEvent, IrNode, IrNodeId, IrNodeKind, IrParticipantGroup, IrPieMeta, IrPieSlice,
+        IrPort, IrPortId, IrPortSideHint, IrSequenceFragment, IrSequenceMeta, IrSequenceNote,
+        IrStyleDef, IrStyleRef, IrStyleTarget, IrSubgraph, IrSubgraphId, IrXyAxis, IrXyChartMeta,
+        IrXySeries, IrXySeriesKind, LifecycleEventKind, MERMAID_SCHEMA_VERSION,
+        MermaidBudgetLedger, MermaidConfig, MermaidDecisionWeight, MermaidDegradationPlan,
+        MermaidDiagramIr, MermaidError, MermaidErrorCode, MermaidFallbackAction,
+        MermaidFallbackPolicy, MermaidFidelity, MermaidGlyphMode, MermaidGuardReport,
+        MermaidLayoutDecisionAlternative, MermaidLayoutDecisionLedger, MermaidLayoutDecisionRecord,
+        MermaidLensBinding, MermaidLensEdit, MermaidLensEditResult, MermaidLensError,
+        MermaidNativePressureSignals, MermaidPressureReport, MermaidPressureTier,
+        MermaidQualityMode, MermaidSanitizeMode, MermaidSourceMap, MermaidSourceMapEntry,
+        MermaidSourceMapKind, MermaidSupportLevel, MermaidTextRange, MermaidWarningCode,
+        MermaidWasmPressureSignals, NodeMap, NodeSet, NodeShape, NotePosition, Position, Span,
+        StructuredDiagnostic, apply_lens_edit, build_lens_bindings, callback_function_name,
+        capability_matrix, capability_matrix_json_pretty,
         capability_readme_supported_diagram_types_markdown, capability_readme_surface_markdown,
         documented_diagram_types, is_allowed_style_property, is_safe_link_target,
         mermaid_layout_guard_observability, parse_mermaid_js_config_value, parse_style_string,
@@ -5781,7 +6354,7 @@ mod tests {
             "theme": "dark",
             "themeVariables": {
                 "primaryColor": "#ffffff",
-                "lineColor": 12
+                "fontSize": 12
             },
             "flowchart": {
                 "direction": "RL",
@@ -5812,7 +6385,7 @@ mod tests {
             parsed
                 .config
                 .theme_variables
-                .get("lineColor")
+                .get("fontSize")
                 .map(String::as_str),
             Some("12")
         );
@@ -5855,6 +6428,41 @@ mod tests {
         );
     }
 
+    #[test]
+    fn mermaid_js_config_rejects_invalid_theme_variable_values() {
+        let parsed = parse_mermaid_js_config_value(&json!({
+            "themeVariables": {
+                "primaryColor": "bluee",
+                "lineColor": "Red",
+                "fontSize": "-3px",
+                "pieOpacity": 2
+            }
+        }));
+
+        let fields: Vec<&str> = parsed.errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec![
+                "themeVariables.fontSize",
+                "themeVariables.pieOpacity",
+                "themeVariables.primaryColor"
+            ]
+        );
+        assert!(parsed.errors[2].message.contains("not a valid color"));
+        assert_eq!(
+            parsed.config.theme_variables.keys().collect::<Vec<_>>(),
+            vec!["lineColor"]
+        );
+        assert_eq!(
+            parsed
+                .config
+                .theme_variables
+                .get("lineColor")
+                .map(String::as_str),
+            Some("red")
+        );
+    }
+
     #[test]
     fn mermaid_js_config_can_be_projected_to_init_parse() {
         let parsed = parse_mermaid_js_config_value(&json!({
@@ -8530,6 +9138,7 @@ mod tests {
             span: sample_span(2, 1, 6),
             extras: None,
             inline_style: None,
+            weight: None,
         });
 
         let encoded = serde_json::to_string(&ir).expect("serialize ir");
@@ -8590,6 +9199,7 @@ mod tests {
             span: sample_span(6, 1, 9),
             extras: None,
             inline_style: None,
+            weight: None,
         };
 
         assert_eq!(edge.from, edge.to);
@@ -9356,6 +9966,12 @@ mod tests {
                 participant: IrNodeId(3),
                 at_edge: 5,
             }],
+            autonumber_changes: vec![IrAutonumberChange {
+                edge_index: 2,
+                start: None,
+                increment: None,
+                enabled: false,
+            }],
         };
 
         let json = serde_json::to_string(&meta).expect("serialize");
@@ -9417,6 +10033,32 @@ mod tests {
         assert_eq!(meta.autonumber_value(2), Some(20));
     }
 
+    #[test]
+    fn sequence_meta_autonumber_changes_turn_numbering_on_off_and_restart() {
+        let change = |edge_index, start, increment, enabled| IrAutonumberChange {
+            edge_index,
+            start,
+            increment,
+            enabled,
+        };
+        let meta = IrSequenceMeta {
+            autonumber: true,
+            autonumber_changes: vec![
+                change(1, None, None, true),
+                change(3, None, None, false),
+                change(4, None, None, true),
+                change(5, Some(10), Some(5), true),
+            ],
+            ..Default::default()
+        };
+
+        let numbers: Vec<_> = (0..7).map(|edge| meta.autonumber_value(edge)).collect();
+        assert_eq!(
+            numbers,
+            vec![None, Some(2), Some(3), None, Some(5), Some(10), Some(15)]
+        );
+    }
+
     #[test]
     fn ir_without_sequence_meta_omits_field() {
         let ir = MermaidDiagramIr::empty(DiagramType::Flowchart);
@@ -9514,6 +10156,39 @@ mod tests {
         assert_eq!(meta, deser);
     }
 
+    #[test]
+    fn chart_data_borrows_populated_chart_metadata() {
+        let mut pie = MermaidDiagramIr::empty(DiagramType::Pie);
+        pie.pie_meta = Some(IrPieMeta::default());
+        assert_eq!(pie.chart_data(), None);
+        pie.pie_meta = Some(IrPieMeta {
+            slices: vec![IrPieSlice {
+                label: "Dogs".to_string(),
+                value: 386.0,
+            }],
+            ..IrPieMeta::default()
+        });
+        assert!(matches!(
+            pie.chart_data(),
+            Some(ChartData::Pie(meta)) if meta.slices[0].value == 386.0
+        ));
+
+        let mut xy = MermaidDiagramIr::empty(DiagramType::XyChart);
+        xy.xy_chart_meta = Some(IrXyChartMeta {
+            series: vec![IrXySeries {
+                values: vec![1.0, 2.0],
+                ..IrXySeries::default()
+            }],
+            ..IrXyChartMeta::default()
+        });
+        assert!(matches!(
+            xy.chart_data(),
+            Some(ChartData::XyChart(meta)) if meta.series[0].values == [1.0, 2.0]
+        ));
+        xy.diagram_type = DiagramType::Flowchart;
+        assert_eq!(xy.chart_data(), None);
+    }
+
     #[test]
     fn ir_with_xy_chart_meta_round_trip() {
         let mut ir = MermaidDiagramIr::empty(DiagramType::XyChart);
@@ -10296,6 +10971,35 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn callback_function_name_keeps_only_identifier_paths() {
+        assert_eq!(callback_function_name("focusNode"), Some("focusNode"));
+        assert_eq!(
+            callback_function_name(" app.open_node "),
+            Some("app.open_node")
+        );
+        assert_eq!(
+            callback_function_name("callback(\"A\", 2)"),
+            Some("callback")
+        );
+        assert_eq!(callback_function_name("$handler()"), Some("$handler"));
+        for unsafe_callback in [
+            "",
+            "alert(1);steal()",
+            "fn(",
+            "1st",
+            "a..b",
+            "javascript:alert(1)",
+            "x\"><script>",
+        ] {
+            assert_eq!(
+                callback_function_name(unsafe_callback),
+                None,
+                "{unsafe_callback:?}"
+            );
+        }
+    }
+
     #[test]
     fn safe_link_target_lenient_allows_anything() {
         assert!(is_safe_link_target(
@@ -10486,6 +11190,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn ir_inline_style_reads_pixel_lengths() {
+        let style = parse_style_string("stroke-width:4px,stroke:#f00,opacity:0.5,rx:3");
+        assert_eq!(style.length_px("stroke-width"), Some(4.0));
+        assert_eq!(style.length_px("rx"), Some(3.0));
+        assert_eq!(style.length_px("stroke"), None);
+        assert_eq!(style.length_px("font-size"), None);
+        assert_eq!(
+            parse_style_string("stroke-width:2em").length_px("stroke-width"),
+            None
+        );
+    }
+
     #[test]
     fn ir_style_def_serde_roundtrip() {
         let def = IrStyleDef {
@@ -10562,6 +11279,40 @@ mod tests {
         assert_eq!(node_style.properties.get("fill").unwrap(), "#000");
     }
 
+    #[test]
+    fn populate_structured_styles_default_class_styles_every_node_first() {
+        let mut ir = MermaidDiagramIr::empty(DiagramType::Flowchart);
+        for (id, classes) in [("A", vec![]), ("B", vec!["hot".to_string()])] {
+            ir.nodes.push(IrNode {
+                id: id.to_string(),
+                classes,
+                ..Default::default()
+            });
+        }
+        for (name, style) in [
+            (DEFAULT_STYLE_CLASS, "fill:#eee,stroke:#333"),
+            ("hot", "fill:#f96"),
+        ] {
+            ir.style_refs.push(IrStyleRef {
+                target: IrStyleTarget::Class(name.to_string()),
+                style: style.to_string(),
+                span: Span::default(),
+            });
+        }
+
+        ir.populate_structured_styles();
+
+        let property = |node: usize, key: &str| {
+            ir.nodes[node]
+                .inline_style
+                .as_ref()
+                .and_then(|style| style.properties.get(key).cloned())
+        };
+        assert_eq!(property(0, "fill").as_deref(), Some("#eee"));
+        assert_eq!(property(1, "fill").as_deref(), Some("#f96"));
+        assert_eq!(property(1, "stroke").as_deref(), Some("#333"));
+    }
+
     #[test]
     fn populate_structured_styles_link_default_and_specific() {
         let mut ir = MermaidDiagramIr::empty(DiagramType::Flowchart);
@@ -0,0 +1,248 @@
+//! The markdown subset Mermaid allows in labels.
+//!
+//! [`parse_label_markdown`] splits a label into [`IrLabelSegment`]s: `**bold**`, `*italic*`,
+//! `~~strikethrough~~` and `` `code` `` spans, with each newline a line break. Delimiters that
+//! cannot be matched are kept as text instead of styling the rest of the label: a span only
+//! opens when its closer follows, emphasis has to hug its text (`2 * 3 * 4` stays literal), and
+//! `\*`, `\~` and `` \` `` write the character itself.
+
+use crate::IrLabelSegment;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Delimiter {
+    Bold,
+    Italic,
+    Strike,
+    Code,
+}
+
+impl Delimiter {
+    const fn marker(self) -> &'static str {
+        match self {
+            Self::Bold => "**",
+            Self::Italic => "*",
+            Self::Strike => "~~",
+            Self::Code => "`",
+        }
+    }
+
+    const fn len(self) -> usize {
+        self.marker().len()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Style {
+    bold: bool,
+    italic: bool,
+    strike: bool,
+    code: bool,
+}
+
+impl Style {
+    fn flag(&mut self, delimiter: Delimiter) -> &mut bool {
+        match delimiter {
+            Delimiter::Bold => &mut self.bold,
+            Delimiter::Italic => &mut self.italic,
+            Delimiter::Strike => &mut self.strike,
+            Delimiter::Code => &mut self.code,
+        }
+    }
+}
+
+/// Split `text` into styled segments. Text without any markup comes back as a single plain
+/// segment (or none for empty text), so [`label_segments_text`] always round-trips the visible
+/// text.
+#[must_use]
+pub fn parse_label_markdown(text: &str) -> Vec<IrLabelSegment> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut segments = Vec::new();
+    let mut buffer = String::new();
+    let mut style = Style::default();
+    let mut idx = 0;
+
+    while idx < chars.len() {
+        let ch = chars[idx];
+        if ch == '\n' {
+            flush(&mut segments, &mut buffer, style);
+            segments.push(IrLabelSegment::LineBreak);
+            idx += 1;
+            continue;
+        }
+        if !style.code
+            && let Some(escaped) = escaped_at(&chars, idx)
+        {
+            buffer.push(escaped);
+            idx += 2;
+            continue;
+        }
+        let Some(delimiter) = delimiter_at(&chars, idx, style.code) else {
+            buffer.push(ch);
+            idx += 1;
+            continue;
+        };
+        let toggles = if *style.flag(delimiter) {
+            closes_at(&chars, idx, delimiter)
+        } else {
+            opens_at(&chars, idx, delimiter) && has_closer(&chars, idx + delimiter.len(), delimiter)
+        };
+        if toggles {
+            flush(&mut segments, &mut buffer, style);
+            let flag = style.flag(delimiter);
+            *flag = !*flag;
+        } else {
+            buffer.push_str(delimiter.marker());
+        }
+        idx += delimiter.len();
+    }
+
+    flush(&mut segments, &mut buffer, style);
+    segments
+}
+
+/// The visible text of `segments`, with line breaks as `\n`.
+#[must_use]
+pub fn label_segments_text(segments: &[IrLabelSegment]) -> String {
+    let mut text = String::new();
+    for segment in segments {
+        match segment {
+            IrLabelSegment::Text { text: value, .. } => text.push_str(value),
+            IrLabelSegment::LineBreak => text.push('\n'),
+        }
+    }
+    text
+}
+
+fn flush(segments: &mut Vec<IrLabelSegment>, buffer: &mut String, style: Style) {
+    if !buffer.is_empty() {
+        segments.push(IrLabelSegment::Text {
+            text: std::mem::take(buffer),
+            bold: style.bold,
+            italic: style.italic,
+            code: style.code,
+            strike: style.strike,
+        });
+    }
+}
+
+/// The markup character a backslash at `idx` escapes.
+fn escaped_at(chars: &[char], idx: usize) -> Option<char> {
+    if chars[idx] != '\\' {
+        return None;
+    }
+    chars
+        .get(idx + 1)
+        .copied()
+        .filter(|next| matches!(next, '*' | '~' | '`'))
+}
+
+fn delimiter_at(chars: &[char], idx: usize, in_code: bool) -> Option<Delimiter> {
+    let next = chars.get(idx + 1).copied();
+    match chars[idx] {
+        '`' => Some(Delimiter::Code),
+        _ if in_code => None,
+        '*' if next == Some('*') => Some(Delimiter::Bold),
+        '*' => Some(Delimiter::Italic),
+        '~' if next == Some('~') => Some(Delimiter::Strike),
+        _ => None,
+    }
+}
+
+/// An opener must be followed by text, not whitespace; code spans open anywhere.
+fn opens_at(chars: &[char], idx: usize, delimiter: Delimiter) -> bool {
+    delimiter == Delimiter::Code
+        || chars
+            .get(idx + delimiter.len())
+            .is_some_and(|next| !next.is_whitespace())
+}
+
+/// A closer must follow text, not whitespace; code spans close anywhere.
+fn closes_at(chars: &[char], idx: usize, delimiter: Delimiter) -> bool {
+    delimiter == Delimiter::Code
+        || idx
+            .checked_sub(1)
+            .is_some_and(|prev| !chars[prev].is_whitespace())
+}
+
+/// Whether a closer for `delimiter` follows `from` with at least one character in between.
+fn has_closer(chars: &[char], from: usize, delimiter: Delimiter) -> bool {
+    let mut idx = from;
+    while idx < chars.len() {
+        if delimiter != Delimiter::Code && escaped_at(chars, idx).is_some() {
+            idx += 2;
+            continue;
+        }
+        match delimiter_at(chars, idx, delimiter == Delimiter::Code) {
+            Some(found) if found == delimiter && idx > from && closes_at(chars, idx, found) => {
+                return true;
+            }
+            // `**` inside an italic span is a bold pair, never the italic closer.
+            Some(found) => idx += found.len(),
+            None => idx += 1,
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(value: &str, bold: bool, italic: bool) -> IrLabelSegment {
+        IrLabelSegment::Text {
+            text: value.to_string(),
+            bold,
+            italic,
+            code: false,
+            strike: false,
+        }
+    }
+
+    #[test]
+    fn parses_emphasis_and_line_breaks() {
+        assert_eq!(
+            parse_label_markdown("**Bold** and *it*\nnext"),
+            vec![
+                text("Bold", true, false),
+                text(" and ", false, false),
+                text("it", false, true),
+                IrLabelSegment::LineBreak,
+                text("next", false, false),
+            ]
+        );
+        assert_eq!(
+            parse_label_markdown("**a *b* c**"),
+            vec![
+                text("a ", true, false),
+                text("b", true, true),
+                text(" c", true, false),
+            ]
+        );
+        assert!(matches!(
+            parse_label_markdown("`*x*` ~~gone~~").as_slice(),
+            [
+                IrLabelSegment::Text { text: code_text, code: true, italic: false, .. },
+                IrLabelSegment::Text { .. },
+                IrLabelSegment::Text { text: gone, strike: true, .. },
+            ] if code_text == "*x*" && gone == "gone"
+        ));
+    }
+
+    #[test]
+    fn unmatched_or_spaced_delimiters_stay_literal() {
+        for literal in ["2 * 3 * 4", "**open", "a*", "~single~", "** x **", "**"] {
+            assert_eq!(
+                parse_label_markdown(literal),
+                vec![text(literal, false, false)],
+                "{literal:?}"
+            );
+        }
+        assert_eq!(
+            parse_label_markdown(r"\*not italic\*"),
+            vec![text("*not italic*", false, false)]
+        );
+        assert_eq!(parse_label_markdown(""), Vec::new());
+        let segments = parse_label_markdown("**Bold**\n*x*");
+        assert_eq!(label_segments_text(&segments), "Bold\nx");
+    }
+}
@@ -0,0 +1,185 @@
+//! Plug-in registry for custom node shapes.
+//!
+//! Renderers consult a [`NodeShapeRegistry`] before falling back to their built-in shape
+//! geometry, so downstream crates can add shapes (or restyle built-in ones) without forking the
+//! per-renderer `match` on [`NodeShape`]. A node picks a registered drawer through
+//! [`IrNode::custom_shape`]; otherwise the built-in shape name ([`NodeShape::as_str`]) is looked
+//! up, which lets a registration override an existing shape.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::panic::RefUnwindSafe;
+use std::sync::Arc;
+
+use crate::{IrNode, NodeShape};
+
+/// Draws one custom node shape for the SVG and terminal renderers.
+///
+/// Drawers must be unwind-safe so render configs holding them can cross `catch_unwind`.
+pub trait NodeShapeDrawer: Send + Sync + RefUnwindSafe {
+    /// SVG path data (`d` attribute) outlining the shape inside the given box.
+    fn svg_path(&self, x: f32, y: f32, width: f32, height: f32) -> String;
+
+    /// Border glyphs for a `width` x `height` cell box, as `(dx, dy, glyph)` offsets from the
+    /// box's top-left cell. `None` keeps the terminal renderer's plain rectangle.
+    fn terminal_border(&self, width: usize, height: usize) -> Option<Vec<(usize, usize, char)>> {
+        let _ = (width, height);
+        None
+    }
+}
+
+/// Shape drawers keyed by shape name. Cloning is cheap: drawers are shared behind `Arc`.
+#[derive(Clone, Default)]
+pub struct NodeShapeRegistry {
+    drawers: BTreeMap<String, Arc<dyn NodeShapeDrawer>>,
+}
+
+impl NodeShapeRegistry {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `drawer` under `name` (case-insensitive), returning any drawer it replaces.
+    pub fn register(
+        &mut self,
+        name: &str,
+        drawer: Arc<dyn NodeShapeDrawer>,
+    ) -> Option<Arc<dyn NodeShapeDrawer>> {
+        self.drawers.insert(normalize_shape_name(name), drawer)
+    }
+
+    /// Builder-style [`Self::register`].
+    #[must_use]
+    pub fn with(mut self, name: &str, drawer: Arc<dyn NodeShapeDrawer>) -> Self {
+        self.register(name, drawer);
+        self
+    }
+
+    /// Remove the drawer registered under `name`.
+    pub fn unregister(&mut self, name: &str) -> Option<Arc<dyn NodeShapeDrawer>> {
+        self.drawers.remove(&normalize_shape_name(name))
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&dyn NodeShapeDrawer> {
+        if self.drawers.is_empty() {
+            return None;
+        }
+        self.drawers
+            .get(&normalize_shape_name(name))
+            .map(AsRef::as_ref)
+    }
+
+    /// Drawer for `node`: its [`IrNode::custom_shape`] if registered, else a drawer registered
+    /// under the built-in shape's name.
+    #[must_use]
+    pub fn resolve(&self, node: &IrNode) -> Option<&dyn NodeShapeDrawer> {
+        if self.drawers.is_empty() {
+            return None;
+        }
+        node.custom_shape
+            .as_deref()
+            .and_then(|name| self.get(name))
+            .or_else(|| self.get(node.shape.as_str()))
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.drawers.is_empty()
+    }
+
+    /// Registered shape names in sorted order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.drawers.keys().map(String::as_str)
+    }
+}
+
+impl fmt::Debug for NodeShapeRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.names()).finish()
+    }
+}
+
+fn normalize_shape_name(name: &str) -> String {
+    name.trim().to_ascii_lowercase()
+}
+
+impl NodeShape {
+    /// Stable kebab-case shape name, as used for registry lookups and CSS classes.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Rect => "rect",
+            Self::Rounded => "rounded",
+            Self::Stadium => "stadium",
+            Self::Subroutine => "subroutine",
+            Self::Diamond => "diamond",
+            Self::Hexagon => "hexagon",
+            Self::Circle => "circle",
+            Self::FilledCircle => "filled-circle",
+            Self::Asymmetric => "asymmetric",
+            Self::Cylinder => "cylinder",
+            Self::Trapezoid => "trapezoid",
+            Self::DoubleCircle => "double-circle",
+            Self::HorizontalBar => "horizontal-bar",
+            Self::Note => "note",
+            Self::InvTrapezoid => "inv-trapezoid",
+            Self::Parallelogram => "parallelogram",
+            Self::InvParallelogram => "inv-parallelogram",
+            Self::Triangle => "triangle",
+            Self::Pentagon => "pentagon",
+            Self::Star => "star",
+            Self::Cloud => "cloud",
+            Self::Tag => "tag",
+            Self::CrossedCircle => "crossed-circle",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Bolt;
+
+    impl NodeShapeDrawer for Bolt {
+        fn svg_path(&self, x: f32, y: f32, width: f32, height: f32) -> String {
+            format!("M{x},{y} L{},{} Z", x + width, y + height)
+        }
+    }
+
+    #[test]
+    fn resolve_prefers_custom_shape_then_builtin_name() {
+        let registry = NodeShapeRegistry::new()
+            .with("Bolt", Arc::new(Bolt))
+            .with("hexagon", Arc::new(Bolt));
+
+        let mut node = IrNode {
+            custom_shape: Some(String::from("bolt")),
+            ..IrNode::default()
+        };
+        assert!(registry.resolve(&node).is_some());
+
+        node.custom_shape = Some(String::from("unknown"));
+        assert!(registry.resolve(&node).is_none());
+
+        node.shape = NodeShape::Hexagon;
+        assert!(registry.resolve(&node).is_some());
+        assert_eq!(
+            registry.names().collect::<Vec<_>>(),
+            vec!["bolt", "hexagon"]
+        );
+        assert_eq!(format!("{registry:?}"), r#"{"bolt", "hexagon"}"#);
+    }
+
+    #[test]
+    fn default_terminal_border_is_none() {
+        assert!(Bolt.terminal_border(5, 3).is_none());
+        let mut registry = NodeShapeRegistry::new();
+        assert!(registry.register("bolt", Arc::new(Bolt)).is_none());
+        assert!(registry.register("BOLT", Arc::new(Bolt)).is_some());
+        assert!(registry.unregister("bolt").is_some());
+        assert!(registry.is_empty());
+    }
+}
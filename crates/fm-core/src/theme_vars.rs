@@ -0,0 +1,455 @@
+//! Validation for Mermaid `themeVariables` values.
+//!
+//! Theme variables arrive as free-form strings from init directives, front matter, and config
+//! files and end up inside CSS custom properties. Each key is classified by naming convention
+//! (`*Color`/`*Bkg`/`*Border` are colors, `*Size`/`*Width` are lengths, and so on) and the value
+//! is checked against that kind, so a typo such as `primaryColor: bluee` is reported and dropped
+//! instead of producing a broken stylesheet.
+
+/// Largest accepted length value (in the value's own unit) for size-like theme variables.
+const MAX_THEME_LENGTH: f64 = 512.0;
+
+/// The kind of value a theme variable is expected to hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThemeVariableKind {
+    /// A CSS color (`#rgb`, `#rrggbb`, `rgb(...)`, `hsl(...)`, or a named color).
+    Color,
+    /// A non-negative length such as `16px`, `1.2em`, or a bare number.
+    Length,
+    /// An opacity in `[0, 1]`.
+    Opacity,
+    /// `true` or `false`.
+    Boolean,
+    /// A CSS font-family list.
+    FontFamily,
+    /// Anything else; only checked for characters that would break out of a CSS declaration.
+    Text,
+}
+
+impl ThemeVariableKind {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Color => "color",
+            Self::Length => "length",
+            Self::Opacity => "opacity",
+            Self::Boolean => "boolean",
+            Self::FontFamily => "font-family",
+            Self::Text => "text",
+        }
+    }
+}
+
+/// Classify a Mermaid theme variable by its key.
+#[must_use]
+pub fn theme_variable_kind(key: &str) -> ThemeVariableKind {
+    let lower = key.to_ascii_lowercase();
+    if lower == "fontfamily" {
+        return ThemeVariableKind::FontFamily;
+    }
+    if lower == "darkmode" {
+        return ThemeVariableKind::Boolean;
+    }
+    if lower.ends_with("opacity") {
+        return ThemeVariableKind::Opacity;
+    }
+    if lower.ends_with("size") || lower.ends_with("width") {
+        return ThemeVariableKind::Length;
+    }
+    let indexed_color = [
+        "pie",
+        "cscale",
+        "cscalelabel",
+        "cscalepeer",
+        "git",
+        "filltype",
+    ]
+    .iter()
+    .any(|prefix| {
+        lower
+            .strip_prefix(prefix)
+            .is_some_and(|rest| !rest.is_empty() && rest.bytes().all(|b| b.is_ascii_digit()))
+    });
+    if indexed_color
+        || ["color", "bkg", "background", "border", "fill", "stroke"]
+            .iter()
+            .any(|suffix| lower.ends_with(suffix))
+    {
+        return ThemeVariableKind::Color;
+    }
+    ThemeVariableKind::Text
+}
+
+/// Validate a theme variable value against the kind implied by its key.
+///
+/// Returns the normalized value on success (trimmed; named colors lowercased), or a
+/// human-readable reason suitable for a `MermaidConfigError` message.
+pub fn validate_theme_variable(key: &str, value: &str) -> Result<String, String> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return Err("must not be empty".to_string());
+    }
+    let kind = theme_variable_kind(key);
+    match kind {
+        ThemeVariableKind::Color => {
+            if is_valid_css_color(trimmed) {
+                if is_css_named_color(trimmed) {
+                    Ok(trimmed.to_ascii_lowercase())
+                } else {
+                    Ok(trimmed.to_string())
+                }
+            } else {
+                Err(format!(
+                    "'{trimmed}' is not a valid color (expected #hex, rgb()/hsl(), or a CSS color name)"
+                ))
+            }
+        }
+        ThemeVariableKind::Length => match parse_css_length(trimmed) {
+            Some(length) if (0.0..=MAX_THEME_LENGTH).contains(&length) => Ok(trimmed.to_string()),
+            Some(_) => Err(format!(
+                "'{trimmed}' is out of range (expected 0 to {MAX_THEME_LENGTH})"
+            )),
+            None => Err(format!(
+                "'{trimmed}' is not a valid length (expected a number with optional px/em/rem/pt/% unit)"
+            )),
+        },
+        ThemeVariableKind::Opacity => match trimmed.parse::<f64>() {
+            Ok(opacity) if (0.0..=1.0).contains(&opacity) => Ok(trimmed.to_string()),
+            Ok(_) => Err(format!("'{trimmed}' is out of range (expected 0 to 1)")),
+            Err(_) => Err(format!("'{trimmed}' is not a number")),
+        },
+        ThemeVariableKind::Boolean => match trimmed {
+            "true" | "false" => Ok(trimmed.to_string()),
+            _ => Err(format!("'{trimmed}' must be true or false")),
+        },
+        ThemeVariableKind::FontFamily | ThemeVariableKind::Text => {
+            if trimmed
+                .chars()
+                .any(|ch| matches!(ch, ';' | '{' | '}' | '<' | '>' | '\\') || ch.is_control())
+            {
+                Err(format!(
+                    "'{trimmed}' contains characters that are not allowed in a {} value",
+                    kind.as_str()
+                ))
+            } else {
+                Ok(trimmed.to_string())
+            }
+        }
+    }
+}
+
+/// Whether `value` is a well-formed CSS color: a named color, `#rgb[a]`/`#rrggbb[aa]`, or an
+/// `rgb[a]()`/`hsl[a]()` function with in-range components.
+#[must_use]
+pub fn is_valid_css_color(value: &str) -> bool {
+    let value = value.trim();
+    if is_css_named_color(value) || value.eq_ignore_ascii_case("currentcolor") {
+        return true;
+    }
+    if let Some(hex) = value.strip_prefix('#') {
+        return matches!(hex.len(), 3 | 4 | 6 | 8) && hex.bytes().all(|b| b.is_ascii_hexdigit());
+    }
+    let lower = value.to_ascii_lowercase();
+    let Some(open) = lower.find('(') else {
+        return false;
+    };
+    let Some(args) = lower[open + 1..].strip_suffix(')') else {
+        return false;
+    };
+    let function = &lower[..open];
+    let parts: Vec<&str> = args
+        .split([',', '/', ' '])
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect();
+    if !matches!(parts.len(), 3 | 4) {
+        return false;
+    }
+    let alpha_ok = parts.get(3).is_none_or(|alpha| {
+        parse_percent_or_number(alpha).is_some_and(|(number, percent)| {
+            if percent {
+                (0.0..=100.0).contains(&number)
+            } else {
+                (0.0..=1.0).contains(&number)
+            }
+        })
+    });
+    if !alpha_ok {
+        return false;
+    }
+    match function {
+        "rgb" | "rgba" => parts[..3].iter().all(|part| {
+            parse_percent_or_number(part).is_some_and(|(number, percent)| {
+                if percent {
+                    (0.0..=100.0).contains(&number)
+                } else {
+                    (0.0..=255.0).contains(&number)
+                }
+            })
+        }),
+        "hsl" | "hsla" => {
+            let hue = parts[0].trim_end_matches("deg").parse::<f64>();
+            hue.is_ok_and(f64::is_finite)
+                && parts[1..3].iter().all(|part| {
+                    parse_percent_or_number(part)
+                        .is_some_and(|(number, _)| (0.0..=100.0).contains(&number))
+                })
+        }
+        _ => false,
+    }
+}
+
+fn parse_percent_or_number(text: &str) -> Option<(f64, bool)> {
+    let (number, percent) = match text.strip_suffix('%') {
+        Some(number) => (number, true),
+        None => (text, false),
+    };
+    number
+        .parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite())
+        .map(|value| (value, percent))
+}
+
+fn parse_css_length(text: &str) -> Option<f64> {
+    // `rem` first: stripping `em` from it would leave an unparseable `1.5r`.
+    let number = ["px", "rem", "em", "pt", "%"]
+        .iter()
+        .find_map(|unit| text.strip_suffix(unit))
+        .unwrap_or(text);
+    number
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite())
+}
+
+/// Whether `value` is one of the CSS named colors (case-insensitive).
+#[must_use]
+pub fn is_css_named_color(value: &str) -> bool {
+    matches!(
+        value.to_ascii_lowercase().as_str(),
+        "aliceblue"
+            | "antiquewhite"
+            | "aqua"
+            | "aquamarine"
+            | "azure"
+            | "beige"
+            | "bisque"
+            | "black"
+            | "blanchedalmond"
+            | "blue"
+            | "blueviolet"
+            | "brown"
+            | "burlywood"
+            | "cadetblue"
+            | "chartreuse"
+            | "chocolate"
+            | "coral"
+            | "cornflowerblue"
+            | "cornsilk"
+            | "crimson"
+            | "cyan"
+            | "darkblue"
+            | "darkcyan"
+            | "darkgoldenrod"
+            | "darkgray"
+            | "darkgreen"
+            | "darkgrey"
+            | "darkkhaki"
+            | "darkmagenta"
+            | "darkolivegreen"
+            | "darkorange"
+            | "darkorchid"
+            | "darkred"
+            | "darksalmon"
+            | "darkseagreen"
+            | "darkslateblue"
+            | "darkslategray"
+            | "darkslategrey"
+            | "darkturquoise"
+            | "darkviolet"
+            | "deeppink"
+            | "deepskyblue"
+            | "dimgray"
+            | "dimgrey"
+            | "dodgerblue"
+            | "firebrick"
+            | "floralwhite"
+            | "forestgreen"
+            | "fuchsia"
+            | "gainsboro"
+            | "ghostwhite"
+            | "gold"
+            | "goldenrod"
+            | "gray"
+            | "green"
+            | "greenyellow"
+            | "grey"
+            | "honeydew"
+            | "hotpink"
+            | "indianred"
+            | "indigo"
+            | "ivory"
+            | "khaki"
+            | "lavender"
+            | "lavenderblush"
+            | "lawngreen"
+            | "lemonchiffon"
+            | "lightblue"
+            | "lightcoral"
+            | "lightcyan"
+            | "lightgoldenrodyellow"
+            | "lightgray"
+            | "lightgreen"
+            | "lightgrey"
+            | "lightpink"
+            | "lightsalmon"
+            | "lightseagreen"
+            | "lightskyblue"
+            | "lightslategray"
+            | "lightslategrey"
+            | "lightsteelblue"
+            | "lightyellow"
+            | "lime"
+            | "limegreen"
+            | "linen"
+            | "magenta"
+            | "maroon"
+            | "mediumaquamarine"
+            | "mediumblue"
+            | "mediumorchid"
+            | "mediumpurple"
+            | "mediumseagreen"
+            | "mediumslateblue"
+            | "mediumspringgreen"
+            | "mediumturquoise"
+            | "mediumvioletred"
+            | "midnightblue"
+            | "mintcream"
+            | "mistyrose"
+            | "moccasin"
+            | "navajowhite"
+            | "navy"
+            | "oldlace"
+            | "olive"
+            | "olivedrab"
+            | "orange"
+            | "orangered"
+            | "orchid"
+            | "palegoldenrod"
+            | "palegreen"
+            | "paleturquoise"
+            | "palevioletred"
+            | "papayawhip"
+            | "peachpuff"
+            | "peru"
+            | "pink"
+            | "plum"
+            | "powderblue"
+            | "purple"
+            | "rebeccapurple"
+            | "red"
+            | "rosybrown"
+            | "royalblue"
+            | "saddlebrown"
+            | "salmon"
+            | "sandybrown"
+            | "seagreen"
+            | "seashell"
+            | "sienna"
+            | "silver"
+            | "skyblue"
+            | "slateblue"
+            | "slategray"
+            | "slategrey"
+            | "snow"
+            | "springgreen"
+            | "steelblue"
+            | "tan"
+            | "teal"
+            | "thistle"
+            | "tomato"
+            | "transparent"
+            | "turquoise"
+            | "violet"
+            | "wheat"
+            | "white"
+            | "whitesmoke"
+            | "yellow"
+            | "yellowgreen"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_keys_by_convention() {
+        assert_eq!(
+            theme_variable_kind("primaryColor"),
+            ThemeVariableKind::Color
+        );
+        assert_eq!(theme_variable_kind("mainBkg"), ThemeVariableKind::Color);
+        assert_eq!(
+            theme_variable_kind("clusterBorder"),
+            ThemeVariableKind::Color
+        );
+        assert_eq!(theme_variable_kind("pie7"), ThemeVariableKind::Color);
+        assert_eq!(theme_variable_kind("fontSize"), ThemeVariableKind::Length);
+        assert_eq!(
+            theme_variable_kind("pieStrokeWidth"),
+            ThemeVariableKind::Length
+        );
+        assert_eq!(
+            theme_variable_kind("pieOpacity"),
+            ThemeVariableKind::Opacity
+        );
+        assert_eq!(theme_variable_kind("darkMode"), ThemeVariableKind::Boolean);
+        assert_eq!(
+            theme_variable_kind("fontFamily"),
+            ThemeVariableKind::FontFamily
+        );
+        assert_eq!(
+            theme_variable_kind("pieLegendText"),
+            ThemeVariableKind::Text
+        );
+    }
+
+    #[test]
+    fn validates_colors() {
+        assert_eq!(
+            validate_theme_variable("primaryColor", " Blue "),
+            Ok("blue".to_string())
+        );
+        assert!(validate_theme_variable("primaryColor", "#ff00aa").is_ok());
+        assert!(validate_theme_variable("primaryColor", "rgb(10, 20, 30)").is_ok());
+        assert!(validate_theme_variable("primaryColor", "rgba(10 20 30 / 0.5)").is_ok());
+        assert!(validate_theme_variable("primaryColor", "hsl(210deg, 40%, 50%)").is_ok());
+        assert!(validate_theme_variable("primaryColor", "bluee").is_err());
+        assert!(validate_theme_variable("primaryColor", "#ggg").is_err());
+        assert!(validate_theme_variable("primaryColor", "rgb(300, 0, 0)").is_err());
+        assert!(validate_theme_variable("lineColor", "red; background: url(x)").is_err());
+    }
+
+    #[test]
+    fn validates_numeric_ranges() {
+        assert!(validate_theme_variable("fontSize", "16px").is_ok());
+        assert!(validate_theme_variable("fontSize", "1.2em").is_ok());
+        assert!(validate_theme_variable("fontSize", "1.5rem").is_ok());
+        assert_eq!(parse_css_length("1.5rem"), Some(1.5));
+        assert!(validate_theme_variable("fontSize", "-4px").is_err());
+        assert!(validate_theme_variable("fontSize", "huge").is_err());
+        assert!(validate_theme_variable("pieOpacity", "0.7").is_ok());
+        assert!(validate_theme_variable("pieOpacity", "1.5").is_err());
+        assert!(validate_theme_variable("darkMode", "yes").is_err());
+    }
+
+    #[test]
+    fn rejects_css_breakout_in_free_text() {
+        assert!(validate_theme_variable("fontFamily", "Inter, sans-serif").is_ok());
+        assert!(validate_theme_variable("fontFamily", "x} body {color:red").is_err());
+        assert!(validate_theme_variable("someLabel", "").is_err());
+    }
+}
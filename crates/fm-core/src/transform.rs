@@ -0,0 +1,755 @@
+//! Transforms that cut a diagram down to part of itself, or combine several into one.
+//!
+//! Each function returns a new, self-consistent [`MermaidDiagramIr`]: the nodes it keeps are
+//! renumbered in their original order, and everything that indexes nodes, ports, edges,
+//! clusters or subgraphs is remapped to match. Edges lose nothing but the ones touching a
+//! removed node; clusters and subgraphs are dropped once every node and nested subgraph in them
+//! is gone. Labels are left as they are, so label ids never change.
+//!
+//! [`extract_subgraph`] gives "this service and its neighbours" views of a large diagram,
+//! [`filter_nodes`] keeps whatever a predicate selects and [`prune_isolated_nodes`] removes
+//! nodes no edge touches. [`merge_irs`] goes the other way, composing one system diagram from
+//! per-service diagrams that share node ids.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::{
+    DiagramType, IrCluster, IrClusterId, IrConstraint, IrEdge, IrEndpoint, IrGraphCluster,
+    IrGraphEdge, IrGraphNode, IrLabelId, IrNode, IrNodeId, IrPort, IrPortId, IrProvenance,
+    IrStyleRef, IrStyleTarget, IrSubgraph, IrSubgraphId, MermaidDiagramIr,
+};
+
+/// The nodes named by `node_ids` plus every node within `depth` edges of one of them, following
+/// edges in either direction. Ids that name no node are ignored.
+#[must_use]
+pub fn extract_subgraph(
+    ir: &MermaidDiagramIr,
+    node_ids: &[&str],
+    depth: usize,
+) -> MermaidDiagramIr {
+    let mut neighbours = vec![Vec::new(); ir.nodes.len()];
+    for edge in &ir.edges {
+        let from = ir.resolve_endpoint_node(edge.from);
+        let to = ir.resolve_endpoint_node(edge.to);
+        if let (Some(from), Some(to)) = (from, to)
+            && from.0 < ir.nodes.len()
+            && to.0 < ir.nodes.len()
+        {
+            neighbours[from.0].push(to.0);
+            neighbours[to.0].push(from.0);
+        }
+    }
+
+    let mut distance = vec![None; ir.nodes.len()];
+    let mut queue = VecDeque::new();
+    for (index, node) in ir.nodes.iter().enumerate() {
+        if node_ids.contains(&node.id.as_str()) {
+            distance[index] = Some(0);
+            queue.push_back(index);
+        }
+    }
+    while let Some(index) = queue.pop_front() {
+        let next = distance[index].unwrap_or(0) + 1;
+        if next > depth {
+            continue;
+        }
+        for &neighbour in &neighbours[index] {
+            if distance[neighbour].is_none() {
+                distance[neighbour] = Some(next);
+                queue.push_back(neighbour);
+            }
+        }
+    }
+
+    let keep: Vec<bool> = distance.iter().map(Option::is_some).collect();
+    retain_nodes(ir, &keep)
+}
+
+/// The nodes for which `keep` returns `true`.
+#[must_use]
+pub fn filter_nodes(
+    ir: &MermaidDiagramIr,
+    mut keep: impl FnMut(&IrNode) -> bool,
+) -> MermaidDiagramIr {
+    let keep: Vec<bool> = ir.nodes.iter().map(&mut keep).collect();
+    retain_nodes(ir, &keep)
+}
+
+/// Every node except those no edge starts or ends at.
+#[must_use]
+pub fn prune_isolated_nodes(ir: &MermaidDiagramIr) -> MermaidDiagramIr {
+    let mut keep = vec![false; ir.nodes.len()];
+    for edge in &ir.edges {
+        for endpoint in [edge.from, edge.to] {
+            if let Some(node) = ir.resolve_endpoint_node(endpoint)
+                && let Some(keep) = keep.get_mut(node.0)
+            {
+                *keep = true;
+            }
+        }
+    }
+    retain_nodes(ir, &keep)
+}
+
+/// Union of `irs` as one diagram, with [`MermaidDiagramIr::provenance`] recording which input
+/// each node, edge and cluster came from.
+///
+/// Nodes that share an id become one node: the first explicit declaration supplies its label
+/// and shape, and the classes of every declaration are combined. Edges, ports, clusters and
+/// subgraphs are appended in input order; subgraph keys are prefixed with the input's index
+/// (`1:Backend`) so same-named subgraphs from different files stay apart, and an edge id an
+/// earlier input already used gets the same prefix (`1:e1`). `classDef`s keep the
+/// first definition of each name. The diagram type, direction, metadata and diagram-specific
+/// tables (sequence, gantt, ...) come from the first input, whose nodes and edges keep their
+/// indices. Each input is expected to pass [`validate_ir`](crate::validate_ir).
+#[must_use]
+pub fn merge_irs(irs: &[MermaidDiagramIr]) -> MermaidDiagramIr {
+    let Some(first) = irs.first() else {
+        return MermaidDiagramIr::empty(DiagramType::Flowchart);
+    };
+    let mut out = MermaidDiagramIr::empty(first.diagram_type);
+    out.direction = first.direction;
+    out.meta = first.meta.clone();
+    out.sequence_meta = first.sequence_meta.clone();
+    out.gantt_meta = first.gantt_meta.clone();
+    out.xy_chart_meta = first.xy_chart_meta.clone();
+    out.pie_meta = first.pie_meta.clone();
+    out.quadrant_meta = first.quadrant_meta.clone();
+    out.gitgraph_meta = first.gitgraph_meta.clone();
+
+    let mut provenance = IrProvenance::default();
+    let mut node_by_id: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut edge_ids: BTreeSet<String> = BTreeSet::new();
+    for (source, ir) in irs.iter().enumerate() {
+        let label_offset = out.labels.len();
+        let label = move |id: Option<IrLabelId>| id.map(|id| IrLabelId(id.0 + label_offset));
+        out.labels.extend(ir.labels.iter().cloned());
+        out.label_markup.extend(
+            ir.label_markup
+                .iter()
+                .map(|(id, segments)| (IrLabelId(id.0 + label_offset), segments.clone())),
+        );
+
+        let mut node_map = Vec::with_capacity(ir.nodes.len());
+        let mut declared_here = Vec::with_capacity(ir.nodes.len());
+        for (index, node) in ir.nodes.iter().enumerate() {
+            let relabelled = IrNode {
+                label: label(node.label),
+                ..node.clone()
+            };
+            let merged = if let Some(&merged) = node_by_id.get(node.id.as_str()) {
+                let kept = &mut out.nodes[merged];
+                let mut classes = std::mem::take(&mut kept.classes);
+                if kept.implicit && !node.implicit {
+                    *kept = relabelled;
+                    if let Some(graph_node) = ir.graph.nodes.iter().find(|g| g.node_id.0 == index) {
+                        out.graph.nodes[merged].kind = graph_node.kind;
+                    }
+                }
+                for class in &node.classes {
+                    if !classes.contains(class) {
+                        classes.push(class.clone());
+                    }
+                }
+                kept.classes = classes;
+                if provenance.nodes[merged].last() != Some(&source) {
+                    provenance.nodes[merged].push(source);
+                }
+                merged
+            } else {
+                let merged = out.nodes.len();
+                node_by_id.insert(node.id.as_str(), merged);
+                out.nodes.push(relabelled);
+                out.graph.nodes.push(IrGraphNode {
+                    node_id: IrNodeId(merged),
+                    ..IrGraphNode::default()
+                });
+                provenance.nodes.push(vec![source]);
+                merged
+            };
+            declared_here.push(provenance.nodes[merged][0] == source);
+            node_map.push(IrNodeId(merged));
+        }
+        let node = |id: IrNodeId| node_map.get(id.0).copied().unwrap_or(id);
+
+        let port_offset = out.ports.len();
+        let edge_offset = out.edges.len();
+        let cluster_offset = out.clusters.len();
+        let subgraph_offset = out.graph.subgraphs.len();
+        let endpoint = |endpoint: IrEndpoint| match endpoint {
+            IrEndpoint::Unresolved => IrEndpoint::Unresolved,
+            IrEndpoint::Node(id) => IrEndpoint::Node(node(id)),
+            IrEndpoint::Port(id) => IrEndpoint::Port(IrPortId(id.0 + port_offset)),
+        };
+        let cluster = |id: IrClusterId| IrClusterId(id.0 + cluster_offset);
+        let subgraph = |id: IrSubgraphId| IrSubgraphId(id.0 + subgraph_offset);
+
+        for graph_node in &ir.graph.nodes {
+            let Some(&merged) = node_map.get(graph_node.node_id.0) else {
+                continue;
+            };
+            let target = &mut out.graph.nodes[merged.0];
+            if declared_here[graph_node.node_id.0] {
+                target.kind = graph_node.kind;
+            }
+            target
+                .clusters
+                .extend(graph_node.clusters.iter().copied().map(cluster));
+            target
+                .subgraphs
+                .extend(graph_node.subgraphs.iter().copied().map(subgraph));
+        }
+        out.ports.extend(ir.ports.iter().map(|port| IrPort {
+            node: node(port.node),
+            ..port.clone()
+        }));
+        for edge in &ir.edges {
+            let mut merged = IrEdge {
+                from: endpoint(edge.from),
+                to: endpoint(edge.to),
+                label: label(edge.label),
+                ..edge.clone()
+            };
+            if let Some(user_id) = edge.user_id()
+                && !edge_ids.insert(user_id.to_string())
+            {
+                let namespaced = format!("{source}:{user_id}");
+                edge_ids.insert(namespaced.clone());
+                merged.extras_mut().user_id = Some(namespaced.into_boxed_str());
+            }
+            out.edges.push(merged);
+        }
+        out.graph
+            .edges
+            .extend(ir.graph.edges.iter().map(|graph_edge| IrGraphEdge {
+                edge_id: graph_edge.edge_id + edge_offset,
+                from: endpoint(graph_edge.from),
+                to: endpoint(graph_edge.to),
+                ..graph_edge.clone()
+            }));
+        provenance
+            .edges
+            .extend(std::iter::repeat_n(source, ir.edges.len()));
+
+        out.clusters
+            .extend(ir.clusters.iter().map(|kept_cluster| IrCluster {
+                id: cluster(kept_cluster.id),
+                title: label(kept_cluster.title),
+                members: kept_cluster.members.iter().copied().map(node).collect(),
+                ..kept_cluster.clone()
+            }));
+        out.graph.clusters.extend(
+            ir.graph
+                .clusters
+                .iter()
+                .map(|graph_cluster| IrGraphCluster {
+                    cluster_id: cluster(graph_cluster.cluster_id),
+                    title: label(graph_cluster.title),
+                    members: graph_cluster.members.iter().copied().map(node).collect(),
+                    subgraph: graph_cluster.subgraph.map(subgraph),
+                    ..graph_cluster.clone()
+                }),
+        );
+        out.graph
+            .subgraphs
+            .extend(ir.graph.subgraphs.iter().map(|graph_subgraph| {
+                IrSubgraph {
+                    id: subgraph(graph_subgraph.id),
+                    key: format!("{source}:{}", graph_subgraph.key),
+                    title: label(graph_subgraph.title),
+                    parent: graph_subgraph.parent.map(subgraph),
+                    children: graph_subgraph
+                        .children
+                        .iter()
+                        .copied()
+                        .map(subgraph)
+                        .collect(),
+                    members: graph_subgraph.members.iter().copied().map(node).collect(),
+                    cluster: graph_subgraph.cluster.map(cluster),
+                    ..graph_subgraph.clone()
+                }
+            }));
+        provenance
+            .clusters
+            .extend(std::iter::repeat_n(source, ir.clusters.len()));
+
+        out.style_refs
+            .extend(ir.style_refs.iter().map(|style_ref| IrStyleRef {
+                target: match style_ref.target {
+                    IrStyleTarget::Node(id) => IrStyleTarget::Node(node(id)),
+                    IrStyleTarget::Link(index) => IrStyleTarget::Link(index + edge_offset),
+                    IrStyleTarget::Class(_) | IrStyleTarget::LinkDefault => {
+                        style_ref.target.clone()
+                    }
+                },
+                ..style_ref.clone()
+            }));
+        for style_def in &ir.style_defs {
+            if !out
+                .style_defs
+                .iter()
+                .any(|kept| kept.name == style_def.name)
+            {
+                out.style_defs.push(style_def.clone());
+            }
+        }
+        out.constraints.extend(ir.constraints.iter().cloned());
+        out.state_notes.extend(ir.state_notes.iter().cloned());
+        out.diagnostics.extend(ir.diagnostics.iter().cloned());
+    }
+
+    out.provenance = Some(provenance);
+    out
+}
+
+/// New index of every kept position in a table where `keep` marks the survivors.
+fn index_map(keep: &[bool]) -> Vec<Option<usize>> {
+    let mut next = 0;
+    keep.iter()
+        .map(|&kept| {
+            kept.then(|| {
+                next += 1;
+                next - 1
+            })
+        })
+        .collect()
+}
+
+fn remapped(map: &[Option<usize>], index: usize) -> Option<usize> {
+    map.get(index).copied().flatten()
+}
+
+/// Copy of `ir` holding only the nodes `keep` marks, with every reference remapped.
+fn retain_nodes(ir: &MermaidDiagramIr, keep: &[bool]) -> MermaidDiagramIr {
+    let node_map = index_map(keep);
+    let node = |id: IrNodeId| remapped(&node_map, id.0).map(IrNodeId);
+    let kept_ids: BTreeSet<&str> = ir
+        .nodes
+        .iter()
+        .zip(keep)
+        .filter(|(_, kept)| **kept)
+        .map(|(node, _)| node.id.as_str())
+        .collect();
+
+    let port_keep: Vec<bool> = ir
+        .ports
+        .iter()
+        .map(|port| node(port.node).is_some())
+        .collect();
+    let port_map = index_map(&port_keep);
+    let endpoint = |endpoint: IrEndpoint| match endpoint {
+        IrEndpoint::Unresolved => Some(IrEndpoint::Unresolved),
+        IrEndpoint::Node(id) => node(id).map(IrEndpoint::Node),
+        IrEndpoint::Port(id) => remapped(&port_map, id.0).map(|id| IrEndpoint::Port(IrPortId(id))),
+    };
+
+    let edge_keep: Vec<bool> = ir
+        .edges
+        .iter()
+        .map(|edge| endpoint(edge.from).is_some() && endpoint(edge.to).is_some())
+        .collect();
+    let edge_map = index_map(&edge_keep);
+    // Positions between messages (sequence ranges, note anchors) move to the first kept edge at
+    // or after them.
+    let edge_position = |index: usize| {
+        edge_keep[..index.min(edge_keep.len())]
+            .iter()
+            .filter(|kept| **kept)
+            .count()
+    };
+
+    let subgraph_keep = subgraph_keep(ir, keep);
+    let subgraph_map = index_map(&subgraph_keep);
+    let subgraph = |id: IrSubgraphId| remapped(&subgraph_map, id.0).map(IrSubgraphId);
+    let cluster_keep: Vec<bool> = ir
+        .clusters
+        .iter()
+        .enumerate()
+        .map(|(index, cluster)| {
+            cluster.members.iter().any(|&member| node(member).is_some())
+                || match ir
+                    .graph
+                    .clusters
+                    .get(index)
+                    .and_then(|graph| graph.subgraph)
+                {
+                    Some(id) => subgraph(id).is_some(),
+                    None => cluster.members.is_empty(),
+                }
+        })
+        .collect();
+    let cluster_map = index_map(&cluster_keep);
+    let cluster = |id: IrClusterId| remapped(&cluster_map, id.0).map(IrClusterId);
+
+    let mut out = ir.clone();
+    out.nodes = retained(&ir.nodes, keep);
+    out.ports = ir
+        .ports
+        .iter()
+        .filter_map(|port| {
+            Some(IrPort {
+                node: node(port.node)?,
+                ..port.clone()
+            })
+        })
+        .collect();
+    out.edges = ir
+        .edges
+        .iter()
+        .filter_map(|edge| {
+            Some(IrEdge {
+                from: endpoint(edge.from)?,
+                to: endpoint(edge.to)?,
+                ..edge.clone()
+            })
+        })
+        .collect();
+    out.clusters = retained(&ir.clusters, &cluster_keep);
+    for (index, kept_cluster) in out.clusters.iter_mut().enumerate() {
+        kept_cluster.id = IrClusterId(index);
+        kept_cluster
+            .members
+            .retain_mut(|member| remap_in_place(member, node));
+    }
+
+    out.graph.nodes.retain_mut(|graph_node| {
+        let Some(id) = node(graph_node.node_id) else {
+            return false;
+        };
+        graph_node.node_id = id;
+        graph_node
+            .clusters
+            .retain_mut(|id| remap_in_place(id, cluster));
+        graph_node
+            .subgraphs
+            .retain_mut(|id| remap_in_place(id, subgraph));
+        true
+    });
+    out.graph.edges.retain_mut(|graph_edge| {
+        let (Some(edge_id), Some(from), Some(to)) = (
+            remapped(&edge_map, graph_edge.edge_id),
+            endpoint(graph_edge.from),
+            endpoint(graph_edge.to),
+        ) else {
+            return false;
+        };
+        (graph_edge.edge_id, graph_edge.from, graph_edge.to) = (edge_id, from, to);
+        true
+    });
+    out.graph.clusters = retained(&ir.graph.clusters, &cluster_keep);
+    for (index, graph_cluster) in out.graph.clusters.iter_mut().enumerate() {
+        graph_cluster.cluster_id = IrClusterId(index);
+        graph_cluster
+            .members
+            .retain_mut(|member| remap_in_place(member, node));
+        graph_cluster.subgraph = graph_cluster.subgraph.and_then(subgraph);
+    }
+    out.graph.subgraphs = retained(&ir.graph.subgraphs, &subgraph_keep);
+    for (index, graph_subgraph) in out.graph.subgraphs.iter_mut().enumerate() {
+        graph_subgraph.id = IrSubgraphId(index);
+        graph_subgraph.parent = graph_subgraph.parent.and_then(subgraph);
+        graph_subgraph
+            .children
+            .retain_mut(|id| remap_in_place(id, subgraph));
+        graph_subgraph
+            .members
+            .retain_mut(|member| remap_in_place(member, node));
+        graph_subgraph.cluster = graph_subgraph.cluster.and_then(cluster);
+    }
+
+    out.style_refs = ir
+        .style_refs
+        .iter()
+        .filter_map(|style_ref| {
+            let target = match style_ref.target {
+                IrStyleTarget::Node(id) => IrStyleTarget::Node(node(id)?),
+                IrStyleTarget::Link(index) => IrStyleTarget::Link(remapped(&edge_map, index)?),
+                IrStyleTarget::Class(_) | IrStyleTarget::LinkDefault => style_ref.target.clone(),
+            };
+            Some(IrStyleRef {
+                target,
+                ..style_ref.clone()
+            })
+        })
+        .collect();
+    out.constraints.retain_mut(|constraint| match constraint {
+        IrConstraint::SameRank { node_ids, .. } | IrConstraint::OrderInRank { node_ids, .. } => {
+            node_ids.retain(|id| kept_ids.contains(id.as_str()));
+            node_ids.len() > 1
+        }
+        IrConstraint::MinLength { from_id, to_id, .. } => {
+            kept_ids.contains(from_id.as_str()) && kept_ids.contains(to_id.as_str())
+        }
+        IrConstraint::Pin { node_id, .. } => kept_ids.contains(node_id.as_str()),
+    });
+    out.state_notes
+        .retain(|note| kept_ids.contains(note.target.as_str()));
+
+    if let Some(meta) = &mut out.sequence_meta {
+        meta.activations.retain_mut(|activation| {
+            activation.start_edge = edge_position(activation.start_edge);
+            activation.end_edge = edge_position(activation.end_edge);
+            remap_in_place(&mut activation.participant, node)
+        });
+        meta.notes.retain_mut(|note| {
+            note.after_edge = edge_position(note.after_edge);
+            note.participants.retain_mut(|id| remap_in_place(id, node));
+            !note.participants.is_empty()
+        });
+        for fragment in &mut meta.fragments {
+            fragment.start_edge = edge_position(fragment.start_edge);
+            fragment.end_edge = edge_position(fragment.end_edge);
+            for alternative in &mut fragment.alternatives {
+                alternative.start_edge = edge_position(alternative.start_edge);
+                alternative.end_edge = edge_position(alternative.end_edge);
+            }
+        }
+        meta.participant_groups.retain_mut(|group| {
+            group.participants.retain_mut(|id| remap_in_place(id, node));
+            !group.participants.is_empty()
+        });
+        meta.lifecycle_events.retain_mut(|event| {
+            event.at_edge = edge_position(event.at_edge);
+            remap_in_place(&mut event.participant, node)
+        });
+        for change in &mut meta.autonumber_changes {
+            change.edge_index = edge_position(change.edge_index);
+        }
+    }
+    if let Some(provenance) = &mut out.provenance {
+        provenance.nodes = retained(&provenance.nodes, keep);
+        provenance.edges = retained(&provenance.edges, &edge_keep);
+        provenance.clusters = retained(&provenance.clusters, &cluster_keep);
+    }
+    if let Some(meta) = &mut out.gantt_meta {
+        meta.tasks
+            .retain_mut(|task| remap_in_place(&mut task.node, node));
+    }
+    if let Some(meta) = &mut out.gitgraph_meta {
+        meta.commits
+            .retain_mut(|commit| remap_in_place(&mut commit.node, node));
+    }
+    if let Some(meta) = &mut out.xy_chart_meta {
+        for series in &mut meta.series {
+            series.nodes.retain_mut(|id| remap_in_place(id, node));
+        }
+    }
+
+    out
+}
+
+/// Which subgraphs survive: those keeping a node or a nested subgraph, plus those that were
+/// empty to begin with.
+fn subgraph_keep(ir: &MermaidDiagramIr, keep: &[bool]) -> Vec<bool> {
+    fn visit(
+        ir: &MermaidDiagramIr,
+        keep: &[bool],
+        index: usize,
+        memo: &mut [Option<bool>],
+    ) -> bool {
+        if let Some(kept) = memo[index] {
+            return kept;
+        }
+        // Guard against cyclic parent links in hand-built IRs.
+        memo[index] = Some(false);
+        let subgraph = &ir.graph.subgraphs[index];
+        let kept = (subgraph.members.is_empty() && subgraph.children.is_empty())
+            || subgraph
+                .members
+                .iter()
+                .any(|member| keep.get(member.0).copied().unwrap_or(false))
+            || subgraph
+                .children
+                .iter()
+                .any(|child| child.0 < memo.len() && visit(ir, keep, child.0, memo));
+        memo[index] = Some(kept);
+        kept
+    }
+
+    let mut memo = vec![None; ir.graph.subgraphs.len()];
+    (0..memo.len())
+        .map(|index| visit(ir, keep, index, &mut memo))
+        .collect()
+}
+
+fn retained<T: Clone>(items: &[T], keep: &[bool]) -> Vec<T> {
+    items
+        .iter()
+        .zip(keep)
+        .filter(|(_, kept)| **kept)
+        .map(|(item, _)| item.clone())
+        .collect()
+}
+
+/// Replace `id` with its new value, returning `false` when it was removed.
+fn remap_in_place<T: Copy>(id: &mut T, remap: impl Fn(T) -> Option<T>) -> bool {
+    match remap(*id) {
+        Some(new_id) => {
+            *id = new_id;
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ArrowType, DiagramType, IrBuilder, IrNodeKind, validate_ir};
+
+    /// `A -> B -> C -> D`, `E` alone, `B` and `C` in one cluster and `D` in another.
+    fn chain() -> MermaidDiagramIr {
+        let mut builder = IrBuilder::new(DiagramType::Flowchart);
+        for id in ["A", "B", "C", "D", "E"] {
+            builder.node(id).label(id);
+        }
+        builder.edge("A", "B");
+        builder.edge("B", "C").arrow(ArrowType::DottedArrow);
+        builder.edge("C", "D").label("last");
+        builder.cluster("Middle").member("B").member("C");
+        builder.cluster("End").member("D");
+        let mut ir = builder.build().unwrap();
+        ir.style_refs.push(IrStyleRef {
+            target: IrStyleTarget::Link(2),
+            style: "stroke:red".to_string(),
+            span: crate::Span::default(),
+        });
+        ir
+    }
+
+    fn ids(ir: &MermaidDiagramIr) -> Vec<&str> {
+        ir.nodes.iter().map(|node| node.id.as_str()).collect()
+    }
+
+    #[test]
+    fn extract_subgraph_keeps_neighbours_within_depth() {
+        let ir = chain();
+        let focus = extract_subgraph(&ir, &["C"], 1);
+        assert_eq!(ids(&focus), ["B", "C", "D"]);
+        assert_eq!(focus.edges.len(), 2);
+        assert_eq!(focus.edges[0].arrow, ArrowType::DottedArrow);
+        assert_eq!(focus.edges[0].from, IrEndpoint::Node(IrNodeId(0)));
+        // `linkStyle 2` followed its edge to index 1.
+        assert_eq!(focus.style_refs[0].target, IrStyleTarget::Link(1));
+        assert_eq!(focus.clusters.len(), 2);
+        assert_eq!(focus.clusters[1].members, [IrNodeId(2)]);
+        assert!(validate_ir(&focus).is_valid());
+
+        assert_eq!(ids(&extract_subgraph(&ir, &["A"], 0)), ["A"]);
+        assert_eq!(ids(&extract_subgraph(&ir, &["A"], 5)), ["A", "B", "C", "D"]);
+        assert!(extract_subgraph(&ir, &["Missing"], 3).nodes.is_empty());
+    }
+
+    #[test]
+    fn filter_and_prune_drop_emptied_clusters() {
+        let ir = chain();
+        let filtered = filter_nodes(&ir, |node| node.id != "D");
+        assert_eq!(ids(&filtered), ["A", "B", "C", "E"]);
+        assert_eq!(filtered.clusters.len(), 1);
+        assert_eq!(filtered.graph.clusters.len(), 1);
+        assert!(filtered.style_refs.is_empty());
+        assert!(validate_ir(&filtered).is_valid());
+
+        let pruned = prune_isolated_nodes(&ir);
+        assert_eq!(ids(&pruned), ["A", "B", "C", "D"]);
+        assert_eq!(pruned.edges.len(), 3);
+        assert_eq!(pruned.labels, ir.labels);
+    }
+
+    #[test]
+    fn merge_irs_dedups_shared_nodes_and_records_provenance() {
+        let mut api = IrBuilder::new(DiagramType::Flowchart);
+        for id in ["Gateway", "Auth"] {
+            api.node(id).label(id);
+        }
+        api.edge("Gateway", "Auth").label("login");
+        api.cluster("Backend").member("Auth");
+        let mut api = api.build().unwrap();
+        api.nodes[1].implicit = true;
+        let mut billing = IrBuilder::new(DiagramType::Flowchart);
+        billing.node("Auth").label("Auth service").class("core");
+        billing.node("Ledger");
+        billing.edge("Auth", "Ledger").id("charge");
+        billing.cluster("Backend").member("Ledger");
+        let mut billing = billing.build().unwrap();
+        billing.graph.subgraphs.push(IrSubgraph {
+            key: "backend".to_string(),
+            members: vec![IrNodeId(1)],
+            cluster: Some(IrClusterId(0)),
+            ..IrSubgraph::default()
+        });
+        billing.graph.clusters[0].subgraph = Some(IrSubgraphId(0));
+
+        let merged = merge_irs(&[api.clone(), billing]);
+        assert_eq!(ids(&merged), ["Gateway", "Auth", "Ledger"]);
+        // The first diagram only referenced `Auth`, so the second one's declaration wins.
+        let auth = &merged.nodes[1];
+        assert_eq!(merged.labels[auth.label.unwrap().0].text, "Auth service");
+        assert_eq!(auth.classes, ["core"]);
+        assert_eq!(merged.edges[1].from, IrEndpoint::Node(IrNodeId(1)));
+        assert_eq!(merged.edges[1].user_id(), Some("charge"));
+        assert_eq!(merged.clusters[1].members, [IrNodeId(2)]);
+        assert_eq!(merged.clusters[1].id, IrClusterId(1));
+        let backend = &merged.graph.subgraphs[0];
+        assert_eq!(backend.key, "1:backend");
+        assert_eq!(backend.members, [IrNodeId(2)]);
+        assert_eq!(backend.cluster, Some(IrClusterId(1)));
+        let provenance = merged.provenance.as_ref().unwrap();
+        assert_eq!(provenance.nodes, [vec![0], vec![0, 1], vec![1]]);
+        assert_eq!(provenance.edges, [0, 1]);
+        assert_eq!(provenance.clusters, [0, 1]);
+        assert!(validate_ir(&merged).is_valid());
+
+        let only_billing = filter_nodes(&merged, |node| node.id != "Gateway");
+        assert_eq!(
+            only_billing.provenance.unwrap().nodes,
+            [vec![0, 1], vec![1]]
+        );
+        assert_eq!(merge_irs(&[api.clone()]).nodes, api.nodes);
+        assert!(merge_irs(&[]).nodes.is_empty());
+    }
+
+    #[test]
+    fn merge_irs_takes_the_node_kind_from_the_explicit_declaration() {
+        let mut referenced = IrBuilder::new(DiagramType::Flowchart);
+        referenced.node("Idle");
+        let mut referenced = referenced.build().unwrap();
+        referenced.nodes[0].implicit = true;
+        let mut declared = IrBuilder::new(DiagramType::Flowchart);
+        declared.node("Idle").label("Waiting");
+        let mut declared = declared.build().unwrap();
+        declared.graph.nodes[0].kind = IrNodeKind::State;
+
+        let merged = merge_irs(&[referenced.clone(), declared.clone()]);
+        assert_eq!(
+            merged.labels[merged.nodes[0].label.unwrap().0].text,
+            "Waiting"
+        );
+        assert_eq!(merged.graph.nodes[0].kind, IrNodeKind::State);
+
+        // An explicit first declaration keeps its kind against a later one.
+        referenced.nodes[0].implicit = false;
+        let merged = merge_irs(&[referenced, declared]);
+        assert_eq!(merged.graph.nodes[0].kind, IrNodeKind::Generic);
+    }
+
+    #[test]
+    fn merge_irs_namespaces_edge_ids_used_by_an_earlier_input() {
+        let service = |from: &str, to: &str| {
+            let mut builder = IrBuilder::new(DiagramType::Flowchart);
+            builder.node(from);
+            builder.node(to);
+            builder.edge(from, to).id("e1");
+            builder.edge(to, from);
+            builder.build().unwrap()
+        };
+
+        let merged = merge_irs(&[service("A", "B"), service("B", "C"), service("C", "D")]);
+        let user_ids: Vec<_> = merged.edges.iter().map(IrEdge::user_id).collect();
+        assert_eq!(
+            user_ids,
+            [Some("e1"), None, Some("1:e1"), None, Some("2:e1"), None]
+        );
+        assert!(validate_ir(&merged).is_valid());
+    }
+}
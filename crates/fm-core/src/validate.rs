@@ -0,0 +1,328 @@
+//! Referential integrity checks for a [`MermaidDiagramIr`].
+//!
+//! The IR cross-references itself by index ([`IrLabelId`], [`IrNodeId`], [`IrPortId`]) and by
+//! node id (constraints). The parser only emits valid references, but an IR deserialized from
+//! JSON or assembled by hand can carry dangling ones, which renderers would otherwise skip
+//! without a word. [`validate_ir`] reports each broken reference as a [`Diagnostic`] pointing at
+//! the element that holds it.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    Diagnostic, DiagnosticCategory, IrConstraint, IrEndpoint, IrLabelId, IrNodeId, IrPortId,
+    IrStyleTarget, MermaidDiagramIr, MermaidError, MermaidValidation, RelatedDiagnostic, Span,
+};
+
+/// Check every index and node-id reference in `ir`.
+///
+/// Errors: dangling label, node and port ids, unresolved edge endpoints, duplicate node and edge
+/// ids and constraints naming missing nodes. Warnings: empty clusters and `linkStyle` indices
+/// past the last edge. Each finding is a [`DiagnosticCategory::Semantic`] diagnostic whose
+/// `rule_id` names the check; error-level findings are repeated in `errors` as
+/// [`MermaidError::Validation`].
+#[must_use]
+pub fn validate_ir(ir: &MermaidDiagramIr) -> MermaidValidation {
+    let mut checker = Checker {
+        ir,
+        validation: MermaidValidation::default(),
+    };
+    checker.check_labels();
+    checker.check_nodes();
+    checker.check_edges();
+    checker.check_clusters();
+    checker.check_constraints();
+    checker.check_style_refs();
+    checker.validation
+}
+
+struct Checker<'a> {
+    ir: &'a MermaidDiagramIr,
+    validation: MermaidValidation,
+}
+
+impl Checker<'_> {
+    fn error(&mut self, rule: &str, message: String, span: Span) {
+        self.validation.errors.push(MermaidError::Validation {
+            message: message.clone(),
+            span,
+        });
+        self.push(Diagnostic::error(message), rule, span);
+    }
+
+    fn warning(&mut self, rule: &str, message: String, span: Span) {
+        self.push(Diagnostic::warning(message), rule, span);
+    }
+
+    fn push(&mut self, diagnostic: Diagnostic, rule: &str, span: Span) {
+        let mut diagnostic = diagnostic
+            .with_category(DiagnosticCategory::Semantic)
+            .with_rule_id(format!("ir.integrity.{rule}"));
+        if !span.is_unknown() {
+            diagnostic = diagnostic.with_span(span);
+        }
+        self.validation.diagnostics.push(diagnostic);
+    }
+
+    fn check_label(&mut self, label: Option<IrLabelId>, owner: &str, span: Span) {
+        if let Some(label) = label
+            && label.0 >= self.ir.labels.len()
+        {
+            self.error(
+                "dangling_label",
+                format!("{owner} references missing label {}", label.0),
+                span,
+            );
+        }
+    }
+
+    fn check_node(&mut self, node: IrNodeId, owner: &str, span: Span) {
+        if node.0 >= self.ir.nodes.len() {
+            self.error(
+                "dangling_node",
+                format!("{owner} references missing node {}", node.0),
+                span,
+            );
+        }
+    }
+
+    /// Point the last diagnostic at the first declaration of the id it reports as duplicated.
+    fn relate_to_first(&mut self, first_span: Span) {
+        if !first_span.is_unknown()
+            && let Some(diagnostic) = self.validation.diagnostics.last_mut()
+        {
+            diagnostic.related.push(RelatedDiagnostic {
+                message: "first declared here".to_string(),
+                span: first_span,
+            });
+        }
+    }
+
+    fn check_labels(&mut self) {
+        let ir = self.ir;
+        for label in ir.label_markup.keys() {
+            self.check_label(Some(*label), "label markup", Span::default());
+        }
+    }
+
+    fn check_nodes(&mut self) {
+        let ir = self.ir;
+        let mut first_by_id: BTreeMap<&str, usize> = BTreeMap::new();
+        for (index, node) in ir.nodes.iter().enumerate() {
+            let owner = format!("node '{}'", node.id);
+            self.check_label(node.label, &owner, node.span_primary);
+            if let Some(&first) = first_by_id.get(node.id.as_str()) {
+                let message = format!(
+                    "node id '{}' is declared by nodes {first} and {index}",
+                    node.id
+                );
+                self.error("duplicate_node_id", message, node.span_primary);
+                self.relate_to_first(ir.nodes[first].span_primary);
+            } else {
+                first_by_id.insert(node.id.as_str(), index);
+            }
+        }
+        for (index, port) in ir.ports.iter().enumerate() {
+            self.check_node(port.node, &format!("port {index}"), port.span);
+        }
+    }
+
+    fn check_edges(&mut self) {
+        let ir = self.ir;
+        let mut first_by_id: BTreeMap<&str, usize> = BTreeMap::new();
+        for (index, edge) in ir.edges.iter().enumerate() {
+            let owner = format!("edge {index}");
+            self.check_label(edge.label, &owner, edge.span);
+            for endpoint in [edge.from, edge.to] {
+                match endpoint {
+                    IrEndpoint::Unresolved => self.error(
+                        "unresolved_endpoint",
+                        format!("{owner} has an unresolved endpoint"),
+                        edge.span,
+                    ),
+                    IrEndpoint::Node(node) => self.check_node(node, &owner, edge.span),
+                    IrEndpoint::Port(IrPortId(port)) if port >= ir.ports.len() => self.error(
+                        "dangling_port",
+                        format!("{owner} references missing port {port}"),
+                        edge.span,
+                    ),
+                    IrEndpoint::Port(_) => {}
+                }
+            }
+            if let Some(user_id) = edge.user_id() {
+                if let Some(&first) = first_by_id.get(user_id) {
+                    let message =
+                        format!("edge id '{user_id}' is used by edges {first} and {index}");
+                    self.error("duplicate_edge_id", message, edge.span);
+                    self.relate_to_first(ir.edges[first].span);
+                } else {
+                    first_by_id.insert(user_id, index);
+                }
+            }
+        }
+    }
+
+    fn check_clusters(&mut self) {
+        let ir = self.ir;
+        for (index, cluster) in ir.clusters.iter().enumerate() {
+            let owner = format!("cluster {index}");
+            self.check_label(cluster.title, &owner, cluster.span);
+            for &member in &cluster.members {
+                self.check_node(member, &owner, cluster.span);
+            }
+            if cluster.members.is_empty() {
+                self.warning(
+                    "empty_cluster",
+                    format!("{owner} has no members"),
+                    cluster.span,
+                );
+            }
+        }
+        for subgraph in &ir.graph.subgraphs {
+            let owner = format!("subgraph '{}'", subgraph.key);
+            self.check_label(subgraph.title, &owner, subgraph.span);
+            for &member in &subgraph.members {
+                self.check_node(member, &owner, subgraph.span);
+            }
+        }
+    }
+
+    fn check_constraints(&mut self) {
+        let ir = self.ir;
+        let declared: BTreeSet<&str> = ir.nodes.iter().map(|node| node.id.as_str()).collect();
+        for constraint in &ir.constraints {
+            let (referenced, span): (Vec<&str>, Span) = match constraint {
+                IrConstraint::SameRank { node_ids, span }
+                | IrConstraint::OrderInRank { node_ids, span } => {
+                    (node_ids.iter().map(String::as_str).collect(), *span)
+                }
+                IrConstraint::MinLength {
+                    from_id,
+                    to_id,
+                    span,
+                    ..
+                } => (vec![from_id.as_str(), to_id.as_str()], *span),
+                IrConstraint::Pin { node_id, span, .. } => (vec![node_id.as_str()], *span),
+            };
+            for id in referenced {
+                if !declared.contains(id) {
+                    self.error(
+                        "constraint_unknown_node",
+                        format!("constraint references unknown node '{id}'"),
+                        span,
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_style_refs(&mut self) {
+        let ir = self.ir;
+        for style_ref in &ir.style_refs {
+            match style_ref.target {
+                IrStyleTarget::Node(node) => self.check_node(node, "style", style_ref.span),
+                IrStyleTarget::Link(edge) if edge >= ir.edges.len() => self.warning(
+                    "dangling_edge",
+                    format!("linkStyle references missing edge {edge}"),
+                    style_ref.span,
+                ),
+                IrStyleTarget::Class(_) | IrStyleTarget::Link(_) | IrStyleTarget::LinkDefault => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DiagramType, IrBuilder, IrCluster, IrEdge, IrNode, IrPort};
+
+    fn rules(validation: &MermaidValidation) -> Vec<&str> {
+        validation
+            .diagnostics
+            .iter()
+            .filter_map(|diagnostic| diagnostic.rule_id.as_deref())
+            .collect()
+    }
+
+    #[test]
+    fn a_consistent_ir_has_no_findings() {
+        let mut builder = IrBuilder::new(DiagramType::Flowchart);
+        builder.node("A").label("Start");
+        builder.node("B");
+        builder.edge("A", "B").label("go");
+        builder.cluster("Group").member("B");
+        let validation = validate_ir(&builder.build().unwrap());
+        assert_eq!(validation, MermaidValidation::default());
+    }
+
+    #[test]
+    fn reports_each_broken_reference() {
+        let mut ir = MermaidDiagramIr::empty(DiagramType::Flowchart);
+        for _ in 0..2 {
+            ir.nodes.push(IrNode {
+                id: "A".to_string(),
+                label: Some(IrLabelId(7)),
+                ..IrNode::default()
+            });
+        }
+        ir.ports.push(IrPort {
+            node: IrNodeId(9),
+            ..IrPort::default()
+        });
+        ir.edges.push(IrEdge {
+            from: IrEndpoint::Port(IrPortId(3)),
+            to: IrEndpoint::Unresolved,
+            ..IrEdge::default()
+        });
+        ir.clusters.push(IrCluster::default());
+        ir.constraints.push(IrConstraint::Pin {
+            node_id: "Ghost".to_string(),
+            x: 0.0,
+            y: 0.0,
+            span: Span::default(),
+        });
+
+        let validation = validate_ir(&ir);
+        assert_eq!(
+            rules(&validation),
+            [
+                "ir.integrity.dangling_label",
+                "ir.integrity.dangling_label",
+                "ir.integrity.duplicate_node_id",
+                "ir.integrity.dangling_node",
+                "ir.integrity.dangling_port",
+                "ir.integrity.unresolved_endpoint",
+                "ir.integrity.empty_cluster",
+                "ir.integrity.constraint_unknown_node",
+            ]
+        );
+        // Everything but the empty cluster is an error.
+        assert_eq!(validation.errors.len(), 7);
+        assert!(validation.errors[0].to_string().contains("missing label 7"));
+    }
+
+    #[test]
+    fn reports_duplicate_edge_ids_against_the_first_edge() {
+        let mut builder = IrBuilder::new(DiagramType::Flowchart);
+        builder.node("A");
+        builder.node("B");
+        builder.edge("A", "B").id("e1");
+        builder.edge("B", "A");
+        let mut ir = builder.build().unwrap();
+        ir.edges[0].span = Span::at_line(2, 10);
+        ir.edges[1].span = Span::at_line(3, 10);
+        ir.edges[1].extras_mut().user_id = Some(Box::from("e1"));
+
+        let validation = validate_ir(&ir);
+        assert_eq!(rules(&validation), ["ir.integrity.duplicate_edge_id"]);
+        assert!(
+            validation.errors[0]
+                .to_string()
+                .contains("edge id 'e1' is used by edges 0 and 1")
+        );
+        assert_eq!(
+            validation.diagnostics[0].related[0].span,
+            Span::at_line(2, 10)
+        );
+    }
+}
@@ -0,0 +1,335 @@
+//! Width-aware label wrapping shared by the renderers.
+//!
+//! [`wrap_label`] breaks a label into lines no wider than a column budget under a
+//! [`MermaidWrapMode`], keeps at most a given number of lines, and marks anything it drops
+//! with `…`. Width is measured by the caller, so the terminal renderer can count display
+//! columns while the SVG renderer counts characters, and both break labels the same way.
+//! [`wrap_label_with_marker`] and [`ellipsize_with_marker`] take the marker as a parameter for
+//! output that cannot show `…`, such as a terminal in ASCII glyph mode.
+
+use crate::MermaidWrapMode;
+
+/// Marker appended where a label was cut short.
+pub const WRAP_ELLIPSIS: &str = "…";
+
+/// [`WRAP_ELLIPSIS`] spelled in ASCII.
+pub const WRAP_ELLIPSIS_ASCII: &str = "...";
+
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+
+/// Wrap `text` into lines at most `max_width` wide, as measured by `measure`, keeping at most
+/// `max_lines` of them.
+///
+/// Explicit newlines always break, and runs of whitespace collapse to one space. Under
+/// [`MermaidWrapMode::Word`] lines break between words and a word too wide for a line is cut
+/// with `…`; [`MermaidWrapMode::Char`] fills every line and breaks anywhere;
+/// [`MermaidWrapMode::WordChar`] breaks between words and carries an overlong word onto the
+/// following lines; [`MermaidWrapMode::None`] keeps each source line whole and cuts it with
+/// `…`. Blank source lines are kept, and blank text gives a single empty line. When lines are
+/// dropped, the last kept line ends in `…`. Characters that `measure` gives no width, and
+/// anything joined by a zero-width joiner, stay with the character before them.
+#[must_use]
+pub fn wrap_label(
+    text: &str,
+    mode: MermaidWrapMode,
+    max_width: usize,
+    max_lines: usize,
+    measure: impl Fn(&str) -> usize,
+) -> Vec<String> {
+    wrap_label_with_marker(text, mode, max_width, max_lines, WRAP_ELLIPSIS, measure)
+}
+
+/// [`wrap_label`] marking cuts with `marker` instead of `…`.
+#[must_use]
+pub fn wrap_label_with_marker(
+    text: &str,
+    mode: MermaidWrapMode,
+    max_width: usize,
+    max_lines: usize,
+    marker: &str,
+    measure: impl Fn(&str) -> usize,
+) -> Vec<String> {
+    let max_width = max_width.max(1);
+    let max_lines = max_lines.max(1);
+    let mut lines: Vec<String> = Vec::new();
+    for source in text.lines() {
+        let words: Vec<&str> = source.split_whitespace().collect();
+        if words.is_empty() {
+            lines.push(String::new());
+            continue;
+        }
+        match mode {
+            MermaidWrapMode::None => {
+                lines.push(ellipsize_with_marker(
+                    &words.join(" "),
+                    max_width,
+                    marker,
+                    &measure,
+                ));
+            }
+            MermaidWrapMode::Char => {
+                fill_lines(&mut lines, &words.join(" "), max_width, &measure);
+            }
+            MermaidWrapMode::Word | MermaidWrapMode::WordChar => {
+                wrap_words(&mut lines, &words, mode, max_width, marker, &measure);
+            }
+        }
+        if lines.len() > max_lines {
+            break;
+        }
+    }
+    if lines.is_empty() {
+        lines.push(String::new());
+    }
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        if let Some(last) = lines.last_mut() {
+            *last = mark_cut(last, max_width, marker, &measure);
+        }
+    }
+    lines
+}
+
+/// `text` cut to at most `max_width`, ending in `…` when anything was removed.
+#[must_use]
+pub fn ellipsize(text: &str, max_width: usize, measure: impl Fn(&str) -> usize) -> String {
+    ellipsize_with_marker(text, max_width, WRAP_ELLIPSIS, measure)
+}
+
+/// [`ellipsize`] ending in `marker` instead of `…`.
+#[must_use]
+pub fn ellipsize_with_marker(
+    text: &str,
+    max_width: usize,
+    marker: &str,
+    measure: impl Fn(&str) -> usize,
+) -> String {
+    if measure(text) <= max_width {
+        return text.to_owned();
+    }
+    let marker_width = measure(marker);
+    let mut kept = take_width(text, max_width.saturating_sub(marker_width), &measure).0;
+    kept.push_str(marker);
+    kept
+}
+
+/// `line` with `marker` appended, cut further when the marker would not fit.
+fn mark_cut(
+    line: &str,
+    max_width: usize,
+    marker: &str,
+    measure: &impl Fn(&str) -> usize,
+) -> String {
+    if line.ends_with(marker) {
+        return line.to_owned();
+    }
+    let room = max_width.saturating_sub(measure(marker));
+    let mut kept = take_width(line, room, measure).0.trim_end().to_owned();
+    kept.push_str(marker);
+    kept
+}
+
+fn wrap_words(
+    lines: &mut Vec<String>,
+    words: &[&str],
+    mode: MermaidWrapMode,
+    max_width: usize,
+    marker: &str,
+    measure: &impl Fn(&str) -> usize,
+) {
+    let space = measure(" ");
+    let mut current = String::new();
+    let mut current_width = 0;
+    for word in words {
+        let word_width = measure(word);
+        if !current.is_empty() && current_width + space + word_width <= max_width {
+            current.push(' ');
+            current.push_str(word);
+            current_width += space + word_width;
+            continue;
+        }
+        if !current.is_empty() {
+            lines.push(std::mem::take(&mut current));
+        }
+        if word_width <= max_width {
+            current.push_str(word);
+            current_width = word_width;
+        } else if mode == MermaidWrapMode::Word {
+            current = ellipsize_with_marker(word, max_width, marker, measure);
+            current_width = measure(&current);
+        } else {
+            fill_lines(lines, word, max_width, measure);
+            current = lines.pop().unwrap_or_default();
+            current_width = measure(&current);
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+}
+
+/// Break `text` into lines filled up to `max_width`, dropping the spaces lines would start with.
+fn fill_lines(
+    lines: &mut Vec<String>,
+    text: &str,
+    max_width: usize,
+    measure: &impl Fn(&str) -> usize,
+) {
+    let mut rest = text;
+    while !rest.trim_start().is_empty() {
+        rest = rest.trim_start();
+        let (line, consumed) = take_width(rest, max_width, measure);
+        lines.push(line.trim_end().to_owned());
+        rest = &rest[consumed..];
+    }
+}
+
+/// The longest run of whole clusters at the start of `text` no wider than `max_width`, and its
+/// length in bytes. At least one cluster is taken so that a cluster wider than the limit still
+/// makes progress, unless the limit is zero.
+fn take_width(text: &str, max_width: usize, measure: &impl Fn(&str) -> usize) -> (String, usize) {
+    let mut taken = String::new();
+    let mut width = 0;
+    let mut end = 0;
+    for cluster in clusters(text, measure) {
+        let cluster_width = measure(cluster);
+        if width + cluster_width > max_width && (max_width == 0 || end > 0) {
+            break;
+        }
+        taken.push_str(cluster);
+        width += cluster_width;
+        end += cluster.len();
+    }
+    (taken, end)
+}
+
+/// `text` split into user-perceived characters: a character together with the zero-width
+/// characters after it and anything a zero-width joiner attaches.
+fn clusters<'a>(
+    text: &'a str,
+    measure: &'a impl Fn(&str) -> usize,
+) -> impl Iterator<Item = &'a str> + 'a {
+    let mut indices = text.char_indices().peekable();
+    std::iter::from_fn(move || {
+        let (start, first) = indices.next()?;
+        let mut joined = first == ZERO_WIDTH_JOINER;
+        let mut end = start + first.len_utf8();
+        while let Some(&(index, ch)) = indices.peek() {
+            let attaches = joined
+                || ch == ZERO_WIDTH_JOINER
+                || measure(&text[index..index + ch.len_utf8()]) == 0;
+            if !attaches {
+                break;
+            }
+            joined = ch == ZERO_WIDTH_JOINER;
+            end = index + ch.len_utf8();
+            indices.next();
+        }
+        Some(&text[start..end])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(text: &str) -> usize {
+        text.chars().count()
+    }
+
+    /// Display columns with CJK counted double and combining marks and joiners counted zero.
+    fn columns(text: &str) -> usize {
+        text.chars()
+            .map(|ch| match ch {
+                '\u{0300}'..='\u{036F}' | '\u{200D}' | '\u{FE0F}' => 0,
+                ch if crate::is_east_asian_wide(ch) => 2,
+                _ => 1,
+            })
+            .sum()
+    }
+
+    #[test]
+    fn modes_break_overlong_words_differently() {
+        let text = "read configuration file";
+        assert_eq!(
+            wrap_label(text, MermaidWrapMode::Word, 8, 5, chars),
+            ["read", "configu…", "file"]
+        );
+        assert_eq!(
+            wrap_label(text, MermaidWrapMode::WordChar, 8, 5, chars),
+            ["read", "configur", "ation", "file"]
+        );
+        assert_eq!(
+            wrap_label(text, MermaidWrapMode::Char, 8, 5, chars),
+            ["read con", "figurati", "on file"]
+        );
+        assert_eq!(
+            wrap_label(text, MermaidWrapMode::None, 8, 5, chars),
+            ["read co…"]
+        );
+    }
+
+    #[test]
+    fn dropped_lines_end_the_last_kept_line_with_an_ellipsis() {
+        assert_eq!(
+            wrap_label("one two three four", MermaidWrapMode::WordChar, 9, 2, chars),
+            ["one two", "three…"]
+        );
+        assert_eq!(
+            wrap_label("abcdef ghi", MermaidWrapMode::Word, 6, 1, chars),
+            ["abcde…"]
+        );
+        assert_eq!(
+            wrap_label("first\nsecond\nthird", MermaidWrapMode::Word, 20, 2, chars),
+            ["first", "second…"]
+        );
+        assert_eq!(
+            wrap_label("  spaced \t out  ", MermaidWrapMode::Word, 20, 1, chars),
+            ["spaced out"]
+        );
+        assert_eq!(wrap_label("", MermaidWrapMode::WordChar, 4, 2, chars), [""]);
+    }
+
+    #[test]
+    fn wide_and_combining_characters_are_measured_as_clusters() {
+        assert_eq!(
+            wrap_label("界面界面界", MermaidWrapMode::Char, 5, 3, columns),
+            ["界面", "界面", "界"]
+        );
+        let accented = "e\u{301}e\u{301}e\u{301}";
+        let lines = wrap_label(accented, MermaidWrapMode::Char, 2, 2, columns);
+        assert_eq!(lines, ["e\u{301}e\u{301}", "e\u{301}"]);
+        assert_eq!(ellipsize("界面界面", 4, columns), "界…");
+        let family = "👩\u{200D}👩\u{200D}👧 family";
+        assert_eq!(
+            wrap_label(family, MermaidWrapMode::Char, 1, 1, chars),
+            ["…"]
+        );
+        assert!(
+            wrap_label(family, MermaidWrapMode::Char, 3, 3, chars)[0]
+                .starts_with("👩\u{200D}👩\u{200D}👧")
+        );
+    }
+
+    #[test]
+    fn the_marker_can_be_spelled_in_ascii() {
+        let marker = WRAP_ELLIPSIS_ASCII;
+        assert_eq!(
+            wrap_label_with_marker(
+                "read configuration file",
+                MermaidWrapMode::Word,
+                8,
+                2,
+                marker,
+                chars
+            ),
+            ["read", "confi..."]
+        );
+        assert_eq!(
+            wrap_label_with_marker("one two three", MermaidWrapMode::Word, 9, 1, marker, chars),
+            ["one tw..."]
+        );
+        assert_eq!(ellipsize_with_marker("abcdef", 5, marker, chars), "ab...");
+        assert_eq!(ellipsize_with_marker("abc", 5, marker, chars), "abc");
+    }
+}
@@ -0,0 +1,112 @@
+//! Criterion benchmarks for the force layout's Barnes-Hut repulsion.
+//!
+//! Each input is measured two ways:
+//! - Displacement error: summed |approx - exact| over summed |exact| per theta, printed once
+//!   before timing.
+//! - Runtime: the exact O(n^2) sum against the quadtree at each theta.
+//!
+//! Two node distributions are used. `uniform` scatters nodes over a square. `hubs` packs most
+//! nodes into a few dense hubs with sparse spokes, the shape that piled nodes into a handful of
+//! cells under the former uniform-grid approximation.
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use fm_layout::bench_internals::{force_repulsion_barnes_hut, force_repulsion_exact};
+use std::hint::black_box;
+
+/// `k^2` for the default spacing (node 80px, rank 80px).
+const K_SQ: f32 = 6400.0;
+
+const THETAS: [f32; 4] = [0.5, 0.8, 1.0, 1.2];
+
+/// Deterministic xorshift stream in `[0, 1)`.
+fn unit_stream(seed: u64) -> impl FnMut() -> f32 {
+    let mut state = seed.max(1);
+    move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state >> 40) as f32 / (1_u64 << 24) as f32
+    }
+}
+
+fn uniform_positions(n: usize, seed: u64) -> Vec<(f32, f32)> {
+    let mut next = unit_stream(seed);
+    let side = (n as f32).sqrt() * 120.0;
+    (0..n).map(|_| (next() * side, next() * side)).collect()
+}
+
+/// `hub_count` hubs far apart, each ringed by nodes at exponentially distributed radii.
+fn hub_positions(n: usize, hub_count: usize, seed: u64) -> Vec<(f32, f32)> {
+    let mut next = unit_stream(seed);
+    let side = (n as f32).sqrt() * 240.0;
+    let hubs: Vec<(f32, f32)> = (0..hub_count)
+        .map(|_| (next() * side, next() * side))
+        .collect();
+    (0..n)
+        .map(|i| {
+            let (hx, hy) = hubs[i % hub_count];
+            let angle = next() * std::f32::consts::TAU;
+            let radius = -(1.0 - next()).ln() * 40.0;
+            (
+                radius.mul_add(angle.cos(), hx),
+                radius.mul_add(angle.sin(), hy),
+            )
+        })
+        .collect()
+}
+
+fn relative_error(approx: &[(f32, f32)], exact: &[(f32, f32)]) -> f64 {
+    let error: f64 = approx
+        .iter()
+        .zip(exact)
+        .map(|(a, e)| f64::from((a.0 - e.0).hypot(a.1 - e.1)))
+        .sum();
+    let total: f64 = exact.iter().map(|e| f64::from(e.0.hypot(e.1))).sum();
+    if total > 0.0 { error / total } else { 0.0 }
+}
+
+fn inputs() -> Vec<(String, Vec<(f32, f32)>)> {
+    let mut inputs = Vec::new();
+    for n in [500, 2000] {
+        inputs.push((
+            format!("uniform_{n}"),
+            uniform_positions(n, 0x5eed + n as u64),
+        ));
+        inputs.push((format!("hubs_{n}"), hub_positions(n, 4, 0xb0b + n as u64)));
+    }
+    inputs
+}
+
+fn benchmark_repulsion(c: &mut Criterion) {
+    let mut group = c.benchmark_group("force_repulsion");
+    group.sample_size(20);
+
+    for (name, positions) in inputs() {
+        let exact = force_repulsion_exact(&positions, K_SQ);
+        for theta in THETAS {
+            let approx = force_repulsion_barnes_hut(&positions, K_SQ, theta);
+            println!(
+                "force_repulsion/{name}: theta={theta} relative displacement error {:.5}",
+                relative_error(&approx, &exact)
+            );
+        }
+
+        group.bench_with_input(BenchmarkId::new("exact", &name), &positions, |b, p| {
+            b.iter(|| force_repulsion_exact(black_box(p), K_SQ));
+        });
+        for theta in THETAS {
+            group.bench_with_input(
+                BenchmarkId::new(format!("barnes_hut_theta_{theta}"), &name),
+                &positions,
+                |b, p| {
+                    b.iter(|| force_repulsion_barnes_hut(black_box(p), K_SQ, theta));
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_repulsion);
+criterion_main!(benches);
@@ -469,6 +469,10 @@ mod tests {
                 collapsed_clusters: 0,
                 reversed_edge_total_length: 0.0,
                 total_edge_length: 0.0,
+                total_rank_span: 0,
+                total_rank_span_before_optimization: 0,
+                overlaps_resolved: 0,
+                budget_exceeded: false,
                 phase_iterations: 0,
             },
             extensions: crate::LayoutExtensions::default(),
@@ -0,0 +1,549 @@
+//! Geometric layouts for pie, quadrant, gantt and xy charts.
+//!
+//! Charts are not graphs: a pie is a set of angular wedges, a quadrant chart is a scatter plot
+//! on the unit square, a gantt chart is a set of day ranges and an xy chart is a value range over
+//! categories. [`crate::layout_diagram`] still places one label box per slice, point, task or
+//! value so generic consumers (hit testing, source maps, the layout JSON) keep working, but those
+//! boxes are derived from the geometry here. Renderers that draw the chart itself should use
+//! [`layout_chart`] for slice angles, plot coordinates, task dates and value ranges instead of
+//! re-deriving them.
+
+use std::f32::consts::{PI, TAU};
+
+use fm_core::{DiagramType, MermaidDiagramIr};
+use serde::{Deserialize, Serialize};
+
+use crate::{LayoutPoint, LayoutRect};
+
+/// Angle of the first slice's leading edge: 12 o'clock. Angles grow clockwise on screen (y down).
+pub const PIE_START_ANGLE: f32 = -PI / 2.0;
+
+/// One pie wedge. Angles are in radians in screen coordinates, so `start_angle` of the first
+/// slice is [`PIE_START_ANGLE`] and each slice starts where the previous one ends.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PieSliceLayout {
+    pub label: String,
+    /// Slice value, with negative values clamped to zero.
+    pub value: f32,
+    /// Share of the total (`0.0..=1.0`).
+    pub fraction: f32,
+    pub start_angle: f32,
+    pub end_angle: f32,
+}
+
+impl PieSliceLayout {
+    #[must_use]
+    pub fn sweep(&self) -> f32 {
+        self.end_angle - self.start_angle
+    }
+
+    /// Bisector of the wedge, where its label goes.
+    #[must_use]
+    pub fn mid_angle(&self) -> f32 {
+        self.start_angle + self.sweep() / 2.0
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PieLayout {
+    pub center: LayoutPoint,
+    pub radius: f32,
+    /// Radius at which slice labels are centred.
+    pub label_radius: f32,
+    /// Slices in source order; slice `i` belongs to IR node `i`.
+    pub slices: Vec<PieSliceLayout>,
+}
+
+impl PieLayout {
+    /// Point at `angle` on the circle of `radius` around the pie's centre.
+    #[must_use]
+    pub fn point_at(&self, angle: f32, radius: f32) -> LayoutPoint {
+        LayoutPoint {
+            x: self.center.x + radius * angle.cos(),
+            y: self.center.y + radius * angle.sin(),
+        }
+    }
+
+    /// Index of the slice covering the screen-space `angle` (any real angle; it is wrapped into
+    /// the pie's turn). Zero-value slices cover nothing.
+    #[must_use]
+    pub fn slice_at_angle(&self, angle: f32) -> Option<usize> {
+        let angle = PIE_START_ANGLE + (angle - PIE_START_ANGLE).rem_euclid(TAU);
+        self.slices
+            .iter()
+            .position(|slice| angle >= slice.start_angle && angle < slice.end_angle)
+    }
+}
+
+/// One data point of a quadrant chart.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QuadrantPointLayout {
+    pub label: String,
+    /// Normalized position in `0.0..=1.0`, with `y = 1.0` at the top as in the source.
+    pub x: f32,
+    pub y: f32,
+    /// The same position in layout coordinates inside [`QuadrantLayout::plot`].
+    pub position: LayoutPoint,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QuadrantLayout {
+    /// The square plot area spanned by the two axes.
+    pub plot: LayoutRect,
+    /// Points in source order; point `i` belongs to IR node `i`.
+    pub points: Vec<QuadrantPointLayout>,
+    /// Plot plus margins for axis labels.
+    pub bounds: LayoutRect,
+}
+
+impl QuadrantLayout {
+    /// Map a normalized `(x, y)` (`y = 1.0` at the top) into the plot area.
+    #[must_use]
+    pub fn position(&self, x: f32, y: f32) -> LayoutPoint {
+        LayoutPoint {
+            x: self.plot.x + x * self.plot.width,
+            y: self.plot.y + (1.0 - y) * self.plot.height,
+        }
+    }
+
+    /// Area of quadrant `1..=4` in Mermaid numbering: 1 top-right, 2 top-left, 3 bottom-left,
+    /// 4 bottom-right.
+    #[must_use]
+    pub fn quadrant(&self, quadrant: usize) -> Option<LayoutRect> {
+        let half_width = self.plot.width / 2.0;
+        let half_height = self.plot.height / 2.0;
+        let (column, row) = match quadrant {
+            1 => (1.0, 0.0),
+            2 => (0.0, 0.0),
+            3 => (0.0, 1.0),
+            4 => (1.0, 1.0),
+            _ => return None,
+        };
+        Some(LayoutRect {
+            x: half_width.mul_add(column, self.plot.x),
+            y: half_height.mul_add(row, self.plot.y),
+            width: half_width,
+            height: half_height,
+        })
+    }
+}
+
+/// One scheduled gantt task. Days count from the Unix epoch.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct GanttTaskLayout {
+    /// Index into `IrGanttMeta::tasks`.
+    pub task_index: usize,
+    pub start_day: i32,
+    /// First day after the task, past any excluded days it spans; `start_day` for milestones.
+    pub end_day: i32,
+    pub milestone: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct GanttLayout {
+    /// Earliest task start.
+    pub first_day: i32,
+    /// Last day any task occupies.
+    pub last_day: i32,
+    /// Tasks in source order.
+    pub tasks: Vec<GanttTaskLayout>,
+}
+
+impl GanttLayout {
+    /// `YYYY-MM-DD` for a day number, as on the layout's axis ticks.
+    #[must_use]
+    pub fn date_label(day: i32) -> String {
+        crate::format_gantt_axis_tick(day)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct XyChartLayout {
+    /// Value at the bottom of the plot: the axis minimum, or the smallest value (and at most `0`).
+    pub y_min: f32,
+    /// Value at the top of the plot: the axis maximum, or the largest value (and at least `0`).
+    pub y_max: f32,
+    /// One label per category. Numeric x axes, and series longer than the category list, get
+    /// generated labels.
+    pub categories: Vec<String>,
+}
+
+impl XyChartLayout {
+    /// Where `value` falls between `y_min` (`0.0`) and `y_max` (`1.0`), clamped to the plot.
+    #[must_use]
+    pub fn fraction(&self, value: f32) -> f32 {
+        ((value - self.y_min) / (self.y_max - self.y_min).max(f32::EPSILON)).clamp(0.0, 1.0)
+    }
+
+    /// Step between round tick values (1, 2 or 5 times a power of ten) that splits the value range
+    /// into at most `max_ticks` intervals.
+    #[must_use]
+    pub fn tick_step(&self, max_ticks: usize) -> f32 {
+        let raw = (self.y_max - self.y_min) / max_ticks.max(1) as f32;
+        if raw <= 0.0 || !raw.is_finite() {
+            return 1.0;
+        }
+        let magnitude = 10.0_f32.powf(raw.log10().floor());
+        let multiple = [1.0, 2.0, 5.0]
+            .into_iter()
+            .find(|&multiple| multiple * magnitude >= raw)
+            .unwrap_or(10.0);
+        multiple * magnitude
+    }
+
+    /// Tick label text: whole numbers without decimals, anything else to one place.
+    #[must_use]
+    pub fn tick_label(value: f32) -> String {
+        if (value - value.round()).abs() < 0.0001 {
+            format!("{value:.0}")
+        } else {
+            format!("{value:.1}")
+        }
+    }
+}
+
+/// Chart geometry for diagrams that are not node-link graphs.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ChartLayout {
+    Pie(PieLayout),
+    Quadrant(QuadrantLayout),
+    Gantt(GanttLayout),
+    XyChart(XyChartLayout),
+}
+
+/// Chart geometry for pie, quadrant, gantt and xy charts; `None` for every other diagram type and
+/// for gantt charts without tasks or xy charts without series.
+#[must_use]
+pub fn layout_chart(ir: &MermaidDiagramIr) -> Option<ChartLayout> {
+    match ir.diagram_type {
+        DiagramType::Pie => Some(ChartLayout::Pie(layout_pie(ir))),
+        DiagramType::QuadrantChart => Some(ChartLayout::Quadrant(layout_quadrant(ir))),
+        DiagramType::Gantt => layout_gantt(ir).map(ChartLayout::Gantt),
+        DiagramType::XyChart => layout_xychart(ir).map(ChartLayout::XyChart),
+        _ => None,
+    }
+}
+
+/// Pie geometry: the radius grows with the slice count, and slices without data in
+/// `ir.pie_meta` get equal shares.
+#[must_use]
+pub fn layout_pie(ir: &MermaidDiagramIr) -> PieLayout {
+    let node_count = ir.nodes.len();
+    let base_radius = 100.0_f32 + (node_count as f32 * 8.0).min(100.0);
+    let radius = base_radius.clamp(80.0, 220.0);
+
+    let entries: Vec<(String, f32)> = match ir.pie_meta.as_ref() {
+        Some(pie) => pie
+            .slices
+            .iter()
+            .map(|slice| (slice.label.clone(), slice.value.max(0.0)))
+            .collect(),
+        None => ir
+            .nodes
+            .iter()
+            .map(|node| (crate::display_node_label(ir, node), 1.0))
+            .collect(),
+    };
+    let total: f32 = entries
+        .iter()
+        .map(|(_, value)| value)
+        .sum::<f32>()
+        .max(f32::EPSILON);
+
+    let mut angle = PIE_START_ANGLE;
+    let slices = entries
+        .into_iter()
+        .map(|(label, value)| {
+            let sweep = (value / total) * 2.0 * PI;
+            let slice = PieSliceLayout {
+                label,
+                value,
+                fraction: value / total,
+                start_angle: angle,
+                end_angle: angle + sweep,
+            };
+            angle += sweep;
+            slice
+        })
+        .collect();
+
+    PieLayout {
+        center: LayoutPoint {
+            x: radius + 70.0,
+            y: radius + 50.0,
+        },
+        radius,
+        label_radius: radius + 50.0,
+        slices,
+    }
+}
+
+/// Quadrant geometry: a square plot that grows with the point count, with a left margin wide
+/// enough for the x-axis start label.
+#[must_use]
+pub fn layout_quadrant(ir: &MermaidDiagramIr) -> QuadrantLayout {
+    let metrics = fm_core::FontMetrics::default_metrics();
+    let base_size = 300.0_f32 + (ir.nodes.len() as f32 * 15.0).min(200.0);
+    let plot_size = base_size.clamp(200.0, 600.0);
+    let axis_label_width = ir
+        .quadrant_meta
+        .as_ref()
+        .and_then(|meta| meta.x_axis_left.as_ref())
+        .map_or(0.0, |label| metrics.estimate_dimensions(label).0);
+    let plot = LayoutRect {
+        x: (axis_label_width + 20.0).clamp(50.0, 120.0),
+        y: 60.0,
+        width: plot_size,
+        height: plot_size,
+    };
+
+    let mut layout = QuadrantLayout {
+        plot,
+        points: Vec::new(),
+        bounds: LayoutRect {
+            x: 0.0,
+            y: 0.0,
+            width: plot.x + plot.width + 40.0,
+            height: plot.y + plot.height + 40.0,
+        },
+    };
+    let normalize = |value: f32| {
+        if value.is_finite() {
+            value.clamp(0.0, 1.0)
+        } else {
+            0.5
+        }
+    };
+    layout.points = ir
+        .quadrant_meta
+        .as_ref()
+        .map(|meta| &meta.points[..])
+        .unwrap_or_default()
+        .iter()
+        .map(|point| {
+            let (x, y) = (normalize(point.x), normalize(point.y));
+            QuadrantPointLayout {
+                label: point.label.clone(),
+                x,
+                y,
+                position: layout.position(x, y),
+            }
+        })
+        .collect();
+    layout
+}
+
+/// Gantt schedule: the same start and end days the gantt layout positions its task boxes by.
+/// `None` without `ir.gantt_meta` tasks.
+#[must_use]
+pub fn layout_gantt(ir: &MermaidDiagramIr) -> Option<GanttLayout> {
+    let meta = ir
+        .gantt_meta
+        .as_ref()
+        .filter(|meta| !meta.tasks.is_empty())?;
+    let schedule = crate::schedule_gantt_tasks(meta);
+    let tasks: Vec<GanttTaskLayout> = (0..meta.tasks.len())
+        .map(|task_index| GanttTaskLayout {
+            task_index,
+            start_day: schedule.start_days[task_index],
+            end_day: schedule.end_exclusive_days[task_index],
+            milestone: schedule.milestones[task_index],
+        })
+        .collect();
+    Some(GanttLayout {
+        first_day: tasks.iter().map(|task| task.start_day).min()?,
+        last_day: tasks
+            .iter()
+            .map(|task| (task.end_day - 1).max(task.start_day))
+            .max()?,
+        tasks,
+    })
+}
+
+/// xy chart value range and category labels: the same range the xy chart layout scales its bars
+/// and points to. `None` without `ir.xy_chart_meta` series.
+#[must_use]
+pub fn layout_xychart(ir: &MermaidDiagramIr) -> Option<XyChartLayout> {
+    let meta = ir
+        .xy_chart_meta
+        .as_ref()
+        .filter(|meta| !meta.series.is_empty())?;
+    let (y_min, y_max) = crate::resolve_xychart_y_domain(meta);
+    let count = crate::xy_chart_category_count(meta);
+    let categories = if meta.x_axis.categories.is_empty() {
+        let x_min = meta.x_axis.min.unwrap_or(0.0);
+        let x_max = meta
+            .x_axis
+            .max
+            .unwrap_or_else(|| count.saturating_sub(1) as f32);
+        let step = (x_max - x_min) / (count.saturating_sub(1) as f32).max(1.0);
+        (0..count)
+            .map(|index| XyChartLayout::tick_label(step.mul_add(index as f32, x_min)))
+            .collect()
+    } else {
+        let mut categories = meta.x_axis.categories.clone();
+        categories.extend((categories.len()..count).map(|index| (index + 1).to_string()));
+        categories
+    };
+    Some(XyChartLayout {
+        y_min,
+        y_max,
+        categories,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fm_core::{
+        GanttDate, GanttExclude, GanttTaskType, IrGanttMeta, IrGanttTask, IrNode, IrPieMeta,
+        IrPieSlice, IrQuadrantMeta, IrQuadrantPoint, IrXyAxis, IrXyChartMeta, IrXySeries,
+    };
+
+    #[test]
+    fn pie_slices_cover_one_clockwise_turn_in_proportion() {
+        let mut ir = MermaidDiagramIr::empty(DiagramType::Pie);
+        let mut slices = Vec::new();
+        for (label, value) in [("Dogs", 3.0), ("Cats", 1.0), ("None", 0.0)] {
+            ir.nodes.push(IrNode {
+                id: label.to_string(),
+                ..IrNode::default()
+            });
+            slices.push(IrPieSlice {
+                label: label.to_string(),
+                value,
+            });
+        }
+        ir.pie_meta = Some(IrPieMeta {
+            slices,
+            ..IrPieMeta::default()
+        });
+
+        let Some(ChartLayout::Pie(pie)) = layout_chart(&ir) else {
+            panic!("expected a pie layout");
+        };
+        let fractions: Vec<f32> = pie.slices.iter().map(|slice| slice.fraction).collect();
+        assert_eq!(fractions, vec![0.75, 0.25, 0.0]);
+        assert_eq!(pie.slices[0].start_angle, PIE_START_ANGLE);
+        assert_eq!(pie.slices[1].start_angle, pie.slices[0].end_angle);
+        assert!((pie.slices[2].end_angle - (PIE_START_ANGLE + TAU)).abs() < 1e-5);
+
+        // Dogs runs clockwise from 12 o'clock to 9 o'clock, Cats fills the last quarter.
+        assert_eq!(pie.slice_at_angle(PI / 2.0), Some(0));
+        assert_eq!(pie.slice_at_angle(1.25 * PI), Some(1));
+        assert_eq!(pie.slice_at_angle(-0.75 * PI), Some(1));
+        let top = pie.point_at(PIE_START_ANGLE, pie.radius);
+        assert!((top.x - pie.center.x).abs() < 1e-3);
+        assert!((top.y - (pie.center.y - pie.radius)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn quadrant_points_map_into_the_plot_with_y_up() {
+        let mut ir = MermaidDiagramIr::empty(DiagramType::QuadrantChart);
+        let points = vec![
+            IrQuadrantPoint {
+                label: "A".to_string(),
+                x: 0.25,
+                y: 0.75,
+            },
+            IrQuadrantPoint {
+                label: "B".to_string(),
+                x: 2.0,
+                y: f32::NAN,
+            },
+        ];
+        for point in &points {
+            ir.nodes.push(IrNode {
+                id: point.label.clone(),
+                ..IrNode::default()
+            });
+        }
+        ir.quadrant_meta = Some(IrQuadrantMeta {
+            points,
+            ..IrQuadrantMeta::default()
+        });
+
+        let Some(ChartLayout::Quadrant(quadrant)) = layout_chart(&ir) else {
+            panic!("expected a quadrant layout");
+        };
+        let plot = quadrant.plot;
+        assert_eq!(plot.width, plot.height);
+        let a = &quadrant.points[0];
+        assert_eq!((a.x, a.y), (0.25, 0.75));
+        assert_eq!(a.position.x, plot.x + plot.width * 0.25);
+        assert_eq!(a.position.y, plot.y + plot.height * 0.25);
+        // A sits in the top-left quadrant.
+        let top_left = quadrant.quadrant(2).unwrap();
+        assert!(a.position.x < top_left.x + top_left.width);
+        assert!(a.position.y < top_left.y + top_left.height);
+        // Out-of-range and non-finite coordinates are clamped or centred.
+        assert_eq!((quadrant.points[1].x, quadrant.points[1].y), (1.0, 0.5));
+        assert!(layout_chart(&MermaidDiagramIr::empty(DiagramType::Flowchart)).is_none());
+    }
+
+    #[test]
+    fn gantt_schedule_follows_dependencies_and_skips_excluded_days() {
+        let mut ir = MermaidDiagramIr::empty(DiagramType::Gantt);
+        ir.gantt_meta = Some(IrGanttMeta {
+            excludes: vec![GanttExclude::Dates(vec!["2026-02-06".to_string()])],
+            tasks: vec![
+                // Three working days around the excluded 2026-02-06 end on the 8th.
+                IrGanttTask {
+                    task_id: Some("build".to_string()),
+                    start: Some(GanttDate::Absolute("2026-02-05".to_string())),
+                    end: Some(GanttDate::DurationDays(3)),
+                    ..IrGanttTask::default()
+                },
+                IrGanttTask {
+                    start: Some(GanttDate::AfterTask("build".to_string())),
+                    end: Some(GanttDate::DurationDays(0)),
+                    task_type: GanttTaskType::Milestone,
+                    ..IrGanttTask::default()
+                },
+            ],
+            ..IrGanttMeta::default()
+        });
+
+        let Some(ChartLayout::Gantt(gantt)) = layout_chart(&ir) else {
+            panic!("expected a gantt layout");
+        };
+        let build = &gantt.tasks[0];
+        assert_eq!(GanttLayout::date_label(build.start_day), "2026-02-05");
+        assert_eq!(GanttLayout::date_label(build.end_day), "2026-02-09");
+        let release = &gantt.tasks[1];
+        assert!(release.milestone);
+        assert_eq!(release.start_day, build.end_day);
+        assert_eq!(release.end_day, release.start_day);
+        assert_eq!(gantt.first_day, build.start_day);
+        assert_eq!(gantt.last_day, release.start_day);
+        assert!(layout_gantt(&MermaidDiagramIr::empty(DiagramType::Gantt)).is_none());
+    }
+
+    #[test]
+    fn xychart_range_starts_at_zero_and_pads_categories() {
+        let mut ir = MermaidDiagramIr::empty(DiagramType::XyChart);
+        ir.xy_chart_meta = Some(IrXyChartMeta {
+            x_axis: IrXyAxis {
+                categories: vec!["jan".to_string(), "feb".to_string()],
+                ..IrXyAxis::default()
+            },
+            series: vec![IrXySeries {
+                values: vec![12.0, 30.0, 47.0],
+                ..IrXySeries::default()
+            }],
+            ..IrXyChartMeta::default()
+        });
+
+        let Some(ChartLayout::XyChart(xy)) = layout_chart(&ir) else {
+            panic!("expected an xy chart layout");
+        };
+        assert_eq!((xy.y_min, xy.y_max), (0.0, 47.0));
+        assert_eq!(xy.categories, ["jan", "feb", "3"]);
+        assert_eq!(xy.fraction(47.0), 1.0);
+        assert_eq!(xy.fraction(-5.0), 0.0);
+        assert_eq!(xy.tick_step(5), 10.0);
+        assert_eq!(xy.tick_step(2), 50.0);
+        assert_eq!(XyChartLayout::tick_label(2.5), "2.5");
+        assert!(layout_xychart(&MermaidDiagramIr::empty(DiagramType::XyChart)).is_none());
+    }
+}
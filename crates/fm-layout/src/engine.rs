@@ -0,0 +1,186 @@
+//! Pluggable layout engines.
+//!
+//! Every built-in algorithm is reachable through the [`LayoutEngine`] trait, and a
+//! [`LayoutEngineRegistry`] maps [`LayoutAlgorithm`]s to engines. Downstream crates register their
+//! own engine (say, a bridge to an external layouter) under an algorithm to replace the built-in
+//! one, then lay out with [`crate::layout_diagram_traced_with_engines`]: dispatch, guardrails, and
+//! tracing behave exactly as for the built-in engines, only the selected algorithm's placement
+//! step is swapped.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use fm_core::MermaidDiagramIr;
+
+use crate::{LayoutAlgorithm, LayoutConfig, TracedLayout};
+
+/// Computes a layout for one diagram.
+pub trait LayoutEngine: Send + Sync {
+    fn layout(&self, ir: &MermaidDiagramIr, config: &LayoutConfig) -> TracedLayout;
+}
+
+/// One of the crate's own algorithms, as a [`LayoutEngine`].
+///
+/// [`LayoutAlgorithm::Auto`] runs the Sugiyama engine; dispatch resolves `Auto` before an engine
+/// is consulted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuiltinLayoutEngine(pub LayoutAlgorithm);
+
+impl LayoutEngine for BuiltinLayoutEngine {
+    fn layout(&self, ir: &MermaidDiagramIr, config: &LayoutConfig) -> TracedLayout {
+        crate::run_builtin_layout_algorithm(ir, self.0, config)
+    }
+}
+
+/// Layout engines keyed by algorithm. Algorithms without a registration use their built-in
+/// engine, so an empty registry reproduces the default pipeline. Cloning is cheap: engines are
+/// shared behind `Arc`.
+#[derive(Clone, Default)]
+pub struct LayoutEngineRegistry {
+    engines: BTreeMap<LayoutAlgorithm, Arc<dyn LayoutEngine>>,
+}
+
+impl LayoutEngineRegistry {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `engine` for `algorithm`, returning any engine it replaces.
+    pub fn register(
+        &mut self,
+        algorithm: LayoutAlgorithm,
+        engine: Arc<dyn LayoutEngine>,
+    ) -> Option<Arc<dyn LayoutEngine>> {
+        self.engines.insert(algorithm, engine)
+    }
+
+    /// Builder-style [`Self::register`].
+    #[must_use]
+    pub fn with(mut self, algorithm: LayoutAlgorithm, engine: Arc<dyn LayoutEngine>) -> Self {
+        self.register(algorithm, engine);
+        self
+    }
+
+    /// Remove the engine registered for `algorithm`, restoring the built-in one.
+    pub fn unregister(&mut self, algorithm: LayoutAlgorithm) -> Option<Arc<dyn LayoutEngine>> {
+        self.engines.remove(&algorithm)
+    }
+
+    /// The engine registered for `algorithm`, if any.
+    #[must_use]
+    pub fn get(&self, algorithm: LayoutAlgorithm) -> Option<&dyn LayoutEngine> {
+        self.engines.get(&algorithm).map(AsRef::as_ref)
+    }
+
+    /// Lay out `ir` with the engine for `algorithm`: the registered one, else the built-in.
+    #[must_use]
+    pub fn layout(
+        &self,
+        ir: &MermaidDiagramIr,
+        algorithm: LayoutAlgorithm,
+        config: &LayoutConfig,
+    ) -> TracedLayout {
+        match self.get(algorithm) {
+            Some(engine) => engine.layout(ir, config),
+            None => BuiltinLayoutEngine(algorithm).layout(ir, config),
+        }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.engines.is_empty()
+    }
+
+    /// Algorithms with a registered engine, in declaration order.
+    pub fn algorithms(&self) -> impl Iterator<Item = LayoutAlgorithm> + '_ {
+        self.engines.keys().copied()
+    }
+}
+
+impl fmt::Debug for LayoutEngineRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set()
+            .entries(self.algorithms().map(LayoutAlgorithm::as_str))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LayoutGuardrails, layout_diagram_traced_with_engines};
+    use fm_core::{DiagramType, IrNode};
+
+    /// Stacks every node in one column by delegating to the grid engine and rewriting x.
+    struct Column;
+
+    impl LayoutEngine for Column {
+        fn layout(&self, ir: &MermaidDiagramIr, config: &LayoutConfig) -> TracedLayout {
+            let mut traced = BuiltinLayoutEngine(LayoutAlgorithm::Grid).layout(ir, config);
+            let layout = Arc::make_mut(&mut traced.layout);
+            let mut y = 0.0;
+            for node in &mut layout.nodes {
+                node.bounds.x = 0.0;
+                node.bounds.y = y;
+                y += node.bounds.height + 10.0;
+            }
+            traced
+        }
+    }
+
+    fn nodes_ir(count: usize) -> MermaidDiagramIr {
+        let mut ir = MermaidDiagramIr::empty(DiagramType::Flowchart);
+        for index in 0..count {
+            ir.nodes.push(IrNode {
+                id: format!("N{index}"),
+                ..IrNode::default()
+            });
+        }
+        ir
+    }
+
+    #[test]
+    fn empty_registry_matches_builtin_pipeline() {
+        let ir = nodes_ir(3);
+        let registry = LayoutEngineRegistry::new();
+        let via_registry = layout_diagram_traced_with_engines(
+            &ir,
+            LayoutAlgorithm::Sugiyama,
+            LayoutConfig::default(),
+            LayoutGuardrails::default(),
+            &registry,
+        );
+        let builtin = crate::layout_diagram_traced_with_config_and_guardrails(
+            &ir,
+            LayoutAlgorithm::Sugiyama,
+            LayoutConfig::default(),
+            LayoutGuardrails::default(),
+        );
+        assert_eq!(via_registry.layout.nodes, builtin.layout.nodes);
+        assert_eq!(via_registry.layout.edges, builtin.layout.edges);
+    }
+
+    #[test]
+    fn registered_engine_replaces_selected_algorithm() {
+        let ir = nodes_ir(4);
+        let mut registry =
+            LayoutEngineRegistry::new().with(LayoutAlgorithm::Force, Arc::new(Column));
+        assert_eq!(format!("{registry:?}"), r#"{"force"}"#);
+
+        let traced = layout_diagram_traced_with_engines(
+            &ir,
+            LayoutAlgorithm::Force,
+            LayoutConfig::default(),
+            LayoutGuardrails::default(),
+            &registry,
+        );
+        assert_eq!(traced.trace.dispatch.selected, LayoutAlgorithm::Force);
+        assert_eq!(traced.trace.snapshots[0].stage, "dispatch");
+        assert!(traced.layout.nodes.iter().all(|node| node.bounds.x == 0.0));
+
+        assert!(registry.unregister(LayoutAlgorithm::Force).is_some());
+        assert!(registry.is_empty());
+    }
+}
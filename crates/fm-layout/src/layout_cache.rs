@@ -0,0 +1,343 @@
+//! Content-addressed cache of computed layouts.
+//!
+//! [`LayoutCache`] keys each traced layout by a hash of the whole IR (structure, labels, spans,
+//! direction, metadata) plus the layout config and guardrails, so re-rendering an unchanged
+//! diagram — a TUI redrawing on every resize or keystroke — skips the pipeline entirely. Unlike
+//! [`crate::IncrementalLayoutEngine`], which remembers only the last request, the cache holds
+//! several diagrams at once and evicts the least recently used one when full.
+
+use std::hash::{Hash, Hasher};
+use std::io;
+
+use fm_core::{FxHashMap, MermaidConfig, MermaidDiagramIr};
+use tracing::trace;
+
+use crate::{
+    IncrementalRecomputeTrace, LayoutAlgorithm, LayoutConfig, LayoutGuardrails, LayoutMemoKey,
+    TracedLayout, layout_diagram_traced_with_config_and_guardrails, layout_memo_key,
+};
+
+/// Hit/miss counters and occupancy of a [`LayoutCache`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LayoutCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub entries: usize,
+    pub capacity: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct LayoutCacheKey {
+    ir_hash: u128,
+    request: LayoutMemoKey,
+}
+
+#[derive(Debug, Clone)]
+struct LayoutCacheEntry {
+    traced: TracedLayout,
+    last_used: u64,
+}
+
+/// LRU cache of traced layouts keyed by IR content and layout request.
+#[derive(Debug, Clone)]
+pub struct LayoutCache {
+    enabled: bool,
+    capacity: usize,
+    entries: FxHashMap<LayoutCacheKey, LayoutCacheEntry>,
+    clock: u64,
+    stats: LayoutCacheStats,
+}
+
+impl Default for LayoutCache {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+impl LayoutCache {
+    /// Enough for a handful of diagrams on screen plus their recent edits.
+    pub const DEFAULT_CAPACITY: usize = 32;
+
+    /// An enabled cache holding at most `capacity` layouts (`0` disables caching).
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            enabled: capacity > 0,
+            capacity,
+            entries: FxHashMap::default(),
+            clock: 0,
+            stats: LayoutCacheStats {
+                capacity,
+                ..LayoutCacheStats::default()
+            },
+        }
+    }
+
+    /// A default-capacity cache that is only active when `config.cache_enabled` is set.
+    #[must_use]
+    pub fn from_config(config: &MermaidConfig) -> Self {
+        Self {
+            enabled: config.cache_enabled,
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub const fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Like [`layout_diagram_traced_with_config_and_guardrails`], but returns a stored layout when
+    /// the same IR was laid out with the same request before. Hits report
+    /// `query_type = "layout_cache_hit"` in the incremental trace. A disabled cache always
+    /// recomputes and records nothing.
+    pub fn layout_traced(
+        &mut self,
+        ir: &MermaidDiagramIr,
+        algorithm: LayoutAlgorithm,
+        config: LayoutConfig,
+        guardrails: LayoutGuardrails,
+    ) -> TracedLayout {
+        if !self.enabled {
+            return layout_diagram_traced_with_config_and_guardrails(
+                ir, algorithm, config, guardrails,
+            );
+        }
+        let key = LayoutCacheKey {
+            ir_hash: ir_content_hash(ir),
+            request: layout_memo_key(algorithm, &config, guardrails),
+        };
+        self.clock += 1;
+        if let Some(entry) = self.entries.get_mut(&key) {
+            entry.last_used = self.clock;
+            self.stats.hits += 1;
+            trace!(algorithm = algorithm.as_str(), "layout_cache.hit");
+            let mut traced = entry.traced.clone();
+            traced.trace.incremental = IncrementalRecomputeTrace {
+                query_type: "layout_cache_hit",
+                cache_hit: true,
+                recomputed_nodes: 0,
+                total_nodes: ir.nodes.len(),
+                recompute_duration_us: 0,
+            };
+            return traced;
+        }
+
+        self.stats.misses += 1;
+        trace!(algorithm = algorithm.as_str(), "layout_cache.miss");
+        let traced =
+            layout_diagram_traced_with_config_and_guardrails(ir, algorithm, config, guardrails);
+        // A partial layout is only valid for the run that ran out of budget.
+        if traced.layout.stats.budget_exceeded {
+            return traced;
+        }
+        if self.entries.len() >= self.capacity {
+            self.evict_least_recently_used();
+        }
+        self.entries.insert(
+            key,
+            LayoutCacheEntry {
+                traced: traced.clone(),
+                last_used: self.clock,
+            },
+        );
+        self.stats.entries = self.entries.len();
+        traced
+    }
+
+    /// Current counters; `entries` and `capacity` reflect the cache right now.
+    #[must_use]
+    pub fn cache_stats(&self) -> LayoutCacheStats {
+        self.stats
+    }
+
+    /// Drop every stored layout. Counters are kept.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.stats.entries = 0;
+    }
+
+    fn evict_least_recently_used(&mut self) {
+        let oldest = self
+            .entries
+            .iter()
+            .min_by_key(|(_, entry)| entry.last_used)
+            .map(|(key, _)| key.clone());
+        if let Some(key) = oldest {
+            self.entries.remove(&key);
+            self.stats.evictions += 1;
+        }
+    }
+}
+
+/// 128-bit FNV-1a over the IR's serialized form, two independently seeded 64-bit lanes.
+///
+/// Streaming the serde encoding covers every IR field without a hand-maintained field list, and
+/// the byte stream is identical across runs and platforms.
+fn ir_content_hash(ir: &MermaidDiagramIr) -> u128 {
+    let mut hasher = ContentHasher::default();
+    // Writing into the hasher cannot fail and the IR has no non-string map keys, so the only
+    // possible error is unreachable; fall back to hashing the debug form just in case.
+    if serde_json::to_writer(&mut hasher, ir).is_err() {
+        hasher = ContentHasher::default();
+        format!("{ir:?}").hash(&mut hasher);
+    }
+    hasher.finish_u128()
+}
+
+struct ContentHasher {
+    upper: u64,
+    lower: u64,
+}
+
+impl Default for ContentHasher {
+    fn default() -> Self {
+        Self {
+            upper: 0xcbf2_9ce4_8422_2325,
+            lower: 0x6c62_272e_07bb_0142,
+        }
+    }
+}
+
+impl ContentHasher {
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    const fn finish_u128(&self) -> u128 {
+        ((self.upper as u128) << 64) | self.lower as u128
+    }
+}
+
+impl Hasher for ContentHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.upper = (self.upper ^ u64::from(byte)).wrapping_mul(Self::PRIME);
+            self.lower = (self.lower ^ u64::from(byte.rotate_left(3))).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.upper ^ self.lower
+    }
+}
+
+impl io::Write for ContentHasher {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        Hasher::write(self, bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fm_core::{
+        ArrowType, DiagramType, IrEdge, IrEndpoint, IrLabel, IrLabelId, IrNode, IrNodeId,
+    };
+
+    fn chain_ir(labels: &[&str]) -> MermaidDiagramIr {
+        let mut ir = MermaidDiagramIr::empty(DiagramType::Flowchart);
+        for (index, text) in labels.iter().enumerate() {
+            ir.labels.push(IrLabel {
+                text: (*text).to_string(),
+                ..IrLabel::default()
+            });
+            ir.nodes.push(IrNode {
+                id: format!("N{index}"),
+                label: Some(IrLabelId(index)),
+                ..IrNode::default()
+            });
+            if index > 0 {
+                ir.edges.push(IrEdge {
+                    from: IrEndpoint::Node(IrNodeId(index - 1)),
+                    to: IrEndpoint::Node(IrNodeId(index)),
+                    arrow: ArrowType::Arrow,
+                    ..IrEdge::default()
+                });
+            }
+        }
+        ir
+    }
+
+    fn lay_out(cache: &mut LayoutCache, ir: &MermaidDiagramIr) -> TracedLayout {
+        cache.layout_traced(
+            ir,
+            LayoutAlgorithm::Auto,
+            LayoutConfig::default(),
+            LayoutGuardrails::default(),
+        )
+    }
+
+    #[test]
+    fn repeated_requests_hit_and_content_changes_miss() {
+        let mut cache = LayoutCache::new(4);
+        let ir = chain_ir(&["a", "b", "c"]);
+        let first = lay_out(&mut cache, &ir);
+        let second = lay_out(&mut cache, &ir);
+        assert_eq!(second.layout, first.layout);
+        assert!(second.trace.incremental.cache_hit);
+        assert_eq!(second.trace.incremental.query_type, "layout_cache_hit");
+
+        // A label edit and a direction change are both new keys.
+        let relabelled = chain_ir(&["a", "b", "a much longer label"]);
+        assert!(!lay_out(&mut cache, &relabelled).trace.incremental.cache_hit);
+        let mut turned = ir.clone();
+        turned.direction = fm_core::GraphDirection::LR;
+        assert!(!lay_out(&mut cache, &turned).trace.incremental.cache_hit);
+
+        // So is a different layout config for the same IR.
+        let spaced = LayoutConfig {
+            spacing: crate::LayoutSpacing {
+                node_spacing: 99.0,
+                ..crate::LayoutSpacing::default()
+            },
+            ..LayoutConfig::default()
+        };
+        let traced = cache.layout_traced(
+            &ir,
+            LayoutAlgorithm::Auto,
+            spaced,
+            LayoutGuardrails::default(),
+        );
+        assert!(!traced.trace.incremental.cache_hit);
+
+        let stats = cache.cache_stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 4, 4));
+    }
+
+    #[test]
+    fn full_cache_evicts_least_recently_used_layout() {
+        let mut cache = LayoutCache::new(2);
+        let (a, b, c) = (chain_ir(&["a"]), chain_ir(&["b"]), chain_ir(&["c"]));
+        lay_out(&mut cache, &a);
+        lay_out(&mut cache, &b);
+        lay_out(&mut cache, &a); // `b` is now the least recently used.
+        lay_out(&mut cache, &c);
+        assert!(lay_out(&mut cache, &a).trace.incremental.cache_hit);
+        assert!(!lay_out(&mut cache, &b).trace.incremental.cache_hit);
+        let stats = cache.cache_stats();
+        assert_eq!(stats.evictions, 2);
+        assert_eq!(stats.entries, 2);
+        assert_eq!(stats.capacity, 2);
+    }
+
+    #[test]
+    fn disabled_cache_always_recomputes() {
+        let config = MermaidConfig {
+            cache_enabled: false,
+            ..MermaidConfig::default()
+        };
+        let mut cache = LayoutCache::from_config(&config);
+        assert!(!cache.is_enabled());
+        let ir = chain_ir(&["a", "b"]);
+        lay_out(&mut cache, &ir);
+        assert!(!lay_out(&mut cache, &ir).trace.incremental.cache_hit);
+        assert_eq!(cache.cache_stats().entries, 0);
+        assert_eq!(cache.cache_stats().hits + cache.cache_stats().misses, 0);
+        assert!(LayoutCache::from_config(&MermaidConfig::default()).is_enabled());
+    }
+}
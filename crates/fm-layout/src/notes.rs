@@ -0,0 +1,218 @@
+//! Notes placed beside the nodes they annotate.
+//!
+//! State diagrams attach notes with `note right of X` and `note left of X`. Each note is sized
+//! from its text, set [`NOTE_GAP`] beside its node's box, and joined to the node by a leader
+//! line. The first note on a side is centred on the node; further notes on that side stack
+//! below it. Notes that would fall left of or above the layout origin shift the whole layout so
+//! renderers that anchor at the origin keep every note on the canvas.
+
+use std::collections::BTreeMap;
+
+use fm_core::{FontMetrics, MermaidDiagramIr, NotePosition};
+
+use crate::{DiagramLayout, LayoutNodeNote, LayoutPoint, LayoutRect, translate_rect, union_rect};
+
+/// Horizontal gap between a node and its notes; the leader line spans it.
+const NOTE_GAP: f32 = 28.0;
+/// Padding between a note's border and its text.
+const NOTE_PADDING: f32 = 10.0;
+/// Vertical gap between notes stacked on the same side of a node.
+const NOTE_STACK_GAP: f32 = 8.0;
+
+/// Place `ir.state_notes` beside their target nodes in `layout`. Notes whose target has no node
+/// box (for example one folded into a collapsed cluster) are skipped.
+pub(crate) fn attach_node_notes(
+    ir: &MermaidDiagramIr,
+    layout: &mut DiagramLayout,
+    metrics: &FontMetrics,
+) {
+    let mut next_y: BTreeMap<(usize, bool), f32> = BTreeMap::new();
+    let mut notes = Vec::new();
+    for note in &ir.state_notes {
+        let Some(node) = layout.nodes.iter().find(|node| node.node_id == note.target) else {
+            continue;
+        };
+        let position = note.note_position();
+        let right = position != NotePosition::LeftOf;
+        let node_bounds = node.bounds;
+        let node_center = node_bounds.center();
+        let (text_width, text_height) = metrics.estimate_dimensions(&note.text);
+        let width = text_width + NOTE_PADDING * 2.0;
+        let height = text_height + NOTE_PADDING * 2.0;
+
+        let side = (node.node_index, right);
+        let y = next_y
+            .get(&side)
+            .copied()
+            .unwrap_or(node_center.y - height / 2.0);
+        next_y.insert(side, y + height + NOTE_STACK_GAP);
+        let x = if right {
+            node_bounds.x + node_bounds.width + NOTE_GAP
+        } else {
+            node_bounds.x - NOTE_GAP - width
+        };
+
+        notes.push(LayoutNodeNote {
+            node_index: node.node_index,
+            position,
+            text: note.text.clone(),
+            bounds: LayoutRect {
+                x,
+                y,
+                width,
+                height,
+            },
+            leader_from: LayoutPoint {
+                x: if right {
+                    node_bounds.x + node_bounds.width
+                } else {
+                    node_bounds.x
+                },
+                y: node_center.y,
+            },
+            leader_to: LayoutPoint {
+                x: if right { x } else { x + width },
+                y: node_center.y.clamp(y, y + height),
+            },
+        });
+    }
+    if notes.is_empty() {
+        return;
+    }
+
+    let min_x = notes
+        .iter()
+        .map(|note| note.bounds.x)
+        .fold(f32::INFINITY, f32::min);
+    let min_y = notes
+        .iter()
+        .map(|note| note.bounds.y)
+        .fold(f32::INFINITY, f32::min);
+    // Renderers anchor node geometry at (0, 0), not at the bounds' margin-padded origin.
+    let dx = (-min_x).max(0.0);
+    let dy = (-min_y).max(0.0);
+    if dx > 0.0 || dy > 0.0 {
+        translate_layout(layout, dx, dy);
+        for note in &mut notes {
+            note.bounds = translate_rect(note.bounds, dx, dy);
+            note.leader_from = translate_point(note.leader_from, dx, dy);
+            note.leader_to = translate_point(note.leader_to, dx, dy);
+        }
+    }
+    for note in &notes {
+        layout.bounds = union_rect(layout.bounds, note.bounds);
+    }
+    layout.extensions.node_notes = notes;
+}
+
+fn translate_point(point: LayoutPoint, dx: f32, dy: f32) -> LayoutPoint {
+    LayoutPoint {
+        x: point.x + dx,
+        y: point.y + dy,
+    }
+}
+
+/// Move the node-diagram geometry of `layout` by `(dx, dy)`, keeping its bounds' origin.
+fn translate_layout(layout: &mut DiagramLayout, dx: f32, dy: f32) {
+    for node in &mut layout.nodes {
+        node.bounds = translate_rect(node.bounds, dx, dy);
+    }
+    for cluster in &mut layout.clusters {
+        cluster.bounds = translate_rect(cluster.bounds, dx, dy);
+    }
+    for cycle in &mut layout.cycle_clusters {
+        cycle.bounds = translate_rect(cycle.bounds, dx, dy);
+    }
+    for edge in &mut layout.edges {
+        for point in edge.points.iter_mut() {
+            *point = translate_point(*point, dx, dy);
+        }
+    }
+    for band in &mut layout.extensions.bands {
+        band.bounds = translate_rect(band.bounds, dx, dy);
+    }
+    for divider in &mut layout.extensions.cluster_dividers {
+        divider.start = translate_point(divider.start, dx, dy);
+        divider.end = translate_point(divider.end, dx, dy);
+    }
+    for region in &mut layout.dirty_regions {
+        *region = translate_rect(*region, dx, dy);
+    }
+    layout.bounds.width += dx;
+    layout.bounds.height += dy;
+}
+
+#[cfg(test)]
+mod tests {
+    use fm_core::{DiagramType, IrEdge, IrEndpoint, IrNode, IrNodeId, IrStateNote};
+
+    use super::*;
+    use crate::layout_diagram;
+
+    /// `Idle --> Busy` with a note per `(position, target)`.
+    fn state_layout(notes: &[(&str, &str)]) -> DiagramLayout {
+        let mut ir = MermaidDiagramIr::empty(DiagramType::State);
+        for id in ["Idle", "Busy"] {
+            ir.nodes.push(IrNode {
+                id: id.to_string(),
+                ..IrNode::default()
+            });
+        }
+        ir.edges.push(IrEdge {
+            from: IrEndpoint::Node(IrNodeId(0)),
+            to: IrEndpoint::Node(IrNodeId(1)),
+            ..IrEdge::default()
+        });
+        for (position, target) in notes {
+            ir.state_notes.push(IrStateNote {
+                target: (*target).to_string(),
+                position: (*position).to_string(),
+                text: format!("about {target}"),
+                ..IrStateNote::default()
+            });
+        }
+        layout_diagram(&ir)
+    }
+
+    #[test]
+    fn notes_sit_beside_their_node_with_a_leader() {
+        let layout = state_layout(&[("right", "Idle"), ("left", "Busy")]);
+        let notes = &layout.extensions.node_notes;
+        assert_eq!(notes.len(), 2);
+        let node = |index: usize| layout.nodes[index].bounds;
+
+        let idle = node(0);
+        let right = &notes[0];
+        assert_eq!(
+            (right.node_index, right.position),
+            (0, NotePosition::RightOf)
+        );
+        assert!(right.bounds.x >= idle.x + idle.width + NOTE_GAP - 0.01);
+        assert!((right.leader_from.x - (idle.x + idle.width)).abs() < 0.01);
+        assert!((right.leader_to.x - right.bounds.x).abs() < 0.01);
+
+        let busy = node(1);
+        let left = &notes[1];
+        assert_eq!((left.node_index, left.position), (1, NotePosition::LeftOf));
+        assert!(left.bounds.x + left.bounds.width <= busy.x - NOTE_GAP + 0.01);
+        assert!((left.leader_from.x - busy.x).abs() < 0.01);
+        assert!((left.leader_to.x - (left.bounds.x + left.bounds.width)).abs() < 0.01);
+
+        let bounds = layout.bounds;
+        for note in notes {
+            assert!(note.bounds.x >= 0.0 && note.bounds.y >= 0.0);
+            assert!(note.bounds.x >= bounds.x - 0.01 && note.bounds.y >= bounds.y - 0.01);
+            assert!(note.bounds.x + note.bounds.width <= bounds.x + bounds.width + 0.01);
+            assert!(note.bounds.y + note.bounds.height <= bounds.y + bounds.height + 0.01);
+        }
+    }
+
+    #[test]
+    fn notes_on_the_same_side_stack_and_unknown_targets_are_skipped() {
+        let layout = state_layout(&[("right", "Idle"), ("right", "Idle"), ("right", "Missing")]);
+        let notes = &layout.extensions.node_notes;
+        assert_eq!(notes.len(), 2);
+        assert!(notes[1].bounds.y >= notes[0].bounds.y + notes[0].bounds.height);
+        assert!(state_layout(&[]).extensions.node_notes.is_empty());
+    }
+}
@@ -0,0 +1,321 @@
+//! Quantitative layout quality metrics.
+//!
+//! [`score_layout`] measures a finished [`DiagramLayout`] purely from its geometry, so it works
+//! for every algorithm (including custom [`crate::engine::LayoutEngine`]s) and lets CI and
+//! benchmark harnesses assert on numbers instead of eyeballing SVGs. Unlike
+//! [`crate::LayoutStats::crossing_count`], which counts rank-order inversions during Sugiyama
+//! ordering, the crossings here are actual intersections of the routed edge polylines.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{DiagramLayout, LayoutPoint, LayoutRect, polyline_length};
+
+/// Geometric quality of one layout. Lower is better for every field except `symmetry`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct LayoutQuality {
+    /// Pairs of segments from different edges that properly cross. Edges meeting at a shared
+    /// endpoint or running along each other do not count.
+    pub edge_crossings: usize,
+    /// Sum of the polyline lengths of all edges.
+    pub total_edge_length: f32,
+    /// Interior edge points where the path changes direction.
+    pub edge_bends: usize,
+    /// Pairs of node boxes whose interiors overlap.
+    pub node_overlaps: usize,
+    /// Width / height of the layout bounds (`1.0` for empty or degenerate bounds).
+    pub aspect_ratio: f32,
+    /// `|ln(aspect_ratio)|`: `0.0` for a square layout, and the same for 2:1 as for 1:2.
+    pub aspect_ratio_deviation: f32,
+    /// Fraction of nodes (`0.0..=1.0`) with a same-sized mirror partner across the layout's
+    /// vertical or horizontal centre line, whichever axis scores higher. Nodes on the axis are
+    /// their own partner; an empty layout scores `1.0`.
+    pub symmetry: f32,
+}
+
+/// Distance under which two coordinates are considered equal for bends and symmetry.
+const TOLERANCE: f32 = 1.0;
+
+/// Score `layout`; see [`LayoutQuality`] for what each metric means.
+#[must_use]
+pub fn score_layout(layout: &DiagramLayout) -> LayoutQuality {
+    let aspect_ratio = if layout.bounds.width > 0.0 && layout.bounds.height > 0.0 {
+        layout.bounds.width / layout.bounds.height
+    } else {
+        1.0
+    };
+    LayoutQuality {
+        edge_crossings: count_edge_crossings(layout),
+        total_edge_length: layout
+            .edges
+            .iter()
+            .map(|edge| polyline_length(&edge.points))
+            .sum(),
+        edge_bends: layout
+            .edges
+            .iter()
+            .map(|edge| count_bends(&edge.points))
+            .sum(),
+        node_overlaps: count_node_overlaps(layout),
+        aspect_ratio,
+        aspect_ratio_deviation: aspect_ratio.ln().abs(),
+        symmetry: mirror_symmetry(layout),
+    }
+}
+
+fn count_bends(points: &[LayoutPoint]) -> usize {
+    let mut directions = points.windows(2).filter_map(|pair| {
+        let (dx, dy) = (pair[1].x - pair[0].x, pair[1].y - pair[0].y);
+        let length = dx.hypot(dy);
+        (length > f32::EPSILON).then(|| (dx / length, dy / length))
+    });
+    let Some(mut previous) = directions.next() else {
+        return 0;
+    };
+    let mut bends = 0;
+    for direction in directions {
+        let cross = previous.0.mul_add(direction.1, -(previous.1 * direction.0));
+        let dot = previous.0.mul_add(direction.0, previous.1 * direction.1);
+        if cross.abs() > 1.0e-3 || dot < 0.0 {
+            bends += 1;
+        }
+        previous = direction;
+    }
+    bends
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    edge: usize,
+    start: (f64, f64),
+    end: (f64, f64),
+    min_x: f64,
+    max_x: f64,
+    min_y: f64,
+    max_y: f64,
+}
+
+/// Sweep over segments sorted by left x, so only pairs with overlapping x extents are tested.
+fn count_edge_crossings(layout: &DiagramLayout) -> usize {
+    let mut segments: Vec<Segment> = layout
+        .edges
+        .iter()
+        .enumerate()
+        .flat_map(|(edge, path)| {
+            path.points.windows(2).map(move |pair| {
+                let start = (f64::from(pair[0].x), f64::from(pair[0].y));
+                let end = (f64::from(pair[1].x), f64::from(pair[1].y));
+                Segment {
+                    edge,
+                    start,
+                    end,
+                    min_x: start.0.min(end.0),
+                    max_x: start.0.max(end.0),
+                    min_y: start.1.min(end.1),
+                    max_y: start.1.max(end.1),
+                }
+            })
+        })
+        .collect();
+    segments.sort_by(|left, right| left.min_x.total_cmp(&right.min_x));
+
+    let mut crossings = 0;
+    for (index, segment) in segments.iter().enumerate() {
+        for other in &segments[index + 1..] {
+            if other.min_x > segment.max_x {
+                break;
+            }
+            if other.edge != segment.edge
+                && other.min_y <= segment.max_y
+                && other.max_y >= segment.min_y
+                && segments_cross(segment, other)
+            {
+                crossings += 1;
+            }
+        }
+    }
+    crossings
+}
+
+/// Proper crossing: each segment's endpoints lie strictly on opposite sides of the other.
+fn segments_cross(a: &Segment, b: &Segment) -> bool {
+    const EPSILON: f64 = 1.0e-6;
+    let side = |from: (f64, f64), to: (f64, f64), point: (f64, f64)| {
+        let value = (to.0 - from.0) * (point.1 - from.1) - (to.1 - from.1) * (point.0 - from.0);
+        if value > EPSILON {
+            1
+        } else if value < -EPSILON {
+            -1
+        } else {
+            0
+        }
+    };
+    let (a1, a2) = (side(b.start, b.end, a.start), side(b.start, b.end, a.end));
+    let (b1, b2) = (side(a.start, a.end, b.start), side(a.start, a.end, b.end));
+    a1 * a2 < 0 && b1 * b2 < 0
+}
+
+fn count_node_overlaps(layout: &DiagramLayout) -> usize {
+    let mut boxes: Vec<LayoutRect> = layout.nodes.iter().map(|node| node.bounds).collect();
+    boxes.sort_by(|left, right| left.x.total_cmp(&right.x));
+    let mut overlaps = 0;
+    for (index, rect) in boxes.iter().enumerate() {
+        for other in &boxes[index + 1..] {
+            if other.x >= rect.x + rect.width {
+                break;
+            }
+            if other.y < rect.y + rect.height && rect.y < other.y + other.height {
+                overlaps += 1;
+            }
+        }
+    }
+    overlaps
+}
+
+fn mirror_symmetry(layout: &DiagramLayout) -> f32 {
+    if layout.nodes.is_empty() {
+        return 1.0;
+    }
+    // (centre x, centre y, width, height), sorted by centre y for the vertical-axis lookup.
+    let mut by_y: Vec<[f32; 4]> = layout
+        .nodes
+        .iter()
+        .map(|node| {
+            let rect = node.bounds;
+            [
+                rect.width.mul_add(0.5, rect.x),
+                rect.height.mul_add(0.5, rect.y),
+                rect.width,
+                rect.height,
+            ]
+        })
+        .collect();
+    // Mirroring across the horizontal axis is mirroring across the vertical one with x and y
+    // swapped.
+    let mut by_x: Vec<[f32; 4]> = by_y
+        .iter()
+        .map(|&[x, y, width, height]| [y, x, height, width])
+        .collect();
+    by_y.sort_by(|left, right| left[1].total_cmp(&right[1]));
+    by_x.sort_by(|left, right| left[1].total_cmp(&right[1]));
+    vertical_axis_symmetry(&by_y).max(vertical_axis_symmetry(&by_x))
+}
+
+/// Fraction of `centres` (sorted by y) whose mirror image across the vertical line through the
+/// middle of their x extent is another same-sized entry.
+fn vertical_axis_symmetry(centres: &[[f32; 4]]) -> f32 {
+    let (min_x, max_x) = centres
+        .iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), centre| {
+            (low.min(centre[0]), high.max(centre[0]))
+        });
+    let axis_twice = min_x + max_x;
+    let matched = centres
+        .iter()
+        .filter(|&&[x, y, width, height]| {
+            let mirrored_x = axis_twice - x;
+            let first = centres.partition_point(|other| other[1] < y - TOLERANCE);
+            centres[first..]
+                .iter()
+                .take_while(|other| other[1] <= y + TOLERANCE)
+                .any(|other| {
+                    (other[0] - mirrored_x).abs() <= TOLERANCE
+                        && (other[2] - width).abs() <= TOLERANCE
+                        && (other[3] - height).abs() <= TOLERANCE
+                })
+        })
+        .count();
+    matched as f32 / centres.len() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LayoutEdgePath, LayoutNodeBox, layout_diagram};
+    use fm_core::{
+        ArrowType, DiagramType, IrEdge, IrEndpoint, IrNode, IrNodeId, MermaidDiagramIr, Span,
+    };
+    use smallvec::smallvec;
+
+    fn fan_ir(children: usize) -> MermaidDiagramIr {
+        let mut ir = MermaidDiagramIr::empty(DiagramType::Flowchart);
+        for index in 0..=children {
+            ir.nodes.push(IrNode {
+                id: format!("N{index}"),
+                ..IrNode::default()
+            });
+        }
+        for child in 1..=children {
+            ir.edges.push(IrEdge {
+                from: IrEndpoint::Node(IrNodeId(0)),
+                to: IrEndpoint::Node(IrNodeId(child)),
+                arrow: ArrowType::Arrow,
+                ..IrEdge::default()
+            });
+        }
+        ir
+    }
+
+    fn point(x: f32, y: f32) -> LayoutPoint {
+        LayoutPoint { x, y }
+    }
+
+    #[test]
+    fn fan_layout_is_clean_and_symmetric() {
+        let layout = layout_diagram(&fan_ir(2));
+        let quality = score_layout(&layout);
+        assert_eq!(quality.edge_crossings, 0);
+        assert_eq!(quality.node_overlaps, 0);
+        assert_eq!(quality.symmetry, 1.0);
+        assert!(quality.total_edge_length > 0.0);
+        assert!(quality.aspect_ratio > 0.0);
+        assert!((quality.aspect_ratio_deviation - quality.aspect_ratio.ln().abs()).abs() < 1e-6);
+        assert_eq!(score_layout(&layout), quality, "scoring is deterministic");
+    }
+
+    #[test]
+    fn crossings_bends_and_overlaps_are_counted() {
+        let mut layout = layout_diagram(&fan_ir(2));
+        let template = layout.edges[0].clone();
+        layout.edges = vec![
+            LayoutEdgePath {
+                points: smallvec![point(0.0, 0.0), point(100.0, 0.0), point(100.0, 100.0)],
+                ..template.clone()
+            },
+            LayoutEdgePath {
+                edge_index: 1,
+                points: smallvec![point(50.0, -50.0), point(50.0, 50.0), point(150.0, 50.0)],
+                ..template.clone()
+            },
+            // Touches the first edge at its start point only: not a crossing.
+            LayoutEdgePath {
+                edge_index: 2,
+                points: smallvec![point(0.0, 0.0), point(-40.0, 30.0)],
+                ..template
+            },
+        ];
+        let node = |node_index: usize, x: f32| LayoutNodeBox {
+            node_index,
+            node_id: format!("N{node_index}"),
+            rank: 0,
+            order: node_index,
+            span: Span::default(),
+            bounds: LayoutRect {
+                x,
+                y: 0.0,
+                width: 40.0,
+                height: 20.0,
+            },
+        };
+        layout.nodes = vec![node(0, 0.0), node(1, 30.0), node(2, 60.0), node(3, 200.0)];
+        layout.nodes[3].bounds.y = 100.0;
+
+        let quality = score_layout(&layout);
+        // (0,0)-(100,0) x (50,-50)-(50,50) and (100,0)-(100,100) x (50,50)-(150,50).
+        assert_eq!(quality.edge_crossings, 2);
+        assert_eq!(quality.edge_bends, 2);
+        assert!((quality.total_edge_length - 450.0).abs() < 1e-3);
+        // N0/N1 and N1/N2 overlap; N0 and N2 only share an x range with N1.
+        assert_eq!(quality.node_overlaps, 2);
+        assert!(quality.symmetry < 1.0);
+    }
+}
@@ -0,0 +1,261 @@
+//! Lifeline-oriented view of sequence diagram layouts.
+//!
+//! [`crate::layout_diagram_sequence`] already places participants in columns and messages in
+//! source-order rows, but stores the result in the generic [`DiagramLayout`] shape: headers are
+//! node boxes, messages are edge paths, and lifelines, activations and fragments live in
+//! [`crate::LayoutExtensions`]. [`layout_sequence`] returns the same geometry as a
+//! [`SequenceLayout`], which pairs every participant with its lifeline and every message with its
+//! row and endpoints, while keeping the underlying [`DiagramLayout`] so the SVG and terminal
+//! renderers can draw it unchanged.
+
+use fm_core::MermaidDiagramIr;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    DiagramLayout, LayoutActivationBar, LayoutBandKind, LayoutClusterBox, LayoutPoint, LayoutRect,
+    LayoutSequenceFragment, LayoutSequenceLifecycleMarker, LayoutSequenceLifecycleMarkerKind,
+    LayoutSequenceNote, endpoint_node_index, layout_diagram_sequence,
+};
+
+/// One participant column: its header box, optional mirrored footer and lifeline.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SequenceParticipant {
+    /// IR node index of the participant.
+    pub node_index: usize,
+    pub node_id: String,
+    /// Header box at the top of the column.
+    pub header: LayoutRect,
+    /// Header repeated below the lifeline when `mirrorActors` is enabled.
+    pub mirror_header: Option<LayoutRect>,
+    /// X coordinate of the lifeline (the column centre).
+    pub lifeline_x: f32,
+    pub lifeline_top: f32,
+    pub lifeline_bottom: f32,
+    /// True when the participant is destroyed and its lifeline ends in a cross.
+    pub destroyed: bool,
+}
+
+/// One message row, in source order.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SequenceMessage {
+    /// IR edge index of the message.
+    pub edge_index: usize,
+    /// Row number; equal to the message's position in the source.
+    pub row: usize,
+    /// Sending participant (IR node index).
+    pub from: usize,
+    /// Receiving participant (IR node index).
+    pub to: usize,
+    /// Y coordinate of the row.
+    pub y: f32,
+    /// True for self-messages, which loop out to the right of the lifeline.
+    pub is_self: bool,
+    /// Routed arrow path, identical to the matching [`crate::LayoutEdgePath`].
+    pub points: Vec<LayoutPoint>,
+}
+
+/// Sequence diagram layout with participants, messages and decorations as first-class items.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SequenceLayout {
+    /// Participant columns in declaration order.
+    pub participants: Vec<SequenceParticipant>,
+    /// Messages in source order.
+    pub messages: Vec<SequenceMessage>,
+    /// The generic layout the renderers consume (`render_svg_with_layout`, terminal renderers).
+    pub layout: DiagramLayout,
+}
+
+impl SequenceLayout {
+    /// Build the lifeline view of a layout produced by [`crate::layout_diagram_sequence`].
+    #[must_use]
+    pub fn from_diagram_layout(ir: &MermaidDiagramIr, layout: DiagramLayout) -> Self {
+        let lifelines: Vec<LayoutRect> = layout
+            .extensions
+            .bands
+            .iter()
+            .filter(|band| band.kind == LayoutBandKind::Lane)
+            .map(|band| band.bounds)
+            .collect();
+        let extensions = &layout.extensions;
+        let participants = layout
+            .nodes
+            .iter()
+            .map(|node| {
+                let header = node.bounds;
+                let header_bottom = header.y + header.height;
+                let (lifeline_top, lifeline_bottom) = lifelines
+                    .get(node.node_index)
+                    .map_or((header_bottom, header_bottom), |lane| {
+                        (lane.y, lane.y + lane.height)
+                    });
+                let destroyed = extensions.sequence_lifecycle_markers.iter().any(|marker| {
+                    marker.participant_index == node.node_index
+                        && marker.kind == LayoutSequenceLifecycleMarkerKind::Destroy
+                });
+                SequenceParticipant {
+                    node_index: node.node_index,
+                    node_id: node.node_id.clone(),
+                    header,
+                    mirror_header: extensions
+                        .sequence_mirror_headers
+                        .iter()
+                        .find(|mirror| mirror.node_index == node.node_index)
+                        .map(|mirror| mirror.bounds),
+                    lifeline_x: header.width.mul_add(0.5, header.x),
+                    lifeline_top,
+                    lifeline_bottom,
+                    destroyed,
+                }
+            })
+            .collect();
+
+        let mut edges: Vec<_> = layout.edges.iter().collect();
+        edges.sort_by_key(|edge| edge.edge_index);
+        let messages = edges
+            .into_iter()
+            .enumerate()
+            .filter_map(|(row, edge)| {
+                let ir_edge = ir.edges.get(edge.edge_index)?;
+                let from = endpoint_node_index(ir, ir_edge.from)?;
+                let to = endpoint_node_index(ir, ir_edge.to)?;
+                Some(SequenceMessage {
+                    edge_index: edge.edge_index,
+                    row,
+                    from,
+                    to,
+                    y: edge.points.first().map_or(0.0, |point| point.y),
+                    is_self: from == to,
+                    points: edge.points.to_vec(),
+                })
+            })
+            .collect();
+
+        Self {
+            participants,
+            messages,
+            layout,
+        }
+    }
+
+    #[must_use]
+    pub fn activation_bars(&self) -> &[LayoutActivationBar] {
+        &self.layout.extensions.activation_bars
+    }
+
+    /// Loop, alt, opt, par and other interaction fragment boxes.
+    #[must_use]
+    pub fn fragments(&self) -> &[LayoutSequenceFragment] {
+        &self.layout.extensions.sequence_fragments
+    }
+
+    #[must_use]
+    pub fn notes(&self) -> &[LayoutSequenceNote] {
+        &self.layout.extensions.sequence_notes
+    }
+
+    #[must_use]
+    pub fn lifecycle_markers(&self) -> &[LayoutSequenceLifecycleMarker] {
+        &self.layout.extensions.sequence_lifecycle_markers
+    }
+
+    /// Participant `box` groups.
+    #[must_use]
+    pub fn groups(&self) -> &[LayoutClusterBox] {
+        &self.layout.clusters
+    }
+
+    #[must_use]
+    pub fn bounds(&self) -> LayoutRect {
+        self.layout.bounds
+    }
+}
+
+impl From<SequenceLayout> for DiagramLayout {
+    fn from(sequence: SequenceLayout) -> Self {
+        sequence.layout
+    }
+}
+
+/// Lay out a sequence diagram as actor columns, message rows in source order, activation bars and
+/// fragment boxes.
+#[must_use]
+pub fn layout_sequence(ir: &MermaidDiagramIr) -> SequenceLayout {
+    SequenceLayout::from_diagram_layout(ir, layout_diagram_sequence(ir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fm_core::{
+        ArrowType, DiagramType, IrActivation, IrEdge, IrEndpoint, IrNode, IrNodeId, IrSequenceMeta,
+    };
+
+    fn chat_ir() -> MermaidDiagramIr {
+        let mut ir = MermaidDiagramIr::empty(DiagramType::Sequence);
+        for id in ["Alice", "Bob"] {
+            ir.nodes.push(IrNode {
+                id: id.to_string(),
+                ..IrNode::default()
+            });
+        }
+        for (from, to) in [(0, 1), (1, 1), (1, 0)] {
+            ir.edges.push(IrEdge {
+                from: IrEndpoint::Node(IrNodeId(from)),
+                to: IrEndpoint::Node(IrNodeId(to)),
+                arrow: ArrowType::Arrow,
+                ..IrEdge::default()
+            });
+        }
+        ir.sequence_meta = Some(IrSequenceMeta {
+            activations: vec![IrActivation {
+                participant: IrNodeId(1),
+                start_edge: 0,
+                end_edge: 2,
+                depth: 0,
+            }],
+            ..IrSequenceMeta::default()
+        });
+        ir
+    }
+
+    #[test]
+    fn participants_get_columns_and_messages_get_source_order_rows() {
+        let ir = chat_ir();
+        let sequence = layout_sequence(&ir);
+
+        assert_eq!(sequence.participants.len(), 2);
+        let (alice, bob) = (&sequence.participants[0], &sequence.participants[1]);
+        assert!(alice.lifeline_x < bob.lifeline_x);
+        for participant in &sequence.participants {
+            assert!(participant.lifeline_top >= participant.header.y + participant.header.height);
+            assert!(participant.lifeline_bottom > participant.lifeline_top);
+            assert!(!participant.destroyed);
+        }
+
+        let rows: Vec<(usize, usize, usize)> = sequence
+            .messages
+            .iter()
+            .map(|message| (message.row, message.from, message.to))
+            .collect();
+        assert_eq!(rows, vec![(0, 0, 1), (1, 1, 1), (2, 1, 0)]);
+        assert!(
+            sequence
+                .messages
+                .windows(2)
+                .all(|pair| pair[0].y < pair[1].y)
+        );
+        assert!(sequence.messages[1].is_self);
+        assert_eq!(
+            sequence.messages[0].points.first().unwrap().x,
+            alice.lifeline_x
+        );
+        assert_eq!(
+            sequence.messages[0].points.last().unwrap().x,
+            bob.lifeline_x
+        );
+
+        assert_eq!(sequence.activation_bars().len(), 1);
+        assert_eq!(sequence.activation_bars()[0].participant_index, 1);
+        assert_eq!(DiagramLayout::from(sequence), layout_diagram_sequence(&ir));
+    }
+}
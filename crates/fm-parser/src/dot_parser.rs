@@ -1,6 +1,6 @@
 use std::{borrow::Cow, iter::Peekable, str::CharIndices};
 
-use fm_core::{ArrowType, DiagramType, NodeShape, Span};
+use fm_core::{ArrowType, DiagramType, GraphDirection, NodeShape, Span};
 use memchr::memchr2;
 use unicode_segmentation::UnicodeSegmentation;
 
@@ -111,6 +111,24 @@ pub fn parse_dot(input: &str) -> ParseResult {
                 continue;
             }
 
+            if let Some(attributes) = dot_graph_attributes(statement) {
+                apply_dot_graph_attributes(
+                    attributes,
+                    active_clusters.last().copied(),
+                    line_number,
+                    span_for(line_number, line),
+                    &mut builder,
+                );
+                continue;
+            }
+            // `node [...]` and `edge [...]` set defaults for later statements. These are valid
+            // DOT but we parse-and-skip them (no runtime behavior yet).
+            if dot_keyword_attributes(statement, "node").is_some()
+                || dot_keyword_attributes(statement, "edge").is_some()
+            {
+                continue;
+            }
+
             if parse_dot_edge_statement(
                 statement,
                 directed,
@@ -133,22 +151,6 @@ pub fn parse_dot(input: &str) -> ParseResult {
                 continue;
             }
 
-            // Handle graph/edge/node default attribute statements.
-            // These are valid DOT but we parse-and-skip them (no runtime behavior yet).
-            let lower = statement.trim().to_ascii_lowercase();
-            if lower.starts_with("graph ")
-                || lower.starts_with("graph[")
-                || lower.starts_with("graph\t")
-                || lower.starts_with("edge ")
-                || lower.starts_with("edge[")
-                || lower.starts_with("edge\t")
-                || lower.starts_with("node ")
-                || lower.starts_with("node[")
-                || lower.starts_with("node\t")
-            {
-                continue;
-            }
-
             builder.add_warning(format!(
                 "Line {line_number}: unsupported DOT statement: {statement}"
             ));
@@ -302,6 +304,73 @@ fn starts_with_keyword(line: &str, keyword: &str) -> bool {
         .is_some_and(|ch| ch.is_whitespace() || ch == '{')
 }
 
+/// Attribute list of a keyword statement such as `node [shape=box]`. DOT keywords are
+/// case-insensitive.
+fn dot_keyword_attributes<'a>(statement: &'a str, keyword: &str) -> Option<&'a str> {
+    let head = statement.get(..keyword.len())?;
+    if !head.eq_ignore_ascii_case(keyword) {
+        return None;
+    }
+    let rest = statement[keyword.len()..].trim_start();
+    rest.starts_with('[').then_some(rest)
+}
+
+/// Attributes of the enclosing graph or cluster, set by `graph [...]` or a bare `key=value`.
+fn dot_graph_attributes(statement: &str) -> Option<&str> {
+    if let Some(attributes) = dot_keyword_attributes(statement, "graph") {
+        return Some(attributes);
+    }
+    let (key, _) = statement.split_once('=')?;
+    let key = key.trim();
+    (!key.is_empty()
+        && key
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_'))
+    .then_some(statement)
+}
+
+/// Apply the graph attributes the IR can express: `label` titles the diagram (or the cluster
+/// being parsed) and a top-level `rankdir` sets the layout direction. Graphviz ignores
+/// `rankdir` inside subgraphs, and so do we.
+fn apply_dot_graph_attributes(
+    attributes: &str,
+    cluster: Option<usize>,
+    line_number: usize,
+    span: Span,
+    builder: &mut IrBuilder,
+) {
+    for (key, value) in DotAttributeIter::new(attributes) {
+        if key.eq_ignore_ascii_case("label") {
+            let Some(label) = parse_dot_label_value(&value) else {
+                continue;
+            };
+            match cluster {
+                Some(cluster_index) => builder.set_cluster_title(cluster_index, &label, span),
+                None => builder.set_title(label),
+            }
+        } else if key.eq_ignore_ascii_case("rankdir") && cluster.is_none() {
+            match dot_rankdir(&value) {
+                Some(direction) => builder.set_direction(direction),
+                None => builder.add_warning(format!(
+                    "Line {line_number}: unsupported DOT rankdir: {value}"
+                )),
+            }
+        }
+    }
+}
+
+fn dot_rankdir(value: &str) -> Option<GraphDirection> {
+    let value = value.trim().trim_matches('"');
+    [
+        GraphDirection::TB,
+        GraphDirection::LR,
+        GraphDirection::BT,
+        GraphDirection::RL,
+    ]
+    .into_iter()
+    .find(|direction| value.eq_ignore_ascii_case(direction.as_str()))
+}
+
 fn parse_dot_edge_statement(
     statement: &str,
     directed: bool,
@@ -1292,7 +1361,7 @@ fn span_for(line_number: usize, line: &str) -> Span {
 
 #[cfg(test)]
 mod tests {
-    use fm_core::{ArrowType, DiagramType};
+    use fm_core::{ArrowType, DiagramType, GraphDirection};
 
     use super::{looks_like_dot, parse_dot};
 
@@ -1425,6 +1494,34 @@ mod tests {
         assert_eq!(second_member, "b");
     }
 
+    #[test]
+    fn graph_attributes_set_direction_and_titles_instead_of_nodes() {
+        let parsed = parse_dot(
+            "digraph G { rankdir=LR; label=\"System\"; node [shape=box]; \
+             subgraph cluster_api { label=\"API\"; rankdir=BT; a; } a -> b; }",
+        );
+        let ids: Vec<&str> = parsed
+            .ir
+            .nodes
+            .iter()
+            .map(|node| node.id.as_str())
+            .collect();
+        assert_eq!(ids, ["a", "b"]);
+        assert_eq!(parsed.ir.direction, GraphDirection::LR);
+        assert_eq!(parsed.ir.meta.title.as_deref(), Some("System"));
+        let title = |label: Option<fm_core::IrLabelId>| &parsed.ir.labels[label.unwrap().0].text;
+        assert_eq!(title(parsed.ir.clusters[0].title), "API");
+        assert_eq!(title(parsed.ir.graph.subgraphs[0].title), "API");
+        assert!(parsed.warnings.is_empty());
+
+        let parsed = parse_dot("graph G { GRAPH [rankdir=\"rl\"]; edge [color=red]; a -- b; }");
+        assert_eq!(parsed.ir.direction, GraphDirection::RL);
+        assert_eq!(parsed.ir.nodes.len(), 2);
+        let parsed = parse_dot("digraph G { rankdir=XY; a -> b; }");
+        assert_eq!(parsed.ir.direction, GraphDirection::TB);
+        assert_eq!(parsed.warnings.len(), 1);
+    }
+
     #[test]
     fn parses_html_labels() {
         let parsed = parse_dot("digraph G { a [label=<b>Alpha</b>]; }");
@@ -1,18 +1,19 @@
 use std::collections::BTreeMap;
 use std::collections::hash_map::Entry;
+use std::fmt::Write as _;
 
 use rustc_hash::{FxHashMap, FxHashSet};
 
 use fm_core::{
     ArrowType, ClassMemberKind, ClassStereotype, Diagnostic, DiagnosticCategory, DiagramType,
-    FragmentAlternative, FragmentKind, GraphDirection, IrActivation, IrAttributeKey, IrC4NodeMeta,
-    IrClassMember, IrClassNodeMeta, IrCluster, IrClusterId, IrEdge, IrEdgeKind, IrEndpoint,
-    IrEntityAttribute, IrGanttMeta, IrGraphCluster, IrGraphEdge, IrGraphNode, IrLabel, IrLabelId,
-    IrLabelSegment, IrLifecycleEvent, IrNode, IrNodeId, IrNodeKind, IrParticipantGroup,
-    IrSequenceFragment, IrSequenceMeta, IrSequenceNote, IrStyleRef, IrStyleTarget, IrSubgraph,
-    IrSubgraphId, IrXyChartMeta, LifecycleEventKind, MermaidDiagramIr, MermaidError,
-    MermaidParseMode, MermaidSanitizeMode, MermaidWarning, MermaidWarningCode, NodeShape,
-    NotePosition, Span,
+    FragmentAlternative, FragmentKind, GraphDirection, IrActivation, IrAttributeKey,
+    IrAutonumberChange, IrC4NodeMeta, IrClassMember, IrClassNodeMeta, IrCluster, IrClusterId,
+    IrEdge, IrEdgeKind, IrEndpoint, IrEntityAttribute, IrGanttMeta, IrGraphCluster, IrGraphEdge,
+    IrGraphNode, IrLabel, IrLabelId, IrLabelSegment, IrLifecycleEvent, IrNode, IrNodeId,
+    IrNodeKind, IrParticipantGroup, IrSequenceFragment, IrSequenceMeta, IrSequenceNote, IrStyleRef,
+    IrStyleTarget, IrSubgraph, IrSubgraphId, IrXyChartMeta, LifecycleEventKind, MermaidDiagramIr,
+    MermaidError, MermaidParseMode, MermaidSanitizeMode, MermaidWarning, MermaidWarningCode,
+    NodeShape, NotePosition, Span, label_segments_text, parse_label_markdown,
 };
 
 use crate::mermaid_parser::trim_fast;
@@ -242,6 +243,29 @@ impl ParsedLabel {
         }
     }
 
+    /// A label written without markdown-string backticks: `**bold**`, `*italic*` and `~~strike~~`
+    /// spans become markup, and text they leave unstyled stays exactly as written.
+    pub(crate) fn markdown(text: impl Into<String>) -> Self {
+        let text = text.into();
+        if text.contains(['*', '~']) {
+            let segments = parse_label_markdown(&text);
+            let styled = segments.iter().any(|segment| {
+                matches!(
+                    segment,
+                    IrLabelSegment::Text { bold, italic, code, strike, .. }
+                        if *bold || *italic || *code || *strike
+                )
+            });
+            if styled {
+                return Self {
+                    text: label_segments_text(&segments),
+                    segments,
+                };
+            }
+        }
+        Self::plain(text)
+    }
+
     pub(crate) fn as_str(&self) -> &str {
         &self.text
     }
@@ -360,6 +384,10 @@ impl IrBuilder {
         self.ir.quadrant_meta = Some(quadrant_meta);
     }
 
+    pub(crate) fn set_gitgraph_meta(&mut self, gitgraph_meta: fm_core::IrGitGraphMeta) {
+        self.ir.gitgraph_meta = Some(gitgraph_meta);
+    }
+
     pub(crate) fn set_acc_title(&mut self, title: String) {
         self.ir.meta.acc_title = Some(title);
     }
@@ -436,6 +464,60 @@ impl IrBuilder {
         meta.autonumber_increment = increment;
     }
 
+    /// Record an `autonumber` statement at the current message position; [`Self::finish`] turns
+    /// the statements into the IR's numbering.
+    pub(crate) fn record_autonumber(
+        &mut self,
+        start: Option<u32>,
+        increment: Option<u32>,
+        enabled: bool,
+    ) {
+        let edge_index = self.ir.edges.len();
+        self.ir
+            .sequence_meta
+            .get_or_insert_with(IrSequenceMeta::default)
+            .autonumber_changes
+            .push(IrAutonumberChange {
+                edge_index,
+                start,
+                increment,
+                enabled,
+            });
+    }
+
+    /// A single `autonumber` ahead of every message becomes the plain start/increment form;
+    /// otherwise the statements stay as changes, led by any numbering `showSequenceNumbers`
+    /// turned on for the whole diagram.
+    fn resolve_autonumber(&mut self) {
+        let Some(meta) = self.ir.sequence_meta.as_mut() else {
+            return;
+        };
+        let mut changes = std::mem::take(&mut meta.autonumber_changes);
+        match changes.as_slice() {
+            [] => {}
+            [only] if only.edge_index == 0 && only.enabled => {
+                meta.autonumber = true;
+                meta.autonumber_start = only.start.unwrap_or(1);
+                meta.autonumber_increment = only.increment.unwrap_or(1);
+            }
+            [first, ..] => {
+                if meta.autonumber && first.edge_index > 0 {
+                    changes.insert(
+                        0,
+                        IrAutonumberChange {
+                            edge_index: 0,
+                            start: Some(meta.autonumber_start),
+                            increment: Some(meta.autonumber_increment),
+                            enabled: true,
+                        },
+                    );
+                }
+                meta.autonumber = changes.iter().any(|change| change.enabled);
+                meta.autonumber_changes = changes;
+            }
+        }
+    }
+
     pub(crate) fn hide_sequence_footbox(&mut self) {
         self.ir
             .sequence_meta
@@ -713,6 +795,21 @@ impl IrBuilder {
         true
     }
 
+    /// Suffix that keeps the `[*]` pseudo-states of the innermost open composite state (and of
+    /// each of its concurrent regions) apart from the enclosing ones; `None` at the top level.
+    pub(crate) fn state_pseudo_scope(&self) -> Option<String> {
+        let context = self.state_stack.last()?;
+        let path = context
+            .lookup_key
+            .strip_prefix("state/")
+            .unwrap_or(&context.lookup_key);
+        let mut scope = path.replace('/', "_");
+        if context.region_count > 0 {
+            let _ = write!(scope, "__region_{}", context.region_count + 1);
+        }
+        Some(scope)
+    }
+
     pub(crate) fn attach_state_node(&mut self, node_id: IrNodeId) {
         for context_index in 0..self.state_stack.len() {
             let (cluster_index, subgraph_index, current_region_subgraph, should_track_member) = {
@@ -892,6 +989,7 @@ impl IrBuilder {
         while self.end_fragment() {}
         self.flush_open_activations();
         self.end_participant_group();
+        self.resolve_autonumber();
 
         // Apply semantic recovery
         self.apply_semantic_recovery();
@@ -1071,6 +1169,7 @@ impl IrBuilder {
             requirement_meta: None,
             c4_meta: None,
             inline_style: None,
+            custom_shape: None,
         };
 
         self.ir.nodes.push(node);
@@ -1166,6 +1265,27 @@ impl IrBuilder {
         Some(cluster_index)
     }
 
+    /// Replace the title of a cluster, its graph entry and the subgraph it is linked to.
+    pub(crate) fn set_cluster_title(&mut self, cluster_index: usize, title: &str, span: Span) {
+        let Some(title_text) = clean_label(Some(title)) else {
+            return;
+        };
+        if cluster_index >= self.ir.clusters.len() {
+            return;
+        }
+        let label_id = self.intern_label(&ParsedLabel::plain(title_text), span);
+        self.ir.clusters[cluster_index].title = Some(label_id);
+        if let Some(graph_cluster) = self.ir.graph.clusters.get_mut(cluster_index) {
+            graph_cluster.title = Some(label_id);
+            if let Some(subgraph) = graph_cluster
+                .subgraph
+                .and_then(|id| self.ir.graph.subgraphs.get_mut(id.0))
+            {
+                subgraph.title = Some(label_id);
+            }
+        }
+    }
+
     pub(crate) fn add_node_to_cluster(&mut self, cluster_index: usize, node_id: IrNodeId) {
         let Some(cluster) = self.ir.clusters.get_mut(cluster_index) else {
             return;
@@ -1416,6 +1536,7 @@ impl IrBuilder {
             requirement_meta: None,
             c4_meta: None,
             inline_style: None,
+            custom_shape: None,
         });
         self.ir.graph.nodes.push(IrGraphNode {
             node_id,
@@ -1603,7 +1724,7 @@ impl IrBuilder {
         label: Option<&str>,
         span: Span,
     ) {
-        let parsed_label = clean_label(label).map(ParsedLabel::plain);
+        let parsed_label = clean_label(label).map(ParsedLabel::markdown);
         let label_id = parsed_label
             .as_ref()
             .map(|value| self.intern_label(value, span));
@@ -1615,6 +1736,7 @@ impl IrBuilder {
             span,
             extras: None,
             inline_style: None,
+            weight: None,
         });
         self.ir.graph.edges.push(IrGraphEdge {
             edge_id: self.ir.edges.len() - 1,
@@ -1644,6 +1766,29 @@ impl IrBuilder {
         }
     }
 
+    /// Give the edge at `index` the id the source named it by. Returns `false`, leaving the edge
+    /// unnamed, when another edge already has that id.
+    pub(crate) fn set_edge_user_id(&mut self, index: usize, user_id: &str) -> bool {
+        if self
+            .ir
+            .edges
+            .iter()
+            .any(|edge| edge.user_id() == Some(user_id))
+        {
+            return false;
+        }
+        if let Some(edge) = self.ir.edges.get_mut(index) {
+            edge.extras_mut().user_id = Some(Box::from(user_id));
+        }
+        true
+    }
+
+    pub(crate) fn set_last_edge_class_relation(&mut self, relation: fm_core::IrClassRelation) {
+        if let Some(edge) = self.ir.edges.last_mut() {
+            edge.extras_mut().class_relation = Some(relation);
+        }
+    }
+
     fn intern_label(&mut self, label: &ParsedLabel, span: Span) -> IrLabelId {
         // Hash the (text, segments) pair ONCE for the get+insert pair below (a new label was
         // hashed twice). Byte-identical; monotonically fewer hashes.
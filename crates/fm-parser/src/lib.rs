@@ -3,6 +3,7 @@
 mod dot_parser;
 mod ir_builder;
 mod mermaid_parser;
+mod plantuml_parser;
 
 use fm_core::{
     DiagramType, MermaidDiagramIr, MermaidLensBinding, MermaidLensEdit, MermaidLensEditResult,
@@ -15,6 +16,9 @@ use unicode_segmentation::UnicodeSegmentation;
 
 pub use dot_parser::{looks_like_dot, parse_dot};
 pub use mermaid_parser::first_significant_line;
+pub use plantuml_parser::{
+    detect_plantuml_type, looks_like_plantuml, parse_plantuml, plantuml_diagram_type,
+};
 
 /// Normalize a Mermaid identifier by trimming, stripping quotes, and replacing
 /// unsafe characters with underscores.
@@ -260,6 +264,8 @@ pub enum DetectionMethod {
     ContentHeuristic,
     /// DOT format detection
     DotFormat,
+    /// PlantUML document (`@startuml`)
+    PlantUmlFormat,
     /// Fallback to flowchart (lowest confidence)
     Fallback,
 }
@@ -273,6 +279,7 @@ impl DetectionMethod {
             Self::FuzzyKeyword => "fuzzy keyword match",
             Self::ContentHeuristic => "content heuristics",
             Self::DotFormat => "DOT format detected",
+            Self::PlantUmlFormat => "PlantUML format detected",
             Self::Fallback => "fallback to flowchart",
         }
     }
@@ -295,10 +302,11 @@ pub struct DetectedType {
 ///
 /// Uses multiple detection strategies in order of precedence:
 /// 1. Exact keyword match
-/// 2. Fuzzy keyword match (edit distance <= 2)
-/// 3. Content heuristics (characteristic patterns)
-/// 4. DOT format detection
-/// 5. Fallback to flowchart
+/// 2. PlantUML detection (`@startuml`)
+/// 3. Fuzzy keyword match (edit distance <= 2)
+/// 4. Content heuristics (characteristic patterns)
+/// 5. DOT format detection
+/// 6. Fallback to flowchart
 #[must_use]
 pub fn detect_type_with_confidence(input: &str) -> DetectedType {
     detect_type_with_confidence_and_config(input, &ParserConfig::default())
@@ -349,6 +357,11 @@ pub fn detect_type_with_confidence_and_config(input: &str, config: &ParserConfig
         return detected;
     }
 
+    // PlantUML documents open with `@startuml`, which no Mermaid header matches.
+    if looks_like_plantuml(input) {
+        return detect_plantuml_type(input);
+    }
+
     if config.intent_inference {
         // Strategy 3: Fuzzy keyword match
         let lower = first_line.to_ascii_lowercase();
@@ -805,6 +818,13 @@ pub fn parse_with_mode_and_config(
         return result;
     }
 
+    if detection.method == DetectionMethod::PlantUmlFormat {
+        let mut result =
+            plantuml_parser::parse_plantuml_with_mode_and_config(input, parse_mode, config);
+        result.format_complement = MermaidFormatComplement::default();
+        return result;
+    }
+
     let mut result = mermaid_parser::parse_mermaid_with_detection_and_config(
         input, detection, parse_mode, config,
     );
@@ -1456,7 +1476,7 @@ mod tests {
         let response = apply_parse_lens_edit(
             input,
             &MermaidLensEdit {
-                element_id: "fm-edge-0".to_string(),
+                element_id: "fm-edge-a-b-0".to_string(),
                 replacement: "A[Alpha] -.-> B[Beta]".to_string(),
             },
         )
@@ -1730,6 +1750,18 @@ mod tests {
         assert_eq!(result.detection_method, DetectionMethod::ContentHeuristic);
     }
 
+    #[test]
+    fn detection_plantuml_format() {
+        let result = detect_type_with_confidence("@startuml\nclass Order\n@enduml");
+        assert_eq!(result.diagram_type, DiagramType::Class);
+        assert_eq!(result.method, DetectionMethod::PlantUmlFormat);
+
+        let result = parse("@startuml\nAlice -> Bob : hi\n@enduml");
+        assert_eq!(result.ir.diagram_type, DiagramType::Sequence);
+        assert_eq!(result.detection_method, DetectionMethod::PlantUmlFormat);
+        assert_eq!(result.ir.edges.len(), 1);
+    }
+
     #[test]
     fn parse_result_dot_format_has_high_confidence() {
         let result = parse("digraph G { a -> b; }");
@@ -2207,4 +2239,67 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn emitted_flowcharts_parse_back_to_the_same_diagram() {
+        let source = "flowchart LR
+    A(Start) --> B{\"Ready?\"}
+    B -->|yes| C[[Run]]
+    B e2@-.->|no| D[(Store)]
+    subgraph work[Work]
+        direction TB
+        C
+        D
+    end
+    classDef hot fill:#f96
+    class C hot
+    style A fill:#fff
+    linkStyle 1 stroke:red
+";
+        let first = parse(source).ir;
+        let emitted = fm_core::emit_mermaid(&first).unwrap();
+        let again = parse(&emitted).ir;
+
+        let nodes = |ir: &fm_core::MermaidDiagramIr| {
+            ir.nodes
+                .iter()
+                .map(|node| {
+                    let label = node.label.map(|label| ir.labels[label.0].text.clone());
+                    (node.id.clone(), label, node.shape, node.classes.clone())
+                })
+                .collect::<Vec<_>>()
+        };
+        let edges = |ir: &fm_core::MermaidDiagramIr| {
+            ir.edges
+                .iter()
+                .map(|edge| {
+                    let label = edge.label.map(|label| ir.labels[label.0].text.clone());
+                    (
+                        edge.from,
+                        edge.to,
+                        edge.arrow,
+                        label,
+                        edge.user_id().map(str::to_string),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(nodes(&again), nodes(&first), "{emitted}");
+        assert_eq!(edges(&again), edges(&first), "{emitted}");
+        assert_eq!(again.graph.subgraphs.len(), 1);
+        assert_eq!(again.graph.subgraphs[0].key, "work");
+        assert_eq!(
+            again.graph.subgraphs[0].direction,
+            Some(fm_core::GraphDirection::TB)
+        );
+        let styles = |ir: &fm_core::MermaidDiagramIr| {
+            ir.style_refs
+                .iter()
+                .map(|style_ref| (style_ref.target.clone(), style_ref.style.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(styles(&again), styles(&first));
+        // Emitting is a fixed point once the source is canonical.
+        assert_eq!(fm_core::emit_mermaid(&again).unwrap(), emitted);
+    }
 }
@@ -6,9 +6,9 @@ use chumsky::prelude::*;
 use fm_core::{
     ArrowType, Diagnostic, DiagnosticCategory, DiagramType, GanttDate, GanttExclude, GanttTaskType,
     GanttTickInterval, GraphDirection, IrAttributeKey, IrC4NodeMeta, IrGanttMeta, IrGanttSection,
-    IrGanttTask, IrLabelSegment, IrNodeId, IrXyAxis, IrXyChartMeta, IrXySeries, IrXySeriesKind,
-    MermaidParseMode, MermaidSupportLevel, NodeShape, Span, is_safe_link_target,
-    parse_mermaid_js_config_value, to_init_parse,
+    IrGanttTask, IrNodeId, IrXyAxis, IrXyChartMeta, IrXySeries, IrXySeriesKind, MermaidParseMode,
+    MermaidSupportLevel, NodeShape, Span, is_safe_link_target, label_segments_text,
+    parse_label_markdown, parse_mermaid_js_config_value, to_init_parse,
 };
 use serde_json::Value;
 
@@ -64,15 +64,41 @@ const SEQUENCE_OPERATORS: [(&str, ArrowType); 26] = [
     ("-x", ArrowType::Cross),
 ];
 
-const CLASS_OPERATORS: [(&str, ArrowType); 6] = [
+const CLASS_OPERATORS: [(&str, ArrowType); 10] = [
     ("<|--", ArrowType::Arrow),
     ("--|>", ArrowType::Arrow),
+    ("<|..", ArrowType::DottedArrow),
+    ("..|>", ArrowType::DottedArrow),
+    ("*--", ArrowType::Line),
+    ("--*", ArrowType::Line),
     ("..>", ArrowType::DottedArrow),
     ("<..", ArrowType::DottedArrow),
     ("-->", ArrowType::Arrow),
     ("--", ArrowType::Line),
 ];
 
+/// UML relation for a [`CLASS_OPERATORS`] entry.
+fn class_relation_for_operator(operator: &str) -> Option<fm_core::IrClassRelation> {
+    use fm_core::ClassRelationKind;
+    let (kind, marker_at_from) = match operator {
+        "<|--" => (ClassRelationKind::Inheritance, true),
+        "--|>" => (ClassRelationKind::Inheritance, false),
+        "<|.." => (ClassRelationKind::Realization, true),
+        "..|>" => (ClassRelationKind::Realization, false),
+        "*--" => (ClassRelationKind::Composition, true),
+        "--*" => (ClassRelationKind::Composition, false),
+        "..>" => (ClassRelationKind::Dependency, false),
+        "<.." => (ClassRelationKind::Dependency, true),
+        "-->" => (ClassRelationKind::Association, false),
+        "--" => (ClassRelationKind::Link, false),
+        _ => return None,
+    };
+    Some(fm_core::IrClassRelation {
+        kind,
+        marker_at_from,
+    })
+}
+
 const PACKET_OPERATORS: [(&str, ArrowType); 4] = [
     ("-->", ArrowType::Arrow),
     ("->", ArrowType::Arrow),
@@ -171,6 +197,8 @@ enum SequenceStatement {
     Autonumber {
         start: Option<u32>,
         increment: Option<u32>,
+        /// `false` for `autonumber off`.
+        enabled: bool,
     },
     HideFootbox,
     Link {
@@ -215,6 +243,8 @@ enum ClassStatement {
     Stereotype(String, fm_core::ClassStereotype),
     /// Cardinality labels to attach to the most recently created edge.
     Cardinality(Option<String>, Option<String>),
+    /// UML relation to attach to the most recently created edge.
+    Relation(fm_core::IrClassRelation),
     End,
 }
 
@@ -511,6 +541,8 @@ enum FlowDocumentItem<'a> {
     },
     Statements {
         asts: Vec<FlowAst>,
+        /// Ids the statement gave its edges (`A e1@--> B`), in source order.
+        edge_ids: Vec<String>,
         line_number: usize,
         source_line: &'a str,
     },
@@ -764,6 +796,7 @@ fn flow_statement_parser<'a>()
         .then(required_ws)
         .ignore_then(ident)
         .then_ignore(required_ws)
+        .then_ignore(just("href").then(required_ws).or_not())
         .then(quoted_string_link.or(bare_word))
         .then(required_ws.ignore_then(quoted_string_tooltip).or_not())
         .then_ignore(end())
@@ -870,9 +903,15 @@ fn lower_flow_ast(
                     "Line {line_number}: click directive target is empty after normalization"
                 ));
             } else if *is_callback {
-                // Callback: store function name, add interactive class.
+                // Callback: store the bare function name, add interactive class.
+                let Some(callback) = fm_core::callback_function_name(cleaned) else {
+                    builder.add_warning(format!(
+                        "Line {line_number}: unsafe click callback blocked: {cleaned}"
+                    ));
+                    return;
+                };
                 builder.add_class_to_node(node, "has-callback", span);
-                builder.set_node_callback(node, cleaned, span);
+                builder.set_node_callback(node, callback, span);
                 if let Some(tip) = tooltip {
                     let tip_cleaned = tip
                         .trim()
@@ -978,9 +1017,11 @@ fn lower_flow_document_item(
         }
         FlowDocumentItem::Statements {
             asts,
+            edge_ids,
             line_number,
             source_line,
         } => {
+            let first_edge = builder.edge_count();
             for ast in &asts {
                 lower_flow_ast(
                     ast,
@@ -991,6 +1032,9 @@ fn lower_flow_document_item(
                     active_subgraphs,
                 );
             }
+            if !edge_ids.is_empty() {
+                assign_flow_edge_ids(builder, first_edge, &edge_ids, line_number);
+            }
         }
         FlowDocumentItem::Subgraph {
             id,
@@ -1318,6 +1362,22 @@ fn parse_flowchart_document_items<'a>(
                 continue;
             }
 
+            // `A e1@--> B`: the ids come off before parsing so the edge grammar never sees them.
+            if let Some((stripped, edge_ids)) = strip_flowchart_edge_ids(normalized_statement) {
+                if let Some(asts) =
+                    parse_flowchart_statement_asts(&stripped, line_number, line, warnings, config)
+                {
+                    items.push(FlowDocumentItem::Statements {
+                        asts,
+                        edge_ids,
+                        line_number,
+                        source_line: line,
+                    });
+                    parsed_line = true;
+                }
+                continue;
+            }
+
             if let Some((from, arrow, to)) =
                 parse_fast_simple_flowchart_edge_parts(normalized_statement)
             {
@@ -1359,6 +1419,7 @@ fn parse_flowchart_document_items<'a>(
             ) {
                 items.push(FlowDocumentItem::Statements {
                     asts,
+                    edge_ids: Vec::new(),
                     line_number,
                     source_line: line,
                 });
@@ -1398,6 +1459,65 @@ fn find_triple_colon(s: &str) -> Option<usize> {
     None
 }
 
+/// Split the edge ids off a statement such as `A e1@--> B e2@==> C`: each `id@` written right
+/// before a flow operator (after whitespace, outside brackets and quotes) is removed and its id
+/// collected in source order. `None` when the statement names no edge.
+fn strip_flowchart_edge_ids(statement: &str) -> Option<(String, Vec<String>)> {
+    memchr::memchr(b'@', statement.as_bytes())?;
+    let mut stripped = String::with_capacity(statement.len());
+    let mut edge_ids = Vec::new();
+    let mut copied = 0;
+    let mut from = 0;
+    while let Some((idx, operator, _)) =
+        find_operator_from_index(statement, from, &FLOW_OPERATORS, FLOW_OP_GATE)
+    {
+        from = idx + operator.len();
+        let Some(before) = statement[..idx].strip_suffix('@') else {
+            continue;
+        };
+        let id_start = before
+            .trim_end_matches(|ch: char| ch.is_ascii_alphanumeric() || ch == '_')
+            .len();
+        if id_start == before.len() || !before[..id_start].ends_with(char::is_whitespace) {
+            continue;
+        }
+        edge_ids.push(before[id_start..].to_string());
+        stripped.push_str(&statement[copied..id_start]);
+        copied = idx;
+    }
+    if edge_ids.is_empty() {
+        return None;
+    }
+    stripped.push_str(&statement[copied..]);
+    Some((stripped, edge_ids))
+}
+
+/// Give the edges a statement added (from `first_edge` on) the ids it named. A statement that
+/// fans out (`A e1@--> B & C`) adds more edges than it names, so its ids are dropped with a
+/// warning rather than guessed at; an id already taken is dropped the same way.
+fn assign_flow_edge_ids(
+    builder: &mut IrBuilder,
+    first_edge: usize,
+    edge_ids: &[String],
+    line_number: usize,
+) {
+    let added = builder.edge_count() - first_edge;
+    if added != edge_ids.len() {
+        builder.add_warning(format!(
+            "Line {line_number}: edge ids ignored: {} ids for {added} edges",
+            edge_ids.len()
+        ));
+        return;
+    }
+    for (offset, edge_id) in edge_ids.iter().enumerate() {
+        if !builder.set_edge_user_id(first_edge + offset, edge_id) {
+            builder.add_warning(format!(
+                "Line {line_number}: duplicate edge id ignored: {edge_id}"
+            ));
+        }
+    }
+}
+
 fn parse_flowchart_statement_asts(
     statement: &str,
     line_number: usize,
@@ -2009,10 +2129,11 @@ fn parse_sequence_statement(line: &str) -> Option<SequenceStatement> {
 fn parse_sequence_autonumber(line: &str) -> Option<SequenceStatement> {
     let rest = line.strip_prefix("autonumber")?;
     let rest = rest.trim();
-    if rest.is_empty() {
+    if rest.is_empty() || rest == "off" {
         return Some(SequenceStatement::Autonumber {
             start: None,
             increment: None,
+            enabled: rest.is_empty(),
         });
     }
 
@@ -2021,10 +2142,12 @@ fn parse_sequence_autonumber(line: &str) -> Option<SequenceStatement> {
         [start] => Some(SequenceStatement::Autonumber {
             start: Some(start.parse().ok()?),
             increment: None,
+            enabled: true,
         }),
         [start, increment] => Some(SequenceStatement::Autonumber {
             start: Some(start.parse().ok()?),
             increment: Some(increment.parse().ok()?),
+            enabled: true,
         }),
         _ => None,
     }
@@ -2241,7 +2364,7 @@ fn extract_sequence_box_color(rest: &str) -> Option<(String, &str)> {
         .find(|ch: char| ch.is_whitespace())
         .unwrap_or(rest.len());
     let color_name = &rest[..token_end];
-    if is_css_named_color(color_name) {
+    if fm_core::is_css_named_color(color_name) {
         return Some((color_name.to_string(), rest[token_end..].trim_start()));
     }
 
@@ -2265,161 +2388,6 @@ fn consume_css_function(value: &str) -> Option<usize> {
     None
 }
 
-fn is_css_named_color(value: &str) -> bool {
-    matches!(
-        value.to_ascii_lowercase().as_str(),
-        "aliceblue"
-            | "antiquewhite"
-            | "aqua"
-            | "aquamarine"
-            | "azure"
-            | "beige"
-            | "bisque"
-            | "black"
-            | "blanchedalmond"
-            | "blue"
-            | "blueviolet"
-            | "brown"
-            | "burlywood"
-            | "cadetblue"
-            | "chartreuse"
-            | "chocolate"
-            | "coral"
-            | "cornflowerblue"
-            | "cornsilk"
-            | "crimson"
-            | "cyan"
-            | "darkblue"
-            | "darkcyan"
-            | "darkgoldenrod"
-            | "darkgray"
-            | "darkgreen"
-            | "darkgrey"
-            | "darkkhaki"
-            | "darkmagenta"
-            | "darkolivegreen"
-            | "darkorange"
-            | "darkorchid"
-            | "darkred"
-            | "darksalmon"
-            | "darkseagreen"
-            | "darkslateblue"
-            | "darkslategray"
-            | "darkslategrey"
-            | "darkturquoise"
-            | "darkviolet"
-            | "deeppink"
-            | "deepskyblue"
-            | "dimgray"
-            | "dimgrey"
-            | "dodgerblue"
-            | "firebrick"
-            | "floralwhite"
-            | "forestgreen"
-            | "fuchsia"
-            | "gainsboro"
-            | "ghostwhite"
-            | "gold"
-            | "goldenrod"
-            | "gray"
-            | "green"
-            | "greenyellow"
-            | "grey"
-            | "honeydew"
-            | "hotpink"
-            | "indianred"
-            | "indigo"
-            | "ivory"
-            | "khaki"
-            | "lavender"
-            | "lavenderblush"
-            | "lawngreen"
-            | "lemonchiffon"
-            | "lightblue"
-            | "lightcoral"
-            | "lightcyan"
-            | "lightgoldenrodyellow"
-            | "lightgray"
-            | "lightgreen"
-            | "lightgrey"
-            | "lightpink"
-            | "lightsalmon"
-            | "lightseagreen"
-            | "lightskyblue"
-            | "lightslategray"
-            | "lightslategrey"
-            | "lightsteelblue"
-            | "lightyellow"
-            | "lime"
-            | "limegreen"
-            | "linen"
-            | "magenta"
-            | "maroon"
-            | "mediumaquamarine"
-            | "mediumblue"
-            | "mediumorchid"
-            | "mediumpurple"
-            | "mediumseagreen"
-            | "mediumslateblue"
-            | "mediumspringgreen"
-            | "mediumturquoise"
-            | "mediumvioletred"
-            | "midnightblue"
-            | "mintcream"
-            | "mistyrose"
-            | "moccasin"
-            | "navajowhite"
-            | "navy"
-            | "oldlace"
-            | "olive"
-            | "olivedrab"
-            | "orange"
-            | "orangered"
-            | "orchid"
-            | "palegoldenrod"
-            | "palegreen"
-            | "paleturquoise"
-            | "palevioletred"
-            | "papayawhip"
-            | "peachpuff"
-            | "peru"
-            | "pink"
-            | "plum"
-            | "powderblue"
-            | "purple"
-            | "rebeccapurple"
-            | "red"
-            | "rosybrown"
-            | "royalblue"
-            | "saddlebrown"
-            | "salmon"
-            | "sandybrown"
-            | "seagreen"
-            | "seashell"
-            | "sienna"
-            | "silver"
-            | "skyblue"
-            | "slateblue"
-            | "slategray"
-            | "slategrey"
-            | "snow"
-            | "springgreen"
-            | "steelblue"
-            | "tan"
-            | "teal"
-            | "thistle"
-            | "tomato"
-            | "transparent"
-            | "turquoise"
-            | "violet"
-            | "wheat"
-            | "white"
-            | "whitesmoke"
-            | "yellow"
-            | "yellowgreen"
-    )
-}
-
 /// Parse fragment start keywords: loop, alt, opt, par, critical, break, rect.
 fn parse_sequence_fragment_start(line: &str) -> Option<SequenceStatement> {
     let (kind, rest) = if let Some(r) = line.strip_prefix("loop") {
@@ -2484,8 +2452,12 @@ fn lower_sequence_statement(
         SequenceStatement::Message(data) => {
             let _ = lower_sequence_message(&data, line_number, source_line, builder);
         }
-        SequenceStatement::Autonumber { start, increment } => {
-            builder.enable_autonumber_with(start.unwrap_or(1), increment.unwrap_or(1));
+        SequenceStatement::Autonumber {
+            start,
+            increment,
+            enabled,
+        } => {
+            builder.record_autonumber(start, increment, enabled);
         }
         SequenceStatement::HideFootbox => {
             builder.hide_sequence_footbox();
@@ -2631,11 +2603,20 @@ fn parse_class(input: &str, builder: &mut IrBuilder) {
         }
 
         // Inside a class block: parse member declarations. The open class name lives in the builder's
-        // `current_class` context (set on `BlockStart`, read by `add_class_member`), so this branch
-        // only needs to know a block is open — the previous per-member `class_name.clone()` was written
-        // into `cn` and immediately dropped unused, one wasted heap alloc+copy+free per member line.
-        if in_block.is_some() {
-            if let Some(member) = parse_class_member(trimmed) {
+        // `current_class` context (set on `BlockStart`, read by `add_class_member`), so members need
+        // no per-line `class_name.clone()`; only a `<<annotation>>` line, which names the open class
+        // as its stereotype target, reads `in_block`.
+        if let Some(class_name) = &in_block {
+            if let Some(annotation) = trimmed
+                .strip_prefix("<<")
+                .and_then(|rest| rest.strip_suffix(">>"))
+            {
+                let stereotype = ClassStatement::Stereotype(
+                    class_name.clone(),
+                    class_stereotype(annotation.trim()),
+                );
+                lower_class_statement(stereotype, line_number, line, builder);
+            } else if let Some(member) = parse_class_member(trimmed) {
                 lower_class_statement(ClassStatement::Member(member), line_number, line, builder);
             }
             continue;
@@ -2718,56 +2699,114 @@ fn parse_class_member(trimmed: &str) -> Option<fm_core::IrClassMember> {
         return None;
     }
 
-    // Check if it's a method (contains parentheses)
-    let is_method = rest.contains('(');
-
-    // Check for static ($) and abstract (*) markers at end
-    let is_static = rest.ends_with('$');
-    let is_abstract = rest.ends_with('*');
-    let rest = trim_fast(rest.trim_end_matches('$').trim_end_matches('*'));
-
-    // Parse type annotation. `rsplit_once(':')` / `rfind(')')` on a single ASCII char go through
-    // the scalar `CharSearcher` reverse searcher (a profiled self-symbol on class parse); the SIMD
-    // `memchr::memrchr` finds the same last-byte index (`:` / `)` are ASCII ⇒ char boundaries).
-    let (name_part, return_type) = if let Some(colon) = memchr::memrchr(b':', rest.as_bytes()) {
-        // Colon-separated: `name : Type` or `method() : ReturnType`
-        (
+    // A method has a parameter list; `$` (static) and `*` (abstract) follow its closing paren
+    // (`count()$ int`) or end the line (`total$`, `area()*`).
+    if let Some(open) = memchr::memchr(b'(', rest.as_bytes()) {
+        let close = memchr::memrchr(b')', rest.as_bytes()).filter(|&close| close > open);
+        let Some(close) = close else {
+            return Some(class_member(
+                visibility,
+                fm_core::ClassMemberKind::Method,
+                rest,
+                None,
+            ));
+        };
+        let mut after = trim_fast(&rest[close + 1..]);
+        let mut markers = String::new();
+        while let Some(marker) = after.chars().next().filter(|ch| matches!(ch, '$' | '*')) {
+            markers.push(marker);
+            after = trim_start_fast(&after[1..]);
+        }
+        let (after, trailing) = strip_class_member_markers(after);
+        markers.push_str(trailing);
+        let return_type = trim_fast(after.strip_prefix(':').unwrap_or(after));
+        let mut member = class_member(
+            visibility,
+            fm_core::ClassMemberKind::Method,
+            &rest[..=close],
+            (!return_type.is_empty()).then(|| return_type.to_string()),
+        );
+        member.parameters = parse_class_parameters(&rest[open + 1..close]);
+        member.is_static = markers.contains('$');
+        member.is_abstract = markers.contains('*');
+        return Some(member);
+    }
+
+    // Attribute: `Type name` or `name : Type`, markers at the end of the line. `rsplit_once(':')`
+    // on a single ASCII char goes through the scalar `CharSearcher` reverse searcher (a profiled
+    // self-symbol on class parse); the SIMD `memchr::memrchr` finds the same last-byte index.
+    let (rest, markers) = strip_class_member_markers(rest);
+    let mut member = match memchr::memrchr(b':', rest.as_bytes()) {
+        Some(colon) => class_member(
+            visibility,
+            fm_core::ClassMemberKind::Attribute,
             trim_fast(&rest[..colon]),
             Some(trim_fast(&rest[colon + 1..]).to_string()),
-        )
-    } else if is_method {
-        // For methods without colon, check for return type after closing paren
-        // e.g., `eat() void` → name="eat()", return_type=Some("void")
-        if let Some(paren_end) = memchr::memrchr(b')', rest.as_bytes()) {
-            let after_paren = trim_fast(&rest[paren_end + 1..]);
-            if after_paren.is_empty() {
-                (rest, None)
-            } else {
-                (&rest[..=paren_end], Some(after_paren.to_string()))
-            }
-        } else {
-            (rest, None)
-        }
-    } else {
-        (rest, None)
-    };
-
-    let name = name_part.to_string();
-
-    let kind = if is_method {
-        fm_core::ClassMemberKind::Method
-    } else {
-        fm_core::ClassMemberKind::Attribute
+        ),
+        None => class_member(visibility, fm_core::ClassMemberKind::Attribute, rest, None),
     };
-
-    Some(fm_core::IrClassMember {
+    member.is_static = markers.contains('$');
+    member.is_abstract = markers.contains('*');
+    Some(member)
+}
+
+fn class_member(
+    visibility: fm_core::ClassVisibility,
+    kind: fm_core::ClassMemberKind,
+    name: &str,
+    return_type: Option<String>,
+) -> fm_core::IrClassMember {
+    fm_core::IrClassMember {
         visibility,
         kind,
-        name,
+        name: name.to_string(),
         return_type,
-        is_static,
-        is_abstract,
-    })
+        ..fm_core::IrClassMember::default()
+    }
+}
+
+/// Split the `$`/`*` markers off the end of a member declaration.
+fn strip_class_member_markers(text: &str) -> (&str, &str) {
+    let stripped = trim_end_fast(text.trim_end_matches(['$', '*']));
+    let markers = text[stripped.len()..].trim_start();
+    (stripped, markers)
+}
+
+/// Parameters of a method's parameter list. Commas inside generics (`Map~K,V~`, `Map<K,V>`)
+/// do not split parameters. Each one is `name: Type`, `Type name` or a bare name.
+fn parse_class_parameters(list: &str) -> Vec<fm_core::IrClassParameter> {
+    let mut parameters = Vec::new();
+    let (mut depth, mut in_tilde, mut start) = (0usize, false, 0);
+    for (idx, ch) in list
+        .char_indices()
+        .chain(std::iter::once((list.len(), ',')))
+    {
+        match ch {
+            '~' => in_tilde = !in_tilde,
+            '<' => depth += 1,
+            '>' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 && !in_tilde => {
+                let parameter = trim_fast(&list[start..idx]);
+                start = idx + 1;
+                if parameter.is_empty() {
+                    continue;
+                }
+                let (name, type_name) = match parameter.split_once(':') {
+                    Some((name, type_name)) => (trim_fast(name), Some(trim_fast(type_name))),
+                    None => match parameter.rsplit_once(char::is_whitespace) {
+                        Some((type_name, name)) => (name, Some(trim_fast(type_name))),
+                        None => (parameter, None),
+                    },
+                };
+                parameters.push(fm_core::IrClassParameter {
+                    name: name.to_string(),
+                    type_name: type_name.map(str::to_string),
+                });
+            }
+            _ => {}
+        }
+    }
+    parameters
 }
 
 /// Strip quoted cardinality labels from a class-diagram relationship line.
@@ -2839,6 +2878,17 @@ fn strip_class_cardinality(statement: &str) -> Option<(String, Option<String>, O
     Some((result, source_card, target_card))
 }
 
+/// The stereotype named by a `<<annotation>>`.
+fn class_stereotype(annotation: &str) -> fm_core::ClassStereotype {
+    match annotation.to_lowercase().as_str() {
+        "interface" => fm_core::ClassStereotype::Interface,
+        "abstract" => fm_core::ClassStereotype::Abstract,
+        "enum" | "enumeration" => fm_core::ClassStereotype::Enum,
+        "service" => fm_core::ClassStereotype::Service,
+        _ => fm_core::ClassStereotype::Custom(annotation.to_string()),
+    }
+}
+
 fn parse_class_statements(line: &str, config: &ParserConfig) -> Option<Vec<ClassStatement>> {
     if line.starts_with("class ") && line.ends_with('{') {
         let raw_name = trim_fast(trim_fast(line.trim_start_matches("class")).trim_end_matches('{'));
@@ -2858,16 +2908,9 @@ fn parse_class_statements(line: &str, config: &ParserConfig) -> Option<Vec<Class
     if line.starts_with("<<")
         && let Some(end) = line.find(">>")
     {
-        let annotation = &line[2..end];
         let class_name = line[end + 2..].trim().to_string();
-        let stereotype = match annotation.to_lowercase().as_str() {
-            "interface" => fm_core::ClassStereotype::Interface,
-            "abstract" => fm_core::ClassStereotype::Abstract,
-            "enum" | "enumeration" => fm_core::ClassStereotype::Enum,
-            "service" => fm_core::ClassStereotype::Service,
-            _ => fm_core::ClassStereotype::Custom(annotation.to_string()),
-        };
         if !class_name.is_empty() {
+            let stereotype = class_stereotype(&line[2..end]);
             return Some(vec![ClassStatement::Stereotype(class_name, stereotype)]);
         }
     }
@@ -2912,8 +2955,14 @@ fn parse_class_statements(line: &str, config: &ParserConfig) -> Option<Vec<Class
             config,
             0,
         ) {
+            let relation = find_operator(edge_input, &CLASS_OPERATORS, CLASS_OP_GATE)
+                .and_then(|(_, operator, _)| class_relation_for_operator(operator));
             for ast in asts {
+                let is_edge = matches!(ast, FlowAst::Edge { .. });
                 statements.push(ClassStatement::Ast(ast));
+                if is_edge && let Some(relation) = relation {
+                    statements.push(ClassStatement::Relation(relation));
+                }
                 // Attach cardinality to this edge in lower_class_statement. `stripped` is `Some`
                 // only when at least one cardinality was extracted, so no is_some() re-check.
                 if let Some((_, source_card, target_card)) = &stripped {
@@ -2966,6 +3015,9 @@ fn lower_class_statement(
         ClassStatement::Cardinality(source, target) => {
             builder.set_last_edge_cardinality(source.as_deref(), target.as_deref());
         }
+        ClassStatement::Relation(relation) => {
+            builder.set_last_edge_class_relation(relation);
+        }
         ClassStatement::End => {
             builder.clear_current_class();
         }
@@ -3202,15 +3254,17 @@ fn lower_state_flow_ast(
                 from.label.as_ref().map(ParsedLabel::as_str),
                 from.shape,
                 true,
+                builder,
             );
             let (to_id_key, to_label, to_shape) = state_edge_endpoint(
                 &to.id,
                 to.label.as_ref().map(ParsedLabel::as_str),
                 to.shape,
                 false,
+                builder,
             );
-            let from_id = builder.intern_node(from_id_key, from_label, from_shape, span);
-            let to_id = builder.intern_node(to_id_key, to_label, to_shape, span);
+            let from_id = builder.intern_node(&from_id_key, from_label, from_shape, span);
+            let to_id = builder.intern_node(&to_id_key, to_label, to_shape, span);
             if let (Some(f), Some(t)) = (from_id, to_id) {
                 builder.attach_state_node(f);
                 builder.attach_state_node(t);
@@ -3315,11 +3369,15 @@ fn lower_state_node(
     span: Span,
 ) {
     let (id, label, shape) = if id == STATE_PSEUDO_TOKEN {
-        (STATE_START_NODE_ID, None, NodeShape::FilledCircle)
+        (
+            state_pseudo_node_id(STATE_START_NODE_ID, builder),
+            None,
+            NodeShape::FilledCircle,
+        )
     } else {
-        (id, label, shape)
+        (Cow::Borrowed(id), label, shape)
     };
-    if let Some(node_id) = builder.intern_node(id, label, shape, span) {
+    if let Some(node_id) = builder.intern_node(&id, label, shape, span) {
         builder.attach_state_node(node_id);
     }
 }
@@ -3329,18 +3387,36 @@ fn state_edge_endpoint<'a>(
     label: Option<&'a str>,
     shape: NodeShape,
     is_source: bool,
-) -> (&'a str, Option<&'a str>, NodeShape) {
+    builder: &IrBuilder,
+) -> (Cow<'a, str>, Option<&'a str>, NodeShape) {
     if id != STATE_PSEUDO_TOKEN {
-        return (id, label, shape);
+        return (Cow::Borrowed(id), label, shape);
     }
 
     if is_source {
-        (STATE_START_NODE_ID, None, NodeShape::FilledCircle)
+        (
+            state_pseudo_node_id(STATE_START_NODE_ID, builder),
+            None,
+            NodeShape::FilledCircle,
+        )
     } else {
-        (STATE_END_NODE_ID, None, NodeShape::DoubleCircle)
+        (
+            state_pseudo_node_id(STATE_END_NODE_ID, builder),
+            None,
+            NodeShape::DoubleCircle,
+        )
     }
 }
 
+/// `[*]` inside a composite state is that composite's own start/end state, not the diagram's.
+fn state_pseudo_node_id(base: &'static str, builder: &IrBuilder) -> Cow<'static, str> {
+    builder
+        .state_pseudo_scope()
+        .map_or(Cow::Borrowed(base), |scope| {
+            Cow::Owned(format!("{base}_{scope}"))
+        })
+}
+
 fn finalize_requirement_block(
     builder: &mut IrBuilder,
     current_req_node: &mut Option<fm_core::IrNodeId>,
@@ -4152,8 +4228,8 @@ fn parse_click_directive_ast(
     } else if target_token.eq_ignore_ascii_case("call")
         || target_token.eq_ignore_ascii_case("callback")
     {
-        // Parse callback function name: `click nodeId call functionName ["tooltip"]`
-        let Some((fn_name, cb_remaining)) = take_token(after_target) else {
+        // Parse callback function name: `click nodeId call functionName[(args)] ["tooltip"]`
+        let Some((fn_name, cb_remaining)) = take_callback_call(after_target) else {
             warnings.push(format!(
                 "Line {line_number}: click callback directive missing function name: {statement}"
             ));
@@ -4191,6 +4267,20 @@ fn parse_click_directive_ast(
     })
 }
 
+/// The callback of `click nodeId call ...`: its first token, extended through the closing `)` of
+/// an argument list that holds spaces (`fn(a, "b c")`).
+fn take_callback_call(input: &str) -> Option<(&str, &str)> {
+    let trimmed = input.trim_start();
+    let (token, rest) = take_token(trimmed)?;
+    if token.contains('(')
+        && !token.ends_with(')')
+        && let Some(close) = trimmed.find(')')
+    {
+        return Some((&trimmed[..=close], &trimmed[close + 1..]));
+    }
+    Some((token, rest))
+}
+
 fn take_token(input: &str) -> Option<(&str, &str)> {
     let trimmed = input.trim_start();
     if trimmed.is_empty() {
@@ -6126,6 +6216,8 @@ struct GitGraphState {
     commit_counter: usize,
     /// Ordered list of branch names for color indexing.
     branch_order: Vec<String>,
+    /// Every commit with the branch it was made on, in source order.
+    commits: Vec<fm_core::IrGitCommit>,
 }
 
 impl GitGraphState {
@@ -6135,6 +6227,7 @@ impl GitGraphState {
             current_branch: "main".to_string(),
             commit_counter: 0,
             branch_order: vec!["main".to_string()],
+            commits: Vec::new(),
         }
     }
 
@@ -6158,6 +6251,55 @@ impl GitGraphState {
     fn set_head(&mut self, branch: &str, node_id: IrNodeId) {
         self.branches.insert(branch.to_string(), node_id);
     }
+
+    fn register_branch(&mut self, branch: &str) {
+        if !self.branch_order.iter().any(|known| known == branch) {
+            self.branch_order.push(branch.to_string());
+        }
+    }
+
+    /// Record `node_id` as a commit on the current branch, tag it with the branch color class and
+    /// advance the branch head to it.
+    fn record_commit(
+        &mut self,
+        commit_id: &str,
+        node_id: IrNodeId,
+        span: Span,
+        builder: &mut IrBuilder,
+    ) {
+        // The class is one of 8 fixed strings, so index a static table instead of a per-commit
+        // `format!` heap allocation.
+        const GIT_BRANCH_CLASSES: [&str; fm_core::IrGitGraphMeta::BRANCH_COLOR_COUNT] = [
+            "git-branch-0",
+            "git-branch-1",
+            "git-branch-2",
+            "git-branch-3",
+            "git-branch-4",
+            "git-branch-5",
+            "git-branch-6",
+            "git-branch-7",
+        ];
+        let branch = self.branch_index(&self.current_branch);
+        builder.add_class_to_node(
+            commit_id,
+            GIT_BRANCH_CLASSES[fm_core::IrGitGraphMeta::branch_color_index(branch)],
+            span,
+        );
+        if !self.commits.iter().any(|commit| commit.node == node_id) {
+            self.commits.push(fm_core::IrGitCommit {
+                node: node_id,
+                branch,
+            });
+        }
+        self.set_head(&self.current_branch.clone(), node_id);
+    }
+
+    fn into_meta(self) -> fm_core::IrGitGraphMeta {
+        fm_core::IrGitGraphMeta {
+            branches: self.branch_order,
+            commits: self.commits,
+        }
+    }
 }
 
 enum GitGraphCommand {
@@ -6634,6 +6776,10 @@ fn parse_gitgraph(input: &str, builder: &mut IrBuilder) {
             "Line {line_number}: unsupported gitGraph syntax: {trimmed}"
         ));
     }
+
+    if !state.commits.is_empty() {
+        builder.set_gitgraph_meta(state.into_meta());
+    }
 }
 
 fn parse_gitgraph_command(line: &str) -> Option<Result<GitGraphCommand, String>> {
@@ -6787,29 +6933,12 @@ fn parse_git_commit(
         );
     }
 
-    // Apply branch color class. `branch_index` borrows `&self`, so no per-commit clone of the branch
-    // name is needed; and the class is one of 8 fixed strings, so index a static table instead of a
-    // per-commit `format!` heap allocation. Byte-identical.
-    const GIT_BRANCH_CLASSES: [&str; 8] = [
-        "git-branch-0",
-        "git-branch-1",
-        "git-branch-2",
-        "git-branch-3",
-        "git-branch-4",
-        "git-branch-5",
-        "git-branch-6",
-        "git-branch-7",
-    ];
-    let branch_index = state.branch_index(&state.current_branch);
-    builder.add_class_to_node(&commit_id, GIT_BRANCH_CLASSES[branch_index % 8], span);
-
     // Link from current branch head if it exists
     if let Some(parent_id) = state.current_head() {
         builder.push_edge(parent_id, node_id, ArrowType::Line, None, span);
     }
 
-    // Update current branch head
-    state.set_head(&state.current_branch.clone(), node_id);
+    state.record_commit(&commit_id, node_id, span, builder);
 }
 
 /// Parsed git commit options.
@@ -6954,15 +7083,16 @@ fn parse_git_branch(
     }
 
     // Track branch ordering for color assignment.
-    if !state.branch_order.contains(&normalized) {
-        state.branch_order.push(normalized.clone());
-    }
+    state.register_branch(&normalized);
 
     // When creating a branch, it inherits the current head
     if let Some(current_head) = state.current_head() {
         state.set_head(&normalized, current_head);
     }
     // If no current head, the branch starts empty (first commit will set it)
+
+    // As in Mermaid, `branch` also checks the new branch out.
+    state.current_branch = normalized;
 }
 
 fn parse_git_checkout(
@@ -6979,6 +7109,7 @@ fn parse_git_checkout(
     }
 
     // Allow checking out branches that don't exist yet (they'll be created on first commit)
+    state.register_branch(&normalized);
     state.current_branch = normalized;
 }
 
@@ -7028,8 +7159,7 @@ fn parse_git_merge(
         builder.push_edge(current_head, merge_node, ArrowType::Line, None, span);
     }
 
-    // Update current branch head
-    state.set_head(&state.current_branch.clone(), merge_node);
+    state.record_commit(&merge_id, merge_node, span, builder);
 }
 
 fn parse_git_merge_options(spec: &str) -> Option<GitMergeOptions> {
@@ -7096,8 +7226,7 @@ fn parse_git_cherry_pick(
         builder.push_edge(current_head, new_node, ArrowType::Line, None, span);
     }
 
-    // Update current branch head
-    state.set_head(&state.current_branch.clone(), new_node);
+    state.record_commit(&new_commit_id, new_node, span, builder);
 }
 
 fn parse_git_cherry_pick_id(spec: &str) -> Result<String, &'static str> {
@@ -8432,15 +8561,15 @@ fn parse_label_pretrimmed(raw: &str) -> Option<ParsedLabel> {
 }
 
 fn parse_label_inner(raw: &str, pretrimmed: bool) -> Option<ParsedLabel> {
-    // Fast path: a label with no surrounding quotes (`"` `'`), markdown backtick, or HTML entity
-    // (`&` `#`) reduces to a plain trimmed copy -- the quote-stripping, markdown, and entity-decode
+    // Fast path: a label with no surrounding quotes (`"` `'`), markdown (`` ` `` `*` `~`), or HTML
+    // entity (`&` `#`) reduces to a plain trimmed copy -- the quote-stripping, markdown, and entity-decode
     // passes below all leave it unchanged. Byte-identical: for such a label the full path also ends
     // at `Some(ParsedLabel::plain(raw.trim()))` (or `None` when empty). Skips ~3 redundant
     // `trim`/`trim_matches` scans + the two-`find` entity decode per label (parse_label runs once
     // per node label; doc-parse is ~49% of flowchart parse).
     if !raw
         .bytes()
-        .any(|byte| matches!(byte, b'"' | b'\'' | b'`' | b'&' | b'#'))
+        .any(|byte| matches!(byte, b'"' | b'\'' | b'`' | b'&' | b'#' | b'*' | b'~'))
     {
         // `trim_fast` == `str::trim` byte-for-byte but skips the `char::is_whitespace` CharSearcher.
         // When the caller already trimmed (`pretrimmed`), `trim_fast(raw) == raw`, so skip it entirely
@@ -8465,8 +8594,8 @@ fn parse_label_inner(raw: &str, pretrimmed: bool) -> Option<ParsedLabel> {
     {
         let normalized = replace_br_with_newlines(markdown_body.trim());
         let decoded = decode_mermaid_entities(&normalized);
-        let segments = parse_markdown_label_segments(&decoded);
-        let text = flatten_label_segments(&segments);
+        let segments = parse_label_markdown(&decoded);
+        let text = label_segments_text(&segments);
         if text.trim().is_empty() {
             return None;
         }
@@ -8475,86 +8604,9 @@ fn parse_label_inner(raw: &str, pretrimmed: bool) -> Option<ParsedLabel> {
 
     let decoded = decode_mermaid_entities(without_quotes.trim());
     if decoded.is_empty() {
-        None
-    } else {
-        Some(ParsedLabel::plain(decoded))
-    }
-}
-
-fn parse_markdown_label_segments(text: &str) -> Vec<IrLabelSegment> {
-    let mut segments = Vec::new();
-    let mut buffer = String::new();
-    let mut bold = false;
-    let mut italic = false;
-    let mut code = false;
-    let mut strike = false;
-    let chars: Vec<char> = text.chars().collect();
-    let mut idx = 0usize;
-
-    let flush = |segments: &mut Vec<IrLabelSegment>,
-                 buffer: &mut String,
-                 bold: bool,
-                 italic: bool,
-                 code: bool,
-                 strike: bool| {
-        if !buffer.is_empty() {
-            segments.push(IrLabelSegment::Text {
-                text: std::mem::take(buffer),
-                bold,
-                italic,
-                code,
-                strike,
-            });
-        }
-    };
-
-    while idx < chars.len() {
-        match chars[idx] {
-            '\n' => {
-                flush(&mut segments, &mut buffer, bold, italic, code, strike);
-                segments.push(IrLabelSegment::LineBreak);
-                idx += 1;
-            }
-            '~' if !code && chars.get(idx + 1) == Some(&'~') => {
-                flush(&mut segments, &mut buffer, bold, italic, code, strike);
-                strike = !strike;
-                idx += 2;
-            }
-            '*' if !code && chars.get(idx + 1) == Some(&'*') => {
-                flush(&mut segments, &mut buffer, bold, italic, code, strike);
-                bold = !bold;
-                idx += 2;
-            }
-            '*' if !code => {
-                flush(&mut segments, &mut buffer, bold, italic, code, strike);
-                italic = !italic;
-                idx += 1;
-            }
-            '`' => {
-                flush(&mut segments, &mut buffer, bold, italic, code, strike);
-                code = !code;
-                idx += 1;
-            }
-            ch => {
-                buffer.push(ch);
-                idx += 1;
-            }
-        }
-    }
-
-    flush(&mut segments, &mut buffer, bold, italic, code, strike);
-    segments
-}
-
-fn flatten_label_segments(segments: &[IrLabelSegment]) -> String {
-    let mut text = String::new();
-    for segment in segments {
-        match segment {
-            IrLabelSegment::Text { text: value, .. } => text.push_str(value),
-            IrLabelSegment::LineBreak => text.push('\n'),
-        }
+        return None;
     }
-    text
+    Some(ParsedLabel::markdown(decoded))
 }
 
 fn normalize_subgraph_title(raw: &str) -> Option<String> {
@@ -9711,17 +9763,17 @@ fn extract_style_directives(input: &str, builder: &mut IrBuilder) {
             if first_style_span.is_none() {
                 first_style_span = Some(span);
             }
-            // classDef className fill:#fff,stroke:#000,...
+            // classDef className[,otherClass...] fill:#fff,stroke:#000,...
             let rest = rest.trim();
-            if let Some((name, style)) = rest.split_once(' ') {
-                let name = name.trim();
+            if let Some((names, style)) = rest.split_once(' ') {
+                let names = names.trim();
                 let style = style.trim();
-                if !name.is_empty() && !style.is_empty() {
+                if !names.is_empty() && !style.is_empty() {
                     let (_parsed, rejected) = fm_core::parse_style_string_with_rejections(style);
                     if !rejected.is_empty() {
                         let rejected_list = rejected.join(", ");
                         let message = format!(
-                            "classDef '{name}' includes unsupported or unsafe style properties (ignored): {rejected_list}"
+                            "classDef '{names}' includes unsupported or unsafe style properties (ignored): {rejected_list}"
                         );
                         builder.add_diagnostic(
                             Diagnostic::warning(message)
@@ -9730,11 +9782,15 @@ fn extract_style_directives(input: &str, builder: &mut IrBuilder) {
                                 .with_rule_id("classdef-unsupported-style"),
                         );
                     }
-                    builder.push_style_ref(
-                        fm_core::IrStyleTarget::Class(name.to_string()),
-                        style.to_string(),
-                        span,
-                    );
+                    for name in names.split(',').map(str::trim) {
+                        if !name.is_empty() {
+                            builder.push_style_ref(
+                                fm_core::IrStyleTarget::Class(name.to_string()),
+                                style.to_string(),
+                                span,
+                            );
+                        }
+                    }
                 }
             }
         } else if let Some(rest) = line.strip_prefix("style ") {
@@ -9768,7 +9824,7 @@ fn extract_style_directives(input: &str, builder: &mut IrBuilder) {
             if first_style_span.is_none() {
                 first_style_span = Some(span);
             }
-            // linkStyle 0 stroke:#f00,...
+            // linkStyle 0 stroke:#f00,...  /  linkStyle 0,2 ...  /  linkStyle default ...
             let rest = rest.trim();
             if let Some((index_str, style)) = rest.split_once(' ') {
                 let index_str = index_str.trim();
@@ -9780,12 +9836,27 @@ fn extract_style_directives(input: &str, builder: &mut IrBuilder) {
                             style.to_string(),
                             span,
                         );
-                    } else if let Ok(link_index) = index_str.parse::<usize>() {
-                        builder.push_style_ref(
-                            fm_core::IrStyleTarget::Link(link_index),
-                            style.to_string(),
-                            span,
-                        );
+                    } else {
+                        for index in index_str.split(',').map(str::trim) {
+                            match index.parse::<usize>() {
+                                Ok(link_index) if link_index < builder.edge_count() => {
+                                    builder.push_style_ref(
+                                        fm_core::IrStyleTarget::Link(link_index),
+                                        style.to_string(),
+                                        span,
+                                    );
+                                }
+                                _ => builder.add_diagnostic(
+                                    Diagnostic::warning(format!(
+                                        "linkStyle index '{index}' does not name one of the diagram's {} links (ignored)",
+                                        builder.edge_count()
+                                    ))
+                                    .with_category(DiagnosticCategory::Semantic)
+                                    .with_span(span)
+                                    .with_rule_id("linkstyle-unknown-link"),
+                                ),
+                            }
+                        }
                     }
                 }
             }
@@ -9936,7 +10007,7 @@ mod tests {
     use chumsky::Parser;
     use fm_core::{
         ArrowType, DiagnosticCategory, DiagnosticSeverity, DiagramType, GanttDate, GanttExclude,
-        GanttTaskType, GanttTickInterval, GraphDirection, IrEndpoint, IrLabelSegment,
+        GanttTaskType, GanttTickInterval, GraphDirection, IrEdge, IrEndpoint, IrLabelSegment,
         IrXySeriesKind, MermaidParseMode, NodeShape,
     };
 
@@ -10315,6 +10386,31 @@ mod tests {
         assert!(segments.contains(&IrLabelSegment::LineBreak));
     }
 
+    #[test]
+    fn flowchart_plain_labels_pick_up_markdown_emphasis_only_when_balanced() {
+        let parsed = parse_mermaid("flowchart LR\nA[**Bold** text] -->|*why*| B[2 * 3 * 4]");
+        let label_of = |id: &str| {
+            let node = parsed.ir.nodes.iter().find(|node| node.id == id).unwrap();
+            node.label.expect("label")
+        };
+
+        let bold = label_of("A");
+        assert_eq!(parsed.ir.labels[bold.0].text, "Bold text");
+        assert!(matches!(
+            parsed.ir.label_markup.get(&bold).map(Vec::as_slice),
+            Some([IrLabelSegment::Text { text, bold: true, .. }, IrLabelSegment::Text { bold: false, .. }])
+                if text == "Bold"
+        ));
+
+        let edge_label = parsed.ir.edges[0].label.expect("edge label");
+        assert_eq!(parsed.ir.labels[edge_label.0].text, "why");
+        assert!(parsed.ir.label_markup.contains_key(&edge_label));
+
+        let literal = label_of("B");
+        assert_eq!(parsed.ir.labels[literal.0].text, "2 * 3 * 4");
+        assert!(!parsed.ir.label_markup.contains_key(&literal));
+    }
+
     #[test]
     fn state_diagram_parallel_edge_syntax_does_not_create_edges() {
         // Parallel edge syntax (`A & B --> C`) is a flowchart feature.
@@ -10802,6 +10898,59 @@ mod tests {
         );
     }
 
+    #[test]
+    fn flowchart_click_href_keyword_and_callback_arguments() {
+        let parsed = parse_mermaid(
+            "flowchart LR\nA-->B-->C-->D\nclick A href \"https://example.com\" \"Docs\"\nclick B call open(\"B\", 2) \"Open\"\nclick C call fn()\nclick D call alert(1)+steal()",
+        );
+        let node = |id: &str| parsed.ir.nodes.iter().find(|node| node.id == id).unwrap();
+        assert_eq!(node("A").href(), Some("https://example.com"));
+        assert_eq!(node("A").tooltip(), Some("Docs"));
+        assert_eq!(node("B").callback(), Some("open"));
+        assert_eq!(node("B").tooltip(), Some("Open"));
+        assert_eq!(node("C").callback(), Some("fn"));
+        assert_eq!(node("D").callback(), None);
+        assert!(
+            parsed
+                .warnings
+                .iter()
+                .any(|warning| warning.contains("unsafe click callback blocked")),
+            "{:?}",
+            parsed.warnings
+        );
+    }
+
+    #[test]
+    fn flowchart_edge_ids_name_their_edges() {
+        let parsed = parse_mermaid(
+            "flowchart LR\nA e1@--> B e2@-.->|why| C\nB --> D\nA e3@--> B & C\nC e1@--> D",
+        );
+        let ids: Vec<_> = parsed
+            .ir
+            .nodes
+            .iter()
+            .map(|node| node.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["A", "B", "C", "D"]);
+        let user_ids: Vec<_> = parsed.ir.edges.iter().map(IrEdge::user_id).collect();
+        assert_eq!(
+            user_ids,
+            vec![Some("e1"), Some("e2"), None, None, None, None]
+        );
+        assert_eq!(parsed.ir.edges[1].arrow, ArrowType::DottedArrow);
+        assert!(parsed.ir.edges[1].label.is_some());
+        for expected in ["edge ids ignored", "duplicate edge id ignored: e1"] {
+            assert!(
+                parsed
+                    .warnings
+                    .iter()
+                    .any(|warning| warning.contains(expected)),
+                "{expected}: {:?}",
+                parsed.warnings
+            );
+        }
+    }
+
     #[test]
     fn flowchart_click_callback_directive_sets_callback_and_tooltip() {
         let parsed = parse_mermaid("flowchart LR\nA-->B\nclick B call focusNode \"Focus node\"");
@@ -12507,6 +12656,27 @@ Rel_Back(db, app, "Responds")"#,
         assert_eq!(meta.autonumber_increment, 5);
     }
 
+    #[test]
+    fn sequence_autonumber_numbers_only_messages_after_it_and_honors_off() {
+        let input = "sequenceDiagram\n  Alice->>Bob: one\n  autonumber\n  Bob->>Alice: two\n  \
+                     autonumber off\n  Alice->>Bob: three\n  autonumber 10 5\n  Bob->>Alice: four\n  \
+                     Alice->>Bob: five";
+        let parsed = parse_mermaid(input);
+        assert!(
+            !parsed.warnings.iter().any(|w| w.contains("autonumber")),
+            "{:?}",
+            parsed.warnings
+        );
+        let meta = parsed
+            .ir
+            .sequence_meta
+            .expect("sequence_meta should be set");
+        assert!(meta.autonumber);
+        assert_eq!(meta.autonumber_changes.len(), 3);
+        let numbers: Vec<_> = (0..5).map(|edge| meta.autonumber_value(edge)).collect();
+        assert_eq!(numbers, vec![None, Some(2), None, Some(10), Some(15)]);
+    }
+
     #[test]
     fn sequence_without_autonumber_has_no_meta() {
         let input = "sequenceDiagram\n  Alice->>Bob: Hello";
@@ -13173,6 +13343,92 @@ Rel_Back(db, app, "Responds")"#,
         assert_eq!(meta.methods[0].return_type, Some("void".to_string()));
     }
 
+    #[test]
+    fn class_methods_record_parameters_and_markers() {
+        use fm_core::{ClassVisibility, IrClassParameter};
+
+        let parsed = parse_mermaid(
+            "classDiagram\n  class Account {\n    -int total$\n    +deposit(amount: int, String note) bool\n    +count()$ int\n    #area()*\n    +merge(Map~K,V~ other, List<T> rest) : void\n    ~reset()\n  }",
+        );
+        let account = parsed.ir.nodes.iter().find(|n| n.id == "Account").unwrap();
+        let meta = account.class_meta.as_deref().expect("class meta");
+
+        let total = &meta.attributes[0];
+        assert_eq!((total.name.as_str(), total.is_static), ("int total", true));
+
+        let parameter = |name: &str, type_name: Option<&str>| IrClassParameter {
+            name: name.to_string(),
+            type_name: type_name.map(str::to_string),
+        };
+        let methods = &meta.methods;
+        assert_eq!(methods.len(), 5);
+        assert_eq!(methods[0].name, "deposit(amount: int, String note)");
+        assert_eq!(methods[0].return_type.as_deref(), Some("bool"));
+        assert_eq!(
+            methods[0].parameters,
+            vec![
+                parameter("amount", Some("int")),
+                parameter("note", Some("String"))
+            ]
+        );
+        assert_eq!(
+            (
+                methods[1].name.as_str(),
+                methods[1].is_static,
+                methods[1].return_type.as_deref()
+            ),
+            ("count()", true, Some("int"))
+        );
+        assert!(methods[1].parameters.is_empty());
+        assert_eq!(
+            (
+                methods[2].visibility,
+                methods[2].is_abstract,
+                methods[2].return_type.as_deref()
+            ),
+            (ClassVisibility::Protected, true, None)
+        );
+        assert_eq!(
+            methods[3].parameters,
+            vec![
+                parameter("other", Some("Map~K,V~")),
+                parameter("rest", Some("List<T>"))
+            ]
+        );
+        assert_eq!(methods[3].return_type.as_deref(), Some("void"));
+        assert_eq!(methods[4].visibility, ClassVisibility::Package);
+    }
+
+    #[test]
+    fn class_relation_operators_record_uml_relation_kind() {
+        use fm_core::ClassRelationKind;
+
+        let input = "classDiagram\n  Animal <|-- Duck\n  Fish --|> Animal\n  Shape <|.. Circle\n  Zoo *-- Animal\n  Duck --> Pond\n  Duck ..> Food\n  Duck -- Fish";
+        let parsed = parse_mermaid(input);
+        let relations: Vec<_> = parsed
+            .ir
+            .edges
+            .iter()
+            .map(|edge| {
+                let relation = edge.class_relation().expect("class relation");
+                (relation.kind, relation.marker_at_from)
+            })
+            .collect();
+        assert_eq!(
+            relations,
+            vec![
+                (ClassRelationKind::Inheritance, true),
+                (ClassRelationKind::Inheritance, false),
+                (ClassRelationKind::Realization, true),
+                (ClassRelationKind::Composition, true),
+                (ClassRelationKind::Association, false),
+                (ClassRelationKind::Dependency, false),
+                (ClassRelationKind::Link, false),
+            ]
+        );
+        assert!(parsed.ir.nodes.iter().any(|node| node.id == "Circle"));
+    }
+
     #[test]
     fn class_member_visibility_markers() {
         let input = "classDiagram\n  class Foo {\n    +pub_attr\n    -priv_attr\n    #prot_attr\n    ~pkg_attr\n  }";
@@ -13241,6 +13497,26 @@ Rel_Back(db, app, "Responds")"#,
         );
     }
 
+    #[test]
+    fn state_composite_pseudo_states_are_scoped_to_their_composite() {
+        let input = "stateDiagram-v2\n  [*] --> Active\n  state Active {\n    [*] --> Ready\n    Ready --> [*]\n  }\n  Active --> [*]";
+        let parsed = parse_mermaid(input);
+        let ids: Vec<&str> = parsed
+            .ir
+            .nodes
+            .iter()
+            .map(|node| node.id.as_str())
+            .collect();
+        for id in [
+            "__state_start",
+            "__state_end",
+            "__state_start_Active",
+            "__state_end_Active",
+        ] {
+            assert!(ids.contains(&id), "missing {id} in {ids:?}");
+        }
+    }
+
     #[test]
     fn state_composite_alias_and_regions_preserve_hierarchy() {
         let input = "stateDiagram-v2\n  state \"Active Mode\" as Active {\n    [*] --> Processing\n    state Worker {\n      [*] --> Busy\n    }\n    --\n    [*] --> Monitoring\n  }";
@@ -14223,6 +14499,33 @@ Rel_Back(db, app, "Responds")"#,
         }
     }
 
+    #[test]
+    fn linkstyle_index_lists_style_each_link_and_warn_on_unknown_ones() {
+        let parsed = parse_mermaid(
+            "flowchart LR\n  A --> B\n  B --> C\n  C --> D\n  linkStyle 0,2 stroke:#f00,stroke-width:4px\n  linkStyle 7 stroke:#0f0",
+        );
+        let stroke = |index: usize| {
+            parsed.ir.edges[index]
+                .inline_style
+                .as_ref()
+                .and_then(|style| style.properties.get("stroke").cloned())
+        };
+        assert_eq!(stroke(0).as_deref(), Some("#f00"));
+        assert_eq!(stroke(1), None);
+        assert_eq!(stroke(2).as_deref(), Some("#f00"));
+        assert!(
+            !parsed
+                .ir
+                .style_refs
+                .iter()
+                .any(|sr| sr.target == fm_core::IrStyleTarget::Link(7))
+        );
+        assert!(parsed.ir.diagnostics.iter().any(|diagnostic| {
+            diagnostic.rule_id.as_deref() == Some("linkstyle-unknown-link")
+                && diagnostic.message.contains("'7'")
+        }));
+    }
+
     #[test]
     fn classdef_and_style_cascade_correctly() {
         let parsed = parse_mermaid(
@@ -14348,6 +14651,32 @@ Rel_Back(db, app, "Responds")"#,
         assert_eq!(style.properties.get("font-weight").unwrap(), "bold");
     }
 
+    #[test]
+    fn classdef_name_lists_and_default_class_style_nodes() {
+        let parsed = parse_mermaid(
+            "flowchart LR\n  A --> B --> C\n  classDef default fill:#eee,color:#111\n  classDef hot,warm stroke:#f00\n  class B hot\n  class C warm",
+        );
+        let names: Vec<_> = parsed
+            .ir
+            .style_defs
+            .iter()
+            .map(|def| def.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["default", "hot", "warm"]);
+        let property = |id: &str, key: &str| {
+            let node = parsed.ir.nodes.iter().find(|node| node.id == id).unwrap();
+            node.inline_style
+                .as_ref()
+                .and_then(|style| style.properties.get(key).cloned())
+        };
+        assert_eq!(property("A", "fill").as_deref(), Some("#eee"));
+        assert_eq!(property("A", "stroke"), None);
+        for id in ["B", "C"] {
+            assert_eq!(property(id, "stroke").as_deref(), Some("#f00"));
+            assert_eq!(property(id, "color").as_deref(), Some("#111"));
+        }
+    }
+
     #[test]
     fn classdef_applied_to_nonexistent_node_no_error() {
         // Applying class to node that doesn't exist shouldn't cause a crash
@@ -14777,6 +15106,34 @@ Rel_Back(db, app, "Responds")"#,
         );
     }
 
+    #[test]
+    fn gitgraph_meta_records_branch_of_every_commit() {
+        let parsed = parse_mermaid(
+            "gitGraph\n  commit id: a\n  branch dev\n  commit id: b\n  checkout main\n  merge dev id: m\n  checkout hotfix\n  commit id: h",
+        );
+        let meta = parsed
+            .ir
+            .gitgraph_meta
+            .expect("gitgraph_meta should be set");
+        assert_eq!(meta.branches, vec!["main", "dev", "hotfix"]);
+        // `branch dev` checks dev out, so `b` lands on it; the merge commit stays on main.
+        let branches: Vec<usize> = meta.commits.iter().map(|commit| commit.branch).collect();
+        assert_eq!(branches, vec![0, 1, 0, 2]);
+        let merge = parsed
+            .ir
+            .nodes
+            .iter()
+            .position(|node| node.id == "m")
+            .expect("merge commit");
+        assert_eq!(meta.branch_of(fm_core::IrNodeId(merge)), Some(0));
+        assert!(
+            parsed.ir.nodes[merge]
+                .classes
+                .iter()
+                .any(|c| c == "git-branch-0")
+        );
+    }
+
     // ── Kanban metadata parsing tests ─────────────────────────────────
 
     #[test]
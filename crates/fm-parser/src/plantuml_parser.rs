@@ -0,0 +1,1100 @@
+//! PlantUML import for sequence and class diagrams.
+//!
+//! PlantUML's sequence and class syntax is close to Mermaid's, so rather than lowering it a second
+//! way, each supported line is rewritten as the Mermaid statement it means and the rewrite goes
+//! through the Mermaid sequence or class parser. A PlantUML file therefore produces the same IR as
+//! its Mermaid equivalent. The rewrite keeps one line per source line, so spans and `Line N:`
+//! warnings point into the PlantUML file.
+//!
+//! Three things have no one-line Mermaid form and are handled around the rewrite. The `title` and
+//! class stereotypes (`interface Shape`, `class Order <<Entity>>`) are applied to the parsed IR.
+//! `extends`/`implements` on a class body header become relations written on the `@enduml` line.
+
+use fm_core::{ClassStereotype, DiagramType, MermaidParseMode};
+
+use crate::{DetectedType, DetectionMethod, ParseResult, ParserConfig, mermaid_parser};
+
+/// Participant keywords. `actor` and `participant` map to themselves, the rest to a participant
+/// whose `type` picks the matching shape.
+const PARTICIPANT_KEYWORDS: [&str; 8] = [
+    "participant",
+    "actor",
+    "boundary",
+    "control",
+    "entity",
+    "database",
+    "collections",
+    "queue",
+];
+
+/// Sequence fragments with a Mermaid counterpart of the same name.
+const FRAGMENT_KEYWORDS: [&str; 6] = ["alt", "opt", "loop", "par", "break", "critical"];
+
+/// Class-like declarations, longest first so `abstract class` wins over `abstract`.
+const CLASS_KEYWORDS: [&str; 6] = [
+    "abstract class",
+    "abstract",
+    "class",
+    "interface",
+    "enum",
+    "annotation",
+];
+
+/// Layout and styling statements that carry no diagram structure.
+const PRESENTATION_KEYWORDS: [&str; 11] = [
+    "skinparam",
+    "hide",
+    "show",
+    "scale",
+    "header",
+    "footer",
+    "caption",
+    "autoactivate",
+    "newpage",
+    "left to right direction",
+    "top to bottom direction",
+];
+
+/// Whether `input` is a PlantUML document: its first line that is neither blank nor a `'`
+/// comment is `@startuml`.
+#[must_use]
+pub fn looks_like_plantuml(input: &str) -> bool {
+    input
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('\''))
+        .is_some_and(|line| starts_with_ignore_ascii_case(line, "@startuml"))
+}
+
+/// The diagram family of a PlantUML document: [`DiagramType::Class`] when it declares a class,
+/// interface, enum or package or draws an inheritance, composition or aggregation arrow,
+/// [`DiagramType::Sequence`] otherwise.
+#[must_use]
+pub fn plantuml_diagram_type(input: &str) -> DiagramType {
+    let is_class = input.lines().map(str::trim).any(|line| {
+        CLASS_KEYWORDS
+            .iter()
+            .chain(&["package", "namespace"])
+            .any(|keyword| keyword_rest(line, keyword).is_some())
+            || ["<|", "|>", "*--", "--*", "o--", "--o"]
+                .iter()
+                .any(|operator| line.contains(operator))
+    });
+    if is_class {
+        DiagramType::Class
+    } else {
+        DiagramType::Sequence
+    }
+}
+
+/// How `input`, a PlantUML document, was detected. Documents with no class syntax, sequence
+/// arrow or participant declaration are another PlantUML kind (activity, use case,
+/// component, ...): they are still read as a sequence diagram, but with low confidence and a
+/// warning instead of an empty diagram presented as a confident match.
+#[must_use]
+pub fn detect_plantuml_type(input: &str) -> DetectedType {
+    let diagram_type = plantuml_diagram_type(input);
+    let recognized = diagram_type == DiagramType::Class
+        || input
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.starts_with('\''))
+            .any(|line| {
+                participant_declaration(line).is_some()
+                    || keyword_rest(line, "create").is_some()
+                    || sequence_message(line).is_some()
+            });
+    if recognized {
+        DetectedType {
+            diagram_type,
+            confidence: 0.95,
+            method: DetectionMethod::PlantUmlFormat,
+            warnings: vec![],
+        }
+    } else {
+        DetectedType {
+            diagram_type,
+            confidence: 0.3,
+            method: DetectionMethod::PlantUmlFormat,
+            warnings: vec![
+                "Unsupported PlantUML diagram kind; only sequence and class diagrams are imported"
+                    .to_string(),
+            ],
+        }
+    }
+}
+
+#[must_use]
+pub fn parse_plantuml(input: &str) -> ParseResult {
+    parse_plantuml_with_mode_and_config(input, MermaidParseMode::Compat, &ParserConfig::default())
+}
+
+pub(crate) fn parse_plantuml_with_mode_and_config(
+    input: &str,
+    parse_mode: MermaidParseMode,
+    config: &ParserConfig,
+) -> ParseResult {
+    let mut detection = detect_plantuml_type(input);
+    let translation = Translator::new(detection.diagram_type).translate(input);
+    detection.warnings.extend(translation.warnings);
+    let mut result = mermaid_parser::parse_mermaid_with_detection_and_config(
+        &translation.mermaid,
+        detection,
+        parse_mode,
+        config,
+    );
+
+    if let Some(title) = translation.title {
+        result.ir.meta.title = Some(title);
+    }
+    for (class_name, stereotype) in translation.stereotypes {
+        let id = crate::normalize_identifier(&class_name);
+        if let Some(node) = result.ir.nodes.iter_mut().find(|node| node.id == id) {
+            node.class_meta.get_or_insert_with(Box::default).stereotype = Some(stereotype);
+        }
+    }
+    result
+}
+
+#[derive(Debug, Default)]
+struct Translation {
+    mermaid: String,
+    warnings: Vec<String>,
+    title: Option<String>,
+    stereotypes: Vec<(String, ClassStereotype)>,
+}
+
+/// An open `{ ... }` or `... end` block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+    Class,
+    Package,
+    /// A fragment or box written out, closed by a Mermaid `end`.
+    Fragment,
+    /// A block whose delimiters are dropped but whose content is kept (`group`, `together {`).
+    Unwrapped,
+    /// A block dropped with its content (`skinparam x {`).
+    Styling,
+}
+
+/// A multi-line note collected up to `end note`. `head` is `None` for a note Mermaid can't place.
+#[derive(Debug)]
+struct PendingNote {
+    head: Option<String>,
+    lines: Vec<String>,
+}
+
+struct Translator {
+    diagram_type: DiagramType,
+    blocks: Vec<Block>,
+    note: Option<PendingNote>,
+    in_comment: bool,
+    /// Relations from `extends`/`implements` on class body headers.
+    deferred: Vec<String>,
+    enduml_index: Option<usize>,
+    translation: Translation,
+}
+
+impl Translator {
+    fn new(diagram_type: DiagramType) -> Self {
+        Self {
+            diagram_type,
+            blocks: Vec::new(),
+            note: None,
+            in_comment: false,
+            deferred: Vec::new(),
+            enduml_index: None,
+            translation: Translation::default(),
+        }
+    }
+
+    fn translate(mut self, input: &str) -> Translation {
+        let mut lines = Vec::new();
+        for (index, line) in input.lines().enumerate() {
+            let trimmed = line.trim();
+            let statement = self.line(index, trimmed).unwrap_or_else(|| {
+                self.translation.warnings.push(format!(
+                    "Line {}: unsupported PlantUML syntax: {trimmed}",
+                    index + 1
+                ));
+                String::new()
+            });
+            lines.push(statement);
+        }
+        if !self.deferred.is_empty() {
+            let relations = self.deferred.join("; ");
+            match self.enduml_index {
+                Some(index) => lines[index] = relations,
+                None => lines.push(relations),
+            }
+        }
+        self.translation.mermaid = lines.join("\n");
+        self.translation
+    }
+
+    /// The Mermaid statement for one trimmed source line: empty for lines with nothing to draw,
+    /// `None` for syntax outside the supported subset.
+    fn line(&mut self, index: usize, line: &str) -> Option<String> {
+        if self.in_comment {
+            self.in_comment = !line.ends_with("'/");
+            return Some(String::new());
+        }
+        if let Some(mut note) = self.note.take() {
+            if is_note_end(line) {
+                return Some(note.head.map_or_else(String::new, |head| {
+                    format!("{head}: {}", note.lines.join("<br/>"))
+                }));
+            }
+            note.lines.push(line.to_string());
+            self.note = Some(note);
+            return Some(String::new());
+        }
+
+        if self.blocks.last() == Some(&Block::Styling) {
+            if line.starts_with('}') {
+                self.blocks.pop();
+            }
+            return Some(String::new());
+        }
+        if line.is_empty() || line.starts_with('\'') {
+            return Some(String::new());
+        }
+        if let Some(comment) = line.strip_prefix("/'") {
+            self.in_comment = !comment.ends_with("'/");
+            return Some(String::new());
+        }
+        if starts_with_ignore_ascii_case(line, "@startuml") {
+            return Some(String::new());
+        }
+        if starts_with_ignore_ascii_case(line, "@enduml") {
+            self.enduml_index = Some(index);
+            return Some(String::new());
+        }
+        if let Some(title) = keyword_rest(line, "title") {
+            self.translation.title = Some(unquote(title).to_string());
+            return Some(String::new());
+        }
+        if self.diagram_type == DiagramType::Sequence && line == "hide footbox" {
+            return Some(line.to_string());
+        }
+        if line.starts_with('!')
+            || PRESENTATION_KEYWORDS
+                .iter()
+                .any(|keyword| keyword_rest(line, keyword).is_some())
+        {
+            if line.ends_with('{') {
+                self.blocks.push(Block::Styling);
+            }
+            return Some(String::new());
+        }
+
+        if self.diagram_type == DiagramType::Class {
+            self.class_line(line)
+        } else {
+            self.sequence_line(line)
+        }
+    }
+
+    fn sequence_line(&mut self, line: &str) -> Option<String> {
+        if line == "end" || line == "end box" {
+            return match self.blocks.pop()? {
+                Block::Fragment => Some("end".to_string()),
+                _ => Some(String::new()),
+            };
+        }
+        if let Some(label) = keyword_rest(line, "else") {
+            return Some(format!("else {label}").trim_end().to_string());
+        }
+        for keyword in FRAGMENT_KEYWORDS {
+            if let Some(label) = keyword_rest(line, keyword) {
+                self.blocks.push(Block::Fragment);
+                return Some(format!("{keyword} {label}").trim_end().to_string());
+            }
+        }
+        if keyword_rest(line, "group").is_some() {
+            self.blocks.push(Block::Unwrapped);
+            return Some(String::new());
+        }
+        if let Some(rest) = keyword_rest(line, "box") {
+            self.blocks.push(Block::Fragment);
+            let (label, color) = match rest.rsplit_once('#') {
+                Some((label, color)) => (label.trim(), Some(color.trim())),
+                None => (rest, None),
+            };
+            let label = unquote(label);
+            return Some(match color {
+                Some(color) => format!("box #{color} {label}").trim_end().to_string(),
+                None => format!("box {label}").trim_end().to_string(),
+            });
+        }
+
+        if let Some(declaration) = participant_declaration(line) {
+            return Some(declaration);
+        }
+        if let Some(rest) = keyword_rest(line, "create") {
+            let rest = PARTICIPANT_KEYWORDS
+                .iter()
+                .find(|keyword| keyword_rest(rest, keyword).is_some())
+                .map_or_else(|| format!("participant {rest}"), |_| rest.to_string());
+            return participant_declaration(&rest)
+                .map(|declaration| format!("create {declaration}"));
+        }
+        for keyword in ["activate", "deactivate", "destroy"] {
+            if let Some(rest) = keyword_rest(line, keyword) {
+                let name = strip_decorations(rest);
+                return (!name.is_empty()).then(|| format!("{keyword} {name}"));
+            }
+        }
+        if let Some(rest) = keyword_rest(line, "autonumber") {
+            return Some(match rest {
+                "stop" => "autonumber off".to_string(),
+                "resume" => "autonumber".to_string(),
+                _ => std::iter::once("autonumber")
+                    .chain(
+                        rest.split_whitespace()
+                            .take_while(|token| token.parse::<u32>().is_ok())
+                            .take(2),
+                    )
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            });
+        }
+        if let Some(rest) = ["note", "rnote", "hnote"]
+            .iter()
+            .find_map(|keyword| keyword_rest(line, keyword))
+        {
+            return self.sequence_note(rest);
+        }
+        // Delays, spacers and dividers only affect vertical spacing.
+        if line.starts_with("...")
+            || (line.starts_with("||") && line.ends_with("||"))
+            || line.starts_with("==")
+        {
+            return Some(String::new());
+        }
+
+        sequence_message(line)
+    }
+
+    fn sequence_note(&mut self, rest: &str) -> Option<String> {
+        let (target, text) = split_label(rest);
+        let target = strip_decorations(target);
+        let head = ["left of ", "right of ", "over "]
+            .iter()
+            .any(|position| target.starts_with(position))
+            .then(|| format!("Note {target}"));
+        match text {
+            Some(text) => head.map(|head| format!("{head}: {}", display_text(text))),
+            None => {
+                let supported = head.is_some();
+                self.note = Some(PendingNote {
+                    head,
+                    lines: Vec::new(),
+                });
+                supported.then(String::new)
+            }
+        }
+    }
+
+    fn class_line(&mut self, line: &str) -> Option<String> {
+        if line.starts_with('}') {
+            return match self.blocks.pop()? {
+                Block::Class | Block::Package => Some("}".to_string()),
+                Block::Fragment | Block::Unwrapped | Block::Styling => Some(String::new()),
+            };
+        }
+        if self.blocks.last() == Some(&Block::Class) {
+            return Some(class_member(line));
+        }
+
+        if let Some(rest) = ["package", "namespace"]
+            .iter()
+            .find_map(|keyword| keyword_rest(line, keyword))
+        {
+            let rest = rest.strip_suffix('{')?.trim_end();
+            let (name, _) = take_class_name(rest);
+            let name = unquote(name);
+            if name.is_empty() {
+                return None;
+            }
+            self.blocks.push(Block::Package);
+            return Some(format!("namespace {name} {{"));
+        }
+        if line == "together {" {
+            self.blocks.push(Block::Unwrapped);
+            return Some(String::new());
+        }
+        if let Some(rest) = keyword_rest(line, "note") {
+            // Class notes have no Mermaid form; skip the body of a multi-line one too.
+            if split_label(rest).1.is_none() && !rest.contains(" as ") {
+                self.note = Some(PendingNote {
+                    head: None,
+                    lines: Vec::new(),
+                });
+            }
+            return None;
+        }
+
+        if let Some(declaration) = self.class_declaration(line) {
+            return declaration;
+        }
+        class_relation(line)
+    }
+
+    /// `Some(None)` for a declaration that could not be read, `None` when `line` is not one.
+    fn class_declaration(&mut self, line: &str) -> Option<Option<String>> {
+        let (keyword, rest) = CLASS_KEYWORDS
+            .iter()
+            .find_map(|keyword| keyword_rest(line, keyword).map(|rest| (*keyword, rest)))?;
+        let (rest, opens_body) = match rest.strip_suffix('{') {
+            Some(rest) => (rest.trim_end(), true),
+            None => (rest, false),
+        };
+        let (mut name, mut rest) = take_class_name(rest);
+        let mut stereotype = match keyword {
+            "abstract class" | "abstract" => Some(ClassStereotype::Abstract),
+            "interface" => Some(ClassStereotype::Interface),
+            "enum" => Some(ClassStereotype::Enum),
+            "annotation" => Some(ClassStereotype::Custom("annotation".to_string())),
+            _ => None,
+        };
+        let mut relations = Vec::new();
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix("<<") {
+                let Some((annotation, after)) = after.split_once(">>") else {
+                    return Some(None);
+                };
+                stereotype = Some(class_stereotype(annotation));
+                rest = after.trim_start();
+            } else if let Some(after) = keyword_rest(rest, "as") {
+                (name, rest) = take_class_name(after);
+            } else if let Some((operator, after)) = [("extends", "<|--"), ("implements", "<|..")]
+                .iter()
+                .find_map(|(keyword, operator)| {
+                    keyword_rest(rest, keyword).map(|after| (*operator, after))
+                })
+            {
+                let end = [" extends ", " implements ", "<<", " #"]
+                    .iter()
+                    .filter_map(|stop| after.find(stop))
+                    .min()
+                    .unwrap_or(after.len());
+                relations.extend(
+                    after[..end]
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|base| !base.is_empty())
+                        .map(|base| (operator, base.to_string())),
+                );
+                rest = after[end..].trim_start();
+            } else if rest.starts_with('#') {
+                rest = rest
+                    .split_once(char::is_whitespace)
+                    .map_or("", |(_, after)| after.trim_start());
+            } else {
+                return Some(None);
+            }
+        }
+
+        let name = unquote_name(name);
+        if name.is_empty() {
+            return Some(None);
+        }
+        let base_name = name.split('<').next().unwrap_or(&name).to_string();
+        if let Some(stereotype) = stereotype {
+            self.translation
+                .stereotypes
+                .push((base_name.clone(), stereotype));
+        }
+        let relations = relations
+            .into_iter()
+            .map(|(operator, base)| format!("{} {operator} {base_name}", generic_name(&base)));
+        let declaration = format!("class {}", generic_name(&name));
+        if opens_body {
+            self.blocks.push(Block::Class);
+            self.deferred.extend(relations);
+            Some(Some(format!("{declaration} {{")))
+        } else {
+            Some(Some(
+                std::iter::once(declaration)
+                    .chain(relations)
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            ))
+        }
+    }
+}
+
+/// `participant`, `actor` or a typed participant (`database Orders`), with an optional display
+/// name: `participant "Web Shop" as Shop` or `participant Shop as "Web Shop"`.
+fn participant_declaration(line: &str) -> Option<String> {
+    let (keyword, rest) = PARTICIPANT_KEYWORDS
+        .iter()
+        .find_map(|keyword| keyword_rest(line, keyword).map(|rest| (*keyword, rest)))?;
+    let rest = strip_decorations(rest);
+    let (id, label) = match rest.split_once(" as ") {
+        Some((label, id)) if label.starts_with('"') => (id.trim(), Some(unquote(label))),
+        Some((id, label)) => (id.trim(), Some(unquote(label.trim()))),
+        None => (rest, None),
+    };
+    if id.is_empty() {
+        return None;
+    }
+    let declaration = match keyword {
+        "participant" | "actor" => format!("{keyword} {id}"),
+        kind => format!("participant {id} {{\"type\": \"{kind}\"}}"),
+    };
+    Some(match label {
+        Some(label) => format!("{declaration} as {label}"),
+        None => declaration,
+    })
+}
+
+/// `A -> B : text` and its variants: `-->` dashed, `->>` thin head, `->x` lost, `<-` reversed,
+/// `<->` both ends, `-[#red]>` coloured, and `++`/`--` activation after the target.
+fn sequence_message(line: &str) -> Option<String> {
+    let (head, text) = split_label(line);
+    let bytes = head.as_bytes();
+    let start = bytes.iter().position(|&byte| matches!(byte, b'-' | b'<'))?;
+    let mut end = start;
+    while let Some(&byte) = bytes.get(end) {
+        match byte {
+            b'-' | b'<' | b'>' | b'\\' | b'/' => end += 1,
+            b'[' => end += head[end..].find(']')? + 1,
+            _ => break,
+        }
+    }
+    if matches!(bytes.get(end), Some(b'x' | b'o'))
+        && bytes
+            .get(end + 1)
+            .is_none_or(|byte| byte.is_ascii_whitespace())
+    {
+        end += 1;
+    }
+
+    let mut arrow = head[start..end].to_string();
+    while let (Some(open), Some(close)) = (arrow.find('['), arrow.find(']')) {
+        arrow.replace_range(open..=close.max(open), "");
+    }
+    let from = head[..start].trim();
+    let mut to = head[end..].trim();
+    let mut activation = "";
+    for (suffix, marker) in [("++", "+"), ("--", "-"), ("**", ""), ("!!", "")] {
+        if let Some(target) = to.strip_suffix(suffix) {
+            to = target.trim_end();
+            activation = marker;
+            break;
+        }
+    }
+    if from.is_empty() || to.is_empty() || from.starts_with('[') || to.ends_with(']') {
+        return None;
+    }
+
+    let backward = arrow.starts_with('<');
+    let forward = arrow.ends_with(['>', '\\', '/', 'x', 'o']);
+    let dotted = arrow.contains("--");
+    let operator = match (backward, forward) {
+        (false, false) => return None,
+        (true, true) if dotted => "<<-->>",
+        (true, true) => "<<->>",
+        _ => {
+            let cross = !backward && arrow.ends_with('x');
+            let thin = if backward {
+                arrow.starts_with("<<")
+            } else {
+                arrow.ends_with(">>") || arrow.ends_with("\\\\") || arrow.ends_with("//")
+            };
+            match (cross, thin, dotted) {
+                (true, _, false) => "-x",
+                (true, _, true) => "--x",
+                (false, true, false) => "-)",
+                (false, true, true) => "--)",
+                (false, false, false) => "->>",
+                (false, false, true) => "-->>",
+            }
+        }
+    };
+    let (from, to) = if backward && !forward {
+        (to, from)
+    } else {
+        (from, to)
+    };
+
+    let message = format!("{from}{operator}{activation}{to}");
+    Some(match text.filter(|text| !text.is_empty()) {
+        Some(text) => format!("{message}: {}", display_text(text)),
+        None => message,
+    })
+}
+
+/// A class body line as a Mermaid member. Separator lines (`--`, `..`, `==`, `__`) become empty.
+fn class_member(line: &str) -> String {
+    if ["--", "..", "==", "__"]
+        .iter()
+        .any(|separator| line.starts_with(separator))
+    {
+        return String::new();
+    }
+    let mut member = line;
+    let mut markers = String::new();
+    while let Some(rest) = member.strip_prefix('{') {
+        let Some((modifier, after)) = rest.split_once('}') else {
+            break;
+        };
+        match modifier.trim() {
+            "static" | "classifier" => markers.push('$'),
+            "abstract" => markers.push('*'),
+            _ => {}
+        }
+        member = after.trim_start();
+    }
+    format!("{member}{markers}")
+}
+
+/// `A "1" *-- "many" B : label`, with PlantUML's longer (`<|---`), directed (`-up->`) and
+/// coloured (`-[#red]->`) arrows reduced to the Mermaid operator they draw. Aggregation (`o--`)
+/// and plain dotted links (`..`) have no Mermaid operator and are drawn as `--`.
+fn class_relation(line: &str) -> Option<String> {
+    let (head, label) = split_label(line);
+    let bytes = head.as_bytes();
+    let mut in_quote = false;
+    let mut start = None;
+    for (index, &byte) in bytes.iter().enumerate() {
+        match byte {
+            b'"' => in_quote = !in_quote,
+            b'-' if !in_quote => {
+                start = Some(index);
+                break;
+            }
+            b'.' if !in_quote && bytes.get(index + 1) == Some(&b'.') => {
+                start = Some(index);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let start = start?;
+
+    let before = &head[..start];
+    let left_head = if before.ends_with("<|") {
+        "<|"
+    } else if before.ends_with(['<', '*']) {
+        &before[before.len() - 1..]
+    } else if before.ends_with('o') && before[..before.len() - 1].ends_with(char::is_whitespace) {
+        "o"
+    } else {
+        ""
+    };
+
+    let mut end = start;
+    'body: while let Some(&byte) = bytes.get(end) {
+        match byte {
+            b'-' | b'.' => end += 1,
+            b'[' => end += head[end..].find(']')? + 1,
+            _ if bytes[end - 1] == b'-' => {
+                for direction in [
+                    "right", "left", "down", "up", "ri", "le", "do", "r", "l", "d", "u",
+                ] {
+                    if head[end..]
+                        .strip_prefix(direction)
+                        .is_some_and(|after| after.starts_with('-'))
+                    {
+                        end += direction.len();
+                        continue 'body;
+                    }
+                }
+                break;
+            }
+            _ => break,
+        }
+    }
+    let after = &head[end..];
+    let right_head = if after.starts_with("|>") {
+        "|>"
+    } else if after.starts_with(['>', '*']) {
+        &after[..1]
+    } else if after.starts_with('o') && after[1..].starts_with(char::is_whitespace) {
+        "o"
+    } else {
+        ""
+    };
+    let dotted = head[start..end].contains('.');
+
+    let (operator, reversed) = match (left_head, right_head) {
+        ("<|", _) => (if dotted { "<|.." } else { "<|--" }, false),
+        (_, "|>") => (if dotted { "..|>" } else { "--|>" }, false),
+        ("*", _) => ("*--", false),
+        (_, "*") => ("--*", false),
+        ("<", ">") => ("--", false),
+        (_, ">") => (if dotted { "..>" } else { "-->" }, false),
+        ("<", _) if dotted => ("<..", false),
+        ("<", _) => ("-->", true),
+        _ => ("--", false),
+    };
+
+    let (from, from_card) = split_cardinality_suffix(head[..start - left_head.len()].trim());
+    let (to_card, to) = split_cardinality_prefix(after[right_head.len()..].trim());
+    if from.is_empty() || to.is_empty() {
+        return None;
+    }
+    let (from, from_card, to_card, to) = if reversed {
+        (to, to_card, from_card, from)
+    } else {
+        (from, from_card, to_card, to)
+    };
+
+    let mut relation = generic_name(from);
+    for part in [from_card, Some(operator), to_card].into_iter().flatten() {
+        relation.push(' ');
+        relation.push_str(part);
+    }
+    relation.push(' ');
+    relation.push_str(&generic_name(to));
+    let label = label
+        .map(|label| label.trim_matches(['<', '>']).trim())
+        .filter(|label| !label.is_empty());
+    if let Some(label) = label {
+        relation.push_str(" : ");
+        relation.push_str(label);
+    }
+    Some(relation)
+}
+
+/// `A "1"` → (`A`, `"1"`).
+fn split_cardinality_suffix(side: &str) -> (&str, Option<&str>) {
+    if side.ends_with('"')
+        && let Some(open) = side[..side.len() - 1].rfind('"')
+        && open > 0
+    {
+        return (side[..open].trim_end(), Some(&side[open..]));
+    }
+    (side, None)
+}
+
+/// `"many" B` → (`"many"`, `B`).
+fn split_cardinality_prefix(side: &str) -> (Option<&str>, &str) {
+    if let Some(rest) = side.strip_prefix('"')
+        && let Some(close) = rest.find('"')
+        && close + 2 < side.len()
+    {
+        return (Some(&side[..close + 2]), side[close + 2..].trim_start());
+    }
+    (None, side)
+}
+
+/// The class name at the start of `rest` (quoted, or running to whitespace or a stereotype,
+/// generics included) and what follows it.
+fn take_class_name(rest: &str) -> (&str, &str) {
+    if let Some(quoted) = rest.strip_prefix('"') {
+        let end = quoted.find('"').map_or(rest.len(), |close| close + 2);
+        return (&rest[..end], rest[end..].trim_start());
+    }
+    let mut depth = 0usize;
+    for (index, ch) in rest.char_indices() {
+        match ch {
+            '<' if depth == 0 && rest[index..].starts_with("<<") => {
+                return (&rest[..index], rest[index..].trim_start());
+            }
+            '<' => depth += 1,
+            '>' => depth = depth.saturating_sub(1),
+            ch if ch.is_whitespace() && depth == 0 => {
+                return (&rest[..index], rest[index..].trim_start());
+            }
+            _ => {}
+        }
+    }
+    (rest, "")
+}
+
+/// A class name without quotes, its words joined by `_` so it stays one Mermaid token.
+fn unquote_name(name: &str) -> String {
+    unquote(name)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// `List<Map<K, V>>` → `List~Map<K,V>~`, the Mermaid generic form.
+fn generic_name(name: &str) -> String {
+    match name.find('<') {
+        Some(open) if name.ends_with('>') => {
+            let parameters: String = name[open + 1..name.len() - 1]
+                .chars()
+                .filter(|ch| !ch.is_whitespace())
+                .collect();
+            format!("{}~{parameters}~", &name[..open])
+        }
+        _ => name.to_string(),
+    }
+}
+
+fn class_stereotype(annotation: &str) -> ClassStereotype {
+    let annotation = annotation.trim();
+    // `<< (S,#FF7700) Singleton >>` puts a spot character before the name.
+    let annotation = match annotation.strip_prefix('(') {
+        Some(rest) => rest.split_once(')').map_or(rest, |(_, name)| name.trim()),
+        None => annotation,
+    };
+    match annotation.to_ascii_lowercase().as_str() {
+        "interface" => ClassStereotype::Interface,
+        "abstract" => ClassStereotype::Abstract,
+        "enum" | "enumeration" => ClassStereotype::Enum,
+        "service" => ClassStereotype::Service,
+        _ => ClassStereotype::Custom(annotation.to_string()),
+    }
+}
+
+fn is_note_end(line: &str) -> bool {
+    [
+        "end note",
+        "endnote",
+        "end rnote",
+        "endrnote",
+        "end hnote",
+        "endhnote",
+    ]
+    .contains(&line)
+}
+
+/// `text` before the first `:` outside double quotes, and what follows it.
+fn split_label(line: &str) -> (&str, Option<&str>) {
+    let mut in_quote = false;
+    for (index, ch) in line.char_indices() {
+        match ch {
+            '"' => in_quote = !in_quote,
+            ':' if !in_quote => return (line[..index].trim_end(), Some(line[index + 1..].trim())),
+            _ => {}
+        }
+    }
+    (line, None)
+}
+
+/// `text` with trailing `#color`, `<<stereotype>>` and `order N` decorations removed.
+fn strip_decorations(text: &str) -> &str {
+    let end = [" #", " <<", " order "]
+        .iter()
+        .filter_map(|decoration| text.find(decoration))
+        .min()
+        .unwrap_or(text.len());
+    text[..end].trim()
+}
+
+/// PlantUML's `\n` line breaks as the `<br/>` Mermaid display text uses.
+fn display_text(text: &str) -> String {
+    text.replace("\\n", "<br/>")
+}
+
+fn unquote(text: &str) -> &str {
+    let text = text.trim();
+    text.strip_prefix('"')
+        .and_then(|inner| inner.strip_suffix('"'))
+        .unwrap_or(text)
+}
+
+/// The rest of `line` after `keyword` when `keyword` is a whole word at its start.
+fn keyword_rest<'a>(line: &'a str, keyword: &str) -> Option<&'a str> {
+    let rest = line.strip_prefix(keyword)?;
+    (rest.is_empty() || rest.starts_with(char::is_whitespace)).then(|| rest.trim())
+}
+
+fn starts_with_ignore_ascii_case(line: &str, prefix: &str) -> bool {
+    line.as_bytes()
+        .get(..prefix.len())
+        .is_some_and(|head| head.eq_ignore_ascii_case(prefix.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use fm_core::{
+        ClassStereotype, ClassVisibility, DiagramType, IrEndpoint, IrLabelId, MermaidDiagramIr,
+    };
+
+    use super::{detect_plantuml_type, looks_like_plantuml, parse_plantuml, plantuml_diagram_type};
+    use crate::DetectionMethod;
+
+    fn label(ir: &MermaidDiagramIr, label: Option<IrLabelId>) -> Option<&str> {
+        label.map(|label| ir.labels[label.0].text.as_str())
+    }
+
+    fn endpoint(ir: &MermaidDiagramIr, endpoint: IrEndpoint) -> &str {
+        match endpoint {
+            IrEndpoint::Node(node) => &ir.nodes[node.0].id,
+            _ => "?",
+        }
+    }
+
+    /// Nodes and edges with labels and endpoints resolved, so two parses can be compared
+    /// without their spans.
+    fn projection(ir: &MermaidDiagramIr) -> Vec<String> {
+        let nodes = ir.nodes.iter().map(|node| {
+            format!(
+                "{} {:?} {:?} {:?}",
+                node.id,
+                label(ir, node.label),
+                node.shape,
+                node.classes
+            )
+        });
+        let edges = ir.edges.iter().map(|edge| {
+            format!(
+                "{} {:?} {} {:?}",
+                endpoint(ir, edge.from),
+                edge.arrow,
+                endpoint(ir, edge.to),
+                label(ir, edge.label)
+            )
+        });
+        nodes.chain(edges).collect()
+    }
+
+    #[test]
+    fn detects_plantuml_documents_and_their_family() {
+        assert!(looks_like_plantuml(
+            "' generated\n@startuml\nA -> B\n@enduml"
+        ));
+        assert!(!looks_like_plantuml("sequenceDiagram\nA->>B: hi"));
+        assert_eq!(
+            plantuml_diagram_type("@startuml\nAlice -> Bob : hi\n@enduml"),
+            DiagramType::Sequence
+        );
+        assert_eq!(
+            plantuml_diagram_type("@startuml\nAnimal <|-- Dog\n@enduml"),
+            DiagramType::Class
+        );
+    }
+
+    #[test]
+    fn other_plantuml_kinds_are_detected_with_low_confidence() {
+        let sequence = detect_plantuml_type("@startuml\nparticipant Alice\n@enduml");
+        assert_eq!(sequence.confidence, 0.95);
+        assert!(sequence.warnings.is_empty());
+
+        let activity = "@startuml\nstart\n:Load config;\nstop\n@enduml";
+        let detection = detect_plantuml_type(activity);
+        assert!(detection.confidence < 0.5);
+        assert!(detection.warnings[0].contains("Unsupported PlantUML diagram kind"));
+        let result = parse_plantuml(activity);
+        assert!(result.confidence < 0.5);
+        assert!(
+            result
+                .warnings
+                .iter()
+                .any(|warning| warning.contains("Unsupported PlantUML diagram kind"))
+        );
+    }
+
+    #[test]
+    fn sequence_diagrams_parse_like_their_mermaid_equivalent() {
+        let parsed = parse_plantuml(
+            "@startuml
+title Checkout
+actor User
+participant \"Web Shop\" as Shop
+database Orders
+autonumber
+User -> Shop ++ : place order
+Shop -> Orders : insert\\nrow
+Orders --> Shop
+Shop ->> User : confirmation
+note right of User : waits
+alt paid
+  Shop -[#green]> Orders : commit
+else declined
+  User <- Shop : retry
+end
+skinparam sequence {
+  ArrowColor red
+}
+@enduml",
+        );
+        let mermaid = crate::parse(
+            "sequenceDiagram
+actor User
+participant Shop as Web Shop
+participant Orders {\"type\": \"database\"}
+autonumber
+User->>+Shop: place order
+Shop->>Orders: insert<br/>row
+Orders-->>Shop
+Shop-)User: confirmation
+Note right of User: waits
+alt paid
+Shop->>Orders: commit
+else declined
+Shop->>User: retry
+end",
+        );
+
+        assert!(parsed.warnings.is_empty(), "{:?}", parsed.warnings);
+        assert_eq!(parsed.detection_method, DetectionMethod::PlantUmlFormat);
+        assert_eq!(parsed.ir.diagram_type, DiagramType::Sequence);
+        assert_eq!(parsed.ir.meta.title.as_deref(), Some("Checkout"));
+        assert_eq!(projection(&parsed.ir), projection(&mermaid.ir));
+        assert_eq!(parsed.ir.sequence_meta, mermaid.ir.sequence_meta);
+    }
+
+    #[test]
+    fn class_diagrams_keep_members_relations_and_stereotypes() {
+        let parsed = parse_plantuml(
+            "@startuml
+package shapes {
+  interface Shape {
+    +area() : double
+  }
+  abstract class Base implements Shape {
+    {static} count : int
+    -name : String
+    --
+    {abstract} +draw() : void
+  }
+  class Circle extends Base
+}
+Circle \"1\" *-- \"many\" Point : center >
+Shape <-- Canvas
+note \"free note\" as N1
+@enduml",
+        );
+        let ir = &parsed.ir;
+        assert_eq!(ir.diagram_type, DiagramType::Class);
+        assert!(parsed.warnings.contains(
+            &"Line 16: unsupported PlantUML syntax: note \"free note\" as N1".to_string()
+        ));
+
+        let class = |id: &str| {
+            ir.nodes
+                .iter()
+                .find(|node| node.id == id)
+                .and_then(|node| node.class_meta.as_deref())
+                .unwrap()
+        };
+        assert_eq!(class("Shape").stereotype, Some(ClassStereotype::Interface));
+        assert_eq!(
+            class("Shape").methods[0].return_type.as_deref(),
+            Some("double")
+        );
+        let base = class("Base");
+        assert_eq!(base.stereotype, Some(ClassStereotype::Abstract));
+        assert!(base.attributes[0].is_static);
+        assert_eq!(base.attributes[1].visibility, ClassVisibility::Private);
+        assert!(base.methods[0].is_abstract);
+
+        let edges: Vec<(&str, &str)> = ir
+            .edges
+            .iter()
+            .map(|edge| (endpoint(ir, edge.from), endpoint(ir, edge.to)))
+            .collect();
+        assert_eq!(
+            edges,
+            [
+                ("Base", "Circle"),
+                ("Circle", "Point"),
+                ("Canvas", "Shape"),
+                ("Shape", "Base"),
+            ]
+        );
+        assert_eq!(ir.edges[1].source_cardinality(), Some("1"));
+        assert_eq!(ir.edges[1].target_cardinality(), Some("many"));
+        assert_eq!(ir.graph.subgraphs[0].key, "shapes");
+        assert_eq!(ir.graph.subgraphs[0].members.len(), 3);
+    }
+}
@@ -354,8 +354,7 @@ fn compute_quality_metrics(
         variance.sqrt()
     };
 
-    // Count edge crossings (simplified: check segment intersections)
-    let edge_crossings = count_edge_crossings(layout);
+    let edge_crossings = fm_layout::quality::score_layout(layout).edge_crossings;
 
     // Count back-edges (edges going upward in TB layout)
     let back_edge_count = count_back_edges(ir, layout);
@@ -373,112 +372,6 @@ fn compute_quality_metrics(
     })
 }
 
-fn count_edge_crossings(layout: &DiagramLayout) -> usize {
-    let mut crossings = 0;
-    let edges: Vec<_> = layout.edges.iter().collect();
-
-    for i in 0..edges.len() {
-        for j in (i + 1)..edges.len() {
-            let e1 = &edges[i];
-            let e2 = &edges[j];
-
-            // Skip if edges share an endpoint (they'll trivially intersect there)
-            if edges_share_endpoint(e1, e2) {
-                continue;
-            }
-
-            for seg1_idx in 1..e1.points.len() {
-                let p1 = (
-                    f64::from(e1.points[seg1_idx - 1].x),
-                    f64::from(e1.points[seg1_idx - 1].y),
-                );
-                let p2 = (
-                    f64::from(e1.points[seg1_idx].x),
-                    f64::from(e1.points[seg1_idx].y),
-                );
-
-                for seg2_idx in 1..e2.points.len() {
-                    let p3 = (
-                        f64::from(e2.points[seg2_idx - 1].x),
-                        f64::from(e2.points[seg2_idx - 1].y),
-                    );
-                    let p4 = (
-                        f64::from(e2.points[seg2_idx].x),
-                        f64::from(e2.points[seg2_idx].y),
-                    );
-
-                    if segments_intersect(p1, p2, p3, p4) {
-                        crossings += 1;
-                    }
-                }
-            }
-        }
-    }
-    crossings
-}
-
-fn edges_share_endpoint(e1: &fm_layout::LayoutEdgePath, e2: &fm_layout::LayoutEdgePath) -> bool {
-    const EPS: f32 = 1.0;
-    let e1_start = e1.points.first();
-    let e1_end = e1.points.last();
-    let e2_start = e2.points.first();
-    let e2_end = e2.points.last();
-
-    match (e1_start, e1_end, e2_start, e2_end) {
-        (Some(s1), Some(e1), Some(s2), Some(e2)) => {
-            points_close(s1, s2, EPS)
-                || points_close(s1, e2, EPS)
-                || points_close(e1, s2, EPS)
-                || points_close(e1, e2, EPS)
-        }
-        _ => false,
-    }
-}
-
-fn points_close(a: &fm_layout::LayoutPoint, b: &fm_layout::LayoutPoint, eps: f32) -> bool {
-    (a.x - b.x).abs() < eps && (a.y - b.y).abs() < eps
-}
-
-fn segments_intersect(p1: (f64, f64), p2: (f64, f64), p3: (f64, f64), p4: (f64, f64)) -> bool {
-    const EPS: f64 = 1e-9;
-
-    let d1 = direction(p3, p4, p1);
-    let d2 = direction(p3, p4, p2);
-    let d3 = direction(p1, p2, p3);
-    let d4 = direction(p1, p2, p4);
-
-    // Standard crossing test
-    if ((d1 > EPS && d2 < -EPS) || (d1 < -EPS && d2 > EPS))
-        && ((d3 > EPS && d4 < -EPS) || (d3 < -EPS && d4 > EPS))
-    {
-        return true;
-    }
-
-    // Collinear overlap cases (using epsilon comparison)
-    if d1.abs() < EPS && on_segment(p3, p4, p1) {
-        return true;
-    }
-    if d2.abs() < EPS && on_segment(p3, p4, p2) {
-        return true;
-    }
-    if d3.abs() < EPS && on_segment(p1, p2, p3) {
-        return true;
-    }
-    if d4.abs() < EPS && on_segment(p1, p2, p4) {
-        return true;
-    }
-
-    false
-}
-
-fn direction(p1: (f64, f64), p2: (f64, f64), p3: (f64, f64)) -> f64 {
-    (p3.0 - p1.0) * (p2.1 - p1.1) - (p2.0 - p1.0) * (p3.1 - p1.1)
-}
-
-fn on_segment(p1: (f64, f64), p2: (f64, f64), p: (f64, f64)) -> bool {
-    p.0 >= p1.0.min(p2.0) && p.0 <= p1.0.max(p2.0) && p.1 >= p1.1.min(p2.1) && p.1 <= p1.1.max(p2.1)
-}
-
 fn count_back_edges(_ir: &MermaidDiagramIr, layout: &DiagramLayout) -> usize {
     // For TB layout, count edges where target Y < source Y (going upward)
     let mut count = 0;
@@ -1,13 +1,17 @@
 //! Accessibility features for SVG diagrams.
 //!
-//! Provides ARIA attributes, text alternatives, and accessibility CSS utilities.
+//! Provides ARIA attributes, text alternatives, keyboard focus order, and accessibility CSS
+//! utilities.
 
 use std::borrow::Cow;
+use std::cmp::Reverse;
 use std::fmt::Write as _;
 
-use fm_core::{ArrowType, IrNode, MermaidDiagramIr};
+use fm_core::{ArrowType, GraphDirection, IrNode, MermaidDiagramIr, mermaid_node_element_id};
 use fm_layout::DiagramLayout;
 
+use crate::BuildUpPlan;
+
 /// Generate an accessible description for a diagram.
 #[must_use]
 pub fn describe_diagram(ir: &MermaidDiagramIr) -> String {
@@ -290,6 +294,217 @@ pub(crate) fn describe_edge_labels(
     }
 }
 
+/// Element ids of the focusable nodes and edges in logical reading order.
+///
+/// Nodes are ordered topologically (longest-path depth from the sources, cycles broken as in
+/// [`BuildUpPlan`]), then by layout rank, then by in-rank position along the reading axis. Each
+/// edge is visited right after its source node, ordered by where its target falls; edges without
+/// a resolvable source come last.
+#[must_use]
+pub fn focus_order(ir: &MermaidDiagramIr, layout: &DiagramLayout) -> Vec<String> {
+    let steps = BuildUpPlan::from_ir(ir).node_steps;
+    let mut boxes = vec![None; ir.nodes.len()];
+    for node_box in &layout.nodes {
+        if let Some(slot) = boxes.get_mut(node_box.node_index) {
+            *slot = Some(node_box);
+        }
+    }
+    let horizontal_ranks = matches!(ir.direction, GraphDirection::LR | GraphDirection::RL);
+    let position = |node_index: usize| {
+        boxes[node_index].map_or((usize::MAX, f32::MAX, f32::MAX), |node_box| {
+            let center = node_box.bounds.center();
+            if horizontal_ranks {
+                (node_box.rank, center.y, center.x)
+            } else {
+                (node_box.rank, center.x, center.y)
+            }
+        })
+    };
+
+    let mut node_order: Vec<usize> = (0..ir.nodes.len()).collect();
+    node_order.sort_by(|&left, &right| {
+        let (left_rank, left_across, left_along) = position(left);
+        let (right_rank, right_across, right_along) = position(right);
+        steps[left]
+            .cmp(&steps[right])
+            .then(left_rank.cmp(&right_rank))
+            .then(left_across.total_cmp(&right_across))
+            .then(left_along.total_cmp(&right_along))
+            .then(left.cmp(&right))
+    });
+    let mut node_rank = vec![0_usize; ir.nodes.len()];
+    for (rank, &node_index) in node_order.iter().enumerate() {
+        node_rank[node_index] = rank;
+    }
+
+    let endpoint = |endpoint| {
+        ir.resolve_endpoint_node(endpoint)
+            .map(|id| id.0)
+            .filter(|&index| index < ir.nodes.len())
+    };
+    let mut outgoing: Vec<Vec<(usize, usize)>> = vec![Vec::new(); ir.nodes.len()];
+    let mut detached = Vec::new();
+    for (edge_index, edge) in ir.edges.iter().enumerate() {
+        match endpoint(edge.from) {
+            Some(source) => {
+                let target_rank = endpoint(edge.to).map_or(usize::MAX, |target| node_rank[target]);
+                outgoing[source].push((target_rank, edge_index));
+            }
+            None => detached.push(edge_index),
+        }
+    }
+
+    let edge_ids = ir.edge_element_ids();
+    let mut order = Vec::with_capacity(ir.nodes.len() + ir.edges.len());
+    for node_index in node_order {
+        order.push(mermaid_node_element_id(&ir.nodes[node_index].id));
+        let edges = &mut outgoing[node_index];
+        edges.sort_unstable();
+        order.extend(
+            edges
+                .iter()
+                .map(|&(_, edge_index)| edge_ids[edge_index].clone()),
+        );
+    }
+    order.extend(
+        detached
+            .into_iter()
+            .map(|edge_index| edge_ids[edge_index].clone()),
+    );
+    order
+}
+
+/// Post-pass: make every element of `order` present in `svg` focusable (`tabindex="0"`) and
+/// point its `aria-flowto` at the next present element, then move runs of adjacent sibling
+/// elements into `order` so sequential focus follows it too. Tab stops stay in the host page's
+/// own sequence; a positive `tabindex` would pull them ahead of everything else on the page.
+/// Ids missing from the SVG are skipped.
+pub(crate) fn apply_focus_order(svg: &mut String, order: &[String]) {
+    let present: Vec<&str> = order
+        .iter()
+        .map(String::as_str)
+        .filter(|id| start_tag(svg, id).is_some())
+        .collect();
+
+    let mut edits: Vec<(usize, usize, String)> = present
+        .iter()
+        .enumerate()
+        .filter_map(|(position, id)| {
+            let (start, end) = start_tag(svg, id)?;
+            let original = &svg[start..end];
+            let self_closing = original.ends_with('/');
+            let mut tag = strip_attribute(
+                strip_attribute(original.trim_end_matches('/'), "tabindex").as_ref(),
+                "aria-flowto",
+            )
+            .into_owned();
+            tag.push_str(" tabindex=\"0\"");
+            if let Some(next) = present.get(position + 1) {
+                let _ = write!(tag, " aria-flowto=\"{next}\"");
+            }
+            if self_closing {
+                tag.push('/');
+            }
+            Some((start, end, tag))
+        })
+        .collect();
+    edits.sort_unstable_by_key(|&(start, _, _)| Reverse(start));
+    for (start, end, tag) in edits {
+        svg.replace_range(start..end, &tag);
+    }
+
+    // Whole-element byte ranges in document order, tagged with their focus position. Elements
+    // nested inside an earlier one move with it and are left out.
+    let mut elements: Vec<(usize, usize, usize)> = present
+        .iter()
+        .enumerate()
+        .filter_map(|(position, id)| {
+            let (start, _) = start_tag(svg, id)?;
+            Some((start, element_end(svg, start)?, position))
+        })
+        .collect();
+    elements.sort_unstable();
+    let mut top_level: Vec<(usize, usize, usize)> = Vec::with_capacity(elements.len());
+    for element in elements {
+        if top_level.last().is_none_or(|&(_, end, _)| element.0 >= end) {
+            top_level.push(element);
+        }
+    }
+
+    // Siblings separated by nothing but whitespace share a parent, so they can trade places.
+    let mut rewrites: Vec<(usize, usize, String)> = Vec::new();
+    let mut run_start = 0;
+    for index in 1..=top_level.len() {
+        let continues = index < top_level.len()
+            && svg[top_level[index - 1].1..top_level[index].0]
+                .trim()
+                .is_empty();
+        if continues {
+            continue;
+        }
+        let run = &top_level[run_start..index];
+        if run.windows(2).any(|pair| pair[0].2 > pair[1].2) {
+            let mut sorted = run.to_vec();
+            sorted.sort_unstable_by_key(|&(_, _, position)| position);
+            let mut text = String::with_capacity(run[run.len() - 1].1 - run[0].0);
+            for (slot, &(start, end, _)) in sorted.iter().enumerate() {
+                text.push_str(&svg[start..end]);
+                if let Some(next) = run.get(slot + 1) {
+                    text.push_str(&svg[run[slot].1..next.0]);
+                }
+            }
+            rewrites.push((run[0].0, run[run.len() - 1].1, text));
+        }
+        run_start = index;
+    }
+    for (start, end, text) in rewrites.into_iter().rev() {
+        svg.replace_range(start..end, &text);
+    }
+}
+
+/// Byte range of the start tag carrying ` id="{id}"`, without its closing `>`.
+fn start_tag(svg: &str, id: &str) -> Option<(usize, usize)> {
+    let needle = format!(" id=\"{id}\"");
+    let at = memchr::memmem::find(svg.as_bytes(), needle.as_bytes())?;
+    let start = memchr::memrchr(b'<', &svg.as_bytes()[..at])?;
+    let end = at + memchr::memchr(b'>', &svg.as_bytes()[at..])?;
+    Some((start, end))
+}
+
+/// End (exclusive) of the element whose start tag begins at `start`, found by matching tags.
+fn element_end(svg: &str, start: usize) -> Option<usize> {
+    let bytes = svg.as_bytes();
+    let mut depth = 0_usize;
+    let mut cursor = start;
+    loop {
+        let open = cursor + memchr::memchr(b'<', &bytes[cursor..])?;
+        let close = open + memchr::memchr(b'>', &bytes[open..])?;
+        cursor = close + 1;
+        match bytes.get(open + 1) {
+            Some(b'/') => depth = depth.checked_sub(1)?,
+            Some(b'!' | b'?') => {}
+            _ if bytes[close - 1] == b'/' => {}
+            _ => depth += 1,
+        }
+        if depth == 0 {
+            return Some(cursor);
+        }
+    }
+}
+
+/// `tag` without its ` name="…"` attribute.
+fn strip_attribute<'a>(tag: &'a str, name: &str) -> Cow<'a, str> {
+    let needle = format!(" {name}=\"");
+    let Some(start) = tag.find(&needle) else {
+        return Cow::Borrowed(tag);
+    };
+    let value_start = start + needle.len();
+    let end = tag[value_start..]
+        .find('"')
+        .map_or(tag.len(), |offset| value_start + offset + 1);
+    Cow::Owned(format!("{}{}", &tag[..start], &tag[end..]))
+}
+
 /// Generate accessibility CSS with media query support.
 #[must_use]
 pub fn accessibility_css() -> &'static str {
@@ -352,6 +567,9 @@ pub struct A11yConfig {
     pub keyboard_nav: bool,
     /// Whether to include accessibility CSS (high contrast, reduced motion).
     pub accessibility_css: bool,
+    /// Whether to emit a logical focus order ([`focus_order`]) through `aria-flowto` and sibling
+    /// order instead of leaving focus in render order.
+    pub focus_order: bool,
 }
 
 impl A11yConfig {
@@ -363,6 +581,7 @@ impl A11yConfig {
             text_alternatives: true,
             keyboard_nav: true,
             accessibility_css: true,
+            focus_order: false,
         }
     }
 
@@ -374,6 +593,7 @@ impl A11yConfig {
             text_alternatives: false,
             keyboard_nav: false,
             accessibility_css: false,
+            focus_order: false,
         }
     }
 
@@ -385,6 +605,7 @@ impl A11yConfig {
             text_alternatives: false,
             keyboard_nav: false,
             accessibility_css: false,
+            focus_order: false,
         }
     }
 }
@@ -515,6 +736,66 @@ mod tests {
         Ok(())
     }
 
+    fn push_edge(ir: &mut MermaidDiagramIr, from: usize, to: usize) {
+        use fm_core::{IrEdge, IrEndpoint, IrNodeId};
+        ir.edges.push(IrEdge {
+            from: IrEndpoint::Node(IrNodeId(from)),
+            to: IrEndpoint::Node(IrNodeId(to)),
+            arrow: ArrowType::Arrow,
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn focus_order_is_topological_then_rank_order() {
+        let mut ir = create_test_ir();
+        // Insertion order C, A, B; flow A -> B -> C and A -> C.
+        ir.nodes.insert(
+            0,
+            IrNode {
+                id: "C".to_string(),
+                ..Default::default()
+            },
+        );
+        push_edge(&mut ir, 2, 0);
+        push_edge(&mut ir, 1, 2);
+        push_edge(&mut ir, 1, 0);
+
+        let layout = fm_layout::layout_diagram(&ir);
+        assert_eq!(
+            focus_order(&ir, &layout),
+            vec![
+                "fm-node-a-c40bf6cc",
+                "fm-edge-a-b-0",
+                "fm-edge-a-c-0",
+                "fm-node-b-c70bfb85",
+                "fm-edge-b-c-0",
+                "fm-node-c-c60bf9f2",
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_focus_order_sets_flowto_and_reorders_siblings() {
+        let mut svg = String::from(
+            r#"<svg><g id="b" tabindex="0"><title>B</title></g><g id="a" role="x" tabindex="0"></g><path id="c"/></svg>"#,
+        );
+        let order = ["a", "missing", "b", "c"].map(String::from);
+        apply_focus_order(&mut svg, &order);
+        assert_eq!(
+            svg,
+            r#"<svg><g id="a" role="x" tabindex="0" aria-flowto="b"></g><g id="b" tabindex="0" aria-flowto="c"><title>B</title></g><path id="c" tabindex="0"/></svg>"#
+        );
+
+        // Elements under different parents keep their place and rely on `aria-flowto`.
+        let mut svg = String::from(r#"<svg><g><path id="e"/></g> <g><g id="n"></g></g></svg>"#);
+        apply_focus_order(&mut svg, &["n", "e"].map(String::from));
+        assert_eq!(
+            svg,
+            r#"<svg><g><path id="e" tabindex="0"/></g> <g><g id="n" tabindex="0" aria-flowto="e"></g></g></svg>"#
+        );
+    }
+
     #[test]
     fn accessibility_css_includes_media_queries() {
         let css = accessibility_css();
@@ -536,6 +817,7 @@ mod tests {
         assert!(config.text_alternatives);
         assert!(config.keyboard_nav);
         assert!(config.accessibility_css);
+        assert!(!config.focus_order);
     }
 
     #[test]
@@ -0,0 +1,363 @@
+//! Stage-by-stage "build-up" export for slide decks and walkthroughs.
+//!
+//! Nodes are assigned to steps by longest-path depth over the IR edges (sources first), edges
+//! appear together with their later endpoint, and clusters appear with their earliest member.
+//! The plan drives two export shapes: a sequence of standalone SVG frames where frame `k` shows
+//! every element whose step is `<= k`, and a single SVG that reveals the steps in order with CSS
+//! `animation-delay`s.
+
+use std::fmt::Write as _;
+
+use fm_core::{
+    MermaidDiagramIr, mermaid_cluster_element_id, mermaid_node_element_id,
+    mermaid_node_element_id_with_variant,
+};
+use fm_layout::DiagramLayout;
+
+use crate::{SvgRenderConfig, render_svg_with_layout};
+
+/// Reveal step assignment for every node, edge, and cluster of a diagram.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildUpPlan {
+    /// Step at which each IR node appears, indexed by node index.
+    pub node_steps: Vec<usize>,
+    /// Step at which each IR edge appears, indexed by edge index.
+    pub edge_steps: Vec<usize>,
+    /// Step at which each IR cluster appears, indexed by cluster index.
+    pub cluster_steps: Vec<usize>,
+    /// Number of distinct steps (0 for an empty diagram).
+    pub step_count: usize,
+}
+
+impl BuildUpPlan {
+    /// Compute the topological reveal order for `ir`.
+    ///
+    /// Steps are longest-path depths from the sources. Cycles are broken deterministically by
+    /// promoting the lowest-index node still waiting on unresolved predecessors, so the plan is
+    /// stable for identical input.
+    #[must_use]
+    pub fn from_ir(ir: &MermaidDiagramIr) -> Self {
+        let node_count = ir.nodes.len();
+        let mut successors: Vec<Vec<usize>> = vec![Vec::new(); node_count];
+        let mut in_degree = vec![0_usize; node_count];
+        let endpoints: Vec<(Option<usize>, Option<usize>)> = ir
+            .edges
+            .iter()
+            .map(|edge| {
+                let from = ir
+                    .resolve_endpoint_node(edge.from)
+                    .map(|id| id.0)
+                    .filter(|&index| index < node_count);
+                let to = ir
+                    .resolve_endpoint_node(edge.to)
+                    .map(|id| id.0)
+                    .filter(|&index| index < node_count);
+                (from, to)
+            })
+            .collect();
+        for &(from, to) in &endpoints {
+            if let (Some(from), Some(to)) = (from, to)
+                && from != to
+            {
+                successors[from].push(to);
+                in_degree[to] += 1;
+            }
+        }
+
+        let mut node_steps = vec![0_usize; node_count];
+        let mut placed = vec![false; node_count];
+        let mut ready: std::collections::BTreeSet<usize> = (0..node_count)
+            .filter(|&index| in_degree[index] == 0)
+            .collect();
+        let mut placed_count = 0;
+        while placed_count < node_count {
+            let next = match ready.pop_first() {
+                Some(index) => index,
+                // Only cycles remain: break them at the lowest unplaced index.
+                None => (0..node_count)
+                    .find(|&index| !placed[index])
+                    .unwrap_or_default(),
+            };
+            if placed[next] {
+                continue;
+            }
+            placed[next] = true;
+            placed_count += 1;
+            for &succ in &successors[next] {
+                // Edges back into an already placed node are the broken cycle edges.
+                if placed[succ] {
+                    continue;
+                }
+                node_steps[succ] = node_steps[succ].max(node_steps[next] + 1);
+                in_degree[succ] = in_degree[succ].saturating_sub(1);
+                if in_degree[succ] == 0 {
+                    ready.insert(succ);
+                }
+            }
+        }
+
+        let edge_steps = endpoints
+            .iter()
+            .map(|&(from, to)| {
+                let from_step = from.map_or(0, |index| node_steps[index]);
+                let to_step = to.map_or(0, |index| node_steps[index]);
+                from_step.max(to_step)
+            })
+            .collect();
+
+        let cluster_steps = ir
+            .clusters
+            .iter()
+            .map(|cluster| {
+                cluster
+                    .members
+                    .iter()
+                    .filter_map(|member| node_steps.get(member.0).copied())
+                    .min()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let step_count = node_steps.iter().max().map_or(0, |max| max + 1);
+        Self {
+            node_steps,
+            edge_steps,
+            cluster_steps,
+            step_count,
+        }
+    }
+
+    /// Element ids (as emitted by the SVG renderer) of everything revealed at exactly `step`.
+    ///
+    /// Each node and edge is followed by the `-label` id of the wrapper that layered output
+    /// ([`SvgRenderConfig::layers`]) lifts its label into.
+    #[must_use]
+    pub fn element_ids_at_step(&self, ir: &MermaidDiagramIr, step: usize) -> Vec<String> {
+        let mut ids = Vec::new();
+        for (index, &node_step) in self.node_steps.iter().enumerate() {
+            if node_step == step {
+                let node_id = &ir.nodes[index].id;
+                ids.push(mermaid_node_element_id(node_id));
+                ids.push(mermaid_node_element_id_with_variant(node_id, Some("label")));
+            }
+        }
+        let edge_ids = ir.edge_element_ids();
+        for (&edge_step, edge_id) in self.edge_steps.iter().zip(edge_ids) {
+            if edge_step == step {
+                let label_id = format!("{edge_id}-label");
+                ids.push(edge_id);
+                ids.push(label_id);
+            }
+        }
+        for (index, &cluster_step) in self.cluster_steps.iter().enumerate() {
+            if cluster_step == step {
+                ids.push(mermaid_cluster_element_id(index));
+            }
+        }
+        ids
+    }
+}
+
+/// Render one standalone SVG per build-up step.
+///
+/// Every frame shares the full diagram geometry (same viewBox and positions); frame `k` hides the
+/// elements whose step is greater than `k`, so flipping through the frames adds one layer at a time.
+/// An empty diagram yields a single frame.
+#[must_use]
+pub fn render_svg_build_up_frames(
+    ir: &MermaidDiagramIr,
+    layout: &DiagramLayout,
+    config: &SvgRenderConfig,
+) -> Vec<String> {
+    let plan = BuildUpPlan::from_ir(ir);
+    let svg = render_svg_with_layout(ir, layout, config);
+    if plan.step_count <= 1 {
+        return vec![svg];
+    }
+
+    let step_ids: Vec<Vec<String>> = (0..plan.step_count)
+        .map(|step| plan.element_ids_at_step(ir, step))
+        .collect();
+    (0..plan.step_count)
+        .map(|frame| {
+            let hidden = step_ids[frame + 1..].iter().flatten();
+            let mut css = String::new();
+            write_selector_list(&mut css, hidden);
+            if css.is_empty() {
+                return svg.clone();
+            }
+            css.push_str("{display:none}");
+            inject_style(&svg, &css)
+        })
+        .collect()
+}
+
+/// Render a single SVG that reveals the build-up steps in order.
+///
+/// Each step starts once the previous step's entrance (`config.animation_duration_ms`) finished.
+/// The reveal is suppressed under `prefers-reduced-motion`.
+#[must_use]
+pub fn render_svg_build_up_animated(
+    ir: &MermaidDiagramIr,
+    layout: &DiagramLayout,
+    config: &SvgRenderConfig,
+) -> String {
+    let plan = BuildUpPlan::from_ir(ir);
+    let svg = render_svg_with_layout(ir, layout, config);
+    let step_seconds = config.animation_duration_ms.max(1) as f32 / 1000.0;
+
+    let mut css = String::from("@keyframes fm-build-up-reveal{from{opacity:0}to{opacity:1}}\n");
+    let mut reduced_motion = String::new();
+    for step in 0..plan.step_count {
+        let ids = plan.element_ids_at_step(ir, step);
+        if ids.is_empty() {
+            continue;
+        }
+        let start = css.len();
+        write_selector_list(&mut css, ids.iter());
+        let selectors = css[start..].to_string();
+        let _ = writeln!(
+            css,
+            "{{animation:fm-build-up-reveal {step_seconds:.2}s ease-out both;animation-delay:{:.2}s}}",
+            step as f32 * step_seconds
+        );
+        if !reduced_motion.is_empty() {
+            reduced_motion.push(',');
+        }
+        reduced_motion.push_str(&selectors);
+    }
+    if !reduced_motion.is_empty() {
+        let _ = writeln!(
+            css,
+            "@media (prefers-reduced-motion: reduce){{{reduced_motion}{{animation:none !important}}}}"
+        );
+    }
+    inject_style(&svg, &css)
+}
+
+fn write_selector_list<'a>(out: &mut String, ids: impl Iterator<Item = &'a String>) {
+    for (position, id) in ids.enumerate() {
+        if position > 0 {
+            out.push(',');
+        }
+        out.push('#');
+        out.push_str(id);
+    }
+}
+
+/// Append a `<style>` block just before the closing root tag.
+fn inject_style(svg: &str, css: &str) -> String {
+    let Some(close) = svg.rfind("</svg>") else {
+        return svg.to_string();
+    };
+    let mut out = String::with_capacity(svg.len() + css.len() + 32);
+    out.push_str(&svg[..close]);
+    out.push_str("<style class=\"fm-build-up\">");
+    out.push_str(css);
+    out.push_str("</style>");
+    out.push_str(&svg[close..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fm_core::{DiagramType, IrEdge, IrEndpoint, IrLabel, IrLabelId, IrNode, IrNodeId};
+    use fm_layout::layout_diagram;
+
+    fn chain_ir(edges: &[(usize, usize)], node_count: usize) -> MermaidDiagramIr {
+        let mut ir = MermaidDiagramIr::empty(DiagramType::Flowchart);
+        for index in 0..node_count {
+            ir.nodes.push(IrNode {
+                id: format!("N{index}"),
+                ..IrNode::default()
+            });
+        }
+        for &(from, to) in edges {
+            ir.edges.push(IrEdge {
+                from: IrEndpoint::Node(IrNodeId(from)),
+                to: IrEndpoint::Node(IrNodeId(to)),
+                ..IrEdge::default()
+            });
+        }
+        ir
+    }
+
+    #[test]
+    fn plan_uses_longest_path_depth() {
+        // N0 -> N1 -> N2, plus shortcut N0 -> N2.
+        let ir = chain_ir(&[(0, 1), (1, 2), (0, 2)], 3);
+        let plan = BuildUpPlan::from_ir(&ir);
+        assert_eq!(plan.node_steps, vec![0, 1, 2]);
+        assert_eq!(plan.edge_steps, vec![1, 2, 2]);
+        assert_eq!(plan.step_count, 3);
+    }
+
+    #[test]
+    fn plan_breaks_cycles_deterministically() {
+        let ir = chain_ir(&[(0, 1), (1, 2), (2, 0)], 3);
+        let plan = BuildUpPlan::from_ir(&ir);
+        assert_eq!(plan, BuildUpPlan::from_ir(&ir));
+        assert_eq!(plan.node_steps[0], 0);
+        assert_eq!(plan.step_count, 3);
+    }
+
+    #[test]
+    fn frames_hide_later_steps() {
+        let ir = chain_ir(&[(0, 1)], 2);
+        let layout = layout_diagram(&ir);
+        let frames = render_svg_build_up_frames(&ir, &layout, &SvgRenderConfig::default());
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0].contains("#fm-node-n1-83e6f3a0"));
+        assert!(frames[0].contains("#fm-edge-n0-n1-0"));
+        assert!(frames[0].contains("display:none"));
+        assert!(!frames[1].contains("fm-build-up"));
+    }
+
+    #[test]
+    fn layered_frames_hide_lifted_labels() {
+        let mut ir = chain_ir(&[(0, 1)], 2);
+        for text in ["Start", "Next", "go"] {
+            ir.labels.push(IrLabel {
+                text: text.to_string(),
+                ..IrLabel::default()
+            });
+        }
+        ir.nodes[0].label = Some(IrLabelId(0));
+        ir.nodes[1].label = Some(IrLabelId(1));
+        ir.edges[0].label = Some(IrLabelId(2));
+        let layout = layout_diagram(&ir);
+        let config = SvgRenderConfig {
+            layers: true,
+            ..SvgRenderConfig::default()
+        };
+        let frames = render_svg_build_up_frames(&ir, &layout, &config);
+        for label_id in ["fm-node-n1-83e6f3a0-label", "fm-edge-n0-n1-0-label"] {
+            assert!(frames[0].contains(&format!("id=\"{label_id}\"")));
+            assert!(frames[0].contains(&format!("#{label_id}")));
+        }
+        assert!(frames[0].contains("id=\"fm-node-n0-84e6f533-label\""));
+        assert!(!frames[0].contains("#fm-node-n0-84e6f533-label"));
+    }
+
+    #[test]
+    fn animated_export_staggers_steps() {
+        let ir = chain_ir(&[(0, 1)], 2);
+        let layout = layout_diagram(&ir);
+        let config = SvgRenderConfig {
+            animation_duration_ms: 500,
+            ..SvgRenderConfig::default()
+        };
+        let svg = render_svg_build_up_animated(&ir, &layout, &config);
+        assert!(svg.contains("@keyframes fm-build-up-reveal"));
+        assert!(svg.contains(
+            "#fm-node-n0-84e6f533,#fm-node-n0-84e6f533-label{animation:fm-build-up-reveal 0.50s"
+        ));
+        assert!(svg.contains(
+            "#fm-node-n1-83e6f3a0,#fm-node-n1-83e6f3a0-label,#fm-edge-n0-n1-0,\
+             #fm-edge-n0-n1-0-label{animation"
+        ));
+        assert!(svg.contains("animation-delay:0.50s"));
+        assert!(svg.contains("prefers-reduced-motion"));
+    }
+}
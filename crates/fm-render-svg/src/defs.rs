@@ -31,6 +31,44 @@ impl MarkerOrient {
     }
 }
 
+/// Cardinality of one end of an ER relationship, as drawn by [`ArrowheadMarker::crows_foot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErCardinality {
+    /// `||`
+    ExactlyOne,
+    /// `|o` / `o|`
+    ZeroOrOne,
+    /// `}|` / `|{`
+    OneOrMore,
+    /// `}o` / `o{`
+    ZeroOrMore,
+}
+
+impl ErCardinality {
+    /// Every cardinality, in marker emission order.
+    pub const ALL: [Self; 4] = [
+        Self::ExactlyOne,
+        Self::ZeroOrOne,
+        Self::OneOrMore,
+        Self::ZeroOrMore,
+    ];
+
+    /// Cardinality of one end of an ER operator (`||`, `o{`, `}|`, …), read from either side.
+    /// `None` for an end with no cardinality mark.
+    #[must_use]
+    pub fn from_notation(end: &str) -> Option<Self> {
+        let many = end.contains(['{', '}']);
+        let zero = end.contains('o');
+        match (many, zero) {
+            (true, true) => Some(Self::ZeroOrMore),
+            (true, false) => Some(Self::OneOrMore),
+            (false, true) => Some(Self::ZeroOrOne),
+            (false, false) if end.contains('|') => Some(Self::ExactlyOne),
+            (false, false) => None,
+        }
+    }
+}
+
 /// Arrowhead marker definition.
 #[derive(Debug, Clone)]
 pub struct ArrowheadMarker {
@@ -297,6 +335,99 @@ impl ArrowheadMarker {
         }
     }
 
+    /// Create a hollow triangle (UML generalization), outlined in `stroke` and filled with the
+    /// background color `fill` so the edge line does not show through it.
+    #[must_use]
+    pub fn hollow_triangle(id: &str, stroke: &str, fill: &str) -> Self {
+        let path = PathBuilder::new()
+            .move_to(0.5, 0.5)
+            .line_to(11.0, 5.5)
+            .line_to(0.5, 10.5)
+            .close()
+            .build();
+
+        Self {
+            id: id.to_string(),
+            marker_width: 12.0,
+            marker_height: 11.0,
+            ref_x: 11.0,
+            ref_y: 5.5,
+            orient: MarkerOrient::Auto,
+            path,
+            fill: fill.to_string(),
+            stroke: Some(stroke.to_string()),
+            stroke_width: Some(1.0),
+        }
+    }
+
+    /// Create a UML diamond (aggregation when `fill` is the background, composition when it is
+    /// the stroke color).
+    #[must_use]
+    pub fn uml_diamond(id: &str, stroke: &str, fill: &str) -> Self {
+        let path = PathBuilder::new()
+            .move_to(0.5, 5.0)
+            .line_to(7.0, 0.5)
+            .line_to(13.5, 5.0)
+            .line_to(7.0, 9.5)
+            .close()
+            .build();
+
+        Self {
+            id: id.to_string(),
+            marker_width: 14.0,
+            marker_height: 10.0,
+            ref_x: 13.5,
+            ref_y: 5.0,
+            orient: MarkerOrient::Auto,
+            path,
+            fill: fill.to_string(),
+            stroke: Some(stroke.to_string()),
+            stroke_width: Some(1.0),
+        }
+    }
+
+    /// Create a crow's foot cardinality mark for one end of an ER relationship, pointing at the
+    /// entity: bars for one, a ring for zero, a three-pronged foot for many. Rings and feet are
+    /// filled with the background color `fill`, so the edge line does not show through the ring,
+    /// while the foot's middle prong is stroked over the fill.
+    #[must_use]
+    pub fn crows_foot(id: &str, cardinality: ErCardinality, stroke: &str, fill: &str) -> Self {
+        let ring = |path: PathBuilder, cx: f32| {
+            path.move_to(cx + 2.5, 5.0)
+                .arc_to(2.5, 2.5, 0.0, false, true, cx - 2.5, 5.0)
+                .arc_to(2.5, 2.5, 0.0, false, true, cx + 2.5, 5.0)
+                .close()
+        };
+        let foot = |path: PathBuilder| {
+            path.move_to(15.5, 0.5)
+                .line_to(9.0, 5.0)
+                .line_to(15.5, 9.5)
+                .move_to(9.0, 5.0)
+                .line_to(15.5, 5.0)
+        };
+        let bar = |path: PathBuilder, x: f32| path.move_to(x, 0.5).line_to(x, 9.5);
+        let path = match cardinality {
+            ErCardinality::ExactlyOne => bar(bar(PathBuilder::new(), 8.0), 12.0),
+            ErCardinality::ZeroOrOne => ring(bar(PathBuilder::new(), 12.0), 6.5),
+            ErCardinality::OneOrMore => foot(bar(PathBuilder::new(), 6.0)),
+            ErCardinality::ZeroOrMore => foot(ring(PathBuilder::new(), 4.0)),
+        }
+        .build();
+
+        Self {
+            id: id.to_string(),
+            marker_width: 16.0,
+            marker_height: 10.0,
+            ref_x: 15.5,
+            ref_y: 5.0,
+            orient: MarkerOrient::Auto,
+            path,
+            fill: fill.to_string(),
+            stroke: Some(stroke.to_string()),
+            stroke_width: Some(1.0),
+        }
+    }
+
     /// Set the orientation of the marker.
     #[must_use]
     pub fn with_orient(mut self, orient: MarkerOrient) -> Self {
@@ -764,6 +895,57 @@ mod tests {
         assert!(svg.contains("id=\"arrow-filled\""));
     }
 
+    #[test]
+    fn uml_markers_are_outlined_and_filled_separately() {
+        let triangle =
+            ArrowheadMarker::hollow_triangle("uml-triangle", "#666", "#fff").to_element();
+        let svg = triangle.render();
+        assert!(svg.contains("fill=\"#fff\""));
+        assert!(svg.contains("stroke=\"#666\""));
+
+        let diamond = ArrowheadMarker::uml_diamond("uml-composition", "#666", "#666")
+            .with_orient(MarkerOrient::AutoStartReverse)
+            .to_element()
+            .render();
+        assert!(diamond.contains("orient=\"auto-start-reverse\""));
+        assert!(diamond.contains("fill=\"#666\""));
+    }
+
+    #[test]
+    fn crows_feet_follow_the_er_operator_ends() {
+        assert_eq!(
+            ErCardinality::from_notation("||"),
+            Some(ErCardinality::ExactlyOne)
+        );
+        assert_eq!(
+            ErCardinality::from_notation("|o"),
+            Some(ErCardinality::ZeroOrOne)
+        );
+        assert_eq!(
+            ErCardinality::from_notation("}|"),
+            Some(ErCardinality::OneOrMore)
+        );
+        assert_eq!(
+            ErCardinality::from_notation("o{"),
+            Some(ErCardinality::ZeroOrMore)
+        );
+        assert_eq!(ErCardinality::from_notation(""), None);
+
+        let paths: Vec<String> = ErCardinality::ALL
+            .iter()
+            .map(|&cardinality| {
+                ArrowheadMarker::crows_foot("er", cardinality, "#666", "#fff")
+                    .to_element()
+                    .render()
+            })
+            .collect();
+        assert!(paths.iter().all(|svg| svg.contains("stroke=\"#666\"")));
+        // Rings are arcs, feet fan out from one point; "exactly one" is just two bars.
+        assert!(!paths[0].contains('A'));
+        assert!(paths[1].contains('A') && paths[3].contains('A'));
+        assert_eq!(paths[2].matches('M').count(), 3);
+    }
+
     #[test]
     fn creates_open_arrowhead() {
         let marker = ArrowheadMarker::open("arrow-open", "#666");
@@ -0,0 +1,364 @@
+//! Animated SVG diffs between two versions of a diagram.
+//!
+//! Both versions are laid out and drawn on a shared canvas (the union of their bounds), so a node
+//! keeps its coordinates unless the layout really moved it. The new drawing is the base; nodes and
+//! edges that only exist in the old version are copied in from the old drawing and fade out, new
+//! ones fade in, and nodes whose position changed slide from their old spot. Nodes match by their
+//! Mermaid id, edges by their element ids (which derive from the endpoint ids), and every rule
+//! targets the renderer's element ids.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+
+use fm_core::{MermaidDiagramIr, mermaid_node_element_id};
+use fm_layout::{DiagramLayout, LayoutRect};
+
+use crate::{SvgRenderConfig, render_svg_with_layout};
+
+/// Movement below this many pixels is layout noise, not a move.
+const MOVE_EPSILON: f32 = 0.5;
+
+/// Render `new` as an SVG that animates the changes from `old`.
+///
+/// Removed elements fade out, moved nodes slide to their new position, and added elements (plus
+/// edges attached to a moved node, which are drawn at their final route) fade in once the moves
+/// finished. Each phase lasts `config.animation_duration_ms`. The animation is suppressed under
+/// `prefers-reduced-motion`, which leaves the new diagram as-is.
+#[must_use]
+pub fn render_diff_svg(
+    old: &MermaidDiagramIr,
+    new: &MermaidDiagramIr,
+    config: &SvgRenderConfig,
+) -> String {
+    let layout_config = || fm_layout::LayoutConfig {
+        font_metrics: Some(config.font_metrics()),
+        ..Default::default()
+    };
+    let mut old_layout = fm_layout::layout_diagram_with_config(old, layout_config());
+    let mut new_layout = fm_layout::layout_diagram_with_config(new, layout_config());
+    let bounds = union_bounds(old_layout.bounds, new_layout.bounds);
+    old_layout.bounds = bounds;
+    new_layout.bounds = bounds;
+
+    let svg = render_svg_with_layout(new, &new_layout, config);
+    let changes = DiagramChanges::between(old, &old_layout, new, &new_layout);
+    if changes.is_empty() {
+        return svg;
+    }
+
+    let mut removed = String::new();
+    if !changes.removed.is_empty() {
+        let old_svg = render_svg_with_layout(old, &old_layout, config);
+        for id in &changes.removed {
+            if let Some(fragment) = element_fragment(&old_svg, id) {
+                // Keep the old id out of the way of a new element that reuses it.
+                removed.push_str(&fragment.replacen(" id=\"", " id=\"fm-diff-old-", 1));
+            }
+        }
+    }
+
+    let seconds = config.animation_duration_ms.max(1) as f32 / 1000.0;
+    let mut css = String::from(
+        "@keyframes fm-diff-fade-in{from{opacity:0}to{opacity:1}}\n\
+         @keyframes fm-diff-fade-out{from{opacity:1}to{opacity:0}}\n",
+    );
+    let _ = writeln!(
+        css,
+        ".fm-diff-removed{{animation:fm-diff-fade-out {seconds:.2}s ease-in both;pointer-events:none}}"
+    );
+    let mut animated = vec![".fm-diff-removed".to_string()];
+    for (position, (id, dx, dy)) in changes.moved.iter().enumerate() {
+        let _ = writeln!(
+            css,
+            "@keyframes fm-diff-move-{position}{{from{{transform:translate({dx:.1}px,{dy:.1}px)}}}}\n\
+             #{id}{{animation:fm-diff-move-{position} {seconds:.2}s ease-in-out both}}"
+        );
+        animated.push(format!("#{id}"));
+    }
+    if !changes.added.is_empty() {
+        let start = css.len();
+        for (position, id) in changes.added.iter().enumerate() {
+            if position > 0 {
+                css.push(',');
+            }
+            css.push('#');
+            css.push_str(id);
+        }
+        animated.push(css[start..].to_string());
+        let _ = writeln!(
+            css,
+            "{{animation:fm-diff-fade-in {seconds:.2}s ease-out both;animation-delay:{seconds:.2}s}}"
+        );
+    }
+    let _ = writeln!(
+        css,
+        "@media (prefers-reduced-motion: reduce){{.fm-diff-removed{{display:none}}{}{{animation:none !important}}}}",
+        animated.join(",")
+    );
+
+    inject_before_close(&svg, &removed, &css)
+}
+
+/// Element ids of what changed between two laid-out diagrams.
+#[derive(Debug, Default)]
+struct DiagramChanges {
+    /// Old-drawing element ids of nodes and edges missing from the new diagram.
+    removed: Vec<String>,
+    /// New-drawing element ids of nodes and edges missing from the old diagram, plus edges attached
+    /// to a moved node.
+    added: Vec<String>,
+    /// New-drawing node element ids with the offset from their new to their old position.
+    moved: Vec<(String, f32, f32)>,
+}
+
+impl DiagramChanges {
+    fn between(
+        old: &MermaidDiagramIr,
+        old_layout: &DiagramLayout,
+        new: &MermaidDiagramIr,
+        new_layout: &DiagramLayout,
+    ) -> Self {
+        let mut changes = Self::default();
+
+        let old_centers = node_centers(old, old_layout);
+        let new_centers = node_centers(new, new_layout);
+        let mut moved_nodes = vec![false; new.nodes.len()];
+        for (index, node) in new.nodes.iter().enumerate() {
+            match old_centers.get(node.id.as_str()) {
+                None => changes.added.push(mermaid_node_element_id(&node.id)),
+                Some(&old_center) => {
+                    let Some(&new_center) = new_centers.get(node.id.as_str()) else {
+                        continue;
+                    };
+                    let (dx, dy) = (old_center.0 - new_center.0, old_center.1 - new_center.1);
+                    if dx.abs() > MOVE_EPSILON || dy.abs() > MOVE_EPSILON {
+                        moved_nodes[index] = true;
+                        changes
+                            .moved
+                            .push((mermaid_node_element_id(&node.id), dx, dy));
+                    }
+                }
+            }
+        }
+        for node in &old.nodes {
+            if !new_centers.contains_key(node.id.as_str()) {
+                changes.removed.push(mermaid_node_element_id(&node.id));
+            }
+        }
+
+        // Equal element ids mean the same edge. An edge the source named keeps its id when it is
+        // pointed at other nodes, so its endpoints are compared too.
+        let old_edges = old.edge_element_ids();
+        let new_edges = new.edge_element_ids();
+        let old_endpoints: BTreeMap<&str, [Option<&str>; 2]> = old_edges
+            .iter()
+            .enumerate()
+            .map(|(index, id)| (id.as_str(), edge_node_ids(old, index)))
+            .collect();
+        let new_set: BTreeSet<&str> = new_edges.iter().map(String::as_str).collect();
+        for (index, id) in new_edges.iter().enumerate() {
+            let touches_moved = edge_nodes(new, index)
+                .into_iter()
+                .flatten()
+                .any(|node| moved_nodes.get(node).copied().unwrap_or(false));
+            if touches_moved || old_endpoints.get(id.as_str()) != Some(&edge_node_ids(new, index)) {
+                changes.added.push(id.clone());
+            }
+        }
+        changes.removed.extend(
+            old_edges
+                .iter()
+                .filter(|id| !new_set.contains(id.as_str()))
+                .cloned(),
+        );
+
+        changes
+    }
+
+    fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.added.is_empty() && self.moved.is_empty()
+    }
+}
+
+fn union_bounds(a: LayoutRect, b: LayoutRect) -> LayoutRect {
+    let x = a.x.min(b.x);
+    let y = a.y.min(b.y);
+    LayoutRect {
+        x,
+        y,
+        width: (a.x + a.width).max(b.x + b.width) - x,
+        height: (a.y + a.height).max(b.y + b.height) - y,
+    }
+}
+
+/// Node centers by Mermaid id.
+fn node_centers<'a>(
+    ir: &'a MermaidDiagramIr,
+    layout: &DiagramLayout,
+) -> BTreeMap<&'a str, (f32, f32)> {
+    layout
+        .nodes
+        .iter()
+        .filter_map(|node_box| {
+            let node = ir.nodes.get(node_box.node_index)?;
+            let center = node_box.bounds.center();
+            Some((node.id.as_str(), (center.x, center.y)))
+        })
+        .collect()
+}
+
+fn edge_nodes(ir: &MermaidDiagramIr, index: usize) -> [Option<usize>; 2] {
+    let Some(edge) = ir.edges.get(index) else {
+        return [None, None];
+    };
+    [
+        ir.resolve_endpoint_node(edge.from).map(|id| id.0),
+        ir.resolve_endpoint_node(edge.to).map(|id| id.0),
+    ]
+}
+
+/// Ids of the nodes edge `index` joins.
+fn edge_node_ids(ir: &MermaidDiagramIr, index: usize) -> [Option<&str>; 2] {
+    edge_nodes(ir, index).map(|node| {
+        node.and_then(|node| ir.nodes.get(node))
+            .map(|node| node.id.as_str())
+    })
+}
+
+/// The complete markup of the element whose `id` attribute is `id`.
+fn element_fragment<'a>(svg: &'a str, id: &str) -> Option<&'a str> {
+    let needle = format!(" id=\"{id}\"");
+    let attr = svg.find(&needle)?;
+    let start = svg[..attr].rfind('<')?;
+    let tag_end = start + svg[start..].find('>')?;
+    if svg.as_bytes()[tag_end - 1] == b'/' {
+        return Some(&svg[start..=tag_end]);
+    }
+    let name_end = svg[start + 1..]
+        .find(|c: char| c.is_ascii_whitespace() || c == '>')
+        .map(|offset| start + 1 + offset)?;
+    let name = &svg[start + 1..name_end];
+    let open = format!("<{name}");
+    let close = format!("</{name}>");
+
+    let mut depth = 1_usize;
+    let mut cursor = tag_end + 1;
+    while depth > 0 {
+        let next_close = cursor + svg[cursor..].find(&close)?;
+        // Count same-name elements opened (and not self-closed) before the next close tag.
+        let mut scan = cursor;
+        while let Some(offset) = svg[scan..next_close].find(&open) {
+            let at = scan + offset;
+            let after = svg.as_bytes().get(at + open.len()).copied();
+            let inner_end = at + svg[at..].find('>')?;
+            if matches!(after, Some(b' ' | b'>')) && svg.as_bytes()[inner_end - 1] != b'/' {
+                depth += 1;
+            }
+            scan = at + open.len();
+        }
+        depth -= 1;
+        cursor = next_close + close.len();
+    }
+    Some(&svg[start..cursor])
+}
+
+/// Insert the removed-element layer and the diff `<style>` just before the closing root tag.
+fn inject_before_close(svg: &str, removed: &str, css: &str) -> String {
+    let Some(close) = svg.rfind("</svg>") else {
+        return svg.to_string();
+    };
+    let mut out = String::with_capacity(svg.len() + removed.len() + css.len() + 64);
+    out.push_str(&svg[..close]);
+    if !removed.is_empty() {
+        out.push_str("<g class=\"fm-diff-removed\">");
+        out.push_str(removed);
+        out.push_str("</g>");
+    }
+    out.push_str("<style class=\"fm-diff\">");
+    out.push_str(css);
+    out.push_str("</style>");
+    out.push_str(&svg[close..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fm_core::{DiagramType, IrEdge, IrEndpoint, IrNode, IrNodeId};
+
+    fn graph(ids: &[&str], edges: &[(usize, usize)]) -> MermaidDiagramIr {
+        let mut ir = MermaidDiagramIr::empty(DiagramType::Flowchart);
+        for id in ids {
+            ir.nodes.push(IrNode {
+                id: (*id).to_string(),
+                ..IrNode::default()
+            });
+        }
+        for &(from, to) in edges {
+            ir.edges.push(IrEdge {
+                from: IrEndpoint::Node(IrNodeId(from)),
+                to: IrEndpoint::Node(IrNodeId(to)),
+                ..IrEdge::default()
+            });
+        }
+        ir
+    }
+
+    #[test]
+    fn identical_diagrams_render_without_diff_styles() {
+        let ir = graph(&["A", "B"], &[(0, 1)]);
+        let svg = render_diff_svg(&ir, &ir, &SvgRenderConfig::default());
+        assert!(!svg.contains("fm-diff"));
+    }
+
+    #[test]
+    fn added_removed_and_moved_elements_are_animated() {
+        let old = graph(&["A", "B", "C"], &[(0, 1), (1, 2)]);
+        let new = graph(&["A", "B", "D"], &[(0, 1), (0, 2)]);
+        let config = SvgRenderConfig {
+            animation_duration_ms: 400,
+            ..SvgRenderConfig::default()
+        };
+        let svg = render_diff_svg(&old, &new, &config);
+
+        let removed_at = svg
+            .find("<g class=\"fm-diff-removed\">")
+            .expect("removed layer");
+        assert!(svg[removed_at..].contains("id=\"fm-diff-old-fm-node-c-c60bf9f2\""));
+        assert!(svg[removed_at..].contains("id=\"fm-diff-old-fm-edge-b-c-0\""));
+        assert!(svg.contains("#fm-node-d-c10bf213,"));
+        assert!(svg.contains("#fm-edge-a-d-0{animation:fm-diff-fade-in 0.40s"));
+        assert!(svg.contains("animation-delay:0.40s"));
+        assert!(svg.contains("prefers-reduced-motion"));
+        assert!(svg.ends_with("</style></svg>"));
+    }
+
+    #[test]
+    fn changes_match_nodes_by_id_across_reindexing() {
+        let old = graph(&["A", "B"], &[(0, 1)]);
+        let new = graph(&["Z", "A", "B"], &[(0, 1), (1, 2)]);
+        let old_layout = fm_layout::layout_diagram(&old);
+        let new_layout = fm_layout::layout_diagram(&new);
+        let changes = DiagramChanges::between(&old, &old_layout, &new, &new_layout);
+        assert_eq!(
+            changes.added.first().map(String::as_str),
+            Some("fm-node-z-df0c214d")
+        );
+        assert!(changes.removed.is_empty());
+        for (id, dx, dy) in &changes.moved {
+            assert!(id.starts_with("fm-node-"));
+            assert!(dx.abs() > MOVE_EPSILON || dy.abs() > MOVE_EPSILON);
+        }
+    }
+
+    #[test]
+    fn element_fragment_balances_nested_groups() {
+        let svg = "<svg><g id=\"a\"><g><rect/></g><path/></g><g id=\"b\"/></svg>";
+        assert_eq!(
+            element_fragment(svg, "a"),
+            Some("<g id=\"a\"><g><rect/></g><path/></g>")
+        );
+        assert_eq!(element_fragment(svg, "b"), Some("<g id=\"b\"/>"));
+        assert_eq!(element_fragment(svg, "c"), None);
+    }
+}
@@ -22,6 +22,7 @@ pub struct SvgDocument {
     defs: Option<DefsBuilder>,
     children: Vec<Element>,
     style: Option<String>,
+    script: Option<String>,
 }
 
 impl SvgDocument {
@@ -38,6 +39,7 @@ impl SvgDocument {
             defs: None,
             children: Vec::new(),
             style: None,
+            script: None,
         }
     }
 
@@ -137,6 +139,14 @@ impl SvgDocument {
         self
     }
 
+    /// Add an inline script, emitted as a CDATA section after every child so that the elements
+    /// it looks up already exist when it runs.
+    #[must_use]
+    pub fn script(mut self, js: impl Into<String>) -> Self {
+        self.script = Some(js.into());
+        self
+    }
+
     /// Add a child element.
     #[must_use]
     pub fn child(mut self, elem: Element) -> Self {
@@ -154,7 +164,7 @@ impl SvgDocument {
     /// Write the SVG document to a string.
     pub fn write_to_string(&self, output: &mut String) {
         self.write_prelude(output);
-        output.push_str("</svg>");
+        self.write_epilogue(output);
     }
 
     /// Serialize everything up to (but not including) the closing `</svg>`: the open tag with all
@@ -247,10 +257,21 @@ impl SvgDocument {
         let mut output = String::with_capacity(capacity.max(4096));
         self.write_prelude(&mut output);
         body(&mut output);
-        output.push_str("</svg>");
+        self.write_epilogue(&mut output);
         output
     }
 
+    /// Serialize the trailing `<script>` (if any) and the closing `</svg>`. A `]]>` inside the
+    /// script is split across two CDATA sections so it cannot end the section early.
+    fn write_epilogue(&self, output: &mut String) {
+        if let Some(ref js) = self.script {
+            output.push_str("<script><![CDATA[");
+            output.push_str(&js.replace("]]>", "]]]]><![CDATA[>"));
+            output.push_str("]]></script>");
+        }
+        output.push_str("</svg>");
+    }
+
     /// Write the SVG document to an io::Write implementor.
     pub fn write_to<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
         let svg = self.to_string();
@@ -349,4 +370,17 @@ mod tests {
         );
         assert!(!svg.contains("</style><script>"));
     }
+
+    #[test]
+    fn script_follows_the_children_inside_cdata() {
+        let doc = SvgDocument::new()
+            .child(Element::rect())
+            .script("if (a && b < c) { x = ']]>'; }");
+        let svg = doc.to_string();
+        assert!(svg.ends_with(
+            "<rect/><script><![CDATA[if (a && b < c) { x = ']]]]><![CDATA[>'; }]]></script></svg>"
+        ));
+        let streamed = doc.to_string_with_body(0, |out| out.push_str("<g/>"));
+        assert!(streamed.contains("<g/><script><![CDATA["));
+    }
 }
@@ -613,6 +613,39 @@ impl Element {
         self.kind
     }
 
+    /// Whether this is a raw fragment holding exactly one `<text>` element.
+    #[must_use]
+    pub(crate) fn is_raw_text(&self) -> bool {
+        self.kind == ElementKind::Raw
+            && matches!(
+                &self.text_content,
+                Some(ElementText::Text(svg))
+                    if svg.starts_with("<text")
+                        && svg.ends_with("</text>")
+                        && svg.matches("<text").count() == 1
+            )
+    }
+
+    /// Remove every descendant `matches` accepts, without looking inside the ones it takes, and
+    /// return them in document order.
+    pub(crate) fn take_descendants(&mut self, matches: &impl Fn(&Self) -> bool) -> Vec<Self> {
+        let mut taken = Vec::new();
+        self.take_descendants_into(matches, &mut taken);
+        taken
+    }
+
+    fn take_descendants_into(&mut self, matches: &impl Fn(&Self) -> bool, taken: &mut Vec<Self>) {
+        for child in std::mem::take(&mut self.children) {
+            if matches(&child) {
+                taken.push(child);
+            } else {
+                let mut child = child;
+                child.take_descendants_into(matches, taken);
+                self.children.push(child);
+            }
+        }
+    }
+
     /// Render the element to a string.
     #[must_use]
     pub fn render(&self) -> String {
@@ -749,6 +782,23 @@ mod tests {
         assert!(svg.ends_with("</text>"));
     }
 
+    #[test]
+    fn take_descendants_lifts_matches_out_in_document_order() {
+        let mut group = Element::group()
+            .child(Element::rect())
+            .child(Element::text().content("a"))
+            .child(
+                Element::group()
+                    .child(Element::raw_svg("<text>b</text>".to_string()))
+                    .child(Element::raw_svg("<line/><text>c</text>".to_string())),
+            );
+        let taken =
+            group.take_descendants(&|elem| elem.kind() == ElementKind::Text || elem.is_raw_text());
+        let taken: Vec<String> = taken.iter().map(Element::render).collect();
+        assert_eq!(taken, ["<text>a</text>", "<text>b</text>"]);
+        assert_eq!(group.render(), "<g><rect/><g><line/><text>c</text></g></g>");
+    }
+
     #[test]
     fn raw_svg_parts_render_in_order_without_escaping() {
         let elem = Element::raw_svg_parts(vec![
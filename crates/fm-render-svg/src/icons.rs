@@ -0,0 +1,158 @@
+//! Named node icons.
+//!
+//! An [`IconRegistry`] maps icon names to path-based [`CustomSvgIcon`]s. The renderer looks a
+//! node's icon up in [`SvgRenderConfig::custom_icons`](crate::SvgRenderConfig::custom_icons)
+//! first and then in the built-in set ([`IconRegistry::builtin`]), which covers the icons
+//! architecture-beta services reference: `database`, `disk`, `cloud`, `server` and `internet`.
+//! Names the registry does not know fall through to the renderer's hand-drawn icons and finally
+//! to an initials badge.
+
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use crate::{CustomSvgIcon, normalize_icon_token};
+
+/// Built-in icons as `(name, path data)` in a 24x24 viewBox, drawn as unfilled outlines.
+const BUILTIN_ICONS: &[(&str, &str)] = &[
+    (
+        "database",
+        "M4 6 C4 4.3 7.6 3 12 3 C16.4 3 20 4.3 20 6 C20 7.7 16.4 9 12 9 C7.6 9 4 7.7 4 6 Z \
+         M4 6 L4 18 C4 19.7 7.6 21 12 21 C16.4 21 20 19.7 20 18 L20 6 \
+         M4 12 C4 13.7 7.6 15 12 15 C16.4 15 20 13.7 20 12",
+    ),
+    (
+        "disk",
+        "M3 14 L21 14 L21 19 C21 19.6 20.6 20 20 20 L4 20 C3.4 20 3 19.6 3 19 Z \
+         M3 14 L6 5 L18 5 L21 14 M16 17 A1 1 0 1 0 18 17 A1 1 0 1 0 16 17",
+    ),
+    (
+        "cloud",
+        "M7 18 A4 4 0 0 1 6.5 10 A6 6 0 0 1 18 9 A4.5 4.5 0 0 1 18 18 Z",
+    ),
+    (
+        "server",
+        "M4 3 L20 3 L20 10 L4 10 Z M4 14 L20 14 L20 21 L4 21 Z M7 6.5 L9 6.5 M7 17.5 L9 17.5",
+    ),
+    (
+        "internet",
+        "M3 12 A9 9 0 1 0 21 12 A9 9 0 1 0 3 12 Z M3 12 L21 12 \
+         M12 3 C14.5 5.5 15.5 8.5 15.5 12 C15.5 15.5 14.5 18.5 12 21 \
+         M12 3 C9.5 5.5 8.5 8.5 8.5 12 C8.5 15.5 9.5 18.5 12 21",
+    ),
+];
+
+/// Icons keyed by normalized name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IconRegistry {
+    icons: BTreeMap<String, CustomSvgIcon>,
+}
+
+impl IconRegistry {
+    /// An empty registry.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry holding the built-in icon set.
+    #[must_use]
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        for (name, path_data) in BUILTIN_ICONS {
+            registry.register(
+                name,
+                CustomSvgIcon {
+                    path_data: (*path_data).to_string(),
+                    view_box_width: 24.0,
+                    view_box_height: 24.0,
+                    fill: None,
+                    stroke: None,
+                    stroke_width: 1.8,
+                },
+            );
+        }
+        registry
+    }
+
+    /// Register `icon` under `name`, returning any icon it replaces.
+    pub fn register(&mut self, name: &str, icon: CustomSvgIcon) -> Option<CustomSvgIcon> {
+        self.icons.insert(normalize_icon_token(name), icon)
+    }
+
+    /// Builder-style [`Self::register`].
+    #[must_use]
+    pub fn with(mut self, name: &str, icon: CustomSvgIcon) -> Self {
+        self.register(name, icon);
+        self
+    }
+
+    /// Icon registered under `name`. Names are normalized the way node icons are (`fa:fa-database`
+    /// finds `database`), and the common aliases `db`, `hdd`/`storage` and `globe`/`web` find the
+    /// built-in names.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&CustomSvgIcon> {
+        let normalized = normalize_icon_token(name);
+        self.icons
+            .get(&normalized)
+            .or_else(|| self.icons.get(builtin_alias(&normalized)?))
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.icons.is_empty()
+    }
+
+    /// Registered icon names in sorted order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.icons.keys().map(String::as_str)
+    }
+}
+
+/// The shared built-in registry the renderer consults.
+pub(crate) fn builtin_icons() -> &'static IconRegistry {
+    static BUILTIN: OnceLock<IconRegistry> = OnceLock::new();
+    BUILTIN.get_or_init(IconRegistry::builtin)
+}
+
+fn builtin_alias(normalized: &str) -> Option<&'static str> {
+    match normalized {
+        "db" => Some("database"),
+        "hdd" | "hard-drive" | "harddisk" | "drive" | "storage" => Some("disk"),
+        "globe" | "web" | "www" | "world" => Some("internet"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_set_resolves_names_and_aliases() {
+        let registry = IconRegistry::builtin();
+        assert_eq!(
+            registry.names().collect::<Vec<_>>(),
+            vec!["cloud", "database", "disk", "internet", "server"]
+        );
+        assert_eq!(registry.get("db"), registry.get("database"));
+        assert_eq!(registry.get("fa:fa-database"), registry.get("database"));
+        assert_eq!(registry.get("HDD"), registry.get("disk"));
+        assert_eq!(registry.get("globe"), registry.get("internet"));
+        assert!(registry.get("logos:aws-s3").is_none());
+        assert!(IconRegistry::new().get("database").is_none());
+    }
+
+    #[test]
+    fn registrations_replace_builtins() {
+        let icon = CustomSvgIcon {
+            path_data: "M0 0 L24 24".to_string(),
+            view_box_width: 24.0,
+            view_box_height: 24.0,
+            fill: None,
+            stroke: None,
+            stroke_width: 1.0,
+        };
+        let registry = IconRegistry::builtin().with("Cloud", icon.clone());
+        assert_eq!(registry.get("cloud"), Some(&icon));
+    }
+}
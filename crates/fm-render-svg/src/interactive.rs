@@ -0,0 +1,96 @@
+//! Pan/zoom and hover script embedded by `SvgRenderConfig::interactive`.
+//!
+//! The script is dependency-free and works on the `viewBox` of the `<svg>` it is embedded in:
+//! the mouse wheel zooms around the pointer, dragging pans, and a double click restores the
+//! original view. Hovering a node highlights every element that shares its `data-id` and dims
+//! the rest. Scripts only run when the SVG is opened directly or through `<object>`/`<iframe>`;
+//! an `<img>` embed shows the static drawing.
+
+/// The embedded script. It locates its own `<svg>` through `document.currentScript`.
+pub(crate) const INTERACTIVE_SCRIPT: &str = r"(function () {
+  var script = document.currentScript;
+  var svg = script && script.ownerSVGElement;
+  if (!svg || !svg.viewBox || !svg.viewBox.baseVal) return;
+  var base = svg.viewBox.baseVal;
+  var home = { x: base.x, y: base.y, w: base.width, h: base.height };
+  var view = { x: home.x, y: home.y, w: home.w, h: home.h };
+  var drag = null;
+  function apply() {
+    svg.setAttribute('viewBox', view.x + ' ' + view.y + ' ' + view.w + ' ' + view.h);
+  }
+  function toDiagram(event) {
+    var point = svg.createSVGPoint();
+    point.x = event.clientX;
+    point.y = event.clientY;
+    return point.matrixTransform(svg.getScreenCTM().inverse());
+  }
+  svg.addEventListener('wheel', function (event) {
+    event.preventDefault();
+    var scale = Math.exp(event.deltaY * 0.0015);
+    var width = Math.min(Math.max(view.w * scale, home.w / 20), home.w * 20);
+    scale = width / view.w;
+    var at = toDiagram(event);
+    view.x = at.x - (at.x - view.x) * scale;
+    view.y = at.y - (at.y - view.y) * scale;
+    view.w *= scale;
+    view.h *= scale;
+    apply();
+  }, { passive: false });
+  svg.addEventListener('pointerdown', function (event) {
+    if (event.button !== 0) return;
+    drag = toDiagram(event);
+    svg.setPointerCapture(event.pointerId);
+    svg.classList.add('fm-panning');
+  });
+  svg.addEventListener('pointermove', function (event) {
+    if (!drag) return;
+    var at = toDiagram(event);
+    view.x -= at.x - drag.x;
+    view.y -= at.y - drag.y;
+    apply();
+  });
+  function endDrag() {
+    drag = null;
+    svg.classList.remove('fm-panning');
+  }
+  svg.addEventListener('pointerup', endDrag);
+  svg.addEventListener('pointercancel', endDrag);
+  svg.addEventListener('dblclick', function () {
+    view = { x: home.x, y: home.y, w: home.w, h: home.h };
+    apply();
+  });
+  var nodes = svg.querySelectorAll('.fm-node[data-id]');
+  function highlight(id) {
+    svg.classList.toggle('fm-hovering', id !== null);
+    nodes.forEach(function (node) {
+      node.classList.toggle('fm-node-hover', id !== null && node.getAttribute('data-id') === id);
+    });
+  }
+  nodes.forEach(function (node) {
+    node.addEventListener('mouseenter', function () { highlight(node.getAttribute('data-id')); });
+    node.addEventListener('mouseleave', function () { highlight(null); });
+  });
+})();";
+
+/// Cursor and hover-highlight rules the script relies on.
+pub(crate) const INTERACTIVE_CSS: &str = "
+svg { cursor: grab; touch-action: none; }
+svg.fm-panning { cursor: grabbing; }
+.fm-hovering .fm-node:not(.fm-node-hover) { opacity: 0.45; }
+.fm-node-hover { opacity: 1; }
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_handles_zoom_pan_fit_and_hover() {
+        for event in ["'wheel'", "'pointermove'", "'dblclick'", "'mouseenter'"] {
+            assert!(INTERACTIVE_SCRIPT.contains(event), "{event}");
+        }
+        assert!(INTERACTIVE_SCRIPT.contains("data-id"));
+        assert!(!INTERACTIVE_SCRIPT.contains("]]>"));
+        assert!(INTERACTIVE_CSS.contains(".fm-node-hover"));
+    }
+}
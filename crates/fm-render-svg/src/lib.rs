@@ -12,39 +12,55 @@
 
 mod a11y;
 mod attributes;
+mod build_up;
 pub mod cga_transform;
 mod defs;
+mod diff;
 mod document;
 mod element;
+mod icons;
+mod interactive;
 mod path;
+#[cfg(feature = "raster")]
+mod raster;
 mod text;
 mod theme;
 mod transform;
 
 pub use a11y::{
     A11yConfig, accessibility_css, describe_diagram, describe_diagram_with_layout, describe_edge,
-    describe_node,
+    describe_node, focus_order,
 };
 pub use attributes::{Attribute, AttributeValue, Attributes};
-pub use defs::{ArrowheadMarker, DefsBuilder, Filter, Gradient, GradientStop, MarkerOrient};
+pub use build_up::{BuildUpPlan, render_svg_build_up_animated, render_svg_build_up_frames};
+pub use defs::{
+    ArrowheadMarker, DefsBuilder, ErCardinality, Filter, Gradient, GradientStop, MarkerOrient,
+};
+pub use diff::render_diff_svg;
 pub use document::SvgDocument;
 pub use element::{Element, ElementKind};
+pub use icons::IconRegistry;
 pub use path::{PathBuilder, PathCommand};
+#[cfg(feature = "raster")]
+pub use raster::{
+    RasterError, make_config_raster_safe, render_png,
+    resolve_svg_custom_properties_for_rasterization, svg_to_png,
+};
 pub use text::{TextAnchor, TextBuilder};
 pub use theme::{FontConfig, Theme, ThemeColors, ThemePreset, generate_palette};
 pub use transform::{Transform, TransformBuilder};
 
 use std::{
     borrow::Cow,
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap},
     sync::OnceLock,
 };
 
 use fm_core::{
     DiagramType, IrLabelId, IrLabelSegment, IrXyChartMeta, IrXySeriesKind, MermaidDiagramIr,
-    MermaidLinkMode, MermaidSanitizeMode, MermaidTier, Span, is_safe_link_target,
-    mermaid_cluster_element_id, mermaid_edge_element_id, mermaid_node_element_id,
-    mermaid_node_element_id_with_variant,
+    MermaidLinkMode, MermaidSanitizeMode, MermaidTier, MermaidWrapMode, NodeShapeRegistry, Span,
+    is_safe_link_target, mermaid_cluster_element_id, mermaid_edge_element_id,
+    mermaid_node_element_id, mermaid_node_element_id_with_variant,
 };
 use fm_layout::{
     CentralityTier, DiagramLayout, FillStyle, LayoutBand, LayoutBandKind, LayoutEdgePath,
@@ -76,6 +92,44 @@ pub enum SvgBackend {
     Scene,
 }
 
+/// A group of the layered output (see [`SvgRenderConfig::layers`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SvgLayer {
+    /// Cluster and subgraph boxes with their titles.
+    Clusters,
+    /// Edge paths.
+    Edges,
+    /// Edge label boxes, cardinality labels and bundle counts.
+    EdgeLabels,
+    /// Node shapes.
+    Nodes,
+    /// Node label text.
+    NodeLabels,
+}
+
+impl SvgLayer {
+    /// Every layer in paint order.
+    pub const ALL: [Self; 5] = [
+        Self::Clusters,
+        Self::Edges,
+        Self::EdgeLabels,
+        Self::Nodes,
+        Self::NodeLabels,
+    ];
+
+    /// The `id` of the layer's `<g>`, e.g. `fm-layer-edge-labels`.
+    #[must_use]
+    pub const fn element_id(self) -> &'static str {
+        match self {
+            Self::Clusters => "fm-layer-clusters",
+            Self::Edges => "fm-layer-edges",
+            Self::EdgeLabels => "fm-layer-edge-labels",
+            Self::Nodes => "fm-layer-nodes",
+            Self::NodeLabels => "fm-layer-node-labels",
+        }
+    }
+}
+
 /// Node icon placement strategy.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum NodeIconPosition {
@@ -84,6 +138,9 @@ pub enum NodeIconPosition {
     Above,
     /// Place the icon to the left of the text label.
     Left,
+    /// Draw a small icon in the node's top-left corner without moving the label. Architecture
+    /// services always use this placement.
+    Corner,
 }
 
 /// Configurable custom SVG icon definition.
@@ -110,13 +167,26 @@ pub struct SvgRenderConfig {
     pub backend: SvgBackend,
     /// Whether to include responsive sizing attributes.
     pub responsive: bool,
+    /// Fixed output width in px, emitted as the root `width` attribute next to the `viewBox`.
+    /// With only one of `width`/`height` set, the other follows the diagram's aspect ratio.
+    /// Either one replaces the responsive 100% sizing.
+    pub width: Option<f32>,
+    /// Fixed output height in px. See [`Self::width`].
+    pub height: Option<f32>,
+    /// Largest output width in px. Fixed-size output wider than this shrinks to fit, keeping its
+    /// aspect ratio; responsive output gets a `max-width` style instead.
+    pub max_width: Option<f32>,
+    /// Output px per `viewBox` unit when neither `width` nor `height` is set. Any value other
+    /// than 1 gives the output a fixed size.
+    pub scale: f32,
     /// Whether to include accessibility attributes.
     pub accessible: bool,
     /// Default font family for text.
     pub font_family: String,
     /// Default font size in pixels.
     pub font_size: f32,
-    /// Average character width for text measurement (in pixels).
+    /// Average character width (in pixels) for measuring text when `font_family` names no font
+    /// fm-core has metric tables for; known fonts are measured per character class instead.
     pub avg_char_width: f32,
     /// Line height multiplier for multi-line text.
     pub line_height: f32,
@@ -172,7 +242,8 @@ pub struct SvgRenderConfig {
     pub hover_scale: f32,
     /// Position for node icons relative to the label.
     pub node_icon_position: NodeIconPosition,
-    /// User-provided custom icon definitions keyed by normalized icon name.
+    /// User-provided custom icon definitions keyed by normalized icon name. These take
+    /// precedence over the built-in [`IconRegistry`].
     pub custom_icons: BTreeMap<String, CustomSvgIcon>,
     /// Detail tier selection (`auto`, `compact`, `normal`, `rich`).
     pub detail_tier: MermaidTier,
@@ -186,6 +257,18 @@ pub struct SvgRenderConfig {
     pub include_source_spans: bool,
     /// How (or if) to emit node links.
     pub link_mode: MermaidLinkMode,
+    /// Custom node shape drawers consulted before the built-in shapes.
+    pub shape_registry: NodeShapeRegistry,
+    /// Where wrapped text (descriptions, cluster titles) breaks when a line is too wide.
+    pub wrap_mode: MermaidWrapMode,
+    /// Whether to embed a script for wheel zoom, drag pan, double-click-to-fit and node hover
+    /// highlighting.
+    pub interactive: bool,
+    /// Whether to paint clusters, edges, edge labels, nodes and node labels in separate
+    /// `<g id="fm-layer-*">` groups (see [`SvgLayer`]) so a host can hide one with CSS, e.g.
+    /// `#fm-layer-edge-labels { display: none }`. Node labels leave their node's group for it;
+    /// class and ER compartments stay with their dividers. Layout backend only.
+    pub layers: bool,
 }
 
 impl SvgRenderConfig {
@@ -231,6 +314,10 @@ impl Default for SvgRenderConfig {
         Self {
             backend: SvgBackend::LegacyLayout,
             responsive: true,
+            width: None,
+            height: None,
+            max_width: None,
+            scale: 1.0,
             accessible: true,
             font_family: String::from(
                 "'Inter', -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, Helvetica, Arial, sans-serif",
@@ -271,6 +358,10 @@ impl Default for SvgRenderConfig {
             a11y: A11yConfig::full(),
             include_source_spans: false,
             link_mode: MermaidLinkMode::Off,
+            shape_registry: NodeShapeRegistry::default(),
+            wrap_mode: MermaidWrapMode::WordChar,
+            interactive: false,
+            layers: false,
         }
     }
 }
@@ -322,14 +413,21 @@ pub fn render_svg_with_layout(
     layout: &DiagramLayout,
     config: &SvgRenderConfig,
 ) -> String {
+    let display_ir = layout.display_ir(ir);
+    let ir = display_ir.as_ref();
     let mut svg = match config.backend {
         SvgBackend::LegacyLayout => render_layout_to_svg(layout, ir, config),
         SvgBackend::Scene => {
-            let scene = build_render_scene(ir, layout);
+            let mut scene = build_render_scene(ir, layout);
+            let theme = resolve_theme(Some(ir), config);
+            apply_theme_to_scene(&mut scene.root, &theme.colors, config.embed_theme_css);
             render_scene_document_with_ir(&scene, config, Some(ir))
         }
     };
     strip_unused_state_css(&mut svg);
+    if config.a11y.focus_order {
+        a11y::apply_focus_order(&mut svg, &focus_order(ir, layout));
+    }
     // The output post-passes below (marker-def strip, dead-marker-CSS prune, CSS minify) each walk
     // the SVG and rebuild a buffer. On a SMALL/MEDIUM diagram that is cheap and the byte win is a
     // meaningful fraction (the fixed CSS + the 12-marker set dominate small output). On a LARGE
@@ -746,55 +844,257 @@ const DEFAULT_EDGE_COLOR: &str = "#94a3b8";
 /// both render backends add via `DefsBuilder::marker`. Byte-identical to those children because it
 /// calls the same `Element::write_to_string`.
 fn build_marker_defs_body(edge_color: &str, emit_fancy: bool) -> String {
-    use crate::defs::MarkerOrient;
     let mut s = String::new();
-    let push = |s: &mut String, m: ArrowheadMarker| m.to_element().write_to_string(s);
-    push(&mut s, ArrowheadMarker::standard("arrow-end", edge_color));
-    if emit_fancy {
-        push(&mut s, ArrowheadMarker::filled("arrow-filled", edge_color));
+    for (id, basic) in ARROW_MARKERS {
+        if (basic || emit_fancy)
+            && let Some(marker) = arrow_marker(id, id, edge_color)
+        {
+            marker.to_element().write_to_string(&mut s);
+        }
     }
-    push(&mut s, ArrowheadMarker::open("arrow-open", edge_color));
-    if emit_fancy {
-        push(
-            &mut s,
-            ArrowheadMarker::half_top("arrow-half-top", edge_color),
-        );
-        push(
-            &mut s,
-            ArrowheadMarker::half_bottom("arrow-half-bottom", edge_color),
-        );
-        push(
-            &mut s,
-            ArrowheadMarker::stick_top("arrow-stick-top", edge_color),
-        );
-        push(
-            &mut s,
-            ArrowheadMarker::stick_bottom("arrow-stick-bottom", edge_color),
-        );
-        push(
-            &mut s,
-            ArrowheadMarker::standard("arrow-start", edge_color)
-                .with_orient(MarkerOrient::AutoStartReverse),
-        );
-        push(
-            &mut s,
-            ArrowheadMarker::filled("arrow-start-filled", edge_color)
-                .with_orient(MarkerOrient::AutoStartReverse),
-        );
-        push(
-            &mut s,
-            ArrowheadMarker::circle_marker("arrow-circle", edge_color),
-        );
-        push(
-            &mut s,
-            ArrowheadMarker::cross_marker("arrow-cross", edge_color),
-        );
-        push(
-            &mut s,
-            ArrowheadMarker::diamond_marker("arrow-diamond", edge_color),
+    s
+}
+
+/// Every arrowhead marker id in emission order, flagged when it belongs to the basic set that
+/// flowcharts using only plain arrows get.
+const ARROW_MARKERS: [(&str, bool); 12] = [
+    ("arrow-end", true),
+    ("arrow-filled", false),
+    ("arrow-open", true),
+    ("arrow-half-top", false),
+    ("arrow-half-bottom", false),
+    ("arrow-stick-top", false),
+    ("arrow-stick-bottom", false),
+    ("arrow-start", false),
+    ("arrow-start-filled", false),
+    ("arrow-circle", false),
+    ("arrow-cross", false),
+    ("arrow-diamond", false),
+];
+
+/// The arrowhead marker `base` (one of [`ARROW_MARKERS`]) drawn in `color` under the id `id`.
+fn arrow_marker(base: &str, id: &str, color: &str) -> Option<ArrowheadMarker> {
+    use crate::defs::MarkerOrient;
+    let marker = match base {
+        "arrow-end" => ArrowheadMarker::standard(id, color),
+        "arrow-filled" => ArrowheadMarker::filled(id, color),
+        "arrow-open" => ArrowheadMarker::open(id, color),
+        "arrow-half-top" => ArrowheadMarker::half_top(id, color),
+        "arrow-half-bottom" => ArrowheadMarker::half_bottom(id, color),
+        "arrow-stick-top" => ArrowheadMarker::stick_top(id, color),
+        "arrow-stick-bottom" => ArrowheadMarker::stick_bottom(id, color),
+        "arrow-start" => {
+            ArrowheadMarker::standard(id, color).with_orient(MarkerOrient::AutoStartReverse)
+        }
+        "arrow-start-filled" => {
+            ArrowheadMarker::filled(id, color).with_orient(MarkerOrient::AutoStartReverse)
+        }
+        "arrow-circle" => ArrowheadMarker::circle_marker(id, color),
+        "arrow-cross" => ArrowheadMarker::cross_marker(id, color),
+        "arrow-diamond" => ArrowheadMarker::diamond_marker(id, color),
+        _ => return None,
+    };
+    Some(marker)
+}
+
+/// `(dasharray, marker_start, marker_end)` for an edge drawn forward with `arrow`.
+const fn arrow_markers(
+    arrow: fm_core::ArrowType,
+) -> (
+    Option<&'static str>,
+    Option<&'static str>,
+    Option<&'static str>,
+) {
+    use fm_core::ArrowType;
+    match arrow {
+        ArrowType::Line | ArrowType::ThickLine => (None, None, None),
+        ArrowType::Arrow => (None, None, Some("url(#arrow-end)")),
+        ArrowType::OpenArrow => (None, None, Some("url(#arrow-open)")),
+        ArrowType::HalfArrowTop => (None, None, Some("url(#arrow-half-top)")),
+        ArrowType::HalfArrowBottom => (None, None, Some("url(#arrow-half-bottom)")),
+        ArrowType::HalfArrowTopReverse => (None, Some("url(#arrow-half-bottom)"), None),
+        ArrowType::HalfArrowBottomReverse => (None, Some("url(#arrow-half-top)"), None),
+        ArrowType::StickArrowTop => (None, None, Some("url(#arrow-stick-top)")),
+        ArrowType::StickArrowBottom => (None, None, Some("url(#arrow-stick-bottom)")),
+        ArrowType::StickArrowTopReverse => (None, Some("url(#arrow-stick-bottom)"), None),
+        ArrowType::StickArrowBottomReverse => (None, Some("url(#arrow-stick-top)"), None),
+        ArrowType::ThickArrow => (None, None, Some("url(#arrow-filled)")),
+        ArrowType::DottedArrow => (Some("5,5"), None, Some("url(#arrow-end)")),
+        ArrowType::DottedOpenArrow => (Some("5,5"), None, Some("url(#arrow-open)")),
+        ArrowType::DottedCross => (Some("5,5"), None, Some("url(#arrow-cross)")),
+        ArrowType::HalfArrowTopDotted => (Some("5,5"), None, Some("url(#arrow-half-top)")),
+        ArrowType::HalfArrowBottomDotted => (Some("5,5"), None, Some("url(#arrow-half-bottom)")),
+        ArrowType::HalfArrowTopReverseDotted => {
+            (Some("5,5"), Some("url(#arrow-half-bottom)"), None)
+        }
+        ArrowType::HalfArrowBottomReverseDotted => {
+            (Some("5,5"), Some("url(#arrow-half-top)"), None)
+        }
+        ArrowType::StickArrowTopDotted => (Some("5,5"), None, Some("url(#arrow-stick-top)")),
+        ArrowType::StickArrowBottomDotted => (Some("5,5"), None, Some("url(#arrow-stick-bottom)")),
+        ArrowType::StickArrowTopReverseDotted => {
+            (Some("5,5"), Some("url(#arrow-stick-bottom)"), None)
+        }
+        ArrowType::StickArrowBottomReverseDotted => {
+            (Some("5,5"), Some("url(#arrow-stick-top)"), None)
+        }
+        ArrowType::Circle => (None, None, Some("url(#arrow-circle)")),
+        ArrowType::Cross => (None, None, Some("url(#arrow-cross)")),
+        ArrowType::DottedLine => (Some("5,5"), None, None),
+        ArrowType::DoubleArrow => (None, Some("url(#arrow-start)"), Some("url(#arrow-end)")),
+        ArrowType::DoubleThickArrow => (
+            None,
+            Some("url(#arrow-start-filled)"),
+            Some("url(#arrow-filled)"),
+        ),
+        ArrowType::DoubleDottedArrow => (
+            Some("5,5"),
+            Some("url(#arrow-start)"),
+            Some("url(#arrow-end)"),
+        ),
+    }
+}
+
+/// The `stroke` color an edge's inline style or `linkStyle` sets, which its arrowheads take on.
+fn edge_stroke_color(ir: &MermaidDiagramIr, edge_index: usize) -> Option<String> {
+    use fm_core::{IrStyleTarget, parse_style_string};
+    if let Some(edge) = ir.edges.get(edge_index)
+        && let Some(style) = edge.inline_style.as_ref()
+    {
+        return style.properties.get("stroke").cloned();
+    }
+    let mut stroke = None;
+    for target in [IrStyleTarget::LinkDefault, IrStyleTarget::Link(edge_index)] {
+        for sr in ir.style_refs.iter().filter(|sr| sr.target == target) {
+            if let Some(value) = parse_style_string(&sr.style).properties.remove("stroke") {
+                stroke = Some(value);
+            }
+        }
+    }
+    stroke
+}
+
+/// `url(#arrow-…)` rewritten to the variant of that marker drawn in the stroke color `color`.
+fn stroke_colored_marker_url(url: &str, color: &str) -> String {
+    let base = url.trim_start_matches("url(#").trim_end_matches(')');
+    format!("url(#{})", stroke_colored_marker_id(base, color))
+}
+
+/// Id of the variant of arrow marker `base` drawn in `color`: the color's letters and digits,
+/// with every other character turned into `-`, appended to the base id.
+fn stroke_colored_marker_id(base: &str, color: &str) -> String {
+    let suffix: String = color
+        .trim()
+        .trim_start_matches('#')
+        .chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '-' })
+        .collect();
+    format!("{base}-{suffix}")
+}
+
+/// Markers for the UML relations of class-diagram edges, each in an end and a start variant (see
+/// [`class_relation_markers`]). Hollow shapes are filled with the `background` so the edge line
+/// stops at their outline.
+fn uml_relation_markers(edge_color: &str, background: &str) -> [ArrowheadMarker; 7] {
+    use crate::defs::MarkerOrient;
+    let start = |marker: ArrowheadMarker| marker.with_orient(MarkerOrient::AutoStartReverse);
+    [
+        ArrowheadMarker::hollow_triangle("uml-triangle", edge_color, background),
+        start(ArrowheadMarker::hollow_triangle(
+            "uml-triangle-start",
+            edge_color,
+            background,
+        )),
+        ArrowheadMarker::uml_diamond("uml-composition", edge_color, edge_color),
+        start(ArrowheadMarker::uml_diamond(
+            "uml-composition-start",
+            edge_color,
+            edge_color,
+        )),
+        ArrowheadMarker::uml_diamond("uml-aggregation", edge_color, background),
+        start(ArrowheadMarker::uml_diamond(
+            "uml-aggregation-start",
+            edge_color,
+            background,
+        )),
+        start(ArrowheadMarker::open("uml-open-start", edge_color)),
+    ]
+}
+
+/// `(marker_start, marker_end)` for a UML class relation: a hollow triangle for inheritance and
+/// realization, a filled or hollow diamond for composition or aggregation, and an open arrow for
+/// association and dependency, on whichever end the relation's operator marks.
+fn class_relation_markers(
+    relation: fm_core::IrClassRelation,
+) -> (Option<&'static str>, Option<&'static str>) {
+    use fm_core::ClassRelationKind;
+    let (start, end) = match relation.kind {
+        ClassRelationKind::Inheritance | ClassRelationKind::Realization => {
+            ("url(#uml-triangle-start)", "url(#uml-triangle)")
+        }
+        ClassRelationKind::Composition => ("url(#uml-composition-start)", "url(#uml-composition)"),
+        ClassRelationKind::Aggregation => ("url(#uml-aggregation-start)", "url(#uml-aggregation)"),
+        ClassRelationKind::Association | ClassRelationKind::Dependency => {
+            ("url(#uml-open-start)", "url(#arrow-open)")
+        }
+        ClassRelationKind::Link => return (None, None),
+    };
+    if relation.marker_at_from {
+        (Some(start), None)
+    } else {
+        (None, Some(end))
+    }
+}
+
+/// Crow's foot markers for the ends of ER relationships, an end and a start variant per
+/// cardinality (see [`er_relation_markers`]).
+fn er_cardinality_markers(edge_color: &str, background: &str) -> [ArrowheadMarker; 8] {
+    use crate::defs::MarkerOrient;
+    std::array::from_fn(|index| {
+        let cardinality = ErCardinality::ALL[index / 2];
+        let at_start = index % 2 == 1;
+        let marker = ArrowheadMarker::crows_foot(
+            er_marker(cardinality, at_start).0,
+            cardinality,
+            edge_color,
+            background,
         );
+        if at_start {
+            marker.with_orient(MarkerOrient::AutoStartReverse)
+        } else {
+            marker
+        }
+    })
+}
+
+/// Marker id and `url(#…)` reference of the crow's foot for `cardinality` at the start or end
+/// of an edge.
+const fn er_marker(cardinality: ErCardinality, at_start: bool) -> (&'static str, &'static str) {
+    match (cardinality, at_start) {
+        (ErCardinality::ExactlyOne, false) => ("er-one", "url(#er-one)"),
+        (ErCardinality::ExactlyOne, true) => ("er-one-start", "url(#er-one-start)"),
+        (ErCardinality::ZeroOrOne, false) => ("er-zero-one", "url(#er-zero-one)"),
+        (ErCardinality::ZeroOrOne, true) => ("er-zero-one-start", "url(#er-zero-one-start)"),
+        (ErCardinality::OneOrMore, false) => ("er-one-many", "url(#er-one-many)"),
+        (ErCardinality::OneOrMore, true) => ("er-one-many-start", "url(#er-one-many-start)"),
+        (ErCardinality::ZeroOrMore, false) => ("er-zero-many", "url(#er-zero-many)"),
+        (ErCardinality::ZeroOrMore, true) => ("er-zero-many-start", "url(#er-zero-many-start)"),
     }
-    s
+}
+
+/// `(marker_start, marker_end)` for an ER relationship operator such as `||--o{`: the left half's
+/// cardinality marks the `from` entity and the right half's the `to` entity.
+fn er_relation_markers(notation: &str) -> (Option<&'static str>, Option<&'static str>) {
+    let Some(connector) = notation.find("--").or_else(|| notation.find("..")) else {
+        return (None, None);
+    };
+    let url = |end: &str, at_start: bool| {
+        ErCardinality::from_notation(end).map(|cardinality| er_marker(cardinality, at_start).1)
+    };
+    (
+        url(&notation[..connector], true),
+        url(&notation[connector + 2..], false),
+    )
 }
 
 /// The arrowhead-marker `<defs>` body for `(edge_color, emit_fancy)`, memoized for the default theme.
@@ -831,6 +1131,46 @@ fn render_scene_document(scene: &RenderScene, config: &SvgRenderConfig) -> Strin
     render_scene_document_with_ir(scene, config, None)
 }
 
+/// Size the root `<svg>` of a `view_width` x `view_height` viewBox from the config's size
+/// options: fixed pixel `width`/`height` when any are set (or `scale` is not 1), otherwise the
+/// responsive 100% sizing capped by `max_width`, or no size at all.
+fn apply_output_size(
+    doc: SvgDocument,
+    config: &SvgRenderConfig,
+    view_width: f32,
+    view_height: f32,
+) -> SvgDocument {
+    let positive = |value: Option<f32>| value.filter(|value| value.is_finite() && *value > 0.0);
+    let max_width = positive(config.max_width);
+    let aspect = view_height / view_width.max(1.0);
+    let fixed = match (positive(config.width), positive(config.height)) {
+        (Some(width), Some(height)) => Some((width, height)),
+        (Some(width), None) => Some((width, width * aspect)),
+        (None, Some(height)) => Some((height / aspect.max(f32::EPSILON), height)),
+        (None, None) => {
+            let scale = positive(Some(config.scale)).unwrap_or(1.0);
+            ((scale - 1.0).abs() > f32::EPSILON || (!config.responsive && max_width.is_some()))
+                .then_some((view_width * scale, view_height * scale))
+        }
+    };
+
+    let px = |value: f32| ((value * 100.0).round() / 100.0).to_string();
+    match fixed {
+        Some((width, height)) => {
+            let shrink = max_width.map_or(1.0, |max_width| (max_width / width).min(1.0));
+            doc.width(&px(width * shrink)).height(&px(height * shrink))
+        }
+        None if config.responsive => {
+            let doc = doc.responsive();
+            match max_width {
+                Some(max_width) => doc.attr("style", format!("max-width: {}px", px(max_width))),
+                None => doc,
+            }
+        }
+        None => doc,
+    }
+}
+
 fn resolve_accessibility_text(
     ir: Option<&MermaidDiagramIr>,
     layout: Option<&DiagramLayout>,
@@ -1015,9 +1355,7 @@ fn render_scene_document_with_ir(
         doc = doc.font_family(&config.font_family);
     }
 
-    if config.responsive {
-        doc = doc.responsive();
-    }
+    doc = apply_output_size(doc, config, width, height);
 
     let (group_count, path_count, text_count) = count_scene_items(&scene.root);
 
@@ -1089,6 +1427,10 @@ fn render_scene_document_with_ir(
     if !classdef_css.is_empty() {
         css.push_str(&classdef_css);
     }
+    if config.interactive {
+        css.push_str(interactive::INTERACTIVE_CSS);
+        doc = doc.script(interactive::INTERACTIVE_SCRIPT);
+    }
     if !css.is_empty() {
         doc = doc.style(css);
     }
@@ -1155,6 +1497,55 @@ fn count_scene_items(group: &RenderGroup) -> (usize, usize, usize) {
     (groups, paths, texts)
 }
 
+/// Repaint a scene built from the IR with the theme. Node, edge and cluster paths and their labels
+/// take the `--fm-*` custom properties of the embedded style block, falling back to the theme's
+/// colors, or the theme's colors themselves when no style block is embedded. Scenes handed to
+/// [`render_scene_to_svg`] keep the colors they carry.
+fn apply_theme_to_scene(group: &mut RenderGroup, colors: &ThemeColors, embed_css: bool) {
+    let paint = |var: &str, color: &str| {
+        if embed_css {
+            format!("var({var}, {color})")
+        } else {
+            color.to_string()
+        }
+    };
+    for child in &mut group.children {
+        match child {
+            RenderItem::Group(group) => apply_theme_to_scene(group, colors, embed_css),
+            RenderItem::Path(path) => {
+                let (fill, stroke) = match path.source {
+                    RenderSource::Node(_) => (
+                        Some(("--fm-node-fill", &colors.node_fill)),
+                        Some(("--fm-node-stroke", &colors.node_stroke)),
+                    ),
+                    RenderSource::Edge(_) => (None, Some(("--fm-edge-color", &colors.edge))),
+                    RenderSource::Cluster(_) => (
+                        Some(("--fm-cluster-fill", &colors.cluster_fill)),
+                        Some(("--fm-cluster-stroke", &colors.cluster_stroke)),
+                    ),
+                    RenderSource::Diagram => (None, None),
+                };
+                if let Some(FillStyle::Solid { color, .. }) = path.fill.as_mut()
+                    && let Some((var, themed)) = fill
+                {
+                    *color = paint(var, themed);
+                }
+                if let Some(style) = path.stroke.as_mut()
+                    && let Some((var, themed)) = stroke
+                {
+                    style.color = paint(var, themed);
+                }
+            }
+            RenderItem::Text(text) => {
+                if text.source != RenderSource::Diagram {
+                    let FillStyle::Solid { color, .. } = &mut text.fill;
+                    *color = paint("--fm-text-color", &colors.text);
+                }
+            }
+        }
+    }
+}
+
 fn render_scene_group(
     group: &RenderGroup,
     config: &SvgRenderConfig,
@@ -1688,7 +2079,7 @@ pub(crate) fn sanitize_svg_paint(value: &str) -> Option<String> {
     }
 
     let lower = trimmed.to_ascii_lowercase();
-    if is_css_named_color(&lower) {
+    if fm_core::is_css_named_color(&lower) {
         return Some(lower);
     }
 
@@ -1714,161 +2105,6 @@ pub(crate) fn sanitize_svg_paint(value: &str) -> Option<String> {
     None
 }
 
-fn is_css_named_color(value: &str) -> bool {
-    matches!(
-        value,
-        "aliceblue"
-            | "antiquewhite"
-            | "aqua"
-            | "aquamarine"
-            | "azure"
-            | "beige"
-            | "bisque"
-            | "black"
-            | "blanchedalmond"
-            | "blue"
-            | "blueviolet"
-            | "brown"
-            | "burlywood"
-            | "cadetblue"
-            | "chartreuse"
-            | "chocolate"
-            | "coral"
-            | "cornflowerblue"
-            | "cornsilk"
-            | "crimson"
-            | "cyan"
-            | "darkblue"
-            | "darkcyan"
-            | "darkgoldenrod"
-            | "darkgray"
-            | "darkgreen"
-            | "darkgrey"
-            | "darkkhaki"
-            | "darkmagenta"
-            | "darkolivegreen"
-            | "darkorange"
-            | "darkorchid"
-            | "darkred"
-            | "darksalmon"
-            | "darkseagreen"
-            | "darkslateblue"
-            | "darkslategray"
-            | "darkslategrey"
-            | "darkturquoise"
-            | "darkviolet"
-            | "deeppink"
-            | "deepskyblue"
-            | "dimgray"
-            | "dimgrey"
-            | "dodgerblue"
-            | "firebrick"
-            | "floralwhite"
-            | "forestgreen"
-            | "fuchsia"
-            | "gainsboro"
-            | "ghostwhite"
-            | "gold"
-            | "goldenrod"
-            | "gray"
-            | "green"
-            | "greenyellow"
-            | "grey"
-            | "honeydew"
-            | "hotpink"
-            | "indianred"
-            | "indigo"
-            | "ivory"
-            | "khaki"
-            | "lavender"
-            | "lavenderblush"
-            | "lawngreen"
-            | "lemonchiffon"
-            | "lightblue"
-            | "lightcoral"
-            | "lightcyan"
-            | "lightgoldenrodyellow"
-            | "lightgray"
-            | "lightgreen"
-            | "lightgrey"
-            | "lightpink"
-            | "lightsalmon"
-            | "lightseagreen"
-            | "lightskyblue"
-            | "lightslategray"
-            | "lightslategrey"
-            | "lightsteelblue"
-            | "lightyellow"
-            | "lime"
-            | "limegreen"
-            | "linen"
-            | "magenta"
-            | "maroon"
-            | "mediumaquamarine"
-            | "mediumblue"
-            | "mediumorchid"
-            | "mediumpurple"
-            | "mediumseagreen"
-            | "mediumslateblue"
-            | "mediumspringgreen"
-            | "mediumturquoise"
-            | "mediumvioletred"
-            | "midnightblue"
-            | "mintcream"
-            | "mistyrose"
-            | "moccasin"
-            | "navajowhite"
-            | "navy"
-            | "oldlace"
-            | "olive"
-            | "olivedrab"
-            | "orange"
-            | "orangered"
-            | "orchid"
-            | "palegoldenrod"
-            | "palegreen"
-            | "paleturquoise"
-            | "palevioletred"
-            | "papayawhip"
-            | "peachpuff"
-            | "peru"
-            | "pink"
-            | "plum"
-            | "powderblue"
-            | "purple"
-            | "rebeccapurple"
-            | "red"
-            | "rosybrown"
-            | "royalblue"
-            | "saddlebrown"
-            | "salmon"
-            | "sandybrown"
-            | "seagreen"
-            | "seashell"
-            | "sienna"
-            | "silver"
-            | "skyblue"
-            | "slateblue"
-            | "slategray"
-            | "slategrey"
-            | "snow"
-            | "springgreen"
-            | "steelblue"
-            | "tan"
-            | "teal"
-            | "thistle"
-            | "tomato"
-            | "transparent"
-            | "turquoise"
-            | "violet"
-            | "wheat"
-            | "white"
-            | "whitesmoke"
-            | "yellow"
-            | "yellowgreen"
-    )
-}
-
 const TEXT_STYLE_PROPERTIES: &[&str] = &[
     "color",
     "font-size",
@@ -1964,21 +2200,30 @@ fn collect_classdef_css(ir: &MermaidDiagramIr) -> String {
         ir.style_defs.clone()
     };
 
-    defs.sort_by(|a, b| a.name.cmp(&b.name));
+    // `classDef default` styles every node; it goes first so the named classes override it.
+    defs.sort_by(|a, b| {
+        (a.name != fm_core::DEFAULT_STYLE_CLASS, &a.name)
+            .cmp(&(b.name != fm_core::DEFAULT_STYLE_CLASS, &b.name))
+    });
     for def in &defs {
         let class_slug = sanitize_css_token(&def.name);
         if class_slug.is_empty() || def.properties.is_empty() {
             continue;
         }
+        let scope = if def.name == fm_core::DEFAULT_STYLE_CLASS {
+            ".fm-node".to_string()
+        } else {
+            format!(".fm-node-user-{class_slug}")
+        };
         let (shape_props, text_props) = split_style_properties(&def.properties);
         if let Some(shape_css) = style_map_to_css(&shape_props) {
             css.push_str(&format!(
-                ".fm-node-user-{class_slug} .fm-node-shape, .fm-node-user-{class_slug} .fm-node-shape * {{ {shape_css}; }}\n"
+                "{scope} .fm-node-shape, {scope} .fm-node-shape * {{ {shape_css}; }}\n"
             ));
         }
         if let Some(text_css) = style_map_to_css(&text_props) {
             css.push_str(&format!(
-                ".fm-node-user-{class_slug} .fm-node-label, .fm-node-user-{class_slug} .fm-node-label * {{ {text_css}; }}\n"
+                "{scope} .fm-node-label, {scope} .fm-node-label * {{ {text_css}; }}\n"
             ));
         }
     }
@@ -2039,23 +2284,17 @@ fn resolve_edge_inline_style(ir: &MermaidDiagramIr, edge_index: usize) -> Option
 }
 
 fn truncate_label(label: &str, max_chars: Option<usize>) -> Cow<'_, str> {
-    let Some(limit) = max_chars else {
-        return Cow::Borrowed(label);
-    };
-    let mut chars = label.chars();
-    let needs_truncation = chars.clone().count() > limit;
-    if !needs_truncation {
-        return Cow::Borrowed(label);
-    }
-    let mut text = String::new();
-    for _ in 0..limit.saturating_sub(1) {
-        let Some(ch) = chars.next() else {
-            break;
-        };
-        text.push(ch);
+    match max_chars {
+        Some(limit) if label.chars().count() > limit => {
+            Cow::Owned(fm_core::ellipsize(label, limit, char_count))
+        }
+        _ => Cow::Borrowed(label),
     }
-    text.push('…');
-    Cow::Owned(text)
+}
+
+/// Width of `text` in characters, the unit of the SVG label limits.
+fn char_count(text: &str) -> usize {
+    text.chars().count()
 }
 
 fn detail_tier_name(tier: RenderDetailTier) -> &'static str {
@@ -2414,6 +2653,11 @@ fn render_edges_serial(
     }
 }
 
+/// Mermaid's sticky-note colors, kept on every theme so notes read as annotations rather than
+/// nodes.
+const NODE_NOTE_FILL: &str = "#fff5ad";
+const NODE_NOTE_STROKE: &str = "#aaaa33";
+
 fn render_layout_to_svg(
     layout: &DiagramLayout,
     ir: &MermaidDiagramIr,
@@ -2458,9 +2702,7 @@ fn render_layout_to_svg(
         doc = doc.font_family(&config.font_family);
     }
 
-    if config.responsive {
-        doc = doc.responsive();
-    }
+    doc = apply_output_size(doc, config, width, height);
 
     if config.accessible {
         let (title, desc) = resolve_accessibility_text(Some(ir), Some(layout), config, || {
@@ -2520,6 +2762,54 @@ fn render_layout_to_svg(
     // markers slot so the output is byte-identical to the per-marker `.marker()` children it replaces,
     // skipping the ~1-6 µs of Element construction + serialization rebuilt on every render.
     defs = defs.raw_markers(marker_defs_body(edge_color, emit_fancy_markers));
+    // UML relation markers (hollow triangles, diamonds) for class diagrams; any the diagram does not
+    // reference are stripped with the rest by `strip_unused_markers`.
+    if ir.diagram_type == fm_core::DiagramType::Class
+        && ir.edges.iter().any(|edge| edge.class_relation().is_some())
+    {
+        for marker in uml_relation_markers(edge_color, &theme.colors.background) {
+            defs = defs.marker(marker);
+        }
+    }
+    // Crow's foot cardinality markers for ER relationships, stripped the same way when unused.
+    if ir.diagram_type == fm_core::DiagramType::Er
+        && ir.edges.iter().any(|edge| edge.er_notation().is_some())
+    {
+        for marker in er_cardinality_markers(edge_color, &theme.colors.background) {
+            defs = defs.marker(marker);
+        }
+    }
+    // Edges whose style sets a stroke color point at variants of their arrow markers drawn in
+    // that color (see `edge_stroke_color`); add one per marker and color actually referenced.
+    if !ir.style_refs.is_empty() || ir.edges.iter().any(|edge| edge.inline_style.is_some()) {
+        let mut stroke_markers = BTreeSet::new();
+        for edge_path in &layout.edges {
+            let Some(edge) = ir.edges.get(edge_path.edge_index) else {
+                continue;
+            };
+            if edge.class_relation().is_some() || edge.er_notation().is_some() {
+                continue;
+            }
+            let Some(color) = edge_stroke_color(ir, edge_path.edge_index) else {
+                continue;
+            };
+            let (_, start, end) = if edge_path.reversed {
+                (None, None, Some("url(#arrow-open)"))
+            } else {
+                arrow_markers(edge.arrow)
+            };
+            for url in [start, end].into_iter().flatten() {
+                let base = url.trim_start_matches("url(#").trim_end_matches(')');
+                stroke_markers.insert((base, color.clone()));
+            }
+        }
+        for (base, color) in &stroke_markers {
+            if let Some(marker) = arrow_marker(base, &stroke_colored_marker_id(base, color), color)
+            {
+                defs = defs.marker(marker);
+            }
+        }
+    }
 
     // Add drop shadow filter if enabled. Skip the `<filter id="drop-shadow">` def when the theme
     // CSS is embedded: its only referrer is the inline `filter="url(#drop-shadow)"` on node shapes,
@@ -2587,6 +2877,9 @@ fn render_layout_to_svg(
         if !classdef_css.is_empty() {
             css.push_str(&classdef_css);
         }
+        if config.interactive {
+            css.push_str(interactive::INTERACTIVE_CSS);
+        }
 
         doc = doc.style(css);
     } else {
@@ -2607,10 +2900,16 @@ fn render_layout_to_svg(
         if !classdef_css.is_empty() {
             css.push_str(&classdef_css);
         }
+        if config.interactive {
+            css.push_str(interactive::INTERACTIVE_CSS);
+        }
         if !css.is_empty() {
             doc = doc.style(css);
         }
     }
+    if config.interactive {
+        doc = doc.script(interactive::INTERACTIVE_SCRIPT);
+    }
 
     // Offset for padding
     let offset_x = padding - layout.bounds.x;
@@ -2702,6 +3001,21 @@ fn render_layout_to_svg(
         doc = doc.child(Element::raw_svg(ticks_svg));
     }
 
+    // Git graph branch lanes, drawn under the commits in each branch's accent color.
+    for lane in &layout.extensions.git_lanes {
+        doc = doc.child(
+            Element::line()
+                .x1(lane.start.x + offset_x)
+                .y1(lane.start.y + offset_y)
+                .x2(lane.end.x + offset_x)
+                .y2(lane.end.y + offset_y)
+                .stroke(&theme.colors.accents[lane.color_index % theme.colors.accents.len()])
+                .stroke_width(2.0)
+                .class("fm-git-lane")
+                .data("branch", &lane.branch),
+        );
+    }
+
     // Render sequence diagram activation bars.
     for bar in &layout.extensions.activation_bars {
         let mut rect = Element::rect()
@@ -2848,8 +3162,9 @@ fn render_layout_to_svg(
             format!("{kind_label} [{}]", fragment.label)
         };
 
-        // Label background tab.
-        let label_width = label_text.len() as f32 * config.avg_char_width + 16.0;
+        // Label background tab, with a tenth extra for the bold weight.
+        let label_width =
+            TextMeasure::new(config).width(&label_text, config.font_size * 0.75) * 1.1 + 16.0;
         let label_height = config.font_size + 8.0;
         doc = doc.child(
             Element::rect()
@@ -2887,6 +3202,8 @@ fn render_layout_to_svg(
             .unwrap_or(std::cmp::Ordering::Equal)
     });
 
+    let title_measure = TextMeasure::new(config);
+    let mut cluster_layer = Vec::new();
     for (_sort_idx, cluster) in sorted_clusters {
         let ir_cluster = ir.clusters.get(cluster.cluster_index);
 
@@ -2912,44 +3229,22 @@ fn render_layout_to_svg(
             || ir.diagram_type.as_str() == "gantt"
             || ir.diagram_type.as_str() == "kanban";
 
-        // Configure styling based on cluster type
-        let (fill_color, stroke_color, stroke_style, label_color) = if is_c4_boundary {
-            // C4 boundaries: dashed gray border, very light gray fill
-            (
-                "rgba(128,128,128,0.05)".to_string(),
-                "#888".to_string(),
-                Some("4,2"),
-                "#555".to_string(),
-            )
-        } else if is_swimlane {
-            // Swimlanes: solid subtle border, alternating translucent fill
-            (
-                "rgba(200,220,240,0.15)".to_string(),
-                "#b8c9db".to_string(),
-                None,
-                "#4a6785".to_string(),
-            )
+        // Clusters take the theme's cluster colors, the same ones the embedded `.fm-cluster*`
+        // rules read through `--fm-cluster-fill` / `--fm-cluster-stroke`; C4 boundaries are dashed.
+        let colors = &theme.colors;
+        let stroke_style = is_c4_boundary.then_some("4,2");
+        let (fill_color, stroke_color) = if is_c4_boundary || is_swimlane {
+            (colors.cluster_fill.clone(), colors.cluster_stroke.clone())
         } else if let Some(color) = cluster.color.as_deref().and_then(sanitize_svg_paint) {
-            let fill_color = if color == "transparent" {
-                "transparent".to_string()
-            } else {
-                color.clone()
-            };
-            let stroke_color = if color == "transparent" {
-                "#dee2e6".to_string()
+            if color == "transparent" {
+                (color, colors.cluster_stroke.clone())
             } else {
-                color
-            };
-            (fill_color, stroke_color, None, "#6c757d".to_string())
+                (color.clone(), color)
+            }
         } else {
-            // Standard clusters: translucent fill, subtle border
-            (
-                "rgba(248,249,250,0.85)".to_string(),
-                "#dee2e6".to_string(),
-                None,
-                "#6c757d".to_string(),
-            )
+            (colors.cluster_fill.clone(), colors.cluster_stroke.clone())
         };
+        let label_color = &colors.text;
 
         let mut rect = Element::rect()
             .id(&mermaid_cluster_element_id(cluster.cluster_index))
@@ -2987,7 +3282,7 @@ fn render_layout_to_svg(
             rect = apply_span_metadata(rect, cluster.span);
         }
 
-        doc = doc.child(rect);
+        cluster_layer.push(rect);
 
         // Cluster label if present
         if detail.show_cluster_labels && !title_text.is_empty() {
@@ -3020,13 +3315,24 @@ fn render_layout_to_svg(
                 title_text.to_string()
             };
 
+            // Titles wider than their cluster are cut to one line with the shared wrap engine.
+            let display_title = title_measure
+                .wrap(
+                    &display_title,
+                    cluster.bounds.width - 16.0,
+                    detail.cluster_font_size,
+                    config.wrap_mode,
+                    1,
+                )
+                .swap_remove(0);
+
             if !display_title.is_empty() {
                 let text = TextBuilder::new(&display_title)
                     .x(cluster.bounds.x + offset_x + 8.0)
                     .y(cluster.bounds.y + offset_y + 16.0)
                     .font_family_unless_embedded_css(&config.font_family, config.embed_theme_css)
                     .font_size(detail.cluster_font_size)
-                    .fill(&label_color)
+                    .fill(label_color)
                     .class("fm-cluster-label")
                     .build();
                 let text = if config.include_source_spans {
@@ -3034,7 +3340,7 @@ fn render_layout_to_svg(
                 } else {
                     text
                 };
-                doc = doc.child(text);
+                cluster_layer.push(text);
             }
         }
     }
@@ -3058,7 +3364,64 @@ fn render_layout_to_svg(
             line = apply_span_metadata(line, cluster_span);
         }
 
-        doc = doc.child(line);
+        cluster_layer.push(line);
+    }
+    doc = push_layer(doc, SvgLayer::Clusters, cluster_layer, config.layers);
+
+    // Node notes: yellow sticky notes beside their node, joined to it by a dashed leader.
+    for note in &layout.extensions.node_notes {
+        doc = doc.child(
+            Element::line()
+                .x1(note.leader_from.x + offset_x)
+                .y1(note.leader_from.y + offset_y)
+                .x2(note.leader_to.x + offset_x)
+                .y2(note.leader_to.y + offset_y)
+                .stroke(NODE_NOTE_STROKE)
+                .stroke_width(1.0)
+                .stroke_dasharray("4,3")
+                .class("fm-note-leader"),
+        );
+
+        let nx = note.bounds.x + offset_x;
+        let ny = note.bounds.y + offset_y;
+        let nw = note.bounds.width;
+        let nh = note.bounds.height;
+        let fold = 8.0_f32.min(nw / 2.0).min(nh / 2.0);
+        let path = PathBuilder::new()
+            .move_to(nx, ny)
+            .line_to(nx + nw - fold, ny)
+            .line_to(nx + nw, ny + fold)
+            .line_to(nx + nw, ny + nh)
+            .line_to(nx, ny + nh)
+            .close()
+            .move_to(nx + nw - fold, ny)
+            .line_to(nx + nw - fold, ny + fold)
+            .line_to(nx + nw, ny + fold)
+            .build();
+        doc = doc.child(
+            Element::path()
+                .d(&path)
+                .fill(NODE_NOTE_FILL)
+                .stroke(NODE_NOTE_STROKE)
+                .stroke_width(1.0)
+                .class("fm-note"),
+        );
+
+        if !note.text.is_empty() {
+            doc = doc.child(
+                TextBuilder::new(&note.text)
+                    .x(nx + 10.0)
+                    .y(ny + 10.0)
+                    .font_family_unless_embedded_css(&config.font_family, config.embed_theme_css)
+                    .font_size(config.font_size)
+                    .line_height(config.line_height)
+                    .baseline(text::DominantBaseline::Hanging)
+                    .anchor(TextAnchor::Start)
+                    .fill("#333333")
+                    .class("fm-note-text")
+                    .build(),
+            );
+        }
     }
 
     // Build centrality tier lookup map for O(1) access during node rendering. Hoisted above the
@@ -3070,6 +3433,8 @@ fn render_layout_to_svg(
         .map(|nc| (nc.node_index, nc.tier))
         .collect();
 
+    let label_metrics = config.font_metrics();
+    let edge_ids = ir.edge_element_ids();
     let edge_context = EdgeRenderContext {
         ir,
         offset_x,
@@ -3078,6 +3443,9 @@ fn render_layout_to_svg(
         detail,
         colors: &theme.colors,
         accessible_node_labels: accessible_node_labels.as_deref(),
+        node_boxes: &layout.nodes,
+        label_metrics: &label_metrics,
+        edge_ids: &edge_ids,
     };
 
     // Fast path for the common case: a diagram small enough that both loops render serially AND for
@@ -3101,8 +3469,10 @@ fn render_layout_to_svg(
     // in byte-identical order, so they no longer disqualify a diagram. This pulls ER, class-relation, and
     // sequence diagrams (previously always slow-path) onto the streaming path — killing the second copy of
     // `edge_svg`+`cardinality_svg`+`node_svg`+mirror-header fragments.
+    // Layered output lifts labels out of their edge and node groups, which only the `Element`
+    // paths allow.
     let no_between_or_after_children =
-        !legend_enabled && layout.edges.iter().all(|edge| edge.bundle_count <= 1);
+        !legend_enabled && !config.layers && layout.edges.iter().all(|edge| edge.bundle_count <= 1);
     #[cfg(not(target_arch = "wasm32"))]
     let stream_fast_path =
         no_between_or_after_children && layout.edges.len() < 4096 && layout.nodes.len() < 2048;
@@ -3167,7 +3537,6 @@ fn render_layout_to_svg(
                 )
                 .id(&mermaid_node_element_id_with_variant(
                     &node_box.node_id,
-                    node_box.node_index,
                     Some("mirror-header"),
                 ))
                 .class("fm-sequence-mirror-header")
@@ -3176,6 +3545,28 @@ fn render_layout_to_svg(
         });
     }
 
+    if config.layers {
+        doc = render_graph_layers(
+            doc,
+            layout,
+            &edge_context,
+            emit_classdef_classes,
+            &centrality_map,
+        );
+        if legend_enabled {
+            doc = doc.child(render_c4_legend(
+                ir,
+                padding,
+                layout.bounds.height + padding + 18.0,
+                width - (padding * 2.0),
+                legend_height - 18.0,
+                config,
+                &theme.colors,
+            ));
+        }
+        return finish_layout_svg_document(doc, ir, layout);
+    }
+
     // Render edges (skip edges absorbed into bundles). Edge subtrees are serialized immediately
     // and inserted as one internal raw fragment so the root document does not retain thousands of
     // short-lived edge element trees until final serialization.
@@ -3239,23 +3630,10 @@ fn render_layout_to_svg(
 
     // Render bundle count labels for bundled edges (e.g., "×3").
     for edge_path in &layout.edges {
-        if edge_path.bundle_count > 1 && edge_path.points.len() >= 2 {
-            let mid_idx = edge_path.points.len() / 2;
-            let mid_pt = &edge_path.points[mid_idx];
-            let label = format!("\u{00d7}{}", edge_path.bundle_count);
-            doc = doc.child(
-                Element::text()
-                    .x(mid_pt.x + offset_x + 6.0)
-                    .y(mid_pt.y + offset_y - 12.0)
-                    .content(&label)
-                    .attr("text-anchor", "start")
-                    .attr("dominant-baseline", "auto")
-                    .attr_num("font-size", config.font_size * 0.65)
-                    .font_family_unless_embedded_css(&config.font_family, config.embed_theme_css)
-                    .fill(&theme.colors.edge)
-                    .attr("fill-opacity", "0.7")
-                    .class("fm-bundle-count"),
-            );
+        if let Some(label) =
+            bundle_count_label(edge_path, offset_x, offset_y, config, &theme.colors)
+        {
+            doc = doc.child(label);
         }
     }
 
@@ -3406,7 +3784,6 @@ fn render_layout_to_svg(
         )
         .id(&mermaid_node_element_id_with_variant(
             &node_box.node_id,
-            node_box.node_index,
             Some("mirror-header"),
         ));
         doc = doc.child(node_elem.class("fm-sequence-mirror-header"));
@@ -3427,42 +3804,217 @@ fn render_layout_to_svg(
     finish_layout_svg_document(doc, ir, layout)
 }
 
-fn finish_layout_svg_document(
+/// Add `children` to the document, wrapped in the layer's group when `layered`. Label layers
+/// ignore the pointer so clicks reach the shapes and edges under them.
+fn push_layer(
     doc: SvgDocument,
-    ir: &MermaidDiagramIr,
-    layout: &DiagramLayout,
-) -> String {
-    doc.to_string_with_capacity(layout_svg_capacity_hint(ir, layout))
-}
-
-fn build_accessible_node_label_cache(ir: &MermaidDiagramIr) -> Vec<&str> {
-    ir.nodes
-        .iter()
-        .map(|node| crate::a11y::accessible_node_label(node, ir))
-        .collect()
-}
-
-fn layout_svg_capacity_hint(ir: &MermaidDiagramIr, layout: &DiagramLayout) -> usize {
-    const BASE_DOCUMENT_BYTES: usize = 16 * 1024;
-    const NODE_BYTES: usize = 768;
-    const EDGE_BYTES: usize = 384;
-    const CLUSTER_BYTES: usize = 512;
-    const AUXILIARY_ITEM_BYTES: usize = 192;
-
-    let auxiliary_items = layout.extensions.bands.len()
-        + layout.extensions.axis_ticks.len()
-        + layout.extensions.activation_bars.len()
-        + layout.extensions.sequence_lifecycle_markers.len()
-        + layout.extensions.sequence_notes.len()
-        + layout.extensions.sequence_fragments.len()
-        + layout.extensions.cluster_dividers.len()
-        + layout.extensions.sequence_mirror_headers.len();
-
-    BASE_DOCUMENT_BYTES
-        + ir.nodes.len().saturating_mul(NODE_BYTES)
-        + layout.edges.len().saturating_mul(EDGE_BYTES)
-        + layout.clusters.len().saturating_mul(CLUSTER_BYTES)
-        + auxiliary_items.saturating_mul(AUXILIARY_ITEM_BYTES)
+    layer: SvgLayer,
+    children: Vec<Element>,
+    layered: bool,
+) -> SvgDocument {
+    if !layered {
+        return doc.children(children);
+    }
+    let mut group = Element::group().id(layer.element_id());
+    if matches!(layer, SvgLayer::EdgeLabels | SvgLayer::NodeLabels) {
+        group = group.attr("pointer-events", "none");
+    }
+    doc.child(group.children(children))
+}
+
+/// Edges, edge labels, nodes and node labels as four layer groups. Every edge and node takes the
+/// `Element` path so its label can be lifted out of its group; the label keeps the owner's
+/// `data-fm-edge-id` or `data-id` on a wrapper whose id is the owner's with a `-label` suffix.
+fn render_graph_layers(
+    mut doc: SvgDocument,
+    layout: &DiagramLayout,
+    edge_context: &EdgeRenderContext<'_>,
+    emit_classdef_classes: bool,
+    centrality_map: &HashMap<usize, CentralityTier>,
+) -> SvgDocument {
+    let EdgeRenderContext {
+        ir,
+        offset_x,
+        offset_y,
+        config,
+        detail,
+        colors,
+        ..
+    } = *edge_context;
+
+    let mut edges = Vec::with_capacity(layout.edges.len());
+    let mut edge_labels = Vec::new();
+    for edge_path in layout.edges.iter().filter(|edge_path| !edge_path.bundled) {
+        let mut edge = render_edge(edge_path, edge_context);
+        let label = edge
+            .take_descendants(&|elem| matches!(elem.kind(), ElementKind::Rect | ElementKind::Text));
+        if !label.is_empty() {
+            let owner_id = edge_context.edge_element_id(edge_path.edge_index);
+            edge_labels.push(
+                Element::group()
+                    .id(&format!("{owner_id}-label"))
+                    .class("fm-edge-label")
+                    .attr_int("data-fm-edge-id", edge_path.edge_index as i32)
+                    .children(label),
+            );
+        }
+        edges.push(edge);
+    }
+    edge_labels.extend(
+        layout.edges.iter().filter_map(|edge_path| {
+            bundle_count_label(edge_path, offset_x, offset_y, config, colors)
+        }),
+    );
+    let mut cardinality_svg = String::new();
+    if ir.diagram_type == fm_core::DiagramType::Er {
+        write_er_cardinality_labels_into(
+            &mut cardinality_svg,
+            ir,
+            layout,
+            offset_x,
+            offset_y,
+            config,
+            colors,
+        );
+    }
+    write_class_cardinality_labels_into(
+        &mut cardinality_svg,
+        ir,
+        layout,
+        offset_x,
+        offset_y,
+        config,
+        colors,
+    );
+    if !cardinality_svg.is_empty() {
+        edge_labels.push(Element::raw_svg(cardinality_svg));
+    }
+
+    let render = |node_box: &LayoutNodeBox| {
+        render_node(
+            node_box,
+            ir,
+            offset_x,
+            offset_y,
+            config,
+            detail,
+            colors,
+            emit_classdef_classes,
+            centrality_map,
+            false,
+        )
+    };
+    let rendered_nodes = layout
+        .nodes
+        .iter()
+        .map(|node_box| (node_box, "label", render(node_box)))
+        .chain(
+            layout
+                .extensions
+                .sequence_mirror_headers
+                .iter()
+                .map(|node_box| {
+                    let header = render(node_box)
+                        .id(&mermaid_node_element_id_with_variant(
+                            &node_box.node_id,
+                            Some("mirror-header"),
+                        ))
+                        .class("fm-sequence-mirror-header");
+                    (node_box, "mirror-header-label", header)
+                }),
+        );
+    let mut nodes = Vec::with_capacity(layout.nodes.len());
+    let mut node_labels = Vec::new();
+    for (node_box, label_variant, mut node) in rendered_nodes {
+        let label =
+            node.take_descendants(&|elem| elem.kind() == ElementKind::Text || elem.is_raw_text());
+        if !label.is_empty() {
+            node_labels.push(
+                Element::group()
+                    .id(&mermaid_node_element_id_with_variant(
+                        &node_box.node_id,
+                        Some(label_variant),
+                    ))
+                    .class("fm-node-label-group")
+                    .attr("data-id", &node_box.node_id)
+                    .children(label),
+            );
+        }
+        nodes.push(node);
+    }
+
+    doc = push_layer(doc, SvgLayer::Edges, edges, true);
+    doc = push_layer(doc, SvgLayer::EdgeLabels, edge_labels, true);
+    doc = push_layer(doc, SvgLayer::Nodes, nodes, true);
+    push_layer(doc, SvgLayer::NodeLabels, node_labels, true)
+}
+
+/// The "×N" count drawn beside a bundled edge.
+fn bundle_count_label(
+    edge_path: &LayoutEdgePath,
+    offset_x: f32,
+    offset_y: f32,
+    config: &SvgRenderConfig,
+    colors: &ThemeColors,
+) -> Option<Element> {
+    if edge_path.bundle_count <= 1 || edge_path.points.len() < 2 {
+        return None;
+    }
+    let mid_pt = &edge_path.points[edge_path.points.len() / 2];
+    let label = format!("\u{00d7}{}", edge_path.bundle_count);
+    Some(
+        Element::text()
+            .x(mid_pt.x + offset_x + 6.0)
+            .y(mid_pt.y + offset_y - 12.0)
+            .content(&label)
+            .attr("text-anchor", "start")
+            .attr("dominant-baseline", "auto")
+            .attr_num("font-size", config.font_size * 0.65)
+            .font_family_unless_embedded_css(&config.font_family, config.embed_theme_css)
+            .fill(&colors.edge)
+            .attr("fill-opacity", "0.7")
+            .class("fm-bundle-count"),
+    )
+}
+
+fn finish_layout_svg_document(
+    doc: SvgDocument,
+    ir: &MermaidDiagramIr,
+    layout: &DiagramLayout,
+) -> String {
+    doc.to_string_with_capacity(layout_svg_capacity_hint(ir, layout))
+}
+
+fn build_accessible_node_label_cache(ir: &MermaidDiagramIr) -> Vec<&str> {
+    ir.nodes
+        .iter()
+        .map(|node| crate::a11y::accessible_node_label(node, ir))
+        .collect()
+}
+
+fn layout_svg_capacity_hint(ir: &MermaidDiagramIr, layout: &DiagramLayout) -> usize {
+    const BASE_DOCUMENT_BYTES: usize = 16 * 1024;
+    const NODE_BYTES: usize = 768;
+    const EDGE_BYTES: usize = 384;
+    const CLUSTER_BYTES: usize = 512;
+    const AUXILIARY_ITEM_BYTES: usize = 192;
+
+    let auxiliary_items = layout.extensions.bands.len()
+        + layout.extensions.axis_ticks.len()
+        + layout.extensions.activation_bars.len()
+        + layout.extensions.sequence_lifecycle_markers.len()
+        + layout.extensions.sequence_notes.len()
+        + layout.extensions.node_notes.len() * 3
+        + layout.extensions.sequence_fragments.len()
+        + layout.extensions.cluster_dividers.len()
+        + layout.extensions.sequence_mirror_headers.len()
+        + layout.extensions.git_lanes.len();
+
+    BASE_DOCUMENT_BYTES
+        + ir.nodes.len().saturating_mul(NODE_BYTES)
+        + layout.edges.len().saturating_mul(EDGE_BYTES)
+        + layout.clusters.len().saturating_mul(CLUSTER_BYTES)
+        + auxiliary_items.saturating_mul(AUXILIARY_ITEM_BYTES)
 }
 
 /// Stream a layout band (`<g class="fm-band fm-band-…"><rect/>[<text>label</text>]</g>`) directly into
@@ -3874,20 +4426,13 @@ fn render_quadrant_svg(
     config: &SvgRenderConfig,
     theme: &Theme,
 ) -> SvgDocument {
-    // Replicate the exact chart dimensions from the layout engine so axes align
-    // with the node positions computed by layout_diagram_quadrant_traced().
-    let metrics = fm_core::FontMetrics::default_metrics();
-    let node_count = layout.nodes.len();
-    let base_size = 300.0_f32 + (node_count as f32 * 15.0).min(200.0);
-    let chart_w = base_size.clamp(200.0, 600.0);
-    let chart_h = chart_w;
-    let axis_label_width = quad_meta
-        .x_axis_left
-        .as_ref()
-        .map(|label| metrics.estimate_dimensions(label).0)
-        .unwrap_or(0.0);
-    let margin_left = (axis_label_width + 20.0).clamp(50.0, 120.0) + offset_x;
-    let margin_top = 60.0_f32 + offset_y;
+    // The plot area comes from the layout's chart geometry, so axes align with the point
+    // positions.
+    let plot = fm_layout::chart::layout_quadrant(ir).plot;
+    let chart_w = plot.width;
+    let chart_h = plot.height;
+    let margin_left = plot.x + offset_x;
+    let margin_top = plot.y + offset_y;
 
     let quadrant_fills: [&str; 4] = [
         &theme.colors.accents[0 % theme.colors.accents.len()],
@@ -4078,8 +4623,7 @@ fn render_quadrant_svg(
         );
     }
 
-    // Data points.
-    let accent_colors: Vec<&str> = theme.colors.accents.iter().map(String::as_str).collect();
+    // Data points, each in the color of the quadrant it falls in.
     // Stream all data points (circle + label) into one raw fragment under embedded CSS — the label's only
     // config-dependent attribute (`font-family`) is then CSS-driven/absent, so the whole point stack is a
     // fixed set of bytes. Skips two `Element` builds + `Attributes` Vecs per point. Byte-identical; the
@@ -4089,7 +4633,7 @@ fn render_quadrant_svg(
         for (i, node_box) in layout.nodes.iter().enumerate() {
             let cx = node_box.bounds.x + node_box.bounds.width / 2.0 + offset_x;
             let cy = node_box.bounds.y + node_box.bounds.height / 2.0 + offset_y;
-            let color = accent_colors[i % accent_colors.len()];
+            let color = quadrant_fills[quadrant_index(quad_meta.points.get(i), i)];
             let label = quad_meta
                 .points
                 .get(i)
@@ -4117,7 +4661,7 @@ fn render_quadrant_svg(
     for (i, node_box) in layout.nodes.iter().enumerate() {
         let cx = node_box.bounds.x + node_box.bounds.width / 2.0 + offset_x;
         let cy = node_box.bounds.y + node_box.bounds.height / 2.0 + offset_y;
-        let color = accent_colors[i % accent_colors.len()];
+        let color = quadrant_fills[quadrant_index(quad_meta.points.get(i), i)];
         doc = doc.child(
             Element::circle()
                 .cx(cx)
@@ -4150,6 +4694,21 @@ fn render_quadrant_svg(
     doc
 }
 
+/// Index of the quadrant `point` falls in, in `quadrant_labels` order: Q1 top-right, Q2 top-left,
+/// Q3 bottom-left, Q4 bottom-right. Points on a midline go to the right or top. A node without
+/// point data cycles through the quadrants by `index`.
+fn quadrant_index(point: Option<&fm_core::IrQuadrantPoint>, index: usize) -> usize {
+    match point {
+        Some(point) => match (point.x >= 0.5, point.y >= 0.5) {
+            (true, true) => 0,
+            (false, true) => 1,
+            (false, false) => 2,
+            (true, false) => 3,
+        },
+        None => index % 4,
+    }
+}
+
 /// Stream a quadrant data point (`<circle>` + `<text>` label) byte-identical to the slow path's
 /// `Element`s under embedded CSS (the label's `font-family` is CSS-driven, so absent inline). `r="6"` /
 /// `stroke-width="1.50"` are the fixed `r(6.0)`/`stroke_width(1.5)` serializations. Skips the two per-point
@@ -4508,6 +5067,10 @@ fn write_pie_text_into(
     f.push_str("</text>");
 }
 
+/// Smallest share of a pie whose wedge is labeled with its percentage; thinner wedges cannot
+/// hold the text.
+const PIE_PERCENT_MIN_FRACTION: f32 = 0.04;
+
 #[allow(clippy::too_many_arguments)]
 fn render_pie_svg(
     mut doc: SvgDocument,
@@ -4523,11 +5086,13 @@ fn render_pie_svg(
 
     let bounds = &layout.bounds;
     let accent_colors: Vec<&str> = theme.colors.accents.iter().map(String::as_str).collect();
+    let measure = TextMeasure::new(config);
+    let entry_font_size = clamp_font_size(config.font_size * 0.8, config.min_font_size);
     let legend_label_width = pie_meta
         .slices
         .iter()
         .map(|slice| {
-            (slice.label.chars().count() as f32) * (config.avg_char_width * 0.9)
+            measure.width(&slice.label, entry_font_size)
                 + if pie_meta.show_data { 88.0 } else { 0.0 }
         })
         .fold(0.0_f32, f32::max);
@@ -4547,12 +5112,9 @@ fn render_pie_svg(
     let cy = chart_top + chart_height / 2.0;
     let radius = (chart_width.min(chart_height) / 2.0 - 36.0).max(40.0);
 
-    let total: f32 = pie_meta
-        .slices
-        .iter()
-        .map(|s| s.value.max(0.0))
-        .sum::<f32>()
-        .max(f32::EPSILON);
+    // Wedge angles and shares come from the layout's chart geometry; only the centre and radius
+    // are fitted to the space left beside the legend.
+    let pie = fm_layout::chart::layout_pie(ir);
 
     // Stream the whole pie (title + per-slice wedge+label + legend group) into ONE raw fragment instead
     // of ~4 `Element`s per slice + the legend group/box/title. Byte-identical: same element bytes/attr
@@ -4591,7 +5153,6 @@ fn render_pie_svg(
     use std::fmt::Write as _;
     let pie_head = format!("M {cx} {cy} L ");
     let pie_arc = format!(" A {radius} {radius} 0 ");
-    let mut angle = -PI / 2.0;
     // A normal wedge's END point (at `angle + sweep`) is the NEXT wedge's START point (at the
     // next `angle`, since `angle += sweep` below) — bit-for-bit the same float, so its
     // Grisu-formatted `"{x} {y}"` text is identical. Cache it and reuse it as the next wedge's
@@ -4600,9 +5161,10 @@ fn render_pie_svg(
     // emit no boundary point yet still advance `angle`, so they clear the cache. One reused buffer.
     let mut prev_end_point = String::new();
     let mut have_prev_end = false;
-    for (i, slice) in pie_meta.slices.iter().enumerate() {
-        let value = slice.value.max(0.0);
-        let sweep = (value / total) * 2.0 * PI;
+    for (i, (slice, wedge)) in pie_meta.slices.iter().zip(&pie.slices).enumerate() {
+        let value = wedge.value;
+        let angle = wedge.start_angle;
+        let sweep = wedge.sweep();
         let color = accent_colors[i % accent_colors.len()];
 
         if value <= f32::EPSILON {
@@ -4624,8 +5186,8 @@ fn render_pie_svg(
             pie_svg.push_str("\" stroke-width=\"2\" class=\"fm-pie-slice fm-pie-slice-full\"/>");
             have_prev_end = false;
         } else {
-            let x2 = cx + radius * (angle + sweep).cos();
-            let y2 = cy + radius * (angle + sweep).sin();
+            let x2 = cx + radius * wedge.end_angle.cos();
+            let y2 = cy + radius * wedge.end_angle.sin();
             let large_arc = i32::from(sweep > PI);
             pie_svg.push_str("<path d=\"");
             pie_svg.push_str(&pie_head);
@@ -4654,11 +5216,35 @@ fn render_pie_svg(
             pie_svg.push_str("\" stroke-width=\"2\" class=\"fm-pie-slice\"/>");
         }
 
-        let mid_angle = angle + sweep / 2.0;
+        let mid_angle = wedge.mid_angle();
+        // The slice's share, printed inside the wedge when the wedge is wide enough to hold it.
+        if wedge.fraction >= PIE_PERCENT_MIN_FRACTION {
+            // A full circle has no wedge to sit in; its share goes in the middle.
+            let percent_radius = if (sweep - 2.0 * PI).abs() <= 0.0001 {
+                0.0
+            } else {
+                radius * 0.62
+            };
+            write_pie_text_into(
+                &mut pie_svg,
+                cx + percent_radius * mid_angle.cos(),
+                cy + percent_radius * mid_angle.sin(),
+                "middle",
+                true,
+                family,
+                embed,
+                clamp_font_size(config.font_size * 0.8, config.min_font_size),
+                true,
+                bg,
+                "fm-pie-percent",
+                &format!("{:.0}%", wedge.fraction * 100.0),
+            );
+        }
+
         let label_radius = radius + 24.0;
         let lx = cx + label_radius * mid_angle.cos();
         let ly = cy + label_radius * mid_angle.sin();
-        let pct = (value / total) * 100.0;
+        let pct = wedge.fraction * 100.0;
         let label_text = if pie_meta.show_data {
             format!("{}: {:.0} ({:.1}%)", slice.label, value, pct)
         } else {
@@ -4685,7 +5271,6 @@ fn render_pie_svg(
             "fm-pie-label",
             &label_text,
         );
-        angle += sweep;
     }
 
     let legend_x = chart_left + chart_width + chart_gap;
@@ -4722,14 +5307,14 @@ fn render_pie_svg(
         "Legend",
     );
 
-    for (index, slice) in pie_meta.slices.iter().enumerate() {
+    for (index, wedge) in pie.slices.iter().enumerate() {
         let row_y = legend_y + 34.0 + index as f32 * 24.0;
         let color = accent_colors[index % accent_colors.len()];
-        let pct = (slice.value.max(0.0) / total) * 100.0;
+        let pct = wedge.fraction * 100.0;
         let entry_label = if pie_meta.show_data {
-            format!("{}: {:.0} ({:.1}%)", slice.label, slice.value.max(0.0), pct)
+            format!("{}: {:.0} ({:.1}%)", wedge.label, wedge.value, pct)
         } else {
-            slice.label.clone()
+            wedge.label.clone()
         };
         pie_svg.push_str("<rect x=\"");
         let _ = crate::attributes::write_number_into(&mut pie_svg, legend_x + 14.0);
@@ -4748,7 +5333,7 @@ fn render_pie_svg(
             true,
             family,
             embed,
-            clamp_font_size(config.font_size * 0.8, config.min_font_size),
+            entry_font_size,
             false,
             text_fill,
             "fm-pie-legend-entry",
@@ -5536,6 +6121,12 @@ fn write_class_compartments_into(
         );
         cursor_y += line_h;
     }
+    // Abstract class names are italic as well as bold, as in UML.
+    let name_extra = if is_abstract_class(meta) {
+        " font-weight=\"bold\" font-style=\"italic\""
+    } else {
+        " font-weight=\"bold\""
+    };
     // No-generics name is written directly (the slow path's `class_name.to_string()` copy is avoided).
     if meta.generics.is_empty() {
         write_class_text_into(
@@ -5544,7 +6135,7 @@ fn write_class_compartments_into(
             cursor_y,
             "middle",
             font_size,
-            " font-weight=\"bold\"",
+            name_extra,
             fill,
             class_name,
         );
@@ -5556,7 +6147,7 @@ fn write_class_compartments_into(
             cursor_y,
             "middle",
             font_size,
-            " font-weight=\"bold\"",
+            name_extra,
             fill,
             &display_name,
         );
@@ -5570,68 +6161,49 @@ fn write_class_compartments_into(
         if cursor_y > y + h - line_h * 0.5 {
             break;
         }
-        let vis = visibility_symbol(attr.visibility);
-        let text = if let Some(ref ret) = attr.return_type {
-            format!("{vis}{}: {ret}", attr.name)
-        } else {
-            format!("{vis}{}", attr.name)
-        };
         write_class_text_into(
             f,
             text_x,
             cursor_y,
             "start",
             member_font_size,
-            "",
+            class_member_extra(attr),
             fill,
-            &text,
+            &class_member_text(attr),
         );
     }
-    if !meta.attributes.is_empty() && !meta.methods.is_empty() {
-        cursor_y += line_h * 0.3;
-        write_class_separator_into(f, x, cursor_y, x + w);
-        cursor_y += line_h * 0.3;
-    }
+    // The methods compartment is always separated, even when one of the two is empty.
+    cursor_y += line_h * 0.3;
+    write_class_separator_into(f, x, cursor_y, x + w);
+    cursor_y += line_h * 0.3;
     for method in &meta.methods {
         cursor_y += member_font_size * config.line_height * 0.9;
         if cursor_y > y + h - line_h * 0.5 {
             break;
         }
-        let vis = visibility_symbol(method.visibility);
-        let suffix = if method.is_abstract {
-            "*"
-        } else if method.is_static {
-            "$"
-        } else {
-            ""
-        };
-        let ret = method
-            .return_type
-            .as_deref()
-            .map(|t| format!(": {t}"))
-            .unwrap_or_default();
-        let text = format!("{vis}{}{suffix}{ret}", method.name);
         write_class_text_into(
             f,
             text_x,
             cursor_y,
             "start",
             member_font_size,
-            "",
+            class_member_extra(method),
             fill,
-            &text,
+            &class_member_text(method),
         );
     }
 }
 
-/// Stream an ER entity's body (name header `<text>` + divider `<line>` + one `<text>` per attribute)
-/// byte-identical to what `render_node`'s ER branch builds via `Element`s under embedded CSS with no
-/// per-label style and no classdef class. Attrs replicate that branch's builder call order exactly:
-/// name/attr `<text>` carry `x, y, text-anchor, dominant-baseline, font-size, font-weight, fill, class`
-/// (font-family is embedded-CSS-driven, so absent); the divider `<line>` carries `x1, y1, x2, y2,
-/// stroke-width` (stroke is CSS-driven, so absent). The per-attribute content `{key_prefix}{data_type}
-/// {name}` streams in pieces instead of a `format!` temp — byte-identical because `write_escaped_text`
-/// escapes per char and `key_prefix`/the separating space hold no XML specials. Used only via the ER
+/// Stream an ER entity's body (name header `<text>` + divider `<line>` + one row per attribute: a band
+/// `<rect>` on every other row, a key badge `<rect>` + `<text>` for keyed attributes, and the attribute
+/// `<text>`) byte-identical to what `render_node`'s ER branch builds via `Element`s under embedded CSS
+/// with no per-label style and no classdef class. Attrs replicate that branch's builder call order
+/// exactly: name/badge/attr `<text>` carry `x, y, text-anchor, dominant-baseline, font-size,
+/// font-weight, fill, class` (font-family is embedded-CSS-driven, so absent); the divider `<line>`
+/// carries `x1, y1, x2, y2, stroke-width` (stroke is CSS-driven, so absent); band and badge `<rect>`s
+/// carry `x, y, width, height, [rx,] style, class`. The per-attribute content `{data_type} {name}`
+/// streams in pieces instead of a `format!` temp — byte-identical because `write_escaped_text`
+/// escapes per char and the separating space is no XML special. Used only via the ER
 /// branch's fast path, mirroring [`write_class_compartments_into`]; replaces ~2 + N `Element`s per
 /// entity (~400 for a 40-entity diagram) with one raw fragment.
 #[allow(clippy::too_many_arguments)]
@@ -5677,22 +6249,57 @@ fn write_er_entity_into(
     // `stroke_width(0.8)` serializes via `write_number_into` -> two decimals ("0.80"), NOT "0.8".
     f.push_str("\" stroke-width=\"0.80\"/>");
 
-    // Attribute list.
+    // Attribute rows: every other row banded, keys as badges in a column of their own.
+    let row_pitch = attr_font_size * 1.3;
+    let badge_width = attr_font_size * ER_KEY_BADGE_WIDTH;
+    let badge_font_size = clamp_font_size(attr_font_size * 0.75, config.min_font_size);
+    let text_x = er_attribute_text_x(node, x, badge_width);
+    let band_style = er_row_band_style(colors);
     let mut attr_y = y + header_height + attr_font_size * 0.9;
-    for attr in &node.members {
-        let key_prefix = match attr.key {
-            fm_core::IrAttributeKey::Pk => "PK ",
-            fm_core::IrAttributeKey::Fk => "FK ",
-            fm_core::IrAttributeKey::Uk => "UK ",
-            fm_core::IrAttributeKey::None => "",
-        };
+    for (row, attr) in node.members.iter().enumerate() {
+        if row % 2 == 1 {
+            f.push_str("<rect x=\"");
+            let _ = write_number_into(f, x + 1.0);
+            f.push_str("\" y=\"");
+            let _ = write_number_into(f, attr_y - row_pitch / 2.0);
+            f.push_str("\" width=\"");
+            let _ = write_number_into(f, w - 2.0);
+            f.push_str("\" height=\"");
+            let _ = write_number_into(f, row_pitch);
+            f.push_str("\" style=\"");
+            let _ = write_escaped_attr(f, &band_style);
+            f.push_str("\" class=\"fm-er-row\"/>");
+        }
+        if let Some((badge, accent)) = er_key_badge(attr.key, colors) {
+            f.push_str("<rect x=\"");
+            let _ = write_number_into(f, x + 8.0);
+            f.push_str("\" y=\"");
+            let _ = write_number_into(f, attr_y - attr_font_size * 0.55);
+            f.push_str("\" width=\"");
+            let _ = write_number_into(f, badge_width);
+            f.push_str("\" height=\"");
+            let _ = write_number_into(f, attr_font_size * 1.1);
+            f.push_str("\" rx=\"2\" style=\"");
+            let _ = write_escaped_attr(f, &er_key_badge_style(accent));
+            f.push_str("\" class=\"fm-er-key-badge\"/><text x=\"");
+            let _ = write_number_into(f, x + 8.0 + badge_width / 2.0);
+            f.push_str("\" y=\"");
+            let _ = write_number_into(f, attr_y);
+            f.push_str("\" text-anchor=\"middle\" dominant-baseline=\"central\" font-size=\"");
+            let _ = write_number_into(f, badge_font_size);
+            f.push_str("\" font-weight=\"bold\" fill=\"");
+            let _ = write_escaped_attr(f, fill);
+            f.push_str("\" class=\"fm-er-key\">");
+            f.push_str(badge);
+            f.push_str("</text>");
+        }
         let font_weight = if attr.key == fm_core::IrAttributeKey::None {
             "normal"
         } else {
             "bold"
         };
         f.push_str("<text x=\"");
-        let _ = write_number_into(f, x + 8.0);
+        let _ = write_number_into(f, text_x);
         f.push_str("\" y=\"");
         let _ = write_number_into(f, attr_y);
         f.push_str("\" text-anchor=\"start\" dominant-baseline=\"central\" font-size=\"");
@@ -5702,16 +6309,56 @@ fn write_er_entity_into(
         f.push_str("\" fill=\"");
         let _ = write_escaped_attr(f, fill);
         f.push_str("\" class=\"fm-er-attribute\">");
-        // `attr_text = format!("{key_prefix}{data_type} {name}")`, escaped in pieces (identical bytes).
-        f.push_str(key_prefix);
+        // `format!("{data_type} {name}")`, escaped in pieces (identical bytes).
         let _ = write_escaped_text(f, &attr.data_type);
         f.push(' ');
         let _ = write_escaped_text(f, &attr.name);
         f.push_str("</text>");
-        attr_y += attr_font_size * 1.3;
+        attr_y += row_pitch;
+    }
+}
+
+/// Width of an ER key badge, in attribute font sizes.
+const ER_KEY_BADGE_WIDTH: f32 = 2.2;
+
+/// Badge text and theme accent for an ER attribute key; `None` for an attribute without one.
+fn er_key_badge(
+    key: fm_core::IrAttributeKey,
+    colors: &ThemeColors,
+) -> Option<(&'static str, &str)> {
+    match key {
+        fm_core::IrAttributeKey::Pk => Some(("PK", &colors.accents[4])),
+        fm_core::IrAttributeKey::Fk => Some(("FK", &colors.accents[1])),
+        fm_core::IrAttributeKey::Uk => Some(("UK", &colors.accents[6])),
+        fm_core::IrAttributeKey::None => None,
+    }
+}
+
+/// Left edge of an ER entity's attribute text: past the badge column when any attribute has a
+/// key, so names line up whether or not their row carries a badge.
+fn er_attribute_text_x(node: &fm_core::IrNode, x: f32, badge_width: f32) -> f32 {
+    let keyed = node
+        .members
+        .iter()
+        .any(|attr| attr.key != fm_core::IrAttributeKey::None);
+    if keyed {
+        x + 12.0 + badge_width
+    } else {
+        x + 8.0
     }
 }
 
+/// Inline style of a banded ER attribute row. Inline, like the key badge's, so the node-shape
+/// rules of the theme CSS (fill, outline, drop shadow) do not restyle it as a node outline.
+fn er_row_band_style(colors: &ThemeColors) -> String {
+    format!("fill:{};stroke:none;filter:none", colors.cluster_fill)
+}
+
+/// Inline style of an ER key badge: a light wash of `accent` inside an `accent` outline.
+fn er_key_badge_style(accent: &str) -> String {
+    format!("fill:{accent};fill-opacity:0.18;stroke:{accent};stroke-width:0.8;filter:none")
+}
+
 /// Stream a complete class-diagram node (`<g>` + gradient `<rect>` + compartment stack + `<title>`)
 /// directly into `out`, **byte-identical** to what `render_node` builds via `Element`s — the class-node
 /// analogue of [`write_common_node_fragment_into`]. The `<g>`/rect/title bytes replicate that helper's
@@ -5730,7 +6377,6 @@ fn write_class_node_fragment_into<const A11Y: bool>(
     node: &fm_core::IrNode,
     meta: &fm_core::IrClassNodeMeta,
     node_id: &str,
-    node_index: usize,
     raw_label: &str,
     ir: &MermaidDiagramIr,
     x: f32,
@@ -5746,7 +6392,7 @@ fn write_class_node_fragment_into<const A11Y: bool>(
     use crate::attributes::{write_escaped_attr, write_escaped_text};
     // <g id=".." class="fm-node fm-node-accent-N fm-node-shape-rect[ fm-node-user-…]" data-id=".." …>
     out.push_str("<g id=\"");
-    fm_core::write_mermaid_node_element_id_into(out, node_id, node_index);
+    fm_core::write_mermaid_node_element_id_into(out, node_id);
     out.push_str("\" class=\"fm-node fm-node-accent-");
     // `stable_accent_index` (small palette index) via the digit-table writer, not `write!`'s
     // Formatter/`pad_integral` machinery. Byte-identical: same decimal digits.
@@ -5801,7 +6447,6 @@ fn write_c4_node_fragment_into(
     node: &fm_core::IrNode,
     c4_meta: &fm_core::IrC4NodeMeta,
     node_id: &str,
-    node_index: usize,
     raw_label: &str,
     ir: &MermaidDiagramIr,
     x: f32,
@@ -5817,7 +6462,7 @@ fn write_c4_node_fragment_into(
     use crate::attributes::{write_escaped_attr, write_escaped_text, write_number_into};
     // <g id=".." class="fm-node fm-node-accent-N fm-node-shape-rounded[ fm-node-user-…]" data-id=".." …>
     out.push_str("<g id=\"");
-    fm_core::write_mermaid_node_element_id_into(out, node_id, node_index);
+    fm_core::write_mermaid_node_element_id_into(out, node_id);
     out.push_str("\" class=\"fm-node fm-node-accent-");
     let _ = crate::attributes::write_uint_into(out, stable_accent_index(node_id) as u64);
     out.push(' ');
@@ -5894,8 +6539,13 @@ fn write_c4_node_fragment_into(
     if let Some(description) = &c4_meta.description {
         cursor_y += line_h * 0.9;
         let available_width = (w - 20.0).max(32.0);
-        let description_lines =
-            wrap_text_to_lines(description, available_width, config.avg_char_width * 0.92);
+        let description_lines = wrap_text_to_lines(
+            description,
+            available_width,
+            description_font,
+            &TextMeasure::new(config),
+            config.wrap_mode,
+        );
         if !description_lines.is_empty() {
             let description_height = (description_lines.len().saturating_sub(1) as f32)
                 * description_font
@@ -5971,7 +6621,6 @@ fn write_er_node_fragment_into<const A11Y: bool>(
     out: &mut String,
     node: &fm_core::IrNode,
     node_id: &str,
-    node_index: usize,
     raw_label: &str,
     label_text: &str,
     cx: f32,
@@ -5988,7 +6637,7 @@ fn write_er_node_fragment_into<const A11Y: bool>(
     use crate::attributes::{write_escaped_attr, write_escaped_text};
     // <g id=".." class="fm-node fm-node-accent-N fm-node-shape-rect[ fm-node-user-…]" data-id=".." …>
     out.push_str("<g id=\"");
-    fm_core::write_mermaid_node_element_id_into(out, node_id, node_index);
+    fm_core::write_mermaid_node_element_id_into(out, node_id);
     out.push_str("\" class=\"fm-node fm-node-accent-");
     let _ = crate::attributes::write_uint_into(out, stable_accent_index(node_id) as u64);
     out.push(' ');
@@ -6138,7 +6787,6 @@ fn write_cylinder_shape_into(
 fn write_subroutine_node_fragment_into(
     out: &mut String,
     node_id: &str,
-    node_index: usize,
     accent: usize,
     raw_label: &str,
     label: &str,
@@ -6156,7 +6804,7 @@ fn write_subroutine_node_fragment_into(
     use crate::attributes::{write_escaped_attr, write_escaped_text};
 
     out.push_str("<g id=\"");
-    fm_core::write_mermaid_node_element_id_into(out, node_id, node_index);
+    fm_core::write_mermaid_node_element_id_into(out, node_id);
     out.push_str("\" class=\"fm-node fm-node-accent-");
     // `accent` (small palette index) via the digit-table writer, not `write!`'s Formatter/`pad_integral`
     // machinery (measured ~1.87% of node-heavy render). Byte-identical: same decimal digits.
@@ -6229,7 +6877,6 @@ const fn uniform_a11y(a11y: &A11yConfig) -> Option<bool> {
 #[allow(clippy::too_many_arguments)]
 fn build_common_node_fragment<const A11Y: bool>(
     node_id: &str,
-    node_index: usize,
     accent: usize,
     raw_label: &str,
     label: &str,
@@ -6252,7 +6899,6 @@ fn build_common_node_fragment<const A11Y: bool>(
     write_common_node_fragment_into::<A11Y>(
         &mut f,
         node_id,
-        node_index,
         accent,
         raw_label,
         label,
@@ -6278,7 +6924,6 @@ fn build_common_node_fragment<const A11Y: bool>(
 fn write_common_node_fragment_into<const A11Y: bool>(
     f: &mut String,
     node_id: &str,
-    node_index: usize,
     accent: usize,
     raw_label: &str,
     label: &str,
@@ -6298,11 +6943,11 @@ fn write_common_node_fragment_into<const A11Y: bool>(
     use crate::attributes::{write_escaped_attr, write_escaped_text};
     // <g id=".." class="fm-node fm-node-accent-N fm-node-shape-rect[ fm-node-user-…]" data-id=".." …>
     f.push_str("<g id=\"");
-    // The node id is `fm-node-[{sanitized}-]{index}` — only `[a-z0-9-]`, never an escapable byte — so
+    // The node id is `fm-node-[{sanitized}-]{hash}` — only `[a-z0-9-]`, never an escapable byte — so
     // write it straight into `f` (skipping `mermaid_node_element_id`'s 3 throwaway allocations: the
     // sanitizer's two Strings + the id String). Byte-identical to `write_escaped_attr(id)` because the
     // id can never contain `& < > " '`; pinned by `node_fast_fragment_matches_render`.
-    fm_core::write_mermaid_node_element_id_into(f, node_id, node_index);
+    fm_core::write_mermaid_node_element_id_into(f, node_id);
     f.push_str("\" class=\"fm-node fm-node-accent-");
     // `accent` (small palette index) via the digit-table writer, not `write!`'s Formatter/`pad_integral`.
     let _ = crate::attributes::write_uint_into(f, accent as u64);
@@ -6509,7 +7154,6 @@ fn write_requirement_node_fragment_into<const A11Y: bool>(
     out: &mut String,
     meta: &fm_core::IrRequirementNodeMeta,
     node_id: &str,
-    node_index: usize,
     raw_label: &str,
     label: &str,
     x: f32,
@@ -6525,7 +7169,7 @@ fn write_requirement_node_fragment_into<const A11Y: bool>(
     use crate::attributes::{write_escaped_attr, write_escaped_text};
 
     out.push_str("<g id=\"");
-    fm_core::write_mermaid_node_element_id_into(out, node_id, node_index);
+    fm_core::write_mermaid_node_element_id_into(out, node_id);
     out.push_str("\" class=\"fm-node fm-node-accent-");
     // `stable_accent_index` (small palette index) via the digit-table writer, not `write!`'s
     // Formatter/`pad_integral` machinery. Byte-identical: same decimal digits.
@@ -6721,6 +7365,23 @@ fn render_node_into(
     use fm_core::NodeShape;
 
     let ir_node = ir.nodes.get(node_box.node_index);
+    // Registered custom shapes are only drawn by the `Element` path.
+    if ir_node.is_some_and(|node| config.shape_registry.resolve(node).is_some()) {
+        render_node(
+            node_box,
+            ir,
+            offset_x,
+            offset_y,
+            config,
+            detail,
+            colors,
+            emit_classdef_classes,
+            centrality_map,
+            true,
+        )
+        .write_to_string(out);
+        return;
+    }
     let shape = ir_node.map_or(NodeShape::Rect, |n| n.shape);
     let (shape_style, text_style) = resolve_node_inline_styles(ir, node_box.node_index);
     let node_id = ir_node
@@ -6792,7 +7453,6 @@ fn render_node_into(
             out,
             meta,
             node_id,
-            node_box.node_index,
             raw_label_text,
             &label_text,
             x,
@@ -6837,7 +7497,6 @@ fn render_node_into(
             out,
             meta,
             node_id,
-            node_box.node_index,
             raw_label_text,
             &label_text,
             x,
@@ -6892,7 +7551,6 @@ fn render_node_into(
             node,
             meta,
             node_id,
-            node_box.node_index,
             raw_label_text,
             ir,
             x,
@@ -6935,7 +7593,6 @@ fn render_node_into(
             node,
             meta,
             node_id,
-            node_box.node_index,
             raw_label_text,
             ir,
             x,
@@ -6982,7 +7639,15 @@ fn render_node_into(
         && node.href().is_none()
         && node.callback().is_none()
         && c4_meta.description.as_ref().is_none_or(|d| {
-            wrap_text_to_lines(d, (w - 20.0).max(32.0), config.avg_char_width * 0.92).len() <= 1
+            wrap_text_to_lines(
+                d,
+                (w - 20.0).max(32.0),
+                clamp_font_size(node_font_size * 0.72, config.min_font_size),
+                &TextMeasure::new(config),
+                config.wrap_mode,
+            )
+            .len()
+                <= 1
         })
         && let Some(user_classes) = simple_class_node_user_suffix(node)
     {
@@ -6991,7 +7656,6 @@ fn render_node_into(
             node,
             c4_meta,
             node_id,
-            node_box.node_index,
             raw_label_text,
             ir,
             x,
@@ -7048,7 +7712,6 @@ fn render_node_into(
             out,
             node,
             node_id,
-            node_box.node_index,
             raw_label_text,
             label_text.as_ref(),
             cx,
@@ -7094,7 +7757,6 @@ fn render_node_into(
             out,
             node,
             node_id,
-            node_box.node_index,
             raw_label_text,
             label_text.as_ref(),
             cx,
@@ -7142,8 +7804,7 @@ fn render_node_into(
         write_subroutine_node_fragment_into(
             out,
             node_id,
-            node_box.node_index,
-            stable_accent_index(node_id),
+            node_accent_index(ir, node_box.node_index, node_id),
             raw_label_text,
             &label_text,
             x,
@@ -7207,8 +7868,7 @@ fn render_node_into(
         write(
             out,
             node_id,
-            node_box.node_index,
-            stable_accent_index(node_id),
+            node_accent_index(ir, node_box.node_index, node_id),
             raw_label_text,
             &label_text,
             x,
@@ -7265,6 +7925,7 @@ fn render_node(
 
     let ir_node = ir.nodes.get(node_box.node_index);
     let shape = ir_node.map_or(NodeShape::Rect, |n| n.shape);
+    let custom_drawer = ir_node.and_then(|n| config.shape_registry.resolve(n));
     let (shape_style, text_style) = resolve_node_inline_styles(ir, node_box.node_index);
     let node_id = ir_node
         .map(|node| node.id.as_str())
@@ -7302,6 +7963,7 @@ fn render_node(
         .map(str::trim)
         .filter(|icon| !icon.is_empty())
         .filter(|_| ir_node.is_none_or(|node| node.class_meta.is_none() && node.c4_meta.is_none()));
+    let icon_position = node_icon_position(ir_node, config);
     let apply_label_class =
         |elem: Element| maybe_add_class(elem, "fm-node-label", emit_classdef_classes);
 
@@ -7327,6 +7989,7 @@ fn render_node(
     // `requirement_meta`/icon/centrality already gated below).
     let user_class_suffix = ir_node.and_then(simple_node_user_class_suffix);
     if permit_fast
+        && custom_drawer.is_none()
         && matches!(
             shape,
             NodeShape::Rect
@@ -7370,8 +8033,7 @@ fn render_node(
         };
         return Element::raw_svg(build(
             node_id,
-            node_box.node_index,
-            stable_accent_index(node_id),
+            node_accent_index(ir, node_box.node_index, node_id),
             raw_label_text,
             &label_text,
             x,
@@ -7395,9 +8057,12 @@ fn render_node(
 
     // Create group for node shape + label
     let mut group = Element::group()
-        .id(&mermaid_node_element_id(node_id, node_box.node_index))
+        .id(&mermaid_node_element_id(node_id))
         .class("fm-node")
-        .class_prefixed_usize("fm-node-accent-", stable_accent_index(node_id))
+        .class_prefixed_usize(
+            "fm-node-accent-",
+            node_accent_index(ir, node_box.node_index, node_id),
+        )
         .class(node_shape_css_class(shape))
         .data("id", node_id);
     // Add centrality tier class if available (FNX semantic styling)
@@ -7416,9 +8081,10 @@ fn render_node(
         if !icon_class.is_empty() {
             group = group.class_prefixed("fm-node-icon-", &icon_class);
         }
-        group = group.class(match config.node_icon_position {
+        group = group.class(match icon_position {
             NodeIconPosition::Above => "fm-node-icon-pos-above",
             NodeIconPosition::Left => "fm-node-icon-pos-left",
+            NodeIconPosition::Corner => "fm-node-icon-pos-corner",
         });
     }
     if config.include_source_spans {
@@ -7531,429 +8197,438 @@ fn render_node(
     }
 
     // Create shape element based on node type
-    let shape_elem = match shape {
-        NodeShape::Rect => Element::rect()
-            .x(x)
-            .y(y)
-            .width(w)
-            .height(h)
-            .fill(&colors.node_fill)
-            .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
-            .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
-            .rx(config.rounded_corners * 0.55),
-
-        NodeShape::Rounded => Element::rect()
-            .x(x)
-            .y(y)
-            .width(w)
-            .height(h)
-            .fill(&colors.node_fill)
-            .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
-            .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
-            .rx(config.rounded_corners),
-
-        NodeShape::Stadium => Element::rect()
-            .x(x)
-            .y(y)
-            .width(w)
-            .height(h)
+    // A registered drawer replaces the built-in geometry for this shape.
+    let shape_elem = if let Some(drawer) = custom_drawer {
+        Element::path()
+            .d(&drawer.svg_path(x, y, w, h))
             .fill(&colors.node_fill)
             .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
             .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
-            .rx(w.min(h) / 2.0),
-
-        NodeShape::Diamond => {
-            let path = PathBuilder::new()
-                .move_to(cx, y)
-                .line_to(x + w, cy)
-                .line_to(cx, y + h)
-                .line_to(x, cy)
-                .close()
-                .build();
-            Element::path()
-                .d(&path)
-                .fill(&colors.node_fill)
-                .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
-                .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
-        }
-
-        NodeShape::Hexagon => {
-            let inset = w * 0.15;
-            let path = PathBuilder::new()
-                .move_to(x + inset, y)
-                .line_to(x + w - inset, y)
-                .line_to(x + w, cy)
-                .line_to(x + w - inset, y + h)
-                .line_to(x + inset, y + h)
-                .line_to(x, cy)
-                .close()
-                .build();
-            Element::path()
-                .d(&path)
+    } else {
+        match shape {
+            NodeShape::Rect => Element::rect()
+                .x(x)
+                .y(y)
+                .width(w)
+                .height(h)
                 .fill(&colors.node_fill)
                 .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
                 .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
-        }
-
-        NodeShape::Circle | NodeShape::FilledCircle | NodeShape::DoubleCircle => {
-            let r = w.min(h) / 2.0;
-            let mut elem = Element::circle()
-                .cx(cx)
-                .cy(cy)
-                .r(r)
-                .fill(if shape == NodeShape::FilledCircle {
-                    colors.node_stroke.as_str()
-                } else {
-                    colors.node_fill.as_str()
-                })
-                .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
-                .stroke_width_unless_embedded_css(1.6, config.embed_theme_css);
-
-            if shape == NodeShape::DoubleCircle {
-                // For double circle, we'll use a slightly smaller stroke
-                elem = elem.stroke_width(2.0);
-            }
-            elem
-        }
+                .rx(config.rounded_corners * 0.55),
 
-        NodeShape::HorizontalBar => Element::rect()
-            .x(x)
-            .y(y + h * 0.25)
-            .width(w)
-            .height((h * 0.5).max(8.0))
-            .fill(&colors.node_stroke)
-            .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
-            .stroke_width(1.0)
-            .rx((h * 0.25).max(3.0)),
-
-        NodeShape::Cylinder => {
-            let ry = h * 0.1;
-            let path = PathBuilder::new()
-                .move_to(x, y + ry)
-                .arc_to(w / 2.0, ry, 0.0, false, true, x + w, y + ry)
-                .line_to(x + w, y + h - ry)
-                .arc_to(w / 2.0, ry, 0.0, false, false, x, y + h - ry)
-                .close()
-                .move_to(x, y + ry)
-                .arc_to(w / 2.0, ry, 0.0, false, false, x + w, y + ry)
-                .build();
-            Element::path()
-                .d(&path)
+            NodeShape::Rounded => Element::rect()
+                .x(x)
+                .y(y)
+                .width(w)
+                .height(h)
                 .fill(&colors.node_fill)
                 .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
                 .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
-        }
+                .rx(config.rounded_corners),
 
-        NodeShape::Trapezoid => {
-            let inset = w * 0.15;
-            let path = PathBuilder::new()
-                .move_to(x + inset, y)
-                .line_to(x + w - inset, y)
-                .line_to(x + w, y + h)
-                .line_to(x, y + h)
-                .close()
-                .build();
-            Element::path()
-                .d(&path)
+            NodeShape::Stadium => Element::rect()
+                .x(x)
+                .y(y)
+                .width(w)
+                .height(h)
                 .fill(&colors.node_fill)
                 .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
                 .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
-        }
-
-        NodeShape::Subroutine => {
-            let inset = 8.0;
-            let mut g = Element::group();
-            g = g.child(
-                Element::rect()
-                    .x(x)
-                    .y(y)
-                    .width(w)
-                    .height(h)
-                    .fill(if config.node_gradients {
-                        "url(#fm-node-gradient)"
-                    } else {
-                        colors.node_fill.as_str()
-                    })
+                .rx(w.min(h) / 2.0),
+
+            NodeShape::Diamond => {
+                let path = PathBuilder::new()
+                    .move_to(cx, y)
+                    .line_to(x + w, cy)
+                    .line_to(cx, y + h)
+                    .line_to(x, cy)
+                    .close()
+                    .build();
+                Element::path()
+                    .d(&path)
+                    .fill(&colors.node_fill)
                     .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
                     .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
-                    .rx(config.rounded_corners * 0.45),
-            );
-            // Left vertical line
-            g = g.child(
-                Element::line()
-                    .x1(x + inset)
-                    .y1(y)
-                    .x2(x + inset)
-                    .y2(y + h)
-                    .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
-                    .stroke_width(1.0),
-            );
-            // Right vertical line
-            g = g.child(
-                Element::line()
-                    .x1(x + w - inset)
-                    .y1(y)
-                    .x2(x + w - inset)
-                    .y2(y + h)
+            }
+
+            NodeShape::Hexagon => {
+                let inset = w * 0.15;
+                let path = PathBuilder::new()
+                    .move_to(x + inset, y)
+                    .line_to(x + w - inset, y)
+                    .line_to(x + w, cy)
+                    .line_to(x + w - inset, y + h)
+                    .line_to(x + inset, y + h)
+                    .line_to(x, cy)
+                    .close()
+                    .build();
+                Element::path()
+                    .d(&path)
+                    .fill(&colors.node_fill)
                     .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
-                    .stroke_width(1.0),
-            );
-            g = maybe_add_class(g, "fm-node-shape", emit_classdef_classes);
-            if detail.show_node_labels {
-                return group.child(g).child(render_node_label_text(
-                    ir,
-                    label_id,
-                    label_text.as_ref(),
-                    cx,
-                    cy + node_font_size / 3.0,
-                    node_font_size,
-                    config,
-                    colors,
-                    text_style.as_deref(),
-                    emit_classdef_classes,
-                ));
+                    .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
             }
-            return group.child(g);
-        }
 
-        NodeShape::Asymmetric => {
-            let flag = w * 0.15;
-            let path = PathBuilder::new()
-                .move_to(x, y)
-                .line_to(x + w - flag, y)
-                .line_to(x + w, cy)
-                .line_to(x + w - flag, y + h)
-                .line_to(x, y + h)
-                .close()
-                .build();
-            Element::path()
-                .d(&path)
-                .fill(&colors.node_fill)
-                .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
-                .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
-        }
+            NodeShape::Circle | NodeShape::FilledCircle | NodeShape::DoubleCircle => {
+                let r = w.min(h) / 2.0;
+                let mut elem = Element::circle()
+                    .cx(cx)
+                    .cy(cy)
+                    .r(r)
+                    .fill(if shape == NodeShape::FilledCircle {
+                        colors.node_stroke.as_str()
+                    } else {
+                        colors.node_fill.as_str()
+                    })
+                    .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
+                    .stroke_width_unless_embedded_css(1.6, config.embed_theme_css);
 
-        NodeShape::Note => {
-            let fold = 10.0;
-            let path = PathBuilder::new()
-                .move_to(x, y)
-                .line_to(x + w - fold, y)
-                .line_to(x + w, y + fold)
-                .line_to(x + w, y + h)
-                .line_to(x, y + h)
-                .close()
-                .move_to(x + w - fold, y)
-                .line_to(x + w - fold, y + fold)
-                .line_to(x + w, y + fold)
-                .build();
-            Element::path()
-                .d(&path)
-                .fill(&colors.node_fill)
+                if shape == NodeShape::DoubleCircle {
+                    // For double circle, we'll use a slightly smaller stroke
+                    elem = elem.stroke_width(2.0);
+                }
+                elem
+            }
+
+            NodeShape::HorizontalBar => Element::rect()
+                .x(x)
+                .y(y + h * 0.25)
+                .width(w)
+                .height((h * 0.5).max(8.0))
+                .fill(&colors.node_stroke)
                 .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
                 .stroke_width(1.0)
-        }
+                .rx((h * 0.25).max(3.0)),
+
+            NodeShape::Cylinder => {
+                let ry = h * 0.1;
+                let path = PathBuilder::new()
+                    .move_to(x, y + ry)
+                    .arc_to(w / 2.0, ry, 0.0, false, true, x + w, y + ry)
+                    .line_to(x + w, y + h - ry)
+                    .arc_to(w / 2.0, ry, 0.0, false, false, x, y + h - ry)
+                    .close()
+                    .move_to(x, y + ry)
+                    .arc_to(w / 2.0, ry, 0.0, false, false, x + w, y + ry)
+                    .build();
+                Element::path()
+                    .d(&path)
+                    .fill(&colors.node_fill)
+                    .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
+                    .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
+            }
 
-        // Extended shapes for FrankenMermaid
-        NodeShape::InvTrapezoid => {
-            let inset = w * 0.15;
-            let path = PathBuilder::new()
-                .move_to(x, y)
-                .line_to(x + w, y)
-                .line_to(x + w - inset, y + h)
-                .line_to(x + inset, y + h)
-                .close()
-                .build();
-            Element::path()
-                .d(&path)
-                .fill(&colors.node_fill)
-                .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
-                .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
-        }
+            NodeShape::Trapezoid => {
+                let inset = w * 0.15;
+                let path = PathBuilder::new()
+                    .move_to(x + inset, y)
+                    .line_to(x + w - inset, y)
+                    .line_to(x + w, y + h)
+                    .line_to(x, y + h)
+                    .close()
+                    .build();
+                Element::path()
+                    .d(&path)
+                    .fill(&colors.node_fill)
+                    .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
+                    .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
+            }
 
-        NodeShape::Parallelogram => {
-            let inset = w * 0.15;
-            let path = PathBuilder::new()
-                .move_to(x + inset, y)
-                .line_to(x + w, y)
-                .line_to(x + w - inset, y + h)
-                .line_to(x, y + h)
-                .close()
-                .build();
-            Element::path()
-                .d(&path)
-                .fill(&colors.node_fill)
-                .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
-                .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
-        }
+            NodeShape::Subroutine => {
+                let inset = 8.0;
+                let mut g = Element::group();
+                g = g.child(
+                    Element::rect()
+                        .x(x)
+                        .y(y)
+                        .width(w)
+                        .height(h)
+                        .fill(if config.node_gradients {
+                            "url(#fm-node-gradient)"
+                        } else {
+                            colors.node_fill.as_str()
+                        })
+                        .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
+                        .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
+                        .rx(config.rounded_corners * 0.45),
+                );
+                // Left vertical line
+                g = g.child(
+                    Element::line()
+                        .x1(x + inset)
+                        .y1(y)
+                        .x2(x + inset)
+                        .y2(y + h)
+                        .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
+                        .stroke_width(1.0),
+                );
+                // Right vertical line
+                g = g.child(
+                    Element::line()
+                        .x1(x + w - inset)
+                        .y1(y)
+                        .x2(x + w - inset)
+                        .y2(y + h)
+                        .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
+                        .stroke_width(1.0),
+                );
+                g = maybe_add_class(g, "fm-node-shape", emit_classdef_classes);
+                if detail.show_node_labels {
+                    return group.child(g).child(render_node_label_text(
+                        ir,
+                        label_id,
+                        label_text.as_ref(),
+                        cx,
+                        cy + node_font_size / 3.0,
+                        node_font_size,
+                        config,
+                        colors,
+                        text_style.as_deref(),
+                        emit_classdef_classes,
+                    ));
+                }
+                return group.child(g);
+            }
 
-        NodeShape::InvParallelogram => {
-            let inset = w * 0.15;
-            let path = PathBuilder::new()
-                .move_to(x, y)
-                .line_to(x + w - inset, y)
-                .line_to(x + w, y + h)
-                .line_to(x + inset, y + h)
-                .close()
-                .build();
-            Element::path()
-                .d(&path)
-                .fill(&colors.node_fill)
-                .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
-                .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
-        }
+            NodeShape::Asymmetric => {
+                let flag = w * 0.15;
+                let path = PathBuilder::new()
+                    .move_to(x, y)
+                    .line_to(x + w - flag, y)
+                    .line_to(x + w, cy)
+                    .line_to(x + w - flag, y + h)
+                    .line_to(x, y + h)
+                    .close()
+                    .build();
+                Element::path()
+                    .d(&path)
+                    .fill(&colors.node_fill)
+                    .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
+                    .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
+            }
 
-        NodeShape::Triangle => {
-            let path = PathBuilder::new()
-                .move_to(cx, y)
-                .line_to(x + w, y + h)
-                .line_to(x, y + h)
-                .close()
-                .build();
-            Element::path()
-                .d(&path)
-                .fill(&colors.node_fill)
-                .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
-                .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
-        }
+            NodeShape::Note => {
+                let fold = 10.0;
+                let path = PathBuilder::new()
+                    .move_to(x, y)
+                    .line_to(x + w - fold, y)
+                    .line_to(x + w, y + fold)
+                    .line_to(x + w, y + h)
+                    .line_to(x, y + h)
+                    .close()
+                    .move_to(x + w - fold, y)
+                    .line_to(x + w - fold, y + fold)
+                    .line_to(x + w, y + fold)
+                    .build();
+                Element::path()
+                    .d(&path)
+                    .fill(&colors.node_fill)
+                    .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
+                    .stroke_width(1.0)
+            }
 
-        NodeShape::Pentagon => {
-            // Regular pentagon (5 sides)
-            let angle_offset = -std::f32::consts::FRAC_PI_2; // Start at top
-            let r = w.min(h) / 2.0;
-            let mut path = PathBuilder::new();
-            for i in 0..5 {
-                let angle = angle_offset + (i as f32) * 2.0 * std::f32::consts::PI / 5.0;
-                let px = cx + r * angle.cos();
-                let py = cy + r * angle.sin();
-                if i == 0 {
-                    path = path.move_to(px, py);
-                } else {
-                    path = path.line_to(px, py);
-                }
+            // Extended shapes for FrankenMermaid
+            NodeShape::InvTrapezoid => {
+                let inset = w * 0.15;
+                let path = PathBuilder::new()
+                    .move_to(x, y)
+                    .line_to(x + w, y)
+                    .line_to(x + w - inset, y + h)
+                    .line_to(x + inset, y + h)
+                    .close()
+                    .build();
+                Element::path()
+                    .d(&path)
+                    .fill(&colors.node_fill)
+                    .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
+                    .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
             }
-            Element::path()
-                .d(&path.close().build())
-                .fill(&colors.node_fill)
-                .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
-                .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
-        }
 
-        NodeShape::Star => {
-            // 5-pointed star
-            let outer_r = w.min(h) / 2.0;
-            let inner_r = outer_r * 0.4;
-            let angle_offset = -std::f32::consts::FRAC_PI_2;
-            let mut path = PathBuilder::new();
-            for i in 0..10 {
-                let r = if i % 2 == 0 { outer_r } else { inner_r };
-                let angle = angle_offset + (i as f32) * std::f32::consts::PI / 5.0;
-                let px = cx + r * angle.cos();
-                let py = cy + r * angle.sin();
-                if i == 0 {
-                    path = path.move_to(px, py);
-                } else {
-                    path = path.line_to(px, py);
-                }
+            NodeShape::Parallelogram => {
+                let inset = w * 0.15;
+                let path = PathBuilder::new()
+                    .move_to(x + inset, y)
+                    .line_to(x + w, y)
+                    .line_to(x + w - inset, y + h)
+                    .line_to(x, y + h)
+                    .close()
+                    .build();
+                Element::path()
+                    .d(&path)
+                    .fill(&colors.node_fill)
+                    .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
+                    .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
             }
-            Element::path()
-                .d(&path.close().build())
-                .fill(&colors.node_fill)
-                .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
-                .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
-        }
 
-        NodeShape::Cloud => {
-            // Simplified cloud shape using circles
-            let r = h / 3.0;
-            let path = PathBuilder::new()
-                .move_to(x + r, y + h * 0.6)
-                .arc_to(r, r, 0.0, true, true, x + r * 2.0, y + h * 0.3)
-                .arc_to(r * 0.8, r * 0.8, 0.0, true, true, x + w * 0.5, y + r * 0.5)
-                .arc_to(r, r, 0.0, true, true, x + w - r * 2.0, y + h * 0.3)
-                .arc_to(r, r, 0.0, true, true, x + w - r, y + h * 0.6)
-                .arc_to(r * 0.7, r * 0.7, 0.0, true, true, x + w - r, y + h * 0.8)
-                .line_to(x + r, y + h * 0.8)
-                .arc_to(r * 0.7, r * 0.7, 0.0, true, true, x + r, y + h * 0.6)
-                .close()
-                .build();
-            Element::path()
-                .d(&path)
-                .fill(&colors.node_fill)
-                .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
-                .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
-        }
+            NodeShape::InvParallelogram => {
+                let inset = w * 0.15;
+                let path = PathBuilder::new()
+                    .move_to(x, y)
+                    .line_to(x + w - inset, y)
+                    .line_to(x + w, y + h)
+                    .line_to(x + inset, y + h)
+                    .close()
+                    .build();
+                Element::path()
+                    .d(&path)
+                    .fill(&colors.node_fill)
+                    .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
+                    .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
+            }
 
-        NodeShape::Tag => {
-            // Tag/flag shape (rectangle with arrow point on right)
-            let point = w * 0.2;
-            let path = PathBuilder::new()
-                .move_to(x, y)
-                .line_to(x + w - point, y)
-                .line_to(x + w, cy)
-                .line_to(x + w - point, y + h)
-                .line_to(x, y + h)
-                .close()
-                .build();
-            Element::path()
-                .d(&path)
-                .fill(&colors.node_fill)
-                .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
-                .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
-        }
+            NodeShape::Triangle => {
+                let path = PathBuilder::new()
+                    .move_to(cx, y)
+                    .line_to(x + w, y + h)
+                    .line_to(x, y + h)
+                    .close()
+                    .build();
+                Element::path()
+                    .d(&path)
+                    .fill(&colors.node_fill)
+                    .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
+                    .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
+            }
 
-        NodeShape::CrossedCircle => {
-            // Circle with X through it
-            let r = w.min(h) / 2.0;
-            let mut g = Element::group();
-            g = g.child(
-                Element::circle()
-                    .cx(cx)
-                    .cy(cy)
-                    .r(r)
-                    .fill(if config.node_gradients {
-                        "url(#fm-node-gradient)"
+            NodeShape::Pentagon => {
+                // Regular pentagon (5 sides)
+                let angle_offset = -std::f32::consts::FRAC_PI_2; // Start at top
+                let r = w.min(h) / 2.0;
+                let mut path = PathBuilder::new();
+                for i in 0..5 {
+                    let angle = angle_offset + (i as f32) * 2.0 * std::f32::consts::PI / 5.0;
+                    let px = cx + r * angle.cos();
+                    let py = cy + r * angle.sin();
+                    if i == 0 {
+                        path = path.move_to(px, py);
                     } else {
-                        colors.node_fill.as_str()
-                    })
+                        path = path.line_to(px, py);
+                    }
+                }
+                Element::path()
+                    .d(&path.close().build())
+                    .fill(&colors.node_fill)
                     .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
-                    .stroke_width_unless_embedded_css(1.6, config.embed_theme_css),
-            );
-            // Diagonal lines
-            let offset = r * 0.707; // r * cos(45°)
-            g = g.child(
-                Element::line()
-                    .x1(cx - offset)
-                    .y1(cy - offset)
-                    .x2(cx + offset)
-                    .y2(cy + offset)
+                    .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
+            }
+
+            NodeShape::Star => {
+                // 5-pointed star
+                let outer_r = w.min(h) / 2.0;
+                let inner_r = outer_r * 0.4;
+                let angle_offset = -std::f32::consts::FRAC_PI_2;
+                let mut path = PathBuilder::new();
+                for i in 0..10 {
+                    let r = if i % 2 == 0 { outer_r } else { inner_r };
+                    let angle = angle_offset + (i as f32) * std::f32::consts::PI / 5.0;
+                    let px = cx + r * angle.cos();
+                    let py = cy + r * angle.sin();
+                    if i == 0 {
+                        path = path.move_to(px, py);
+                    } else {
+                        path = path.line_to(px, py);
+                    }
+                }
+                Element::path()
+                    .d(&path.close().build())
+                    .fill(&colors.node_fill)
                     .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
-                    .stroke_width_unless_embedded_css(1.6, config.embed_theme_css),
-            );
-            g = g.child(
-                Element::line()
-                    .x1(cx + offset)
-                    .y1(cy - offset)
-                    .x2(cx - offset)
-                    .y2(cy + offset)
+                    .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
+            }
+
+            NodeShape::Cloud => {
+                // Simplified cloud shape using circles
+                let r = h / 3.0;
+                let path = PathBuilder::new()
+                    .move_to(x + r, y + h * 0.6)
+                    .arc_to(r, r, 0.0, true, true, x + r * 2.0, y + h * 0.3)
+                    .arc_to(r * 0.8, r * 0.8, 0.0, true, true, x + w * 0.5, y + r * 0.5)
+                    .arc_to(r, r, 0.0, true, true, x + w - r * 2.0, y + h * 0.3)
+                    .arc_to(r, r, 0.0, true, true, x + w - r, y + h * 0.6)
+                    .arc_to(r * 0.7, r * 0.7, 0.0, true, true, x + w - r, y + h * 0.8)
+                    .line_to(x + r, y + h * 0.8)
+                    .arc_to(r * 0.7, r * 0.7, 0.0, true, true, x + r, y + h * 0.6)
+                    .close()
+                    .build();
+                Element::path()
+                    .d(&path)
+                    .fill(&colors.node_fill)
                     .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
-                    .stroke_width_unless_embedded_css(1.6, config.embed_theme_css),
-            );
-            g = maybe_add_class(g, "fm-node-shape", emit_classdef_classes);
-            if detail.show_node_labels {
-                return group.child(g).child(render_node_label_text(
-                    ir,
-                    label_id,
-                    &label_text,
-                    cx,
-                    cy + node_font_size / 3.0,
-                    node_font_size,
-                    config,
-                    colors,
-                    text_style.as_deref(),
-                    emit_classdef_classes,
-                ));
+                    .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
+            }
+
+            NodeShape::Tag => {
+                // Tag/flag shape (rectangle with arrow point on right)
+                let point = w * 0.2;
+                let path = PathBuilder::new()
+                    .move_to(x, y)
+                    .line_to(x + w - point, y)
+                    .line_to(x + w, cy)
+                    .line_to(x + w - point, y + h)
+                    .line_to(x, y + h)
+                    .close()
+                    .build();
+                Element::path()
+                    .d(&path)
+                    .fill(&colors.node_fill)
+                    .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
+                    .stroke_width_unless_embedded_css(1.6, config.embed_theme_css)
+            }
+
+            NodeShape::CrossedCircle => {
+                // Circle with X through it
+                let r = w.min(h) / 2.0;
+                let mut g = Element::group();
+                g = g.child(
+                    Element::circle()
+                        .cx(cx)
+                        .cy(cy)
+                        .r(r)
+                        .fill(if config.node_gradients {
+                            "url(#fm-node-gradient)"
+                        } else {
+                            colors.node_fill.as_str()
+                        })
+                        .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
+                        .stroke_width_unless_embedded_css(1.6, config.embed_theme_css),
+                );
+                // Diagonal lines
+                let offset = r * 0.707; // r * cos(45°)
+                g = g.child(
+                    Element::line()
+                        .x1(cx - offset)
+                        .y1(cy - offset)
+                        .x2(cx + offset)
+                        .y2(cy + offset)
+                        .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
+                        .stroke_width_unless_embedded_css(1.6, config.embed_theme_css),
+                );
+                g = g.child(
+                    Element::line()
+                        .x1(cx + offset)
+                        .y1(cy - offset)
+                        .x2(cx - offset)
+                        .y2(cy + offset)
+                        .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
+                        .stroke_width_unless_embedded_css(1.6, config.embed_theme_css),
+                );
+                g = maybe_add_class(g, "fm-node-shape", emit_classdef_classes);
+                if detail.show_node_labels {
+                    return group.child(g).child(render_node_label_text(
+                        ir,
+                        label_id,
+                        &label_text,
+                        cx,
+                        cy + node_font_size / 3.0,
+                        node_font_size,
+                        config,
+                        colors,
+                        text_style.as_deref(),
+                        emit_classdef_classes,
+                    ));
+                }
+                return group.child(g);
             }
-            return group.child(g);
         }
     };
 
@@ -8011,31 +8686,40 @@ fn render_node(
         group = group.filter("url(#node-glow)");
     }
 
-    let icon_size = clamp_font_size(node_font_size * 1.35, config.min_font_size + 2.0);
-    let icon_reserved_height = node_icon.map_or(0.0, |_| match config.node_icon_position {
+    let icon_size = match icon_position {
+        NodeIconPosition::Corner => clamp_font_size(node_font_size * 0.9, config.min_font_size),
+        NodeIconPosition::Above | NodeIconPosition::Left => {
+            clamp_font_size(node_font_size * 1.35, config.min_font_size + 2.0)
+        }
+    };
+    let icon_reserved_height = node_icon.map_or(0.0, |_| match icon_position {
         NodeIconPosition::Above => icon_size + 10.0,
-        NodeIconPosition::Left => 0.0,
+        NodeIconPosition::Left | NodeIconPosition::Corner => 0.0,
     });
-    let icon_reserved_width = node_icon.map_or(0.0, |_| match config.node_icon_position {
-        NodeIconPosition::Above => 0.0,
+    let icon_reserved_width = node_icon.map_or(0.0, |_| match icon_position {
         NodeIconPosition::Left => icon_size + 14.0,
+        NodeIconPosition::Above | NodeIconPosition::Corner => 0.0,
     });
     if let Some(icon) = node_icon
         && let Some(icon_elem) = render_node_icon(
             icon,
-            if detail.show_node_labels
-                && matches!(config.node_icon_position, NodeIconPosition::Left)
-            {
-                x + (icon_reserved_width * 0.5) + 2.0
-            } else {
-                cx
+            match icon_position {
+                NodeIconPosition::Corner => x + 6.0 + icon_size * 0.5,
+                NodeIconPosition::Left if detail.show_node_labels => {
+                    x + (icon_reserved_width * 0.5) + 2.0
+                }
+                _ => cx,
             },
-            if detail.show_node_labels
-                && matches!(config.node_icon_position, NodeIconPosition::Above)
-            {
-                y + (icon_reserved_height * 0.5) + 2.0
-            } else {
-                cy
+            match icon_position {
+                // Below a cylinder's top ellipse (architecture `database`/`disk` services).
+                NodeIconPosition::Corner if matches!(shape, NodeShape::Cylinder) => {
+                    y + 6.0 + h * 0.2 + icon_size * 0.5
+                }
+                NodeIconPosition::Corner => y + 6.0 + icon_size * 0.5,
+                NodeIconPosition::Above if detail.show_node_labels => {
+                    y + (icon_reserved_height * 0.5) + 2.0
+                }
+                _ => cy,
             },
             icon_size,
             config,
@@ -8246,23 +8930,64 @@ fn render_node(
                         .stroke_width(0.8),
                 );
 
-                // Attribute list
+                // Attribute rows: every other row banded, keys as badges in their own column.
+                let row_pitch = attr_font_size * 1.3;
+                let badge_width = attr_font_size * ER_KEY_BADGE_WIDTH;
+                let badge_font_size = clamp_font_size(attr_font_size * 0.75, config.min_font_size);
+                let text_x = er_attribute_text_x(node, x, badge_width);
+                let band_style = er_row_band_style(colors);
                 let mut attr_y = y + header_height + attr_font_size * 0.9;
-                for attr in &node.members {
-                    let key_prefix = match attr.key {
-                        fm_core::IrAttributeKey::Pk => "PK ",
-                        fm_core::IrAttributeKey::Fk => "FK ",
-                        fm_core::IrAttributeKey::Uk => "UK ",
-                        fm_core::IrAttributeKey::None => "",
-                    };
-                    let attr_text = format!("{key_prefix}{} {}", attr.data_type, attr.name);
+                for (row, attr) in node.members.iter().enumerate() {
+                    if row % 2 == 1 {
+                        group = group.child(
+                            Element::rect()
+                                .x(x + 1.0)
+                                .y(attr_y - row_pitch / 2.0)
+                                .width(w - 2.0)
+                                .height(row_pitch)
+                                .attr("style", &band_style)
+                                .class("fm-er-row"),
+                        );
+                    }
+                    if let Some((badge, accent)) = er_key_badge(attr.key, colors) {
+                        group = group.child(
+                            Element::rect()
+                                .x(x + 8.0)
+                                .y(attr_y - attr_font_size * 0.55)
+                                .width(badge_width)
+                                .height(attr_font_size * 1.1)
+                                .rx(2.0)
+                                .attr("style", &er_key_badge_style(accent))
+                                .class("fm-er-key-badge"),
+                        );
+                        let mut badge_elem = Element::text()
+                            .x(x + 8.0 + badge_width / 2.0)
+                            .y(attr_y)
+                            .content(badge)
+                            .attr("text-anchor", "middle")
+                            .attr("dominant-baseline", "central")
+                            .attr_num("font-size", badge_font_size)
+                            .attr("font-weight", "bold")
+                            .font_family_unless_embedded_css(
+                                &config.font_family,
+                                config.embed_theme_css,
+                            )
+                            .fill(&colors.text)
+                            .class("fm-er-key");
+                        badge_elem = apply_label_class(badge_elem);
+                        if let Some(style) = text_style.as_deref() {
+                            badge_elem = badge_elem.attr("style", style);
+                        }
+                        group = group.child(badge_elem);
+                    }
+                    let attr_text = format!("{} {}", attr.data_type, attr.name);
                     let font_weight = if attr.key == fm_core::IrAttributeKey::None {
                         "normal"
                     } else {
                         "bold"
                     };
                     let mut attr_elem = Element::text()
-                        .x(x + 8.0)
+                        .x(text_x)
                         .y(attr_y)
                         .content(&attr_text)
                         .attr("text-anchor", "start")
@@ -8280,7 +9005,7 @@ fn render_node(
                         attr_elem = attr_elem.attr("style", style);
                     }
                     group = group.child(attr_elem);
-                    attr_y += attr_font_size * 1.3;
+                    attr_y += row_pitch;
                 }
             }
         } else if let Some(node) = ir_node
@@ -8383,9 +9108,10 @@ fn render_node(
         }
     }
 
-    // Callback nodes: emit data-callback attribute for embedding JS integration.
+    // Callback nodes: emit data-callback attribute for embedding JS integration. Only a bare
+    // function name is ever written; the SVG carries no script or event handler of its own.
     if let Some(node) = ir_node
-        && let Some(callback) = node.callback()
+        && let Some(callback) = node.callback().and_then(fm_core::callback_function_name)
     {
         group = group
             .attr("data-callback", callback)
@@ -8411,6 +9137,18 @@ fn serialize_menu_links(links: &[fm_core::IrMenuLink]) -> String {
     }
 }
 
+/// Accent slot (`1..=8`) of a node. Git-graph commits take their branch's color slot so they
+/// match the branch lane; every other node hashes its id.
+fn node_accent_index(ir: &MermaidDiagramIr, node_index: usize, node_id: &str) -> usize {
+    ir.gitgraph_meta
+        .as_ref()
+        .and_then(|meta| meta.branch_of(fm_core::IrNodeId(node_index)))
+        .map_or_else(
+            || stable_accent_index(node_id),
+            |branch| fm_core::IrGitGraphMeta::branch_color_index(branch) + 1,
+        )
+}
+
 fn stable_accent_index(node_id: &str) -> usize {
     // FNV-1a 32-bit hash for deterministic class assignment.
     let mut hash: u32 = 0x811c9dc5;
@@ -8427,8 +9165,9 @@ fn stable_accent_index(node_id: &str) -> usize {
 #[allow(clippy::too_many_arguments)]
 /// Stream one class-compartment `<text>` byte-identical to the `TextBuilder` the slow path builds under
 /// embedded CSS with no label-style/classdef class: attrs `x, y, text-anchor, font-size, [extra], fill`
-/// then escaped content. `extra` is ` font-weight="bold"` (name), ` font-style="italic"` (stereotype), or
-/// `""` (members) — placed right after `font-size` exactly as `TextBuilder::build`'s call order does.
+/// then escaped content. `extra` is ` font-weight="bold"` (name, plus ` font-style="italic"` when
+/// abstract), ` font-style="italic"` (stereotype), or [`class_member_extra`] (members) — placed right
+/// after `font-size` exactly as `TextBuilder::build`'s call order does.
 fn write_class_text_into(
     f: &mut String,
     x: f32,
@@ -8624,15 +9363,17 @@ fn render_class_compartments(
         format!("{class_name}<{}>", meta.generics.join(", "))
     };
 
-    let name_elem = TextBuilder::new(&display_name)
+    let mut name_builder = TextBuilder::new(&display_name)
         .x(x + w / 2.0)
         .y(cursor_y)
         .font_family_unless_embedded_css(&config.font_family, config.embed_theme_css)
         .font_size(font_size)
         .anchor(TextAnchor::Middle)
-        .bold()
-        .fill(&colors.text)
-        .build();
+        .bold();
+    if is_abstract_class(meta) {
+        name_builder = name_builder.italic();
+    }
+    let name_elem = name_builder.fill(&colors.text).build();
     group = group.child(apply_label_style(apply_label_class(name_elem)));
     cursor_y += line_h * 0.5;
 
@@ -8654,13 +9395,7 @@ fn render_class_compartments(
         if cursor_y > y + h - line_h * 0.5 {
             break;
         }
-        let vis = visibility_symbol(attr.visibility);
-        let text = if let Some(ref ret) = attr.return_type {
-            format!("{vis}{}: {ret}", attr.name)
-        } else {
-            format!("{vis}{}", attr.name)
-        };
-        let elem = TextBuilder::new(&text)
+        let elem = style_class_member(TextBuilder::new(&class_member_text(attr)), attr)
             .x(text_x)
             .y(cursor_y)
             .font_family_unless_embedded_css(&config.font_family, config.embed_theme_css)
@@ -8671,19 +9406,17 @@ fn render_class_compartments(
         group = group.child(apply_label_style(apply_label_class(elem)));
     }
 
-    // Separator before methods (only if both sections present).
-    if !meta.attributes.is_empty() && !meta.methods.is_empty() {
-        cursor_y += line_h * 0.3;
-        let sep2 = Element::new(crate::element::ElementKind::Line)
-            .attr_num("x1", x)
-            .attr_num("y1", cursor_y)
-            .attr_num("x2", x + w)
-            .attr_num("y2", cursor_y)
-            .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
-            .stroke_width(1.0);
-        group = group.child(sep2);
-        cursor_y += line_h * 0.3;
-    }
+    // Separator before the methods compartment, drawn even when either compartment is empty.
+    cursor_y += line_h * 0.3;
+    let sep2 = Element::new(crate::element::ElementKind::Line)
+        .attr_num("x1", x)
+        .attr_num("y1", cursor_y)
+        .attr_num("x2", x + w)
+        .attr_num("y2", cursor_y)
+        .stroke_unless_embedded_css(&colors.node_stroke, config.embed_theme_css)
+        .stroke_width(1.0);
+    group = group.child(sep2);
+    cursor_y += line_h * 0.3;
 
     // Methods compartment.
     for method in &meta.methods {
@@ -8691,21 +9424,7 @@ fn render_class_compartments(
         if cursor_y > y + h - line_h * 0.5 {
             break;
         }
-        let vis = visibility_symbol(method.visibility);
-        let suffix = if method.is_abstract {
-            "*"
-        } else if method.is_static {
-            "$"
-        } else {
-            ""
-        };
-        let ret = method
-            .return_type
-            .as_deref()
-            .map(|t| format!(": {t}"))
-            .unwrap_or_default();
-        let text = format!("{vis}{}{suffix}{ret}", method.name);
-        let elem = TextBuilder::new(&text)
+        let elem = style_class_member(TextBuilder::new(&class_member_text(method)), method)
             .x(text_x)
             .y(cursor_y)
             .font_family_unless_embedded_css(&config.font_family, config.embed_theme_css)
@@ -8812,8 +9531,13 @@ fn render_c4_node_content(
     if let Some(description) = &c4_meta.description {
         cursor_y += line_h * 0.9;
         let available_width = (w - 20.0).max(32.0);
-        let description_lines =
-            wrap_text_to_lines(description, available_width, config.avg_char_width * 0.92);
+        let description_lines = wrap_text_to_lines(
+            description,
+            available_width,
+            description_font,
+            &TextMeasure::new(config),
+            config.wrap_mode,
+        );
         if !description_lines.is_empty() {
             let description_text = description_lines.join("\n");
             let description_height = (description_lines.len().saturating_sub(1) as f32)
@@ -8888,6 +9612,23 @@ fn render_c4_person_icon(x: f32, y: f32, stroke: &str) -> Element {
     )
 }
 
+/// Architecture services keep their icon in the top-left corner, as Mermaid draws them; other
+/// nodes use the configured placement.
+fn node_icon_position(
+    node: Option<&fm_core::IrNode>,
+    config: &SvgRenderConfig,
+) -> NodeIconPosition {
+    if node.is_some_and(|node| {
+        node.classes
+            .iter()
+            .any(|class| class == "architecture-service")
+    }) {
+        NodeIconPosition::Corner
+    } else {
+        config.node_icon_position
+    }
+}
+
 fn normalize_icon_token(raw_icon: &str) -> String {
     let trimmed = raw_icon.trim();
     if trimmed.is_empty() {
@@ -8964,90 +9705,30 @@ fn render_node_icon(
         .class_prefixed("fm-node-icon-", &icon_class);
 
     if let Some(custom_icon) = config.custom_icons.get(&normalized) {
-        return Some(icon.child(render_custom_svg_icon(custom_icon, cx, cy, size, stroke)));
+        return Some(icon.child(render_custom_svg_icon(
+            custom_icon,
+            "fm-node-icon-custom",
+            cx,
+            cy,
+            size,
+            stroke,
+        )));
+    }
+    if let Some(builtin_icon) = icons::builtin_icons().get(&normalized) {
+        return Some(icon.child(render_custom_svg_icon(
+            builtin_icon,
+            "fm-node-icon-builtin",
+            cx,
+            cy,
+            size,
+            stroke,
+        )));
     }
 
     match normalized.as_str() {
         "person" | "user" => {
             icon = icon.child(render_c4_person_icon(cx, cy, stroke));
         }
-        "server" => {
-            icon = icon.child(
-                Element::rect()
-                    .x(x)
-                    .y(y - 1.0)
-                    .width(size)
-                    .height(size * 0.72)
-                    .rx(2.0)
-                    .fill(fill)
-                    .stroke(stroke)
-                    .stroke_width(1.1),
-            );
-            icon = icon.child(
-                Element::line()
-                    .x1(x + size * 0.18)
-                    .y1(y + size * 0.2)
-                    .x2(x + size * 0.82)
-                    .y2(y + size * 0.2)
-                    .stroke(stroke)
-                    .stroke_width(1.0),
-            );
-            icon = icon.child(
-                Element::line()
-                    .x1(x + size * 0.18)
-                    .y1(y + size * 0.38)
-                    .x2(x + size * 0.82)
-                    .y2(y + size * 0.38)
-                    .stroke(stroke)
-                    .stroke_width(1.0),
-            );
-        }
-        "database" => {
-            let ry = size * 0.14;
-            let path = PathBuilder::new()
-                .move_to(x, y + ry)
-                .arc_to(size / 2.0, ry, 0.0, false, true, x + size, y + ry)
-                .line_to(x + size, y + size - ry)
-                .arc_to(size / 2.0, ry, 0.0, false, false, x, y + size - ry)
-                .close()
-                .move_to(x, y + ry)
-                .arc_to(size / 2.0, ry, 0.0, false, false, x + size, y + ry)
-                .build();
-            icon = icon.child(
-                Element::path()
-                    .d(&path)
-                    .fill(fill)
-                    .stroke(stroke)
-                    .stroke_width(1.1),
-            );
-        }
-        "cloud" => {
-            let r = size / 3.0;
-            let path = PathBuilder::new()
-                .move_to(x + r, y + size * 0.65)
-                .arc_to(r, r, 0.0, true, true, x + r * 2.0, y + size * 0.35)
-                .arc_to(
-                    r * 0.85,
-                    r * 0.85,
-                    0.0,
-                    true,
-                    true,
-                    x + size * 0.52,
-                    y + r * 0.45,
-                )
-                .arc_to(r, r, 0.0, true, true, x + size - r * 2.0, y + size * 0.35)
-                .arc_to(r, r, 0.0, true, true, x + size - r, y + size * 0.65)
-                .arc_to(r * 0.65, r * 0.65, 0.0, true, true, x + r, y + size * 0.65)
-                .close()
-                .build();
-            icon = icon.child(
-                Element::path()
-                    .d(&path)
-                    .fill(fill)
-                    .stroke(stroke)
-                    .stroke_width(1.1),
-            );
-        }
         "lock" | "security" => {
             icon = icon.child(
                 Element::rect()
@@ -9304,6 +9985,7 @@ fn render_node_icon(
 
 fn render_custom_svg_icon(
     icon: &CustomSvgIcon,
+    class: &str,
     cx: f32,
     cy: f32,
     size: f32,
@@ -9331,7 +10013,7 @@ fn render_custom_svg_icon(
     };
 
     Element::group()
-        .class("fm-node-icon-custom")
+        .class(class)
         .transform(&format!(
             "translate({translate_x:.2} {translate_y:.2}) scale({scale:.4})"
         ))
@@ -9344,36 +10026,101 @@ fn render_custom_svg_icon(
         )
 }
 
-fn wrap_text_to_lines(text: &str, max_width: f32, avg_char_width: f32) -> Vec<String> {
-    if text.trim().is_empty() {
-        return Vec::new();
+/// Text width estimation for the renderer's own sizing and wrapping.
+///
+/// Font stacks fm-core has metric tables for are measured per character class at the requested
+/// font size. Unknown fonts fall back to `avg_char_width` per character (two for East Asian wide
+/// characters), since class ratios fitted to other fonts are no better than a flat average.
+#[derive(Debug, Clone)]
+struct TextMeasure {
+    metrics: Option<fm_core::FontMetrics>,
+    avg_char_width: f32,
+    font_size: f32,
+}
+
+/// Wrap widths are compared in tenths of a pixel so the shared wrap engine can work in integers.
+const WRAP_UNITS_PER_PX: f32 = 10.0;
+
+impl TextMeasure {
+    fn new(config: &SvgRenderConfig) -> Self {
+        let metrics = fm_core::FontPreset::from_known_family(&config.font_family).map(|preset| {
+            fm_core::FontMetrics::new(fm_core::FontMetricsConfig {
+                preset,
+                font_size: config.font_size,
+                line_height: config.line_height,
+                fallback_chain: Vec::new(),
+                trace_fallbacks: false,
+            })
+        });
+        Self {
+            font_size: metrics
+                .as_ref()
+                .map_or(config.font_size.max(1.0), fm_core::FontMetrics::font_size),
+            metrics,
+            avg_char_width: config.avg_char_width.max(1.0),
+        }
     }
-    let max_chars = ((max_width / avg_char_width).floor() as usize).max(8);
-    let mut lines = Vec::new();
-    let mut current = String::new();
 
-    for word in text.split_whitespace() {
-        let next_len = if current.is_empty() {
-            word.chars().count()
-        } else {
-            current.chars().count() + 1 + word.chars().count()
-        };
-        if next_len > max_chars && !current.is_empty() {
-            lines.push(current);
-            current = word.to_string();
-        } else {
-            if !current.is_empty() {
-                current.push(' ');
+    /// Width of one line of `text` set at `font_size`.
+    fn width(&self, text: &str, font_size: f32) -> f32 {
+        let base = match &self.metrics {
+            Some(metrics) => metrics.estimate_width(text),
+            None => {
+                text.chars()
+                    .map(|ch| {
+                        if fm_core::is_east_asian_wide(ch) {
+                            2.0
+                        } else {
+                            1.0
+                        }
+                    })
+                    .sum::<f32>()
+                    * self.avg_char_width
             }
-            current.push_str(word);
-        }
+        };
+        base * font_size / self.font_size
     }
 
-    if !current.is_empty() {
-        lines.push(current);
+    /// Average character width at `font_size`.
+    fn char_width(&self, font_size: f32) -> f32 {
+        let base = self
+            .metrics
+            .as_ref()
+            .map_or(self.avg_char_width, fm_core::FontMetrics::avg_char_width);
+        base * font_size / self.font_size
+    }
+
+    /// Wrap `text` set at `font_size` to lines at most `max_width` wide, keeping at most
+    /// `max_lines` lines.
+    fn wrap(
+        &self,
+        text: &str,
+        max_width: f32,
+        font_size: f32,
+        wrap_mode: MermaidWrapMode,
+        max_lines: usize,
+    ) -> Vec<String> {
+        let max_units = (max_width.max(0.0) * WRAP_UNITS_PER_PX).floor() as usize;
+        fm_core::wrap_label(text, wrap_mode, max_units, max_lines, |part| {
+            (self.width(part, font_size) * WRAP_UNITS_PER_PX).ceil() as usize
+        })
     }
+}
 
-    lines
+/// Wrap a free-text block (C4 descriptions) to `max_width`, never narrower than eight average
+/// characters.
+fn wrap_text_to_lines(
+    text: &str,
+    max_width: f32,
+    font_size: f32,
+    measure: &TextMeasure,
+    wrap_mode: MermaidWrapMode,
+) -> Vec<String> {
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+    let max_width = max_width.max(measure.char_width(font_size) * 8.0);
+    measure.wrap(text, max_width, font_size, wrap_mode, usize::MAX)
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -9401,7 +10148,7 @@ fn render_node_label_text(
             config,
             colors.text.as_str(),
             label_style,
-            emit_classdef_classes,
+            emit_classdef_classes.then_some("fm-node-label"),
         );
     }
 
@@ -9434,6 +10181,8 @@ fn render_node_label_text(
     text
 }
 
+/// Render markdown label segments as one `<text>` with a styled `<tspan>` per run. Only the first
+/// run of each line is positioned (`x` and `dy`); the rest follow inline.
 #[allow(clippy::too_many_arguments)]
 fn render_markdown_text_segments(
     segments: &[IrLabelSegment],
@@ -9443,7 +10192,7 @@ fn render_markdown_text_segments(
     config: &SvgRenderConfig,
     fill: &str,
     label_style: Option<&str>,
-    emit_classdef_classes: bool,
+    class: Option<&str>,
 ) -> Element {
     let line_height_px = font_size * config.line_height;
     let monospace_family = "'JetBrains Mono', 'Fira Code', 'SFMono-Regular', Consolas, monospace";
@@ -9455,7 +10204,9 @@ fn render_markdown_text_segments(
         .font_family_unless_embedded_css(&config.font_family, config.embed_theme_css)
         .attr_num("font-size", font_size)
         .fill(fill);
-    text = maybe_add_class(text, "fm-node-label", emit_classdef_classes);
+    if let Some(class) = class {
+        text = text.class(class);
+    }
 
     if let Some(style) = label_style {
         text = text.attr("style", style);
@@ -9477,12 +10228,12 @@ fn render_markdown_text_segments(
                 code,
                 strike,
             } => {
-                let dy = if first_in_line {
-                    if line_index == 0 { 0.0 } else { line_height_px }
-                } else {
-                    0.0
-                };
-                let mut tspan = Element::tspan().x(x).attr_num("dy", dy).content(value);
+                let mut tspan = Element::tspan();
+                if first_in_line {
+                    let dy = if line_index == 0 { 0.0 } else { line_height_px };
+                    tspan = tspan.x(x).attr_num("dy", dy);
+                }
+                tspan = tspan.content(value);
                 if *bold {
                     tspan = tspan.attr("font-weight", "700");
                 }
@@ -9534,7 +10285,7 @@ fn render_c4_legend(
             .y(y)
             .width(box_width)
             .height(box_height)
-            .fill("rgba(248,249,250,0.96)")
+            .fill(&colors.background)
             .stroke(&colors.cluster_stroke)
             .stroke_width(1.0)
             .rx(8.0)
@@ -9643,6 +10394,46 @@ fn visibility_symbol(vis: fm_core::ClassVisibility) -> &'static str {
     }
 }
 
+/// A class member row: visibility marker, name and `: Type`. Abstract and static members are set
+/// apart by style (see [`class_member_extra`]) rather than by a `*` or `$` suffix.
+fn class_member_text(member: &fm_core::IrClassMember) -> String {
+    let vis = visibility_symbol(member.visibility);
+    match member.return_type.as_deref() {
+        Some(ret) => format!("{vis}{}: {ret}", member.name),
+        None => format!("{vis}{}", member.name),
+    }
+}
+
+/// The UML styling of a member row as the attributes [`write_class_text_into`] places before
+/// `fill`: abstract members are italic, static members underlined. Mirrors [`style_class_member`].
+const fn class_member_extra(member: &fm_core::IrClassMember) -> &'static str {
+    match (member.is_abstract, member.is_static) {
+        (false, false) => "",
+        (true, false) => " font-style=\"italic\"",
+        (false, true) => " text-decoration=\"underline\"",
+        (true, true) => " font-style=\"italic\" text-decoration=\"underline\"",
+    }
+}
+
+/// [`class_member_extra`] for the `TextBuilder` slow path.
+fn style_class_member(builder: TextBuilder, member: &fm_core::IrClassMember) -> TextBuilder {
+    let builder = if member.is_abstract {
+        builder.italic()
+    } else {
+        builder
+    };
+    if member.is_static {
+        builder.underline()
+    } else {
+        builder
+    }
+}
+
+/// Whether a class is abstract, which UML shows with an italic name.
+fn is_abstract_class(meta: &fm_core::IrClassNodeMeta) -> bool {
+    matches!(meta.stereotype, Some(fm_core::ClassStereotype::Abstract))
+}
+
 const fn node_shape_css_class(shape: fm_core::NodeShape) -> &'static str {
     use fm_core::NodeShape;
     match shape {
@@ -9688,6 +10479,20 @@ struct EdgeRenderContext<'a> {
     detail: RenderDetailProfile,
     colors: &'a ThemeColors,
     accessible_node_labels: Option<&'a [&'a str]>,
+    node_boxes: &'a [LayoutNodeBox],
+    label_metrics: &'a fm_core::FontMetrics,
+    /// `MermaidDiagramIr::edge_element_ids`, indexed by edge index.
+    edge_ids: &'a [String],
+}
+
+impl<'a> EdgeRenderContext<'a> {
+    /// The DOM id of edge `edge_index`; layout-only edges past the IR fall back to an index id.
+    fn edge_element_id(&self, edge_index: usize) -> Cow<'a, str> {
+        self.edge_ids.get(edge_index).map_or_else(
+            || Cow::Owned(mermaid_edge_element_id("", "", edge_index)),
+            |id| Cow::Borrowed(id.as_str()),
+        )
+    }
 }
 
 /// Serialize a common solid-arrow edge `<path>` directly into raw SVG bytes, **byte-identical** to
@@ -9772,21 +10577,24 @@ fn write_common_edge_path_tail_with_dasharray_into(
         style_class,
         edge_index,
         "",
+        "",
         marker_end,
         dasharray,
     );
 }
 
-/// `EDGE_ID` appends the trailing `id="fm-edge-{index}"` that the slow path's final
-/// `elem.id(&mermaid_edge_element_id(edge_index))` puts on an *unwrapped* edge — the shape a lean
+/// `EDGE_ID` appends the trailing `id="{edge_id}"` that the slow path's final
+/// `elem.id(edge_id)` puts on an *unwrapped* edge — the shape a lean
 /// (`A11yConfig::none()`) edge takes, since it gets no `<g>` wrapper to carry the id. Group-wrapped
 /// callers pass `false`: there the id is the group's, and `Attributes::set` would have placed it last
 /// anyway, which is why it is written after the markers/dasharray here.
+#[allow(clippy::too_many_arguments)]
 fn write_common_edge_path_tail_with_markers_into<const EDGE_ID: bool>(
     f: &mut String,
     stroke_width: f32,
     style_class: &str,
     edge_index: i32,
+    edge_id: &str,
     marker_start: &str,
     marker_end: &str,
     dasharray: &str,
@@ -9814,10 +10622,9 @@ fn write_common_edge_path_tail_with_markers_into<const EDGE_ID: bool>(
         let _ = write_escaped_attr(f, dasharray);
     }
     if EDGE_ID {
-        // `mermaid_edge_element_id(i)` = "fm-edge-" + decimal(i) — no escapable byte, so the same
-        // `Integer` serializer as `data-fm-edge-id` reproduces it exactly.
-        f.push_str("\" id=\"fm-edge-");
-        let _ = AttributeValue::Integer(edge_index).write_value(f);
+        // Edge element ids are only `[a-z0-9-]` — no escapable byte, so they are written verbatim.
+        f.push_str("\" id=\"");
+        f.push_str(edge_id);
     }
     f.push_str("\"/>");
 }
@@ -9852,6 +10659,7 @@ fn build_common_edge_full_fragment<F>(
     stroke_width: f32,
     style_class: &str,
     edge_index: i32,
+    edge_id: &str,
     marker_end: &str,
     from_label: Option<&str>,
     to_label: Option<&str>,
@@ -9870,6 +10678,7 @@ where
         stroke_width,
         style_class,
         edge_index,
+        edge_id,
         "",
         marker_end,
         "",
@@ -9887,10 +10696,10 @@ where
 /// `A11Y` selects the accessibility variant at compile time, mirroring `write_common_node_fragment_into`:
 ///
 /// - `true` (`A11yConfig::full()`, the default profile) emits `<g id … role … tabindex><path/><title/></g>`.
-/// - `false` (`A11yConfig::none()`, the lean profile) emits the **bare `<path …  id="fm-edge-N"/>`** with no
+/// - `false` (`A11yConfig::none()`, the lean profile) emits the **bare `<path …  id="fm-edge-…"/>`** with no
 ///   group and no title — exactly what the slow `Element` path produces when every a11y flag is off: the
 ///   unlabeled-edge `<title>` group at `render_edge`'s tail is skipped, `role`/`tabindex` are skipped, and
-///   the final `elem.id(&mermaid_edge_element_id(edge_index))` lands on the `<path>` itself (last, because
+///   the final `elem.id(edge_id)` lands on the `<path>` itself (last, because
 ///   `Attributes::set` appends).
 ///
 /// Making it a const parameter rather than a runtime flag keeps the default monomorphization exactly as
@@ -9907,6 +10716,7 @@ fn write_common_edge_full_fragment_into<const A11Y: bool, F>(
     stroke_width: f32,
     style_class: &str,
     edge_index: i32,
+    edge_id: &str,
     marker_start: &str,
     marker_end: &str,
     dasharray: &str,
@@ -9918,11 +10728,11 @@ fn write_common_edge_full_fragment_into<const A11Y: bool, F>(
 {
     use crate::attributes::{AttributeValue, write_escaped_text};
     if A11Y {
-        // <g id="fm-edge-N" class="fm-edge" data-fm-edge-id="N" role="graphics-symbol" tabindex="0">
-        // The id is `mermaid_edge_element_id(edge_index)` = "fm-edge-" + decimal(index); it never contains
-        // an escapable byte, so it goes through the same `Integer` serializer as `data-fm-edge-id`.
-        f.push_str("<g id=\"fm-edge-");
-        let _ = AttributeValue::Integer(edge_index).write_value(f);
+        // <g id="fm-edge-…" class="fm-edge" data-fm-edge-id="N" role="graphics-symbol" tabindex="0">
+        // The id is one of `MermaidDiagramIr::edge_element_ids` — only `[a-z0-9-]`, never an escapable
+        // byte — so it is written verbatim.
+        f.push_str("<g id=\"");
+        f.push_str(edge_id);
         f.push_str("\" class=\"fm-edge\" data-fm-edge-id=\"");
         let _ = AttributeValue::Integer(edge_index).write_value(f);
         f.push_str("\" role=\"graphics-symbol\" tabindex=\"0\">");
@@ -9935,6 +10745,7 @@ fn write_common_edge_full_fragment_into<const A11Y: bool, F>(
             stroke_width,
             style_class,
             edge_index,
+            edge_id,
             marker_start,
             marker_end,
             dasharray,
@@ -9953,6 +10764,7 @@ fn write_common_edge_full_fragment_into<const A11Y: bool, F>(
             stroke_width,
             style_class,
             edge_index,
+            edge_id,
             marker_start,
             marker_end,
             dasharray,
@@ -9960,17 +10772,37 @@ fn write_common_edge_full_fragment_into<const A11Y: bool, F>(
     }
 }
 
-/// Compute the rendered edge label (truncated text with optional sequence autonumber prefix) and its
-/// midpoint, exactly as the labeled-edge fragments need them. Shared by `render_edge` (slow/`Element`
-/// path) and `render_edge_into` (streaming path) so both derive byte-identical label text + position.
+/// A rendered edge label: its text, the center of its background, and the background size.
+struct EdgeLabel<'a> {
+    text: Cow<'a, str>,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+/// Layout spaces parallel edges this far apart, so `parallel_offset / step` is the edge's slot.
+const PARALLEL_EDGE_OFFSET_STEP: f32 = 12.0;
+
+/// Compute the rendered edge label (truncated text with optional sequence autonumber prefix), its
+/// placement, and its background size, exactly as the labeled-edge fragments need them. Shared by
+/// `render_edge` (slow/`Element` path) and `render_edge_into` (streaming path) so both derive
+/// byte-identical label text + position.
 fn compute_edge_label<'a>(
-    ir: &'a MermaidDiagramIr,
     edge_path: &LayoutEdgePath,
-    edge_index: usize,
-    detail: RenderDetailProfile,
-    offset_x: f32,
-    offset_y: f32,
-) -> Option<(Cow<'a, str>, f32, f32)> {
+    context: &EdgeRenderContext<'a>,
+) -> Option<EdgeLabel<'a>> {
+    let EdgeRenderContext {
+        ir,
+        offset_x,
+        offset_y,
+        config,
+        detail,
+        node_boxes,
+        label_metrics,
+        ..
+    } = *context;
+    let edge_index = edge_path.edge_index;
     let ir_edge = ir.edges.get(edge_index);
     if detail.show_edge_labels
         && edge_path.points.len() >= 2
@@ -9987,31 +10819,95 @@ fn compute_edge_label<'a>(
         } else {
             base_label
         };
-        let (lx, ly) = if edge_path.points.len() == 4 {
-            let p1 = &edge_path.points[1];
-            let p2 = &edge_path.points[2];
-            (
-                f32::midpoint(p1.x, p2.x) + offset_x,
-                f32::midpoint(p1.y, p2.y) + offset_y - 8.0,
-            )
-        } else if edge_path.points.len() == 2 {
-            let p1 = &edge_path.points[0];
-            let p2 = &edge_path.points[1];
-            (
-                f32::midpoint(p1.x, p2.x) + offset_x,
-                f32::midpoint(p1.y, p2.y) + offset_y - 8.0,
-            )
-        } else {
-            let mid_idx = edge_path.points.len() / 2;
-            let mid_point = &edge_path.points[mid_idx];
-            (mid_point.x + offset_x, mid_point.y + offset_y - 8.0)
-        };
-        Some((label_text, lx, ly))
+        let (width, height) = edge_label_size(
+            &label_text,
+            detail.edge_font_size,
+            config.line_height,
+            label_metrics,
+        );
+        let (x, y) = place_edge_label(edge_path, node_boxes, width, height);
+        Some(EdgeLabel {
+            text: label_text,
+            x: x + offset_x,
+            y: y + offset_y - 8.0,
+            width,
+            height,
+        })
     } else {
         None
     }
 }
 
+/// Background size for an edge label: the widest line as measured by the label font metrics
+/// (scaled to the edge font size) plus padding, and one line height per extra line.
+fn edge_label_size(
+    text: &str,
+    font_size: f32,
+    line_height: f32,
+    metrics: &fm_core::FontMetrics,
+) -> (f32, f32) {
+    let scale = font_size / metrics.config().font_size;
+    let width = metrics.estimate_multiline_width(text) * scale + 8.0 + 20.0;
+    let lines_count = text.lines().count().max(1) as f32;
+    let height = (lines_count - 1.0) * font_size * line_height + font_size + 14.0;
+    (width, height)
+}
+
+/// Pick where an edge label sits on its path, in layout coordinates.
+///
+/// Each segment's midpoint is scored by how far a `width` x `height` box centered there stays
+/// from every node, capped at the label height so that any clear spot counts the same; ties go
+/// to the segment nearest the middle of the path, then the longer one. Parallel edges then slide
+/// their labels along the chosen segment by one label extent per slot so they do not stack.
+fn place_edge_label(
+    edge_path: &LayoutEdgePath,
+    node_boxes: &[LayoutNodeBox],
+    width: f32,
+    height: f32,
+) -> (f32, f32) {
+    let points = &edge_path.points;
+    let middle = (points.len() - 2) as f32 / 2.0;
+    let mut best: Option<(f32, f32, f32, usize)> = None;
+    for (index, pair) in points.windows(2).enumerate() {
+        let (start, end) = (&pair[0], &pair[1]);
+        let length = (end.x - start.x).hypot(end.y - start.y);
+        let (cx, cy) = (f32::midpoint(start.x, end.x), f32::midpoint(start.y, end.y));
+        let clearance = node_boxes
+            .iter()
+            .map(|node| {
+                let bounds = &node.bounds;
+                let gap_x =
+                    (cx - (bounds.x + bounds.width / 2.0)).abs() - (width + bounds.width) / 2.0;
+                let gap_y =
+                    (cy - (bounds.y + bounds.height / 2.0)).abs() - (height + bounds.height) / 2.0;
+                gap_x.max(gap_y)
+            })
+            .fold(height, f32::min);
+        let distance_from_middle = (index as f32 - middle).abs();
+        let better = best.is_none_or(|(best_clearance, best_distance, best_length, _)| {
+            clearance > best_clearance
+                || (clearance == best_clearance
+                    && (distance_from_middle < best_distance
+                        || (distance_from_middle == best_distance && length > best_length)))
+        });
+        if better {
+            best = Some((clearance, distance_from_middle, length, index));
+        }
+    }
+    let index = best.map_or(0, |(.., index)| index);
+    let (start, end) = (&points[index], &points[index + 1]);
+    let (mut x, mut y) = (f32::midpoint(start.x, end.x), f32::midpoint(start.y, end.y));
+    let slot = edge_path.parallel_offset / PARALLEL_EDGE_OFFSET_STEP;
+    let length = (end.x - start.x).hypot(end.y - start.y);
+    if slot != 0.0 && length > 0.0 {
+        let (dx, dy) = ((end.x - start.x) / length, (end.y - start.y) / length);
+        let step = dx.abs() * width + dy.abs() * height + 4.0;
+        x += dx * step * slot;
+        y += dy * step * slot;
+    }
+    (x, y)
+}
+
 /// Stream the whole labeled-`Arrow` edge fragment (`<g><path/><rect/><text/><title/></g>`) directly into
 /// `out`. Shared by `render_edge` (into a fresh String wrapped in `Element::raw_svg`) and
 /// `render_edge_into` (straight into the output buffer, avoiding the per-edge fragment String + `Element`
@@ -10032,6 +10928,7 @@ fn compute_edge_label<'a>(
 fn write_labeled_edge_fragment_into<const A11Y: bool>(
     out: &mut String,
     edge_index: usize,
+    edge_id: &str,
     path_str: &str,
     stroke_width: f32,
     style_class: &str,
@@ -10040,7 +10937,8 @@ fn write_labeled_edge_fragment_into<const A11Y: bool>(
     lx: f32,
     ly: f32,
     label_font_size: f32,
-    avg_char_width: f32,
+    label_width: f32,
+    label_height: f32,
     from_label: Option<&str>,
     to_label: Option<&str>,
     colors: &ThemeColors,
@@ -10048,11 +10946,9 @@ fn write_labeled_edge_fragment_into<const A11Y: bool>(
     use crate::attributes::{
         AttributeValue, write_escaped_attr, write_escaped_text, write_number_into,
     };
-    let label_width = (label_str.chars().count() as f32 * avg_char_width) + 8.0 + 20.0;
-    let label_height = label_font_size + 14.0;
     let start_y = ly + (label_font_size / 4.0);
-    out.push_str("<g id=\"fm-edge-");
-    let _ = AttributeValue::Integer(edge_index as i32).write_value(out);
+    out.push_str("<g id=\"");
+    out.push_str(edge_id);
     out.push_str("\" class=\"fm-edge-labeled\" data-fm-edge-id=\"");
     let _ = AttributeValue::Integer(edge_index as i32).write_value(out);
     if A11Y {
@@ -10116,9 +11012,11 @@ fn render_edge(edge_path: &LayoutEdgePath, context: &EdgeRenderContext<'_>) -> E
         detail,
         colors,
         accessible_node_labels,
+        ..
     } = *context;
 
     let edge_index = edge_path.edge_index;
+    let edge_id = context.edge_element_id(edge_index);
     let ir_edge = ir.edges.get(edge_index);
     let arrow = ir_edge.map_or(ArrowType::Arrow, |e| e.arrow);
     let is_back_edge = edge_path.reversed;
@@ -10137,106 +11035,34 @@ fn render_edge(edge_path: &LayoutEdgePath, context: &EdgeRenderContext<'_>) -> E
             &colors.cluster_stroke,
         )
     } else {
-        match arrow {
-            ArrowType::Line | ArrowType::ThickLine => (None, None, None, &colors.edge),
-            ArrowType::Arrow => (None, None, Some("url(#arrow-end)"), &colors.edge),
-            ArrowType::OpenArrow => (None, None, Some("url(#arrow-open)"), &colors.edge),
-            ArrowType::HalfArrowTop => (None, None, Some("url(#arrow-half-top)"), &colors.edge),
-            ArrowType::HalfArrowBottom => {
-                (None, None, Some("url(#arrow-half-bottom)"), &colors.edge)
-            }
-            ArrowType::HalfArrowTopReverse => {
-                (None, Some("url(#arrow-half-bottom)"), None, &colors.edge)
-            }
-            ArrowType::HalfArrowBottomReverse => {
-                (None, Some("url(#arrow-half-top)"), None, &colors.edge)
-            }
-            ArrowType::StickArrowTop => (None, None, Some("url(#arrow-stick-top)"), &colors.edge),
-            ArrowType::StickArrowBottom => {
-                (None, None, Some("url(#arrow-stick-bottom)"), &colors.edge)
-            }
-            ArrowType::StickArrowTopReverse => {
-                (None, Some("url(#arrow-stick-bottom)"), None, &colors.edge)
-            }
-            ArrowType::StickArrowBottomReverse => {
-                (None, Some("url(#arrow-stick-top)"), None, &colors.edge)
-            }
-            ArrowType::ThickArrow => (None, None, Some("url(#arrow-filled)"), &colors.edge),
-            ArrowType::DottedArrow => (Some("5,5"), None, Some("url(#arrow-end)"), &colors.edge),
-            ArrowType::DottedOpenArrow => {
-                (Some("5,5"), None, Some("url(#arrow-open)"), &colors.edge)
-            }
-            ArrowType::DottedCross => (Some("5,5"), None, Some("url(#arrow-cross)"), &colors.edge),
-            ArrowType::HalfArrowTopDotted => (
-                Some("5,5"),
-                None,
-                Some("url(#arrow-half-top)"),
-                &colors.edge,
-            ),
-            ArrowType::HalfArrowBottomDotted => (
-                Some("5,5"),
-                None,
-                Some("url(#arrow-half-bottom)"),
-                &colors.edge,
-            ),
-            ArrowType::HalfArrowTopReverseDotted => (
-                Some("5,5"),
-                Some("url(#arrow-half-bottom)"),
-                None,
-                &colors.edge,
-            ),
-            ArrowType::HalfArrowBottomReverseDotted => (
-                Some("5,5"),
-                Some("url(#arrow-half-top)"),
-                None,
-                &colors.edge,
-            ),
-            ArrowType::StickArrowTopDotted => (
-                Some("5,5"),
-                None,
-                Some("url(#arrow-stick-top)"),
-                &colors.edge,
-            ),
-            ArrowType::StickArrowBottomDotted => (
-                Some("5,5"),
-                None,
-                Some("url(#arrow-stick-bottom)"),
-                &colors.edge,
-            ),
-            ArrowType::StickArrowTopReverseDotted => (
-                Some("5,5"),
-                Some("url(#arrow-stick-bottom)"),
-                None,
-                &colors.edge,
-            ),
-            ArrowType::StickArrowBottomReverseDotted => (
-                Some("5,5"),
-                Some("url(#arrow-stick-top)"),
-                None,
-                &colors.edge,
-            ),
-            ArrowType::Circle => (None, None, Some("url(#arrow-circle)"), &colors.edge),
-            ArrowType::Cross => (None, None, Some("url(#arrow-cross)"), &colors.edge),
-            ArrowType::DottedLine => (Some("5,5"), None, None, &colors.edge),
-            ArrowType::DoubleArrow => (
-                None,
-                Some("url(#arrow-start)"),
-                Some("url(#arrow-end)"),
-                &colors.edge,
-            ),
-            ArrowType::DoubleThickArrow => (
-                None,
-                Some("url(#arrow-start-filled)"),
-                Some("url(#arrow-filled)"),
-                &colors.edge,
-            ),
-            ArrowType::DoubleDottedArrow => (
-                Some("5,5"),
-                Some("url(#arrow-start)"),
-                Some("url(#arrow-end)"),
-                &colors.edge,
-            ),
-        }
+        let (dasharray, marker_start, marker_end) = arrow_markers(arrow);
+        (dasharray, marker_start, marker_end, &colors.edge)
+    };
+
+    // Class-diagram relations carry their UML marker on the end the operator marks, and ER
+    // relationships a crow's foot on each end; the arrow type still decides solid vs dashed.
+    let (marker_start, marker_end) =
+        if let Some(relation) = ir_edge.and_then(|edge| edge.class_relation()) {
+            class_relation_markers(relation)
+        } else if let Some(notation) = ir_edge.and_then(|edge| edge.er_notation()) {
+            er_relation_markers(notation)
+        } else {
+            (marker_start, marker_end)
+        };
+    // A stroke color from the edge's style carries over to its arrowheads through the marker
+    // variants drawn in that color.
+    let stroke_markers = ir_edge
+        .filter(|edge| edge.class_relation().is_none() && edge.er_notation().is_none())
+        .and_then(|_| edge_stroke_color(ir, edge_index))
+        .map(|color| {
+            (
+                marker_start.map(|url| stroke_colored_marker_url(url, &color)),
+                marker_end.map(|url| stroke_colored_marker_url(url, &color)),
+            )
+        });
+    let (marker_start, marker_end) = match &stroke_markers {
+        Some((start, end)) => (start.as_deref(), end.as_deref()),
+        None => (marker_start, marker_end),
     };
 
     let stroke_width = match arrow {
@@ -10323,6 +11149,7 @@ fn render_edge(edge_path: &LayoutEdgePath, context: &EdgeRenderContext<'_>) -> E
             stroke_width,
             style_class,
             edge_index as i32,
+            &edge_id,
             marker_end_val,
             from_label,
             to_label,
@@ -10336,26 +11163,28 @@ fn render_edge(edge_path: &LayoutEdgePath, context: &EdgeRenderContext<'_>) -> E
     // Extract the rendered label (text + midpoint) once, up front, so the labeled fast fragment below
     // can return before the `elem` path-`Element` is built. Shared with `render_edge_into` via
     // `compute_edge_label` so the streaming path derives byte-identical text + position.
-    let edge_label = compute_edge_label(ir, edge_path, edge_index, detail, offset_x, offset_y);
+    let edge_label = compute_edge_label(edge_path, context);
 
     // Whole labeled-edge fast fragment, hoisted above `elem`: for the common single-line solid-`Arrow`
     // label under embedded CSS + default a11y, stream `<g><path/><rect/><text/><title/></g>` and RETURN
     // before `elem` is built (it would be discarded here). Byte-identical to the slow Element path
     // (pinned by `golden_svg_test` + `edge_fast_full_fragment_matches_render`); other cases build `elem`.
-    if let Some((label_text, lx, ly)) = &edge_label {
-        let label_str = label_text.as_ref();
+    if let Some(label) = &edge_label {
+        let label_str = label.text.as_ref();
         if config.embed_theme_css
             && config.a11y.aria_labels
             && config.a11y.keyboard_nav
             && config.a11y.text_alternatives
             && !config.animations_enabled
             && !config.include_source_spans
+            && !config.layers
             && !is_back_edge
             && arrow == ArrowType::Arrow
             && marker_start.is_none()
             && base_dasharray.is_none()
             && !label_str.contains('\n')
             && resolve_edge_inline_style(ir, edge_index).is_none()
+            && edge_label_markup(ir, ir_edge, label_str).is_none()
             && let Some(marker_end_val) = marker_end
             && let Some(edge) = ir_edge
         {
@@ -10366,15 +11195,17 @@ fn render_edge(edge_path: &LayoutEdgePath, context: &EdgeRenderContext<'_>) -> E
             write_labeled_edge_fragment_into::<true>(
                 &mut f,
                 edge_index,
+                &edge_id,
                 &path_str,
                 stroke_width,
                 style_class,
                 marker_end_val,
                 label_str,
-                *lx,
-                *ly,
+                label.x,
+                label.y,
                 label_font_size,
-                config.avg_char_width,
+                label.width,
+                label.height,
                 from_label,
                 to_label,
                 colors,
@@ -10458,9 +11289,9 @@ fn render_edge(edge_path: &LayoutEdgePath, context: &EdgeRenderContext<'_>) -> E
     // If edge has a label, wrap in group with text. `edge_label` was extracted up front and the
     // labeled fast fragment already returned for the common single-line solid-`Arrow` case; this is
     // the Element slow path for the labeled edges the fragment does not cover.
-    if let Some((label_text, lx, ly)) = edge_label {
+    if let Some(label) = edge_label {
         let mut group = Element::group()
-            .id(&mermaid_edge_element_id(edge_index))
+            .id(&edge_id)
             .class("fm-edge-labeled")
             .attr_int("data-fm-edge-id", edge_index as i32);
         if let Some(extra) = animation_style.as_deref() {
@@ -10482,29 +11313,17 @@ fn render_edge(edge_path: &LayoutEdgePath, context: &EdgeRenderContext<'_>) -> E
         group = group.child(elem);
 
         // Add background rect for label
-        let label_text = label_text.as_ref();
-        let lines_count = label_text.lines().count().max(1) as f32;
-        let max_line_len = label_text
-            .lines()
-            .map(|l| l.chars().count())
-            .max()
-            .unwrap_or(0);
-        let label_text_width = (max_line_len as f32 * config.avg_char_width) + 8.0;
-        let label_padding_x = 10.0;
-        let label_width = label_text_width + (label_padding_x * 2.0);
-
+        let label_text = label.text.as_ref();
         let label_font_size = detail.edge_font_size;
-        let total_text_height = (lines_count - 1.0) * label_font_size * config.line_height;
-        let label_height = total_text_height + label_font_size + 14.0;
-
-        let start_y = ly - (total_text_height / 2.0) + (label_font_size / 4.0);
+        let total_text_height = label.height - label_font_size - 14.0;
+        let start_y = label.y - (total_text_height / 2.0) + (label_font_size / 4.0);
 
         group = group.child(
             Element::rect()
-                .x(lx - label_width / 2.0)
-                .y(ly - label_height / 2.0 - 1.0)
-                .width(label_width)
-                .height(label_height)
+                .x(label.x - label.width / 2.0)
+                .y(label.y - label.height / 2.0 - 1.0)
+                .width(label.width)
+                .height(label.height)
                 .fill(&colors.background)
                 .stroke(&colors.cluster_stroke)
                 .stroke_width(0.75)
@@ -10512,18 +11331,31 @@ fn render_edge(edge_path: &LayoutEdgePath, context: &EdgeRenderContext<'_>) -> E
                 .ry(6.0),
         );
 
-        // Add label text
+        // Add label text, styled when the label carries markdown and is shown in full.
         group = group.child(
-            TextBuilder::new(label_text)
-                .x(lx)
-                .y(start_y)
-                .font_family_unless_embedded_css(&config.font_family, config.embed_theme_css)
-                .font_size(label_font_size)
-                .line_height(config.line_height)
-                .anchor(TextAnchor::Middle)
-                .fill(&colors.text)
-                .class("edge-label")
-                .build(),
+            if let Some(segments) = edge_label_markup(ir, ir_edge, label_text) {
+                render_markdown_text_segments(
+                    segments,
+                    label.x,
+                    start_y,
+                    label_font_size,
+                    config,
+                    &colors.text,
+                    None,
+                    Some("edge-label"),
+                )
+            } else {
+                TextBuilder::new(label_text)
+                    .x(label.x)
+                    .y(start_y)
+                    .font_family_unless_embedded_css(&config.font_family, config.embed_theme_css)
+                    .font_size(label_font_size)
+                    .line_height(config.line_height)
+                    .anchor(TextAnchor::Middle)
+                    .fill(&colors.text)
+                    .class("edge-label")
+                    .build()
+            },
         );
 
         // Add title element for text alternatives
@@ -10549,7 +11381,7 @@ fn render_edge(edge_path: &LayoutEdgePath, context: &EdgeRenderContext<'_>) -> E
         let edge_desc = crate::a11y::describe_edge_labels(from_label, to_label, arrow, None);
         // Wrap in group to add title
         let mut group = Element::group()
-            .id(&mermaid_edge_element_id(edge_index))
+            .id(&edge_id)
             .class("fm-edge")
             .attr_int("data-fm-edge-id", edge_index as i32);
         if let Some(extra) = animation_style.as_deref() {
@@ -10577,7 +11409,7 @@ fn render_edge(edge_path: &LayoutEdgePath, context: &EdgeRenderContext<'_>) -> E
         elem = elem.attr("tabindex", "0");
     }
 
-    elem = elem.id(&mermaid_edge_element_id(edge_index));
+    elem = elem.id(&edge_id);
 
     elem
 }
@@ -10601,12 +11433,21 @@ fn render_edge_into(out: &mut String, edge_path: &LayoutEdgePath, context: &Edge
         detail,
         colors,
         accessible_node_labels,
+        ..
     } = *context;
     let edge_index = edge_path.edge_index;
+    let edge_id = context.edge_element_id(edge_index);
     let ir_edge = ir.edges.get(edge_index);
     let arrow = ir_edge.map_or(ArrowType::Arrow, |edge| edge.arrow);
     let is_back_edge = edge_path.reversed;
 
+    // UML class relations and ER relationships pick their markers from the relation, not the
+    // arrow type the fast fragments below are keyed on.
+    if ir_edge.is_some_and(|edge| edge.class_relation().is_some() || edge.er_notation().is_some()) {
+        render_edge(edge_path, context).write_to_string(out);
+        return;
+    }
+
     // Stream the labeled-`Arrow` fast fragment straight into `out` instead of falling through to
     // `render_edge(..).write_to_string(out)`, which builds the fragment String + an `Element::raw_svg`
     // then COPIES it in (a per-labeled-edge double-copy — sequence messages / ER-class relationships).
@@ -10634,11 +11475,13 @@ fn render_edge_into(out: &mut String, edge_path: &LayoutEdgePath, context: &Edge
         && !config.animations_enabled
         && !config.include_source_spans
         && let Some(edge) = ir_edge
-        && let Some((label_text, lx, ly)) =
-            compute_edge_label(ir, edge_path, edge_index, detail, offset_x, offset_y)
+        && let Some(label) = compute_edge_label(edge_path, context)
     {
-        let label_str = label_text.as_ref();
-        if !label_str.contains('\n') && resolve_edge_inline_style(ir, edge_index).is_none() {
+        let label_str = label.text.as_ref();
+        if !label_str.contains('\n')
+            && resolve_edge_inline_style(ir, edge_index).is_none()
+            && edge_label_markup(ir, ir_edge, label_str).is_none()
+        {
             let path_str = smooth_layout_edge_path(edge_path, offset_x, offset_y);
             if a11y {
                 let (from_label, to_label) =
@@ -10646,15 +11489,17 @@ fn render_edge_into(out: &mut String, edge_path: &LayoutEdgePath, context: &Edge
                 write_labeled_edge_fragment_into::<true>(
                     out,
                     edge_index,
+                    &edge_id,
                     &path_str,
                     1.8,
                     "fm-edge-solid",
                     "url(#arrow-end)",
                     label_str,
-                    lx,
-                    ly,
+                    label.x,
+                    label.y,
                     detail.edge_font_size,
-                    config.avg_char_width,
+                    label.width,
+                    label.height,
                     from_label,
                     to_label,
                     colors,
@@ -10665,15 +11510,17 @@ fn render_edge_into(out: &mut String, edge_path: &LayoutEdgePath, context: &Edge
                 write_labeled_edge_fragment_into::<false>(
                     out,
                     edge_index,
+                    &edge_id,
                     &path_str,
                     1.8,
                     "fm-edge-solid",
                     "url(#arrow-end)",
                     label_str,
-                    lx,
-                    ly,
+                    label.x,
+                    label.y,
                     detail.edge_font_size,
-                    config.avg_char_width,
+                    label.width,
+                    label.height,
                     None,
                     None,
                     colors,
@@ -10952,6 +11799,7 @@ fn render_edge_into(out: &mut String, edge_path: &LayoutEdgePath, context: &Edge
                 stroke_width,
                 style_class,
                 edge_index as i32,
+                &edge_id,
                 marker_start,
                 marker_end,
                 dasharray,
@@ -10969,6 +11817,7 @@ fn render_edge_into(out: &mut String, edge_path: &LayoutEdgePath, context: &Edge
                 stroke_width,
                 style_class,
                 edge_index as i32,
+                &edge_id,
                 marker_start,
                 marker_end,
                 dasharray,
@@ -10983,6 +11832,18 @@ fn render_edge_into(out: &mut String, edge_path: &LayoutEdgePath, context: &Edge
     render_edge(edge_path, context).write_to_string(out);
 }
 
+/// The markdown segments of an edge label, unless the rendered text was truncated or numbered.
+fn edge_label_markup<'a>(
+    ir: &'a MermaidDiagramIr,
+    edge: Option<&fm_core::IrEdge>,
+    rendered_text: &str,
+) -> Option<&'a [IrLabelSegment]> {
+    let label_id = edge?.label?;
+    let segments = ir.label_markup.get(&label_id)?;
+    (!segments.is_empty() && ir.labels.get(label_id.0)?.text == rendered_text)
+        .then_some(segments.as_slice())
+}
+
 fn edge_endpoint_accessible_labels<'a>(
     edge: &fm_core::IrEdge,
     ir: &'a MermaidDiagramIr,
@@ -11024,7 +11885,7 @@ mod tests {
         let config = SvgRenderConfig::default();
         let svg = render_svg_with_config(&ir, &config);
         let expected = concat!(
-            "<g id=\"fm-node-n0-0\" class=\"fm-node fm-node-accent-4 fm-node-shape-rect\" ",
+            "<g id=\"fm-node-n0-84e6f533\" class=\"fm-node fm-node-accent-4 fm-node-shape-rect\" ",
             "data-id=\"N0\" role=\"graphics-symbol\" aria-label=\"Single Node\" tabindex=\"0\">",
             "<rect x=\"92\" y=\"92\" width=\"148.73\" height=\"66.50\" rx=\"5.50\" ",
             "fill=\"url(#fm-node-gradient)\"/>",
@@ -11039,6 +11900,53 @@ mod tests {
         );
     }
 
+    #[test]
+    fn focus_order_config_keeps_tab_stops_in_page_order() {
+        let ir = create_ir_with_single_node("N0", NodeShape::Rect);
+        let mut config = SvgRenderConfig::default();
+        config.a11y.focus_order = true;
+        let svg = render_svg_with_config(&ir, &config);
+        assert!(svg.contains("aria-label=\"Single Node\" tabindex=\"0\">"));
+        assert!(!svg.contains("tabindex=\"1\""));
+        assert!(!svg.contains("aria-flowto"));
+    }
+
+    struct ChevronShape;
+
+    impl fm_core::NodeShapeDrawer for ChevronShape {
+        fn svg_path(&self, x: f32, y: f32, width: f32, height: f32) -> String {
+            format!(
+                "M{x:.1},{y:.1} L{:.1},{:.1} L{x:.1},{:.1} Z",
+                x + width,
+                y + height / 2.0,
+                y + height
+            )
+        }
+    }
+
+    #[test]
+    fn registered_shape_drawer_replaces_builtin_geometry() {
+        let mut ir = create_ir_with_single_node("N0", NodeShape::Rect);
+        ir.nodes[0].custom_shape = Some(String::from("chevron"));
+        let mut config = SvgRenderConfig::default();
+        let builtin = render_svg_with_config(&ir, &config);
+        assert!(builtin.contains("<rect x=\"92\" y=\"92\""));
+
+        config
+            .shape_registry
+            .register("chevron", std::sync::Arc::new(ChevronShape));
+        let svg = render_svg_with_config(&ir, &config);
+        assert!(svg.contains("d=\"M92.0,92.0 L240.7,125.2 L92.0,158.5 Z\""));
+        assert!(!svg.contains("<rect x=\"92\" y=\"92\""));
+        assert!(svg.contains("Single Node</text>"));
+
+        // Registering under a built-in name overrides that shape for every node using it.
+        ir.nodes[0].custom_shape = None;
+        config.shape_registry =
+            fm_core::NodeShapeRegistry::new().with("rect", std::sync::Arc::new(ChevronShape));
+        assert!(render_svg_with_config(&ir, &config).contains("L240.7,125.2"));
+    }
+
     /// The lean (`A11yConfig::none()`) node now streams through the same fast path. These bytes are the
     /// ones the slow `Element` path produced before the lean monomorphization existed -- verified at the
     /// time by rendering the whole 13-item head-to-head corpus with both builds and comparing SHA-256s.
@@ -11054,7 +11962,7 @@ mod tests {
         };
         let svg = render_svg_with_config(&ir, &config);
         let expected = concat!(
-            "<g id=\"fm-node-n0-0\" class=\"fm-node fm-node-accent-4 fm-node-shape-rect\" ",
+            "<g id=\"fm-node-n0-84e6f533\" class=\"fm-node fm-node-accent-4 fm-node-shape-rect\" ",
             "data-id=\"N0\">",
             "<rect x=\"92\" y=\"92\" width=\"148.73\" height=\"66.50\" rx=\"5.50\" ",
             "fill=\"url(#fm-node-gradient)\"/>",
@@ -11610,6 +12518,7 @@ mod tests {
                      from_label: Option<&str>,
                      to_label: Option<&str>| {
             let d = crate::path::build_smooth_path_by(points.len(), |i| points[i]);
+            let edge_id = fm_core::mermaid_edge_element_id("a", "b", idx as usize);
             let desc =
                 crate::a11y::describe_edge_labels(from_label, to_label, ArrowType::Arrow, None);
             let path_child = Element::raw_svg(build_common_edge_fragment(
@@ -11620,7 +12529,7 @@ mod tests {
                 "url(#arrow-end)",
             ));
             let group = Element::group()
-                .id(&fm_core::mermaid_edge_element_id(idx as usize))
+                .id(&edge_id)
                 .class("fm-edge")
                 .attr_int("data-fm-edge-id", idx)
                 .attr("role", "graphics-symbol")
@@ -11635,6 +12544,7 @@ mod tests {
                 sw,
                 style,
                 idx,
+                &edge_id,
                 "url(#arrow-end)",
                 from_label,
                 to_label,
@@ -11727,6 +12637,9 @@ mod tests {
                 detail,
                 colors: &colors,
                 accessible_node_labels: None,
+                node_boxes: &[],
+                label_metrics: &config.font_metrics(),
+                edge_ids: &ir.edge_element_ids(),
             };
 
             let mut streamed = String::new();
@@ -11802,6 +12715,9 @@ mod tests {
                 detail,
                 colors: &colors,
                 accessible_node_labels: None,
+                node_boxes: &[],
+                label_metrics: &config.font_metrics(),
+                edge_ids: &ir.edge_element_ids(),
             };
 
             let mut streamed = String::new();
@@ -11872,7 +12788,7 @@ mod tests {
     /// remains the live slow path here (`render_edge_into` delegates to it for every gated-out edge). So
     /// this asserts the streamed lean fragment against what the `Element` path *actually* produces, across
     /// the arrow matrix: bare `<path>` (no `<g>`), no `<title>`, no `role`/`tabindex`, and the trailing
-    /// `id="fm-edge-N"` that the slow path's final `elem.id(..)` appends to an unwrapped edge.
+    /// edge `id` that the slow path's final `elem.id(..)` appends to an unwrapped edge.
     #[test]
     fn lean_edge_streaming_matches_element_render() {
         let config = SvgRenderConfig {
@@ -11903,6 +12819,9 @@ mod tests {
                 detail,
                 colors: &colors,
                 accessible_node_labels: None,
+                node_boxes: &[],
+                label_metrics: &config.font_metrics(),
+                edge_ids: &ir.edge_element_ids(),
             };
 
             let mut streamed = String::new();
@@ -11915,8 +12834,9 @@ mod tests {
                 streamed, element_rendered,
                 "streamed lean edge must match Element render for {arrow:?}"
             );
+            let id_attr = format!("id=\"{}\"/>", ir.edge_element_ids()[0]);
             assert!(
-                streamed.starts_with("<path d=\"") && streamed.ends_with("id=\"fm-edge-0\"/>"),
+                streamed.starts_with("<path d=\"") && streamed.ends_with(&id_attr),
                 "lean edge is a bare <path> carrying the id: {streamed}"
             );
             for banned in ["<g ", "<title>", "role=", "tabindex="] {
@@ -12075,6 +12995,9 @@ mod tests {
             detail,
             colors: &colors,
             accessible_node_labels: None,
+            node_boxes: &[],
+            label_metrics: &config.font_metrics(),
+            edge_ids: &ir.edge_element_ids(),
         };
 
         let mut streamed = String::new();
@@ -12125,6 +13048,117 @@ mod tests {
         assert_eq!(truncated.as_ref(), "abc…");
     }
 
+    #[test]
+    fn wrapped_text_follows_the_configured_wrap_mode() {
+        // An unknown font measures a flat 7.5px per character, so 60px holds eight.
+        let measure = TextMeasure::new(&SvgRenderConfig {
+            font_family: String::from("Comic Neue"),
+            ..SvgRenderConfig::default()
+        });
+        let text = "a verylongidentifiername here";
+        assert_eq!(
+            wrap_text_to_lines(text, 60.0, 15.0, &measure, MermaidWrapMode::WordChar),
+            ["a", "verylong", "identifi", "ername", "here"]
+        );
+        assert_eq!(
+            wrap_text_to_lines(text, 60.0, 15.0, &measure, MermaidWrapMode::Word),
+            ["a", "verylon…", "here"]
+        );
+        assert!(
+            wrap_text_to_lines("  ", 60.0, 15.0, &measure, MermaidWrapMode::WordChar).is_empty()
+        );
+    }
+
+    #[test]
+    fn text_measure_uses_font_metric_tables_and_estimates_unknown_fonts() {
+        let measure = TextMeasure::new(&SvgRenderConfig::default());
+        assert!(measure.metrics.is_some());
+        assert!(measure.width("WWWW", 15.0) > measure.width("iiii", 15.0) * 3.0);
+        assert!((measure.width("WWWW", 7.5) * 2.0 - measure.width("WWWW", 15.0)).abs() < 1e-3);
+        assert_eq!(
+            measure.wrap(
+                "iiii iiii iiii",
+                60.0,
+                15.0,
+                MermaidWrapMode::Word,
+                usize::MAX
+            ),
+            ["iiii iiii iiii"]
+        );
+        assert_eq!(
+            measure.wrap(
+                "WWWW WWWW WWWW",
+                60.0,
+                15.0,
+                MermaidWrapMode::Word,
+                usize::MAX
+            ),
+            ["WWWW", "WWWW", "WWWW"]
+        );
+
+        let fallback = TextMeasure::new(&SvgRenderConfig {
+            font_family: String::from("Comic Neue"),
+            ..SvgRenderConfig::default()
+        });
+        assert!(fallback.metrics.is_none());
+        assert!((fallback.width("iW", 15.0) - 15.0).abs() < 1e-3);
+        assert!((fallback.width("漢字", 15.0) - 30.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn class_diagrams_draw_uml_compartments_and_relation_markers() {
+        let parsed = fm_parser::parse(
+            "classDiagram\n  class Shape {\n    <<abstract>>\n    +String name$\n    +area()*\n  }\n  class Point {\n    +move()\n  }\n  Shape <|-- Circle\n  Circle *-- Point",
+        );
+        let svg = render_svg(&parsed.ir);
+
+        // Abstract class names are italic; static members underlined and abstract ones italic,
+        // without the `$` / `*` source markers.
+        assert!(
+            svg.contains("font-weight=\"bold\" font-style=\"italic\""),
+            "{svg}"
+        );
+        assert!(svg.contains("text-decoration=\"underline\""));
+        assert!(svg.contains(">+String name</text>") && svg.contains(">+area()</text>"));
+        assert!(!svg.contains("area()*"));
+        // Point has no attributes but still gets both compartments.
+        assert!(svg.matches("<line").count() >= 4, "{svg}");
+
+        assert!(svg.contains("marker-start=\"url(#uml-triangle-start)\""));
+        assert!(svg.contains("marker-start=\"url(#uml-composition-start)\""));
+        assert!(svg.contains("id=\"uml-triangle-start\""));
+        assert!(!svg.contains("id=\"uml-aggregation\""));
+    }
+
+    #[test]
+    fn er_diagrams_draw_entity_tables_and_crows_feet() {
+        let parsed = fm_parser::parse(
+            "erDiagram\n  CUSTOMER ||--o{ ORDER : places\n  CUSTOMER {\n    int id PK\n    string name\n  }\n  ORDER {\n    int id PK\n    int customer_id FK\n  }",
+        );
+        let streamed = render_svg(&parsed.ir);
+        let attribute_driven = render_svg_with_config(
+            &parsed.ir,
+            &SvgRenderConfig {
+                embed_theme_css: false,
+                ..SvgRenderConfig::default()
+            },
+        );
+        for svg in [&streamed, &attribute_driven] {
+            // The second row of each entity is banded; keys are badges, not text prefixes.
+            assert_eq!(svg.matches("class=\"fm-er-row\"").count(), 2, "{svg}");
+            assert_eq!(svg.matches("class=\"fm-er-key-badge\"").count(), 3);
+            assert!(svg.contains(">PK</text>") && svg.contains(">FK</text>"));
+            assert!(svg.contains(">int customer_id</text>"));
+            assert!(!svg.contains("PK int"));
+
+            assert!(svg.contains("marker-start=\"url(#er-one-start)\""));
+            assert!(svg.contains("marker-end=\"url(#er-zero-many)\""));
+            assert!(svg.contains("id=\"er-zero-many\""));
+            assert!(!svg.contains("id=\"er-one-many\""));
+            assert!(!svg.contains("url(#arrow-end)"));
+        }
+    }
+
     #[test]
     fn plain_node_label_fast_path_matches_text_builder_output() {
         let ir = MermaidDiagramIr::empty(DiagramType::Flowchart);
@@ -12690,6 +13724,59 @@ mod tests {
         assert!(scene_svg.contains("fm-source-kind=\"node\""));
     }
 
+    #[test]
+    fn scene_backend_paints_nodes_and_edges_from_the_theme() {
+        let mut ir = create_ir_with_labeled_edge();
+        ir.meta
+            .theme_overrides
+            .theme_variables
+            .insert("primaryColor".to_string(), "#fde68a".to_string());
+        let config = SvgRenderConfig {
+            backend: SvgBackend::Scene,
+            ..Default::default()
+        };
+
+        let svg = render_svg_with_config(&ir, &config);
+        assert!(
+            svg.contains("fill=\"var(--fm-node-fill, #fde68a)\""),
+            "{svg}"
+        );
+        assert!(svg.contains("stroke=\"var(--fm-edge-color, #94a3b8)\""));
+
+        let svg = render_svg_with_config(
+            &ir,
+            &SvgRenderConfig {
+                embed_theme_css: false,
+                theme: ThemePreset::Dark,
+                ..config
+            },
+        );
+        let dark = ThemeColors::from_preset(ThemePreset::Dark);
+        assert!(svg.contains("fill=\"#fde68a\""));
+        assert!(svg.contains(&format!("stroke=\"{}\"", dark.node_stroke)));
+        assert!(!svg.contains("var(--fm-"));
+    }
+
+    #[test]
+    fn interactive_config_embeds_the_pan_zoom_script() {
+        let ir = create_ir_with_labeled_edge();
+        let svg = render_svg_with_config(&ir, &SvgRenderConfig::default());
+        assert!(!svg.contains("<script"));
+
+        let config = SvgRenderConfig {
+            interactive: true,
+            ..Default::default()
+        };
+        let svg = render_svg_with_config(&ir, &config);
+        assert!(svg.contains(".fm-node-hover"));
+        let script_at = svg.find("<script><![CDATA[").expect("script block");
+        assert!(svg[script_at..].ends_with("]]></script></svg>"));
+        assert!(
+            svg.find("data-id=")
+                .is_some_and(|node_at| node_at < script_at)
+        );
+    }
+
     #[test]
     fn render_scene_to_svg_emits_paths_text_and_source_metadata() {
         let scene = create_scene_with_path_and_text();
@@ -12889,6 +13976,115 @@ mod tests {
         assert!(!svg.contains("50.0%"));
     }
 
+    #[test]
+    fn pie_wedges_carry_their_percentage() {
+        let mut ir = create_pie_ir(false);
+        if let Some(meta) = ir.pie_meta.as_mut() {
+            meta.slices.push(IrPieSlice {
+                label: "Other".to_string(),
+                value: 2.0,
+            });
+        }
+        let svg = render_svg(&ir);
+
+        assert!(svg.contains("class=\"fm-pie-percent\">49%</text>"), "{svg}");
+        assert!(svg.contains("class=\"fm-pie-percent\">29%</text>"));
+        assert!(svg.contains("class=\"fm-pie-percent\">20%</text>"));
+        // A 2% sliver is too thin to hold its label.
+        assert_eq!(svg.matches("fm-pie-percent").count(), 3);
+    }
+
+    #[test]
+    fn quadrant_points_take_the_color_of_their_quadrant() {
+        let parsed = fm_parser::parse(
+            "quadrantChart\n  quadrant-1 Expand\n  A: [0.8, 0.9]\n  B: [0.2, 0.1]\n  C: [0.9, 0.2]",
+        );
+        let svg = render_svg(&parsed.ir);
+        let accents = ThemeColors::default().accents;
+
+        for accent in [&accents[0], &accents[2], &accents[3]] {
+            assert!(svg.contains(&format!("r=\"6\" fill=\"{accent}\"")), "{svg}");
+        }
+        assert!(!svg.contains(&format!("r=\"6\" fill=\"{}\"", accents[1])));
+    }
+
+    #[test]
+    fn edge_labels_avoid_nodes_and_spread_parallel_edges() {
+        let (_, mut edge_path) = single_edge_fixture(ArrowType::Arrow);
+        let blocker = LayoutNodeBox {
+            node_index: 2,
+            node_id: "C".to_string(),
+            rank: 1,
+            order: 0,
+            span: Span::default(),
+            bounds: fm_layout::LayoutRect {
+                x: 40.0,
+                y: 30.0,
+                width: 24.0,
+                height: 36.0,
+            },
+        };
+
+        // The middle segment runs through `C`, so the label moves to a clear segment.
+        assert_eq!(place_edge_label(&edge_path, &[], 40.0, 26.0), (52.0, 48.0));
+        let (x, y) = place_edge_label(&edge_path, std::slice::from_ref(&blocker), 40.0, 26.0);
+        assert_eq!((x, y), (16.0, 24.0));
+
+        // Parallel edges slide their labels apart along the segment instead of stacking.
+        edge_path.parallel_offset = -6.0;
+        let first = place_edge_label(&edge_path, &[], 40.0, 26.0);
+        edge_path.parallel_offset = 6.0;
+        let second = place_edge_label(&edge_path, &[], 40.0, 26.0);
+        assert!(second.0 - first.0 >= 40.0, "{first:?} {second:?}");
+
+        let svg = render_svg(&fm_parser::parse("flowchart LR\n  A -->|yes| B\n  A -->|no| B").ir);
+        assert_eq!(svg.matches("class=\"edge-label\"").count(), 2);
+    }
+
+    #[test]
+    fn arrowheads_follow_the_arrow_type_and_the_edge_stroke() {
+        let svg = render_svg(
+            &fm_parser::parse(
+                "flowchart LR\n  A --o B\n  B --x C\n  C ==> D\n  D <--> E\n  linkStyle 2 stroke:#ff0000",
+            )
+            .ir,
+        );
+
+        assert!(svg.contains("marker-end=\"url(#arrow-circle)\""), "{svg}");
+        assert!(svg.contains("marker-end=\"url(#arrow-cross)\""));
+        assert!(svg.contains("marker-start=\"url(#arrow-start)\""));
+        // The styled thick arrow ends in a red copy of the filled head instead of the themed one.
+        assert!(svg.contains("marker-end=\"url(#arrow-filled-ff0000)\""));
+        assert!(svg.contains("<marker id=\"arrow-filled-ff0000\""));
+        assert!(svg.contains("fill=\"#ff0000\""));
+        assert!(!svg.contains("url(#arrow-filled)"));
+
+        assert_eq!(
+            stroke_colored_marker_url("url(#arrow-end)", "rgb(1, 2, 3)"),
+            "url(#arrow-end-rgb-1--2--3-)"
+        );
+    }
+
+    #[test]
+    fn link_styles_set_color_and_width_on_labeled_and_plain_edges() {
+        let svg = render_svg(
+            &fm_parser::parse(
+                "flowchart LR\n  A -->|go| B\n  B --> C\n  C --> D\n  linkStyle 0,1 stroke:#0000ff,stroke-width:4px",
+            )
+            .ir,
+        );
+        assert_eq!(
+            svg.matches("style=\"stroke:#0000ff; stroke-width:4px\"")
+                .count(),
+            2,
+            "{svg}"
+        );
+        assert!(
+            svg.contains("marker-end=\"url(#arrow-end-0000ff)\""),
+            "{svg}"
+        );
+    }
+
     #[test]
     fn renders_single_slice_pie_as_full_circle() {
         let mut ir = MermaidDiagramIr::empty(DiagramType::Pie);
@@ -13153,7 +14349,7 @@ mod tests {
             &SvgRenderConfig::default(),
         );
         assert!(!svg.contains("url(javascript:alert(1))"));
-        assert!(svg.contains("stroke=\"#dee2e6\""));
+        assert!(svg.contains("stroke=\"#cbd5e1\""));
     }
 
     #[test]
@@ -13234,6 +14430,34 @@ mod tests {
         assert!(svg.contains("fm-node-label"));
     }
 
+    #[test]
+    fn classdef_default_styles_every_node_before_named_classes() {
+        let mut ir = create_ir_with_single_node("node-styled", NodeShape::Rect);
+        ir.nodes[0].classes.push("important".to_string());
+        for (name, style) in [
+            ("important", "fill:#f9f"),
+            ("default", "fill:#eee,color:#222"),
+        ] {
+            ir.style_refs.push(IrStyleRef {
+                target: IrStyleTarget::Class(name.to_string()),
+                style: style.to_string(),
+                span: Span::default(),
+            });
+        }
+
+        let svg = render_svg(&ir);
+
+        let default_rule = svg
+            .find(".fm-node .fm-node-shape,.fm-node .fm-node-shape *{fill:#eee;}")
+            .expect("default class styles every node shape");
+        let named_rule = svg
+            .find(".fm-node-user-important .fm-node-shape")
+            .expect("named class rule");
+        assert!(default_rule < named_rule);
+        assert!(svg.contains(".fm-node .fm-node-label,.fm-node .fm-node-label *{fill:#222;}"));
+        assert!(!svg.contains("fm-node-user-default"));
+    }
+
     #[test]
     fn renders_layout_extensions_for_bands_and_axis_ticks() {
         let ir = MermaidDiagramIr::empty(DiagramType::Gantt);
@@ -13831,6 +15055,18 @@ marker#arrow-open path {
         assert!(svg.contains("cursor: pointer;"));
     }
 
+    #[test]
+    fn callback_nodes_never_emit_anything_but_a_function_name() {
+        let mut ir = create_ir_with_single_node("node-callback", NodeShape::Rect);
+        ir.nodes[0].interaction_mut().callback = Some("x\" onload=\"alert(1)".to_string());
+        let svg = render_svg(&ir);
+        assert!(!svg.contains("data-callback"), "{svg}");
+        assert!(!svg.contains("onload"), "{svg}");
+
+        ir.nodes[0].interaction_mut().callback = Some("app.open(\"A\")".to_string());
+        assert!(render_svg(&ir).contains("data-callback=\"app.open\""));
+    }
+
     #[test]
     fn renders_state_pseudo_state_shapes_without_fallback_ids_as_labels() {
         let mut ir = MermaidDiagramIr::empty(DiagramType::State);
@@ -14057,13 +15293,18 @@ marker#arrow-open path {
         assert!(svg.contains("fm-sequence-mirror-header"));
         assert!(svg.matches("Alice").count() >= 2);
         assert!(svg.matches("Bob").count() >= 2);
-        assert_eq!(svg.matches("id=\"fm-node-alice-0\"").count(), 1);
+        assert_eq!(svg.matches("id=\"fm-node-alice-2cdd8587\"").count(), 1);
+        assert_eq!(
+            svg.matches("id=\"fm-node-alice-2cdd8587-mirror-header\"")
+                .count(),
+            1
+        );
+        assert_eq!(svg.matches("id=\"fm-node-bob-ebcba174\"").count(), 1);
         assert_eq!(
-            svg.matches("id=\"fm-node-alice-0-mirror-header\"").count(),
+            svg.matches("id=\"fm-node-bob-ebcba174-mirror-header\"")
+                .count(),
             1
         );
-        assert_eq!(svg.matches("id=\"fm-node-bob-1\"").count(), 1);
-        assert_eq!(svg.matches("id=\"fm-node-bob-1-mirror-header\"").count(), 1);
     }
 
     #[test]
@@ -14341,6 +15582,41 @@ marker#arrow-open path {
         assert!(svg.contains(">next<"));
     }
 
+    #[test]
+    fn renders_markdown_edge_labels_with_inline_tspans() {
+        let mut ir = MermaidDiagramIr::empty(DiagramType::Flowchart);
+        for id in ["A", "B"] {
+            ir.nodes.push(IrNode {
+                id: id.to_string(),
+                ..Default::default()
+            });
+        }
+        ir.labels.push(fm_core::IrLabel {
+            text: "Bold tail".to_string(),
+            ..Default::default()
+        });
+        ir.label_markup
+            .insert(IrLabelId(0), fm_core::parse_label_markdown("**Bold** tail"));
+        ir.edges.push(IrEdge {
+            from: IrEndpoint::Node(IrNodeId(0)),
+            to: IrEndpoint::Node(IrNodeId(1)),
+            arrow: ArrowType::Arrow,
+            label: Some(IrLabelId(0)),
+            ..Default::default()
+        });
+
+        let svg = render_svg(&ir);
+        let label_start = svg.find("class=\"edge-label\"").expect("edge label text");
+        let label = &svg[label_start..];
+        let label = &label[..label.find("</text>").unwrap()];
+        assert!(
+            label.contains("font-weight=\"700\">Bold</tspan>"),
+            "{label}"
+        );
+        // The run after the first on a line continues inline instead of restarting at `x`.
+        assert!(label.contains("<tspan> tail</tspan>"), "{label}");
+    }
+
     proptest! {
         #![proptest_config(ProptestConfig::with_cases(48))]
 
@@ -14635,6 +15911,144 @@ marker#arrow-open path {
         assert!(svg.contains("fm-node-icon-queue"));
     }
 
+    #[test]
+    fn architecture_services_draw_builtin_icons_in_the_corner() {
+        let parsed = fm_parser::parse(
+            "architecture-beta\n  service db(database)[DB]\n  service files(disk)[Files]\n  \
+             service edge(internet)[Edge]\n  service odd(logos:aws-s3)[S3]",
+        );
+        let svg = render_svg(&parsed.ir);
+
+        assert_eq!(svg.matches("fm-node-icon-pos-corner").count(), 4);
+        assert!(!svg.contains("fm-node-icon-pos-above"));
+        assert_eq!(svg.matches("fm-node-icon-builtin").count(), 3);
+        for name in ["database", "disk", "internet"] {
+            assert!(svg.contains(&format!("fm-node-icon fm-node-icon-{name}")));
+        }
+        // Unknown icon names keep the initials badge.
+        assert!(svg.contains(">LS</text>"));
+    }
+
+    #[test]
+    fn size_options_emit_fixed_dimensions_next_to_the_viewbox() {
+        let ir = create_ir_with_single_node("A", NodeShape::Rect);
+        let root_attr = |svg: &str, name: &str| -> Option<String> {
+            let tag = &svg[..svg.find('>')?];
+            let start = tag.find(&format!(" {name}=\""))? + name.len() + 3;
+            tag[start..].split('"').next().map(str::to_string)
+        };
+        let number = |svg: &str, name: &str| -> f32 {
+            root_attr(svg, name)
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(f32::NAN)
+        };
+        let render = |config: SvgRenderConfig| render_svg_with_config(&ir, &config);
+
+        let natural = render_svg(&ir);
+        assert_eq!(root_attr(&natural, "width").as_deref(), Some("100%"));
+        let view: Vec<f32> = root_attr(&natural, "viewBox")
+            .unwrap_or_default()
+            .split(' ')
+            .filter_map(|value| value.parse().ok())
+            .collect();
+        let (view_width, view_height) = (view[2], view[3]);
+
+        let fixed_width = render(SvgRenderConfig {
+            width: Some(400.0),
+            ..SvgRenderConfig::default()
+        });
+        assert!(root_attr(&fixed_width, "viewBox").is_some());
+        assert!((number(&fixed_width, "width") - 400.0).abs() < 0.01);
+        assert!((number(&fixed_width, "height") - 400.0 * view_height / view_width).abs() < 0.01);
+
+        let scaled = render(SvgRenderConfig {
+            scale: 2.0,
+            ..SvgRenderConfig::default()
+        });
+        assert!((number(&scaled, "width") - view_width * 2.0).abs() < 0.01);
+        assert!((number(&scaled, "height") - view_height * 2.0).abs() < 0.01);
+
+        let capped = render(SvgRenderConfig {
+            width: Some(800.0),
+            height: Some(400.0),
+            max_width: Some(200.0),
+            ..SvgRenderConfig::default()
+        });
+        assert_eq!(root_attr(&capped, "width").as_deref(), Some("200"));
+        assert_eq!(root_attr(&capped, "height").as_deref(), Some("100"));
+
+        let responsive_capped = render(SvgRenderConfig {
+            max_width: Some(500.0),
+            ..SvgRenderConfig::default()
+        });
+        assert_eq!(
+            root_attr(&responsive_capped, "width").as_deref(),
+            Some("100%")
+        );
+        assert_eq!(
+            root_attr(&responsive_capped, "style").as_deref(),
+            Some("max-width: 500px")
+        );
+
+        let unsized_svg = render(SvgRenderConfig {
+            responsive: false,
+            ..SvgRenderConfig::default()
+        });
+        assert!(root_attr(&unsized_svg, "width").is_none());
+    }
+
+    #[test]
+    fn layered_output_splits_labels_into_their_own_groups() {
+        let parsed = fm_parser::parse(
+            "flowchart LR\n  subgraph S [Stage]\n    A[Start] -->|go| B[Finish]\n  end\n",
+        );
+        let flat = render_svg(&parsed.ir);
+        assert!(!flat.contains("fm-layer-"));
+
+        let svg = render_svg_with_config(
+            &parsed.ir,
+            &SvgRenderConfig {
+                layers: true,
+                ..SvgRenderConfig::default()
+            },
+        );
+        let starts: Vec<usize> = SvgLayer::ALL
+            .iter()
+            .map(|layer| {
+                svg.find(&format!("<g id=\"{}\"", layer.element_id()))
+                    .unwrap_or_else(|| panic!("missing {layer:?}"))
+            })
+            .collect();
+        assert!(starts.windows(2).all(|pair| pair[0] < pair[1]));
+        let layer =
+            |index: usize| &svg[starts[index]..starts.get(index + 1).copied().unwrap_or(svg.len())];
+
+        assert!(layer(0).contains(">Stage<"));
+        assert!(!layer(1).contains("<text"));
+        assert!(layer(2).contains("class=\"fm-edge-label\""));
+        assert!(layer(2).contains(">go<"));
+        assert!(!layer(3).contains("<text"));
+        assert!(layer(4).contains("pointer-events=\"none\""));
+        assert!(layer(4).contains("data-id=\"A\""));
+        assert!(layer(4).contains(">Start<") && layer(4).contains(">Finish<"));
+    }
+
+    #[test]
+    fn state_notes_render_as_sticky_notes_with_dashed_leaders() {
+        let parsed = fm_parser::parse(
+            "stateDiagram-v2\n  [*] --> Active\n  note right of Active : Holds the lock\n",
+        );
+        let svg = render_svg(&parsed.ir);
+        assert!(svg.contains("class=\"fm-note-leader\""));
+        assert!(svg.contains("stroke-dasharray=\"4,3\""));
+        assert!(svg.contains("class=\"fm-note\""));
+        assert!(svg.contains(&format!("fill=\"{NODE_NOTE_FILL}\"")));
+        assert!(svg.contains(">Holds the lock<"));
+
+        let plain = render_svg(&fm_parser::parse("stateDiagram-v2\n  [*] --> Active\n").ir);
+        assert!(!plain.contains("fm-note"));
+    }
+
     #[test]
     fn animations_are_disabled_by_default() {
         let ir = create_ir_with_single_node("plain", NodeShape::Rect);
@@ -0,0 +1,279 @@
+//! PNG rasterization of rendered SVG (feature `raster`).
+//!
+//! resvg understands a static subset of SVG and CSS, so diagrams are rendered with the interactive
+//! extras (responsive sizing, theme `<style>`, animations, shadows, glow) switched off and any
+//! remaining `var(--fm-…)` references resolved to concrete colors before they are rasterized.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use fm_core::MermaidDiagramIr;
+
+use crate::{SvgRenderConfig, render_svg_with_config};
+
+/// Why a diagram could not be turned into a PNG.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RasterError {
+    /// resvg rejected the SVG markup.
+    Parse(String),
+    /// The SVG has a zero, negative, or non-finite size.
+    EmptySvg,
+    /// The requested pixel size or scale is zero, negative, or non-finite.
+    InvalidSize,
+    /// The pixmap could not be allocated or encoded.
+    Encode(String),
+}
+
+impl fmt::Display for RasterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(detail) => write!(f, "Failed to parse SVG: {detail}"),
+            Self::EmptySvg => f.write_str("SVG dimensions must be greater than 0"),
+            Self::InvalidSize => f.write_str("PNG dimensions must be greater than 0"),
+            Self::Encode(detail) => write!(f, "Failed to encode PNG: {detail}"),
+        }
+    }
+}
+
+impl std::error::Error for RasterError {}
+
+/// Render `ir` to PNG bytes at `scale` device pixels per SVG unit.
+///
+/// # Errors
+///
+/// Returns [`RasterError::InvalidSize`] for a non-positive or non-finite `scale`, and the other
+/// variants when the rendered SVG cannot be rasterized.
+pub fn render_png(
+    ir: &MermaidDiagramIr,
+    config: &SvgRenderConfig,
+    scale: f32,
+) -> Result<Vec<u8>, RasterError> {
+    if !scale.is_finite() || scale <= 0.0 {
+        return Err(RasterError::InvalidSize);
+    }
+    let mut config = config.clone();
+    make_config_raster_safe(&mut config);
+    let svg = resolve_svg_custom_properties_for_rasterization(&render_svg_with_config(ir, &config));
+    let tree = parse_svg(&svg)?;
+    let (width, height) = svg_size(&tree)?;
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let size = (
+        (width * scale).ceil() as u32,
+        (height * scale).ceil() as u32,
+    );
+    rasterize(&tree, size.0, size.1).map(|(bytes, _, _)| bytes)
+}
+
+/// Switch off the output features resvg cannot reproduce, leaving a static attribute-driven SVG.
+pub fn make_config_raster_safe(config: &mut SvgRenderConfig) {
+    config.responsive = false;
+    config.embed_theme_css = false;
+    config.animations_enabled = false;
+    config.interactive = false;
+    config.print_optimized = false;
+    config.shadows = false;
+    config.glow_enabled = false;
+    config.a11y.accessibility_css = false;
+}
+
+/// Rasterize an SVG document to PNG, returning the bytes and the pixel size.
+///
+/// With only one of `width`/`height` the other follows the SVG's aspect ratio; with neither the
+/// SVG's own size is used.
+///
+/// # Errors
+///
+/// Returns a [`RasterError`] when the SVG cannot be parsed, has no area, or the output size is zero.
+pub fn svg_to_png(
+    svg: &str,
+    width: Option<u32>,
+    height: Option<u32>,
+) -> Result<(Vec<u8>, u32, u32), RasterError> {
+    let tree = parse_svg(svg)?;
+    let (size_width, size_height) = svg_size(&tree)?;
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let (px_width, px_height) = match (width, height) {
+        (Some(w), Some(h)) => (w, h),
+        (Some(w), None) => {
+            let scale = w as f32 / size_width;
+            (w, (size_height * scale) as u32)
+        }
+        (None, Some(h)) => {
+            let scale = h as f32 / size_height;
+            ((size_width * scale) as u32, h)
+        }
+        (None, None) => (size_width as u32, size_height as u32),
+    };
+    rasterize(&tree, px_width, px_height)
+}
+
+fn parse_svg(svg: &str) -> Result<usvg::Tree, RasterError> {
+    usvg::Tree::from_str(svg, &usvg::Options::default())
+        .map_err(|err| RasterError::Parse(err.to_string()))
+}
+
+fn svg_size(tree: &usvg::Tree) -> Result<(f32, f32), RasterError> {
+    let size = tree.size();
+    let (width, height) = (size.width(), size.height());
+    if !width.is_finite() || !height.is_finite() || width <= 0.0 || height <= 0.0 {
+        return Err(RasterError::EmptySvg);
+    }
+    Ok((width, height))
+}
+
+fn rasterize(
+    tree: &usvg::Tree,
+    px_width: u32,
+    px_height: u32,
+) -> Result<(Vec<u8>, u32, u32), RasterError> {
+    use resvg::tiny_skia;
+
+    if px_width == 0 || px_height == 0 {
+        return Err(RasterError::InvalidSize);
+    }
+    let mut pixmap = tiny_skia::Pixmap::new(px_width, px_height)
+        .ok_or_else(|| RasterError::Encode("pixmap allocation failed".to_string()))?;
+    let size = tree.size();
+    let transform = usvg::Transform::from_scale(
+        px_width as f32 / size.width(),
+        px_height as f32 / size.height(),
+    );
+    resvg::render(tree, transform, &mut pixmap.as_mut());
+    let bytes = pixmap
+        .encode_png()
+        .map_err(|err| RasterError::Encode(err.to_string()))?;
+    Ok((bytes, px_width, px_height))
+}
+
+/// Replace `var(--fm-…)` references with the values declared in the SVG's `<style>` block, or with
+/// their fallbacks, since resvg does not evaluate custom properties.
+#[must_use]
+pub fn resolve_svg_custom_properties_for_rasterization(svg: &str) -> String {
+    let mut custom_properties = BTreeMap::new();
+    if let Some(style_start) = svg.find("<style>") {
+        let style_content_start = style_start + "<style>".len();
+        if let Some(style_end_rel) = svg[style_content_start..].find("</style>") {
+            let style_content_end = style_content_start + style_end_rel;
+            let style_content = &svg[style_content_start..style_content_end];
+            custom_properties = extract_svg_custom_properties(style_content);
+        }
+    }
+    if custom_properties.is_empty() && !svg.contains("var(--fm-") {
+        return svg.to_string();
+    }
+
+    let mut resolved = svg.to_string();
+    for _ in 0..8 {
+        let next = substitute_svg_var_calls(&resolved, &custom_properties);
+        if next == resolved {
+            break;
+        }
+        resolved = next;
+    }
+    resolved
+}
+
+fn extract_svg_custom_properties(style_content: &str) -> BTreeMap<String, String> {
+    let mut properties = BTreeMap::new();
+    for line in style_content.lines() {
+        let trimmed = line.trim();
+        if !trimmed.starts_with("--fm-") {
+            continue;
+        }
+        let Some((name, value)) = trimmed.split_once(':') else {
+            continue;
+        };
+        let value = value.trim().trim_end_matches(';').trim();
+        if !value.is_empty() {
+            properties.insert(name.trim().to_string(), value.to_string());
+        }
+    }
+    properties
+}
+
+fn substitute_svg_var_calls(input: &str, custom_properties: &BTreeMap<String, String>) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut cursor = 0;
+
+    while let Some(rel_start) = input[cursor..].find("var(--fm-") {
+        let start = cursor + rel_start;
+        output.push_str(&input[cursor..start]);
+
+        let content_start = start + "var(".len();
+        let mut depth = 1_usize;
+        let mut end = None;
+        for (offset, ch) in input[content_start..].char_indices() {
+            match ch {
+                '(' => depth = depth.saturating_add(1),
+                ')' => {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 {
+                        end = Some(content_start + offset);
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let Some(end) = end else {
+            output.push_str(&input[start..]);
+            return output;
+        };
+        let body = &input[content_start..end];
+        let (property_name, fallback) = match body.split_once(',') {
+            Some((name, fallback)) => (name.trim(), Some(fallback.trim())),
+            None => (body.trim(), None),
+        };
+
+        if let Some(value) = custom_properties.get(property_name) {
+            output.push_str(value);
+        } else if let Some(fallback) = fallback.filter(|value| !value.is_empty()) {
+            output.push_str(fallback);
+        } else {
+            output.push_str(&input[start..=end]);
+        }
+
+        cursor = end + 1;
+    }
+
+    output.push_str(&input[cursor..]);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fm_core::{DiagramType, IrEdge, IrEndpoint, IrNode, IrNodeId};
+
+    #[test]
+    fn render_png_scales_the_diagram_size() {
+        let mut ir = MermaidDiagramIr::empty(DiagramType::Flowchart);
+        for id in ["A", "B"] {
+            ir.nodes.push(IrNode {
+                id: id.to_string(),
+                ..IrNode::default()
+            });
+        }
+        ir.edges.push(IrEdge {
+            from: IrEndpoint::Node(IrNodeId(0)),
+            to: IrEndpoint::Node(IrNodeId(1)),
+            ..IrEdge::default()
+        });
+        let config = SvgRenderConfig::default();
+
+        let png = render_png(&ir, &config, 1.0).expect("1x png");
+        let png_2x = render_png(&ir, &config, 2.0).expect("2x png");
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        let dimensions = |bytes: &[u8]| {
+            let word = |at: usize| u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap());
+            (word(16), word(20))
+        };
+        let (width, height) = dimensions(&png);
+        let (width_2x, height_2x) = dimensions(&png_2x);
+        assert!(width_2x.abs_diff(width * 2) <= 1);
+        assert!(height_2x.abs_diff(height * 2) <= 1);
+
+        assert_eq!(render_png(&ir, &config, 0.0), Err(RasterError::InvalidSize));
+    }
+}
@@ -71,6 +71,7 @@ pub struct TextBuilder {
     font_size: Option<f32>,
     font_weight: Option<String>,
     font_style: Option<String>,
+    text_decoration: Option<String>,
     fill: Option<String>,
     anchor: TextAnchor,
     baseline: DominantBaseline,
@@ -90,6 +91,7 @@ impl TextBuilder {
             font_size: None,
             font_weight: None,
             font_style: None,
+            text_decoration: None,
             fill: None,
             anchor: TextAnchor::Start,
             baseline: DominantBaseline::Auto,
@@ -165,6 +167,13 @@ impl TextBuilder {
         self.font_style("italic")
     }
 
+    /// Underline the text.
+    #[must_use]
+    pub fn underline(mut self) -> Self {
+        self.text_decoration = Some(String::from("underline"));
+        self
+    }
+
     /// Set the fill color.
     #[must_use]
     pub fn fill(mut self, color: &str) -> Self {
@@ -232,6 +241,10 @@ impl TextBuilder {
             elem = elem.attr("font-style", style);
         }
 
+        if let Some(ref decoration) = self.text_decoration {
+            elem = elem.attr("text-decoration", decoration);
+        }
+
         if let Some(ref fill) = self.fill {
             elem = elem.fill(fill);
         }
@@ -0,0 +1,281 @@
+//! Frame-by-frame reveal of a diagram for recorded demos and streamed output.
+//!
+//! The diagram is laid out once and every frame renders a subset of that layout, so nodes never
+//! move between frames: each frame only adds elements to the previous one. Frames are rendered
+//! lazily as the iterator is advanced.
+
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use fm_core::MermaidDiagramIr;
+use fm_layout::{DiagramLayout, layout_diagram};
+
+use crate::config::TermRenderConfig;
+use crate::renderer::render_diagram_with_layout_and_config;
+
+/// Delay between frames unless [`TermAnimation::with_frame_delay`] sets another.
+const DEFAULT_FRAME_DELAY: Duration = Duration::from_millis(300);
+
+/// What each frame of a [`TermAnimation`] adds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RevealOrder {
+    /// One layout rank per frame, with the edges between nodes shown so far.
+    #[default]
+    Ranks,
+    /// One edge per frame, in source order, with its endpoints. Nodes without edges are shown
+    /// from the first frame.
+    Edges,
+}
+
+/// Frames revealing a diagram step by step, as rendered terminal output.
+///
+/// Every frame has the same size, so frames can be drawn over each other in place. The last
+/// frame is the complete diagram.
+#[derive(Debug, Clone)]
+pub struct TermAnimation<'a> {
+    ir: &'a MermaidDiagramIr,
+    layout: DiagramLayout,
+    config: TermRenderConfig,
+    order: RevealOrder,
+    frame_delay: Duration,
+    next_frame: usize,
+}
+
+impl<'a> TermAnimation<'a> {
+    fn new(ir: &'a MermaidDiagramIr, config: &TermRenderConfig) -> Self {
+        Self {
+            ir,
+            layout: layout_diagram(ir),
+            config: config.clone(),
+            order: RevealOrder::default(),
+            frame_delay: DEFAULT_FRAME_DELAY,
+            next_frame: 0,
+        }
+    }
+
+    /// Reveal in `order` instead of rank by rank.
+    #[must_use]
+    pub const fn with_order(mut self, order: RevealOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Show each frame for `delay` before the next one.
+    #[must_use]
+    pub const fn with_frame_delay(mut self, delay: Duration) -> Self {
+        self.frame_delay = delay;
+        self
+    }
+
+    /// How long each frame is meant to stay on screen.
+    #[must_use]
+    pub const fn frame_delay(&self) -> Duration {
+        self.frame_delay
+    }
+
+    /// Number of frames in the whole animation, including those already yielded.
+    #[must_use]
+    pub fn frame_count(&self) -> usize {
+        match self.order {
+            RevealOrder::Ranks => self.ranks().len(),
+            RevealOrder::Edges => self.layout.edges.len(),
+        }
+        .max(1)
+    }
+
+    /// Time from the first frame to the last one appearing.
+    #[must_use]
+    pub fn total_duration(&self) -> Duration {
+        let gaps = u32::try_from(self.frame_count() - 1).unwrap_or(u32::MAX);
+        self.frame_delay.saturating_mul(gaps)
+    }
+
+    fn ranks(&self) -> Vec<usize> {
+        self.layout
+            .nodes
+            .iter()
+            .map(|node_box| node_box.rank)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// IR indices of the nodes and layout indices of the edges visible in `frame`.
+    fn visible(&self, frame: usize) -> (BTreeSet<usize>, BTreeSet<usize>) {
+        let endpoints = |edge_index: usize| {
+            let edge = self.ir.edges.get(edge_index)?;
+            Some((
+                self.ir.resolve_endpoint_node(edge.from)?.0,
+                self.ir.resolve_endpoint_node(edge.to)?.0,
+            ))
+        };
+        match self.order {
+            RevealOrder::Ranks => {
+                let ranks = self.ranks();
+                let last_rank = ranks.get(frame).or(ranks.last()).copied().unwrap_or(0);
+                let nodes: BTreeSet<usize> = self
+                    .layout
+                    .nodes
+                    .iter()
+                    .filter(|node_box| node_box.rank <= last_rank)
+                    .map(|node_box| node_box.node_index)
+                    .collect();
+                let edges = self
+                    .layout
+                    .edges
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, edge_path)| {
+                        endpoints(edge_path.edge_index)
+                            .is_some_and(|(from, to)| nodes.contains(&from) && nodes.contains(&to))
+                    })
+                    .map(|(index, _)| index)
+                    .collect();
+                (nodes, edges)
+            }
+            RevealOrder::Edges => {
+                let edges: BTreeSet<usize> = (0..=frame).collect();
+                let connected: BTreeSet<usize> = self
+                    .layout
+                    .edges
+                    .iter()
+                    .filter_map(|edge_path| endpoints(edge_path.edge_index))
+                    .flat_map(|(from, to)| [from, to])
+                    .collect();
+                let mut nodes: BTreeSet<usize> = self
+                    .layout
+                    .nodes
+                    .iter()
+                    .map(|node_box| node_box.node_index)
+                    .filter(|node_index| !connected.contains(node_index))
+                    .collect();
+                for &index in &edges {
+                    if let Some((from, to)) = self
+                        .layout
+                        .edges
+                        .get(index)
+                        .and_then(|edge_path| endpoints(edge_path.edge_index))
+                    {
+                        nodes.extend([from, to]);
+                    }
+                }
+                (nodes, edges)
+            }
+        }
+    }
+
+    /// The layout cut down to what `frame` shows. Bounds stay those of the whole diagram so
+    /// every frame is scaled the same way.
+    fn frame_layout(&self, frame: usize) -> DiagramLayout {
+        let (nodes, edges) = self.visible(frame);
+        let mut layout = self.layout.clone();
+        layout
+            .nodes
+            .retain(|node_box| nodes.contains(&node_box.node_index));
+        let mut index = 0;
+        layout.edges.retain(|_| {
+            let keep = edges.contains(&index);
+            index += 1;
+            keep
+        });
+        layout.clusters.retain(|cluster_box| {
+            self.ir
+                .clusters
+                .get(cluster_box.cluster_index)
+                .is_some_and(|cluster| {
+                    cluster
+                        .members
+                        .iter()
+                        .any(|member| nodes.contains(&member.0))
+                })
+        });
+        layout
+    }
+}
+
+impl Iterator for TermAnimation<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if self.next_frame >= self.frame_count() {
+            return None;
+        }
+        let layout = self.frame_layout(self.next_frame);
+        self.next_frame += 1;
+        let result = render_diagram_with_layout_and_config(
+            self.ir,
+            &layout,
+            &self.config,
+            self.config.max_width,
+            self.config.max_height,
+        );
+        Some(result.output)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.frame_count().saturating_sub(self.next_frame);
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for TermAnimation<'_> {}
+
+/// Frames revealing `ir` rank by rank, each rendered at `config.max_width` x
+/// `config.max_height`. Use [`TermAnimation::with_order`] to reveal edge by edge instead, and
+/// [`TermAnimation::frame_delay`] for the pacing.
+#[must_use]
+pub fn render_term_animated<'a>(
+    ir: &'a MermaidDiagramIr,
+    config: &TermRenderConfig,
+) -> TermAnimation<'a> {
+    TermAnimation::new(ir, config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rank_frames_add_nodes_until_the_full_diagram() {
+        let parsed = fm_parser::parse("flowchart TD\n  A[Alpha] --> B[Beta]\n  B --> C[Gamma]");
+        let config = TermRenderConfig::compact();
+        let animation = render_term_animated(&parsed.ir, &config);
+        assert_eq!(animation.frame_count(), 3);
+        assert_eq!(animation.total_duration(), DEFAULT_FRAME_DELAY * 2);
+
+        let frames: Vec<String> = animation.collect();
+        assert_eq!(frames.len(), 3);
+        assert!(frames[0].contains("Alpha") && !frames[0].contains("Beta"));
+        assert!(frames[1].contains("Beta") && !frames[1].contains("Gamma"));
+        let full = render_diagram_with_layout_and_config(
+            &parsed.ir,
+            &layout_diagram(&parsed.ir),
+            &config,
+            config.max_width,
+            config.max_height,
+        );
+        assert_eq!(frames[2], full.output);
+        assert!(
+            frames
+                .iter()
+                .all(|frame| frame.lines().count() == full.output.lines().count())
+        );
+    }
+
+    #[test]
+    fn edge_frames_reveal_one_edge_at_a_time() {
+        let parsed = fm_parser::parse(
+            "flowchart LR\n  A[One] --> B[Two]\n  C[Three] --> D[Four]\n  E[Alone]",
+        );
+        let animation = render_term_animated(&parsed.ir, &TermRenderConfig::compact())
+            .with_order(RevealOrder::Edges)
+            .with_frame_delay(Duration::from_millis(50));
+        assert_eq!(animation.len(), 2);
+        assert_eq!(animation.total_duration(), Duration::from_millis(50));
+
+        let frames: Vec<String> = animation.collect();
+        assert!(frames[0].contains("Two") && frames[0].contains("Alone"));
+        assert!(!frames[0].contains("Four"));
+        assert!(frames[1].contains("Four"));
+    }
+}
@@ -1,7 +1,15 @@
 //! ASCII diagram detection and correction.
 //!
 //! Provides utilities for detecting ASCII art diagrams in text and
-//! cleaning up right-border alignment issues.
+//! cleaning up right-border alignment issues, and an importer turning
+//! box-and-arrow art into a flowchart IR.
+
+use std::collections::BTreeMap;
+
+use fm_core::{
+    ArrowType, DiagramType, GraphDirection, IrEdge, IrEndpoint, IrGraphEdge, IrGraphNode, IrLabel,
+    IrLabelId, IrNode, IrNodeId, MermaidDiagramIr,
+};
 
 /// Character classification for diagram detection.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -394,6 +402,416 @@ fn is_horizontal_connector(ch: char) -> bool {
     )
 }
 
+/// A box found in ASCII art: the cells of its border, inclusive, and the text inside.
+#[derive(Debug, Clone)]
+struct AsciiBox {
+    left: usize,
+    top: usize,
+    right: usize,
+    bottom: usize,
+    label: String,
+}
+
+impl AsciiBox {
+    const fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.left && x <= self.right && y >= self.top && y <= self.bottom
+    }
+
+    const fn center(&self) -> (usize, usize) {
+        ((self.left + self.right) / 2, (self.top + self.bottom) / 2)
+    }
+}
+
+/// Parse box-and-arrow ASCII art into a flowchart.
+///
+/// Boxes are rectangles drawn with `+` corners, `-` and `|` sides (Unicode box-drawing works
+/// too); the text inside becomes the node label. Lines of `-`, `|` and `+` joining two boxes
+/// become edges, pointing at the boxes an arrowhead (`>`, `<`, `^`, `v`) touches, or plain
+/// links without one. `=` lines become thick edges, and text inside a horizontal line
+/// (`--yes-->`) becomes the edge label. A line may stop one blank cell short of a box. The
+/// direction is `LR` when the edges run mostly sideways and `TD` otherwise.
+///
+/// Returns `None` when `text` has no box. Boxes inside other boxes are not recognized.
+#[must_use]
+pub fn parse_ascii_diagram(text: &str) -> Option<MermaidDiagramIr> {
+    let grid: Vec<Vec<char>> = text
+        .lines()
+        .map(|line| line.chars().map(plain_art_char).collect())
+        .collect();
+    let boxes = find_boxes(&grid);
+    if boxes.is_empty() {
+        return None;
+    }
+    let links = trace_links(&grid, &boxes);
+
+    let mut ir = MermaidDiagramIr::empty(DiagramType::Flowchart);
+    let (mut across, mut down) = (0, 0);
+    for link in &links {
+        let (from, to) = (boxes[link.from].center(), boxes[link.to].center());
+        across += from.0.abs_diff(to.0);
+        // Terminal cells are about twice as tall as they are wide.
+        down += from.1.abs_diff(to.1) * 2;
+    }
+    ir.direction = if across > down {
+        GraphDirection::LR
+    } else {
+        GraphDirection::TD
+    };
+    for (index, ascii_box) in boxes.iter().enumerate() {
+        let id = format!("box{}", index + 1);
+        let label = if ascii_box.label.is_empty() {
+            id.clone()
+        } else {
+            ascii_box.label.clone()
+        };
+        let label = push_ascii_label(&mut ir, label);
+        ir.nodes.push(IrNode {
+            id,
+            label: Some(label),
+            ..Default::default()
+        });
+        ir.graph.nodes.push(IrGraphNode {
+            node_id: IrNodeId(index),
+            ..Default::default()
+        });
+    }
+    for link in links {
+        let (from, to) = (
+            IrEndpoint::Node(IrNodeId(link.from)),
+            IrEndpoint::Node(IrNodeId(link.to)),
+        );
+        let label = link.label.map(|text| push_ascii_label(&mut ir, text));
+        ir.graph.edges.push(IrGraphEdge {
+            edge_id: ir.edges.len(),
+            from,
+            to,
+            ..Default::default()
+        });
+        ir.edges.push(IrEdge {
+            from,
+            to,
+            arrow: link.arrow,
+            label,
+            ..Default::default()
+        });
+    }
+    Some(ir)
+}
+
+fn push_ascii_label(ir: &mut MermaidDiagramIr, text: String) -> IrLabelId {
+    ir.labels.push(IrLabel {
+        text,
+        ..Default::default()
+    });
+    IrLabelId(ir.labels.len() - 1)
+}
+
+/// `ch` with Unicode box-drawing corners, lines and arrows folded to their ASCII forms.
+fn plain_art_char(ch: char) -> char {
+    match ch {
+        '┌' | '┐' | '└' | '┘' | '├' | '┤' | '┬' | '┴' | '┼' | '╭' | '╮' | '╰' | '╯' => {
+            '+'
+        }
+        '─' => '-',
+        '│' => '|',
+        '═' | '━' => '=',
+        '→' | '▶' => '>',
+        '←' | '◀' => '<',
+        '↑' | '▲' => '^',
+        '↓' | '▼' => 'v',
+        _ => ch,
+    }
+}
+
+fn art_cell(grid: &[Vec<char>], x: usize, y: usize) -> char {
+    grid.get(y)
+        .and_then(|row| row.get(x))
+        .copied()
+        .unwrap_or(' ')
+}
+
+/// Boxes in reading order of their top-left corners.
+fn find_boxes(grid: &[Vec<char>]) -> Vec<AsciiBox> {
+    let mut boxes: Vec<AsciiBox> = Vec::new();
+    for (top, row) in grid.iter().enumerate() {
+        for (left, &cell) in row.iter().enumerate() {
+            if cell != '+'
+                || art_cell(grid, left + 1, top) != '-'
+                || !matches!(art_cell(grid, left, top + 1), '|' | '+')
+                || boxes.iter().any(|found| found.contains(left, top))
+            {
+                continue;
+            }
+            if let Some(found) = box_from_corner(grid, left, top) {
+                boxes.push(found);
+            }
+        }
+    }
+    boxes
+}
+
+/// The smallest box whose top-left corner is at (`left`, `top`).
+fn box_from_corner(grid: &[Vec<char>], left: usize, top: usize) -> Option<AsciiBox> {
+    let horizontal = |x0: usize, x1: usize, y: usize| {
+        (x0..=x1).all(|x| matches!(art_cell(grid, x, y), '-' | '+'))
+    };
+    let vertical = |x: usize, y0: usize, y1: usize| {
+        (y0..=y1).all(|y| matches!(art_cell(grid, x, y), '|' | '+'))
+    };
+    let mut right = left + 1;
+    while matches!(art_cell(grid, right, top), '-' | '+') {
+        if art_cell(grid, right, top) == '+' {
+            let mut bottom = top + 1;
+            while matches!(art_cell(grid, left, bottom), '|' | '+') {
+                if art_cell(grid, left, bottom) == '+'
+                    && art_cell(grid, right, bottom) == '+'
+                    && horizontal(left, right, bottom)
+                    && vertical(right, top, bottom)
+                {
+                    let label = ((top + 1)..bottom)
+                        .map(|y| {
+                            ((left + 1)..right)
+                                .map(|x| art_cell(grid, x, y))
+                                .collect::<String>()
+                                .trim()
+                                .to_string()
+                        })
+                        .filter(|line| !line.is_empty())
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    return Some(AsciiBox {
+                        left,
+                        top,
+                        right,
+                        bottom,
+                        label,
+                    });
+                }
+                bottom += 1;
+            }
+        }
+        right += 1;
+    }
+    None
+}
+
+/// An edge traced between two boxes, by index.
+#[derive(Debug, Clone)]
+struct AsciiLink {
+    from: usize,
+    to: usize,
+    arrow: ArrowType,
+    label: Option<String>,
+}
+
+/// Whether a line cell can join its neighbors to the left and right, and above and below.
+const fn line_joins(ch: char) -> (bool, bool) {
+    match ch {
+        '-' | '=' | '<' | '>' => (true, false),
+        '|' | '^' | 'v' | 'V' => (false, true),
+        '+' => (true, true),
+        _ => (false, false),
+    }
+}
+
+/// Edges drawn between `boxes`: each connected run of line cells outside the boxes links the
+/// boxes it touches.
+fn trace_links(grid: &[Vec<char>], boxes: &[AsciiBox]) -> Vec<AsciiLink> {
+    let height = grid.len();
+    let width = grid.iter().map(Vec::len).max().unwrap_or(0);
+    let inside = |x: usize, y: usize| boxes.iter().position(|found| found.contains(x, y));
+
+    // Line cells outside boxes. Text between two horizontal line cells is an edge label and
+    // counts as line; `v` only counts under a vertical line.
+    let mut line = vec![vec![' '; width]; height];
+    let mut labels: Vec<(usize, usize, String)> = Vec::new();
+    for (y, line_row) in line.iter_mut().enumerate() {
+        let mut x = 0;
+        while x < width {
+            let ch = art_cell(grid, x, y);
+            if inside(x, y).is_some() {
+                x += 1;
+                continue;
+            }
+            let vertical_above = matches!(art_cell(grid, x, y.wrapping_sub(1)), '|' | '+');
+            if matches!(ch, 'v' | 'V') && !(y > 0 && vertical_above) {
+                x += 1;
+                continue;
+            }
+            if line_joins(ch) != (false, false) {
+                line_row[x] = ch;
+            }
+            if matches!(ch, '-' | '=') {
+                let end = (x + 1..width)
+                    .find(|&end| {
+                        inside(end, y).is_some()
+                            || matches!(art_cell(grid, end, y), '-' | '=' | '>' | '<' | '+' | '|')
+                    })
+                    .unwrap_or(width);
+                let between: String = (x + 1..end).map(|col| art_cell(grid, col, y)).collect();
+                if end > x + 1
+                    && inside(end, y).is_none()
+                    && matches!(art_cell(grid, end, y), '-' | '=' | '>')
+                    && between.chars().any(char::is_alphanumeric)
+                {
+                    line_row[x + 1..end].fill(art_cell(grid, x, y));
+                    labels.push((x + 1, y, between.trim().to_string()));
+                    x = end;
+                    continue;
+                }
+            }
+            x += 1;
+        }
+    }
+
+    let mut seen = vec![vec![false; width]; height];
+    let mut links = Vec::new();
+    for start_y in 0..height {
+        for start_x in 0..width {
+            if line[start_y][start_x] == ' ' || seen[start_y][start_x] {
+                continue;
+            }
+            let mut cells = Vec::new();
+            let mut stack = vec![(start_x, start_y)];
+            seen[start_y][start_x] = true;
+            while let Some((x, y)) = stack.pop() {
+                cells.push((x, y));
+                let (across, down) = line_joins(line[y][x]);
+                let steps = [
+                    (across, x.checked_sub(1).map(|nx| (nx, y))),
+                    (across, Some((x + 1, y))),
+                    (down, y.checked_sub(1).map(|ny| (x, ny))),
+                    (down, Some((x, y + 1))),
+                ];
+                for (allowed, next) in steps {
+                    let Some((nx, ny)) =
+                        next.filter(|&(nx, ny)| allowed && nx < width && ny < height)
+                    else {
+                        continue;
+                    };
+                    let (next_across, next_down) = line_joins(line[ny][nx]);
+                    let joins = if ny == y { next_across } else { next_down };
+                    if joins && !seen[ny][nx] {
+                        seen[ny][nx] = true;
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+            links.extend(links_for_run(grid, &line, &cells, &labels, boxes));
+        }
+    }
+    links
+}
+
+/// Edges for one connected run of line cells: from every box it touches without an arrowhead
+/// to every box it points at.
+fn links_for_run(
+    grid: &[Vec<char>],
+    line: &[Vec<char>],
+    cells: &[(usize, usize)],
+    labels: &[(usize, usize, String)],
+    boxes: &[AsciiBox],
+) -> Vec<AsciiLink> {
+    let border = |x: usize, y: usize| {
+        boxes.iter().position(|found| {
+            found.contains(x, y)
+                && (x == found.left || x == found.right || y == found.top || y == found.bottom)
+        })
+    };
+    // Box index -> whether an arrowhead points at it.
+    let mut touched: BTreeMap<usize, bool> = BTreeMap::new();
+    for &(x, y) in cells {
+        let ch = line[y][x];
+        let (across, down) = line_joins(ch);
+        let directions = [
+            (across, -1, 0, '<'),
+            (across, 1, 0, '>'),
+            (down, 0, -1, '^'),
+            (down, 0, 1, 'v'),
+        ];
+        for (allowed, dx, dy, head) in directions {
+            if !allowed {
+                continue;
+            }
+            // The box may sit right next to the line or one blank cell further.
+            for distance in 1..=2_isize {
+                let (Some(nx), Some(ny)) = (
+                    x.checked_add_signed(dx * distance),
+                    y.checked_add_signed(dy * distance),
+                ) else {
+                    break;
+                };
+                if let Some(index) = border(nx, ny) {
+                    let points = ch == head || (head == 'v' && ch == 'V');
+                    *touched.entry(index).or_default() |= points;
+                    break;
+                }
+                if art_cell(grid, nx, ny) != ' ' {
+                    break;
+                }
+            }
+        }
+    }
+    if touched.len() < 2 {
+        return Vec::new();
+    }
+
+    let thick = cells.iter().any(|&(x, y)| line[y][x] == '=');
+    let label = labels
+        .iter()
+        .find(|(x, y, _)| cells.contains(&(*x, *y)))
+        .map(|(_, _, text)| text.clone());
+    let heads: Vec<usize> = touched
+        .iter()
+        .filter(|(_, head)| **head)
+        .map(|(index, _)| *index)
+        .collect();
+    let tails: Vec<usize> = touched
+        .iter()
+        .filter(|(_, head)| !**head)
+        .map(|(index, _)| *index)
+        .collect();
+    let (pairs, arrow): (Vec<(usize, usize)>, ArrowType) =
+        match (tails.as_slice(), heads.as_slice()) {
+            ([first, rest @ ..], []) => (
+                rest.iter().map(|&other| (*first, other)).collect(),
+                if thick {
+                    ArrowType::ThickLine
+                } else {
+                    ArrowType::Line
+                },
+            ),
+            ([], [first, rest @ ..]) => (
+                rest.iter().map(|&other| (*first, other)).collect(),
+                if thick {
+                    ArrowType::DoubleThickArrow
+                } else {
+                    ArrowType::DoubleArrow
+                },
+            ),
+            _ => (
+                tails
+                    .iter()
+                    .flat_map(|&from| heads.iter().map(move |&to| (from, to)))
+                    .collect(),
+                if thick {
+                    ArrowType::ThickArrow
+                } else {
+                    ArrowType::Arrow
+                },
+            ),
+        };
+    pairs
+        .into_iter()
+        .map(|(from, to)| AsciiLink {
+            from,
+            to,
+            arrow,
+            label: label.clone(),
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -603,4 +1021,53 @@ Some text after
         assert!(unicode.contains('─'));
         assert!(unicode.contains('│'));
     }
+
+    #[test]
+    fn box_and_arrow_art_imports_as_a_flowchart() {
+        let art = "\
++-------+       +--------+
+| Start |--yes->| Finish |
++-------+       +--------+
+    |
+    v
++-------+
+| Retry |
++-------+";
+        let ir = parse_ascii_diagram(art).expect("boxes");
+        let labels: Vec<&str> = ir
+            .nodes
+            .iter()
+            .map(|node| ir.labels[node.label.expect("label").0].text.as_str())
+            .collect();
+        assert_eq!(labels, ["Start", "Finish", "Retry"]);
+        assert_eq!(ir.edges.len(), 2);
+        let edge = |from: usize, to: usize| {
+            ir.edges.iter().find(|edge| {
+                edge.from == IrEndpoint::Node(IrNodeId(from))
+                    && edge.to == IrEndpoint::Node(IrNodeId(to))
+            })
+        };
+        let yes = edge(0, 1).expect("Start -> Finish");
+        assert_eq!(yes.arrow, ArrowType::Arrow);
+        assert_eq!(ir.labels[yes.label.expect("edge label").0].text, "yes");
+        assert!(edge(0, 2).is_some_and(|edge| edge.label.is_none()));
+        assert_eq!(ir.graph.edges.len(), 2);
+        assert!(parse_ascii_diagram("no boxes here").is_none());
+    }
+
+    #[test]
+    fn unicode_boxes_with_gapped_plain_and_thick_lines_import() {
+        let art = "\
+┌───┐      ┌───┐ ===> ┌───┐
+│ A │ ──── │ B │      │ C │
+└───┘      └───┘      └───┘";
+        let ir = parse_ascii_diagram(art).expect("boxes");
+        assert_eq!(ir.nodes.len(), 3);
+        assert_eq!(ir.direction, GraphDirection::LR);
+        let arrows: Vec<ArrowType> = ir.edges.iter().map(|edge| edge.arrow).collect();
+        assert_eq!(arrows, [ArrowType::ThickArrow, ArrowType::Line]);
+
+        let rendered = crate::render_term(&ir);
+        assert!(["A", "B", "C"].iter().all(|label| rendered.contains(label)));
+    }
 }
@@ -4,9 +4,61 @@
 //! - Braille (2x4): Highest resolution using Unicode Braille characters (U+2800-U+28FF)
 //! - Block (2x2): Quarter block characters (U+2596-U+259F)
 //! - HalfBlock (1x2): Half block characters (▀▄█ )
+//!
+//! Drawing goes to one of several [`CanvasLayer`]s. When the canvas is flushed, each cell shows
+//! only the pixels of the topmost layer that touches it, so an edge running under a node's
+//! border leaves the border's Braille cells intact instead of merging dots with it.
+
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
 
 use fm_core::MermaidRenderMode;
 
+use crate::color::TermColor;
+
+/// Share of a pixel a line must cover for [`Canvas::draw_smooth_line`] to set it. Below one
+/// half, so the line's crossings from one pixel row to the next get both pixels.
+const SMOOTH_COVERAGE: f32 = 0.3;
+
+/// A drawing layer of a [`Canvas`], listed bottom to top in the default z-order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CanvasLayer {
+    /// Bands, lifelines and other backdrop decorations. Where drawing goes by default.
+    Background,
+    /// Cluster frames.
+    Clusters,
+    /// Edge paths.
+    Edges,
+    /// Node shapes.
+    Nodes,
+    /// Label decorations drawn as pixels.
+    Labels,
+    /// Markers drawn above everything else.
+    Overlay,
+}
+
+impl CanvasLayer {
+    /// Every layer, bottom to top.
+    pub const ALL: [Self; 6] = [
+        Self::Background,
+        Self::Clusters,
+        Self::Edges,
+        Self::Nodes,
+        Self::Labels,
+        Self::Overlay,
+    ];
+
+    const fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Pixels of a layer other than the one being drawn to.
+#[derive(Debug, Clone)]
+struct StashedLayer {
+    layer: CanvasLayer,
+    cell_gen: Vec<u32>,
+}
+
 /// A pixel-level canvas that maps to terminal cells.
 #[derive(Debug, Clone)]
 pub struct Canvas {
@@ -29,6 +81,17 @@ pub struct Canvas {
     mode: MermaidRenderMode,
     /// Generation counter for O(1) clear.
     generation: u32,
+    /// Layer `cell_gen` holds the pixels of.
+    active: CanvasLayer,
+    /// Pixels of the other layers drawn to so far.
+    stash: Vec<StashedLayer>,
+    /// Per cell, the generation its entry was set in and a bit per layer that claimed the cell
+    /// with one of the `occlude_` methods. Empty until a cell is claimed.
+    opaque: Vec<(u32, u8)>,
+    /// Stacking order of each layer, by [`CanvasLayer`] index: higher is drawn on top.
+    z_order: [u8; 6],
+    /// Color of each layer, by [`CanvasLayer`] index.
+    colors: [Option<TermColor>; 6],
 }
 
 impl Canvas {
@@ -48,6 +111,11 @@ impl Canvas {
             cell_height,
             mode,
             generation: 1,
+            active: CanvasLayer::Background,
+            stash: Vec::new(),
+            opaque: Vec::new(),
+            z_order: [0, 1, 2, 3, 4, 5],
+            colors: [None; 6],
         }
     }
 
@@ -58,6 +126,135 @@ impl Canvas {
             // Wrapped around, need to reset everything.
             self.generation = 1;
             self.cell_gen.fill(0);
+            for stashed in &mut self.stash {
+                stashed.cell_gen.fill(0);
+            }
+            self.opaque.fill((0, 0));
+        }
+    }
+
+    /// Send further drawing to `layer`. Pixel reads and writes only see the layer drawn to.
+    pub fn set_layer(&mut self, layer: CanvasLayer) {
+        if layer == self.active {
+            return;
+        }
+        let pixels = match self.stash.iter().position(|stashed| stashed.layer == layer) {
+            Some(index) => self.stash.swap_remove(index).cell_gen,
+            None => vec![0; self.cell_gen.len()],
+        };
+        let previous = std::mem::replace(&mut self.cell_gen, pixels);
+        self.stash.push(StashedLayer {
+            layer: self.active,
+            cell_gen: previous,
+        });
+        self.active = layer;
+    }
+
+    /// The layer being drawn to.
+    #[must_use]
+    pub const fn layer(&self) -> CanvasLayer {
+        self.active
+    }
+
+    /// Stack `layer` at `z`: where layers overlap, the one with the highest `z` is shown, the
+    /// later layer in [`CanvasLayer::ALL`] on a tie.
+    pub fn set_layer_z(&mut self, layer: CanvasLayer, z: u8) {
+        self.z_order[layer.index()] = z;
+    }
+
+    /// Color the cells `layer` shows in, as reported by [`Canvas::render_colors`].
+    pub fn set_layer_color(&mut self, layer: CanvasLayer, color: Option<TermColor>) {
+        self.colors[layer.index()] = color;
+    }
+
+    /// Claim every cell the pixel rectangle touches for the layer being drawn to, so lower layers
+    /// don't show through there even where it sets no pixels (a node's interior, say).
+    pub fn occlude_rect(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let (mult_x, mult_y) = subcell_multiplier(self.mode);
+        let last_x = ((x + width - 1) / mult_x).min(self.cell_width.saturating_sub(1));
+        let last_y = ((y + height - 1) / mult_y).min(self.cell_height.saturating_sub(1));
+        for cell_y in y / mult_y..=last_y {
+            for cell_x in x / mult_x..=last_x {
+                self.claim_cell(cell_x, cell_y);
+            }
+        }
+    }
+
+    /// Like [`Canvas::occlude_rect`] for the closed polygon through `points`: claims the cells
+    /// whose centers lie inside it.
+    pub fn occlude_polygon(&mut self, points: &[(isize, isize)]) {
+        self.occlude_where(points, |px, py| {
+            let mut inside = false;
+            for (index, &(x0, y0)) in points.iter().enumerate() {
+                let (x1, y1) = points[(index + 1) % points.len()];
+                let (x0, y0, x1, y1) = (x0 as f32, y0 as f32, x1 as f32, y1 as f32);
+                if (y0 > py) != (y1 > py) && px < x0 + (py - y0) / (y1 - y0) * (x1 - x0) {
+                    inside = !inside;
+                }
+            }
+            inside
+        });
+    }
+
+    /// Like [`Canvas::occlude_rect`] for the ellipse centered on (`cx`, `cy`).
+    pub fn occlude_ellipse(&mut self, cx: f32, cy: f32, rx: f32, ry: f32) {
+        if rx <= 0.0 || ry <= 0.0 {
+            return;
+        }
+        let corners = [
+            ((cx - rx).floor() as isize, (cy - ry).floor() as isize),
+            ((cx + rx).ceil() as isize, (cy + ry).ceil() as isize),
+        ];
+        self.occlude_where(&corners, |px, py| {
+            let (dx, dy) = ((px - cx) / rx, (py - cy) / ry);
+            dx * dx + dy * dy <= 1.0
+        });
+    }
+
+    /// Claim the cells within the bounding box of `points` whose pixel-space center passes
+    /// `inside`.
+    fn occlude_where(&mut self, points: &[(isize, isize)], inside: impl Fn(f32, f32) -> bool) {
+        let (Some(min_x), Some(max_x)) = (
+            points.iter().map(|point| point.0).min(),
+            points.iter().map(|point| point.0).max(),
+        ) else {
+            return;
+        };
+        let min_y = points.iter().map(|point| point.1).min().unwrap_or(0);
+        let max_y = points.iter().map(|point| point.1).max().unwrap_or(0);
+        let (mult_x, mult_y) = subcell_multiplier(self.mode);
+        let cells_x = min_x.max(0) as usize / mult_x
+            ..=(max_x.max(0) as usize / mult_x).min(self.cell_width.saturating_sub(1));
+        let cells_y = min_y.max(0) as usize / mult_y
+            ..=(max_y.max(0) as usize / mult_y).min(self.cell_height.saturating_sub(1));
+        for cell_y in cells_y {
+            for cell_x in cells_x.clone() {
+                let center_x = (cell_x * mult_x) as f32 + mult_x as f32 / 2.0;
+                let center_y = (cell_y * mult_y) as f32 + mult_y as f32 / 2.0;
+                if inside(center_x, center_y) {
+                    self.claim_cell(cell_x, cell_y);
+                }
+            }
+        }
+    }
+
+    /// Mark the cell as claimed by the layer being drawn to.
+    fn claim_cell(&mut self, cell_x: usize, cell_y: usize) {
+        if self.cell_width == 0 || self.cell_height == 0 {
+            return;
+        }
+        if self.opaque.is_empty() {
+            self.opaque = vec![(0, 0); self.cell_width * self.cell_height];
+        }
+        let bit = 1 << self.active.index();
+        let entry = &mut self.opaque[cell_y * self.cell_width + cell_x];
+        if entry.0 == self.generation {
+            entry.1 |= bit;
+        } else {
+            *entry = (self.generation, bit);
         }
     }
 
@@ -89,23 +286,16 @@ impl Canvas {
         }
     }
 
-    /// Get the value of a pixel at (x, y).
+    /// Get the value of a pixel at (x, y) on the layer being drawn to.
     #[must_use]
     pub fn get_pixel(&self, x: usize, y: usize) -> bool {
-        self.pixel_index(x, y)
-            .map(|index| self.pixel_set_at(index))
-            .unwrap_or(false)
+        self.pixel_in(&self.cell_gen, x, y)
     }
 
-    /// Whether the pixel at a KNOWN-valid buffer `index` is set this generation. The caller must have
-    /// established `index < cell_gen.len()` (e.g. from a cell wholly inside the pixel grid); this is the
-    /// body of [`get_pixel`] without the per-call `pixel_index` bounds-check + multiply + `Option`, so a
-    /// cell renderer that reads several fixed-offset sub-pixels off one precomputed base index skips that
-    /// recompute per sub-pixel. Byte-identical to `get_pixel(x, y)` for the in-bounds `(x, y)` that maps
-    /// to `index`.
-    #[inline]
-    fn pixel_set_at(&self, index: usize) -> bool {
-        self.cell_gen[index] == self.generation
+    /// Whether the pixel at (x, y) of the layer buffer `pixels` is set this generation.
+    fn pixel_in(&self, pixels: &[u32], x: usize, y: usize) -> bool {
+        self.pixel_index(x, y)
+            .is_some_and(|index| pixels[index] == self.generation)
     }
 
     /// Draw a line from (x0, y0) to (x1, y1) using Bresenham's algorithm.
@@ -182,6 +372,85 @@ impl Canvas {
         }
     }
 
+    /// Draw a line two pixels wide: a second line one pixel across the main direction of travel.
+    pub fn draw_thick_line(&mut self, x0: isize, y0: isize, x1: isize, y1: isize) {
+        let (ox, oy) = if (x1 - x0).abs() >= (y1 - y0).abs() {
+            (0, 1)
+        } else {
+            (1, 0)
+        };
+        self.draw_line(x0, y0, x1, y1);
+        self.draw_line(x0 + ox, y0 + oy, x1 + ox, y1 + oy);
+    }
+
+    /// Draw a line between sub-pixel endpoints with a Wu-style weighted rasterizer. Each step
+    /// along the main axis weighs the two pixels the exact line passes between and sets every
+    /// one covered at least [`SMOOTH_COVERAGE`]; where the line crosses from one row of pixels
+    /// to the next both are set, which softens the stair steps of shallow slopes. With `thick`
+    /// both pixels are always set, for a line two pixels wide.
+    pub fn draw_smooth_line(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, thick: bool) {
+        let steep = (y1 - y0).abs() > (x1 - x0).abs();
+        let (mut x0, mut y0, mut x1, mut y1) = if steep {
+            (y0, x0, y1, x1)
+        } else {
+            (x0, y0, x1, y1)
+        };
+        if x0 > x1 {
+            std::mem::swap(&mut x0, &mut x1);
+            std::mem::swap(&mut y0, &mut y1);
+        }
+        let run = x1 - x0;
+        let gradient = if run > f32::EPSILON {
+            (y1 - y0) / run
+        } else {
+            0.0
+        };
+        for major in (x0.round() as isize)..=(x1.round() as isize) {
+            let minor = y0 + gradient * (major as f32 - x0);
+            let below = minor.floor();
+            let coverage = minor - below;
+            let below = below as isize;
+            let weights = [(below, 1.0 - coverage), (below + 1, coverage)];
+            for (row, weight) in weights {
+                if thick || weight >= SMOOTH_COVERAGE {
+                    let (x, y) = if steep { (row, major) } else { (major, row) };
+                    if x >= 0 && y >= 0 {
+                        self.set_pixel(x as usize, y as usize);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Draw a dashed line: runs of `dash` pixels, alternately set and left blank.
+    pub fn draw_dashed_line(&mut self, x0: isize, y0: isize, x1: isize, y1: isize, dash: usize) {
+        let dash = dash.max(1);
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let sx = if x0 < x1 { 1 } else { -1 };
+        let sy = if y0 < y1 { 1 } else { -1 };
+        let mut err = dx + dy;
+        let (mut x, mut y) = (x0, y0);
+
+        for step in 0.. {
+            if (step / dash) % 2 == 0 && x >= 0 && y >= 0 {
+                self.set_pixel(x as usize, y as usize);
+            }
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
     /// Draw a rectangle outline.
     pub fn draw_rect(&mut self, x: usize, y: usize, width: usize, height: usize) {
         if width == 0 || height == 0 {
@@ -199,6 +468,81 @@ impl Canvas {
         self.draw_line(x1, y0, x1, y1); // Right
     }
 
+    /// Draw a rectangle outline whose corners are quarter circles of `radius` pixels, capped so
+    /// opposite corners don't overlap. A radius of half the height gives a stadium.
+    pub fn draw_rounded_rect(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        radius: usize,
+    ) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let radius = radius.min((width - 1) / 2).min((height - 1) / 2);
+        if radius == 0 {
+            self.draw_rect(x, y, width, height);
+            return;
+        }
+
+        let r = radius as isize;
+        let x0 = x as isize;
+        let y0 = y as isize;
+        let x1 = (x + width - 1) as isize;
+        let y1 = (y + height - 1) as isize;
+        self.draw_line(x0 + r, y0, x1 - r, y0); // Top
+        self.draw_line(x0 + r, y1, x1 - r, y1); // Bottom
+        self.draw_line(x0, y0 + r, x0, y1 - r); // Left
+        self.draw_line(x1, y0 + r, x1, y1 - r); // Right
+
+        let rf = radius as f32;
+        let corners = [
+            (x1 - r, y1 - r, 0.0),
+            (x0 + r, y1 - r, FRAC_PI_2),
+            (x0 + r, y0 + r, PI),
+            (x1 - r, y0 + r, PI + FRAC_PI_2),
+        ];
+        for (cx, cy, start) in corners {
+            self.draw_arc(cx as f32, cy as f32, rf, rf, start, start + FRAC_PI_2);
+        }
+    }
+
+    /// Draw the arc of the ellipse centered on (`cx`, `cy`) with radii `rx` and `ry`, from angle
+    /// `start` to `end` in radians. Angles turn from +x towards +y, which is down the canvas.
+    pub fn draw_arc(&mut self, cx: f32, cy: f32, rx: f32, ry: f32, start: f32, end: f32) {
+        let sweep = end - start;
+        // About one pixel of arc per segment.
+        let steps = ((rx.max(ry) * sweep.abs()).ceil() as usize).max(1);
+        let point = |step: usize| {
+            let angle = start + sweep * step as f32 / steps as f32;
+            (
+                (cx + rx * angle.cos()).round() as isize,
+                (cy + ry * angle.sin()).round() as isize,
+            )
+        };
+        let mut from = point(0);
+        for step in 1..=steps {
+            let to = point(step);
+            self.draw_line(from.0, from.1, to.0, to.1);
+            from = to;
+        }
+    }
+
+    /// Draw an ellipse outline centered on (`cx`, `cy`).
+    pub fn draw_ellipse(&mut self, cx: f32, cy: f32, rx: f32, ry: f32) {
+        self.draw_arc(cx, cy, rx, ry, 0.0, TAU);
+    }
+
+    /// Draw the closed polygon through `points`.
+    pub fn draw_polygon(&mut self, points: &[(isize, isize)]) {
+        for (index, &(x0, y0)) in points.iter().enumerate() {
+            let (x1, y1) = points[(index + 1) % points.len()];
+            self.draw_line(x0, y0, x1, y1);
+        }
+    }
+
     /// Fill a rectangle.
     pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize) {
         for dy in 0..height {
@@ -311,15 +655,73 @@ impl Canvas {
         grid
     }
 
-    /// Render a single cell to its character.
+    /// Per cell, the color of the layer it shows, or `None` for empty cells and layers without
+    /// a color. Laid out like [`Canvas::render_char_grid`].
+    #[must_use]
+    pub fn render_colors(&self) -> Vec<Vec<Option<TermColor>>> {
+        (0..self.cell_height)
+            .map(|cell_y| {
+                (0..self.cell_width)
+                    .map(|cell_x| {
+                        self.cell_owner(cell_x, cell_y)
+                            .and_then(|layer| self.colors[layer.index()])
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Pixel buffer of `layer`, if it has been drawn to.
+    fn layer_pixels(&self, layer: CanvasLayer) -> Option<&[u32]> {
+        if layer == self.active {
+            return Some(&self.cell_gen);
+        }
+        self.stash
+            .iter()
+            .find(|stashed| stashed.layer == layer)
+            .map(|stashed| stashed.cell_gen.as_slice())
+    }
+
+    /// The topmost layer that sets a pixel in the cell or claimed it with one of the `occlude_`
+    /// methods.
+    fn cell_owner(&self, cell_x: usize, cell_y: usize) -> Option<CanvasLayer> {
+        let (mult_x, mult_y) = subcell_multiplier(self.mode);
+        let claimed = self
+            .opaque
+            .get(cell_y * self.cell_width + cell_x)
+            .filter(|entry| entry.0 == self.generation)
+            .map_or(0, |entry| entry.1);
+        CanvasLayer::ALL
+            .into_iter()
+            .filter(|layer| {
+                claimed & (1 << layer.index()) != 0
+                    || self.layer_pixels(*layer).is_some_and(|pixels| {
+                        (0..mult_y).any(|dy| {
+                            (0..mult_x).any(|dx| {
+                                self.pixel_in(pixels, cell_x * mult_x + dx, cell_y * mult_y + dy)
+                            })
+                        })
+                    })
+            })
+            .max_by_key(|layer| self.z_order[layer.index()])
+    }
+
+    /// Render a single cell to its character, from the pixels of the layer it shows.
     #[must_use]
     fn render_cell(&self, cell_x: usize, cell_y: usize) -> char {
+        let pixels = if self.stash.is_empty() && self.opaque.is_empty() {
+            &self.cell_gen
+        } else {
+            self.cell_owner(cell_x, cell_y)
+                .and_then(|layer| self.layer_pixels(layer))
+                .unwrap_or(&self.cell_gen)
+        };
         match self.mode {
-            MermaidRenderMode::Braille => self.render_braille_cell(cell_x, cell_y),
-            MermaidRenderMode::Block => self.render_block_cell(cell_x, cell_y),
-            MermaidRenderMode::HalfBlock => self.render_halfblock_cell(cell_x, cell_y),
-            MermaidRenderMode::CellOnly | MermaidRenderMode::Auto => {
-                if self.get_pixel(cell_x, cell_y) {
+            MermaidRenderMode::Braille => self.render_braille_cell(pixels, cell_x, cell_y),
+            MermaidRenderMode::Block => self.render_block_cell(pixels, cell_x, cell_y),
+            MermaidRenderMode::HalfBlock => self.render_halfblock_cell(pixels, cell_x, cell_y),
+            MermaidRenderMode::CellOnly | MermaidRenderMode::Auto | MermaidRenderMode::Pixels => {
+                if self.pixel_in(pixels, cell_x, cell_y) {
                     '█'
                 } else {
                     ' '
@@ -328,8 +730,8 @@ impl Canvas {
         }
     }
 
-    /// Render a 2x4 pixel block as a Braille character.
-    fn render_braille_cell(&self, cell_x: usize, cell_y: usize) -> char {
+    /// Render a 2x4 pixel block of `pixels` as a Braille character.
+    fn render_braille_cell(&self, pixels: &[u32], cell_x: usize, cell_y: usize) -> char {
         // Braille dot pattern:
         // 0 3
         // 1 4
@@ -341,21 +743,21 @@ impl Canvas {
         let mut code_point = 0x2800_u32; // Unicode Braille base
 
         // Fast path: the whole 2x4 sub-pixel block is inside the grid, so compute the base index ONCE
-        // and read the 8 dots by fixed offset (`base + row*width + col`) instead of 8 `get_pixel` calls,
+        // and read the 8 dots by fixed offset (`base + row*width + col`) instead of 8 `pixel_in` calls,
         // each of which recomputes `pixel_index` (bounds check + `y*width+x` multiply + `Option`). This
         // is the default (Braille) render mode, called once per output cell. Byte-identical: every
-        // `pixel_set_at(base + r*w + c)` equals `get_pixel(px+c, py+r)` because the gate guarantees each
+        // `pixels[base + r*w + c]` is the dot `pixel_in(pixels, px+c, py+r)` reads because the gate guarantees each
         // `(px+c, py+r)` is in bounds and maps to exactly that index.
         if px + 1 < self.pixel_width && py + 3 < self.pixel_height {
             let w = self.pixel_width;
             let base = py * w + px;
             let cur_gen = self.generation;
-            // Slice `cell_gen` ONCE over the cell's `[base, base + 3w + 1]` span (the maximum dot index),
+            // Slice `pixels` ONCE over the cell's `[base, base + 3w + 1]` span (the maximum dot index),
             // then read the 8 dots by relative offset. Each offset is `<= 3w + 1 == cell.len() - 1`, so the
             // compiler proves every `cell[offset]` in-bounds from the single slice length and drops the
-            // per-dot bounds check that the 8 `pixel_set_at` calls each paid (it was not inlined — a
-            // separate ~13% frame). Byte-identical: `cell[o] == gen` == `pixel_set_at(base + o)`.
-            let cell = &self.cell_gen[base..=base + 3 * w + 1];
+            // per-dot bounds check that 8 separate index reads would each pay. Byte-identical:
+            // `cell[o] == gen` == `pixels[base + o] == gen`.
+            let cell = &pixels[base..=base + 3 * w + 1];
             if cell[0] == cur_gen {
                 code_point |= 0x01;
             } // Dot 1 (px, py)
@@ -382,28 +784,28 @@ impl Canvas {
             } // Dot 8 (px+1, py+3)
         } else {
             // Edge cell: bounds-safe per-pixel reads (unchanged).
-            if self.get_pixel(px, py) {
+            if self.pixel_in(pixels, px, py) {
                 code_point |= 0x01;
             }
-            if self.get_pixel(px, py + 1) {
+            if self.pixel_in(pixels, px, py + 1) {
                 code_point |= 0x02;
             }
-            if self.get_pixel(px, py + 2) {
+            if self.pixel_in(pixels, px, py + 2) {
                 code_point |= 0x04;
             }
-            if self.get_pixel(px + 1, py) {
+            if self.pixel_in(pixels, px + 1, py) {
                 code_point |= 0x08;
             }
-            if self.get_pixel(px + 1, py + 1) {
+            if self.pixel_in(pixels, px + 1, py + 1) {
                 code_point |= 0x10;
             }
-            if self.get_pixel(px + 1, py + 2) {
+            if self.pixel_in(pixels, px + 1, py + 2) {
                 code_point |= 0x20;
             }
-            if self.get_pixel(px, py + 3) {
+            if self.pixel_in(pixels, px, py + 3) {
                 code_point |= 0x40;
             }
-            if self.get_pixel(px + 1, py + 3) {
+            if self.pixel_in(pixels, px + 1, py + 3) {
                 code_point |= 0x80;
             }
         }
@@ -411,15 +813,15 @@ impl Canvas {
         char::from_u32(code_point).unwrap_or(' ')
     }
 
-    /// Render a 2x2 pixel block as a quarter block character.
-    fn render_block_cell(&self, cell_x: usize, cell_y: usize) -> char {
+    /// Render a 2x2 pixel block of `pixels` as a quarter block character.
+    fn render_block_cell(&self, pixels: &[u32], cell_x: usize, cell_y: usize) -> char {
         let px = cell_x * 2;
         let py = cell_y * 2;
 
-        let tl = self.get_pixel(px, py);
-        let tr = self.get_pixel(px + 1, py);
-        let bl = self.get_pixel(px, py + 1);
-        let br = self.get_pixel(px + 1, py + 1);
+        let tl = self.pixel_in(pixels, px, py);
+        let tr = self.pixel_in(pixels, px + 1, py);
+        let bl = self.pixel_in(pixels, px, py + 1);
+        let br = self.pixel_in(pixels, px + 1, py + 1);
 
         match (tl, tr, bl, br) {
             (false, false, false, false) => ' ',
@@ -441,12 +843,12 @@ impl Canvas {
         }
     }
 
-    /// Render a 1x2 pixel block as a half block character.
-    fn render_halfblock_cell(&self, cell_x: usize, cell_y: usize) -> char {
+    /// Render a 1x2 pixel block of `pixels` as a half block character.
+    fn render_halfblock_cell(&self, pixels: &[u32], cell_x: usize, cell_y: usize) -> char {
         let py = cell_y * 2;
 
-        let top = self.get_pixel(cell_x, py);
-        let bottom = self.get_pixel(cell_x, py + 1);
+        let top = self.pixel_in(pixels, cell_x, py);
+        let bottom = self.pixel_in(pixels, cell_x, py + 1);
 
         match (top, bottom) {
             (false, false) => ' ',
@@ -472,7 +874,7 @@ pub const fn subcell_multiplier(mode: MermaidRenderMode) -> (usize, usize) {
         MermaidRenderMode::Braille => (2, 4),
         MermaidRenderMode::Block => (2, 2),
         MermaidRenderMode::HalfBlock => (1, 2),
-        MermaidRenderMode::CellOnly | MermaidRenderMode::Auto => (1, 1),
+        MermaidRenderMode::CellOnly | MermaidRenderMode::Auto | MermaidRenderMode::Pixels => (1, 1),
     }
 }
 
@@ -567,4 +969,98 @@ mod tests {
         canvas.set_pixel(1, 1);
         assert_eq!(canvas.render(), "█");
     }
+
+    #[test]
+    fn top_layer_owns_shared_cells() {
+        let mut canvas = Canvas::new(3, 1, MermaidRenderMode::Braille);
+        canvas.set_layer(CanvasLayer::Edges);
+        canvas.draw_line(0, 1, 5, 1);
+        canvas.set_layer(CanvasLayer::Nodes);
+        canvas.draw_line(2, 0, 2, 3);
+        assert_eq!(canvas.layer(), CanvasLayer::Nodes);
+        assert!(!canvas.get_pixel(0, 1), "reads see only the layer drawn to");
+        // The middle cell shows the node's left column alone, not merged with the edge.
+        assert_eq!(canvas.render(), "⠒⡇⠒");
+
+        canvas.set_layer_z(CanvasLayer::Edges, 9);
+        assert_eq!(canvas.render(), "⠒⠒⠒");
+
+        let red = TermColor::new(255, 0, 0);
+        canvas.set_layer_color(CanvasLayer::Edges, Some(red));
+        assert_eq!(canvas.render_colors(), vec![vec![Some(red); 3]]);
+
+        canvas.clear();
+        assert_eq!(canvas.render(), "⠀⠀⠀");
+    }
+
+    #[test]
+    fn occluded_cells_hide_lower_layers() {
+        let mut canvas = Canvas::new(4, 1, MermaidRenderMode::CellOnly);
+        canvas.draw_line(0, 0, 3, 0);
+        canvas.set_layer(CanvasLayer::Nodes);
+        canvas.occlude_rect(1, 0, 2, 1);
+        assert_eq!(canvas.render(), "█  █");
+    }
+
+    #[test]
+    fn smooth_lines_bridge_the_steps_of_shallow_slopes() {
+        let mut plain = Canvas::new(10, 2, MermaidRenderMode::Braille);
+        plain.draw_line(0, 0, 19, 4);
+        let mut smooth = Canvas::new(10, 2, MermaidRenderMode::Braille);
+        smooth.draw_smooth_line(0.0, 0.0, 19.0, 4.0, false);
+        let count = |canvas: &Canvas| {
+            (0..20)
+                .flat_map(|x| (0..8).map(move |y| (x, y)))
+                .filter(|&(x, y)| canvas.get_pixel(x, y))
+                .count()
+        };
+        assert!(smooth.get_pixel(0, 0) && smooth.get_pixel(19, 4));
+        // Every column is covered, and some twice where the line changes rows.
+        assert!((0..20).all(|x| (0..8).any(|y| smooth.get_pixel(x, y))));
+        assert!(count(&smooth) > count(&plain), "{}", smooth.render());
+
+        let mut thick = Canvas::new(10, 2, MermaidRenderMode::Braille);
+        thick.draw_smooth_line(0.0, 0.0, 19.0, 4.0, true);
+        assert!((0..20).all(|x| (0..8).filter(|&y| thick.get_pixel(x, y)).count() == 2));
+
+        let mut steep = Canvas::new(2, 4, MermaidRenderMode::Braille);
+        steep.draw_smooth_line(1.0, 0.0, 1.0, 15.0, true);
+        assert!((0..16).all(|y| steep.get_pixel(1, y) && steep.get_pixel(2, y)));
+    }
+
+    #[test]
+    fn rounded_rects_and_ellipses_bend_their_corners() {
+        let mut canvas = Canvas::new(10, 10, MermaidRenderMode::CellOnly);
+        canvas.draw_rounded_rect(0, 0, 9, 5, 2);
+        assert!(!canvas.get_pixel(0, 0) && !canvas.get_pixel(8, 4));
+        assert!(canvas.get_pixel(4, 0) && canvas.get_pixel(0, 2) && canvas.get_pixel(8, 2));
+        assert!(canvas.get_pixel(1, 1) && canvas.get_pixel(7, 3));
+
+        canvas.clear();
+        canvas.draw_ellipse(4.0, 4.0, 4.0, 2.0);
+        for (x, y) in [(8, 4), (4, 6), (0, 4), (4, 2)] {
+            assert!(canvas.get_pixel(x, y), "({x}, {y})");
+        }
+        assert!(!canvas.get_pixel(4, 4) && !canvas.get_pixel(0, 2));
+    }
+
+    #[test]
+    fn polygon_and_ellipse_occlusion_follows_the_outline() {
+        let mut canvas = Canvas::new(5, 5, MermaidRenderMode::CellOnly);
+        canvas.fill_rect(0, 0, 5, 5);
+        canvas.set_layer(CanvasLayer::Nodes);
+        canvas.occlude_polygon(&[(2, 0), (4, 2), (2, 4), (0, 2)]);
+        let rows = canvas.render_char_grid();
+        assert_eq!(rows[2][2], ' ', "{}", canvas.render());
+        for (x, y) in [(0, 0), (4, 0), (4, 4), (0, 4)] {
+            assert_eq!(rows[y][x], '█', "({x}, {y}): {}", canvas.render());
+        }
+
+        canvas.clear();
+        canvas.set_layer(CanvasLayer::Background);
+        canvas.fill_rect(0, 0, 5, 5);
+        canvas.set_layer(CanvasLayer::Nodes);
+        canvas.occlude_ellipse(2.5, 2.5, 2.5, 2.5);
+        assert_eq!(canvas.render(), "█   █\n     \n     \n     \n█   █");
+    }
 }
@@ -0,0 +1,447 @@
+//! Terminal capability detection.
+//!
+//! [`TerminalCapabilities::detect`] looks at the environment the way most terminal tools do:
+//! `TERM`, `COLORTERM`, `TERM_PROGRAM`, the locale, the `colors` entry of the compiled
+//! terminfo description, and variables a few emulators export to identify themselves. `FM_*`
+//! variables override any single answer, and a named profile (`FM_CAPABILITY_PROFILE`, or
+//! `MermaidConfig::capability_profile`) pins all of them at once.
+//! [`TermRenderConfig::auto`](crate::TermRenderConfig::auto) turns the result into glyph, color
+//! and render modes.
+
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+use fm_core::MermaidConfig;
+
+use crate::color::ColorMode;
+use crate::pixels::ImageProtocol;
+
+/// Height of a terminal cell divided by its width, when nothing says otherwise.
+const DEFAULT_CELL_ASPECT_RATIO: f32 = 2.0;
+
+/// Index of the `colors` numeric capability in a compiled terminfo entry.
+const TERMINFO_COLORS: usize = 13;
+
+/// What the terminal can display.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerminalCapabilities {
+    /// Box-drawing, block and Braille characters render correctly.
+    pub unicode: bool,
+    /// Deepest color escape sequences understood.
+    pub color: ColorMode,
+    /// OSC 8 hyperlinks are clickable.
+    pub hyperlinks: bool,
+    /// Inline image protocol, or [`ImageProtocol::None`].
+    pub images: ImageProtocol,
+    /// Cell height divided by cell width.
+    pub cell_aspect_ratio: f32,
+}
+
+impl Default for TerminalCapabilities {
+    /// A plain terminal: ASCII, no color, links or images.
+    fn default() -> Self {
+        Self {
+            unicode: false,
+            color: ColorMode::None,
+            hyperlinks: false,
+            images: ImageProtocol::None,
+            cell_aspect_ratio: DEFAULT_CELL_ASPECT_RATIO,
+        }
+    }
+}
+
+impl TerminalCapabilities {
+    /// Capabilities of the current terminal.
+    #[must_use]
+    pub fn detect() -> Self {
+        Self::detect_from(|name| std::env::var(name).ok())
+    }
+
+    /// Capabilities pinned by `config.capability_profile`, or detected when it is unset or
+    /// names no known profile.
+    #[must_use]
+    pub fn for_mermaid_config(config: &MermaidConfig) -> Self {
+        config
+            .capability_profile
+            .as_deref()
+            .and_then(Self::from_profile)
+            .unwrap_or_else(Self::detect)
+    }
+
+    /// Named capability profile:
+    ///
+    /// | Profile | Unicode | Color | Hyperlinks | Images |
+    /// |---|---|---|---|---|
+    /// | `dumb`, `ascii` | no | none | no | none |
+    /// | `basic`, `ansi16` | yes | 16 | no | none |
+    /// | `256`, `xterm-256color` | yes | 256 | no | none |
+    /// | `truecolor` | yes | 24-bit | yes | none |
+    /// | `kitty` | yes | 24-bit | yes | Kitty |
+    /// | `iterm2` | yes | 24-bit | yes | iTerm2 |
+    /// | `sixel` | yes | 24-bit | yes | Sixel |
+    #[must_use]
+    pub fn from_profile(name: &str) -> Option<Self> {
+        let rich = |images| Self {
+            unicode: true,
+            color: ColorMode::TrueColor,
+            hyperlinks: true,
+            images,
+            ..Self::default()
+        };
+        let profile = match name.trim().to_ascii_lowercase().as_str() {
+            "dumb" | "ascii" => Self::default(),
+            "basic" | "ansi16" => Self {
+                unicode: true,
+                color: ColorMode::Ansi16,
+                ..Self::default()
+            },
+            "256" | "xterm-256color" => Self {
+                unicode: true,
+                color: ColorMode::Ansi256,
+                ..Self::default()
+            },
+            "truecolor" => rich(ImageProtocol::None),
+            "kitty" => rich(ImageProtocol::Kitty),
+            "iterm2" => rich(ImageProtocol::Iterm2),
+            "sixel" => rich(ImageProtocol::Sixel),
+            _ => return None,
+        };
+        Some(profile)
+    }
+
+    /// [`detect`](Self::detect) with environment lookups going through `var`.
+    ///
+    /// Overrides: `FM_CAPABILITY_PROFILE` (a [profile](Self::from_profile)), `FM_UNICODE` and
+    /// `FM_HYPERLINKS` (`1`/`0`), `FM_COLOR` (`none`, `16`, `256` or `truecolor`),
+    /// `FM_IMAGE_PROTOCOL` and `FM_CELL_ASPECT`. `NO_COLOR` turns color off.
+    #[must_use]
+    pub fn detect_from(var: impl Fn(&str) -> Option<String>) -> Self {
+        if let Some(profile) = var("FM_CAPABILITY_PROFILE")
+            .as_deref()
+            .and_then(Self::from_profile)
+        {
+            return profile;
+        }
+        let term = var("TERM").unwrap_or_default();
+        let program = var("TERM_PROGRAM").unwrap_or_default();
+
+        let unicode = var("FM_UNICODE")
+            .as_deref()
+            .and_then(parse_flag)
+            .unwrap_or_else(|| detect_unicode(&var, &term));
+        let color = var("FM_COLOR")
+            .as_deref()
+            .and_then(parse_color_mode)
+            .unwrap_or_else(|| detect_color(&var, &term, &program));
+        let hyperlinks = var("FM_HYPERLINKS")
+            .as_deref()
+            .and_then(parse_flag)
+            .unwrap_or_else(|| detect_hyperlinks(&var, &term, &program));
+        let cell_aspect_ratio = var("FM_CELL_ASPECT")
+            .and_then(|value| value.trim().parse::<f32>().ok())
+            .filter(|ratio| ratio.is_finite() && *ratio > 0.0)
+            .unwrap_or(DEFAULT_CELL_ASPECT_RATIO);
+
+        Self {
+            unicode,
+            color,
+            hyperlinks,
+            images: ImageProtocol::detect_from(&var),
+            cell_aspect_ratio,
+        }
+    }
+}
+
+/// When [`TermRenderConfig::auto_glyph_mode`](crate::TermRenderConfig::auto_glyph_mode) swaps
+/// the configured glyph mode for ASCII.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AutoGlyphMode {
+    /// Always draw with the configured glyph mode.
+    #[default]
+    Off,
+    /// ASCII when `LC_ALL`, `LC_CTYPE` or `LANG` names a locale that is not UTF-8. An unset
+    /// locale says nothing either way; `FM_UNICODE` overrides the locale.
+    Locale,
+    /// As `Locale`, and also ASCII whenever stdout is not a terminal, for output redirected to
+    /// a file or piped into another program (`--ascii-on-pipe`).
+    LocaleOrPipe,
+}
+
+impl AutoGlyphMode {
+    /// Whether the current environment calls for ASCII glyphs.
+    #[must_use]
+    pub fn forces_ascii(self) -> bool {
+        self.forces_ascii_from(
+            |name| std::env::var(name).ok(),
+            std::io::stdout().is_terminal(),
+        )
+    }
+
+    /// [`forces_ascii`](Self::forces_ascii) with environment lookups going through `var` and
+    /// `stdout_is_terminal` standing in for the check on stdout.
+    #[must_use]
+    pub fn forces_ascii_from(
+        self,
+        var: impl Fn(&str) -> Option<String>,
+        stdout_is_terminal: bool,
+    ) -> bool {
+        let non_utf8_locale = || match var("FM_UNICODE").as_deref().and_then(parse_flag) {
+            Some(unicode) => !unicode,
+            None => locale_is_utf8(&var) == Some(false),
+        };
+        match self {
+            Self::Off => false,
+            Self::Locale => non_utf8_locale(),
+            Self::LocaleOrPipe => !stdout_is_terminal || non_utf8_locale(),
+        }
+    }
+}
+
+fn parse_flag(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_color_mode(value: &str) -> Option<ColorMode> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "none" | "off" => Some(ColorMode::None),
+        "16" | "ansi16" => Some(ColorMode::Ansi16),
+        "256" | "ansi256" => Some(ColorMode::Ansi256),
+        "truecolor" | "24bit" => Some(ColorMode::TrueColor),
+        _ => None,
+    }
+}
+
+/// A UTF-8 locale on anything but the dumb terminal and the Linux console, whose font lacks
+/// Braille.
+fn detect_unicode(var: &impl Fn(&str) -> Option<String>, term: &str) -> bool {
+    if matches!(term, "dumb" | "linux") {
+        return false;
+    }
+    locale_is_utf8(var).unwrap_or(false) || var("WT_SESSION").is_some()
+}
+
+/// Whether the first of `LC_ALL`, `LC_CTYPE` and `LANG` that is set names a UTF-8 locale, or
+/// `None` when none of them is.
+fn locale_is_utf8(var: &impl Fn(&str) -> Option<String>) -> Option<bool> {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .into_iter()
+        .find_map(|name| var(name).filter(|value| !value.is_empty()))?
+        .to_ascii_lowercase();
+    Some(locale.contains("utf-8") || locale.contains("utf8"))
+}
+
+fn detect_color(var: &impl Fn(&str) -> Option<String>, term: &str, program: &str) -> ColorMode {
+    if var("NO_COLOR").is_some_and(|value| !value.is_empty()) || term == "dumb" {
+        return ColorMode::None;
+    }
+    let colorterm = var("COLORTERM").unwrap_or_default().to_ascii_lowercase();
+    if matches!(colorterm.as_str(), "truecolor" | "24bit")
+        || matches!(program, "iTerm.app" | "WezTerm" | "ghostty" | "vscode")
+        || var("KITTY_WINDOW_ID").is_some()
+        || var("WT_SESSION").is_some()
+    {
+        return ColorMode::TrueColor;
+    }
+    if term.contains("256color") {
+        return ColorMode::Ansi256;
+    }
+    match terminfo_colors(var, term) {
+        Some(colors) if colors >= 1 << 24 => ColorMode::TrueColor,
+        Some(colors) if colors >= 256 => ColorMode::Ansi256,
+        Some(colors) if colors >= 8 => ColorMode::Ansi16,
+        Some(_) => ColorMode::None,
+        None if term.is_empty() => ColorMode::None,
+        None => ColorMode::Ansi16,
+    }
+}
+
+fn detect_hyperlinks(var: &impl Fn(&str) -> Option<String>, term: &str, program: &str) -> bool {
+    matches!(program, "iTerm.app" | "WezTerm" | "ghostty" | "vscode")
+        || matches!(term, "xterm-kitty" | "foot" | "foot-extra" | "alacritty")
+        || var("KITTY_WINDOW_ID").is_some()
+        || var("WT_SESSION").is_some()
+        || var("KONSOLE_VERSION").is_some()
+        // VTE (GNOME Terminal, Tilix, …) has supported OSC 8 since 0.50.
+        || var("VTE_VERSION")
+            .and_then(|version| version.trim().parse::<u32>().ok())
+            .is_some_and(|version| version >= 5000)
+}
+
+/// `colors` from the compiled terminfo entry for `term`, searched for where ncurses looks.
+fn terminfo_colors(var: &impl Fn(&str) -> Option<String>, term: &str) -> Option<u32> {
+    if term.is_empty() || term.starts_with('.') || term.contains(['/', '\\']) {
+        return None;
+    }
+    let first = term.chars().next()?;
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    dirs.extend(var("TERMINFO").map(PathBuf::from));
+    dirs.extend(var("HOME").map(|home| Path::new(&home).join(".terminfo")));
+    if let Some(list) = var("TERMINFO_DIRS") {
+        dirs.extend(
+            list.split(':')
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+        );
+    }
+    dirs.extend(["/etc/terminfo", "/lib/terminfo", "/usr/share/terminfo"].map(PathBuf::from));
+
+    dirs.iter()
+        .flat_map(|dir| {
+            [
+                dir.join(first.to_string()).join(term),
+                dir.join(format!("{:x}", u32::from(first))).join(term),
+            ]
+        })
+        .find_map(|path| std::fs::read(path).ok())
+        .and_then(|entry| parse_terminfo_colors(&entry))
+}
+
+/// `colors` from a compiled terminfo entry, in the legacy (16-bit numbers) or extended-number
+/// (32-bit) format. `None` when absent or the entry is malformed.
+fn parse_terminfo_colors(entry: &[u8]) -> Option<u32> {
+    let header = |index: usize| {
+        let bytes = entry.get(index * 2..index * 2 + 2)?;
+        usize::try_from(i16::from_le_bytes([bytes[0], bytes[1]])).ok()
+    };
+    let number_size = match header(0)? {
+        0o432 => 2,
+        0o1036 => 4,
+        _ => return None,
+    };
+    let (names, booleans, numbers) = (header(1)?, header(2)?, header(3)?);
+    if numbers <= TERMINFO_COLORS {
+        return None;
+    }
+    // Numbers start on an even offset after the 12-byte header, names and booleans.
+    let numbers_start = (12 + names + booleans).next_multiple_of(2);
+    let start = numbers_start + TERMINFO_COLORS * number_size;
+    let bytes = entry.get(start..start + number_size)?;
+    let colors = if number_size == 2 {
+        i32::from(i16::from_le_bytes([bytes[0], bytes[1]]))
+    } else {
+        i32::from_le_bytes(bytes.try_into().ok()?)
+    };
+    u32::try_from(colors).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(vars: &[(&str, &str)]) -> TerminalCapabilities {
+        TerminalCapabilities::detect_from(|name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| (*value).to_string())
+        })
+    }
+
+    #[test]
+    fn environment_selects_unicode_color_links_and_images() {
+        let kitty = detect(&[
+            ("TERM", "xterm-kitty"),
+            ("LANG", "en_US.UTF-8"),
+            ("KITTY_WINDOW_ID", "1"),
+        ]);
+        assert!(kitty.unicode && kitty.hyperlinks);
+        assert_eq!(kitty.color, ColorMode::TrueColor);
+        assert_eq!(kitty.images, ImageProtocol::Kitty);
+
+        let xterm = detect(&[("TERM", "xterm-256color"), ("LC_ALL", "C.UTF-8")]);
+        assert!(xterm.unicode && !xterm.hyperlinks);
+        assert_eq!(xterm.color, ColorMode::Ansi256);
+        assert_eq!(xterm.images, ImageProtocol::None);
+
+        let console = detect(&[("TERM", "linux"), ("LANG", "en_US.UTF-8")]);
+        assert!(!console.unicode);
+        assert_eq!(detect(&[]), TerminalCapabilities::default());
+    }
+
+    #[test]
+    fn overrides_and_profiles_win_over_detection() {
+        let forced = detect(&[
+            ("TERM", "xterm-256color"),
+            ("COLORTERM", "truecolor"),
+            ("NO_COLOR", "1"),
+            ("FM_UNICODE", "0"),
+            ("FM_HYPERLINKS", "yes"),
+            ("FM_CELL_ASPECT", "2.5"),
+        ]);
+        assert_eq!(forced.color, ColorMode::None);
+        assert!(!forced.unicode && forced.hyperlinks);
+        assert!((forced.cell_aspect_ratio - 2.5).abs() < f32::EPSILON);
+
+        let pinned = detect(&[("TERM", "dumb"), ("FM_CAPABILITY_PROFILE", "sixel")]);
+        assert_eq!(pinned.images, ImageProtocol::Sixel);
+
+        let config = MermaidConfig {
+            capability_profile: Some("ansi16".to_string()),
+            ..MermaidConfig::default()
+        };
+        let capabilities = TerminalCapabilities::for_mermaid_config(&config);
+        assert_eq!(capabilities.color, ColorMode::Ansi16);
+        assert_eq!(TerminalCapabilities::from_profile("nope"), None);
+    }
+
+    #[test]
+    fn auto_glyph_mode_falls_back_to_ascii_off_utf8_or_off_terminal() {
+        let forces_ascii = |mode: AutoGlyphMode, vars: &[(&str, &str)], tty: bool| {
+            mode.forces_ascii_from(
+                |name| {
+                    vars.iter()
+                        .find(|(key, _)| *key == name)
+                        .map(|(_, value)| (*value).to_string())
+                },
+                tty,
+            )
+        };
+        let posix = [("LANG", "en_US.UTF-8"), ("LC_ALL", "C")];
+        assert!(forces_ascii(AutoGlyphMode::Locale, &posix, true));
+        assert!(!forces_ascii(AutoGlyphMode::Off, &posix, false));
+        assert!(!forces_ascii(
+            AutoGlyphMode::Locale,
+            &[("LANG", "de_DE.utf8")],
+            true
+        ));
+        // No locale at all is not evidence against UTF-8.
+        assert!(!forces_ascii(AutoGlyphMode::Locale, &[], true));
+        assert!(!forces_ascii(
+            AutoGlyphMode::Locale,
+            &[("LANG", "C"), ("FM_UNICODE", "1")],
+            true
+        ));
+
+        let utf8 = [("LANG", "en_US.UTF-8")];
+        assert!(!forces_ascii(AutoGlyphMode::LocaleOrPipe, &utf8, true));
+        assert!(forces_ascii(AutoGlyphMode::LocaleOrPipe, &utf8, false));
+        assert!(!forces_ascii(AutoGlyphMode::Locale, &utf8, false));
+    }
+
+    #[test]
+    fn terminfo_colors_are_read_from_both_number_formats() {
+        // Header, the names "x|", no booleans, 14 numbers with `colors` last.
+        let entry = |magic: i16, number_size: usize, colors: i32| {
+            let mut entry = Vec::new();
+            for value in [magic, 3, 0, 14, 0, 0] {
+                entry.extend(value.to_le_bytes());
+            }
+            entry.extend(b"x|\0\0");
+            for index in 0..14 {
+                let value = if index == TERMINFO_COLORS { colors } else { -1 };
+                entry.extend(&value.to_le_bytes()[..number_size]);
+            }
+            entry
+        };
+        assert_eq!(parse_terminfo_colors(&entry(0o432, 2, 256)), Some(256));
+        assert_eq!(
+            parse_terminfo_colors(&entry(0o1036, 4, 1 << 24)),
+            Some(1 << 24)
+        );
+        assert_eq!(parse_terminfo_colors(&entry(0o432, 2, -1)), None);
+        assert_eq!(parse_terminfo_colors(b"not terminfo"), None);
+    }
+}
@@ -0,0 +1,298 @@
+//! Dedicated terminal renderer for class diagrams.
+//!
+//! Each class is a [`grid`](crate::grid) box with three compartments: the name (and stereotype
+//! or generics), the attributes and the methods, separated by horizontal rules and prefixed with
+//! their UML visibility markers. Relationship ends carry UML markers, so inheritance, composition,
+//! aggregation and association read differently:
+//!
+//! ```text
+//! ┌─────────┐               ┌────────┐
+//! │   Zoo   │               │ Animal │
+//! ├─────────┤               ├────────┤
+//! │ +city   ├◆── houses ────┤ +name  │
+//! ├─────────┤               ├────────┤
+//! │ +open() │               │ +eat() │
+//! └─────────┘               └────────┘
+//! ```
+
+use fm_core::{ArrowType, ClassRelationKind, ClassStereotype, ClassVisibility, MermaidDiagramIr};
+use fm_layout::DiagramLayout;
+
+use crate::glyphs::RelationGlyphs;
+use crate::grid::{GridBox, GridLink, Side};
+use crate::renderer::{TermRenderResult, TermRenderer, split_lines};
+
+/// The marker at one end of a relationship.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Marker {
+    Triangle,
+    FilledDiamond,
+    HollowDiamond,
+    Arrow,
+}
+
+/// Map ClassVisibility to its UML symbol.
+const fn visibility_char(visibility: ClassVisibility) -> char {
+    match visibility {
+        ClassVisibility::Public => '+',
+        ClassVisibility::Private => '-',
+        ClassVisibility::Protected => '#',
+        ClassVisibility::Package => '~',
+    }
+}
+
+impl TermRenderer {
+    /// Draw a class diagram with compartmented class boxes and UML relationship markers. `None`
+    /// when the layout places no classes.
+    pub(crate) fn render_class(
+        &self,
+        ir: &MermaidDiagramIr,
+        layout: &DiagramLayout,
+    ) -> Option<TermRenderResult> {
+        let mut class_of = vec![None; ir.nodes.len()];
+        let mut classes = Vec::with_capacity(layout.nodes.len());
+        for node_box in &layout.nodes {
+            let Some(node) = ir.nodes.get(node_box.node_index) else {
+                continue;
+            };
+            let mut title = Vec::new();
+            let meta = node.class_meta.as_ref();
+            if let Some(stereotype) = meta.and_then(|meta| meta.stereotype.as_ref()) {
+                let text = match stereotype {
+                    ClassStereotype::Interface => "<<interface>>",
+                    ClassStereotype::Abstract => "<<abstract>>",
+                    ClassStereotype::Enum => "<<enumeration>>",
+                    ClassStereotype::Service => "<<service>>",
+                    ClassStereotype::Custom(text) => text.as_str(),
+                };
+                title.push(self.cell_text(text));
+            }
+            let name = self
+                .node_display_label(ir, Some(node), &node_box.node_id)
+                .unwrap_or_default();
+            match meta.filter(|meta| !meta.generics.is_empty()) {
+                Some(meta) => {
+                    title.push(self.cell_text(&format!("{name}<{}>", meta.generics.join(", "))))
+                }
+                None => title.extend(split_lines(&name)),
+            }
+
+            let mut sections = vec![title];
+            if let Some(meta) =
+                meta.filter(|meta| !meta.attributes.is_empty() || !meta.methods.is_empty())
+            {
+                sections.push(
+                    meta.attributes
+                        .iter()
+                        .map(|attribute| {
+                            let vis = visibility_char(attribute.visibility);
+                            let text = match &attribute.return_type {
+                                Some(ty) => format!("{vis}{}: {ty}", attribute.name),
+                                None => format!("{vis}{}", attribute.name),
+                            };
+                            self.cell_text(&text)
+                        })
+                        .collect(),
+                );
+                sections.push(
+                    meta.methods
+                        .iter()
+                        .map(|method| {
+                            let vis = visibility_char(method.visibility);
+                            let suffix = if method.is_abstract {
+                                "*"
+                            } else if method.is_static {
+                                "$"
+                            } else {
+                                ""
+                            };
+                            let ret = method
+                                .return_type
+                                .as_deref()
+                                .map(|ty| format!(": {ty}"))
+                                .unwrap_or_default();
+                            self.cell_text(&format!("{vis}{}{suffix}{ret}", method.name))
+                        })
+                        .collect(),
+                );
+            }
+
+            if let Some(slot) = class_of.get_mut(node_box.node_index) {
+                *slot = Some(classes.len());
+            }
+            classes.push(GridBox::new(&node_box.bounds, sections));
+        }
+        if classes.is_empty() {
+            return None;
+        }
+
+        let mut markers = Vec::new();
+        let relations: Vec<GridLink> = ir
+            .edges
+            .iter()
+            .filter_map(|edge| {
+                let from = edge.from.resolved_node_id(&ir.ports)?;
+                let to = edge.to.resolved_node_id(&ir.ports)?;
+                let from = class_of.get(from.0).copied().flatten()?;
+                let to = class_of.get(to.0).copied().flatten()?;
+                let (marker, marker_at_from, dotted) = match edge.class_relation() {
+                    Some(relation) => {
+                        let marker = match relation.kind {
+                            ClassRelationKind::Inheritance | ClassRelationKind::Realization => {
+                                Some(Marker::Triangle)
+                            }
+                            ClassRelationKind::Composition => Some(Marker::FilledDiamond),
+                            ClassRelationKind::Aggregation => Some(Marker::HollowDiamond),
+                            ClassRelationKind::Association | ClassRelationKind::Dependency => {
+                                Some(Marker::Arrow)
+                            }
+                            ClassRelationKind::Link => None,
+                        };
+                        let dotted = matches!(
+                            relation.kind,
+                            ClassRelationKind::Realization | ClassRelationKind::Dependency
+                        );
+                        (marker, relation.marker_at_from, dotted)
+                    }
+                    None => {
+                        let plain = matches!(
+                            edge.arrow,
+                            ArrowType::Line | ArrowType::ThickLine | ArrowType::DottedLine
+                        );
+                        let dotted =
+                            matches!(edge.arrow, ArrowType::DottedArrow | ArrowType::DottedLine);
+                        ((!plain).then_some(Marker::Arrow), false, dotted)
+                    }
+                };
+                markers.push(if marker_at_from {
+                    [marker, None]
+                } else {
+                    [None, marker]
+                });
+                Some(GridLink {
+                    from,
+                    to,
+                    label: edge
+                        .label
+                        .and_then(|label_id| ir.labels.get(label_id.0))
+                        .map(|label| self.cell_text(&label.text))
+                        .unwrap_or_default(),
+                    dotted,
+                })
+            })
+            .collect();
+
+        let glyphs = RelationGlyphs::for_mode(self.config.glyph_mode);
+        let edges = &self.edge_glyphs;
+        Some(
+            self.render_box_grid(ir, layout, classes, &relations, |link, end, side| {
+                let glyph = match (markers[link][end], side) {
+                    (None, _) => return [None; 2],
+                    (Some(Marker::Arrow), side) => {
+                        let arrow = match side {
+                            Side::Left => edges.arrow_right,
+                            Side::Right => edges.arrow_left,
+                            Side::Top => edges.arrow_down,
+                            Side::Bottom => edges.arrow_up,
+                        };
+                        return [Some(arrow), None];
+                    }
+                    (Some(Marker::Triangle), Side::Left) => glyphs.triangle_right,
+                    (Some(Marker::Triangle), Side::Right) => glyphs.triangle_left,
+                    (Some(Marker::Triangle), Side::Top) => glyphs.triangle_down,
+                    (Some(Marker::Triangle), Side::Bottom) => glyphs.triangle_up,
+                    (Some(Marker::FilledDiamond), _) => glyphs.diamond_filled,
+                    (Some(Marker::HollowDiamond), _) => glyphs.diamond_hollow,
+                };
+                let mut chars = glyph.chars();
+                [chars.next(), chars.next()]
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::TermRenderConfig;
+    use crate::renderer::render_diagram_with_config;
+    use fm_core::MermaidGlyphMode;
+
+    fn render(source: &str, config: &TermRenderConfig) -> String {
+        let parsed = fm_parser::parse(source);
+        render_diagram_with_config(&parsed.ir, config, 160, 60).output
+    }
+
+    fn row_of(output: &str, needle: &str) -> usize {
+        output
+            .lines()
+            .position(|line| line.contains(needle))
+            .unwrap_or_else(|| panic!("{needle:?} missing from\n{output}"))
+    }
+
+    #[test]
+    fn classes_have_name_attribute_and_method_compartments() {
+        let output = render(
+            "classDiagram\n  class Animal {\n    +String name\n    -int age\n    #sleep()\n    +eat() void\n  }",
+            &TermRenderConfig::rich(),
+        );
+
+        let name = row_of(&output, "Animal");
+        let attribute = row_of(&output, "name");
+        let method = row_of(&output, "eat()");
+        assert!(name < attribute && attribute < method, "{output}");
+        assert!(
+            output.contains("-int age") || output.contains("-age"),
+            "{output}"
+        );
+        assert!(output.contains("#sleep()"), "{output}");
+        // One rule under the name and one between attributes and methods.
+        let rules = output
+            .lines()
+            .filter(|line| line.trim_start().starts_with('├'))
+            .count();
+        assert_eq!(rules, 2, "{output}");
+    }
+
+    #[test]
+    fn relationship_kinds_get_distinct_markers() {
+        let output = render(
+            "classDiagram\n  Animal <|-- Duck\n  Zoo *-- Animal\n  Keeper --> Zoo",
+            &TermRenderConfig::rich(),
+        );
+
+        assert!(
+            ['▷', '◁', '△', '▽']
+                .iter()
+                .any(|&triangle| output.contains(triangle)),
+            "{output}"
+        );
+        assert!(output.contains('◆'), "{output}");
+        assert!(
+            ['▶', '◀', '▲', '▼']
+                .iter()
+                .any(|&arrow| output.contains(arrow)),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn ascii_mode_spells_triangles_like_mermaid() {
+        let config = TermRenderConfig {
+            glyph_mode: MermaidGlyphMode::Ascii,
+            ..TermRenderConfig::rich()
+        };
+        let output = render(
+            "classDiagram\n  class Animal {\n    +eat()\n  }\n  Animal <|-- Duck",
+            &config,
+        );
+
+        assert!(output.is_ascii(), "{output}");
+        assert!(output.contains("+eat()"), "{output}");
+        assert!(
+            ["<|", "|>", "^", "v"]
+                .iter()
+                .any(|&triangle| output.contains(triangle)),
+            "{output}"
+        );
+    }
+}
@@ -0,0 +1,647 @@
+//! ANSI color output for terminal rendering.
+//!
+//! Rendering stays glyph-only; colors are painted afterwards into a [`ColorLayer`] that mirrors
+//! the output grid and then wrapped around the glyphs as SGR escape sequences. With
+//! [`ColorMode::None`] (the default) nothing is painted and the output is byte-for-byte the
+//! monochrome rendering.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use fm_core::{
+    DEFAULT_STYLE_CLASS, DiagramPalettePreset, IrNodeId, IrStyleTarget, MermaidDiagramIr,
+    parse_style_string,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::text::grapheme_columns;
+
+/// Escape sequences a terminal is asked to understand.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum ColorMode {
+    /// Plain glyphs, no escape sequences.
+    #[default]
+    None,
+    /// The 16 standard colors (`SGR 30–37`, `90–97`), mapped to the nearest match.
+    Ansi16,
+    /// The xterm 256-color palette (`SGR 38;5;n`): 6x6x6 cube plus gray ramp.
+    Ansi256,
+    /// 24-bit color (`SGR 38;2;r;g;b`).
+    TrueColor,
+}
+
+impl ColorMode {
+    /// Whether this mode emits any escape sequences.
+    #[must_use]
+    pub const fn is_enabled(self) -> bool {
+        !matches!(self, Self::None)
+    }
+
+    /// SGR sequence setting the foreground to `color`, or `None` when colors are off.
+    #[must_use]
+    pub fn foreground(self, color: TermColor) -> Option<String> {
+        match self {
+            Self::None => None,
+            Self::Ansi16 => Some(format!("\x1b[{}m", color.nearest_ansi16())),
+            Self::Ansi256 => Some(format!("\x1b[38;5;{}m", color.nearest_ansi256())),
+            Self::TrueColor => Some(format!("\x1b[38;2;{};{};{}m", color.r, color.g, color.b)),
+        }
+    }
+}
+
+/// SGR reset, closing every colored run.
+pub const RESET: &str = "\x1b[0m";
+
+/// SGR faint, for elements outside [`TermRenderConfig::focus_nodes`](crate::TermRenderConfig::focus_nodes).
+const FAINT: &str = "\x1b[2m";
+
+/// SGR bold, for the `**bold**` runs of markdown labels.
+const BOLD: &str = "\x1b[1m";
+
+/// An RGB color.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct TermColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+/// xterm's default RGB values for the 16 standard colors, in SGR order (30–37, then 90–97).
+const ANSI16: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// Channel levels of the 6x6x6 cube in the 256-color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl TermColor {
+    #[must_use]
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Parse a CSS hex color (`#rgb` or `#rrggbb`, the `#` optional).
+    #[must_use]
+    pub fn from_hex(value: &str) -> Option<Self> {
+        let hex = value.trim().trim_start_matches('#');
+        if !hex.is_ascii() {
+            return None;
+        }
+        let channel = |digits: &str| u8::from_str_radix(digits, 16).ok();
+        match hex.len() {
+            3 => {
+                let mut channels = hex.chars().map(|digit| channel(&digit.to_string()));
+                let (r, g, b) = (channels.next()??, channels.next()??, channels.next()??);
+                Some(Self::new(r * 17, g * 17, b * 17))
+            }
+            6 => Some(Self::new(
+                channel(&hex[0..2])?,
+                channel(&hex[2..4])?,
+                channel(&hex[4..6])?,
+            )),
+            _ => None,
+        }
+    }
+
+    fn distance(self, (r, g, b): (u8, u8, u8)) -> u32 {
+        let d = |a: u8, b: u8| u32::from(a.abs_diff(b)).pow(2);
+        d(self.r, r) + d(self.g, g) + d(self.b, b)
+    }
+
+    /// xterm's RGB for entry `index` of the 256-color palette, the first 16 being the standard
+    /// colors.
+    #[must_use]
+    pub(crate) fn from_ansi256(index: u8) -> Self {
+        match index {
+            0..=15 => {
+                let (r, g, b) = ANSI16[usize::from(index)];
+                Self::new(r, g, b)
+            }
+            16..=231 => {
+                let cube = index - 16;
+                let level = |step: u8| CUBE_LEVELS[usize::from(step)];
+                Self::new(level(cube / 36), level(cube / 6 % 6), level(cube % 6))
+            }
+            _ => {
+                let gray = 8 + (index - 232) * 10;
+                Self::new(gray, gray, gray)
+            }
+        }
+    }
+
+    /// CSS hex form, `#rrggbb`.
+    #[must_use]
+    pub fn to_hex(self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+
+    /// SGR foreground code of the closest standard color.
+    fn nearest_ansi16(self) -> u8 {
+        let index = (0..ANSI16.len())
+            .min_by_key(|&index| self.distance(ANSI16[index]))
+            .unwrap_or(7) as u8;
+        if index < 8 { 30 + index } else { 82 + index }
+    }
+
+    /// Closest entry of the 256-color palette: the cube color or the gray ramp step.
+    fn nearest_ansi256(self) -> u8 {
+        let level = |channel: u8| {
+            (0..CUBE_LEVELS.len())
+                .min_by_key(|&index| CUBE_LEVELS[index].abs_diff(channel))
+                .unwrap_or(0)
+        };
+        let (r, g, b) = (level(self.r), level(self.g), level(self.b));
+        let cube = (CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]);
+        let average = (u16::from(self.r) + u16::from(self.g) + u16::from(self.b)) / 3;
+        let step = (average.saturating_sub(3) / 10).min(23) as u8;
+        let gray = 8 + step * 10;
+        if self.distance((gray, gray, gray)) < self.distance(cube) {
+            232 + step
+        } else {
+            16 + (r * 36 + g * 6 + b) as u8
+        }
+    }
+}
+
+/// Colors for each kind of diagram element. `None` leaves the terminal's own foreground.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TermPalette {
+    /// Node borders and shapes.
+    pub node: Option<TermColor>,
+    /// Edge lines and arrowheads.
+    pub edge: Option<TermColor>,
+    /// Cluster borders.
+    pub cluster: Option<TermColor>,
+    /// Node labels.
+    pub text: Option<TermColor>,
+}
+
+impl TermPalette {
+    /// Terminal colors for a palette preset, picked to read on dark and light backgrounds alike.
+    /// `Monochrome` leaves everything in the terminal's foreground color.
+    #[must_use]
+    pub const fn for_preset(preset: DiagramPalettePreset) -> Self {
+        let (node, edge, cluster) = match preset {
+            DiagramPalettePreset::Default => (
+                TermColor::new(0x63, 0x66, 0xf1),
+                TermColor::new(0x94, 0xa3, 0xb8),
+                TermColor::new(0x8b, 0x5c, 0xf6),
+            ),
+            DiagramPalettePreset::Corporate => (
+                TermColor::new(0x25, 0x63, 0xeb),
+                TermColor::new(0x64, 0x74, 0x8b),
+                TermColor::new(0x0d, 0x94, 0x88),
+            ),
+            DiagramPalettePreset::Neon => (
+                TermColor::new(0x00, 0xff, 0x88),
+                TermColor::new(0xff, 0x00, 0xff),
+                TermColor::new(0x00, 0xff, 0xff),
+            ),
+            DiagramPalettePreset::Pastel => (
+                TermColor::new(0xf4, 0x8f, 0xb1),
+                TermColor::new(0xce, 0x93, 0xd8),
+                TermColor::new(0xb3, 0xe5, 0xfc),
+            ),
+            DiagramPalettePreset::HighContrast => (
+                TermColor::new(0xff, 0xff, 0x00),
+                TermColor::new(0x00, 0xff, 0xff),
+                TermColor::new(0xff, 0x00, 0xff),
+            ),
+            DiagramPalettePreset::Monochrome => {
+                return Self {
+                    node: None,
+                    edge: None,
+                    cluster: None,
+                    text: None,
+                };
+            }
+        };
+        Self {
+            node: Some(node),
+            edge: Some(edge),
+            cluster: Some(cluster),
+            text: None,
+        }
+    }
+
+    /// Override entries from Mermaid `themeVariables` (`primaryBorderColor`, `lineColor`,
+    /// `clusterBorder`, `primaryTextColor`); values that aren't hex colors are ignored.
+    #[must_use]
+    pub fn with_theme_variables(mut self, variables: &BTreeMap<String, String>) -> Self {
+        let color = |keys: &[&str]| {
+            keys.iter().find_map(|key| {
+                variables
+                    .get(*key)
+                    .and_then(|value| TermColor::from_hex(value))
+            })
+        };
+        if let Some(node) = color(&["primaryBorderColor", "primaryColor"]) {
+            self.node = Some(node);
+        }
+        if let Some(edge) = color(&["lineColor"]) {
+            self.edge = Some(edge);
+        }
+        if let Some(cluster) = color(&["clusterBorder"]) {
+            self.cluster = Some(cluster);
+        }
+        if let Some(text) = color(&["primaryTextColor", "textColor"]) {
+            self.text = Some(text);
+        }
+        self
+    }
+}
+
+/// Palette preset named by a Mermaid `theme`, as the parser maps it for `MermaidConfig`.
+#[must_use]
+pub fn palette_for_theme(theme: &str) -> Option<DiagramPalettePreset> {
+    match theme.trim().to_ascii_lowercase().as_str() {
+        "default" => Some(DiagramPalettePreset::Default),
+        "corporate" => Some(DiagramPalettePreset::Corporate),
+        "neon" => Some(DiagramPalettePreset::Neon),
+        "monochrome" => Some(DiagramPalettePreset::Monochrome),
+        "pastel" => Some(DiagramPalettePreset::Pastel),
+        "highcontrast" | "high-contrast" => Some(DiagramPalettePreset::HighContrast),
+        _ => None,
+    }
+}
+
+/// Palette for `ir`: the diagram's own `theme` and `themeVariables` win over `preset`.
+#[must_use]
+pub fn diagram_palette(ir: &MermaidDiagramIr, preset: DiagramPalettePreset) -> TermPalette {
+    let overrides = &ir.meta.theme_overrides;
+    let preset = overrides
+        .theme
+        .as_deref()
+        .and_then(palette_for_theme)
+        .unwrap_or(preset);
+    TermPalette::for_preset(preset).with_theme_variables(&overrides.theme_variables)
+}
+
+/// A node's own border color from `style` directives or its `classDef` classes (including
+/// `classDef default`): its stroke, or its fill when nothing sets a stroke.
+pub(crate) fn node_stroke(ir: &MermaidDiagramIr, node_index: usize) -> Option<TermColor> {
+    node_style_color(ir, node_index, "stroke").or_else(|| node_style_color(ir, node_index, "fill"))
+}
+
+/// A node's own label color: the `color` property of its `style` directives or classes.
+pub(crate) fn node_text_color(ir: &MermaidDiagramIr, node_index: usize) -> Option<TermColor> {
+    node_style_color(ir, node_index, "color")
+}
+
+/// `property` of a node as a color, cascading from its `style` directives through its classes
+/// to `classDef default`.
+fn node_style_color(ir: &MermaidDiagramIr, node_index: usize, property: &str) -> Option<TermColor> {
+    let node = ir.nodes.get(node_index)?;
+    let color = |properties: &BTreeMap<String, String>| {
+        properties
+            .get(property)
+            .and_then(|value| TermColor::from_hex(value))
+    };
+    if let Some(found) = node
+        .inline_style
+        .as_ref()
+        .and_then(|style| color(&style.properties))
+    {
+        return Some(found);
+    }
+    let styled = ir.style_refs.iter().rev().find_map(|style_ref| {
+        (style_ref.target == IrStyleTarget::Node(IrNodeId(node_index)))
+            .then(|| color(&parse_style_string(&style_ref.style).properties))
+            .flatten()
+    });
+    let class = |name: &str| {
+        ir.style_defs
+            .iter()
+            .filter(|def| def.name == name)
+            .find_map(|def| color(&def.properties))
+    };
+    styled
+        .or_else(|| node.classes.iter().rev().find_map(|name| class(name)))
+        .or_else(|| class(DEFAULT_STYLE_CLASS))
+}
+
+/// Color of a `classDef` class: its stroke, or its fill when it sets no stroke.
+pub(crate) fn class_color(ir: &MermaidDiagramIr, class: &str) -> Option<TermColor> {
+    let defs = || ir.style_defs.iter().filter(|def| def.name == class);
+    ["stroke", "fill"].into_iter().find_map(|property| {
+        defs().find_map(|def| {
+            def.properties
+                .get(property)
+                .and_then(|value| TermColor::from_hex(value))
+        })
+    })
+}
+
+/// An edge's own stroke color from `linkStyle` directives.
+pub(crate) fn edge_stroke(ir: &MermaidDiagramIr, edge_index: usize) -> Option<TermColor> {
+    let stroke = |properties: &BTreeMap<String, String>| {
+        properties
+            .get("stroke")
+            .and_then(|value| TermColor::from_hex(value))
+    };
+    if let Some(color) = ir
+        .edges
+        .get(edge_index)?
+        .inline_style
+        .as_ref()
+        .and_then(|style| stroke(&style.properties))
+    {
+        return Some(color);
+    }
+    let styled = |target: IrStyleTarget| {
+        ir.style_refs.iter().rev().find_map(|style_ref| {
+            (style_ref.target == target)
+                .then(|| stroke(&parse_style_string(&style_ref.style).properties))
+                .flatten()
+        })
+    };
+    styled(IrStyleTarget::Link(edge_index)).or_else(|| styled(IrStyleTarget::LinkDefault))
+}
+
+/// IR indices of the nodes `ids` name plus those within `hops` edges of them, in either
+/// direction. `None` when no id names a node, so nothing is dimmed.
+pub(crate) fn focused_nodes(
+    ir: &MermaidDiagramIr,
+    ids: &[String],
+    hops: usize,
+) -> Option<BTreeSet<usize>> {
+    let mut focused: BTreeSet<usize> = ids.iter().filter_map(|id| ir.find_node_index(id)).collect();
+    if focused.is_empty() {
+        return None;
+    }
+    let links: Vec<(usize, usize)> = ir
+        .edges
+        .iter()
+        .filter_map(|edge| {
+            Some((
+                ir.resolve_endpoint_node(edge.from)?.0,
+                ir.resolve_endpoint_node(edge.to)?.0,
+            ))
+        })
+        .collect();
+    for _ in 0..hops {
+        let reached: Vec<usize> = links
+            .iter()
+            .filter_map(
+                |&(from, to)| match (focused.contains(&from), focused.contains(&to)) {
+                    (true, false) => Some(to),
+                    (false, true) => Some(from),
+                    _ => None,
+                },
+            )
+            .collect();
+        if reached.is_empty() {
+            break;
+        }
+        focused.extend(reached);
+    }
+    Some(focused)
+}
+
+/// Per-cell foreground colors for a rendered grid, and which cells are drawn faint or bold.
+pub(crate) struct ColorLayer {
+    cells: Vec<Option<TermColor>>,
+    faint: Vec<bool>,
+    bold: Vec<bool>,
+    width: usize,
+    height: usize,
+    dimmed: bool,
+}
+
+impl ColorLayer {
+    pub(crate) fn new(width: usize, height: usize) -> Self {
+        Self {
+            cells: vec![None; width * height],
+            faint: vec![false; width * height],
+            bold: vec![false; width * height],
+            width,
+            height,
+            dimmed: false,
+        }
+    }
+
+    /// Draw what is painted from now on faint, or at full intensity again.
+    pub(crate) fn set_dimmed(&mut self, dimmed: bool) {
+        self.dimmed = dimmed;
+    }
+
+    /// Color the cell, or only set its intensity when `color` is `None`.
+    pub(crate) fn paint(&mut self, x: usize, y: usize, color: Option<TermColor>) {
+        if x < self.width && y < self.height {
+            let index = y * self.width + x;
+            if color.is_some() {
+                self.cells[index] = color;
+            }
+            self.faint[index] = self.dimmed;
+        }
+    }
+
+    /// Draw the cell's glyph bold, keeping its color.
+    pub(crate) fn paint_bold(&mut self, x: usize, y: usize) {
+        if x < self.width && y < self.height {
+            self.bold[y * self.width + x] = true;
+        }
+    }
+
+    pub(crate) fn paint_rect(
+        &mut self,
+        (x, y, w, h): (usize, usize, usize, usize),
+        color: Option<TermColor>,
+    ) {
+        for row in y..y.saturating_add(h).min(self.height) {
+            for col in x..x.saturating_add(w).min(self.width) {
+                self.paint(col, row, color);
+            }
+        }
+    }
+
+    /// Paint only the outline of a rectangle, leaving its interior to whatever sits inside.
+    pub(crate) fn paint_border(
+        &mut self,
+        (x, y, w, h): (usize, usize, usize, usize),
+        color: Option<TermColor>,
+    ) {
+        let (right, bottom) = (x + w.saturating_sub(1), y + h.saturating_sub(1));
+        for col in x..=right {
+            self.paint(col, y, color);
+            self.paint(col, bottom, color);
+        }
+        for row in y..=bottom {
+            self.paint(x, row, color);
+            self.paint(right, row, color);
+        }
+    }
+
+    /// Paint the cells of a Bresenham line, matching how cell-mode edges are drawn.
+    pub(crate) fn paint_line(
+        &mut self,
+        (x0, y0): (usize, usize),
+        (x1, y1): (usize, usize),
+        color: Option<TermColor>,
+    ) {
+        let dx = (x1 as isize - x0 as isize).abs();
+        let dy = -(y1 as isize - y0 as isize).abs();
+        let sx = if x0 < x1 { 1 } else { -1 };
+        let sy = if y0 < y1 { 1 } else { -1 };
+        let mut err = dx + dy;
+        let (mut x, mut y) = (x0 as isize, y0 as isize);
+        loop {
+            self.paint(x as usize, y as usize, color);
+            if x == x1 as isize && y == y1 as isize {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    /// Wrap each run of same-colored glyphs in `output` with SGR sequences. Whitespace and blank
+    /// Braille cells are never colored, and every line ends reset so the output can be sliced by line.
+    pub(crate) fn apply(&self, output: &str, mode: ColorMode) -> String {
+        if !mode.is_enabled() {
+            return output.to_string();
+        }
+        let mut colored = String::with_capacity(output.len() * 2);
+        for (y, line) in output.split('\n').enumerate() {
+            if y > 0 {
+                colored.push('\n');
+            }
+            const PLAIN: (Option<TermColor>, bool, bool) = (None, false, false);
+            let mut current = PLAIN;
+            for (x, grapheme, _) in grapheme_columns(line) {
+                let blank = grapheme
+                    .chars()
+                    .all(|ch| ch.is_whitespace() || ch == '\u{2800}');
+                let style = if blank || y >= self.height || x >= self.width {
+                    PLAIN
+                } else {
+                    let index = y * self.width + x;
+                    (self.cells[index], self.faint[index], self.bold[index])
+                };
+                if style != current {
+                    let (color, faint, bold) = style;
+                    // A new color replaces the old one, but only a reset ends faint or bold text.
+                    let reset = current != PLAIN
+                        && (color.is_none() || current.1 && !faint || current.2 && !bold);
+                    if reset {
+                        colored.push_str(RESET);
+                    }
+                    if faint && (reset || !current.1) {
+                        colored.push_str(FAINT);
+                    }
+                    if bold && (reset || !current.2) {
+                        colored.push_str(BOLD);
+                    }
+                    colored.extend(color.and_then(|color| mode.foreground(color)));
+                    current = style;
+                }
+                colored.push_str(grapheme);
+            }
+            if current != PLAIN {
+                colored.push_str(RESET);
+            }
+        }
+        colored
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_colors_parse_in_short_and_long_form() {
+        assert_eq!(
+            TermColor::from_hex("#f80"),
+            Some(TermColor::new(255, 136, 0))
+        );
+        assert_eq!(
+            TermColor::from_hex("1a2B3c"),
+            Some(TermColor::new(0x1a, 0x2b, 0x3c))
+        );
+        assert_eq!(TermColor::from_hex("red"), None);
+        assert_eq!(TermColor::from_hex("#ffff"), None);
+    }
+
+    #[test]
+    fn each_mode_encodes_the_nearest_color() {
+        let red = TermColor::new(250, 10, 10);
+        assert_eq!(ColorMode::None.foreground(red), None);
+        assert_eq!(
+            ColorMode::Ansi16.foreground(red).as_deref(),
+            Some("\x1b[91m")
+        );
+        assert_eq!(
+            ColorMode::Ansi256.foreground(red).as_deref(),
+            Some("\x1b[38;5;196m")
+        );
+        assert_eq!(
+            ColorMode::TrueColor.foreground(red).as_deref(),
+            Some("\x1b[38;2;250;10;10m")
+        );
+        // Grays land on the gray ramp rather than the cube.
+        assert_eq!(
+            ColorMode::Ansi256
+                .foreground(TermColor::new(128, 128, 128))
+                .as_deref(),
+            Some("\x1b[38;5;244m")
+        );
+    }
+
+    #[test]
+    fn theme_variables_override_the_preset() {
+        let variables = BTreeMap::from([
+            ("lineColor".to_string(), "#ff0000".to_string()),
+            ("primaryBorderColor".to_string(), "not a color".to_string()),
+        ]);
+        let palette =
+            TermPalette::for_preset(DiagramPalettePreset::Default).with_theme_variables(&variables);
+        assert_eq!(palette.edge, Some(TermColor::new(255, 0, 0)));
+        assert_eq!(
+            palette.node,
+            TermPalette::for_preset(DiagramPalettePreset::Default).node
+        );
+    }
+
+    #[test]
+    fn layer_wraps_colored_runs_and_skips_whitespace() {
+        let mut layer = ColorLayer::new(5, 2);
+        let red = Some(TermColor::new(255, 0, 0));
+        layer.paint_rect((0, 0, 5, 1), red);
+        let colored = layer.apply("a b\ncd", ColorMode::Ansi16);
+        assert_eq!(colored, "\x1b[91ma\x1b[0m \x1b[91mb\x1b[0m\ncd");
+        assert_eq!(layer.apply("a b", ColorMode::None), "a b");
+    }
+
+    #[test]
+    fn bold_cells_keep_their_color_and_end_with_a_reset() {
+        let mut layer = ColorLayer::new(4, 1);
+        let red = Some(TermColor::new(255, 0, 0));
+        layer.paint_rect((0, 0, 4, 1), red);
+        layer.paint_bold(1, 0);
+        layer.paint_bold(2, 0);
+        let colored = layer.apply("abcd", ColorMode::Ansi16);
+        assert_eq!(colored, "\x1b[91ma\x1b[1m\x1b[91mbc\x1b[0m\x1b[91md\x1b[0m");
+    }
+}
@@ -1,16 +1,42 @@
 //! Terminal renderer configuration types.
 
-use fm_core::{MermaidGlyphMode, MermaidRenderMode, MermaidTier};
+use std::borrow::Cow;
+
+use fm_core::{
+    DegradationOperator, DiagramPalettePreset, MermaidComplexity, MermaidDegradationPlan,
+    MermaidDiagramIr, MermaidGlyphMode, MermaidLinkMode, MermaidRenderMode, MermaidTier,
+    MermaidWrapMode, NodeShapeRegistry,
+};
+
+use crate::capabilities::{AutoGlyphMode, TerminalCapabilities};
+use crate::color::ColorMode;
+use crate::legend::LegendPosition;
+use crate::pixels::ImageProtocol;
+
+/// Smallest [`TermRenderConfig::zoom`]; below it node labels no longer fit their boxes.
+pub const MIN_ZOOM: f32 = 0.25;
+/// Largest [`TermRenderConfig::zoom`].
+pub const MAX_ZOOM: f32 = 8.0;
+
+/// Viewport cells per point of complexity score below which an `Auto` tier render drops to
+/// compact cells without cluster frames.
+const CROWDED_CELLS_PER_POINT: usize = 6;
+/// Viewport cells per point of complexity score below which it drops further, to an outline
+/// without labels.
+const OVERCROWDED_CELLS_PER_POINT: usize = 2;
 
 /// Configuration for terminal diagram rendering.
 #[derive(Debug, Clone)]
 pub struct TermRenderConfig {
     /// Rendering fidelity tier (Compact/Normal/Rich/Auto).
     pub tier: MermaidTier,
-    /// Sub-cell rendering mode (CellOnly/Braille/Block/HalfBlock/Auto).
+    /// Sub-cell rendering mode (CellOnly/Braille/Block/HalfBlock/Pixels/Auto).
     pub render_mode: MermaidRenderMode,
     /// Glyph mode (Unicode box-drawing vs ASCII fallback).
     pub glyph_mode: MermaidGlyphMode,
+    /// Switch to ASCII glyphs, and from sub-cell modes to plain cells, when the locale or a
+    /// redirected stdout says Unicode may not survive. Off by default.
+    pub auto_glyph_mode: AutoGlyphMode,
     /// Maximum width in terminal columns.
     pub max_width: usize,
     /// Maximum height in terminal rows.
@@ -19,16 +45,47 @@ pub struct TermRenderConfig {
     pub max_label_chars: usize,
     /// Maximum label lines before truncation.
     pub max_label_lines: usize,
+    /// Where labels break when they are wider than `max_label_chars`.
+    pub wrap_mode: MermaidWrapMode,
     /// Show selection highlighting.
     pub show_selection: bool,
     /// Show cluster decorations.
     pub show_clusters: bool,
+    /// Draw edge labels.
+    pub show_edge_labels: bool,
     /// Enable diagonal edge optimization.
     pub diagonal_edges: bool,
+    /// Draw solid and thick edges on the sub-cell canvas with a Wu-style weighted rasterizer
+    /// instead of Bresenham's, for smoother shallow slopes in Braille.
+    pub smooth_edges: bool,
+    /// Magnification applied on top of the fit-to-terminal scale, clamped to
+    /// [`MIN_ZOOM`]..=[`MAX_ZOOM`]. Above 1 the diagram grows past the terminal and is cropped
+    /// at its right and bottom edges; pan over it with
+    /// [`render_term_viewport`](crate::render_term_viewport) to reach the rest.
+    pub zoom: f32,
     /// Padding around the diagram (in cells).
     pub padding: usize,
     /// Overlay a minimap onto the rendered terminal output.
     pub show_minimap: bool,
+    /// Custom node shape border decorators consulted before the built-in shapes.
+    pub shape_registry: NodeShapeRegistry,
+    /// ANSI color output (None/Ansi16/Ansi256/TrueColor).
+    pub color_mode: ColorMode,
+    /// Palette node, edge and cluster colors are drawn from, unless the diagram names a theme.
+    pub palette: DiagramPalettePreset,
+    /// How node `href` links are shown: OSC 8 hyperlinks (`Inline`), a numbered list under the
+    /// diagram (`Footnote`), or not at all.
+    pub link_mode: MermaidLinkMode,
+    /// Inline image protocol for [`MermaidRenderMode::Pixels`]; `Auto` detects it from the
+    /// environment.
+    pub image_protocol: ImageProtocol,
+    /// Legend panel explaining class colors and edge styles, under or beside the diagram.
+    pub legend: LegendPosition,
+    /// Node ids drawn at full intensity while every other node, edge and cluster is dimmed.
+    /// Empty (the default) dims nothing. Needs color output.
+    pub focus_nodes: Vec<String>,
+    /// Also keep nodes within this many edges of a focus node at full intensity.
+    pub focus_hops: usize,
 }
 
 impl Default for TermRenderConfig {
@@ -37,15 +94,28 @@ impl Default for TermRenderConfig {
             tier: MermaidTier::Auto,
             render_mode: MermaidRenderMode::Braille,
             glyph_mode: MermaidGlyphMode::Unicode,
+            auto_glyph_mode: AutoGlyphMode::Off,
             max_width: 120,
             max_height: 40,
             max_label_chars: 24,
             max_label_lines: 2,
+            wrap_mode: MermaidWrapMode::WordChar,
             show_selection: false,
             show_clusters: true,
+            show_edge_labels: true,
             diagonal_edges: true,
+            smooth_edges: false,
+            zoom: 1.0,
             padding: 1,
             show_minimap: false,
+            shape_registry: NodeShapeRegistry::default(),
+            color_mode: ColorMode::None,
+            palette: DiagramPalettePreset::Default,
+            link_mode: MermaidLinkMode::Off,
+            image_protocol: ImageProtocol::Auto,
+            legend: LegendPosition::Off,
+            focus_nodes: Vec::new(),
+            focus_hops: 0,
         }
     }
 }
@@ -81,6 +151,41 @@ impl TermRenderConfig {
         }
     }
 
+    /// Configuration suited to the current terminal, from [`TerminalCapabilities::detect`].
+    #[must_use]
+    pub fn auto() -> Self {
+        Self::for_capabilities(&TerminalCapabilities::detect())
+    }
+
+    /// Default configuration with glyph, color and render modes chosen for `capabilities`:
+    /// inline images when the terminal has an image protocol, otherwise the tier's sub-cell mode
+    /// with Unicode or plain cells without it.
+    #[must_use]
+    pub fn for_capabilities(capabilities: &TerminalCapabilities) -> Self {
+        let glyph_mode = if capabilities.unicode {
+            MermaidGlyphMode::Unicode
+        } else {
+            MermaidGlyphMode::Ascii
+        };
+        let render_mode = if matches!(
+            capabilities.images,
+            ImageProtocol::Kitty | ImageProtocol::Iterm2 | ImageProtocol::Sixel
+        ) {
+            MermaidRenderMode::Pixels
+        } else if capabilities.unicode {
+            MermaidRenderMode::Auto
+        } else {
+            MermaidRenderMode::CellOnly
+        };
+        Self {
+            render_mode,
+            glyph_mode,
+            color_mode: capabilities.color,
+            image_protocol: capabilities.images,
+            ..Self::default()
+        }
+    }
+
     /// Apply a degradation plan to this config, adjusting fidelity and glyph mode.
     pub fn apply_degradation(&mut self, plan: &fm_core::MermaidDegradationPlan) {
         if let Some(glyph_mode) = plan.force_glyph_mode {
@@ -92,6 +197,7 @@ impl TermRenderConfig {
         if plan.hide_labels {
             self.max_label_chars = 0;
             self.max_label_lines = 0;
+            self.show_edge_labels = false;
         }
         match plan.target_fidelity {
             fm_core::MermaidFidelity::Compact => {
@@ -108,6 +214,67 @@ impl TermRenderConfig {
         }
     }
 
+    /// Degradation an `Auto` tier render of `ir` on a `cols` x `rows` terminal goes through: the
+    /// plan in the diagram's guard report (`ir.meta.guard`), plus compact cells and collapsed
+    /// clusters when its complexity score is high for the viewport, and an outline without
+    /// labels when it is far too high.
+    #[must_use]
+    pub fn auto_degradation_plan(
+        &self,
+        ir: &MermaidDiagramIr,
+        cols: usize,
+        rows: usize,
+    ) -> MermaidDegradationPlan {
+        let guard = &ir.meta.guard;
+        let score = if guard.complexity.score > 0 {
+            guard.complexity.score
+        } else {
+            MermaidComplexity::of(ir).score
+        };
+        let area = cols
+            .min(self.max_width)
+            .saturating_mul(rows.min(self.max_height));
+
+        let mut plan = guard.degradation.clone();
+        if score.saturating_mul(CROWDED_CELLS_PER_POINT) > area {
+            DegradationOperator::DowngradeToCompact.apply(&mut plan);
+            DegradationOperator::CollapseClusters.apply(&mut plan);
+        }
+        if score.saturating_mul(OVERCROWDED_CELLS_PER_POINT) > area {
+            DegradationOperator::HideLabels.apply(&mut plan);
+            DegradationOperator::DowngradeToOutline.apply(&mut plan);
+        }
+        plan
+    }
+
+    /// This configuration as used to render `ir` on a `cols` x `rows` terminal: with the
+    /// [`auto_degradation_plan`](Self::auto_degradation_plan) applied when the tier is `Auto`,
+    /// unchanged otherwise.
+    #[must_use]
+    pub fn adapted_to(&self, ir: &MermaidDiagramIr, cols: usize, rows: usize) -> Cow<'_, Self> {
+        if self.tier != MermaidTier::Auto {
+            return Cow::Borrowed(self);
+        }
+        let plan = self.auto_degradation_plan(ir, cols, rows);
+        if !plan.is_degraded() {
+            return Cow::Borrowed(self);
+        }
+        let mut adapted = self.clone();
+        adapted.apply_degradation(&plan);
+        Cow::Owned(adapted)
+    }
+
+    /// The glyph mode to draw with: ASCII when [`auto_glyph_mode`](Self::auto_glyph_mode) calls
+    /// for it, otherwise [`glyph_mode`](Self::glyph_mode).
+    #[must_use]
+    pub fn effective_glyph_mode(&self) -> MermaidGlyphMode {
+        if self.auto_glyph_mode.forces_ascii() {
+            MermaidGlyphMode::Ascii
+        } else {
+            self.glyph_mode
+        }
+    }
+
     /// Resolve the effective tier based on available space.
     #[must_use]
     pub fn effective_tier(&self, available_cols: usize, available_rows: usize) -> MermaidTier {
@@ -130,14 +297,29 @@ impl TermRenderConfig {
     #[must_use]
     pub fn effective_render_mode(&self, tier: MermaidTier) -> MermaidRenderMode {
         match self.render_mode {
-            MermaidRenderMode::Auto => match tier {
-                MermaidTier::Compact => MermaidRenderMode::CellOnly,
-                MermaidTier::Normal => MermaidRenderMode::HalfBlock,
-                MermaidTier::Rich | MermaidTier::Auto => MermaidRenderMode::Braille,
-            },
+            MermaidRenderMode::Auto => Self::tier_render_mode(tier),
             other => other,
         }
     }
+
+    /// Render mode `Auto` picks for a tier.
+    const fn tier_render_mode(tier: MermaidTier) -> MermaidRenderMode {
+        match tier {
+            MermaidTier::Compact => MermaidRenderMode::CellOnly,
+            MermaidTier::Normal => MermaidRenderMode::HalfBlock,
+            MermaidTier::Rich | MermaidTier::Auto => MermaidRenderMode::Braille,
+        }
+    }
+
+    /// Image protocol `Pixels` draws with, detected if `Auto`. Other render modes use none.
+    #[must_use]
+    pub fn effective_image_protocol(&self) -> ImageProtocol {
+        if self.render_mode == MermaidRenderMode::Pixels {
+            self.image_protocol.resolve()
+        } else {
+            ImageProtocol::None
+        }
+    }
 }
 
 /// Resolved configuration after auto-detection.
@@ -150,9 +332,18 @@ pub struct ResolvedConfig {
     pub rows: usize,
     pub max_label_chars: usize,
     pub max_label_lines: usize,
+    pub wrap_mode: MermaidWrapMode,
     pub show_clusters: bool,
+    pub show_edge_labels: bool,
     pub diagonal_edges: bool,
+    pub smooth_edges: bool,
+    pub zoom: f32,
     pub padding: usize,
+    pub color_mode: ColorMode,
+    pub palette: DiagramPalettePreset,
+    pub link_mode: MermaidLinkMode,
+    pub image_protocol: ImageProtocol,
+    pub legend: LegendPosition,
 }
 
 impl ResolvedConfig {
@@ -162,7 +353,29 @@ impl ResolvedConfig {
         let available_cols = cols.min(config.max_width);
         let available_rows = rows.min(config.max_height);
         let tier = config.effective_tier(available_cols, available_rows);
-        let render_mode = config.effective_render_mode(tier);
+        let image_protocol = config.effective_image_protocol();
+        // Without an image protocol, `Pixels` draws the way `Auto` would.
+        let render_mode = if config.render_mode == MermaidRenderMode::Pixels
+            && image_protocol == ImageProtocol::None
+        {
+            TermRenderConfig::tier_render_mode(tier)
+        } else {
+            config.effective_render_mode(tier)
+        };
+
+        // Braille and block characters are no safer than box drawing where ASCII was called for.
+        let ascii_forced = config.auto_glyph_mode.forces_ascii();
+        let render_mode = if ascii_forced
+            && matches!(
+                render_mode,
+                MermaidRenderMode::Braille
+                    | MermaidRenderMode::Block
+                    | MermaidRenderMode::HalfBlock
+            ) {
+            MermaidRenderMode::CellOnly
+        } else {
+            render_mode
+        };
 
         // Adjust label limits based on tier.
         let (max_label_chars, max_label_lines) = match tier {
@@ -181,14 +394,31 @@ impl ResolvedConfig {
         Self {
             tier,
             render_mode,
-            glyph_mode: config.glyph_mode,
+            glyph_mode: if ascii_forced {
+                MermaidGlyphMode::Ascii
+            } else {
+                config.glyph_mode
+            },
             cols: available_cols,
             rows: available_rows,
             max_label_chars,
             max_label_lines,
+            wrap_mode: config.wrap_mode,
             show_clusters: config.show_clusters && !matches!(tier, MermaidTier::Compact),
+            show_edge_labels: config.show_edge_labels,
             diagonal_edges: config.diagonal_edges,
+            smooth_edges: config.smooth_edges,
+            zoom: if config.zoom.is_finite() {
+                config.zoom.clamp(MIN_ZOOM, MAX_ZOOM)
+            } else {
+                1.0
+            },
             padding: config.padding,
+            color_mode: config.color_mode,
+            palette: config.palette,
+            link_mode: config.link_mode,
+            image_protocol,
+            legend: config.legend,
         }
     }
 
@@ -199,7 +429,9 @@ impl ResolvedConfig {
             MermaidRenderMode::Braille => (2, 4), // 2 columns x 4 rows per cell
             MermaidRenderMode::Block => (2, 2),   // 2x2 per cell
             MermaidRenderMode::HalfBlock => (1, 2), // 1x2 per cell
-            MermaidRenderMode::CellOnly | MermaidRenderMode::Auto => (1, 1),
+            MermaidRenderMode::CellOnly | MermaidRenderMode::Auto | MermaidRenderMode::Pixels => {
+                (1, 1)
+            }
         }
     }
 }
@@ -215,6 +447,25 @@ mod tests {
         assert!(config.max_height >= 24);
     }
 
+    #[test]
+    fn capabilities_pick_glyph_color_and_render_modes() {
+        let plain = TermRenderConfig::for_capabilities(&TerminalCapabilities::default());
+        assert_eq!(plain.glyph_mode, MermaidGlyphMode::Ascii);
+        assert_eq!(plain.render_mode, MermaidRenderMode::CellOnly);
+        assert_eq!(plain.color_mode, ColorMode::None);
+
+        let xterm = TerminalCapabilities::from_profile("256").expect("known profile");
+        let config = TermRenderConfig::for_capabilities(&xterm);
+        assert_eq!(config.glyph_mode, MermaidGlyphMode::Unicode);
+        assert_eq!(config.render_mode, MermaidRenderMode::Auto);
+        assert_eq!(config.color_mode, ColorMode::Ansi256);
+
+        let kitty = TerminalCapabilities::from_profile("kitty").expect("known profile");
+        let config = TermRenderConfig::for_capabilities(&kitty);
+        assert_eq!(config.render_mode, MermaidRenderMode::Pixels);
+        assert_eq!(config.image_protocol, ImageProtocol::Kitty);
+    }
+
     #[test]
     fn apply_degradation_forces_ascii_and_compact() {
         let mut config = TermRenderConfig::rich();
@@ -231,6 +482,44 @@ mod tests {
         assert_eq!(config.render_mode, MermaidRenderMode::CellOnly);
         assert!(!config.show_clusters);
         assert_eq!(config.max_label_chars, 0);
+        assert!(!config.show_edge_labels);
+    }
+
+    #[test]
+    fn auto_tier_degrades_by_guard_report_and_complexity() {
+        let small = fm_parser::parse("flowchart LR\n  A --> B");
+        let config = TermRenderConfig::default();
+        assert!(matches!(
+            config.adapted_to(&small.ir, 80, 24),
+            Cow::Borrowed(_)
+        ));
+
+        let mut guarded = small.ir.clone();
+        guarded.meta.guard.degradation.collapse_clusters = true;
+        guarded.meta.guard.degradation.force_glyph_mode = Some(MermaidGlyphMode::Ascii);
+        let adapted = config.adapted_to(&guarded, 80, 24);
+        assert!(!adapted.show_clusters);
+        assert_eq!(adapted.glyph_mode, MermaidGlyphMode::Ascii);
+        // A pinned tier is left alone.
+        let pinned = TermRenderConfig::rich();
+        assert!(matches!(
+            pinned.adapted_to(&guarded, 80, 24),
+            Cow::Borrowed(_)
+        ));
+
+        let mut source = String::from("flowchart TD\n");
+        for index in 0..40 {
+            source.push_str(&format!("  N{index} -->|step {index}| N{}\n", index + 1));
+        }
+        let dense = fm_parser::parse(&source);
+        let roomy = config.adapted_to(&dense.ir, 400, 200);
+        assert_eq!(roomy.tier, MermaidTier::Auto);
+        let crowded = config.adapted_to(&dense.ir, 80, 24);
+        assert_eq!(crowded.tier, MermaidTier::Compact);
+        assert!(crowded.show_edge_labels);
+        let plan = config.auto_degradation_plan(&dense.ir, 40, 12);
+        assert_eq!(plan.target_fidelity, fm_core::MermaidFidelity::Outline);
+        assert!(plan.hide_labels && plan.collapse_clusters);
     }
 
     #[test]
@@ -276,6 +565,21 @@ mod tests {
         assert_eq!(resolved.rows, 30);
     }
 
+    #[test]
+    fn zoom_is_clamped_to_readable_limits() {
+        let resolve = |zoom| {
+            let config = TermRenderConfig {
+                zoom,
+                ..Default::default()
+            };
+            ResolvedConfig::resolve(&config, 80, 24).zoom
+        };
+        assert_eq!(resolve(2.0), 2.0);
+        assert_eq!(resolve(0.01), MIN_ZOOM);
+        assert_eq!(resolve(100.0), MAX_ZOOM);
+        assert_eq!(resolve(f32::NAN), 1.0);
+    }
+
     #[test]
     fn braille_has_2x4_multiplier() {
         let config = ResolvedConfig {
@@ -286,9 +590,18 @@ mod tests {
             rows: 24,
             max_label_chars: 24,
             max_label_lines: 2,
+            wrap_mode: MermaidWrapMode::WordChar,
             show_clusters: true,
+            show_edge_labels: true,
             diagonal_edges: true,
+            smooth_edges: false,
+            zoom: 1.0,
             padding: 1,
+            color_mode: ColorMode::None,
+            palette: DiagramPalettePreset::Default,
+            link_mode: MermaidLinkMode::Off,
+            image_protocol: ImageProtocol::None,
+            legend: LegendPosition::Off,
         };
         assert_eq!(config.subcell_multiplier(), (2, 4));
     }
@@ -3,8 +3,15 @@
 //! Compares two `MermaidDiagramIr` instances and produces a diff result
 //! that identifies added, removed, changed, and unchanged elements.
 
-use crate::{TermRenderConfig, render_diagram_with_config};
-use fm_core::{ArrowType, IrEndpoint, IrNode, MermaidDiagramIr, NodeShape};
+use crate::color::{ColorMode, TermColor};
+use crate::glyphs::BoxGlyphs;
+use crate::legend::{LegendEntry, LegendPosition, legend_panel, place_legend};
+use crate::renderer::{MIN_DIAGRAM_COLS, MIN_DIAGRAM_ROWS};
+use crate::{TermRenderConfig, TermRenderResult, render_diagram_with_config};
+use fm_core::{
+    ArrowType, IrEdge, IrEndpoint, IrGraphEdge, IrGraphNode, IrInlineStyle, IrLabel, IrLabelId,
+    IrNode, IrNodeId, MermaidDiagramIr, MermaidLinkMode, NodeShape, Span,
+};
 use serde::Serialize;
 use std::collections::{BTreeMap, VecDeque};
 
@@ -93,6 +100,11 @@ pub enum EdgeChange {
         old: Option<String>,
         new: Option<String>,
     },
+    /// An edge the source named (`A e1@--> B`) now joins different nodes.
+    EndpointsChanged {
+        old_from: String,
+        old_to: String,
+    },
 }
 
 /// Complete diff result between two diagrams.
@@ -338,34 +350,119 @@ fn compare_nodes(
     changes
 }
 
+/// An old edge paired with the new edge it became, either side missing for removed and added
+/// edges. Indices point into the diagrams' `edges`.
+struct EdgeMatch<'a> {
+    from_id: &'a str,
+    to_id: &'a str,
+    status: DiffStatus,
+    /// Arrow of the new edge, or of the old one when it was removed.
+    arrow: ArrowType,
+    old_index: Option<usize>,
+    new_index: Option<usize>,
+    changes: Vec<EdgeChange>,
+}
+
 fn diff_edges(
     old: &MermaidDiagramIr,
     new: &MermaidDiagramIr,
 ) -> (Vec<DiffEdge>, (usize, usize, usize, usize)) {
-    // Group edges by their endpoint pair (from_id, to_id).
-    let mut old_groups: BTreeMap<(&str, &str), VecDeque<&fm_core::IrEdge>> = BTreeMap::new();
-    for e in &old.edges {
-        if let (Some(f), Some(t)) = (endpoint_id(old, e.from), endpoint_id(old, e.to)) {
-            old_groups.entry((f, t)).or_default().push_back(e);
-        }
-    }
-
-    let mut new_groups: BTreeMap<(&str, &str), VecDeque<&fm_core::IrEdge>> = BTreeMap::new();
-    for e in &new.edges {
-        if let (Some(f), Some(t)) = (endpoint_id(new, e.from), endpoint_id(new, e.to)) {
-            new_groups.entry((f, t)).or_default().push_back(e);
-        }
-    }
-
-    let mut old_groups = old_groups.into_iter().peekable();
-    let mut new_groups = new_groups.into_iter().peekable();
-
-    let mut results = Vec::new();
     let mut added = 0_usize;
     let mut removed = 0_usize;
     let mut changed = 0_usize;
     let mut unchanged = 0_usize;
 
+    let results = match_edges(old, new)
+        .into_iter()
+        .map(|edge_match| {
+            let counter = match edge_match.status {
+                DiffStatus::Added => &mut added,
+                DiffStatus::Removed => &mut removed,
+                DiffStatus::Changed => &mut changed,
+                DiffStatus::Unchanged => &mut unchanged,
+            };
+            *counter += 1;
+            DiffEdge {
+                from_id: edge_match.from_id.to_owned(),
+                to_id: edge_match.to_id.to_owned(),
+                status: edge_match.status,
+                arrow: edge_match.arrow,
+                changes: edge_match.changes,
+            }
+        })
+        .collect();
+
+    (results, (added, removed, changed, unchanged))
+}
+
+fn match_edges<'a>(old: &'a MermaidDiagramIr, new: &'a MermaidDiagramIr) -> Vec<EdgeMatch<'a>> {
+    let mut results = Vec::new();
+
+    // Edges both diagrams name by the same id are the same edge, wherever they now point.
+    let new_by_user_id: BTreeMap<&str, usize> = new
+        .edges
+        .iter()
+        .enumerate()
+        .filter_map(|(index, edge)| Some((edge.user_id()?, index)))
+        .collect();
+    let mut old_identified = vec![false; old.edges.len()];
+    let mut new_identified = vec![false; new.edges.len()];
+    for (old_e, old_edge) in old.edges.iter().enumerate() {
+        let Some(&new_e) = old_edge
+            .user_id()
+            .and_then(|user_id| new_by_user_id.get(user_id))
+        else {
+            continue;
+        };
+        let new_edge = &new.edges[new_e];
+        let (Some(old_from), Some(old_to), Some(from_id), Some(to_id)) = (
+            endpoint_id(old, old_edge.from),
+            endpoint_id(old, old_edge.to),
+            endpoint_id(new, new_edge.from),
+            endpoint_id(new, new_edge.to),
+        ) else {
+            continue;
+        };
+        old_identified[old_e] = true;
+        new_identified[new_e] = true;
+        let mut changes = compare_edges(old, old_edge, new, new_edge);
+        if (old_from, old_to) != (from_id, to_id) {
+            changes.push(EdgeChange::EndpointsChanged {
+                old_from: old_from.to_owned(),
+                old_to: old_to.to_owned(),
+            });
+        }
+        results.push(EdgeMatch {
+            from_id,
+            to_id,
+            status: if changes.is_empty() {
+                DiffStatus::Unchanged
+            } else {
+                DiffStatus::Changed
+            },
+            arrow: new_edge.arrow,
+            old_index: Some(old_e),
+            new_index: Some(new_e),
+            changes,
+        });
+    }
+
+    // Group the remaining edges by their endpoint pair (from_id, to_id).
+    let group = |ir: &'a MermaidDiagramIr, identified: &[bool]| {
+        let mut groups: BTreeMap<(&str, &str), VecDeque<usize>> = BTreeMap::new();
+        for (index, e) in ir.edges.iter().enumerate() {
+            if identified[index] {
+                continue;
+            }
+            if let (Some(f), Some(t)) = (endpoint_id(ir, e.from), endpoint_id(ir, e.to)) {
+                groups.entry((f, t)).or_default().push_back(index);
+            }
+        }
+        groups
+    };
+    let mut old_groups = group(old, &old_identified).into_iter().peekable();
+    let mut new_groups = group(new, &new_identified).into_iter().peekable();
+
     loop {
         let next_pair = match (
             old_groups.peek().map(|(pair, _)| *pair),
@@ -386,6 +483,15 @@ fn diff_edges(
             .map(|(_, edges)| edges)
             .unwrap_or_default();
         let (from_id, to_id) = next_pair;
+        let edge_match = |status, arrow, old_index, new_index, changes| EdgeMatch {
+            from_id,
+            to_id,
+            status,
+            arrow,
+            old_index,
+            new_index,
+            changes,
+        };
 
         // 1. Match identical edges first (Unchanged)
         let mut i = 0;
@@ -394,15 +500,14 @@ fn diff_edges(
             let mut matched = false;
             for j in 0..new_list.len() {
                 let new_e = new_list[j];
-                if compare_edges(old, old_e, new, new_e).is_empty() {
-                    results.push(DiffEdge {
-                        from_id: from_id.to_owned(),
-                        to_id: to_id.to_owned(),
-                        status: DiffStatus::Unchanged,
-                        arrow: new_e.arrow,
-                        changes: Vec::new(),
-                    });
-                    unchanged += 1;
+                if compare_edges(old, &old.edges[old_e], new, &new.edges[new_e]).is_empty() {
+                    results.push(edge_match(
+                        DiffStatus::Unchanged,
+                        new.edges[new_e].arrow,
+                        Some(old_e),
+                        Some(new_e),
+                        Vec::new(),
+                    ));
                     let _ = old_list.remove(i);
                     let _ = new_list.remove(j);
                     matched = true;
@@ -415,48 +520,46 @@ fn diff_edges(
         }
 
         // 2. Match remaining edges as Changed (greedy)
-        while !old_list.is_empty() && !new_list.is_empty() {
-            let old_e = old_list[0];
-            let new_e = new_list[0];
+        while let (Some(old_e), Some(new_e)) =
+            (old_list.front().copied(), new_list.front().copied())
+        {
             let _ = old_list.pop_front();
             let _ = new_list.pop_front();
-            let changes = compare_edges(old, old_e, new, new_e);
-            results.push(DiffEdge {
-                from_id: from_id.to_owned(),
-                to_id: to_id.to_owned(),
-                status: DiffStatus::Changed,
-                arrow: new_e.arrow,
+            let changes = compare_edges(old, &old.edges[old_e], new, &new.edges[new_e]);
+            results.push(edge_match(
+                DiffStatus::Changed,
+                new.edges[new_e].arrow,
+                Some(old_e),
+                Some(new_e),
                 changes,
-            });
-            changed += 1;
+            ));
         }
 
         // 3. Any leftover old edges are Removed
         for old_e in old_list {
-            results.push(DiffEdge {
-                from_id: from_id.to_owned(),
-                to_id: to_id.to_owned(),
-                status: DiffStatus::Removed,
-                arrow: old_e.arrow,
-                changes: Vec::new(),
-            });
-            removed += 1;
+            results.push(edge_match(
+                DiffStatus::Removed,
+                old.edges[old_e].arrow,
+                Some(old_e),
+                None,
+                Vec::new(),
+            ));
         }
 
         // 4. Any leftover new edges are Added
         for new_e in new_list {
-            results.push(DiffEdge {
-                from_id: from_id.to_owned(),
-                to_id: to_id.to_owned(),
-                status: DiffStatus::Added,
-                arrow: new_e.arrow,
-                changes: Vec::new(),
-            });
-            added += 1;
+            results.push(edge_match(
+                DiffStatus::Added,
+                new.edges[new_e].arrow,
+                None,
+                Some(new_e),
+                Vec::new(),
+            ));
         }
     }
 
-    (results, (added, removed, changed, unchanged))
+    results.sort_by_key(|edge_match| (edge_match.from_id, edge_match.to_id));
+    results
 }
 
 fn compare_edges(
@@ -696,8 +799,15 @@ pub fn render_diff_terminal_with_config(
     let pane_width = (total_cols.saturating_sub(7) / 2).max(24);
     let pane_rows = rows.max(12);
 
-    let old_render = render_diagram_with_config(old, config, pane_width, pane_rows);
-    let new_render = render_diagram_with_config(new, config, pane_width, pane_rows);
+    // Panes are aligned line by line, so they render plain; `config.color_mode` picks the
+    // status colors instead. The legend, if any, explains the markers for the whole diff.
+    let pane_config = TermRenderConfig {
+        color_mode: ColorMode::None,
+        legend: LegendPosition::Off,
+        ..config.clone()
+    };
+    let old_render = render_diagram_with_config(old, &pane_config, pane_width, pane_rows);
+    let new_render = render_diagram_with_config(new, &pane_config, pane_width, pane_rows);
     let diff = diff_diagrams(old, new);
 
     let aligned = align_rendered_lines(&old_render.output, &new_render.output);
@@ -727,17 +837,420 @@ pub fn render_diff_terminal_with_config(
 
     for line in aligned {
         let marker = status_symbol(line.status);
-        let marker = colorize_marker(marker, line.status, use_colors);
+        let marker = colorize_marker(marker, line.status, use_colors, config.color_mode);
         let old_text = line.old_line.unwrap_or_default();
         let new_text = line.new_line.unwrap_or_default();
-        let old_padded = pad_display(&truncate_display(&old_text, old_line_width), old_line_width);
+        let old_trimmed = truncate_display(&old_text, old_line_width);
         let new_trimmed = truncate_display(&new_text, pane_width);
+        // Removed lines only exist on the old side and added lines on the new one; changed lines
+        // are tinted on both.
+        let (old_trimmed, new_trimmed) = if use_colors {
+            let color = status_color(line.status, config.color_mode);
+            let tint = |text: String| {
+                if text.trim().is_empty() {
+                    text
+                } else {
+                    format!("{color}{text}{}", colors::RESET)
+                }
+            };
+            match line.status {
+                DiffStatus::Removed => (tint(old_trimmed), new_trimmed),
+                DiffStatus::Added => (old_trimmed, tint(new_trimmed)),
+                DiffStatus::Changed => (tint(old_trimmed), tint(new_trimmed)),
+                DiffStatus::Unchanged => (old_trimmed, new_trimmed),
+            }
+        } else {
+            (old_trimmed, new_trimmed)
+        };
+        let old_padded = pad_display(&old_trimmed, old_line_width);
         output.push_str(&format!("{marker}  {old_padded} | {new_trimmed}\n"));
     }
 
+    if config.legend != LegendPosition::Off {
+        let width = output.lines().map(display_width).max().unwrap_or(0);
+        let (with_legend, _, _) = place_status_legend(&output, width, config, use_colors);
+        output = with_legend;
+        output.push('\n');
+    }
+
     output
 }
 
+/// Render the old and new diagrams next to each other with default settings. See
+/// [`render_diff_side_by_side_with_config`].
+#[must_use]
+pub fn render_diff_side_by_side(
+    old: &MermaidDiagramIr,
+    new: &MermaidDiagramIr,
+    cols: usize,
+    rows: usize,
+) -> String {
+    render_diff_side_by_side_with_config(old, new, &TermRenderConfig::rich(), cols, rows)
+}
+
+/// Render the old diagram into the left half of a `cols` x `rows` area and the new one into the
+/// right half, split by a one-column gutter.
+///
+/// Each diagram is laid out and scaled on its own. Blank rows are then inserted into one pane or
+/// the other so that nodes present in both sit on the same row, as far as the two layouts keep
+/// those nodes in the same top-to-bottom order. A node is only used for this when the first word
+/// of its label shows up on exactly one row of each pane. Unlike [`render_diff_terminal_with_config`], no
+/// status markers are added.
+#[must_use]
+pub fn render_diff_side_by_side_with_config(
+    old: &MermaidDiagramIr,
+    new: &MermaidDiagramIr,
+    config: &TermRenderConfig,
+    cols: usize,
+    rows: usize,
+) -> String {
+    let pane_width = (cols.saturating_sub(3) / 2).max(MIN_DIAGRAM_COLS);
+    let pane_rows = rows.saturating_sub(2).max(MIN_DIAGRAM_ROWS);
+    // Panes are matched up by their text, so they render plain and without link escapes.
+    let pane_config = TermRenderConfig {
+        color_mode: ColorMode::None,
+        link_mode: MermaidLinkMode::Off,
+        legend: LegendPosition::Off,
+        ..config.clone()
+    };
+    let old_render = render_diagram_with_config(old, &pane_config, pane_width, pane_rows);
+    let new_render = render_diagram_with_config(new, &pane_config, pane_width, pane_rows);
+    let old_lines: Vec<&str> = old_render.output.lines().collect();
+    let new_lines: Vec<&str> = new_render.output.lines().collect();
+
+    let anchors = common_node_rows(old, &old_lines, new, &new_lines, config.max_label_chars);
+    let glyphs = BoxGlyphs::for_mode(config.effective_glyph_mode());
+    let pane = |line: &str| pad_display(&truncate_display(line, pane_width), pane_width);
+
+    let mut output = format!("{} {} New\n", pane("Old"), glyphs.vertical);
+    output.push_str(&format!(
+        "{}{}{}\n",
+        glyphs.horizontal.to_string().repeat(pane_width + 1),
+        glyphs.cross,
+        glyphs.horizontal.to_string().repeat(pane_width + 1),
+    ));
+
+    let (mut old_row, mut new_row) = (0, 0);
+    let ends = (old_lines.len(), new_lines.len());
+    for (old_end, new_end) in anchors.into_iter().chain([ends]) {
+        let span = (old_end - old_row).max(new_end - new_row);
+        for offset in 0..span {
+            let left = old_lines[old_row..old_end]
+                .get(offset)
+                .copied()
+                .unwrap_or("");
+            let right = new_lines[new_row..new_end]
+                .get(offset)
+                .copied()
+                .unwrap_or("");
+            let line = format!("{} {} {right}", pane(left), glyphs.vertical);
+            output.push_str(line.trim_end());
+            output.push('\n');
+        }
+        (old_row, new_row) = (old_end, new_end);
+    }
+
+    output
+}
+
+/// Rows of `old_lines` and `new_lines` that show the same node, in increasing order on both
+/// sides: the longest such chain among nodes whose label appears on exactly one row of each.
+fn common_node_rows(
+    old: &MermaidDiagramIr,
+    old_lines: &[&str],
+    new: &MermaidDiagramIr,
+    new_lines: &[&str],
+    max_label_chars: usize,
+) -> Vec<(usize, usize)> {
+    let label_row = |ir: &MermaidDiagramIr, node: &IrNode, lines: &[&str]| {
+        let text = node
+            .label
+            .and_then(|label_id| ir.labels.get(label_id.0))
+            .map_or(node.id.as_str(), |label| label.text.as_str());
+        // Labels may be wrapped or cut short with `…`, but their first word survives, or at
+        // least its start.
+        let word = text.split_whitespace().next()?;
+        let needle = match word.char_indices().nth(max_label_chars.saturating_sub(1)) {
+            Some((end, _)) if word.chars().count() > max_label_chars => &word[..end],
+            _ => word,
+        };
+        if needle.is_empty() {
+            return None;
+        }
+        let mut rows = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| contains_word(line, needle))
+            .map(|(row, _)| row);
+        match (rows.next(), rows.next()) {
+            (Some(row), None) => Some(row),
+            _ => None,
+        }
+    };
+
+    let new_by_id: BTreeMap<&str, &IrNode> = new
+        .nodes
+        .iter()
+        .map(|node| (node.id.as_str(), node))
+        .collect();
+    let mut pairs: Vec<(usize, usize)> = old
+        .nodes
+        .iter()
+        .filter_map(|old_node| {
+            let new_node = new_by_id.get(old_node.id.as_str())?;
+            Some((
+                label_row(old, old_node, old_lines)?,
+                label_row(new, new_node, new_lines)?,
+            ))
+        })
+        .collect();
+    pairs.sort_unstable();
+    pairs.dedup();
+
+    // Longest chain increasing on both sides; pairs are few, so quadratic is fine.
+    let mut best: Vec<(usize, Option<usize>)> = Vec::with_capacity(pairs.len());
+    for (index, &(old_row, new_row)) in pairs.iter().enumerate() {
+        let previous = (0..index)
+            .filter(|&earlier| pairs[earlier].0 < old_row && pairs[earlier].1 < new_row)
+            .max_by_key(|&earlier| best[earlier].0);
+        best.push(match previous {
+            Some(earlier) => (best[earlier].0 + 1, Some(earlier)),
+            None => (1, None),
+        });
+    }
+    let mut chain = Vec::new();
+    let mut cursor = (0..pairs.len()).max_by_key(|&index| best[index].0);
+    while let Some(index) = cursor {
+        chain.push(pairs[index]);
+        cursor = best[index].1;
+    }
+    chain.reverse();
+    chain
+}
+
+/// Whether `needle` occurs in `line` without a letter or digit directly on either side.
+fn contains_word(line: &str, needle: &str) -> bool {
+    line.match_indices(needle).any(|(start, _)| {
+        let before = line[..start].chars().next_back();
+        let after = line[start + needle.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    })
+}
+
+/// Render the new diagram with whatever the old one had and the new one dropped merged back in,
+/// so a change reads in place: added nodes and edges in green, removed ones in red, changed ones
+/// in yellow, in the shades of [`render_diff_terminal_with_config`]. When `config.color_mode` is
+/// off, the labels of those elements start with `+`, `-` or `~` instead.
+///
+/// Removed nodes are laid out with the rest but left out of the new diagram's clusters. Status
+/// colors reach the generic grid renderers only; the dedicated ones (sequence, class, ER and so
+/// on) draw uncolored, so render those with color off to get the prefixes.
+#[must_use]
+pub fn render_diff_diagram(
+    old: &MermaidDiagramIr,
+    new: &MermaidDiagramIr,
+    config: &TermRenderConfig,
+    cols: usize,
+    rows: usize,
+) -> TermRenderResult {
+    let use_colors = config.color_mode.is_enabled();
+    let merged = merge_diff_overlay(old, new, use_colors);
+    let diagram_config = TermRenderConfig {
+        legend: LegendPosition::Off,
+        ..config.clone()
+    };
+    let mut result = render_diagram_with_config(&merged, &diagram_config, cols, rows);
+
+    if config.legend != LegendPosition::Off && !result.summary_only {
+        let (output, width, height) =
+            place_status_legend(&result.output, result.width, config, use_colors);
+        result.output = output;
+        result.width = width;
+        result.height = height;
+    }
+    result
+}
+
+/// `new` plus the nodes and edges only `old` has, each non-unchanged element marked with its
+/// status: a `stroke` color when `use_colors` is set, a status prefix on its label otherwise.
+fn merge_diff_overlay(
+    old: &MermaidDiagramIr,
+    new: &MermaidDiagramIr,
+    use_colors: bool,
+) -> MermaidDiagramIr {
+    let mut merged = new.clone();
+    let old_index: BTreeMap<&str, usize> = old
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(index, node)| (node.id.as_str(), index))
+        .collect();
+    let mut merged_index: BTreeMap<String, usize> = new
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(index, node)| (node.id.clone(), index))
+        .collect();
+    let label_text = |ir: &MermaidDiagramIr, label: Option<IrLabelId>| {
+        label
+            .and_then(|label_id| ir.labels.get(label_id.0))
+            .map(|label| label.text.clone())
+    };
+
+    let (nodes, _) = diff_nodes(old, new);
+    for diff_node in nodes {
+        let status = diff_node.status;
+        let index = match status {
+            DiffStatus::Unchanged => continue,
+            DiffStatus::Removed => {
+                let Some(&from_index) = old_index.get(diff_node.id.as_str()) else {
+                    continue;
+                };
+                let index = merged.nodes.len();
+                merged.nodes.push(old.nodes[from_index].clone());
+                merged.graph.nodes.push(IrGraphNode {
+                    node_id: IrNodeId(index),
+                    kind: old
+                        .graph_node(IrNodeId(from_index))
+                        .map(|graph_node| graph_node.kind)
+                        .unwrap_or_default(),
+                    clusters: Vec::new(),
+                    subgraphs: Vec::new(),
+                });
+                merged_index.insert(diff_node.id.clone(), index);
+                index
+            }
+            DiffStatus::Added | DiffStatus::Changed => match merged_index.get(&diff_node.id) {
+                Some(&index) => index,
+                None => continue,
+            },
+        };
+
+        let source = if status == DiffStatus::Removed {
+            old
+        } else {
+            new
+        };
+        let text =
+            label_text(source, merged.nodes[index].label).unwrap_or_else(|| diff_node.id.clone());
+        if !use_colors {
+            let label = push_label(&mut merged, format!("{} {text}", status_symbol(status)));
+            merged.nodes[index].label = Some(label);
+        } else if status == DiffStatus::Removed {
+            let label = push_label(&mut merged, text);
+            merged.nodes[index].label = Some(label);
+        }
+        if use_colors {
+            merged.nodes[index].inline_style = Some(Box::new(status_stroke(status)));
+        }
+    }
+
+    for edge_match in match_edges(old, new) {
+        let status = edge_match.status;
+        let index = match (status, edge_match.old_index, edge_match.new_index) {
+            (DiffStatus::Unchanged, _, _) => continue,
+            (DiffStatus::Removed, Some(from_index), _) => {
+                let endpoint = |id: &str| {
+                    merged_index
+                        .get(id)
+                        .map(|&index| IrEndpoint::Node(IrNodeId(index)))
+                };
+                let (Some(from), Some(to)) =
+                    (endpoint(edge_match.from_id), endpoint(edge_match.to_id))
+                else {
+                    continue;
+                };
+                let index = merged.edges.len();
+                let old_edge = &old.edges[from_index];
+                merged.edges.push(IrEdge {
+                    from,
+                    to,
+                    ..old_edge.clone()
+                });
+                merged.graph.edges.push(IrGraphEdge {
+                    edge_id: index,
+                    kind: old
+                        .graph
+                        .edge(from_index)
+                        .map(|graph_edge| graph_edge.kind)
+                        .unwrap_or_default(),
+                    from,
+                    to,
+                    span: old_edge.span,
+                });
+                index
+            }
+            (_, _, Some(index)) => index,
+            _ => continue,
+        };
+
+        let source = if status == DiffStatus::Removed {
+            old
+        } else {
+            new
+        };
+        let text = label_text(source, merged.edges[index].label);
+        if !use_colors {
+            let marked = match text {
+                Some(text) => format!("{} {text}", status_symbol(status)),
+                None => status_symbol(status).to_string(),
+            };
+            merged.edges[index].label = Some(push_label(&mut merged, marked));
+        } else if status == DiffStatus::Removed {
+            merged.edges[index].label = text.map(|text| push_label(&mut merged, text));
+        }
+        if use_colors {
+            merged.edges[index].inline_style = Some(Box::new(status_stroke(status)));
+        }
+    }
+
+    merged
+}
+
+fn push_label(ir: &mut MermaidDiagramIr, text: String) -> IrLabelId {
+    ir.labels.push(IrLabel {
+        text,
+        span: Span::default(),
+    });
+    IrLabelId(ir.labels.len() - 1)
+}
+
+/// A `stroke` style in the status's color, which the renderer prefers over classes and the
+/// palette.
+fn status_stroke(status: DiffStatus) -> IrInlineStyle {
+    let TermColor { r, g, b } = status_rgb(status);
+    IrInlineStyle {
+        properties: BTreeMap::from([("stroke".to_string(), format!("#{r:02x}{g:02x}{b:02x}"))]),
+    }
+}
+
+/// Place a legend explaining the status markers next to `output`, which is `width` columns wide.
+fn place_status_legend(
+    output: &str,
+    width: usize,
+    config: &TermRenderConfig,
+    use_colors: bool,
+) -> (String, usize, usize) {
+    let entries: Vec<LegendEntry> = [
+        (DiffStatus::Added, "added"),
+        (DiffStatus::Removed, "removed"),
+        (DiffStatus::Changed, "changed"),
+        (DiffStatus::Unchanged, "unchanged"),
+    ]
+    .into_iter()
+    .map(|(status, text)| LegendEntry {
+        sample: status_symbol(status).to_string(),
+        sgr: use_colors.then(|| status_color(status, config.color_mode)),
+        text: text.to_string(),
+    })
+    .collect();
+    let panel = legend_panel(
+        &entries,
+        &BoxGlyphs::for_mode(config.effective_glyph_mode()),
+    );
+    place_legend(output, width, &panel, config.legend)
+}
+
 fn format_node_change(change: &NodeChange) -> String {
     match change {
         NodeChange::LabelChanged { old, new } => format!("label: {old:?} -> {new:?}"),
@@ -755,6 +1268,9 @@ fn format_edge_change(change: &EdgeChange) -> String {
         EdgeChange::ArrowChanged { old, new } => format!("arrow: {old:?} -> {new:?}"),
         EdgeChange::LabelChanged { old, new } => format!("label: {old:?} -> {new:?}"),
         EdgeChange::ErNotationChanged { old, new } => format!("er_notation: {old:?} -> {new:?}"),
+        EdgeChange::EndpointsChanged { old_from, old_to } => {
+            format!("endpoints: was {old_from} -> {old_to}")
+        }
     }
 }
 
@@ -767,18 +1283,37 @@ fn status_symbol(status: DiffStatus) -> char {
     }
 }
 
-fn colorize_marker(marker: char, status: DiffStatus, use_colors: bool) -> String {
+fn colorize_marker(marker: char, status: DiffStatus, use_colors: bool, mode: ColorMode) -> String {
     if !use_colors {
         return marker.to_string();
     }
 
-    let color = match status {
+    let color = status_color(status, mode);
+    format!("{color}{marker}{}", colors::RESET)
+}
+
+/// SGR sequence for a diff status: the standard 16 colors unless `mode` allows finer shades.
+fn status_color(status: DiffStatus, mode: ColorMode) -> String {
+    let ansi16 = match status {
         DiffStatus::Added => colors::ADDED,
         DiffStatus::Removed => colors::REMOVED,
         DiffStatus::Changed => colors::CHANGED,
         DiffStatus::Unchanged => colors::UNCHANGED,
     };
-    format!("{color}{marker}{}", colors::RESET)
+    match mode {
+        ColorMode::Ansi256 | ColorMode::TrueColor => mode.foreground(status_rgb(status)),
+        ColorMode::None | ColorMode::Ansi16 => None,
+    }
+    .unwrap_or_else(|| ansi16.to_string())
+}
+
+const fn status_rgb(status: DiffStatus) -> TermColor {
+    match status {
+        DiffStatus::Added => TermColor::new(0x22, 0xc5, 0x5e),
+        DiffStatus::Removed => TermColor::new(0xef, 0x44, 0x44),
+        DiffStatus::Changed => TermColor::new(0xf5, 0x9e, 0x0b),
+        DiffStatus::Unchanged => TermColor::new(0x6b, 0x72, 0x80),
+    }
 }
 
 fn display_width(value: &str) -> usize {
@@ -1208,6 +1743,24 @@ mod tests {
         assert!(rendered.contains("| New"));
     }
 
+    #[test]
+    fn terminal_diff_status_colors_follow_the_color_mode() {
+        let old = make_ir_with_nodes(&["A"]);
+        let new = make_ir_with_nodes(&["A", "B"]);
+        let config = TermRenderConfig {
+            color_mode: ColorMode::TrueColor,
+            ..TermRenderConfig::rich()
+        };
+
+        let rendered = render_diff_terminal_with_config(&old, &new, &config, 100, 24, true);
+        assert!(rendered.contains("\x1b[38;2;34;197;94m+"), "{rendered}");
+        // The panes themselves stay plain so their lines still align.
+        assert!(!rendered.contains("\x1b[38;2;99;102;241m"), "{rendered}");
+
+        let plain = render_diff_terminal_with_config(&old, &new, &config, 100, 24, false);
+        assert!(!plain.contains('\x1b'), "{plain}");
+    }
+
     #[test]
     fn handles_parallel_edges() {
         let mut old_ir = make_ir_with_nodes(&["A", "B"]);
@@ -1287,6 +1840,69 @@ mod tests {
         );
     }
 
+    #[test]
+    fn edges_keep_their_identity_across_insertions_and_moves() {
+        fn edge(from: usize, to: usize, user_id: Option<&str>) -> IrEdge {
+            let mut edge = IrEdge {
+                from: IrEndpoint::Node(IrNodeId(from)),
+                to: IrEndpoint::Node(IrNodeId(to)),
+                arrow: ArrowType::Arrow,
+                ..Default::default()
+            };
+            if let Some(user_id) = user_id {
+                edge.extras_mut().user_id = Some(Box::from(user_id));
+            }
+            edge
+        }
+
+        let mut old = make_ir_with_nodes(&["A", "B", "C"]);
+        old.edges
+            .extend([edge(0, 1, None), edge(1, 2, None), edge(0, 2, Some("e1"))]);
+
+        // An edge inserted at the front leaves the rest unchanged, and the named edge moved to
+        // new endpoints is one changed edge rather than a removal plus an addition.
+        let mut new = make_ir_with_nodes(&["A", "B", "C"]);
+        new.edges.extend([
+            edge(2, 0, None),
+            edge(0, 1, None),
+            edge(1, 2, None),
+            edge(1, 0, Some("e1")),
+        ]);
+
+        let diff = diff_diagrams(&old, &new);
+        assert_eq!(
+            (
+                diff.added_edges,
+                diff.removed_edges,
+                diff.changed_edges,
+                diff.unchanged_edges
+            ),
+            (1, 0, 1, 2)
+        );
+        let changed = diff
+            .edges
+            .iter()
+            .find(|edge| edge.status == DiffStatus::Changed)
+            .unwrap();
+        assert_eq!(
+            (changed.from_id.as_str(), changed.to_id.as_str()),
+            ("B", "A")
+        );
+        assert_eq!(
+            changed.changes,
+            vec![EdgeChange::EndpointsChanged {
+                old_from: "A".to_string(),
+                old_to: "C".to_string(),
+            }]
+        );
+        let pairs: Vec<_> = diff
+            .edges
+            .iter()
+            .map(|edge| (edge.from_id.as_str(), edge.to_id.as_str()))
+            .collect();
+        assert!(pairs.is_sorted(), "{pairs:?}");
+    }
+
     #[test]
     fn edge_replacement_reporting() {
         let mut old_ir = make_ir_with_nodes(&["A", "B"]);
@@ -1438,6 +2054,115 @@ mod tests {
         );
     }
 
+    #[test]
+    fn terminal_diff_legend_explains_status_markers() {
+        let old = fm_parser::parse("flowchart LR\n  A-->B");
+        let new = fm_parser::parse("flowchart LR\n  A-->B-->C");
+        let config = TermRenderConfig {
+            legend: LegendPosition::Bottom,
+            ..TermRenderConfig::rich()
+        };
+        let rendered = render_diff_terminal_with_config(&old.ir, &new.ir, &config, 100, 24, false);
+        let tail: Vec<&str> = rendered.lines().rev().take(6).collect();
+        assert!(tail[0].starts_with('└'), "{rendered}");
+        assert!(tail[1].contains("= unchanged"), "{rendered}");
+        assert!(tail[4].contains("+ added"), "{rendered}");
+        assert!(tail[5].starts_with("┌ Legend "), "{rendered}");
+        assert_eq!(rendered.matches("Legend").count(), 1);
+    }
+
+    #[test]
+    fn diff_diagram_prefixes_changed_labels_without_color() {
+        let old = fm_parser::parse("flowchart LR\n  A[Keep] --> B[Gone]\n  A --> C[Before]");
+        let new = fm_parser::parse("flowchart LR\n  A[Keep] --> C[After]\n  A --> D[Fresh]");
+        let result = render_diff_diagram(&old.ir, &new.ir, &TermRenderConfig::compact(), 120, 30);
+
+        assert_eq!(result.node_count, 4);
+        assert_eq!(result.edge_count, 3);
+        for label in ["- Gone", "~ After", "+ Fresh"] {
+            assert!(
+                result.output.contains(label),
+                "{label} missing:\n{}",
+                result.output
+            );
+        }
+        assert!(result.output.contains("Keep"), "{}", result.output);
+        assert!(!result.output.contains("= Keep"), "{}", result.output);
+        assert!(!result.output.contains('\x1b'), "{}", result.output);
+    }
+
+    #[test]
+    fn diff_diagram_colors_elements_by_status() {
+        let old = fm_parser::parse("flowchart LR\n  A[Keep] --> B[Gone]\n  A --> C[Before]");
+        let new = fm_parser::parse("flowchart LR\n  A[Keep] --> C[After]\n  A --> D[Fresh]");
+        let config = TermRenderConfig {
+            color_mode: ColorMode::TrueColor,
+            legend: LegendPosition::Bottom,
+            ..TermRenderConfig::compact()
+        };
+        let result = render_diff_diagram(&old.ir, &new.ir, &config, 120, 30);
+
+        for sgr in [
+            "\x1b[38;2;34;197;94m",
+            "\x1b[38;2;239;68;68m",
+            "\x1b[38;2;245;158;11m",
+        ] {
+            assert!(
+                result.output.contains(sgr),
+                "{sgr:?} missing:\n{}",
+                result.output
+            );
+        }
+        assert!(!result.output.contains("+ Fresh"), "{}", result.output);
+        assert!(result.output.contains("unchanged"), "{}", result.output);
+    }
+
+    #[test]
+    fn side_by_side_diff_puts_common_nodes_on_the_same_row() {
+        let old = fm_parser::parse("flowchart TD\n  A[Start] --> B[Middle]\n  B --> C[Finish]");
+        let new = fm_parser::parse(
+            "flowchart TD\n  A[Start] --> X[Inserted]\n  X --> B[Middle]\n  B --> C[Finish]",
+        );
+        let rendered = render_diff_side_by_side(&old.ir, &new.ir, 100, 40);
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert!(lines[0].starts_with("Old"), "{rendered}");
+        assert!(lines[0].ends_with("│ New"), "{rendered}");
+        let gutter = lines[0]
+            .chars()
+            .position(|ch| ch == '│')
+            .unwrap_or_default();
+        assert_eq!(lines[1].chars().nth(gutter), Some('┼'), "{rendered}");
+        for line in &lines[2..] {
+            assert_eq!(line.chars().nth(gutter), Some('│'), "{rendered}");
+        }
+        for label in ["Start", "Middle", "Finish"] {
+            let row = lines
+                .iter()
+                .find(|line| line.contains(label))
+                .unwrap_or_else(|| panic!("{label} missing:\n{rendered}"));
+            assert_eq!(
+                row.matches(label).count(),
+                2,
+                "{label} not aligned:\n{rendered}"
+            );
+        }
+        assert_eq!(rendered.matches("Inserted").count(), 1);
+    }
+
+    #[test]
+    fn side_by_side_alignment_keeps_rows_in_order_on_both_sides() {
+        let old = make_ir_with_nodes(&["A", "B", "C"]);
+        let new = make_ir_with_nodes(&["A", "B", "C"]);
+        // B moved below C in the new pane; only one of them can be lined up with the old rows.
+        let old_lines = ["A", "", "B", "", "C"];
+        let new_lines = ["A", "C", "", "", "", "B"];
+        let anchors = common_node_rows(&old, &old_lines, &new, &new_lines, 24);
+        assert_eq!(anchors.len(), 2);
+        assert_eq!(anchors[0], (0, 0));
+        assert!(anchors[1] == (2, 5) || anchors[1] == (4, 1), "{anchors:?}");
+    }
+
     #[test]
     fn e2e_diff_across_diagram_types() {
         // Diff a flowchart against a different flowchart (class diagrams, etc.).
@@ -0,0 +1,217 @@
+//! Edge label placement for the generic cell and sub-cell grids.
+//!
+//! A label sits at the middle of its routed path with a blank cell on either side, so the line
+//! visibly breaks around the text. When that spot would cover a node box or a label placed
+//! earlier, the midpoints of the path's other segments are tried, then rows just above and below
+//! each of them. If nothing is free the label stays at the middle of the path.
+
+use fm_core::MermaidDiagramIr;
+use fm_layout::{DiagramLayout, LayoutEdgePath, LayoutPoint};
+
+use crate::renderer::{CellBuffer, TermRenderer};
+use crate::text::display_width;
+
+/// Rows tried above and below each anchor, nearest first.
+const ROW_OFFSETS: [isize; 5] = [0, -1, 1, -2, 2];
+
+/// An edge label positioned on the cell grid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct EdgeLabelPlacement {
+    /// Column of the first label character.
+    pub(crate) x: usize,
+    /// Row of the first label line.
+    pub(crate) y: usize,
+    pub(crate) lines: Vec<String>,
+}
+
+impl EdgeLabelPlacement {
+    fn width(&self) -> usize {
+        self.lines
+            .iter()
+            .map(|line| display_width(line))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Whether the label, with its blank gap on either side, overlaps `(x, y, w, h)`.
+    fn overlaps(&self, (x, y, w, h): (usize, usize, usize, usize)) -> bool {
+        let left = self.x.saturating_sub(1);
+        let right = self.x + self.width() + 1;
+        let bottom = self.y + self.lines.len();
+        left < x + w && x < right && self.y < y + h && y < bottom
+    }
+
+    /// Write the label into `buffer`, with a blank on either side of each line, stopping before
+    /// column `limit`.
+    pub(crate) fn draw(&self, buffer: &mut CellBuffer, limit: usize) {
+        for (row, line) in self.lines.iter().enumerate() {
+            let y = self.y + row;
+            if let Some(gap) = self.x.checked_sub(1) {
+                buffer.set(gap, y, ' ');
+            }
+            buffer.put_str(self.x, y, line, limit);
+            let end = self.x + display_width(line);
+            if end < limit {
+                buffer.set(end, y, ' ');
+            }
+        }
+    }
+}
+
+impl TermRenderer {
+    /// Where each labeled edge's label goes on a `cols` x `rows` grid, clear of node boxes and of
+    /// each other where the path allows it.
+    pub(crate) fn edge_label_placements(
+        &self,
+        ir: &MermaidDiagramIr,
+        layout: &DiagramLayout,
+        (cols, rows): (usize, usize),
+        (scale_x, scale_y): (f32, f32),
+    ) -> Vec<EdgeLabelPlacement> {
+        if !self.config.show_edge_labels {
+            return Vec::new();
+        }
+        let node_rects: Vec<_> = layout
+            .nodes
+            .iter()
+            .chain(&layout.extensions.sequence_mirror_headers)
+            .map(|node_box| self.bounds_to_cells(&node_box.bounds, scale_x, scale_y))
+            .collect();
+        let mut placed: Vec<EdgeLabelPlacement> = Vec::new();
+
+        for edge_path in &layout.edges {
+            if edge_path.points.len() < 2 {
+                continue;
+            }
+            let Some(label) = ir
+                .edges
+                .get(edge_path.edge_index)
+                .and_then(|edge| edge.label)
+                .and_then(|label_id| ir.labels.get(label_id.0))
+            else {
+                continue;
+            };
+            let base_label = self.truncate_label(&label.text);
+            let text = match ir
+                .sequence_meta
+                .as_ref()
+                .and_then(|meta| meta.autonumber_value(edge_path.edge_index))
+            {
+                Some(number) => format!("{number} {base_label}"),
+                None => base_label,
+            };
+            let lines: Vec<String> = text.lines().map(str::to_string).collect();
+            if lines.is_empty() {
+                continue;
+            }
+
+            let at = |(mid_x, mid_y): (usize, usize), dy: isize| {
+                let width = lines.iter().map(|line| display_width(line)).max()?;
+                let y = mid_y
+                    .saturating_sub(lines.len() / 2)
+                    .checked_add_signed(dy)?;
+                (y + lines.len() <= rows).then(|| EdgeLabelPlacement {
+                    x: mid_x
+                        .saturating_sub(width / 2)
+                        .min(cols.saturating_sub(width)),
+                    y,
+                    lines: lines.clone(),
+                })
+            };
+            let anchors = label_anchors(edge_path)
+                .into_iter()
+                .map(|point| self.point_to_cells(&point, scale_x, scale_y))
+                .collect::<Vec<_>>();
+            let free = anchors
+                .iter()
+                .flat_map(|&anchor| ROW_OFFSETS.iter().filter_map(move |&dy| at(anchor, dy)))
+                .find(|candidate| {
+                    !node_rects.iter().any(|&rect| candidate.overlaps(rect))
+                        && !placed.iter().any(|other| {
+                            candidate.overlaps((other.x, other.y, other.width(), other.lines.len()))
+                        })
+                });
+            if let Some(placement) = free.or_else(|| at(anchors[0], 0)) {
+                placed.push(placement);
+            }
+        }
+        placed
+    }
+}
+
+/// Candidate label centers in layout units: the middle of the path first, then the midpoints of
+/// its segments, nearest to the middle first.
+fn label_anchors(edge_path: &LayoutEdgePath) -> Vec<LayoutPoint> {
+    let points = &edge_path.points;
+    let midpoint = |a: &LayoutPoint, b: &LayoutPoint| LayoutPoint {
+        x: f32::midpoint(a.x, b.x),
+        y: f32::midpoint(a.y, b.y),
+    };
+    let middle = match points.len() {
+        2 => midpoint(&points[0], &points[1]),
+        4 => midpoint(&points[1], &points[2]),
+        len => points[len / 2],
+    };
+    let center_segment = (points.len() - 1) / 2;
+    let mut segments: Vec<usize> = (0..points.len() - 1).collect();
+    segments.sort_by_key(|&index| index.abs_diff(center_segment));
+
+    let mut anchors = vec![middle];
+    anchors.extend(
+        segments
+            .into_iter()
+            .map(|index| midpoint(&points[index], &points[index + 1])),
+    );
+    anchors
+}
+
+#[cfg(test)]
+mod tests {
+    use fm_layout::layout_diagram;
+
+    use crate::config::{ResolvedConfig, TermRenderConfig};
+    use crate::renderer::{TermRenderer, render_diagram_with_config};
+    use crate::text::display_width;
+
+    #[test]
+    fn compact_cell_mode_draws_edge_labels_with_a_gap_in_the_line() {
+        let parsed = fm_parser::parse("flowchart LR\n  A[Start] -->|yes| B[Finish]");
+        let result = render_diagram_with_config(&parsed.ir, &TermRenderConfig::compact(), 80, 24);
+        let row = result
+            .output
+            .lines()
+            .find(|line| line.contains("yes"))
+            .unwrap_or_else(|| panic!("label missing:\n{}", result.output));
+        assert!(row.contains(" yes "), "{row:?}");
+    }
+
+    #[test]
+    fn labels_are_truncated_and_kept_out_of_node_boxes() {
+        let parsed = fm_parser::parse(
+            "flowchart TD\n  A[Alpha] -->|a really quite long edge label| B[Beta]\n  A -->|no| C[Gamma]\n  B -->|ok| C",
+        );
+        let layout = layout_diagram(&parsed.ir);
+        let config = TermRenderConfig {
+            max_label_chars: 8,
+            ..TermRenderConfig::rich()
+        };
+        let renderer = TermRenderer::new(ResolvedConfig::resolve(&config, 200, 100));
+        let scale = (0.125, 0.0625);
+        let placements = renderer.edge_label_placements(&parsed.ir, &layout, (200, 100), scale);
+
+        assert_eq!(placements.len(), 3);
+        assert!(
+            placements[0]
+                .lines
+                .iter()
+                .all(|line| display_width(line) <= 8)
+        );
+        for node_box in &layout.nodes {
+            let rect = renderer.bounds_to_cells(&node_box.bounds, scale.0, scale.1);
+            assert!(
+                placements.iter().all(|placement| !placement.overlaps(rect)),
+                "{placements:?} overlaps {rect:?}"
+            );
+        }
+    }
+}
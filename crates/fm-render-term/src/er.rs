@@ -0,0 +1,264 @@
+//! Dedicated terminal renderer for entity-relationship diagrams.
+//!
+//! The generic renderer prints an entity's name and drops its attributes. Here each entity is a
+//! [`grid`](crate::grid) box with its attribute table (key, type, name and comment,
+//! column-aligned) under the name, and relationships carry crow's-foot cardinality marks at both
+//! ends:
+//!
+//! ```text
+//! ┌────────────────┐               ┌────────────────────┐
+//! │    CUSTOMER    │               │       ORDER        │
+//! ├────────────────┤               ├────────────────────┤
+//! │ PK int    id   ├┼┼─ places ──○<┤ PK int id          │
+//! │    string name │               │ FK int customer_id │
+//! └────────────────┘               └────────────────────┘
+//! ```
+
+use fm_core::{IrAttributeKey, MermaidDiagramIr};
+use fm_layout::DiagramLayout;
+
+use crate::glyphs::CardinalityGlyphs;
+use crate::grid::{GridBox, GridLink, Side};
+use crate::renderer::{TermRenderResult, TermRenderer, split_lines};
+
+/// A cardinality mark from the relationship operator (`||--o{`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mark {
+    One,
+    Zero,
+    Many,
+}
+
+impl Mark {
+    const fn from_char(ch: char) -> Option<Self> {
+        match ch {
+            '|' => Some(Self::One),
+            'o' => Some(Self::Zero),
+            '{' | '}' => Some(Self::Many),
+            _ => None,
+        }
+    }
+}
+
+/// Split a relationship operator into the marks at each end, the one touching the entity first,
+/// and whether the line is dotted (non-identifying).
+fn parse_operator(operator: &str) -> ([[Option<Mark>; 2]; 2], bool) {
+    let (index, dotted) = match (operator.find("--"), operator.find("..")) {
+        (Some(index), _) => (index, false),
+        (None, Some(index)) => (index, true),
+        (None, None) => return ([[None; 2]; 2], false),
+    };
+    let mut from = operator[..index].chars().map(Mark::from_char);
+    let mut to = operator[index + 2..].chars().rev().map(Mark::from_char);
+    (
+        [
+            [from.next().flatten(), from.next().flatten()],
+            [to.next().flatten(), to.next().flatten()],
+        ],
+        dotted,
+    )
+}
+
+const fn key_label(key: IrAttributeKey) -> &'static str {
+    match key {
+        IrAttributeKey::Pk => "PK",
+        IrAttributeKey::Fk => "FK",
+        IrAttributeKey::Uk => "UK",
+        IrAttributeKey::None => "",
+    }
+}
+
+impl TermRenderer {
+    /// Draw an ER diagram with attribute tables and crow's-foot relationships. `None` when the
+    /// layout places no entities.
+    pub(crate) fn render_er(
+        &self,
+        ir: &MermaidDiagramIr,
+        layout: &DiagramLayout,
+    ) -> Option<TermRenderResult> {
+        let mut entity_of = vec![None; ir.nodes.len()];
+        let mut entities = Vec::with_capacity(layout.nodes.len());
+        for node_box in &layout.nodes {
+            let Some(node) = ir.nodes.get(node_box.node_index) else {
+                continue;
+            };
+            let title = self
+                .node_display_label(ir, Some(node), &node_box.node_id)
+                .map(|label| split_lines(&label))
+                .unwrap_or_default();
+
+            let cells: Vec<[String; 4]> = node
+                .members
+                .iter()
+                .map(|attribute| {
+                    [
+                        key_label(attribute.key).to_owned(),
+                        self.cell_text(&attribute.data_type),
+                        self.cell_text(&attribute.name),
+                        attribute
+                            .comment
+                            .as_deref()
+                            .map(|comment| self.cell_text(comment))
+                            .unwrap_or_default(),
+                    ]
+                })
+                .collect();
+            let mut widths = [0; 4];
+            for row in &cells {
+                for (width, cell) in widths.iter_mut().zip(row) {
+                    *width = (*width).max(cell.chars().count());
+                }
+            }
+            // Columns nobody fills (no keys, no comments) are left out entirely.
+            let rows: Vec<String> = cells
+                .iter()
+                .map(|row| {
+                    let mut line = String::new();
+                    for (cell, &width) in row.iter().zip(&widths) {
+                        if width == 0 {
+                            continue;
+                        }
+                        if !line.is_empty() {
+                            line.push(' ');
+                        }
+                        line.push_str(cell);
+                        line.extend(std::iter::repeat_n(' ', width - cell.chars().count()));
+                    }
+                    line.trim_end().to_owned()
+                })
+                .collect();
+
+            if let Some(slot) = entity_of.get_mut(node_box.node_index) {
+                *slot = Some(entities.len());
+            }
+            let sections = if rows.is_empty() {
+                vec![title]
+            } else {
+                vec![title, rows]
+            };
+            entities.push(GridBox::new(&node_box.bounds, sections));
+        }
+        if entities.is_empty() {
+            return None;
+        }
+
+        let mut marks = Vec::new();
+        let relationships: Vec<GridLink> = ir
+            .edges
+            .iter()
+            .filter_map(|edge| {
+                let from = edge.from.resolved_node_id(&ir.ports)?;
+                let to = edge.to.resolved_node_id(&ir.ports)?;
+                let from = entity_of.get(from.0).copied().flatten()?;
+                let to = entity_of.get(to.0).copied().flatten()?;
+                let (end_marks, dotted) = edge
+                    .er_notation()
+                    .map_or(([[None; 2]; 2], false), parse_operator);
+                marks.push(end_marks);
+                Some(GridLink {
+                    from,
+                    to,
+                    label: edge
+                        .label
+                        .and_then(|label_id| ir.labels.get(label_id.0))
+                        .map(|label| self.cell_text(&label.text))
+                        .unwrap_or_default(),
+                    dotted,
+                })
+            })
+            .collect();
+
+        let glyphs = CardinalityGlyphs::for_mode(self.config.glyph_mode);
+        Some(
+            self.render_box_grid(ir, layout, entities, &relationships, |link, end, side| {
+                marks[link][end].map(|mark| {
+                    mark.map(|mark| match (mark, side) {
+                        (Mark::One, Side::Left | Side::Right) => glyphs.bar_h,
+                        (Mark::One, Side::Top | Side::Bottom) => glyphs.bar_v,
+                        (Mark::Zero, _) => glyphs.ring,
+                        (Mark::Many, Side::Right) => glyphs.many_left,
+                        (Mark::Many, Side::Left) => glyphs.many_right,
+                        (Mark::Many, Side::Bottom) => glyphs.many_up,
+                        (Mark::Many, Side::Top) => glyphs.many_down,
+                    })
+                })
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::TermRenderConfig;
+    use crate::renderer::render_diagram_with_config;
+    use fm_core::MermaidGlyphMode;
+
+    const CUSTOMER_ORDERS: &str = "erDiagram\n  CUSTOMER {\n    int id PK\n    string name\n  }\n  ORDER {\n    int id PK\n    int customer_id FK\n  }\n  CUSTOMER ||--o{ ORDER : places";
+
+    fn render(source: &str, config: &TermRenderConfig) -> String {
+        let parsed = fm_parser::parse(source);
+        render_diagram_with_config(&parsed.ir, config, 160, 60).output
+    }
+
+    #[test]
+    fn entities_list_their_attributes_in_aligned_columns() {
+        let output = render(CUSTOMER_ORDERS, &TermRenderConfig::rich());
+
+        assert!(output.contains("CUSTOMER"), "{output}");
+        assert!(output.contains("ORDER"), "{output}");
+        // Key, type and name columns line up within each entity.
+        assert!(output.contains("PK int    id"), "{output}");
+        assert!(output.contains("   string name"), "{output}");
+        assert!(output.contains("PK int id"), "{output}");
+        assert!(output.contains("FK int customer_id"), "{output}");
+        assert!(output.contains('├'), "{output}");
+    }
+
+    #[test]
+    fn relationships_carry_crows_foot_marks_and_labels() {
+        let output = render(CUSTOMER_ORDERS, &TermRenderConfig::rich());
+
+        assert!(output.contains("places"), "{output}");
+        // `||` on the customer side, `o{` on the order side.
+        assert_eq!(output.matches('┼').count(), 2, "{output}");
+        assert_eq!(output.matches('○').count(), 1, "{output}");
+        assert!(
+            ['<', '>', '∧', '∨']
+                .iter()
+                .any(|&foot| output.contains(foot)),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn non_identifying_and_self_relationships() {
+        let output = render(
+            "erDiagram\n  PERSON ||--o{ PERSON : parent_of\n  PERSON }|..|{ CAR : drives",
+            &TermRenderConfig::rich(),
+        );
+
+        assert!(output.contains("parent_of"), "{output}");
+        assert!(output.contains("drives"), "{output}");
+        assert!(output.contains('┄') || output.contains('┆'), "{output}");
+        // The self-relationship loops out to the right, its label beside the loop.
+        assert!(
+            output
+                .lines()
+                .any(|line| line.contains('┐') && line.contains("parent_of")),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn ascii_mode_uses_only_ascii() {
+        let config = TermRenderConfig {
+            glyph_mode: MermaidGlyphMode::Ascii,
+            ..TermRenderConfig::rich()
+        };
+        let output = render(CUSTOMER_ORDERS, &config);
+
+        assert!(output.is_ascii(), "{output}");
+        assert!(output.contains("| PK int    id"), "{output}");
+        assert!(output.contains("places"), "{output}");
+    }
+}
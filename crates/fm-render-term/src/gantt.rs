@@ -0,0 +1,327 @@
+//! Dedicated terminal renderer for gantt charts.
+//!
+//! Tasks are placed by the schedule from [`layout_gantt`], one row each under their section's
+//! lane, against a date axis scaled to the viewport width. Task states get distinct fills, and a
+//! dotted column marks today when it falls inside the chart:
+//!
+//! ```text
+//!           2026-02-01           2026-02-08
+//!           ┬──────────────┊─────┬─────────
+//! Build ───────────────────┊───────────────
+//!   Design  ░░░░░░░░░      ┊
+//!   Code             ▒▒▒▒▒▒▒▒▒▒▒▒
+//! Ship ────────────────────┊───────────────
+//!   Test                   ┊     ▓▓▓▓▓▓
+//!   Release                ┊           ◆
+//! ```
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use fm_core::{GanttTaskType, GanttTickInterval, MermaidDiagramIr};
+use fm_layout::DiagramLayout;
+use fm_layout::chart::{GanttLayout, layout_gantt};
+
+use crate::glyphs::GanttGlyphs;
+use crate::renderer::{GridCanvas, TermRenderResult, TermRenderer};
+
+/// Most cells one day may take, so short charts stay compact.
+const MAX_DAY_CELLS: usize = 12;
+/// A `YYYY-MM-DD` tick label and the blank cell after it.
+const TICK_LABEL_CELLS: usize = 11;
+/// Tick steps in days, tried in order until neighbouring labels stop overlapping.
+const TICK_STEPS: [i32; 8] = [1, 2, 7, 14, 30, 91, 182, 365];
+
+/// Days since the Unix epoch by the system clock.
+fn today() -> Option<i32> {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    i32::try_from(seconds / 86_400).ok()
+}
+
+impl TermRenderer {
+    /// Draw a gantt chart with a date axis, section lanes, task bars and a today marker. `None`
+    /// when the chart has no tasks.
+    pub(crate) fn render_gantt(
+        &self,
+        ir: &MermaidDiagramIr,
+        layout: &DiagramLayout,
+    ) -> Option<TermRenderResult> {
+        self.render_gantt_at(ir, layout, today())
+    }
+
+    /// [`Self::render_gantt`] with today's day number given; `None` draws no marker.
+    fn render_gantt_at(
+        &self,
+        ir: &MermaidDiagramIr,
+        layout: &DiagramLayout,
+        today: Option<i32>,
+    ) -> Option<TermRenderResult> {
+        let meta = ir.gantt_meta.as_ref()?;
+        let gantt = layout_gantt(ir)?;
+        let glyphs = GanttGlyphs::for_mode(self.config.glyph_mode);
+        let rules = &self.box_glyphs;
+
+        // Tasks past the last section fall into it, as in the layout.
+        let section_count = meta.sections.len().max(1);
+        let mut lanes = vec![Vec::new(); section_count];
+        for (index, task) in meta.tasks.iter().enumerate() {
+            lanes[task.section_idx.min(section_count - 1)].push(index);
+        }
+        let task_names: Vec<String> = meta
+            .tasks
+            .iter()
+            .map(|task| {
+                let text = ir.nodes.get(task.node.0).map_or("task", |node| {
+                    node.label
+                        .and_then(|label_id| ir.labels.get(label_id.0))
+                        .map_or(node.id.as_str(), |label| label.text.as_str())
+                });
+                self.cell_text(text)
+            })
+            .collect();
+        let section_names: Vec<String> = meta
+            .sections
+            .iter()
+            .map(|section| self.cell_text(&section.name))
+            .collect();
+        let title = meta
+            .title
+            .as_deref()
+            .or(ir.meta.title.as_deref())
+            .map(|title| self.cell_text(title));
+
+        let padding = self.config.padding;
+        let available = self.config.cols.saturating_sub(padding * 2);
+        let label_width = task_names
+            .iter()
+            .map(|name| name.chars().count() + 2)
+            .chain(section_names.iter().map(|name| name.chars().count()))
+            .max()
+            .unwrap_or(0)
+            .clamp(8, (available / 3).max(8));
+        let chart_left = label_width + 1;
+        let chart_width = available.saturating_sub(chart_left).max(1);
+        let span = usize::try_from(gantt.last_day - gantt.first_day + 1).unwrap_or(1);
+        // Whole cells per day while every day fits, so day boundaries line up; past that, days
+        // share cells.
+        let scale = if chart_width >= span {
+            (chart_width / span).min(MAX_DAY_CELLS) as f32
+        } else {
+            chart_width as f32 / span as f32
+        };
+        let column = |day: i32| chart_left + ((day - gantt.first_day) as f32 * scale) as usize;
+        let preferred = match meta.tick_interval {
+            None | Some(GanttTickInterval::Day) => 1,
+            Some(GanttTickInterval::Week) => 7,
+            Some(GanttTickInterval::Month) => 30,
+            Some(GanttTickInterval::Quarter) => 91,
+            Some(GanttTickInterval::Year) => 365,
+        };
+        let step = TICK_STEPS
+            .into_iter()
+            .filter(|&step| step >= preferred)
+            .find(|&step| step as f32 * scale >= TICK_LABEL_CELLS as f32)
+            .unwrap_or(span as i32)
+            .max(1);
+        let ticks: Vec<i32> = (gantt.first_day..=gantt.last_day)
+            .step_by(step as usize)
+            .collect();
+        // The last tick's label may run past the final day.
+        let width = ticks
+            .last()
+            .map_or(0, |&day| column(day) + TICK_LABEL_CELLS - 1)
+            .max(column(gantt.last_day + 1))
+            .max(chart_left + 1);
+
+        let headers = !meta.sections.is_empty();
+        let top = if title.is_some() { 2 } else { 0 };
+        let height = top
+            + 2
+            + lanes
+                .iter()
+                .map(|tasks| tasks.len() + usize::from(headers))
+                .sum::<usize>();
+        let out_width = (width + padding * 2).min(self.config.cols);
+        let out_height = (height + padding * 2).min(self.config.rows);
+        let mut canvas = GridCanvas::new(out_width, out_height, padding);
+
+        if let Some(title) = &title {
+            let x = width.saturating_sub(title.chars().count()) / 2;
+            canvas.text(x, 0, title, width);
+        }
+
+        for x in chart_left..width {
+            canvas.set(x, top + 1, rules.horizontal);
+        }
+        for &day in &ticks {
+            let x = column(day);
+            canvas.set(x, top + 1, rules.t_down);
+            canvas.text(x, top, &GanttLayout::date_label(day), width);
+        }
+
+        let mut row = top + 2;
+        for (section, tasks) in lanes.iter().enumerate() {
+            if headers {
+                let name = section_names.get(section).map_or("", String::as_str);
+                canvas.text(0, row, name, label_width);
+                for x in name.chars().count().min(label_width) + 1..width {
+                    canvas.set(x, row, rules.horizontal);
+                }
+                row += 1;
+            }
+            for &index in tasks {
+                let placed = &gantt.tasks[index];
+                canvas.text(2, row, &task_names[index], label_width);
+                if placed.milestone {
+                    canvas.set(column(placed.start_day), row, glyphs.milestone);
+                } else {
+                    let fill = match meta.tasks[index].task_type {
+                        GanttTaskType::Done => glyphs.done,
+                        GanttTaskType::Active => glyphs.active,
+                        GanttTaskType::Critical => glyphs.critical,
+                        GanttTaskType::Normal | GanttTaskType::Milestone => glyphs.task,
+                    };
+                    let start = column(placed.start_day);
+                    for x in start..column(placed.end_day).max(start + 1) {
+                        canvas.set(x, row, fill);
+                    }
+                }
+                row += 1;
+            }
+        }
+
+        // The marker runs through empty cells and rules, behind the task bars.
+        if let Some(x) = today
+            .filter(|_| meta.today_marker_style.as_deref() != Some("off"))
+            .filter(|day| (gantt.first_day..=gantt.last_day).contains(day))
+            .map(column)
+        {
+            for y in top + 1..height {
+                if canvas
+                    .get(x, y)
+                    .is_some_and(|ch| ch == ' ' || ch == rules.horizontal || ch == rules.t_down)
+                {
+                    canvas.set(x, y, glyphs.today);
+                }
+            }
+        }
+
+        Some(TermRenderResult {
+            output: canvas.buffer.to_output_string(),
+            width: out_width,
+            height: out_height,
+            tier: self.config.tier,
+            render_mode: self.config.render_mode,
+            node_count: layout.nodes.len(),
+            edge_count: layout.edges.len(),
+            summary_only: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{ResolvedConfig, TermRenderConfig};
+    use crate::renderer::TermRenderer;
+    use fm_core::MermaidGlyphMode;
+
+    const ROADMAP: &str = "gantt\n  title Roadmap\n  dateFormat YYYY-MM-DD\n  section Build\n  Design :done, d1, 2026-02-01, 3d\n  Code :active, c1, after d1, 4d\n  section Ship\n  Test :crit, t1, after c1, 2d\n  Release :milestone, r1, after t1, 0d";
+
+    /// Render with `today` given as an offset from the chart's first day.
+    fn render(source: &str, config: &TermRenderConfig, today: Option<i32>) -> String {
+        let parsed = fm_parser::parse(source);
+        let layout = fm_layout::layout_diagram(&parsed.ir);
+        let first_day = fm_layout::chart::layout_gantt(&parsed.ir)
+            .expect("gantt schedule")
+            .first_day;
+        TermRenderer::new(ResolvedConfig::resolve(config, 120, 40))
+            .render_gantt_at(&parsed.ir, &layout, today.map(|offset| first_day + offset))
+            .expect("gantt output")
+            .output
+    }
+
+    fn line_with<'a>(output: &'a str, needle: &str) -> (usize, &'a str) {
+        output
+            .lines()
+            .enumerate()
+            .find(|(_, line)| line.contains(needle))
+            .unwrap_or_else(|| panic!("{needle:?} missing from\n{output}"))
+    }
+
+    fn first_column(line: &str, glyph: char) -> usize {
+        line.chars().position(|ch| ch == glyph).expect("glyph")
+    }
+
+    fn end_column(line: &str, glyph: char) -> usize {
+        line.chars()
+            .enumerate()
+            .filter(|(_, ch)| *ch == glyph)
+            .last()
+            .map(|(column, _)| column + 1)
+            .expect("glyph")
+    }
+
+    #[test]
+    fn tasks_sit_on_a_date_axis_under_section_lanes() {
+        let output = render(ROADMAP, &TermRenderConfig::rich(), None);
+
+        let (title, _) = line_with(&output, "Roadmap");
+        let (axis, _) = line_with(&output, "2026-02-01");
+        let (build, build_line) = line_with(&output, "Build");
+        let (design, design_line) = line_with(&output, "Design");
+        let (_, code_line) = line_with(&output, "Code");
+        let (ship, _) = line_with(&output, "Ship");
+        let (test, test_line) = line_with(&output, "Test");
+        let (_, release_line) = line_with(&output, "Release");
+        assert!(title < axis && axis < build && build < design, "{output}");
+        assert!(design < ship && ship < test, "{output}");
+        assert!(build_line.contains('─'), "{output}");
+
+        // Done, active, crit and milestone tasks each get their own fill, end to end in time.
+        let design_end = end_column(design_line, '░');
+        assert_eq!(first_column(code_line, '▒'), design_end, "{output}");
+        let code_end = end_column(code_line, '▒');
+        assert_eq!(first_column(test_line, '▓'), code_end, "{output}");
+        let test_end = end_column(test_line, '▓');
+        assert_eq!(first_column(release_line, '◆'), test_end, "{output}");
+        assert!(!output.contains('┊'), "{output}");
+    }
+
+    #[test]
+    fn today_marker_runs_down_one_column_unless_turned_off() {
+        let output = render(ROADMAP, &TermRenderConfig::rich(), Some(4));
+
+        let columns: Vec<usize> = output
+            .lines()
+            .filter(|line| line.contains('┊'))
+            .map(|line| first_column(line, '┊'))
+            .collect();
+        // The axis rule, both lanes and every task row not covering day 4.
+        assert!(columns.len() >= 5, "{output}");
+        assert!(
+            columns.windows(2).all(|pair| pair[0] == pair[1]),
+            "{output}"
+        );
+        let (_, code_line) = line_with(&output, "Code");
+        assert!(!code_line.contains('┊'), "{output}");
+
+        let off = ROADMAP.replace("dateFormat", "todayMarker off\n  dateFormat");
+        let output = render(&off, &TermRenderConfig::rich(), Some(4));
+        assert!(!output.contains('┊'), "{output}");
+    }
+
+    #[test]
+    fn ascii_mode_uses_distinct_ascii_fills() {
+        let config = TermRenderConfig {
+            glyph_mode: MermaidGlyphMode::Ascii,
+            ..TermRenderConfig::rich()
+        };
+        let output = render(ROADMAP, &config, Some(4));
+
+        assert!(output.is_ascii(), "{output}");
+        assert!(line_with(&output, "Design").1.contains("==="), "{output}");
+        assert!(line_with(&output, "Code").1.contains("+++"), "{output}");
+        assert!(line_with(&output, "Test").1.contains("!!!"), "{output}");
+        assert!(line_with(&output, "Release").1.contains('*'), "{output}");
+        assert!(output.contains(':'), "{output}");
+    }
+}
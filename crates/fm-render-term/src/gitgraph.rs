@@ -0,0 +1,311 @@
+//! Dedicated terminal renderer for git graphs.
+//!
+//! Drawn like `git log --graph`: one column per branch lane from the layout's
+//! [`git_lanes`](fm_layout::LayoutExtensions::git_lanes), one row per commit in chronological
+//! order, and a connector row wherever a branch forks off or merges back. Each commit's id,
+//! branch head and message sit to the right of the lanes:
+//!
+//! ```text
+//! ● init
+//! ├─╮
+//! │ ● feat (develop) add feature
+//! ● │ fix
+//! ├─╯
+//! ● commit_1 (main) v1.0
+//! ```
+//!
+//! `BT` and `RL` graphs put the newest commit on top instead.
+
+use fm_core::{GraphDirection, MermaidDiagramIr, NodeShape};
+use fm_layout::DiagramLayout;
+
+use crate::glyphs::GitGraphGlyphs;
+use crate::renderer::{GridCanvas, TermRenderResult, TermRenderer};
+
+const UP: u8 = 1;
+const DOWN: u8 = 2;
+const LEFT: u8 = 4;
+const RIGHT: u8 = 8;
+const VERTICAL: u8 = UP | DOWN;
+const HORIZONTAL: u8 = LEFT | RIGHT;
+
+/// One output row, in chronological order.
+enum Line {
+    /// A commit, by IR node index.
+    Commit(usize),
+    /// A branch-off or merge line between two lanes, drawn before the commit it leads to.
+    Link { from: usize, to: usize },
+}
+
+impl TermRenderer {
+    /// Draw a git graph as branch lanes with commit dots, branch and merge connectors, and commit
+    /// text to the right. `None` when the layout has no lanes or they don't fit the viewport.
+    pub(crate) fn render_gitgraph(
+        &self,
+        ir: &MermaidDiagramIr,
+        layout: &DiagramLayout,
+    ) -> Option<TermRenderResult> {
+        let lanes = &layout.extensions.git_lanes;
+        let mut lane_of = vec![None; ir.nodes.len()];
+        for lane in lanes {
+            for &index in &lane.node_indices {
+                if let Some(slot) = lane_of.get_mut(index) {
+                    *slot = Some(lane.lane);
+                }
+            }
+        }
+        let lane_count = lanes.iter().map(|lane| lane.lane + 1).max()?;
+        let padding = self.config.padding;
+        let available = self.config.cols.saturating_sub(padding * 2);
+        let graph_width = lane_count * 2 - 1;
+        if graph_width > available {
+            return None;
+        }
+
+        let mut lines = Vec::new();
+        for (index, &lane) in lane_of.iter().enumerate() {
+            let Some(lane) = lane else {
+                continue;
+            };
+            for edge in &ir.edges {
+                let (Some(from), Some(to)) = (
+                    edge.from.resolved_node_id(&ir.ports),
+                    edge.to.resolved_node_id(&ir.ports),
+                ) else {
+                    continue;
+                };
+                if to.0 != index || from.0 >= index {
+                    continue;
+                }
+                if let Some(from_lane) = lane_of.get(from.0).copied().flatten()
+                    && from_lane != lane
+                {
+                    lines.push(Line::Link {
+                        from: from_lane,
+                        to: lane,
+                    });
+                }
+            }
+            lines.push(Line::Commit(index));
+        }
+
+        // Each lane runs from the first row that touches it to the last.
+        let mut extent = vec![None::<(usize, usize)>; lane_count];
+        let mut touch = |lane: usize, row: usize| {
+            let span = &mut extent[lane];
+            *span = Some(span.map_or((row, row), |(first, last)| (first.min(row), last.max(row))));
+        };
+        for (row, line) in lines.iter().enumerate() {
+            match *line {
+                Line::Commit(index) => {
+                    if let Some(lane) = lane_of[index] {
+                        touch(lane, row);
+                    }
+                }
+                Line::Link { from, to } => {
+                    touch(from, row);
+                    touch(to, row);
+                }
+            }
+        }
+
+        // Connections per cell, with up meaning earlier.
+        let mut cells = vec![vec![0_u8; graph_width]; lines.len()];
+        for (lane, span) in extent.iter().enumerate() {
+            let Some((first, last)) = *span else {
+                continue;
+            };
+            for (row, line) in cells.iter_mut().enumerate().take(last + 1).skip(first) {
+                if row > first {
+                    line[lane * 2] |= UP;
+                }
+                if row < last {
+                    line[lane * 2] |= DOWN;
+                }
+            }
+        }
+        for (row, line) in lines.iter().enumerate() {
+            if let Line::Link { from, to } = *line {
+                let (left, right) = (from.min(to) * 2, from.max(to) * 2);
+                cells[row][left] |= RIGHT;
+                cells[row][right] |= LEFT;
+                for cell in &mut cells[row][left + 1..right] {
+                    *cell |= LEFT | RIGHT;
+                }
+            }
+        }
+
+        let glyphs = GitGraphGlyphs::for_mode(self.config.glyph_mode);
+        let rules = &self.box_glyphs;
+        let reversed = matches!(ir.direction, GraphDirection::BT | GraphDirection::RL);
+        let junction = |mut bits: u8| {
+            if reversed {
+                bits = (bits & HORIZONTAL) | ((bits & UP) << 1) | ((bits & DOWN) >> 1);
+            }
+            match (bits & VERTICAL, bits & HORIZONTAL) {
+                (0, 0) => ' ',
+                (_, 0) => rules.vertical,
+                (0, _) => rules.horizontal,
+                (VERTICAL, HORIZONTAL) => rules.cross,
+                (VERTICAL, LEFT) => rules.t_left,
+                (VERTICAL, _) => rules.t_right,
+                (UP, HORIZONTAL) => rules.t_up,
+                (_, HORIZONTAL) => rules.t_down,
+                (DOWN, RIGHT) => glyphs.top_left,
+                (DOWN, _) => glyphs.top_right,
+                (_, RIGHT) => glyphs.bottom_left,
+                _ => glyphs.bottom_right,
+            }
+        };
+
+        // The last commit on each lane carries the branch name, like a `git log` decoration.
+        let heads: Vec<(usize, &str)> = lanes
+            .iter()
+            .filter_map(|lane| Some((*lane.node_indices.last()?, lane.branch.as_str())))
+            .collect();
+        let texts: Vec<String> = lines
+            .iter()
+            .map(|line| {
+                let Line::Commit(index) = *line else {
+                    return String::new();
+                };
+                let Some(node) = ir.nodes.get(index) else {
+                    return String::new();
+                };
+                let mut text = node.id.clone();
+                for &(_, branch) in heads.iter().filter(|(head, _)| *head == index) {
+                    text.push_str(&format!(" ({branch})"));
+                }
+                if let Some(label) = node.label.and_then(|label_id| ir.labels.get(label_id.0)) {
+                    text.push(' ');
+                    text.push_str(&label.text);
+                }
+                self.cell_text(&text)
+            })
+            .collect();
+
+        let text_left = graph_width + 1;
+        let width = texts
+            .iter()
+            .map(|text| text_left + text.chars().count())
+            .max()
+            .unwrap_or(graph_width);
+        let out_width = (width + padding * 2).min(self.config.cols);
+        let out_height = (lines.len() + padding * 2).min(self.config.rows);
+        let mut canvas = GridCanvas::new(out_width, out_height, padding);
+        let limit = out_width.saturating_sub(padding * 2);
+
+        for (row, line) in lines.iter().enumerate() {
+            let y = if reversed { lines.len() - 1 - row } else { row };
+            for (x, &bits) in cells[row].iter().enumerate() {
+                if bits != 0 {
+                    canvas.set(x, y, junction(bits));
+                }
+            }
+            if let Line::Commit(index) = *line
+                && let Some(lane) = lane_of[index]
+            {
+                let dot = match ir.nodes[index].shape {
+                    NodeShape::FilledCircle => glyphs.reverse,
+                    NodeShape::DoubleCircle => glyphs.highlight,
+                    _ => glyphs.commit,
+                };
+                canvas.set(lane * 2, y, dot);
+            }
+            canvas.text(text_left, y, &texts[row], limit);
+        }
+
+        Some(TermRenderResult {
+            output: canvas.buffer.to_output_string(),
+            width: out_width,
+            height: out_height,
+            tier: self.config.tier,
+            render_mode: self.config.render_mode,
+            node_count: layout.nodes.len(),
+            edge_count: layout.edges.len(),
+            summary_only: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::TermRenderConfig;
+    use crate::renderer::render_diagram_with_config;
+    use fm_core::MermaidGlyphMode;
+
+    const FEATURE_BRANCH: &str = "gitGraph\n  commit id: \"init\"\n  branch develop\n  commit id: \"feat\" msg: \"add feature\"\n  checkout main\n  commit id: \"fix\"\n  merge develop tag: \"v1.0\"";
+
+    fn render(source: &str, config: &TermRenderConfig) -> String {
+        let parsed = fm_parser::parse(source);
+        render_diagram_with_config(&parsed.ir, config, 120, 40).output
+    }
+
+    fn row_of(output: &str, needle: &str) -> usize {
+        output
+            .lines()
+            .position(|line| line.contains(needle))
+            .unwrap_or_else(|| panic!("{needle:?} missing from\n{output}"))
+    }
+
+    #[test]
+    fn commits_sit_in_branch_lanes_with_fork_and_merge_connectors() {
+        let output = render(FEATURE_BRANCH, &TermRenderConfig::rich());
+        let lines: Vec<&str> = output.lines().collect();
+
+        let init = row_of(&output, "init");
+        let feat = row_of(&output, "feat");
+        let fix = row_of(&output, "fix");
+        let merge = row_of(&output, "v1.0");
+        assert!(init < feat && feat < fix && fix < merge, "{output}");
+        // The fork and the merge each get a connector row.
+        assert!(lines[init + 1].contains("├─╮"), "{output}");
+        assert!(lines[merge - 1].contains("├─╯"), "{output}");
+        // develop's commit sits one lane to the right, main's lane running past it.
+        let feat_line = lines[feat].trim_start();
+        assert!(feat_line.starts_with("│ ●"), "{output}");
+        assert!(lines[fix].trim_start().starts_with("● │"), "{output}");
+    }
+
+    #[test]
+    fn branch_heads_and_messages_follow_the_commit_id() {
+        let output = render(FEATURE_BRANCH, &TermRenderConfig::rich());
+
+        assert!(output.contains("feat (develop) add feature"), "{output}");
+        assert!(output.contains("(main) v1.0"), "{output}");
+        assert!(!output.contains("init (main)"), "{output}");
+    }
+
+    #[test]
+    fn bottom_to_top_graphs_put_the_newest_commit_first() {
+        let output = render(
+            &FEATURE_BRANCH.replacen("gitGraph", "gitGraph BT:", 1),
+            &TermRenderConfig::rich(),
+        );
+
+        let lines: Vec<&str> = output.lines().collect();
+        let merge = row_of(&output, "v1.0");
+        let init = row_of(&output, "init");
+        assert!(merge < init, "{output}");
+        // Connectors flip with the graph: the merge opens downward, the fork closes upward.
+        assert!(lines[merge + 1].contains("├─╮"), "{output}");
+        assert!(lines[init - 1].contains("├─╯"), "{output}");
+    }
+
+    #[test]
+    fn ascii_mode_and_commit_types() {
+        let config = TermRenderConfig {
+            glyph_mode: MermaidGlyphMode::Ascii,
+            ..TermRenderConfig::rich()
+        };
+        let output = render(
+            "gitGraph\n  commit id: \"a\"\n  commit id: \"b\" type: REVERSE\n  commit id: \"c\" type: HIGHLIGHT",
+            &config,
+        );
+
+        assert!(output.is_ascii(), "{output}");
+        assert!(output.contains("* a"), "{output}");
+        assert!(output.contains("x b"), "{output}");
+        assert!(output.contains("@ c (main)"), "{output}");
+    }
+}
@@ -1,6 +1,6 @@
 //! Unicode box-drawing and ASCII fallback glyphs.
 
-use fm_core::MermaidGlyphMode;
+use fm_core::{ArrowType, IrEdge, MermaidGlyphMode};
 
 /// Box-drawing character set.
 #[derive(Debug, Clone, Copy)]
@@ -246,12 +246,77 @@ pub struct EdgeGlyphs {
     pub arrow_down: char,
     pub arrow_thick_right: char,
     pub arrow_thick_left: char,
+    pub arrow_thick_up: char,
+    pub arrow_thick_down: char,
+    pub thick_h: char,
+    pub thick_v: char,
     pub dotted_h: char,
     pub dotted_v: char,
     pub circle_head: char,
     pub cross_head: char,
 }
 
+/// Line style of an edge, picked from its [`ArrowType`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeStroke {
+    /// `-->`, `---`, `--o`, `--x`.
+    Solid,
+    /// `==>`, `===`, `<==>`.
+    Thick,
+    /// `-.->`, `-.-`, sequence replies.
+    Dotted,
+}
+
+impl EdgeStroke {
+    #[must_use]
+    pub const fn of(arrow: ArrowType) -> Self {
+        match arrow {
+            ArrowType::ThickArrow | ArrowType::ThickLine | ArrowType::DoubleThickArrow => {
+                Self::Thick
+            }
+            ArrowType::DottedArrow
+            | ArrowType::DottedOpenArrow
+            | ArrowType::DottedCross
+            | ArrowType::HalfArrowTopDotted
+            | ArrowType::HalfArrowBottomDotted
+            | ArrowType::HalfArrowTopReverseDotted
+            | ArrowType::HalfArrowBottomReverseDotted
+            | ArrowType::StickArrowTopDotted
+            | ArrowType::StickArrowBottomDotted
+            | ArrowType::StickArrowTopReverseDotted
+            | ArrowType::StickArrowBottomReverseDotted
+            | ArrowType::DottedLine
+            | ArrowType::DoubleDottedArrow => Self::Dotted,
+            _ => Self::Solid,
+        }
+    }
+}
+
+/// Arrow types drawn with a dotted or dashed line.
+pub(crate) const fn is_dotted(arrow: ArrowType) -> bool {
+    matches!(EdgeStroke::of(arrow), EdgeStroke::Dotted)
+}
+
+/// `stroke-width` (in px) from which a `linkStyle`d edge is drawn with thick glyphs.
+pub const HEAVY_STROKE_WIDTH_PX: f32 = 3.0;
+
+/// `edge`'s arrow in its thick form when its `linkStyle` sets a `stroke-width` of at least
+/// [`HEAVY_STROKE_WIDTH_PX`], so the line and its head draw heavy. Dotted arrows stay dotted.
+#[must_use]
+pub fn styled_arrow(edge: &IrEdge) -> ArrowType {
+    let heavy = edge
+        .inline_style
+        .as_ref()
+        .and_then(|style| style.length_px("stroke-width"))
+        .is_some_and(|width| width >= HEAVY_STROKE_WIDTH_PX);
+    match edge.arrow {
+        ArrowType::Arrow if heavy => ArrowType::ThickArrow,
+        ArrowType::Line if heavy => ArrowType::ThickLine,
+        ArrowType::DoubleArrow if heavy => ArrowType::DoubleThickArrow,
+        arrow => arrow,
+    }
+}
+
 impl EdgeGlyphs {
     /// Unicode edge characters.
     pub const UNICODE: Self = Self {
@@ -265,9 +330,13 @@ impl EdgeGlyphs {
         arrow_down: '▼',
         arrow_thick_right: '▸',
         arrow_thick_left: '◂',
+        arrow_thick_up: '▴',
+        arrow_thick_down: '▾',
+        thick_h: '═',
+        thick_v: '║',
         dotted_h: '┄',
         dotted_v: '┆',
-        circle_head: '●',
+        circle_head: '○',
         cross_head: '✕',
     };
 
@@ -283,6 +352,10 @@ impl EdgeGlyphs {
         arrow_down: 'v',
         arrow_thick_right: '>',
         arrow_thick_left: '<',
+        arrow_thick_up: '^',
+        arrow_thick_down: 'v',
+        thick_h: '=',
+        thick_v: 'H',
         dotted_h: '.',
         dotted_v: ':',
         circle_head: 'o',
@@ -297,6 +370,37 @@ impl EdgeGlyphs {
             MermaidGlyphMode::Ascii => Self::ASCII,
         }
     }
+
+    /// Horizontal and vertical line characters for `stroke`.
+    #[must_use]
+    pub const fn lines(&self, stroke: EdgeStroke) -> (char, char) {
+        match stroke {
+            EdgeStroke::Solid => (self.line_h, self.line_v),
+            EdgeStroke::Thick => (self.thick_h, self.thick_v),
+            EdgeStroke::Dotted => (self.dotted_h, self.dotted_v),
+        }
+    }
+
+    /// Head drawn where an edge of type `arrow` ends, arriving in direction `(dx, dy)`. `None`
+    /// for plain lines.
+    #[must_use]
+    pub const fn head(&self, arrow: ArrowType, dx: isize, dy: isize) -> Option<char> {
+        let thick = matches!(arrow, ArrowType::ThickArrow | ArrowType::DoubleThickArrow);
+        let horizontal = dx.abs() > dy.abs();
+        Some(match arrow {
+            ArrowType::Line | ArrowType::ThickLine | ArrowType::DottedLine => return None,
+            ArrowType::Circle => self.circle_head,
+            ArrowType::Cross | ArrowType::DottedCross => self.cross_head,
+            _ if horizontal && dx > 0 && thick => self.arrow_thick_right,
+            _ if horizontal && dx > 0 => self.arrow_right,
+            _ if horizontal && thick => self.arrow_thick_left,
+            _ if horizontal => self.arrow_left,
+            _ if dy > 0 && thick => self.arrow_thick_down,
+            _ if dy > 0 => self.arrow_down,
+            _ if thick => self.arrow_thick_up,
+            _ => self.arrow_up,
+        })
+    }
 }
 
 /// Cluster/subgraph decoration characters.
@@ -340,6 +444,24 @@ impl ClusterGlyphs {
         fill: '.',
     };
 
+    /// Lighter Unicode set for clusters nested inside another cluster.
+    pub const UNICODE_NESTED: Self = Self {
+        border_h: '·',
+        border_v: '·',
+        corner_tl: '╭',
+        corner_tr: '╮',
+        corner_bl: '╰',
+        corner_br: '╯',
+        ..Self::UNICODE
+    };
+
+    /// Lighter ASCII set for clusters nested inside another cluster.
+    pub const ASCII_NESTED: Self = Self {
+        border_h: '.',
+        border_v: '.',
+        ..Self::ASCII
+    };
+
     /// Get the appropriate cluster glyphs for the mode.
     #[must_use]
     pub const fn for_mode(mode: MermaidGlyphMode) -> Self {
@@ -348,6 +470,360 @@ impl ClusterGlyphs {
             MermaidGlyphMode::Ascii => Self::ASCII,
         }
     }
+
+    /// Glyphs for a cluster inside `depth` others: the plain set at the top level, the nested set
+    /// below it.
+    #[must_use]
+    pub const fn for_depth(mode: MermaidGlyphMode, depth: usize) -> Self {
+        match (mode, depth) {
+            (MermaidGlyphMode::Unicode, 0) => Self::UNICODE,
+            (MermaidGlyphMode::Unicode, _) => Self::UNICODE_NESTED,
+            (MermaidGlyphMode::Ascii, 0) => Self::ASCII,
+            (MermaidGlyphMode::Ascii, _) => Self::ASCII_NESTED,
+        }
+    }
+
+    /// Whether `ch` is a corner of any cluster set.
+    #[must_use]
+    pub const fn is_corner(ch: char) -> bool {
+        matches!(ch, '┌' | '┐' | '└' | '┘' | '╭' | '╮' | '╰' | '╯' | '+')
+    }
+}
+
+/// Crow's-foot cardinality marks drawn on the ends of ER relationship lines.
+#[derive(Debug, Clone, Copy)]
+pub struct CardinalityGlyphs {
+    /// "One" bar across a horizontal line.
+    pub bar_h: char,
+    /// "One" bar across a vertical line.
+    pub bar_v: char,
+    /// "Zero" ring.
+    pub ring: char,
+    /// "Many" foot fanning out toward an entity on the left.
+    pub many_left: char,
+    /// "Many" foot fanning out toward an entity on the right.
+    pub many_right: char,
+    /// "Many" foot fanning out toward an entity above.
+    pub many_up: char,
+    /// "Many" foot fanning out toward an entity below.
+    pub many_down: char,
+}
+
+impl CardinalityGlyphs {
+    /// Unicode cardinality characters.
+    pub const UNICODE: Self = Self {
+        bar_h: '┼',
+        bar_v: '┼',
+        ring: '○',
+        many_left: '>',
+        many_right: '<',
+        many_up: '∨',
+        many_down: '∧',
+    };
+
+    /// ASCII cardinality characters.
+    pub const ASCII: Self = Self {
+        bar_h: '|',
+        bar_v: '-',
+        ring: 'o',
+        many_left: '>',
+        many_right: '<',
+        many_up: 'v',
+        many_down: '^',
+    };
+
+    /// Get the appropriate cardinality glyphs for the mode.
+    #[must_use]
+    pub const fn for_mode(mode: MermaidGlyphMode) -> Self {
+        match mode {
+            MermaidGlyphMode::Unicode => Self::UNICODE,
+            MermaidGlyphMode::Ascii => Self::ASCII,
+        }
+    }
+}
+
+/// UML relationship markers drawn where class diagram edges meet a class box. Each marker is
+/// read from the box outward, one char per cell.
+#[derive(Debug, Clone, Copy)]
+pub struct RelationGlyphs {
+    /// Hollow triangle (inheritance, realization) pointing at a class on the right.
+    pub triangle_right: &'static str,
+    /// Hollow triangle pointing at a class on the left.
+    pub triangle_left: &'static str,
+    /// Hollow triangle pointing at a class above.
+    pub triangle_up: &'static str,
+    /// Hollow triangle pointing at a class below.
+    pub triangle_down: &'static str,
+    /// Filled diamond (composition).
+    pub diamond_filled: &'static str,
+    /// Hollow diamond (aggregation).
+    pub diamond_hollow: &'static str,
+}
+
+impl RelationGlyphs {
+    /// Unicode relationship markers.
+    pub const UNICODE: Self = Self {
+        triangle_right: "▷",
+        triangle_left: "◁",
+        triangle_up: "△",
+        triangle_down: "▽",
+        diamond_filled: "◆",
+        diamond_hollow: "◇",
+    };
+
+    /// ASCII relationship markers, spelled like Mermaid's `<|--` so triangles stay distinct
+    /// from plain arrowheads.
+    pub const ASCII: Self = Self {
+        triangle_right: ">|",
+        triangle_left: "<|",
+        triangle_up: "^-",
+        triangle_down: "v-",
+        diamond_filled: "*",
+        diamond_hollow: "o",
+    };
+
+    /// Get the appropriate relationship markers for the mode.
+    #[must_use]
+    pub const fn for_mode(mode: MermaidGlyphMode) -> Self {
+        match mode {
+            MermaidGlyphMode::Unicode => Self::UNICODE,
+            MermaidGlyphMode::Ascii => Self::ASCII,
+        }
+    }
+}
+
+/// Gantt chart fills and markers. Task states get distinct fills, so they stay apart without
+/// color.
+#[derive(Debug, Clone, Copy)]
+pub struct GanttGlyphs {
+    /// Fill for ordinary tasks.
+    pub task: char,
+    /// Fill for `done` tasks.
+    pub done: char,
+    /// Fill for `active` tasks.
+    pub active: char,
+    /// Fill for `crit` tasks.
+    pub critical: char,
+    /// Single-cell milestone.
+    pub milestone: char,
+    /// Column marking today's date.
+    pub today: char,
+}
+
+impl GanttGlyphs {
+    /// Unicode block and shade characters.
+    pub const UNICODE: Self = Self {
+        task: '█',
+        done: '░',
+        active: '▒',
+        critical: '▓',
+        milestone: '◆',
+        today: '┊',
+    };
+
+    /// ASCII fills.
+    pub const ASCII: Self = Self {
+        task: '#',
+        done: '=',
+        active: '+',
+        critical: '!',
+        milestone: '*',
+        today: ':',
+    };
+
+    /// Get the appropriate gantt glyphs for the mode.
+    #[must_use]
+    pub const fn for_mode(mode: MermaidGlyphMode) -> Self {
+        match mode {
+            MermaidGlyphMode::Unicode => Self::UNICODE,
+            MermaidGlyphMode::Ascii => Self::ASCII,
+        }
+    }
+}
+
+/// Git graph commit dots and the rounded corners where branch and merge lines turn.
+#[derive(Debug, Clone, Copy)]
+pub struct GitGraphGlyphs {
+    /// An ordinary commit.
+    pub commit: char,
+    /// A `type: REVERSE` commit.
+    pub reverse: char,
+    /// A `type: HIGHLIGHT` commit.
+    pub highlight: char,
+    pub top_left: char,
+    pub top_right: char,
+    pub bottom_left: char,
+    pub bottom_right: char,
+}
+
+impl GitGraphGlyphs {
+    /// Unicode dots and rounded corners.
+    pub const UNICODE: Self = Self {
+        commit: '●',
+        reverse: '⊗',
+        highlight: '◉',
+        top_left: '╭',
+        top_right: '╮',
+        bottom_left: '╰',
+        bottom_right: '╯',
+    };
+
+    /// ASCII dots, as in `git log --graph`.
+    pub const ASCII: Self = Self {
+        commit: '*',
+        reverse: 'x',
+        highlight: '@',
+        top_left: '+',
+        top_right: '+',
+        bottom_left: '+',
+        bottom_right: '+',
+    };
+
+    /// Get the appropriate git graph glyphs for the mode.
+    #[must_use]
+    pub const fn for_mode(mode: MermaidGlyphMode) -> Self {
+        match mode {
+            MermaidGlyphMode::Unicode => Self::UNICODE,
+            MermaidGlyphMode::Ascii => Self::ASCII,
+        }
+    }
+}
+
+/// Timeline period markers and the journey score meter. Scores read as a filled run, so they stay
+/// apart without color.
+#[derive(Debug, Clone, Copy)]
+pub struct TimelineGlyphs {
+    /// Period or step mark on the axis.
+    pub marker: char,
+    /// One point of a journey score.
+    pub score_filled: char,
+    /// One missing point, up to the maximum of five.
+    pub score_empty: char,
+}
+
+impl TimelineGlyphs {
+    /// Unicode dots.
+    pub const UNICODE: Self = Self {
+        marker: '●',
+        score_filled: '●',
+        score_empty: '○',
+    };
+
+    /// ASCII marks.
+    pub const ASCII: Self = Self {
+        marker: 'o',
+        score_filled: '#',
+        score_empty: '-',
+    };
+
+    /// Get the appropriate timeline glyphs for the mode.
+    #[must_use]
+    pub const fn for_mode(mode: MermaidGlyphMode) -> Self {
+        match mode {
+            MermaidGlyphMode::Unicode => Self::UNICODE,
+            MermaidGlyphMode::Ascii => Self::ASCII,
+        }
+    }
+}
+
+/// xy chart bar fills and line marks. Bar series cycle through the fills, so neighbouring series
+/// stay apart without color.
+#[derive(Debug, Clone, Copy)]
+pub struct XyChartGlyphs {
+    /// Fills for successive bar series.
+    pub bars: [char; 4],
+    /// Line mark when lines are drawn cell by cell.
+    pub point: char,
+    /// Legend swatch for lines drawn in Braille.
+    pub braille_swatch: char,
+}
+
+impl XyChartGlyphs {
+    /// Unicode blocks and shades.
+    pub const UNICODE: Self = Self {
+        bars: ['█', '▓', '▒', '░'],
+        point: '•',
+        braille_swatch: '⠤',
+    };
+
+    /// ASCII fills. Lines are never drawn in Braille here, so the swatch is the point mark.
+    pub const ASCII: Self = Self {
+        bars: ['#', '%', '=', ':'],
+        point: '*',
+        braille_swatch: '*',
+    };
+
+    /// Get the appropriate xy chart glyphs for the mode.
+    #[must_use]
+    pub const fn for_mode(mode: MermaidGlyphMode) -> Self {
+        match mode {
+            MermaidGlyphMode::Unicode => Self::UNICODE,
+            MermaidGlyphMode::Ascii => Self::ASCII,
+        }
+    }
+}
+
+/// Branch prefixes for indented tree views, like `tree(1)`.
+#[derive(Debug, Clone, Copy)]
+pub struct TreeGlyphs {
+    /// Before a child with later siblings.
+    pub branch: &'static str,
+    /// Before the last child.
+    pub last: &'static str,
+    /// Under a child with later siblings, continuing the branch.
+    pub pipe: &'static str,
+    /// Under the last child.
+    pub blank: &'static str,
+}
+
+impl TreeGlyphs {
+    /// Unicode tree prefixes.
+    pub const UNICODE: Self = Self {
+        branch: "├── ",
+        last: "└── ",
+        pipe: "│   ",
+        blank: "    ",
+    };
+
+    /// ASCII tree prefixes.
+    pub const ASCII: Self = Self {
+        branch: "|-- ",
+        last: "`-- ",
+        pipe: "|   ",
+        blank: "    ",
+    };
+
+    /// Get the appropriate tree prefixes for the mode.
+    #[must_use]
+    pub const fn for_mode(mode: MermaidGlyphMode) -> Self {
+        match mode {
+            MermaidGlyphMode::Unicode => Self::UNICODE,
+            MermaidGlyphMode::Ascii => Self::ASCII,
+        }
+    }
+}
+
+/// Unicode stand-in for a node icon: a symbol for each built-in architecture icon, or the icon
+/// itself when it already is a short emoji. `None` for icon names with no terminal equivalent.
+#[must_use]
+pub fn icon_glyph(icon: &str) -> Option<&str> {
+    let icon = icon.trim();
+    if !icon.is_ascii() && icon.chars().count() <= 4 {
+        return Some(icon);
+    }
+    let name = icon.strip_prefix("fa:").unwrap_or(icon);
+    let name = name
+        .strip_prefix("fa-")
+        .unwrap_or(name)
+        .to_ascii_lowercase();
+    match name.as_str() {
+        "database" | "db" => Some("🗄"),
+        "disk" | "hdd" | "hard-drive" | "harddisk" | "drive" | "storage" => Some("💾"),
+        "cloud" => Some("☁"),
+        "server" => Some("🖥"),
+        "internet" | "globe" | "web" | "www" | "world" => Some("🌐"),
+        _ => None,
+    }
 }
 
 #[cfg(test)]
@@ -369,6 +845,53 @@ mod tests {
         assert!(glyphs.arrow_right.is_ascii());
     }
 
+    #[test]
+    fn arrow_types_get_their_own_lines_and_heads() {
+        for glyphs in [EdgeGlyphs::UNICODE, EdgeGlyphs::ASCII] {
+            let (solid, _) = glyphs.lines(EdgeStroke::of(ArrowType::Arrow));
+            let (thick, _) = glyphs.lines(EdgeStroke::of(ArrowType::ThickArrow));
+            let (dotted, _) = glyphs.lines(EdgeStroke::of(ArrowType::DottedArrow));
+            assert!(solid != thick && thick != dotted && dotted != solid);
+
+            let heads: Vec<_> = [ArrowType::Arrow, ArrowType::Circle, ArrowType::Cross]
+                .into_iter()
+                .filter_map(|arrow| glyphs.head(arrow, 1, 0))
+                .collect();
+            assert_eq!(heads.len(), 3);
+            assert!(heads[0] != heads[1] && heads[1] != heads[2]);
+            assert_eq!(glyphs.head(ArrowType::ThickLine, 1, 0), None);
+        }
+        assert_eq!(EdgeGlyphs::UNICODE.head(ArrowType::Arrow, 0, -3), Some('▲'));
+    }
+
+    #[test]
+    fn wide_link_styles_promote_solid_arrows_to_thick() {
+        let edge = |arrow, stroke_width: &str| IrEdge {
+            arrow,
+            inline_style: Some(Box::new(fm_core::parse_style_string(&format!(
+                "stroke:#f00,stroke-width:{stroke_width}"
+            )))),
+            ..IrEdge::default()
+        };
+        assert_eq!(
+            styled_arrow(&edge(ArrowType::Arrow, "4px")),
+            ArrowType::ThickArrow
+        );
+        assert_eq!(
+            styled_arrow(&edge(ArrowType::Line, "3")),
+            ArrowType::ThickLine
+        );
+        assert_eq!(
+            styled_arrow(&edge(ArrowType::Arrow, "2px")),
+            ArrowType::Arrow
+        );
+        assert_eq!(
+            styled_arrow(&edge(ArrowType::DottedArrow, "6px")),
+            ArrowType::DottedArrow
+        );
+        assert_eq!(styled_arrow(&IrEdge::default()), IrEdge::default().arrow);
+    }
+
     #[test]
     fn mode_selects_correct_glyphs() {
         let unicode = BoxGlyphs::for_mode(MermaidGlyphMode::Unicode);
@@ -376,4 +899,14 @@ mod tests {
         assert_eq!(unicode.horizontal, '─');
         assert_eq!(ascii.horizontal, '-');
     }
+
+    #[test]
+    fn builtin_icons_and_emoji_have_terminal_glyphs() {
+        assert_eq!(icon_glyph("database"), Some("🗄"));
+        assert_eq!(icon_glyph("fa:fa-database"), icon_glyph("db"));
+        assert_eq!(icon_glyph("HDD"), icon_glyph("disk"));
+        assert_eq!(icon_glyph("internet"), Some("🌐"));
+        assert_eq!(icon_glyph(" 🚀 "), Some("🚀"));
+        assert_eq!(icon_glyph("logos:aws-s3"), None);
+    }
 }
@@ -0,0 +1,450 @@
+//! Boxes-and-lines grid shared by the dedicated ER and class diagram renderers.
+//!
+//! Scaled onto the cell grid, a box with a table of rows is only a few rows tall, so the generic
+//! renderer has room for its name alone. Here each box is sized to its text: a centred title
+//! section followed by left-aligned sections, each under a horizontal rule. Boxes keep the
+//! layout's relative placement but are pushed apart until they and the labels of the links
+//! between them fit. Links are routed as orthogonal lines, straight where the boxes overlap
+//! across the gap and with one bend where they do not, and the caller supplies the marks drawn at
+//! each end (crow's feet, UML triangles and diamonds).
+//!
+//! Output wider or taller than the viewport is clipped rather than rescaled, so rows stay
+//! legible.
+
+use fm_core::{MermaidDiagramIr, MermaidTier};
+use fm_layout::{DiagramLayout, LayoutRect};
+
+use crate::renderer::{GridCanvas, TermRenderResult, TermRenderer, split_lines, text_width};
+
+/// Blank columns between boxes that share no link.
+const BOX_GAP_X: usize = 4;
+/// Blank rows between boxes that share no link.
+const BOX_GAP_Y: usize = 1;
+/// Columns between linked boxes side by side, besides the label segment: two mark columns at
+/// each end and the bend column.
+const LINK_GAP_X: usize = 5;
+/// Rows between linked boxes stacked vertically: two mark rows at each end and the bend row.
+const LINK_GAP_Y: usize = 5;
+
+/// The side of a box a link leaves from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Side {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl Side {
+    /// The cell `distance` steps outward from `(x, y)`.
+    const fn step(self, (x, y): (usize, usize), distance: usize) -> (usize, usize) {
+        match self {
+            Self::Left => (x.saturating_sub(distance), y),
+            Self::Right => (x + distance, y),
+            Self::Top => (x, y.saturating_sub(distance)),
+            Self::Bottom => (x, y + distance),
+        }
+    }
+}
+
+/// A box of text sections: the first is the centred title, each later one sits under a rule.
+pub(crate) struct GridBox {
+    sections: Vec<Vec<String>>,
+    /// Box size, borders included.
+    width: usize,
+    height: usize,
+    /// Columns right of the box taken by a self-link loop and its label.
+    reach: usize,
+    /// Layout bounds as `(left, right)` and `(top, bottom)`, used only to order boxes.
+    span_x: (f32, f32),
+    span_y: (f32, f32),
+    /// Top-left cell.
+    left: usize,
+    top: usize,
+}
+
+impl GridBox {
+    /// A box at the layout position `bounds`. Sections after the title take at least one row,
+    /// so an empty one still shows as a compartment.
+    pub(crate) fn new(bounds: &LayoutRect, mut sections: Vec<Vec<String>>) -> Self {
+        if sections.is_empty() {
+            sections.push(Vec::new());
+        }
+        if sections[0].is_empty() {
+            sections[0].push(String::new());
+        }
+        let width = sections
+            .iter()
+            .map(|section| text_width(section))
+            .max()
+            .unwrap_or(0)
+            + 4;
+        let rows: usize = sections.iter().map(|section| section.len().max(1)).sum();
+        Self {
+            width,
+            height: rows + sections.len() + 1,
+            sections,
+            reach: 0,
+            span_x: (bounds.x, bounds.x + bounds.width),
+            span_y: (bounds.y, bounds.y + bounds.height),
+            left: 0,
+            top: 0,
+        }
+    }
+
+    const fn right(&self) -> usize {
+        self.left + self.width - 1
+    }
+
+    const fn bottom(&self) -> usize {
+        self.top + self.height - 1
+    }
+}
+
+/// A link between two boxes, by index.
+pub(crate) struct GridLink {
+    pub(crate) from: usize,
+    pub(crate) to: usize,
+    pub(crate) label: String,
+    pub(crate) dotted: bool,
+}
+
+/// A link routed between two placed boxes.
+struct Route {
+    /// Line corners, from the `from` end to the `to` end.
+    points: Vec<(usize, usize)>,
+    /// Border cell and side at the `from` and `to` ends.
+    ends: [(usize, usize, Side); 2],
+    /// Label text and the cell it starts at.
+    label: Option<(usize, usize, String)>,
+}
+
+/// Place boxes along one axis. Each keeps its scaled layout position unless a box that ends
+/// before it starts (in layout coordinates) pushes it further along, so boxes that are apart in
+/// the layout stay apart however much they grew.
+fn compact(
+    spans: &[(f32, f32)],
+    sizes: &[usize],
+    scale: f32,
+    gap: impl Fn(usize, usize) -> usize,
+) -> Vec<usize> {
+    let origin = spans
+        .iter()
+        .map(|span| span.0)
+        .fold(f32::INFINITY, f32::min);
+    let mut order: Vec<usize> = (0..spans.len()).collect();
+    order.sort_by(|&a, &b| spans[a].0.total_cmp(&spans[b].0));
+    let mut placed = vec![0; spans.len()];
+    for (rank, &index) in order.iter().enumerate() {
+        let mut position = ((spans[index].0 - origin) * scale).max(0.0) as usize;
+        for &before in &order[..rank] {
+            if spans[before].1 <= spans[index].0 {
+                position = position.max(placed[before] + sizes[before] + gap(before, index));
+            }
+        }
+        placed[index] = position;
+    }
+    placed
+}
+
+/// Route a link: a loop out of the right side for self-links, otherwise down from the upper box
+/// to the lower one when they are apart vertically and across from the left box to the right
+/// one when they share rows. The line runs straight when the boxes overlap across it and bends
+/// once next to the far end's marks when they do not.
+fn route(boxes: &[GridBox], link: &GridLink) -> Route {
+    let (a, b) = (&boxes[link.from], &boxes[link.to]);
+    let label = &link.label;
+
+    if link.from == link.to {
+        let (x, top, bottom) = (a.right(), a.top + 1, a.bottom() - 1);
+        return Route {
+            points: vec![(x + 1, top), (x + 4, top), (x + 4, bottom), (x + 1, bottom)],
+            ends: [(x, top, Side::Right), (x, bottom, Side::Right)],
+            label: (!label.is_empty()).then(|| (x + 6, top, label.clone())),
+        };
+    }
+
+    let vertical = a.bottom() < b.top || b.bottom() < a.top;
+    let flipped = if vertical {
+        b.top < a.top
+    } else {
+        b.left < a.left
+    };
+    let (first, second) = if flipped { (b, a) } else { (a, b) };
+    let mut route = if vertical {
+        let (upper, lower) = (first, second);
+        let lo = upper.left.max(lower.left) + 1;
+        let hi = upper.right().min(lower.right()).saturating_sub(1);
+        let (upper_x, lower_x) = if lo <= hi {
+            ((lo + hi) / 2, (lo + hi) / 2)
+        } else {
+            (upper.left + upper.width / 2, lower.left + lower.width / 2)
+        };
+        let start = (upper_x, upper.bottom() + 1);
+        let end = (lower_x, lower.top.saturating_sub(1));
+        let (points, label_at) = if upper_x == lower_x {
+            (vec![start, end], (upper_x + 2, (start.1 + end.1) / 2))
+        } else {
+            let bend = lower.top.saturating_sub(3).max(start.1);
+            (
+                vec![start, (upper_x, bend), (lower_x, bend), end],
+                (upper_x.max(lower_x) + 2, bend),
+            )
+        };
+        Route {
+            points,
+            ends: [
+                (upper_x, upper.bottom(), Side::Bottom),
+                (lower_x, lower.top, Side::Top),
+            ],
+            label: (!label.is_empty()).then(|| (label_at.0, label_at.1, label.clone())),
+        }
+    } else {
+        let (left, right) = (first, second);
+        let lo = left.top.max(right.top) + 1;
+        let hi = left.bottom().min(right.bottom()).saturating_sub(1);
+        let (left_y, right_y) = if lo <= hi {
+            let middle = (lo + hi).div_ceil(2);
+            (middle, middle)
+        } else {
+            (left.top + left.height / 2, right.top + right.height / 2)
+        };
+        let start = (left.right() + 1, left_y);
+        let end = (right.left.saturating_sub(1), right_y);
+        // The label sits on the first segment, a line cell and a space either side of it.
+        let (points, segment_end) = if left_y == right_y {
+            (vec![start, end], end.0.saturating_sub(1))
+        } else {
+            let bend = right.left.saturating_sub(3).max(start.0);
+            (vec![start, (bend, left_y), (bend, right_y), end], bend)
+        };
+        let segment_start = start.0 + 2;
+        let label_x = segment_start
+            + segment_end
+                .saturating_sub(segment_start)
+                .saturating_sub(label.chars().count() + 2)
+                / 2;
+        Route {
+            points,
+            ends: [
+                (left.right(), left_y, Side::Right),
+                (right.left, right_y, Side::Left),
+            ],
+            label: (!label.is_empty()).then(|| (label_x, left_y, format!(" {label} "))),
+        }
+    };
+    route.points.dedup();
+    if flipped {
+        route.points.reverse();
+        route.ends.reverse();
+    }
+    route
+}
+
+impl TermRenderer {
+    /// Place `boxes`, route `links` between them and draw both. `marks(link, end, side)` gives
+    /// the glyphs at the `from` (`end == 0`) or `to` end of a link leaving its box on `side`,
+    /// the one touching the box first.
+    pub(crate) fn render_box_grid(
+        &self,
+        ir: &MermaidDiagramIr,
+        layout: &DiagramLayout,
+        mut boxes: Vec<GridBox>,
+        links: &[GridLink],
+        marks: impl Fn(usize, usize, Side) -> [Option<char>; 2],
+    ) -> TermRenderResult {
+        // A self-link loops out of the right side between the first and last interior rows,
+        // which must differ.
+        for link in links.iter().filter(|link| link.from == link.to) {
+            let label = link.label.chars().count();
+            let grid_box = &mut boxes[link.from];
+            grid_box.reach = grid_box.reach.max(if label == 0 { 4 } else { label + 6 });
+            grid_box.height = grid_box.height.max(4);
+        }
+        // Widest label between two distinct boxes, if they are linked.
+        let link_label = |a: usize, b: usize| {
+            links
+                .iter()
+                .filter(|link| {
+                    a != b && ((link.from, link.to) == (a, b) || (link.from, link.to) == (b, a))
+                })
+                .map(|link| link.label.chars().count())
+                .max()
+        };
+
+        let scale_x = match self.config.tier {
+            MermaidTier::Compact => 0.15,
+            MermaidTier::Normal => 0.2,
+            MermaidTier::Rich | MermaidTier::Auto => 0.25,
+        };
+        // Cells are about twice as tall as they are wide.
+        let scale_y = scale_x / 2.0;
+
+        let spans_y: Vec<_> = boxes.iter().map(|grid_box| grid_box.span_y).collect();
+        let heights: Vec<_> = boxes.iter().map(|grid_box| grid_box.height).collect();
+        let tops = compact(&spans_y, &heights, scale_y, |a, b| {
+            link_label(a, b).map_or(BOX_GAP_Y, |_| LINK_GAP_Y)
+        });
+        // Linked boxes that still share rows are joined side by side and need room for the
+        // marks and the label between them.
+        let apart_y =
+            |a: usize, b: usize| tops[a] + heights[a] <= tops[b] || tops[b] + heights[b] <= tops[a];
+        let spans_x: Vec<_> = boxes.iter().map(|grid_box| grid_box.span_x).collect();
+        let widths: Vec<_> = boxes
+            .iter()
+            .map(|grid_box| grid_box.width + grid_box.reach)
+            .collect();
+        let lefts = compact(&spans_x, &widths, scale_x, |a, b| match link_label(a, b) {
+            Some(label) if !apart_y(a, b) => LINK_GAP_X + if label == 0 { 2 } else { label + 4 },
+            _ => BOX_GAP_X,
+        });
+
+        let title_lines = ir
+            .meta
+            .title
+            .as_deref()
+            .map(|title| split_lines(&self.truncate_label(title)))
+            .unwrap_or_default();
+        let title_rows = if title_lines.is_empty() {
+            0
+        } else {
+            title_lines.len() + 1
+        };
+        for (index, grid_box) in boxes.iter_mut().enumerate() {
+            grid_box.left = lefts[index];
+            grid_box.top = tops[index] + title_rows;
+        }
+
+        let routes: Vec<Route> = links.iter().map(|link| route(&boxes, link)).collect();
+
+        let mut width = boxes
+            .iter()
+            .map(|grid_box| grid_box.right() + 1 + grid_box.reach)
+            .max()
+            .unwrap_or(0)
+            .max(text_width(&title_lines));
+        for (x, _, text) in routes.iter().filter_map(|route| route.label.as_ref()) {
+            width = width.max(x + text.chars().count());
+        }
+        let height = boxes
+            .iter()
+            .map(|grid_box| grid_box.bottom() + 1)
+            .max()
+            .unwrap_or(0);
+
+        let padding = self.config.padding;
+        let out_width = (width + padding * 2).min(self.config.cols);
+        let out_height = (height + padding * 2).min(self.config.rows);
+        let mut canvas = GridCanvas::new(out_width, out_height, padding);
+        let glyphs = &self.box_glyphs;
+        let edges = &self.edge_glyphs;
+
+        // Lines first, so boxes hide any that pass behind them.
+        for (route, link) in routes.iter().zip(links) {
+            let (line_h, line_v) = if link.dotted {
+                (edges.dotted_h, edges.dotted_v)
+            } else {
+                (edges.line_h, edges.line_v)
+            };
+            for pair in route.points.windows(2) {
+                let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+                if y0 == y1 {
+                    for x in x0.min(x1)..=x0.max(x1) {
+                        canvas.set(x, y0, line_h);
+                    }
+                } else {
+                    for y in y0.min(y1)..=y0.max(y1) {
+                        canvas.set(x0, y, line_v);
+                    }
+                }
+            }
+            for corner in route.points.windows(3) {
+                let ((ax, ay), (bx, by), (cx, cy)) = (corner[0], corner[1], corner[2]);
+                // One neighbour shares the corner's row, the other its column.
+                let (arm_x, arm_y) = if ay == by { (ax, cy) } else { (cx, ay) };
+                let glyph = match (arm_x > bx, arm_y > by) {
+                    (true, true) => glyphs.top_left,
+                    (true, false) => glyphs.bottom_left,
+                    (false, true) => glyphs.top_right,
+                    (false, false) => glyphs.bottom_right,
+                };
+                canvas.set(bx, by, glyph);
+            }
+        }
+
+        for grid_box in &boxes {
+            self.draw_box(
+                &mut canvas,
+                grid_box.left,
+                grid_box.top,
+                grid_box.width,
+                grid_box.height,
+            );
+            let inner = grid_box.width - 2;
+            let mut row = grid_box.top + 1;
+            for line in &grid_box.sections[0] {
+                let x = grid_box.left + 1 + inner.saturating_sub(line.chars().count()) / 2;
+                canvas.text(x, row, line, grid_box.right());
+                row += 1;
+            }
+            for section in &grid_box.sections[1..] {
+                canvas.set(grid_box.left, row, glyphs.t_right);
+                for x in grid_box.left + 1..grid_box.right() {
+                    canvas.set(x, row, glyphs.horizontal);
+                }
+                canvas.set(grid_box.right(), row, glyphs.t_left);
+                row += 1;
+                for line in section {
+                    canvas.text(grid_box.left + 2, row, line, grid_box.right());
+                    row += 1;
+                }
+                if section.is_empty() {
+                    row += 1;
+                }
+            }
+        }
+
+        for (index, route) in routes.iter().enumerate() {
+            for (end, &(x, y, side)) in route.ends.iter().enumerate() {
+                let tee = match side {
+                    Side::Left => glyphs.t_left,
+                    Side::Right => glyphs.t_right,
+                    Side::Top => glyphs.t_up,
+                    Side::Bottom => glyphs.t_down,
+                };
+                canvas.set(x, y, tee);
+                for (distance, mark) in marks(index, end, side).into_iter().enumerate() {
+                    if let Some(mark) = mark {
+                        let (mark_x, mark_y) = side.step((x, y), distance + 1);
+                        canvas.set(mark_x, mark_y, mark);
+                    }
+                }
+            }
+            if let Some((x, y, text)) = &route.label {
+                canvas.text(*x, *y, text, width);
+            }
+        }
+
+        for (offset, line) in title_lines.iter().enumerate() {
+            let x = width.saturating_sub(line.chars().count()) / 2;
+            canvas.text(x, offset, line, width);
+        }
+
+        TermRenderResult {
+            output: canvas.buffer.to_output_string(),
+            width: out_width,
+            height: out_height,
+            tier: self.config.tier,
+            render_mode: self.config.render_mode,
+            node_count: layout.nodes.len(),
+            edge_count: layout.edges.len(),
+            summary_only: false,
+        }
+    }
+
+    /// A single-line table cell: sanitised, wrapped to `max_label_chars` and cut with `…` after
+    /// its first line.
+    pub(crate) fn cell_text(&self, text: &str) -> String {
+        self.truncate_label_to(text, self.config.max_label_chars, 1)
+    }
+}
@@ -0,0 +1,259 @@
+//! HTML export of rendered terminal output.
+//!
+//! [`TermRenderResult::to_html`] turns the SGR colors and OSC 8 hyperlinks in a render into
+//! `<span>` and `<a>` elements inside a `<pre>`, so a colored diagram can be pasted into docs
+//! or a pull request comment and look the way it did in the terminal.
+
+use std::fmt::Write as _;
+
+use crate::color::TermColor;
+use crate::patch::parse_cells;
+use crate::renderer::TermRenderResult;
+
+/// How [`TermRenderResult::to_html_with`] attaches styles to the text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HtmlStyleMode {
+    /// A `style` attribute on every span, so the fragment stands alone.
+    #[default]
+    Inline,
+    /// A `class` attribute on every span, defined in a `<style>` block ahead of the `<pre>`.
+    Classes,
+}
+
+/// Text attributes in effect for a run of cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct SpanStyle {
+    fg: Option<TermColor>,
+    bg: Option<TermColor>,
+    bold: bool,
+    faint: bool,
+    italic: bool,
+    underline: bool,
+}
+
+impl SpanStyle {
+    /// Apply the SGR sequences in `sgr`, in order.
+    fn parse(sgr: &str) -> Self {
+        let mut style = Self::default();
+        for sequence in sgr.split("\x1b[").filter(|sequence| !sequence.is_empty()) {
+            let params: Vec<u8> = sequence
+                .trim_end_matches('m')
+                .split(';')
+                .map(|param| param.parse().unwrap_or(0))
+                .collect();
+            let mut params = params.into_iter();
+            while let Some(param) = params.next() {
+                match param {
+                    0 => style = Self::default(),
+                    1 => style.bold = true,
+                    2 => style.faint = true,
+                    3 => style.italic = true,
+                    4 => style.underline = true,
+                    22 => (style.bold, style.faint) = (false, false),
+                    23 => style.italic = false,
+                    24 => style.underline = false,
+                    30..=37 => style.fg = Some(TermColor::from_ansi256(param - 30)),
+                    90..=97 => style.fg = Some(TermColor::from_ansi256(param - 82)),
+                    39 => style.fg = None,
+                    40..=47 => style.bg = Some(TermColor::from_ansi256(param - 40)),
+                    100..=107 => style.bg = Some(TermColor::from_ansi256(param - 92)),
+                    49 => style.bg = None,
+                    38 | 48 => {
+                        let color = match params.next() {
+                            Some(5) => params.next().map(TermColor::from_ansi256),
+                            Some(2) => match (params.next(), params.next(), params.next()) {
+                                (Some(r), Some(g), Some(b)) => Some(TermColor::new(r, g, b)),
+                                _ => None,
+                            },
+                            _ => None,
+                        };
+                        if param == 38 {
+                            style.fg = color;
+                        } else {
+                            style.bg = color;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        style
+    }
+
+    /// CSS declarations for the style, empty for plain text.
+    fn css(&self) -> String {
+        let mut declarations = Vec::new();
+        if let Some(fg) = self.fg {
+            declarations.push(format!("color:{}", fg.to_hex()));
+        }
+        if let Some(bg) = self.bg {
+            declarations.push(format!("background-color:{}", bg.to_hex()));
+        }
+        if self.bold {
+            declarations.push("font-weight:bold".to_string());
+        }
+        if self.faint {
+            declarations.push("opacity:0.5".to_string());
+        }
+        if self.italic {
+            declarations.push("font-style:italic".to_string());
+        }
+        if self.underline {
+            declarations.push("text-decoration:underline".to_string());
+        }
+        declarations.join(";")
+    }
+}
+
+impl TermRenderResult {
+    /// The output as a `<pre class="fm-term">` HTML fragment with inline styles.
+    #[must_use]
+    pub fn to_html(&self) -> String {
+        self.to_html_with(HtmlStyleMode::Inline)
+    }
+
+    /// The output as a `<pre class="fm-term">` HTML fragment. Colors, faint, bold, italic and
+    /// underlined text become styled `<span>`s, and hyperlinks become `<a href>`s. Text is
+    /// escaped; uncolored output comes out as escaped plain text.
+    #[must_use]
+    pub fn to_html_with(&self, mode: HtmlStyleMode) -> String {
+        let mut styles: Vec<SpanStyle> = Vec::new();
+        let mut body = String::with_capacity(self.output.len() * 2);
+        for (y, line) in self.output.split('\n').enumerate() {
+            if y > 0 {
+                body.push('\n');
+            }
+            let cells = parse_cells(line);
+            let mut start = 0;
+            while start < cells.len() {
+                let (sgr, link) = (&cells[start].sgr, &cells[start].link);
+                let end = cells[start..]
+                    .iter()
+                    .position(|cell| cell.sgr != *sgr || cell.link != *link)
+                    .map_or(cells.len(), |run| start + run);
+                let text: String = cells[start..end]
+                    .iter()
+                    .map(|cell| cell.glyph.as_str())
+                    .collect();
+                let style = sgr.as_deref().map(SpanStyle::parse).unwrap_or_default();
+                let href = link.as_deref().and_then(link_target);
+
+                if let Some(href) = href {
+                    let _ = write!(body, "<a href=\"{}\">", escape_html(href));
+                }
+                let styled = style != SpanStyle::default();
+                if styled {
+                    match mode {
+                        HtmlStyleMode::Inline => {
+                            let _ = write!(body, "<span style=\"{}\">", style.css());
+                        }
+                        HtmlStyleMode::Classes => {
+                            let index = styles.iter().position(|known| *known == style);
+                            let index = index.unwrap_or_else(|| {
+                                styles.push(style);
+                                styles.len() - 1
+                            });
+                            let _ = write!(body, "<span class=\"fm-term-s{index}\">");
+                        }
+                    }
+                }
+                body.push_str(&escape_html(&text));
+                if styled {
+                    body.push_str("</span>");
+                }
+                if href.is_some() {
+                    body.push_str("</a>");
+                }
+                start = end;
+            }
+        }
+
+        let mut html = String::new();
+        if !styles.is_empty() {
+            html.push_str("<style>\n");
+            for (index, style) in styles.iter().enumerate() {
+                let _ = writeln!(html, ".fm-term-s{index} {{ {} }}", style.css());
+            }
+            html.push_str("</style>\n");
+        }
+        let _ = writeln!(html, "<pre class=\"fm-term\">{body}</pre>");
+        html
+    }
+}
+
+/// Target of an OSC 8 sequence opening a hyperlink: `ESC ] 8 ; params ; url ST`.
+fn link_target(sequence: &str) -> Option<&str> {
+    let (_, url) = sequence.strip_prefix("\x1b]8;")?.split_once(';')?;
+    let url = url.trim_end_matches("\x1b\\").trim_end_matches('\x07');
+    (!url.is_empty()).then_some(url)
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::ColorMode;
+    use crate::config::TermRenderConfig;
+    use crate::renderer::render_diagram_with_config;
+    use fm_core::{MermaidRenderMode, MermaidTier};
+
+    #[test]
+    fn colored_renders_export_as_styled_spans() {
+        let parsed = fm_parser::parse("flowchart LR\n  A[Start] --> B[End]");
+        let config = TermRenderConfig {
+            color_mode: ColorMode::TrueColor,
+            ..TermRenderConfig::compact()
+        };
+        let result = render_diagram_with_config(&parsed.ir, &config, 60, 12);
+
+        let inline = result.to_html();
+        assert!(inline.starts_with("<pre class=\"fm-term\">"), "{inline}");
+        assert!(!inline.contains('\x1b'));
+        assert!(
+            inline.contains("<span style=\"color:#6366f1\">"),
+            "{inline}"
+        );
+        assert!(inline.contains("Start") && inline.contains("End"));
+
+        let classed = result.to_html_with(HtmlStyleMode::Classes);
+        assert!(
+            classed.starts_with("<style>\n.fm-term-s0 { color:"),
+            "{classed}"
+        );
+        assert!(classed.contains("<span class=\"fm-term-s0\">"));
+        assert!(!classed.contains("style=\""));
+    }
+
+    #[test]
+    fn sgr_attributes_links_and_markup_are_translated() {
+        let result = TermRenderResult {
+            output: "\x1b[2m\x1b[38;5;196mab\x1b[0m <&>\n\x1b]8;;https://x.dev/?a=1&b=2\x1b\\link\x1b]8;;\x1b\\"
+                .to_string(),
+            width: 7,
+            height: 2,
+            tier: MermaidTier::Compact,
+            render_mode: MermaidRenderMode::CellOnly,
+            node_count: 0,
+            edge_count: 0,
+            summary_only: false,
+        };
+        assert_eq!(
+            result.to_html(),
+            "<pre class=\"fm-term\"><span style=\"color:#ff0000;opacity:0.5\">ab</span> &lt;&amp;&gt;\n\
+             <a href=\"https://x.dev/?a=1&amp;b=2\">link</a></pre>\n"
+        );
+    }
+}
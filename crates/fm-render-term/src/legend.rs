@@ -0,0 +1,311 @@
+//! Legend panel explaining the colors and line styles of a rendered diagram.
+//!
+//! The panel lists the `classDef` classes nodes use, with their color, and one sample of each
+//! kind of edge the diagram draws. Diff output gets the status markers instead. It is boxed and
+//! placed under or to the right of the diagram:
+//!
+//! ```text
+//! ┌ Legend ────────┐
+//! │ ■   critical   │
+//! │ ──▶ arrow      │
+//! │ ┄┄▶ dotted     │
+//! └────────────────┘
+//! ```
+
+use fm_core::{ArrowType, MermaidDiagramIr, MermaidGlyphMode};
+use serde::{Deserialize, Serialize};
+
+use crate::color::{RESET, TermColor, class_color, diagram_palette};
+use crate::glyphs::{BoxGlyphs, EdgeGlyphs, is_dotted};
+use crate::renderer::{TermRenderResult, TermRenderer};
+use crate::text::display_width;
+use crate::viewport::visible_width;
+
+/// Where the legend panel goes.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum LegendPosition {
+    /// No legend.
+    #[default]
+    Off,
+    /// Under the diagram, after a blank line.
+    Bottom,
+    /// Beside the diagram, two columns to its right.
+    Right,
+}
+
+/// One legend row: a sample glyph run, optionally colored, and what it means.
+pub(crate) struct LegendEntry {
+    pub(crate) sample: String,
+    pub(crate) sgr: Option<String>,
+    pub(crate) text: String,
+}
+
+/// Kinds of edge distinguished in the legend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EdgeStyle {
+    Arrow,
+    Line,
+    Dotted,
+    Thick,
+    Both,
+    Circle,
+    Cross,
+}
+
+impl EdgeStyle {
+    const fn of(arrow: ArrowType) -> Self {
+        match arrow {
+            ArrowType::Line | ArrowType::ThickLine => Self::Line,
+            ArrowType::DoubleArrow | ArrowType::DoubleThickArrow | ArrowType::DoubleDottedArrow => {
+                Self::Both
+            }
+            ArrowType::Circle => Self::Circle,
+            ArrowType::Cross | ArrowType::DottedCross => Self::Cross,
+            ArrowType::ThickArrow => Self::Thick,
+            other if is_dotted(other) => Self::Dotted,
+            _ => Self::Arrow,
+        }
+    }
+
+    fn sample(self, glyphs: &EdgeGlyphs) -> String {
+        let line = glyphs.line_h;
+        let chars = match self {
+            Self::Arrow => [line, line, glyphs.arrow_right],
+            Self::Line => [line, line, line],
+            Self::Dotted => [glyphs.dotted_h, glyphs.dotted_h, glyphs.arrow_right],
+            Self::Thick => [glyphs.thick_h, glyphs.thick_h, glyphs.arrow_thick_right],
+            Self::Both => [glyphs.arrow_left, line, glyphs.arrow_right],
+            Self::Circle => [line, line, glyphs.circle_head],
+            Self::Cross => [line, line, glyphs.cross_head],
+        };
+        chars.iter().collect()
+    }
+
+    const fn meaning(self) -> &'static str {
+        match self {
+            Self::Arrow => "arrow",
+            Self::Line => "link, no arrowhead",
+            Self::Dotted => "dotted",
+            Self::Thick => "thick",
+            Self::Both => "both directions",
+            Self::Circle => "circle end",
+            Self::Cross => "cross end",
+        }
+    }
+}
+
+impl TermRenderer {
+    /// Add the legend panel where the config asks for one.
+    pub(crate) fn append_legend(
+        &self,
+        ir: &MermaidDiagramIr,
+        result: TermRenderResult,
+    ) -> TermRenderResult {
+        if self.config.legend == LegendPosition::Off || result.summary_only {
+            return result;
+        }
+        let entries = self.legend_entries(ir);
+        if entries.is_empty() {
+            return result;
+        }
+        let panel = legend_panel(&entries, &self.box_glyphs);
+        let (output, width, height) =
+            place_legend(&result.output, result.width, &panel, self.config.legend);
+        TermRenderResult {
+            output,
+            width,
+            height: height.max(result.height),
+            ..result
+        }
+    }
+
+    fn legend_entries(&self, ir: &MermaidDiagramIr) -> Vec<LegendEntry> {
+        let palette = diagram_palette(ir, self.config.palette);
+        let sgr = |color: Option<TermColor>| {
+            color.and_then(|color| self.config.color_mode.foreground(color))
+        };
+        let swatch = match self.config.glyph_mode {
+            MermaidGlyphMode::Unicode => "■",
+            MermaidGlyphMode::Ascii => "#",
+        };
+
+        let mut entries: Vec<LegendEntry> = Vec::new();
+        for def in &ir.style_defs {
+            let used = ir.nodes.iter().any(|node| node.classes.contains(&def.name));
+            if used && !entries.iter().any(|entry| entry.text == def.name) {
+                entries.push(LegendEntry {
+                    sample: swatch.to_string(),
+                    sgr: sgr(class_color(ir, &def.name).or(palette.node)),
+                    text: def.name.clone(),
+                });
+            }
+        }
+
+        let mut styles: Vec<EdgeStyle> = Vec::new();
+        for edge in &ir.edges {
+            let style = EdgeStyle::of(edge.arrow);
+            if !styles.contains(&style) {
+                styles.push(style);
+            }
+        }
+        // A single plain style needs no explaining.
+        if styles != [EdgeStyle::Arrow] {
+            entries.extend(styles.into_iter().map(|style| LegendEntry {
+                sample: style.sample(&self.edge_glyphs),
+                sgr: sgr(palette.edge),
+                text: style.meaning().to_string(),
+            }));
+        }
+        entries
+    }
+}
+
+/// The boxed legend panel, one string per row. Samples with an SGR sequence are colored.
+pub(crate) fn legend_panel(entries: &[LegendEntry], glyphs: &BoxGlyphs) -> Vec<String> {
+    const TITLE: &str = "Legend";
+    let sample_width = entries
+        .iter()
+        .map(|entry| display_width(&entry.sample))
+        .max()
+        .unwrap_or(0);
+    let text_width = entries
+        .iter()
+        .map(|entry| display_width(&entry.text))
+        .max()
+        .unwrap_or(0);
+    let inner = (sample_width + 1 + text_width).max(TITLE.len() + 2);
+    let horizontal = |count: usize| glyphs.horizontal.to_string().repeat(count);
+
+    let mut rows = Vec::with_capacity(entries.len() + 2);
+    rows.push(format!(
+        "{} {TITLE} {}{}",
+        glyphs.top_left,
+        horizontal(inner - TITLE.len()),
+        glyphs.top_right
+    ));
+    for entry in entries {
+        let pad = sample_width - display_width(&entry.sample);
+        let sample = match &entry.sgr {
+            Some(sgr) => format!("{sgr}{}{RESET}", entry.sample),
+            None => entry.sample.clone(),
+        };
+        rows.push(format!(
+            "{v} {sample}{} {}{} {v}",
+            " ".repeat(pad),
+            entry.text,
+            " ".repeat(inner - sample_width - 1 - display_width(&entry.text)),
+            v = glyphs.vertical
+        ));
+    }
+    rows.push(format!(
+        "{}{}{}",
+        glyphs.bottom_left,
+        horizontal(inner + 2),
+        glyphs.bottom_right
+    ));
+    rows
+}
+
+/// `output` with `panel` placed at `position`, and the combined width and height.
+pub(crate) fn place_legend(
+    output: &str,
+    width: usize,
+    panel: &[String],
+    position: LegendPosition,
+) -> (String, usize, usize) {
+    let panel_width = panel.first().map_or(0, |row| visible_width(row));
+    let lines: Vec<&str> = output.split('\n').collect();
+    match position {
+        LegendPosition::Off => (output.to_string(), width, lines.len()),
+        LegendPosition::Bottom => {
+            let mut combined = output.trim_end_matches('\n').to_string();
+            combined.push_str("\n\n");
+            combined.push_str(&panel.join("\n"));
+            let height = combined.split('\n').count();
+            (combined, width.max(panel_width), height)
+        }
+        LegendPosition::Right => {
+            let diagram_width = lines
+                .iter()
+                .map(|line| visible_width(line))
+                .max()
+                .unwrap_or(0)
+                .max(width);
+            let height = lines.len().max(panel.len());
+            let rows: Vec<String> = (0..height)
+                .map(|row| {
+                    let line = lines.get(row).copied().unwrap_or_default();
+                    match panel.get(row) {
+                        Some(panel_row) => {
+                            let pad = diagram_width - visible_width(line) + 2;
+                            format!("{line}{}{panel_row}", " ".repeat(pad))
+                        }
+                        None => line.to_string(),
+                    }
+                })
+                .collect();
+            (rows.join("\n"), diagram_width + 2 + panel_width, height)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::ColorMode;
+    use crate::config::TermRenderConfig;
+    use crate::renderer::render_diagram_with_config;
+
+    const STYLED: &str = "flowchart LR
+  A[Start] --> B[Next] -.-> C[End]
+  class A hot
+  classDef hot stroke:#ff0000";
+
+    fn render(legend: LegendPosition, color_mode: ColorMode) -> TermRenderResult {
+        let parsed = fm_parser::parse(STYLED);
+        let config = TermRenderConfig {
+            legend,
+            color_mode,
+            ..TermRenderConfig::rich()
+        };
+        render_diagram_with_config(&parsed.ir, &config, 100, 30)
+    }
+
+    #[test]
+    fn bottom_legend_lists_classes_and_edge_styles() {
+        let result = render(LegendPosition::Bottom, ColorMode::None);
+        let tail: Vec<&str> = result.output.lines().rev().take(5).collect();
+        assert!(tail[0].starts_with('└'), "{}", result.output);
+        assert!(tail[1].contains("┄┄▶ dotted"), "{}", result.output);
+        assert!(tail[2].contains("──▶ arrow"), "{}", result.output);
+        assert!(tail[3].contains("■   hot"), "{}", result.output);
+        assert!(tail[4].starts_with("┌ Legend "), "{}", result.output);
+        assert_eq!(result.height, result.output.lines().count());
+    }
+
+    #[test]
+    fn right_legend_sits_beside_the_diagram_with_colored_swatches() {
+        let plain = render(LegendPosition::Off, ColorMode::None);
+        let result = render(LegendPosition::Right, ColorMode::TrueColor);
+        let first = result.output.lines().next().unwrap_or_default();
+        assert!(first.trim_end().ends_with('┐'), "{first:?}");
+        assert!(result.width > plain.width);
+        assert!(
+            result.output.contains("\x1b[38;2;255;0;0m■\x1b[0m"),
+            "{}",
+            result.output
+        );
+        assert!(!plain.output.contains("Legend"));
+    }
+
+    #[test]
+    fn plain_single_style_diagrams_get_no_legend() {
+        let parsed = fm_parser::parse("flowchart LR\n  A --> B");
+        let config = TermRenderConfig {
+            legend: LegendPosition::Bottom,
+            ..TermRenderConfig::rich()
+        };
+        let result = render_diagram_with_config(&parsed.ir, &config, 80, 24);
+        assert!(!result.output.contains("Legend"));
+    }
+}
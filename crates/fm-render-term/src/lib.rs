@@ -11,9 +11,20 @@
 //! - **Multi-tier fidelity**: Compact, Normal, and Rich rendering modes
 //! - **Sub-cell canvas modes**: Braille (2x4), Block (2x2), HalfBlock (1x2), and CellOnly
 //! - **Unicode and ASCII support**: Box-drawing characters with ASCII fallback
+//! - **ANSI color**: Optional 16-color, 256-color or truecolor output from the diagram palette
+//! - **Hyperlinks**: Node `href`s as OSC 8 terminal links or a footnote list
+//! - **Legend**: Optional panel explaining class colors, edge styles and diff markers
 //! - **Diagram diffing**: Visual comparison of two diagrams with status highlighting
 //! - **Minimap rendering**: Scaled overview with optional viewport indicator
-//! - **ASCII detection**: Detect and normalize ASCII art diagrams in text
+//! - **Inline images**: Kitty graphics, iTerm2 and Sixel output with fallback to cell rendering
+//! - **Viewport panning**: Windows into a natural-size canvas for diagrams too big to fit
+//! - **HTML export**: Colored output as a styled `<pre>` fragment for docs and PR comments
+//! - **Partial redraws**: Cell patches repainting only what changed between two renders
+//! - **Animated reveal**: Frames adding one rank or one edge at a time, for demos and streaming
+//! - **Ratatui widget**: `DiagramWidget` for TUI apps, behind the `ratatui` feature
+//! - **Capability detection**: Unicode, color depth, hyperlink and image support from the environment
+//! - **ASCII detection**: Detect and normalize ASCII art diagrams in text, and import
+//!   box-and-arrow art as a flowchart
 //!
 //! # Quick Start
 //!
@@ -37,39 +48,82 @@
 //! # Modules
 //!
 //! - [`canvas`]: Sub-cell pixel canvas for high-resolution terminal rendering
+//! - [`capabilities`]: Terminal capability detection feeding [`TermRenderConfig::auto`]
+//! - [`color`]: ANSI 16/256/truecolor output with palette- and style-derived element colors
 //! - [`config`]: Configuration types for rendering options
 //! - [`glyphs`]: Unicode and ASCII box-drawing character sets
-//! - [`renderer`]: Core diagram rendering logic
+//! - [`renderer`]: Core diagram rendering logic, with dedicated grids for sequence, ER, class, gantt, mindmap, git graph, timeline, journey and xy chart diagrams
 //! - [`diff`]: Diagram diffing and comparison
+//! - [`legend`]: Legend panel placed under or beside the diagram
 //! - [`minimap`]: Scaled overview rendering
-//! - [`ascii`]: ASCII diagram detection and normalization
+//! - [`pixels`]: Inline-image rendering for Kitty, iTerm2 and Sixel terminals
+//! - [`ascii`]: ASCII diagram detection, normalization and import
+//! - `widget`: Ratatui `DiagramWidget` with scroll, zoom and selection state (`ratatui` feature)
 
 #![forbid(unsafe_code)]
 
+mod animation;
 pub mod ascii;
 pub mod canvas;
+pub mod capabilities;
+mod class;
+pub mod color;
 pub mod config;
 pub mod diff;
+mod edge_labels;
+mod er;
+mod gantt;
+mod gitgraph;
 pub mod glyphs;
+mod grid;
+mod html;
+pub mod legend;
+mod links;
+mod mindmap;
 pub mod minimap;
+mod patch;
+pub mod pixels;
 pub mod renderer;
+mod sequence;
+mod text;
+mod timeline;
+mod viewport;
+#[cfg(feature = "ratatui")]
+pub mod widget;
+mod xychart;
 
 // Re-exports for convenient access.
-pub use config::{ResolvedConfig, TermRenderConfig};
+pub use animation::{RevealOrder, TermAnimation, render_term_animated};
+pub use capabilities::{AutoGlyphMode, TerminalCapabilities};
+pub use color::{ColorMode, TermColor, TermPalette};
+pub use config::{MAX_ZOOM, MIN_ZOOM, ResolvedConfig, TermRenderConfig};
 pub use diff::{
-    DiagramDiff, DiffEdge, DiffNode, DiffStatus, diff_diagrams, render_diff_plain,
+    DiagramDiff, DiffEdge, DiffNode, DiffStatus, diff_diagrams, render_diff_diagram,
+    render_diff_plain, render_diff_side_by_side, render_diff_side_by_side_with_config,
     render_diff_summary, render_diff_terminal, render_diff_terminal_with_config,
 };
-pub use glyphs::{BoxGlyphs, ClusterGlyphs, EdgeGlyphs, ShapeGlyphs};
+pub use glyphs::{
+    BoxGlyphs, CardinalityGlyphs, ClusterGlyphs, EdgeGlyphs, GanttGlyphs, GitGraphGlyphs,
+    RelationGlyphs, ShapeGlyphs, TimelineGlyphs, TreeGlyphs, XyChartGlyphs, icon_glyph,
+};
+pub use html::HtmlStyleMode;
+pub use legend::LegendPosition;
 pub use minimap::{
-    MinimapConfig, MinimapCorner, MinimapDensity, MinimapDetailLevel, MinimapRect, MinimapResult,
-    Viewport, minimap_cell_to_layout_point, render_minimap, render_minimap_ascii,
+    MinimapConfig, MinimapCorner, MinimapDensity, MinimapDetailLevel, MinimapMapping, MinimapRect,
+    MinimapResult, Viewport, minimap_cell_to_layout_point, render_minimap, render_minimap_ascii,
     render_minimap_colored, viewport_to_minimap_rect,
 };
+pub use patch::CellPatch;
+pub use pixels::{ImageProtocol, RgbaImage};
 pub use renderer::{
-    TermRenderResult, TermRenderer, render_diagram, render_diagram_with_config,
-    render_diagram_with_layout_and_config,
+    MIN_DIAGRAM_COLS, MIN_DIAGRAM_ROWS, TermRenderResult, TermRenderer, render_diagram,
+    render_diagram_with_config, render_diagram_with_layout_and_config,
+};
+pub use viewport::{
+    VirtualCanvas, render_term_viewport, render_term_viewport_with_layout, term_virtual_canvas,
 };
+#[cfg(feature = "ratatui")]
+pub use widget::{DiagramWidget, DiagramWidgetState};
 
 use fm_core::MermaidDiagramIr;
 use fm_layout::DiagramLayout;
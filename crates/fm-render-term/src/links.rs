@@ -0,0 +1,261 @@
+//! Node hyperlinks in terminal output.
+//!
+//! [`MermaidLinkMode::Inline`] wraps each linked node's label in OSC 8 escape sequences, which
+//! terminals that support them turn into clickable links and others ignore.
+//! [`MermaidLinkMode::Footnote`] leaves the diagram alone and lists the links under it instead:
+//!
+//! ```text
+//! [1] Docs: https://example.com/docs
+//! [2] Home: https://example.com
+//! ```
+
+use fm_core::{IrNode, MermaidDiagramIr, MermaidLinkMode, is_safe_link_target};
+use fm_layout::DiagramLayout;
+
+use crate::renderer::{TermRenderResult, TermRenderer};
+use crate::text::{WIDE_TAIL, char_width, display_width};
+
+/// A row's columns `start..end` linking to `target`.
+struct LinkSpan<'a> {
+    row: usize,
+    start: usize,
+    end: usize,
+    target: &'a str,
+}
+
+/// `href` of a node when it is safe to hand to the terminal: allowed by the diagram's sanitize
+/// mode and free of control characters that could end the escape sequence early.
+fn node_link<'a>(ir: &MermaidDiagramIr, node: &'a IrNode) -> Option<&'a str> {
+    node.href().filter(|href| {
+        is_safe_link_target(href, ir.meta.init.config.sanitize_mode)
+            && !href.chars().any(char::is_control)
+    })
+}
+
+impl TermRenderer {
+    /// Wrap the labels of linked nodes in a generic cell grid with OSC 8 hyperlinks. Leaves
+    /// `output` untouched unless the link mode is `Inline`.
+    pub(crate) fn link_nodes(
+        &self,
+        ir: &MermaidDiagramIr,
+        layout: &DiagramLayout,
+        output: String,
+        (scale_x, scale_y): (f32, f32),
+    ) -> String {
+        if self.config.link_mode != MermaidLinkMode::Inline {
+            return output;
+        }
+        let mut spans = Vec::new();
+        for node_box in &layout.nodes {
+            let Some(target) = ir
+                .nodes
+                .get(node_box.node_index)
+                .and_then(|node| node_link(ir, node))
+            else {
+                continue;
+            };
+            let (x, y, w, h) = self.bounds_to_cells(&node_box.bounds, scale_x, scale_y);
+            // Inside the border when there is one; labels of short boxes may sit on it.
+            let (rows, start, end) = if h > 2 {
+                (y + 1..y + h - 1, x + 1, x + w - 1)
+            } else {
+                (y..y + h, x, x + w)
+            };
+            spans.extend(rows.map(|row| LinkSpan {
+                row,
+                start,
+                end,
+                target,
+            }));
+        }
+        if spans.is_empty() {
+            return output;
+        }
+
+        let mut linked = String::with_capacity(output.len() * 2);
+        for (row, line) in output.split('\n').enumerate() {
+            if row > 0 {
+                linked.push('\n');
+            }
+            hyperlink_line(
+                &mut linked,
+                line,
+                spans.iter().filter(|span| span.row == row),
+            );
+        }
+        linked
+    }
+
+    /// Append a numbered list of node links under the diagram when the link mode is `Footnote`.
+    pub(crate) fn append_link_footnotes(
+        &self,
+        ir: &MermaidDiagramIr,
+        mut result: TermRenderResult,
+    ) -> TermRenderResult {
+        if self.config.link_mode != MermaidLinkMode::Footnote || result.summary_only {
+            return result;
+        }
+        let notes: Vec<String> = ir
+            .nodes
+            .iter()
+            .filter_map(|node| {
+                let target = node_link(ir, node)?;
+                let label = self
+                    .node_display_label(ir, Some(node), &node.id)?
+                    .replace('\n', " ");
+                Some(format!("{label}: {target}"))
+            })
+            .enumerate()
+            .map(|(index, note)| format!("[{}] {note}", index + 1))
+            .collect();
+        if notes.is_empty() {
+            return result;
+        }
+
+        result.output.push_str("\n\n");
+        result.output.push_str(&notes.join("\n"));
+        result.height += notes.len() + 1;
+        result.width = result.width.max(
+            notes
+                .iter()
+                .map(|note| display_width(note))
+                .max()
+                .unwrap_or(0),
+        );
+        result
+    }
+}
+
+/// Copy `line` into `out`, opening a hyperlink before the first and closing it after the last
+/// non-blank column of each span. Columns count visible characters, so SGR color sequences
+/// already in the line are passed through without shifting them.
+fn hyperlink_line<'a>(out: &mut String, line: &str, spans: impl Iterator<Item = &'a LinkSpan<'a>>) {
+    // One entry per column; the right half of a double-width character counts as non-blank.
+    let visible: Vec<char> = strip_csi(line)
+        .flat_map(|ch| match char_width(ch) {
+            0 => [None, None],
+            1 => [Some(ch), None],
+            _ => [Some(ch), Some(WIDE_TAIL)],
+        })
+        .flatten()
+        .collect();
+    let blank = |ch: char| ch.is_whitespace() || ch == '\u{2800}';
+    let mut opens: Vec<(usize, usize, &str)> = spans
+        .filter_map(|span| {
+            let end = span.end.min(visible.len());
+            let first = (span.start..end).find(|&col| !blank(visible[col]))?;
+            let last = (first..end).rfind(|&col| !blank(visible[col]))?;
+            Some((first, last, span.target))
+        })
+        .collect();
+    opens.sort_by_key(|&(first, _, _)| first);
+
+    let mut chars = line.chars().peekable();
+    let mut col = 0;
+    let mut open: Option<usize> = None;
+    while let Some(ch) = chars.next() {
+        if ch == '\x1b' {
+            out.push(ch);
+            if chars.peek() == Some(&'[') {
+                for next in chars.by_ref() {
+                    out.push(next);
+                    if ('@'..='~').contains(&next) && next != '[' {
+                        break;
+                    }
+                }
+            }
+            continue;
+        }
+        let width = char_width(ch);
+        if width == 0 {
+            out.push(ch);
+            continue;
+        }
+        if open.is_none()
+            && let Some(&(_, last, target)) = opens.iter().find(|&&(first, _, _)| first == col)
+        {
+            out.push_str(&format!("\x1b]8;;{target}\x1b\\"));
+            open = Some(last);
+        }
+        out.push(ch);
+        if open.is_some_and(|last| last < col + width) {
+            out.push_str("\x1b]8;;\x1b\\");
+            open = None;
+        }
+        col += width;
+    }
+    if open.is_some() {
+        out.push_str("\x1b]8;;\x1b\\");
+    }
+}
+
+/// Visible characters of a line, skipping SGR and other CSI escape sequences.
+fn strip_csi(line: &str) -> impl Iterator<Item = char> + '_ {
+    let mut chars = line.chars().peekable();
+    std::iter::from_fn(move || {
+        loop {
+            let ch = chars.next()?;
+            if ch != '\x1b' {
+                return Some(ch);
+            }
+            if chars.peek() == Some(&'[') {
+                chars.next();
+                for next in chars.by_ref() {
+                    if ('@'..='~').contains(&next) {
+                        break;
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::TermRenderConfig;
+    use crate::renderer::render_diagram_with_config;
+    use fm_core::{MermaidLinkMode, MermaidRenderMode};
+
+    const LINKED: &str =
+        "flowchart LR\n  A[Docs] --> B[Home]\n  click A href \"https://example.com/docs\"";
+
+    fn render(link_mode: MermaidLinkMode, render_mode: MermaidRenderMode) -> String {
+        let parsed = fm_parser::parse(LINKED);
+        let config = TermRenderConfig {
+            link_mode,
+            render_mode,
+            ..TermRenderConfig::rich()
+        };
+        render_diagram_with_config(&parsed.ir, &config, 80, 24).output
+    }
+
+    #[test]
+    fn inline_mode_wraps_linked_labels_in_osc8() {
+        for render_mode in [MermaidRenderMode::CellOnly, MermaidRenderMode::Braille] {
+            let output = render(MermaidLinkMode::Inline, render_mode);
+            assert!(
+                output.contains("\x1b]8;;https://example.com/docs\x1b\\Docs\x1b]8;;\x1b\\"),
+                "{output}"
+            );
+            assert!(!output.contains("\x1b]8;;\x1b\\Home"), "{output}");
+        }
+    }
+
+    #[test]
+    fn footnote_mode_lists_links_under_the_diagram() {
+        let output = render(MermaidLinkMode::Footnote, MermaidRenderMode::Braille);
+        assert!(!output.contains('\x1b'), "{output}");
+        assert_eq!(
+            output.lines().last(),
+            Some("[1] Docs: https://example.com/docs")
+        );
+    }
+
+    #[test]
+    fn links_are_off_by_default() {
+        let parsed = fm_parser::parse(LINKED);
+        let output =
+            render_diagram_with_config(&parsed.ir, &TermRenderConfig::rich(), 80, 24).output;
+        assert!(!output.contains("example.com"), "{output}");
+    }
+}
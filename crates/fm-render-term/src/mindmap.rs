@@ -0,0 +1,379 @@
+//! Dedicated terminal renderer for mindmaps.
+//!
+//! With room to spare, the radial layout is drawn as labels joined by straight branch lines
+//! around a boxed root. When the tier is Compact, or the viewport is too small to keep the
+//! labels apart at the radial positions, the map falls back to an indented tree like `tree(1)`:
+//!
+//! ```text
+//! Project
+//! ├── Goals
+//! │   ├── Speed
+//! │   └── Safety
+//! └── Risks
+//! ```
+
+use fm_core::{MermaidDiagramIr, MermaidTier};
+use fm_layout::DiagramLayout;
+
+use crate::glyphs::TreeGlyphs;
+use crate::renderer::{GridCanvas, TermRenderResult, TermRenderer, text_width};
+
+/// Largest layout-to-cell scale, so small maps are not spread across the whole terminal.
+const MAX_SCALE: f32 = 0.25;
+
+/// Where a label lands on the radial canvas: its centre and the cells it covers.
+struct Spot {
+    node: usize,
+    x: usize,
+    y: usize,
+    left: usize,
+    right: usize,
+    top: usize,
+    bottom: usize,
+}
+
+/// Children of each node, taken from the map's edges in source order, and the nodes without a
+/// parent. A node keeps its first parent only, so every walk from a root terminates.
+fn branches(ir: &MermaidDiagramIr) -> (Vec<Vec<usize>>, Vec<usize>) {
+    let mut children = vec![Vec::new(); ir.nodes.len()];
+    let mut has_parent = vec![false; ir.nodes.len()];
+    for edge in &ir.edges {
+        let (Some(from), Some(to)) = (
+            edge.from.resolved_node_id(&ir.ports),
+            edge.to.resolved_node_id(&ir.ports),
+        ) else {
+            continue;
+        };
+        if from != to && from.0 < children.len() && to.0 < children.len() && !has_parent[to.0] {
+            children[from.0].push(to.0);
+            has_parent[to.0] = true;
+        }
+    }
+    let roots = (0..ir.nodes.len())
+        .filter(|&node| !has_parent[node])
+        .collect();
+    (children, roots)
+}
+
+impl TermRenderer {
+    /// Draw a mindmap radially or as an indented tree, whichever the tier and viewport allow.
+    /// `None` when the map has no nodes.
+    pub(crate) fn render_mindmap(
+        &self,
+        ir: &MermaidDiagramIr,
+        layout: &DiagramLayout,
+    ) -> Option<TermRenderResult> {
+        if ir.nodes.is_empty() {
+            return None;
+        }
+        let labels: Vec<String> = ir
+            .nodes
+            .iter()
+            .map(|node| {
+                let text = node
+                    .label
+                    .and_then(|label_id| ir.labels.get(label_id.0))
+                    .map_or(node.id.as_str(), |label| label.text.as_str());
+                self.cell_text(text)
+            })
+            .collect();
+        let (children, roots) = branches(ir);
+        let title = ir.meta.title.as_deref().map(|title| self.cell_text(title));
+
+        let radial = if self.config.tier == MermaidTier::Compact {
+            None
+        } else {
+            self.render_mindmap_radial(ir, layout, &labels, &children, &roots, title.as_deref())
+        };
+        Some(radial.unwrap_or_else(|| {
+            self.render_mindmap_tree(layout, &labels, &children, &roots, title.as_deref())
+        }))
+    }
+
+    /// Labels at the radial layout's positions, scaled into the viewport. `None` when two labels
+    /// would touch or one would fall off the edge.
+    fn render_mindmap_radial(
+        &self,
+        ir: &MermaidDiagramIr,
+        layout: &DiagramLayout,
+        labels: &[String],
+        children: &[Vec<usize>],
+        roots: &[usize],
+        title: Option<&str>,
+    ) -> Option<TermRenderResult> {
+        let padding = self.config.padding;
+        let top = if title.is_some() { 2 } else { 0 };
+        let width = self.config.cols.saturating_sub(padding * 2);
+        let height = self.config.rows.saturating_sub(padding * 2 + top);
+        let bounds = layout.bounds;
+        if bounds.width <= 0.0 || bounds.height <= 0.0 || width < 2 || height < 2 {
+            return None;
+        }
+        // Cells are about twice as tall as they are wide, so rows get half the column scale.
+        let scale = ((width - 1) as f32 / bounds.width)
+            .min(2.0 * (height - 1) as f32 / bounds.height)
+            .min(MAX_SCALE);
+
+        let mut spots: Vec<Spot> = Vec::with_capacity(layout.nodes.len());
+        let mut placed = vec![None; ir.nodes.len()];
+        for node_box in &layout.nodes {
+            let Some(label) = labels.get(node_box.node_index) else {
+                continue;
+            };
+            let centre = node_box.bounds.center();
+            let x = ((centre.x - bounds.x) * scale).round() as usize;
+            let y = top + ((centre.y - bounds.y) * scale / 2.0).round() as usize;
+            let len = label.chars().count();
+            // The root is boxed: one cell of border and one of space on each side.
+            let (half_width, half_height, span) = if roots.contains(&node_box.node_index) {
+                (len / 2 + 2, 1, len + 4)
+            } else {
+                (len / 2, 0, len)
+            };
+            let left = x.checked_sub(half_width)?;
+            let spot_top = y.checked_sub(half_height)?;
+            let spot = Spot {
+                node: node_box.node_index,
+                x,
+                y,
+                left,
+                right: left + span,
+                top: spot_top,
+                bottom: y + half_height + 1,
+            };
+            if spot.right > width || spot.bottom > top + height || spot.top < top {
+                return None;
+            }
+            placed[spot.node] = Some(spots.len());
+            spots.push(spot);
+        }
+        // Labels need a blank cell between them to stay readable.
+        for (index, a) in spots.iter().enumerate() {
+            for b in &spots[index + 1..] {
+                if a.left <= b.right && b.left <= a.right && a.top < b.bottom && b.top < a.bottom {
+                    return None;
+                }
+            }
+        }
+
+        let used_width = spots
+            .iter()
+            .map(|spot| spot.right)
+            .max()
+            .unwrap_or(0)
+            .max(title.map_or(0, |title| title.chars().count()));
+        let used_height = spots.iter().map(|spot| spot.bottom).max().unwrap_or(top);
+        let out_width = (used_width + padding * 2).min(self.config.cols);
+        let out_height = (used_height + padding * 2).min(self.config.rows);
+        let mut canvas = GridCanvas::new(out_width, out_height, padding);
+        let edges = &self.edge_glyphs;
+
+        for (parent, kids) in children.iter().enumerate() {
+            let Some(from) = placed[parent].map(|index| &spots[index]) else {
+                continue;
+            };
+            for &child in kids {
+                let Some(to) = placed[child].map(|index| &spots[index]) else {
+                    continue;
+                };
+                let dx = to.x as isize - from.x as isize;
+                let dy = to.y as isize - from.y as isize;
+                // Slope as seen on screen, where a row is twice as tall as a column is wide:
+                // steeper than 2.5 reads as vertical, flatter than 0.4 as horizontal.
+                let glyph = if 4 * dy.abs() > 5 * dx.abs() {
+                    edges.line_v
+                } else if 5 * dy.abs() < dx.abs() {
+                    edges.line_h
+                } else if (dx > 0) == (dy > 0) {
+                    edges.line_diag_nw
+                } else {
+                    edges.line_diag_ne
+                };
+                let steps = dx.abs().max(dy.abs());
+                for step in 0..=steps {
+                    let x = from.x as isize + (dx * step) / steps.max(1);
+                    let y = from.y as isize + (dy * step) / steps.max(1);
+                    canvas.set(x as usize, y as usize, glyph);
+                }
+            }
+        }
+
+        for spot in &spots {
+            let label = &labels[spot.node];
+            if spot.bottom - spot.top > 1 {
+                self.draw_box(
+                    &mut canvas,
+                    spot.left,
+                    spot.top,
+                    spot.right - spot.left,
+                    spot.bottom - spot.top,
+                );
+                canvas.text(spot.left + 2, spot.y, label, spot.right);
+            } else {
+                canvas.text(spot.left, spot.y, label, spot.right);
+            }
+        }
+        if let Some(title) = title {
+            let x = used_width.saturating_sub(title.chars().count()) / 2;
+            canvas.text(x, 0, title, used_width);
+        }
+
+        Some(TermRenderResult {
+            output: canvas.buffer.to_output_string(),
+            width: out_width,
+            height: out_height,
+            tier: self.config.tier,
+            render_mode: self.config.render_mode,
+            node_count: layout.nodes.len(),
+            edge_count: layout.edges.len(),
+            summary_only: false,
+        })
+    }
+
+    /// Every root followed by its descendants, one per line, under `tree(1)`-style prefixes.
+    fn render_mindmap_tree(
+        &self,
+        layout: &DiagramLayout,
+        labels: &[String],
+        children: &[Vec<usize>],
+        roots: &[usize],
+        title: Option<&str>,
+    ) -> TermRenderResult {
+        let glyphs = TreeGlyphs::for_mode(self.config.glyph_mode);
+        let mut lines = Vec::new();
+        if let Some(title) = title {
+            lines.push(title.to_owned());
+            lines.push(String::new());
+        }
+        for &root in roots {
+            lines.push(labels[root].clone());
+            // (node, prefix inherited from its ancestors, whether it is the last sibling)
+            let mut stack: Vec<(usize, String, bool)> = children[root]
+                .iter()
+                .rev()
+                .enumerate()
+                .map(|(index, &child)| (child, String::new(), index == 0))
+                .collect();
+            while let Some((node, prefix, last)) = stack.pop() {
+                let (lead, under) = if last {
+                    (glyphs.last, glyphs.blank)
+                } else {
+                    (glyphs.branch, glyphs.pipe)
+                };
+                lines.push(format!("{prefix}{lead}{}", labels[node]));
+                let prefix = format!("{prefix}{under}");
+                for (index, &child) in children[node].iter().rev().enumerate() {
+                    stack.push((child, prefix.clone(), index == 0));
+                }
+            }
+        }
+
+        let padding = self.config.padding;
+        let out_width = (text_width(&lines) + padding * 2).min(self.config.cols);
+        let out_height = (lines.len() + padding * 2).min(self.config.rows);
+        let mut canvas = GridCanvas::new(out_width, out_height, padding);
+        for (y, line) in lines.iter().enumerate() {
+            canvas.text(0, y, line, usize::MAX);
+        }
+
+        TermRenderResult {
+            output: canvas.buffer.to_output_string(),
+            width: out_width,
+            height: out_height,
+            tier: self.config.tier,
+            render_mode: self.config.render_mode,
+            node_count: layout.nodes.len(),
+            edge_count: layout.edges.len(),
+            summary_only: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::TermRenderConfig;
+    use crate::renderer::render_diagram_with_config;
+    use fm_core::MermaidGlyphMode;
+
+    const PROJECT: &str =
+        "mindmap\n  root((Project))\n    Goals\n      Speed\n      Safety\n    Risks";
+
+    fn render(source: &str, config: &TermRenderConfig, cols: usize, rows: usize) -> String {
+        let parsed = fm_parser::parse(source);
+        render_diagram_with_config(&parsed.ir, config, cols, rows).output
+    }
+
+    #[test]
+    fn compact_tier_draws_an_indented_tree() {
+        let output = render(PROJECT, &TermRenderConfig::compact(), 80, 24);
+
+        let lines: Vec<&str> = output.lines().map(str::trim_end).collect();
+        let start = lines
+            .iter()
+            .position(|line| line.trim_start() == "Project")
+            .unwrap_or_else(|| panic!("root missing from\n{output}"));
+        let indent = lines[start].len() - "Project".len();
+        let tree: Vec<&str> = lines[start..=start + 4]
+            .iter()
+            .map(|line| &line[indent..])
+            .collect();
+        assert_eq!(
+            tree,
+            [
+                "Project",
+                "├── Goals",
+                "│   ├── Speed",
+                "│   └── Safety",
+                "└── Risks"
+            ],
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn roomy_viewport_draws_the_radial_layout() {
+        let output = render(PROJECT, &TermRenderConfig::rich(), 160, 60);
+
+        for label in ["Project", "Goals", "Speed", "Safety", "Risks"] {
+            assert!(output.contains(label), "{output}");
+        }
+        // The root is boxed and no tree prefixes appear.
+        let root = output
+            .lines()
+            .find(|line| line.contains("Project"))
+            .expect("root row");
+        assert!(root.contains("│ Project │"), "{output}");
+        assert!(
+            !output.contains("├── ") && !output.contains("└── "),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn cramped_viewport_falls_back_to_the_tree() {
+        let source = format!(
+            "mindmap\n  root((Project))\n{}",
+            (0..12)
+                .map(|index| format!("    Branch number {index}\n"))
+                .collect::<String>()
+        );
+        let output = render(&source, &TermRenderConfig::rich(), 40, 20);
+
+        assert!(output.contains("├── Branch number 0"), "{output}");
+        assert!(output.contains("└── Branch number 11"), "{output}");
+    }
+
+    #[test]
+    fn ascii_tree_uses_ascii_prefixes() {
+        let config = TermRenderConfig {
+            glyph_mode: MermaidGlyphMode::Ascii,
+            ..TermRenderConfig::compact()
+        };
+        let output = render(PROJECT, &config, 80, 24);
+
+        assert!(output.is_ascii(), "{output}");
+        assert!(output.contains("|-- Goals"), "{output}");
+        assert!(output.contains("|   `-- Safety"), "{output}");
+        assert!(output.contains("`-- Risks"), "{output}");
+    }
+}
@@ -2,6 +2,8 @@
 //!
 //! Provides a scaled-down representation of the diagram with optional viewport indicator.
 
+use std::collections::BTreeSet;
+
 use fm_core::{MermaidDiagramIr, MermaidGlyphMode, MermaidRenderMode};
 use fm_layout::{DiagramLayout, layout_diagram};
 
@@ -38,6 +40,12 @@ pub struct MinimapConfig {
     pub use_color: bool,
     /// Detail level selection strategy.
     pub detail_level: MinimapDetailLevel,
+    /// Mouse position in minimap output cells (border included). The node under it is drawn
+    /// highlighted and reported in [`MinimapResult::hovered_node`].
+    pub cursor: Option<(usize, usize)>,
+    /// Shade cells by how many nodes and edges fall into them instead of drawing shapes. Meant
+    /// for diagrams so large that single nodes are smaller than a cell.
+    pub heatmap: bool,
 }
 
 impl Default for MinimapConfig {
@@ -52,6 +60,8 @@ impl Default for MinimapConfig {
             glyph_mode: MermaidGlyphMode::Unicode,
             use_color: false,
             detail_level: MinimapDetailLevel::Auto,
+            cursor: None,
+            heatmap: false,
         }
     }
 }
@@ -138,6 +148,52 @@ pub struct MinimapRect {
     pub height: usize,
 }
 
+/// How cells of a rendered minimap map back onto the diagram.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinimapMapping {
+    /// Output cells the diagram is drawn into: the whole minimap, less its border.
+    pub content: MinimapRect,
+    /// Layout-space area those cells cover.
+    pub world: fm_layout::LayoutRect,
+}
+
+impl MinimapMapping {
+    /// Layout-space point at the center of output cell (`cell_x`, `cell_y`), or `None` when the
+    /// cell is on the border or outside the minimap.
+    #[must_use]
+    pub fn cell_to_world(&self, cell_x: usize, cell_y: usize) -> Option<(f32, f32)> {
+        let col = cell_x.checked_sub(self.content.x)?;
+        let row = cell_y.checked_sub(self.content.y)?;
+        if col >= self.content.width || row >= self.content.height {
+            return None;
+        }
+        Some((
+            self.world.x + self.world.width * (col as f32 + 0.5) / self.content.width as f32,
+            self.world.y + self.world.height * (row as f32 + 0.5) / self.content.height as f32,
+        ))
+    }
+
+    /// `current` moved so that it is centered on the clicked cell, kept inside the diagram.
+    /// `None` when the cell is not part of the minimap content.
+    #[must_use]
+    pub fn jump_viewport(
+        &self,
+        cell_x: usize,
+        cell_y: usize,
+        current: &Viewport,
+    ) -> Option<Viewport> {
+        let (x, y) = self.cell_to_world(cell_x, cell_y)?;
+        let current = current.normalized();
+        let center_x = (x - self.world.x) / self.world.width.max(1.0);
+        let center_y = (y - self.world.y) / self.world.height.max(1.0);
+        Some(Viewport {
+            x: (center_x - current.width / 2.0).clamp(0.0, 1.0 - current.width),
+            y: (center_y - current.height / 2.0).clamp(0.0, 1.0 - current.height),
+            ..current
+        })
+    }
+}
+
 /// Result of minimap rendering.
 #[derive(Debug, Clone)]
 pub struct MinimapResult {
@@ -153,6 +209,19 @@ pub struct MinimapResult {
     pub detail_level: MinimapDetailLevel,
     /// Density classification used during render.
     pub density: MinimapDensity,
+    /// Maps output cells back to layout coordinates, e.g. to turn a click into a viewport jump.
+    pub mapping: MinimapMapping,
+    /// IR index of the node under [`MinimapConfig::cursor`], if any.
+    pub hovered_node: Option<usize>,
+}
+
+impl MinimapResult {
+    /// Layout-space point under output cell (`cell_x`, `cell_y`). See
+    /// [`MinimapMapping::cell_to_world`].
+    #[must_use]
+    pub fn cell_to_world(&self, cell_x: usize, cell_y: usize) -> Option<(f32, f32)> {
+        self.mapping.cell_to_world(cell_x, cell_y)
+    }
 }
 
 /// Render a minimap of the diagram.
@@ -210,6 +279,16 @@ pub fn render_minimap_from_layout(
             scale: 1.0,
             detail_level: config.detail_level,
             density: MinimapDensity::Sparse,
+            mapping: MinimapMapping {
+                content: MinimapRect {
+                    x: 0,
+                    y: 0,
+                    width: 0,
+                    height: 0,
+                },
+                world: layout.bounds,
+            },
+            hovered_node: None,
         };
     }
 
@@ -243,63 +322,77 @@ pub fn render_minimap_from_layout(
     let density = classify_density(layout, pixel_width, pixel_height);
     let detail_level = resolve_detail_level(config.detail_level, density);
 
-    // Create canvas.
-    let mut canvas = Canvas::new(cell_width, cell_height, config.render_mode);
-
-    // Offset to center diagram in canvas (reserved for future use).
-    let _offset_x = (layout.bounds.x * scale_x) as isize;
-    let _offset_y = (layout.bounds.y * scale_y) as isize;
-
-    // Draw nodes as dots or small rectangles.
-    for node_box in &layout.nodes {
-        let x = ((node_box.bounds.x - layout.bounds.x) * scale_x) as usize;
-        let y = ((node_box.bounds.y - layout.bounds.y) * scale_y) as usize;
-        let w = ((node_box.bounds.width * scale_x) as usize).max(1);
-        let h = ((node_box.bounds.height * scale_y) as usize).max(1);
-
-        let draw_as_dot = matches!(detail_level, MinimapDetailLevel::Sparse) || (w <= 2 && h <= 2);
-        if draw_as_dot {
-            canvas.set_pixel(x, y);
-        } else {
-            canvas.fill_rect(x, y, w, h);
+    let base_output = if config.heatmap {
+        render_heatmap(
+            layout,
+            config,
+            viewport,
+            (cell_width, cell_height),
+            (scale_x, scale_y),
+            (mult_x, mult_y),
+        )
+    } else {
+        // Create canvas.
+        let mut canvas = Canvas::new(cell_width, cell_height, config.render_mode);
+
+        // Offset to center diagram in canvas (reserved for future use).
+        let _offset_x = (layout.bounds.x * scale_x) as isize;
+        let _offset_y = (layout.bounds.y * scale_y) as isize;
+
+        // Draw nodes as dots or small rectangles.
+        for node_box in &layout.nodes {
+            let x = ((node_box.bounds.x - layout.bounds.x) * scale_x) as usize;
+            let y = ((node_box.bounds.y - layout.bounds.y) * scale_y) as usize;
+            let w = ((node_box.bounds.width * scale_x) as usize).max(1);
+            let h = ((node_box.bounds.height * scale_y) as usize).max(1);
+
+            let draw_as_dot =
+                matches!(detail_level, MinimapDetailLevel::Sparse) || (w <= 2 && h <= 2);
+            if draw_as_dot {
+                canvas.set_pixel(x, y);
+            } else {
+                canvas.fill_rect(x, y, w, h);
+            }
         }
-    }
 
-    // Draw edges as lines.
-    for edge_path in &layout.edges {
-        if matches!(detail_level, MinimapDetailLevel::Sparse) && edge_path.points.len() > 2 {
-            if let (Some(first), Some(last)) = (edge_path.points.first(), edge_path.points.last()) {
-                let x0 = ((first.x - layout.bounds.x) * scale_x) as isize;
-                let y0 = ((first.y - layout.bounds.y) * scale_y) as isize;
-                let x1 = ((last.x - layout.bounds.x) * scale_x) as isize;
-                let y1 = ((last.y - layout.bounds.y) * scale_y) as isize;
+        // Draw edges as lines.
+        for edge_path in &layout.edges {
+            if matches!(detail_level, MinimapDetailLevel::Sparse) && edge_path.points.len() > 2 {
+                if let (Some(first), Some(last)) =
+                    (edge_path.points.first(), edge_path.points.last())
+                {
+                    let x0 = ((first.x - layout.bounds.x) * scale_x) as isize;
+                    let y0 = ((first.y - layout.bounds.y) * scale_y) as isize;
+                    let x1 = ((last.x - layout.bounds.x) * scale_x) as isize;
+                    let y1 = ((last.y - layout.bounds.y) * scale_y) as isize;
+                    canvas.draw_line(x0, y0, x1, y1);
+                }
+                continue;
+            }
+            for window in edge_path.points.windows(2) {
+                let x0 = ((window[0].x - layout.bounds.x) * scale_x) as isize;
+                let y0 = ((window[0].y - layout.bounds.y) * scale_y) as isize;
+                let x1 = ((window[1].x - layout.bounds.x) * scale_x) as isize;
+                let y1 = ((window[1].y - layout.bounds.y) * scale_y) as isize;
                 canvas.draw_line(x0, y0, x1, y1);
             }
-            continue;
         }
-        for window in edge_path.points.windows(2) {
-            let x0 = ((window[0].x - layout.bounds.x) * scale_x) as isize;
-            let y0 = ((window[0].y - layout.bounds.y) * scale_y) as isize;
-            let x1 = ((window[1].x - layout.bounds.x) * scale_x) as isize;
-            let y1 = ((window[1].y - layout.bounds.y) * scale_y) as isize;
-            canvas.draw_line(x0, y0, x1, y1);
-        }
-    }
 
-    // Draw viewport rectangle if enabled.
-    if config.show_viewport
-        && let Some(vp) = viewport
-    {
-        let vp = vp.normalized();
-        let vp_x = (vp.x * pixel_width as f32) as usize;
-        let vp_y = (vp.y * pixel_height as f32) as usize;
-        let vp_w = (vp.width * pixel_width as f32) as usize;
-        let vp_h = (vp.height * pixel_height as f32) as usize;
-        canvas.draw_rect(vp_x, vp_y, vp_w.max(1), vp_h.max(1));
-    }
+        // Draw viewport rectangle if enabled.
+        if config.show_viewport
+            && let Some(vp) = viewport
+        {
+            let vp = vp.normalized();
+            let vp_x = (vp.x * pixel_width as f32) as usize;
+            let vp_y = (vp.y * pixel_height as f32) as usize;
+            let vp_w = (vp.width * pixel_width as f32) as usize;
+            let vp_h = (vp.height * pixel_height as f32) as usize;
+            canvas.draw_rect(vp_x, vp_y, vp_w.max(1), vp_h.max(1));
+        }
 
-    // Render canvas to string.
-    let base_output = canvas.render();
+        // Render canvas to string.
+        canvas.render()
+    };
 
     // Add border if configured.
     let output = if config.show_border {
@@ -307,6 +400,45 @@ pub fn render_minimap_from_layout(
     } else {
         base_output
     };
+
+    let border = usize::from(config.show_border);
+    let mapping = MinimapMapping {
+        content: MinimapRect {
+            x: border,
+            y: border,
+            width: cell_width,
+            height: cell_height,
+        },
+        world: layout.bounds,
+    };
+
+    // Highlight the node under the cursor, cell by cell.
+    let hovered = config.cursor.and_then(|(cursor_x, cursor_y)| {
+        let col = cursor_x.checked_sub(border)?;
+        let row = cursor_y.checked_sub(border)?;
+        layout.nodes.iter().find_map(|node_box| {
+            let ((left, right), (top, bottom)) = bounds_to_cell_range(
+                layout,
+                &node_box.bounds,
+                (cell_width, cell_height),
+                (scale_x, scale_y),
+                (mult_x, mult_y),
+            );
+            let cells = MinimapRect {
+                x: left + border,
+                y: top + border,
+                width: right - left + 1,
+                height: bottom - top + 1,
+            };
+            ((left..=right).contains(&col) && (top..=bottom).contains(&row))
+                .then_some((node_box.node_index, cells))
+        })
+    });
+    let output = match hovered {
+        Some((_, cells)) => highlight_cells(&output, cells, config),
+        None => output,
+    };
+
     let output = if config.use_color {
         colorize_output(&output)
     } else {
@@ -328,7 +460,182 @@ pub fn render_minimap_from_layout(
         scale,
         detail_level,
         density,
+        mapping,
+        hovered_node: hovered.map(|(node_index, _)| node_index),
+    }
+}
+
+/// Inclusive column and row ranges of content cells that `bounds` covers, clamped to the minimap.
+fn bounds_to_cell_range(
+    layout: &DiagramLayout,
+    bounds: &fm_layout::LayoutRect,
+    (cell_width, cell_height): (usize, usize),
+    (scale_x, scale_y): (f32, f32),
+    (mult_x, mult_y): (usize, usize),
+) -> ((usize, usize), (usize, usize)) {
+    let span = |start: f32, end: f32, scale: f32, mult: usize, cells: usize| {
+        let last = cells.saturating_sub(1);
+        let first = ((start * scale) as usize / mult).min(last);
+        (first, ((end * scale) as usize / mult).clamp(first, last))
+    };
+    (
+        span(
+            bounds.x - layout.bounds.x,
+            bounds.x + bounds.width - layout.bounds.x,
+            scale_x,
+            mult_x,
+            cell_width,
+        ),
+        span(
+            bounds.y - layout.bounds.y,
+            bounds.y + bounds.height - layout.bounds.y,
+            scale_y,
+            mult_y,
+            cell_height,
+        ),
+    )
+}
+
+/// Weight an edge adds to each cell it passes through; a node adds 1 to each cell it covers.
+const HEATMAP_EDGE_WEIGHT: f32 = 0.5;
+
+/// Shade each content cell by how much of the diagram falls into it, from blank through `░▒▓`
+/// to `█` (`.:=#` in ASCII). Levels follow the square root of the weight relative to the
+/// busiest cell, so thinly populated areas stay visible next to dense clusters.
+fn render_heatmap(
+    layout: &DiagramLayout,
+    config: &MinimapConfig,
+    viewport: Option<&Viewport>,
+    (cell_width, cell_height): (usize, usize),
+    scale: (f32, f32),
+    mult: (usize, usize),
+) -> String {
+    let mut weights = vec![0.0_f32; cell_width * cell_height];
+    for node_box in &layout.nodes {
+        let ((left, right), (top, bottom)) = bounds_to_cell_range(
+            layout,
+            &node_box.bounds,
+            (cell_width, cell_height),
+            scale,
+            mult,
+        );
+        for row in top..=bottom {
+            for col in left..=right {
+                weights[row * cell_width + col] += 1.0;
+            }
+        }
+    }
+
+    let point_cell = |point: &fm_layout::LayoutPoint| {
+        let col = ((point.x - layout.bounds.x) * scale.0) as usize / mult.0;
+        let row = ((point.y - layout.bounds.y) * scale.1) as usize / mult.1;
+        (
+            col.min(cell_width.saturating_sub(1)),
+            row.min(cell_height.saturating_sub(1)),
+        )
+    };
+    for edge_path in &layout.edges {
+        let mut touched = BTreeSet::new();
+        for window in edge_path.points.windows(2) {
+            let (x0, y0) = point_cell(&window[0]);
+            let (x1, y1) = point_cell(&window[1]);
+            let steps = x0.abs_diff(x1).max(y0.abs_diff(y1)).max(1);
+            for step in 0..=steps {
+                let t = step as f32 / steps as f32;
+                let col = (x0 as f32 + (x1 as f32 - x0 as f32) * t).round() as usize;
+                let row = (y0 as f32 + (y1 as f32 - y0 as f32) * t).round() as usize;
+                touched.insert(row * cell_width + col);
+            }
+        }
+        for index in touched {
+            weights[index] += HEATMAP_EDGE_WEIGHT;
+        }
     }
+
+    let shades = match config.glyph_mode {
+        MermaidGlyphMode::Ascii => [' ', '.', ':', '=', '#'],
+        MermaidGlyphMode::Unicode => [' ', '░', '▒', '▓', '█'],
+    };
+    let busiest = weights.iter().copied().fold(0.0_f32, f32::max);
+    let mut cells: Vec<char> = weights
+        .iter()
+        .map(|&weight| {
+            if weight <= 0.0 {
+                return shades[0];
+            }
+            let level = ((weight / busiest).sqrt() * 4.0).ceil() as usize;
+            shades[level.clamp(1, 4)]
+        })
+        .collect();
+
+    if config.show_viewport
+        && let Some(vp) = viewport
+        && cell_width >= 2
+        && cell_height >= 2
+    {
+        let vp = vp.normalized();
+        let left = ((vp.x * cell_width as f32) as usize).min(cell_width - 2);
+        let top = ((vp.y * cell_height as f32) as usize).min(cell_height - 2);
+        let right = (((vp.x + vp.width) * cell_width as f32).ceil() as usize)
+            .clamp(left + 2, cell_width)
+            - 1;
+        let bottom = (((vp.y + vp.height) * cell_height as f32).ceil() as usize)
+            .clamp(top + 2, cell_height)
+            - 1;
+        let (tl, tr, bl, br, hz, vt) = border_glyphs(config.glyph_mode);
+        for col in left + 1..right {
+            cells[top * cell_width + col] = hz;
+            cells[bottom * cell_width + col] = hz;
+        }
+        for row in top + 1..bottom {
+            cells[row * cell_width + left] = vt;
+            cells[row * cell_width + right] = vt;
+        }
+        cells[top * cell_width + left] = tl;
+        cells[top * cell_width + right] = tr;
+        cells[bottom * cell_width + left] = bl;
+        cells[bottom * cell_width + right] = br;
+    }
+
+    cells
+        .chunks(cell_width.max(1))
+        .map(|row| row.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Redraw `cells` of `output` in the hover style: diamonds (`@` in ASCII), yellow when colors
+/// are on. Neither glyph is used by the shapes or the heatmap.
+fn highlight_cells(output: &str, cells: MinimapRect, config: &MinimapConfig) -> String {
+    let glyph = match config.glyph_mode {
+        MermaidGlyphMode::Ascii => '@',
+        MermaidGlyphMode::Unicode => '◆',
+    };
+    let styled = if config.use_color {
+        format!("\x1b[1;33m{glyph}\x1b[0m")
+    } else {
+        glyph.to_string()
+    };
+    output
+        .split('\n')
+        .enumerate()
+        .map(|(row, line)| {
+            if row < cells.y || row >= cells.y + cells.height {
+                return line.to_string();
+            }
+            line.chars()
+                .enumerate()
+                .map(|(col, ch)| {
+                    if (cells.x..cells.x + cells.width).contains(&col) {
+                        styled.clone()
+                    } else {
+                        ch.to_string()
+                    }
+                })
+                .collect()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
 }
 
 #[must_use]
@@ -424,7 +731,7 @@ fn subcell_mult(mode: MermaidRenderMode) -> (usize, usize) {
         MermaidRenderMode::Braille => (2, 4),
         MermaidRenderMode::Block => (2, 2),
         MermaidRenderMode::HalfBlock => (1, 2),
-        MermaidRenderMode::CellOnly | MermaidRenderMode::Auto => (1, 1),
+        MermaidRenderMode::CellOnly | MermaidRenderMode::Auto | MermaidRenderMode::Pixels => (1, 1),
     }
 }
 
@@ -492,7 +799,7 @@ fn colorize_output(output: &str) -> String {
                 colored.push_str("\x1b[0m");
             }
             // Block characters and full Braille range (U+2800..=U+28FF)
-            '█' | '▀' | '▄' | '\u{2800}'..='\u{28FF}' => {
+            '█' | '▀' | '▄' | '░' | '▒' | '▓' | '\u{2800}'..='\u{28FF}' => {
                 colored.push_str("\x1b[36m");
                 colored.push(ch);
                 colored.push_str("\x1b[0m");
@@ -716,6 +1023,157 @@ mod tests {
         assert!(y <= layout.bounds.y + layout.bounds.height);
     }
 
+    #[test]
+    fn clicks_map_to_layout_points_and_viewport_jumps() {
+        let ir = sample_ir();
+        let layout = layout_diagram(&ir);
+        let result = render_minimap(&ir, &MinimapConfig::default());
+        let mapping = result.mapping;
+
+        assert_eq!(result.cell_to_world(0, 0), None, "border cell");
+        assert_eq!(result.cell_to_world(result.width, 1), None);
+        let (x, y) = result.cell_to_world(1, 1).expect("first content cell");
+        assert!(x > layout.bounds.x && y > layout.bounds.y);
+
+        let last_x = mapping.content.x + mapping.content.width - 1;
+        let last_y = mapping.content.y + mapping.content.height - 1;
+        let (x, y) = result
+            .cell_to_world(last_x, last_y)
+            .expect("last content cell");
+        assert!(x < layout.bounds.x + layout.bounds.width);
+        assert!(y < layout.bounds.y + layout.bounds.height);
+
+        let viewport = Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: 0.5,
+            height: 0.5,
+        };
+        let jumped = mapping
+            .jump_viewport(last_x, last_y, &viewport)
+            .expect("jump");
+        assert_eq!((jumped.x, jumped.y), (0.5, 0.5));
+        assert_eq!((jumped.width, jumped.height), (0.5, 0.5));
+        assert!(mapping.jump_viewport(0, 0, &viewport).is_none());
+    }
+
+    #[test]
+    fn node_under_cursor_is_highlighted() {
+        let ir = sample_ir();
+        let layout = layout_diagram(&ir);
+        let plain = render_minimap(&ir, &MinimapConfig::default());
+        let target = &layout.nodes[1];
+        let content = plain.mapping.content;
+        let to_cell = |value: f32, start: f32, extent: f32, offset: usize, cells: usize| {
+            offset + ((value - start) / extent * cells as f32) as usize
+        };
+        let cursor = (
+            to_cell(
+                target.bounds.x + target.bounds.width / 2.0,
+                layout.bounds.x,
+                layout.bounds.width,
+                content.x,
+                content.width,
+            ),
+            to_cell(
+                target.bounds.y + target.bounds.height / 2.0,
+                layout.bounds.y,
+                layout.bounds.height,
+                content.y,
+                content.height,
+            ),
+        );
+
+        let hovered = render_minimap(
+            &ir,
+            &MinimapConfig {
+                cursor: Some(cursor),
+                ..Default::default()
+            },
+        );
+        assert_eq!(hovered.hovered_node, Some(target.node_index));
+        assert!(hovered.output.contains('◆'), "{}", hovered.output);
+        assert!(!plain.output.contains('◆'));
+
+        let colored = render_minimap(
+            &ir,
+            &MinimapConfig {
+                cursor: Some(cursor),
+                use_color: true,
+                ..Default::default()
+            },
+        );
+        assert!(colored.output.contains("\x1b[1;33m◆"), "{}", colored.output);
+
+        let on_border = render_minimap(
+            &ir,
+            &MinimapConfig {
+                cursor: Some((0, 0)),
+                ..Default::default()
+            },
+        );
+        assert_eq!(on_border.hovered_node, None);
+    }
+
+    #[test]
+    fn heatmap_shades_cells_instead_of_drawing_shapes() {
+        let mut source = String::from("flowchart LR\n");
+        // Chains of growing length: dense on the left, sparse towards the right.
+        for chain in 0..20 {
+            for step in 0..chain {
+                source.push_str(&format!("  C{chain}x{step} --> C{chain}x{}\n", step + 1));
+            }
+        }
+        let layout = layout_diagram(&fm_parser::parse(&source).ir);
+        let config = MinimapConfig {
+            heatmap: true,
+            max_width: 30,
+            max_height: 12,
+            ..Default::default()
+        };
+        let result = render_minimap_from_layout(&layout, &config, None);
+
+        assert!(
+            !result
+                .output
+                .chars()
+                .any(|ch| ('\u{2800}'..='\u{28FF}').contains(&ch)),
+            "{}",
+            result.output
+        );
+        assert!(result.output.contains('█'), "{}", result.output);
+        let levels = ['░', '▒', '▓', '█']
+            .into_iter()
+            .filter(|&shade| result.output.contains(shade))
+            .count();
+        assert!(levels >= 2, "{}", result.output);
+
+        let viewport = Viewport {
+            x: 0.25,
+            y: 0.25,
+            width: 0.5,
+            height: 0.5,
+        };
+        let with_viewport = render_minimap_from_layout(&layout, &config, Some(&viewport));
+        assert_eq!(
+            with_viewport.output.matches('┐').count(),
+            2,
+            "{}",
+            with_viewport.output
+        );
+
+        let ascii = render_minimap_from_layout(
+            &layout,
+            &MinimapConfig {
+                glyph_mode: MermaidGlyphMode::Ascii,
+                ..config
+            },
+            None,
+        );
+        assert!(ascii.output.contains('#'), "{}", ascii.output);
+        assert!(ascii.output.is_ascii(), "{}", ascii.output);
+    }
+
     #[test]
     fn viewport_from_layout_rect_normalizes_into_unit_space() {
         let rect = fm_layout::LayoutRect {
@@ -0,0 +1,319 @@
+//! Partial redraws for live previews.
+//!
+//! [`TermRenderResult::diff_against`] compares two renders cell by cell, with the color and
+//! hyperlink each cell is drawn in, and returns the runs that changed. Writing those runs over
+//! the previous frame leaves the screen showing the new one without reprinting every line.
+
+use std::fmt;
+
+use crate::renderer::TermRenderResult;
+use crate::text::char_width;
+use crate::viewport::take_escape;
+
+/// Unchanged cells between two changed runs up to which the runs are written as one: a cursor
+/// move costs about as many bytes as rewriting a few cells.
+const MERGE_GAP: usize = 4;
+
+const SGR_RESET: &str = "\x1b[0m";
+const LINK_CLOSE: &str = "\x1b]8;;\x1b\\";
+
+/// A run of changed cells on one row of a rendered diagram.
+///
+/// `x` and `y` are 0-based columns and rows from the diagram's top-left corner. `text` is what
+/// to write there, escape sequences included; it opens its own color and hyperlink and closes
+/// them again, so patches can be written in any order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CellPatch {
+    /// First column of the run.
+    pub x: usize,
+    /// Row of the run.
+    pub y: usize,
+    /// Columns the run covers.
+    pub width: usize,
+    /// Content to write at (`x`, `y`).
+    pub text: String,
+}
+
+impl CellPatch {
+    /// Cursor move to the run followed by its text, for a diagram drawn with its top-left cell
+    /// at the 0-based screen position (`origin_x`, `origin_y`).
+    #[must_use]
+    pub fn to_ansi(&self, origin_x: usize, origin_y: usize) -> String {
+        format!(
+            "\x1b[{};{}H{}",
+            origin_y + self.y + 1,
+            origin_x + self.x + 1,
+            self.text
+        )
+    }
+}
+
+impl fmt::Display for CellPatch {
+    /// The patch for a diagram drawn from the top-left corner of the screen.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_ansi(0, 0))
+    }
+}
+
+impl TermRenderResult {
+    /// Patches turning a screen showing `previous` into one showing `self`.
+    ///
+    /// Cells past the end of a shorter line, and rows `previous` had but `self` does not, are
+    /// blanked. Identical renders give no patches.
+    #[must_use]
+    pub fn diff_against(&self, previous: &Self) -> Vec<CellPatch> {
+        let new_rows: Vec<Vec<Cell>> = self.output.split('\n').map(parse_cells).collect();
+        let old_rows: Vec<Vec<Cell>> = previous.output.split('\n').map(parse_cells).collect();
+        let blank = Cell::blank();
+        let mut patches = Vec::new();
+        for y in 0..new_rows.len().max(old_rows.len()) {
+            let new_row = new_rows.get(y).map_or(&[][..], Vec::as_slice);
+            let old_row = old_rows.get(y).map_or(&[][..], Vec::as_slice);
+            let width = new_row.len().max(old_row.len());
+            let cell_at = |row: &[Cell], x: usize| row.get(x).unwrap_or(&blank).clone();
+            let changed: Vec<bool> = (0..width)
+                .map(|x| cell_at(new_row, x) != cell_at(old_row, x))
+                .collect();
+            for (start, end) in changed_runs(&changed) {
+                let (start, end) = widen_to_whole_glyphs(new_row, old_row, start, end);
+                let cells: Vec<Cell> = (start..end).map(|x| cell_at(new_row, x)).collect();
+                patches.push(CellPatch {
+                    x: start,
+                    y,
+                    width: end - start,
+                    text: write_cells(&cells),
+                });
+            }
+        }
+        patches
+    }
+}
+
+/// One terminal column of a rendered line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Cell {
+    /// Characters drawn in the column, empty for the right half of a double-width glyph.
+    pub(crate) glyph: String,
+    /// SGR sequences in effect since the last reset.
+    pub(crate) sgr: Option<String>,
+    /// OSC 8 sequence opening the hyperlink the column is part of.
+    pub(crate) link: Option<String>,
+}
+
+impl Cell {
+    fn blank() -> Self {
+        Self {
+            glyph: String::from(' '),
+            sgr: None,
+            link: None,
+        }
+    }
+
+    fn is_wide_tail(&self) -> bool {
+        self.glyph.is_empty()
+    }
+}
+
+/// Columns of `line` with the color and hyperlink open at each one.
+pub(crate) fn parse_cells(line: &str) -> Vec<Cell> {
+    let mut cells: Vec<Cell> = Vec::new();
+    let mut sgr: Option<String> = None;
+    let mut link: Option<String> = None;
+    let mut chars = line.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch == '\x1b' {
+            let sequence = take_escape(&mut chars);
+            if sequence.starts_with("\x1b]8;") {
+                link = (!sequence.starts_with("\x1b]8;;\x1b")).then_some(sequence);
+            } else if sequence.ends_with('m') {
+                // Attributes add up (faint, then a color) until a reset clears them.
+                if sequence == SGR_RESET || sequence == "\x1b[m" {
+                    sgr = None;
+                } else {
+                    sgr.get_or_insert_default().push_str(&sequence);
+                }
+            }
+            continue;
+        }
+        match char_width(ch) {
+            0 => {
+                if let Some(cell) = cells.iter_mut().rev().find(|cell| !cell.is_wide_tail()) {
+                    cell.glyph.push(ch);
+                }
+            }
+            width => {
+                cells.push(Cell {
+                    glyph: String::from(ch),
+                    sgr: sgr.clone(),
+                    link: link.clone(),
+                });
+                for _ in 1..width {
+                    cells.push(Cell {
+                        glyph: String::new(),
+                        sgr: sgr.clone(),
+                        link: link.clone(),
+                    });
+                }
+            }
+        }
+    }
+    cells
+}
+
+/// `start..end` column ranges of the changed cells, joining runs separated by fewer than
+/// [`MERGE_GAP`] unchanged ones.
+fn changed_runs(changed: &[bool]) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for (x, _) in changed.iter().enumerate().filter(|(_, changed)| **changed) {
+        match runs.last_mut() {
+            Some((_, end)) if x - *end < MERGE_GAP => *end = x + 1,
+            _ => runs.push((x, x + 1)),
+        }
+    }
+    runs
+}
+
+/// `start..end` grown so it neither starts on the right half nor ends on the left half of a
+/// double-width glyph in either row: writing half a glyph would leave the other half behind.
+fn widen_to_whole_glyphs(
+    new_row: &[Cell],
+    old_row: &[Cell],
+    mut start: usize,
+    mut end: usize,
+) -> (usize, usize) {
+    let is_tail = |x: usize| {
+        [new_row, old_row]
+            .iter()
+            .any(|row| row.get(x).is_some_and(Cell::is_wide_tail))
+    };
+    while start > 0 && is_tail(start) {
+        start -= 1;
+    }
+    while is_tail(end) {
+        end += 1;
+    }
+    (start, end)
+}
+
+/// `cells` as text, switching color and hyperlink where they change and closing both at the end.
+fn write_cells(cells: &[Cell]) -> String {
+    let mut out = String::new();
+    let mut sgr: Option<&str> = None;
+    let mut link: Option<&str> = None;
+    for cell in cells {
+        if cell.sgr.as_deref() != sgr {
+            if sgr.is_some() {
+                out.push_str(SGR_RESET);
+            }
+            sgr = cell.sgr.as_deref();
+            out.extend(sgr);
+        }
+        if cell.link.as_deref() != link {
+            link = cell.link.as_deref();
+            out.push_str(link.unwrap_or(LINK_CLOSE));
+        }
+        out.push_str(&cell.glyph);
+    }
+    if sgr.is_some() {
+        out.push_str(SGR_RESET);
+    }
+    if link.is_some() {
+        out.push_str(LINK_CLOSE);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::ColorMode;
+    use crate::config::TermRenderConfig;
+    use crate::renderer::render_diagram_with_config;
+
+    /// Plain text of `rows`, without trailing blanks.
+    fn plain(rows: &[Vec<Cell>]) -> Vec<String> {
+        let mut lines: Vec<String> = rows
+            .iter()
+            .map(|row| {
+                let text: String = row.iter().map(|cell| cell.glyph.as_str()).collect();
+                text.trim_end().to_string()
+            })
+            .collect();
+        while lines.last().is_some_and(String::is_empty) {
+            lines.pop();
+        }
+        lines
+    }
+
+    fn plain_rows(output: &str) -> Vec<String> {
+        plain(&output.split('\n').map(parse_cells).collect::<Vec<_>>())
+    }
+
+    /// `previous` with every patch applied, as plain text rows.
+    fn apply(previous: &str, patches: &[CellPatch]) -> Vec<String> {
+        let mut rows: Vec<Vec<Cell>> = previous.split('\n').map(parse_cells).collect();
+        for patch in patches {
+            if rows.len() <= patch.y {
+                rows.resize(patch.y + 1, Vec::new());
+            }
+            let row = &mut rows[patch.y];
+            if row.len() < patch.x + patch.width {
+                row.resize(patch.x + patch.width, Cell::blank());
+            }
+            let cells = parse_cells(&patch.text);
+            assert_eq!(cells.len(), patch.width, "{patch:?}");
+            row.splice(patch.x..patch.x + patch.width, cells);
+        }
+        plain(&rows)
+    }
+
+    #[test]
+    fn patches_repaint_only_the_changed_label() {
+        let config = TermRenderConfig::compact();
+        let before = fm_parser::parse("flowchart LR\n  A[Alpha] --> B[Beta]");
+        let after = fm_parser::parse("flowchart LR\n  A[Alpha] --> B[Bet]");
+        let previous = render_diagram_with_config(&before.ir, &config, 60, 12);
+        let current = render_diagram_with_config(&after.ir, &config, 60, 12);
+
+        assert!(current.diff_against(&current).is_empty());
+        let patches = current.diff_against(&previous);
+        assert!(!patches.is_empty());
+        let repainted: usize = patches.iter().map(|patch| patch.width).sum();
+        assert!(repainted < current.output.len() / 4, "{patches:?}");
+        assert!(patches.iter().all(|patch| !patch.text.contains("Alpha")));
+        assert_eq!(
+            apply(&previous.output, &patches),
+            plain_rows(&current.output)
+        );
+        assert!(patches[0].to_ansi(2, 1).starts_with(&format!(
+            "\x1b[{};{}H",
+            patches[0].y + 2,
+            patches[0].x + 3
+        )));
+    }
+
+    #[test]
+    fn patches_carry_their_own_colors_and_blank_removed_rows() {
+        let config = TermRenderConfig {
+            color_mode: ColorMode::TrueColor,
+            ..TermRenderConfig::compact()
+        };
+        let before = fm_parser::parse("flowchart TD\n  A[Start] --> B[Middle] --> C[End]");
+        let after = fm_parser::parse("flowchart TD\n  A[Start] --> B[Middle]");
+        let previous = render_diagram_with_config(&before.ir, &config, 40, 20);
+        let current = render_diagram_with_config(&after.ir, &config, 40, 20);
+
+        let patches = current.diff_against(&previous);
+        assert_eq!(
+            apply(&previous.output, &patches),
+            plain_rows(&current.output)
+        );
+        for patch in &patches {
+            let opens = patch.text.matches("\x1b[3").count();
+            assert!(
+                opens == 0 || patch.text.trim_end().ends_with(SGR_RESET),
+                "{patch:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,949 @@
+//! Inline images for terminals that can show them ([`MermaidRenderMode::Pixels`]).
+//!
+//! The diagram is drawn from its layout straight onto an RGBA buffer: cluster frames, edge
+//! paths with arrowheads, filled node boxes, and labels in a built-in 5x7 bitmap font. The
+//! buffer is then sent to the terminal with the Kitty graphics protocol, as an iTerm2 inline
+//! PNG, or as Sixel. The protocol comes from [`TermRenderConfig::image_protocol`], detected
+//! from the environment by default. When no protocol is available, `Pixels` falls back to the
+//! mode the tier would pick on its own.
+//!
+//! [`TermRenderConfig::image_protocol`]: crate::TermRenderConfig::image_protocol
+
+use std::collections::HashMap;
+
+use fm_core::{ArrowType, MermaidDiagramIr, MermaidRenderMode};
+use fm_layout::{DiagramLayout, LayoutPoint, LayoutRect};
+use serde::{Deserialize, Serialize};
+
+use crate::color::{TermColor, diagram_palette, edge_stroke, node_stroke};
+use crate::glyphs::is_dotted;
+use crate::renderer::{TermRenderResult, TermRenderer};
+
+/// Pixels per terminal cell assumed when sizing the image.
+pub const CELL_PIXEL_WIDTH: usize = 10;
+/// Pixels per terminal cell assumed when sizing the image.
+pub const CELL_PIXEL_HEIGHT: usize = 20;
+
+/// Blank border around the diagram, in pixels.
+const MARGIN: f32 = 8.0;
+/// Diagrams are never drawn larger than this many pixels per layout unit.
+const MAX_SCALE: f32 = 2.0;
+/// Largest Kitty graphics payload chunk, in base64 bytes.
+const KITTY_CHUNK: usize = 4096;
+
+const GRAY: TermColor = TermColor::new(0x9c, 0xa3, 0xaf);
+const INK: TermColor = TermColor::new(0x1f, 0x29, 0x37);
+const PAPER: TermColor = TermColor::new(0xf8, 0xfa, 0xfc);
+const WHITE: TermColor = TermColor::new(0xff, 0xff, 0xff);
+
+/// Inline image protocol used by [`MermaidRenderMode::Pixels`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ImageProtocol {
+    /// Detect from the environment at render time.
+    #[default]
+    Auto,
+    /// No image support; `Pixels` falls back to the cell renderer.
+    None,
+    /// Kitty graphics protocol (kitty, Ghostty).
+    Kitty,
+    /// iTerm2 inline images (iTerm2, WezTerm).
+    Iterm2,
+    /// DEC Sixel graphics (foot, mlterm, xterm built with sixel support).
+    Sixel,
+}
+
+impl ImageProtocol {
+    /// Protocol the current terminal supports, from `FM_IMAGE_PROTOCOL` or else `TERM`,
+    /// `TERM_PROGRAM` and the terminal-specific variables a few emulators export.
+    #[must_use]
+    pub fn detect() -> Self {
+        Self::detect_from(|name| std::env::var(name).ok())
+    }
+
+    /// [`detect`](Self::detect) with environment lookups going through `var`.
+    #[must_use]
+    pub fn detect_from(var: impl Fn(&str) -> Option<String>) -> Self {
+        if let Some(value) = var("FM_IMAGE_PROTOCOL") {
+            match value.trim().to_ascii_lowercase().as_str() {
+                "kitty" => return Self::Kitty,
+                "iterm" | "iterm2" => return Self::Iterm2,
+                "sixel" => return Self::Sixel,
+                "none" | "off" => return Self::None,
+                _ => {}
+            }
+        }
+        let term = var("TERM").unwrap_or_default();
+        let program = var("TERM_PROGRAM").unwrap_or_default();
+        if var("KITTY_WINDOW_ID").is_some()
+            || matches!(term.as_str(), "xterm-kitty" | "xterm-ghostty")
+            || program == "ghostty"
+        {
+            Self::Kitty
+        } else if matches!(program.as_str(), "iTerm.app" | "WezTerm")
+            || var("LC_TERMINAL").as_deref() == Some("iTerm2")
+        {
+            Self::Iterm2
+        } else if term.contains("sixel")
+            || matches!(term.as_str(), "foot" | "foot-extra" | "mlterm")
+        {
+            Self::Sixel
+        } else {
+            Self::None
+        }
+    }
+
+    /// `self`, with `Auto` replaced by the detected protocol.
+    #[must_use]
+    pub fn resolve(self) -> Self {
+        match self {
+            Self::Auto => Self::detect(),
+            other => other,
+        }
+    }
+
+    /// Escape sequence showing `image` across `cols` x `rows` cells, or `None` for `Auto` and
+    /// `None`.
+    #[must_use]
+    pub fn encode(self, image: &RgbaImage, cols: usize, rows: usize) -> Option<String> {
+        match self {
+            Self::Auto | Self::None => None,
+            Self::Kitty => Some(encode_kitty(image, cols, rows)),
+            Self::Iterm2 => Some(encode_iterm2(image, cols, rows)),
+            Self::Sixel => Some(encode_sixel(image)),
+        }
+    }
+}
+
+/// An 8-bit RGBA image, rows top to bottom, starting fully transparent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+    pub width: usize,
+    pub height: usize,
+    /// `width * height * 4` bytes.
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    #[must_use]
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height * 4],
+        }
+    }
+
+    /// `[r, g, b, a]` at `(x, y)`, or `None` outside the image.
+    #[must_use]
+    pub fn pixel(&self, x: usize, y: usize) -> Option<[u8; 4]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let index = (y * self.width + x) * 4;
+        self.pixels[index..index + 4].try_into().ok()
+    }
+
+    fn set(&mut self, x: i64, y: i64, color: TermColor) {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return;
+        }
+        let index = (y as usize * self.width + x as usize) * 4;
+        self.pixels[index..index + 4].copy_from_slice(&[color.r, color.g, color.b, 0xff]);
+    }
+
+    fn fill_rect(&mut self, (x, y, w, h): (i64, i64, i64, i64), color: TermColor) {
+        for py in y..y + h {
+            for px in x..x + w {
+                self.set(px, py, color);
+            }
+        }
+    }
+
+    fn stroke_rect(
+        &mut self,
+        (x, y, w, h): (i64, i64, i64, i64),
+        color: TermColor,
+        thickness: i64,
+    ) {
+        self.fill_rect((x, y, w, thickness), color);
+        self.fill_rect((x, y + h - thickness, w, thickness), color);
+        self.fill_rect((x, y, thickness, h), color);
+        self.fill_rect((x + w - thickness, y, thickness, h), color);
+    }
+
+    /// Bresenham line with a square pen; `dash` leaves gaps of that many pixels between dashes.
+    fn line(
+        &mut self,
+        (x0, y0): (i64, i64),
+        (x1, y1): (i64, i64),
+        color: TermColor,
+        thickness: i64,
+        dash: Option<i64>,
+    ) {
+        let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+        let (sx, sy) = (if x0 < x1 { 1 } else { -1 }, if y0 < y1 { 1 } else { -1 });
+        let (mut x, mut y, mut err) = (x0, y0, dx + dy);
+        let offset = thickness / 2;
+        for step in 0.. {
+            if dash.is_none_or(|gap| step % (gap * 2) < gap) {
+                self.fill_rect((x - offset, y - offset, thickness, thickness), color);
+            }
+            if x == x1 && y == y1 {
+                break;
+            }
+            let doubled = 2 * err;
+            if doubled >= dy {
+                err += dy;
+                x += sx;
+            }
+            if doubled <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    /// Filled triangle pointing at `tip`, coming from `from`.
+    fn arrowhead(&mut self, from: (f32, f32), tip: (f32, f32), size: f32, color: TermColor) {
+        let (dx, dy) = (tip.0 - from.0, tip.1 - from.1);
+        let length = dx.hypot(dy);
+        if length < f32::EPSILON {
+            return;
+        }
+        let (ux, uy) = (dx / length, dy / length);
+        let steps = size.ceil() as i64;
+        for step in 0..=steps {
+            // Cross-section of the triangle `step` pixels back from the tip.
+            let back = step as f32;
+            let half = back * 0.5;
+            let (cx, cy) = (tip.0 - ux * back, tip.1 - uy * back);
+            let start = (
+                (cx - uy * half).round() as i64,
+                (cy + ux * half).round() as i64,
+            );
+            let end = (
+                (cx + uy * half).round() as i64,
+                (cy - ux * half).round() as i64,
+            );
+            self.line(start, end, color, 2, None);
+        }
+    }
+
+    /// Draw `text` with its top-left corner at `(x, y)`, each font pixel `scale` pixels wide.
+    fn text(&mut self, (x, y): (i64, i64), text: &str, color: TermColor, scale: i64) {
+        for (index, ch) in text.chars().enumerate() {
+            let glyph = glyph(ch);
+            let left = x + index as i64 * GLYPH_ADVANCE * scale;
+            for (row, bits) in glyph.iter().enumerate() {
+                for col in 0..5 {
+                    if bits & (0x10 >> col) != 0 {
+                        let px = left + col * scale;
+                        let py = y + row as i64 * scale;
+                        self.fill_rect((px, py, scale, scale), color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Font pixels from one glyph's left edge to the next.
+const GLYPH_ADVANCE: i64 = 6;
+/// Font pixels from one text line's top to the next.
+const LINE_ADVANCE: i64 = 9;
+
+/// 5x7 glyph rows for `ch`, most significant of the low five bits leftmost. Characters outside
+/// printable ASCII draw as `?`.
+fn glyph(ch: char) -> &'static [u8; 7] {
+    let index = (ch as usize)
+        .checked_sub(0x20)
+        .filter(|&index| index < FONT_5X7.len())
+        .unwrap_or('?' as usize - 0x20);
+    &FONT_5X7[index]
+}
+
+/// Printable ASCII, `' '` through `'~'`.
+#[rustfmt::skip]
+const FONT_5X7: [[u8; 7]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // space
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04], // !
+    [0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00, 0x00], // "
+    [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A], // #
+    [0x04, 0x0F, 0x14, 0x0E, 0x05, 0x1E, 0x04], // $
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03], // %
+    [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D], // &
+    [0x04, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00], // '
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02], // (
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08], // )
+    [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00], // *
+    [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00], // +
+    [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08], // ,
+    [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00], // -
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C], // .
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00], // /
+    [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E], // 0
+    [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E], // 1
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F], // 2
+    [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E], // 3
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02], // 4
+    [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E], // 5
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E], // 6
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // 7
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E], // 8
+    [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C], // 9
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00], // :
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08], // ;
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02], // <
+    [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00], // =
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08], // >
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04], // ?
+    [0x0E, 0x11, 0x01, 0x0D, 0x15, 0x15, 0x0E], // @
+    [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // A
+    [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E], // B
+    [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E], // C
+    [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C], // D
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F], // E
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10], // F
+    [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F], // G
+    [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // H
+    [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E], // I
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C], // J
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // K
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F], // L
+    [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11], // M
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // N
+    [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // O
+    [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10], // P
+    [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D], // Q
+    [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11], // R
+    [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E], // S
+    [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // T
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // U
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04], // V
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A], // W
+    [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11], // X
+    [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04], // Y
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F], // Z
+    [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E], // [
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00], // \
+    [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E], // ]
+    [0x04, 0x0A, 0x11, 0x00, 0x00, 0x00, 0x00], // ^
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F], // _
+    [0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00], // `
+    [0x00, 0x00, 0x0E, 0x01, 0x0F, 0x11, 0x0F], // a
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x1E], // b
+    [0x00, 0x00, 0x0E, 0x10, 0x10, 0x11, 0x0E], // c
+    [0x01, 0x01, 0x0D, 0x13, 0x11, 0x11, 0x0F], // d
+    [0x00, 0x00, 0x0E, 0x11, 0x1F, 0x10, 0x0E], // e
+    [0x06, 0x09, 0x08, 0x1C, 0x08, 0x08, 0x08], // f
+    [0x00, 0x0F, 0x11, 0x11, 0x0F, 0x01, 0x0E], // g
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x11], // h
+    [0x04, 0x00, 0x0C, 0x04, 0x04, 0x04, 0x0E], // i
+    [0x02, 0x00, 0x06, 0x02, 0x02, 0x12, 0x0C], // j
+    [0x10, 0x10, 0x12, 0x14, 0x18, 0x14, 0x12], // k
+    [0x0C, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E], // l
+    [0x00, 0x00, 0x1A, 0x15, 0x15, 0x11, 0x11], // m
+    [0x00, 0x00, 0x16, 0x19, 0x11, 0x11, 0x11], // n
+    [0x00, 0x00, 0x0E, 0x11, 0x11, 0x11, 0x0E], // o
+    [0x00, 0x00, 0x1E, 0x11, 0x1E, 0x10, 0x10], // p
+    [0x00, 0x00, 0x0D, 0x13, 0x0F, 0x01, 0x01], // q
+    [0x00, 0x00, 0x16, 0x19, 0x10, 0x10, 0x10], // r
+    [0x00, 0x00, 0x0E, 0x10, 0x0E, 0x01, 0x1E], // s
+    [0x08, 0x08, 0x1C, 0x08, 0x08, 0x09, 0x06], // t
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x13, 0x0D], // u
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x0A, 0x04], // v
+    [0x00, 0x00, 0x11, 0x11, 0x15, 0x15, 0x0A], // w
+    [0x00, 0x00, 0x11, 0x0A, 0x04, 0x0A, 0x11], // x
+    [0x00, 0x00, 0x11, 0x11, 0x0F, 0x01, 0x0E], // y
+    [0x00, 0x00, 0x1F, 0x02, 0x04, 0x08, 0x1F], // z
+    [0x02, 0x04, 0x04, 0x08, 0x04, 0x04, 0x02], // {
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // |
+    [0x08, 0x04, 0x04, 0x02, 0x04, 0x04, 0x08], // }
+    [0x00, 0x00, 0x08, 0x15, 0x02, 0x00, 0x00], // ~
+];
+
+impl TermRenderer {
+    /// Draw the diagram as an inline image when the render mode is `Pixels` and an image
+    /// protocol is available.
+    pub(crate) fn render_pixels(
+        &self,
+        ir: &MermaidDiagramIr,
+        layout: &DiagramLayout,
+    ) -> Option<TermRenderResult> {
+        let bounds = layout.bounds;
+        if layout.nodes.is_empty() || bounds.width <= 0.0 || bounds.height <= 0.0 {
+            return None;
+        }
+        let cols = self
+            .config
+            .cols
+            .saturating_sub(self.config.padding * 2)
+            .max(1);
+        let rows = self
+            .config
+            .rows
+            .saturating_sub(self.config.padding * 2)
+            .max(1);
+        let fit_width = ((cols * CELL_PIXEL_WIDTH) as f32 - 2.0 * MARGIN).max(1.0) / bounds.width;
+        let fit_height =
+            ((rows * CELL_PIXEL_HEIGHT) as f32 - 2.0 * MARGIN).max(1.0) / bounds.height;
+        let scale = fit_width.min(fit_height).min(MAX_SCALE);
+        let cells_w = ((bounds.width * scale + 2.0 * MARGIN) / CELL_PIXEL_WIDTH as f32).ceil();
+        let cells_h = ((bounds.height * scale + 2.0 * MARGIN) / CELL_PIXEL_HEIGHT as f32).ceil();
+        let (cells_w, cells_h) = (cells_w as usize, cells_h as usize);
+
+        let mut image = RgbaImage::new(cells_w * CELL_PIXEL_WIDTH, cells_h * CELL_PIXEL_HEIGHT);
+        let to_px = |point: &LayoutPoint| {
+            (
+                (point.x - bounds.x) * scale + MARGIN,
+                (point.y - bounds.y) * scale + MARGIN,
+            )
+        };
+        let rect_px = |rect: &LayoutRect| {
+            let (x, y) = to_px(&LayoutPoint {
+                x: rect.x,
+                y: rect.y,
+            });
+            (
+                x.round() as i64,
+                y.round() as i64,
+                (rect.width * scale).round().max(2.0) as i64,
+                (rect.height * scale).round().max(2.0) as i64,
+            )
+        };
+        let pen = scale.round().max(1.0) as i64;
+        let font = ((scale * 8.0 / GLYPH_ADVANCE as f32) as i64).clamp(1, 3);
+        let palette = diagram_palette(ir, self.config.palette);
+
+        if self.config.show_clusters {
+            let color = palette.cluster.unwrap_or(GRAY);
+            for cluster_box in &layout.clusters {
+                let rect = rect_px(&cluster_box.bounds);
+                image.stroke_rect(rect, color, pen);
+                if let Some(title) = &cluster_box.title {
+                    let title = fit_line(title, rect.2 - 8, font);
+                    image.text((rect.0 + 4, rect.1 + 4), &title, color, font);
+                }
+            }
+        }
+
+        for edge_path in layout.edges.iter().filter(|edge| !edge.bundled) {
+            let points: Vec<(f32, f32)> = edge_path.points.iter().map(to_px).collect();
+            if points.len() < 2 {
+                continue;
+            }
+            let edge = ir.edges.get(edge_path.edge_index);
+            let arrow = edge.map_or(ArrowType::Arrow, |edge| edge.arrow);
+            let color = edge_stroke(ir, edge_path.edge_index)
+                .or(palette.edge)
+                .unwrap_or(GRAY);
+            let thickness = if matches!(
+                arrow,
+                ArrowType::ThickArrow | ArrowType::ThickLine | ArrowType::DoubleThickArrow
+            ) {
+                pen * 2
+            } else {
+                pen
+            };
+            let dash = (edge_path.reversed || is_dotted(arrow)).then_some(pen * 3);
+            for window in points.windows(2) {
+                let start = (window[0].0.round() as i64, window[0].1.round() as i64);
+                let end = (window[1].0.round() as i64, window[1].1.round() as i64);
+                image.line(start, end, color, thickness, dash);
+            }
+            let head = 6.0 * pen as f32;
+            if !matches!(
+                arrow,
+                ArrowType::Line | ArrowType::ThickLine | ArrowType::DottedLine
+            ) {
+                image.arrowhead(
+                    points[points.len() - 2],
+                    points[points.len() - 1],
+                    head,
+                    color,
+                );
+            }
+            if matches!(
+                arrow,
+                ArrowType::DoubleArrow | ArrowType::DoubleThickArrow | ArrowType::DoubleDottedArrow
+            ) {
+                image.arrowhead(points[1], points[0], head, color);
+            }
+
+            if let Some(label) = edge
+                .and_then(|edge| edge.label)
+                .and_then(|label| ir.labels.get(label.0))
+            {
+                let middle = points.len() / 2;
+                let (x0, y0) = points[middle - 1];
+                let (x1, y1) = points[middle];
+                let center = (((x0 + x1) / 2.0) as i64, ((y0 + y1) / 2.0) as i64);
+                let lines: Vec<String> = self
+                    .truncate_label(&label.text)
+                    .lines()
+                    .map(str::to_string)
+                    .collect();
+                draw_label_block(&mut image, center, &lines, font, INK, Some(PAPER));
+            }
+        }
+
+        for node_box in layout
+            .nodes
+            .iter()
+            .chain(&layout.extensions.sequence_mirror_headers)
+        {
+            let rect = rect_px(&node_box.bounds);
+            let stroke = node_stroke(ir, node_box.node_index)
+                .or(palette.node)
+                .unwrap_or(GRAY);
+            image.fill_rect(rect, tint(stroke, 0.8));
+            image.stroke_rect(rect, stroke, pen);
+            let Some(label) =
+                self.node_display_label(ir, ir.nodes.get(node_box.node_index), &node_box.node_id)
+            else {
+                continue;
+            };
+            let lines: Vec<String> = label
+                .lines()
+                .map(|line| fit_line(line, rect.2 - 2 * pen - 2, font))
+                .collect();
+            let center = (rect.0 + rect.2 / 2, rect.1 + rect.3 / 2);
+            draw_label_block(&mut image, center, &lines, font, INK, None);
+        }
+
+        let output = self
+            .config
+            .image_protocol
+            .encode(&image, cells_w, cells_h)?;
+        Some(TermRenderResult {
+            output,
+            width: cells_w,
+            height: cells_h,
+            tier: self.config.tier,
+            render_mode: MermaidRenderMode::Pixels,
+            node_count: layout.nodes.len(),
+            edge_count: layout.edges.len(),
+            summary_only: false,
+        })
+    }
+}
+
+/// `color` mixed with white, `amount` of the way.
+fn tint(color: TermColor, amount: f32) -> TermColor {
+    let mix = |channel: u8| {
+        (f32::from(channel) + (f32::from(WHITE.r) - f32::from(channel)) * amount).round() as u8
+    };
+    TermColor::new(mix(color.r), mix(color.g), mix(color.b))
+}
+
+/// As many leading characters of `line` as fit in `width` pixels at font scale `font`.
+fn fit_line(line: &str, width: i64, font: i64) -> String {
+    let max_chars = (width + font) / (GLYPH_ADVANCE * font);
+    line.chars().take(max_chars.max(1) as usize).collect()
+}
+
+/// Centered lines of text at `center`, on a `background` box when one is given.
+fn draw_label_block(
+    image: &mut RgbaImage,
+    (cx, cy): (i64, i64),
+    lines: &[String],
+    font: i64,
+    color: TermColor,
+    background: Option<TermColor>,
+) {
+    if lines.is_empty() {
+        return;
+    }
+    let widest = lines
+        .iter()
+        .map(|line| line.chars().count() as i64)
+        .max()
+        .unwrap_or(0);
+    let width = widest * GLYPH_ADVANCE * font - font;
+    let height = lines.len() as i64 * LINE_ADVANCE * font - 2 * font;
+    let (left, top) = (cx - width / 2, cy - height / 2);
+    if let Some(background) = background {
+        let pad = 2 * font;
+        image.fill_rect(
+            (left - pad, top - pad, width + 2 * pad, height + 2 * pad),
+            background,
+        );
+    }
+    for (row, line) in lines.iter().enumerate() {
+        let line_width = line.chars().count() as i64 * GLYPH_ADVANCE * font - font;
+        let x = cx - line_width / 2;
+        image.text(
+            (x, top + row as i64 * LINE_ADVANCE * font),
+            line,
+            color,
+            font,
+        );
+    }
+}
+
+fn encode_kitty(image: &RgbaImage, cols: usize, rows: usize) -> String {
+    let payload = base64(&image.pixels);
+    let chunks: Vec<&str> = payload
+        .as_bytes()
+        .chunks(KITTY_CHUNK)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap_or_default())
+        .collect();
+    let mut out = String::with_capacity(payload.len() + chunks.len() * 16 + 64);
+    for (index, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(index + 1 < chunks.len());
+        if index == 0 {
+            out.push_str(&format!(
+                "\x1b_Ga=T,f=32,s={},v={},c={cols},r={rows},q=2,m={more};{chunk}\x1b\\",
+                image.width, image.height
+            ));
+        } else {
+            out.push_str(&format!("\x1b_Gm={more};{chunk}\x1b\\"));
+        }
+    }
+    out
+}
+
+fn encode_iterm2(image: &RgbaImage, cols: usize, rows: usize) -> String {
+    let png = encode_png(image);
+    format!(
+        "\x1b]1337;File=inline=1;size={};width={cols};height={rows};preserveAspectRatio=1:{}\x07",
+        png.len(),
+        base64(&png)
+    )
+}
+
+/// Sixel image with a 1:1 pixel aspect ratio and a transparent background. Opaque colors get
+/// palette registers in order of first use; past 256 they share the nearest register.
+fn encode_sixel(image: &RgbaImage) -> String {
+    let (width, height) = (image.width, image.height);
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+    let mut registers: HashMap<[u8; 3], usize> = HashMap::new();
+    let indices: Vec<Option<usize>> = image
+        .pixels
+        .as_chunks::<4>()
+        .0
+        .iter()
+        .map(|pixel| {
+            if pixel[3] < 0x80 {
+                return None;
+            }
+            let rgb = [pixel[0], pixel[1], pixel[2]];
+            if let Some(&register) = registers.get(&rgb) {
+                return Some(register);
+            }
+            if palette.len() < 256 {
+                palette.push(rgb);
+                registers.insert(rgb, palette.len() - 1);
+                return Some(palette.len() - 1);
+            }
+            palette
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, entry)| {
+                    entry
+                        .iter()
+                        .zip(rgb)
+                        .map(|(&a, b)| (i32::from(a) - i32::from(b)).pow(2))
+                        .sum::<i32>()
+                })
+                .map(|(register, _)| register)
+        })
+        .collect();
+
+    let mut out = format!("\x1bP0;1;0q\"1;1;{width};{height}");
+    let percent = |channel: u8| u32::from(channel) * 100 / 255;
+    for (register, [r, g, b]) in palette.iter().enumerate() {
+        out.push_str(&format!(
+            "#{register};2;{};{};{}",
+            percent(*r),
+            percent(*g),
+            percent(*b)
+        ));
+    }
+    for band in (0..height).step_by(6) {
+        let rows = band..(band + 6).min(height);
+        let mut used: Vec<usize> = rows
+            .clone()
+            .flat_map(|y| {
+                indices[y * width..(y + 1) * width]
+                    .iter()
+                    .flatten()
+                    .copied()
+            })
+            .collect();
+        used.sort_unstable();
+        used.dedup();
+        for register in used {
+            out.push_str(&format!("#{register}"));
+            let mut run: Option<(char, usize)> = None;
+            for x in 0..width {
+                let bits = rows
+                    .clone()
+                    .enumerate()
+                    .filter(|&(_, y)| indices[y * width + x] == Some(register))
+                    .fold(0u8, |bits, (dy, _)| bits | (1 << dy));
+                let sixel = char::from(63 + bits);
+                run = match run {
+                    Some((ch, count)) if ch == sixel => Some((ch, count + 1)),
+                    Some((ch, count)) => {
+                        push_sixel_run(&mut out, ch, count);
+                        Some((sixel, 1))
+                    }
+                    None => Some((sixel, 1)),
+                };
+            }
+            // A trailing blank run needs no bytes.
+            if let Some((ch, count)) = run.filter(|&(ch, _)| ch != '?') {
+                push_sixel_run(&mut out, ch, count);
+            }
+            out.push('$');
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+    out
+}
+
+fn push_sixel_run(out: &mut String, ch: char, count: usize) {
+    if count > 3 {
+        out.push_str(&format!("!{count}{ch}"));
+    } else {
+        out.extend(std::iter::repeat_n(ch, count));
+    }
+}
+
+/// Uncompressed PNG: RGBA, no filtering, deflate stored blocks.
+fn encode_png(image: &RgbaImage) -> Vec<u8> {
+    let mut raw = Vec::with_capacity((image.width * 4 + 1) * image.height);
+    for row in image.pixels.chunks_exact(image.width.max(1) * 4) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(0xffff).peekable();
+    if raw.is_empty() {
+        zlib.extend([1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        zlib.push(u8::from(blocks.peek().is_none()));
+        let len = block.len() as u16;
+        zlib.extend(len.to_le_bytes());
+        zlib.extend((!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend(adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend((image.width as u32).to_be_bytes());
+    header.extend((image.height as u32).to_be_bytes());
+    header.extend([8, 6, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    push_png_chunk(&mut png, b"IHDR", &header);
+    push_png_chunk(&mut png, b"IDAT", &zlib);
+    push_png_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn push_png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 == 0 {
+                c >> 1
+            } else {
+                0xedb8_8320 ^ (c >> 1)
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+};
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &byte| {
+        CRC_TABLE[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 bytes is the most that can be summed before `b` could overflow.
+    for chunk in bytes.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= 65_521;
+        b %= 65_521;
+    }
+    (b << 16) | a
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (index, &byte)| {
+            n | (u32::from(byte) << (16 - 8 * index))
+        });
+        for index in 0..4 {
+            if index <= chunk.len() {
+                out.push(char::from(ALPHABET[(n >> (18 - 6 * index)) as usize & 63]));
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TermRenderConfig;
+    use crate::renderer::render_diagram_with_config;
+
+    const FLOW: &str = "flowchart LR\n  A[Start] --> B[Done]";
+
+    fn render(image_protocol: ImageProtocol) -> TermRenderResult {
+        let parsed = fm_parser::parse(FLOW);
+        let config = TermRenderConfig {
+            render_mode: MermaidRenderMode::Pixels,
+            image_protocol,
+            ..TermRenderConfig::rich()
+        };
+        render_diagram_with_config(&parsed.ir, &config, 80, 24)
+    }
+
+    #[test]
+    fn protocols_detect_from_terminal_variables() {
+        let detect = |vars: &[(&str, &str)]| {
+            ImageProtocol::detect_from(|name| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| (*value).to_string())
+            })
+        };
+        assert_eq!(detect(&[("TERM", "xterm-kitty")]), ImageProtocol::Kitty);
+        assert_eq!(
+            detect(&[("TERM_PROGRAM", "iTerm.app")]),
+            ImageProtocol::Iterm2
+        );
+        assert_eq!(detect(&[("TERM", "foot")]), ImageProtocol::Sixel);
+        assert_eq!(detect(&[("TERM", "xterm-256color")]), ImageProtocol::None);
+        assert_eq!(
+            detect(&[("TERM", "xterm-kitty"), ("FM_IMAGE_PROTOCOL", "sixel")]),
+            ImageProtocol::Sixel
+        );
+    }
+
+    #[test]
+    fn each_protocol_wraps_the_image_in_its_escape_sequence() {
+        let kitty = render(ImageProtocol::Kitty);
+        assert_eq!(kitty.render_mode, MermaidRenderMode::Pixels);
+        assert!(
+            kitty.output.starts_with("\x1b_Ga=T,f=32,"),
+            "{:?}",
+            &kitty.output[..40]
+        );
+        assert!(kitty.output.ends_with("\x1b\\"));
+        assert!(
+            kitty
+                .output
+                .contains(&format!(",c={},r={},", kitty.width, kitty.height))
+        );
+
+        let iterm = render(ImageProtocol::Iterm2);
+        assert!(iterm.output.starts_with("\x1b]1337;File=inline=1;"));
+        // Base64 of the PNG signature.
+        assert!(iterm.output.contains(":iVBORw0KGgo"));
+        assert!(iterm.output.ends_with('\x07'));
+
+        let sixel = render(ImageProtocol::Sixel);
+        assert!(sixel.output.starts_with("\x1bP0;1;0q\"1;1;"));
+        assert!(sixel.output.ends_with("-\x1b\\"));
+    }
+
+    #[test]
+    fn without_a_protocol_pixels_falls_back_to_the_cell_renderer() {
+        let result = render(ImageProtocol::None);
+        assert_ne!(result.render_mode, MermaidRenderMode::Pixels);
+        assert!(result.output.contains("Start"), "{}", result.output);
+        assert!(!result.output.contains('\x1b'));
+    }
+
+    #[test]
+    fn raster_draws_node_fills_and_label_text() {
+        let parsed = fm_parser::parse(FLOW);
+        let config = TermRenderConfig {
+            render_mode: MermaidRenderMode::Pixels,
+            image_protocol: ImageProtocol::Kitty,
+            ..TermRenderConfig::rich()
+        };
+        let result = render_diagram_with_config(&parsed.ir, &config, 80, 24);
+        // Decode the raw RGBA payload back out of the Kitty chunks.
+        let payload: String = result
+            .output
+            .split("\x1b\\")
+            .filter_map(|chunk| chunk.split_once(';').map(|(_, data)| data))
+            .collect();
+        let node = tint(TermColor::new(0x63, 0x66, 0xf1), 0.8);
+        let pixels = decode_base64(&payload);
+        let colors = pixels.as_chunks::<4>().0;
+        assert!(colors.contains(&[node.r, node.g, node.b, 0xff]));
+        assert!(colors.contains(&[INK.r, INK.g, INK.b, 0xff]));
+        assert!(
+            colors.contains(&[0, 0, 0, 0]),
+            "background stays transparent"
+        );
+    }
+
+    #[test]
+    fn png_and_base64_encoders_match_known_values() {
+        assert_eq!(base64(b"Man"), "TWFu");
+        assert_eq!(base64(b"Ma"), "TWE=");
+        assert_eq!(base64(b"M"), "TQ==");
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+
+        let png = encode_png(&RgbaImage::new(2, 1));
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[png.len() - 12..], b"\0\0\0\0IEND\xae\x42\x60\x82");
+    }
+
+    fn decode_base64(text: &str) -> Vec<u8> {
+        const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let values: Vec<u32> = text
+            .bytes()
+            .filter(|&byte| byte != b'=')
+            .filter_map(|byte| ALPHABET.find(char::from(byte)).map(|value| value as u32))
+            .collect();
+        let mut bytes = Vec::new();
+        for chunk in values.chunks(4) {
+            let n = chunk
+                .iter()
+                .enumerate()
+                .fold(0u32, |n, (index, value)| n | (value << (18 - 6 * index)));
+            for index in 0..chunk.len() - 1 {
+                bytes.push((n >> (16 - 8 * index)) as u8);
+            }
+        }
+        bytes
+    }
+}
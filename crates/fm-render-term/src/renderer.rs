@@ -1,14 +1,83 @@
 //! Core terminal diagram renderer.
 
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
 use fm_core::{
-    ArrowType, GanttTaskType, GraphDirection, MermaidDiagramIr, MermaidRenderMode, MermaidTier,
-    NodeShape,
+    ArrowType, GraphDirection, IrLabelSegment, MermaidDiagramIr, MermaidGlyphMode,
+    MermaidRenderMode, MermaidTier, NodeShape, NodeShapeRegistry,
 };
 use fm_layout::{DiagramLayout, LayoutClusterBox, LayoutEdgePath, LayoutNodeBox, layout_diagram};
 
-use crate::canvas::Canvas;
+use crate::canvas::{Canvas, CanvasLayer};
+use crate::color::{
+    ColorLayer, diagram_palette, edge_stroke, focused_nodes, node_stroke, node_text_color,
+};
 use crate::config::{ResolvedConfig, TermRenderConfig};
-use crate::glyphs::{BoxGlyphs, ClusterGlyphs, EdgeGlyphs};
+use crate::glyphs::{BoxGlyphs, ClusterGlyphs, EdgeGlyphs, EdgeStroke, icon_glyph, styled_arrow};
+use crate::text::{WIDE_TAIL, display_width, grapheme_columns};
+
+/// Cells are placed at `coordinate * scale`, so geometry left of or above the origin would be
+/// clamped onto column or row 0. Nodes start at the origin but the clusters around them are
+/// padded outwards; shift the whole drawing so the outermost cluster starts at 0 instead.
+fn clusters_anchored_at_origin(layout: &DiagramLayout) -> Cow<'_, DiagramLayout> {
+    let (min_x, min_y) = layout
+        .clusters
+        .iter()
+        .map(|cluster| cluster.bounds)
+        .chain(layout.cycle_clusters.iter().map(|cycle| cycle.bounds))
+        .fold((0.0_f32, 0.0_f32), |(x, y), bounds| {
+            (x.min(bounds.x), y.min(bounds.y))
+        });
+    if min_x >= 0.0 && min_y >= 0.0 {
+        return Cow::Borrowed(layout);
+    }
+    let (dx, dy) = (-min_x, -min_y);
+    let shift_rect = |rect: &mut fm_layout::LayoutRect| {
+        rect.x += dx;
+        rect.y += dy;
+    };
+    let shift_point = |point: &mut fm_layout::LayoutPoint| {
+        point.x += dx;
+        point.y += dy;
+    };
+    let mut shifted = layout.clone();
+    for node in &mut shifted.nodes {
+        shift_rect(&mut node.bounds);
+    }
+    for cluster in &mut shifted.clusters {
+        shift_rect(&mut cluster.bounds);
+    }
+    for cycle in &mut shifted.cycle_clusters {
+        shift_rect(&mut cycle.bounds);
+    }
+    for edge in &mut shifted.edges {
+        edge.points.iter_mut().for_each(shift_point);
+    }
+    let extensions = &mut shifted.extensions;
+    for divider in &mut extensions.cluster_dividers {
+        shift_point(&mut divider.start);
+        shift_point(&mut divider.end);
+    }
+    for note in &mut extensions.node_notes {
+        shift_rect(&mut note.bounds);
+        shift_point(&mut note.leader_from);
+        shift_point(&mut note.leader_to);
+    }
+    for header in &mut extensions.sequence_mirror_headers {
+        shift_rect(&mut header.bounds);
+    }
+    for marker in &mut extensions.sequence_lifecycle_markers {
+        shift_point(&mut marker.center);
+    }
+    Cow::Owned(shifted)
+}
+
+/// Smallest drawing area (inside padding) the compact tier can lay a diagram into. Smaller
+/// viewports get a summary card instead.
+pub const MIN_DIAGRAM_COLS: usize = 8;
+/// See [`MIN_DIAGRAM_COLS`].
+pub const MIN_DIAGRAM_ROWS: usize = 3;
 
 /// Result of terminal rendering.
 #[derive(Debug, Clone)]
@@ -27,19 +96,20 @@ pub struct TermRenderResult {
     pub node_count: usize,
     /// Edge count.
     pub edge_count: usize,
+    /// The viewport was below [`MIN_DIAGRAM_COLS`] x [`MIN_DIAGRAM_ROWS`], so `output` is a
+    /// summary card (diagram type and counts) rather than the diagram.
+    pub summary_only: bool,
 }
 
 /// Terminal diagram renderer.
 pub struct TermRenderer {
-    config: ResolvedConfig,
-    box_glyphs: BoxGlyphs,
-    edge_glyphs: EdgeGlyphs,
-    cluster_glyphs: ClusterGlyphs,
-}
-
-#[inline]
-fn compact_label_width(line: &str) -> usize {
-    line.chars().count()
+    pub(crate) config: ResolvedConfig,
+    pub(crate) box_glyphs: BoxGlyphs,
+    pub(crate) edge_glyphs: EdgeGlyphs,
+    pub(crate) cluster_glyphs: ClusterGlyphs,
+    shape_registry: NodeShapeRegistry,
+    focus_nodes: Vec<String>,
+    focus_hops: usize,
 }
 
 impl TermRenderer {
@@ -51,9 +121,27 @@ impl TermRenderer {
             edge_glyphs: EdgeGlyphs::for_mode(config.glyph_mode),
             cluster_glyphs: ClusterGlyphs::for_mode(config.glyph_mode),
             config,
+            shape_registry: NodeShapeRegistry::default(),
+            focus_nodes: Vec::new(),
+            focus_hops: 0,
         }
     }
 
+    /// Use `registry` for custom node shape borders.
+    #[must_use]
+    pub fn with_shape_registry(mut self, registry: NodeShapeRegistry) -> Self {
+        self.shape_registry = registry;
+        self
+    }
+
+    /// Dim everything but the nodes `ids` name and those within `hops` edges of them.
+    #[must_use]
+    pub fn with_focus(mut self, ids: Vec<String>, hops: usize) -> Self {
+        self.focus_nodes = ids;
+        self.focus_hops = hops;
+        self
+    }
+
     /// Render an IR diagram to terminal output.
     #[must_use]
     pub fn render(&self, ir: &MermaidDiagramIr) -> TermRenderResult {
@@ -64,6 +152,76 @@ impl TermRenderer {
     /// Render a pre-computed layout to terminal output.
     #[must_use]
     pub fn render_layout(&self, ir: &MermaidDiagramIr, layout: &DiagramLayout) -> TermRenderResult {
+        let result = self.render_diagram_grid(ir, layout);
+        let result = self.append_legend(ir, result);
+        self.append_link_footnotes(ir, result)
+    }
+
+    fn render_diagram_grid(
+        &self,
+        ir: &MermaidDiagramIr,
+        layout: &DiagramLayout,
+    ) -> TermRenderResult {
+        let padding_total = self.config.padding * 2;
+        if self.config.cols < padding_total + MIN_DIAGRAM_COLS
+            || self.config.rows < padding_total + MIN_DIAGRAM_ROWS
+        {
+            return self.render_summary_card(ir, layout);
+        }
+
+        if self.config.render_mode == MermaidRenderMode::Pixels
+            && let Some(result) = self.render_pixels(ir, layout)
+        {
+            return result;
+        }
+
+        if ir.diagram_type == fm_core::DiagramType::Sequence
+            && let Some(result) = self.render_sequence(ir, layout)
+        {
+            return result;
+        }
+        if ir.diagram_type == fm_core::DiagramType::Er
+            && let Some(result) = self.render_er(ir, layout)
+        {
+            return result;
+        }
+        if ir.diagram_type == fm_core::DiagramType::Class
+            && let Some(result) = self.render_class(ir, layout)
+        {
+            return result;
+        }
+        if ir.diagram_type == fm_core::DiagramType::Gantt
+            && let Some(result) = self.render_gantt(ir, layout)
+        {
+            return result;
+        }
+        if ir.diagram_type == fm_core::DiagramType::Mindmap
+            && let Some(result) = self.render_mindmap(ir, layout)
+        {
+            return result;
+        }
+        if ir.diagram_type == fm_core::DiagramType::GitGraph
+            && let Some(result) = self.render_gitgraph(ir, layout)
+        {
+            return result;
+        }
+        if ir.diagram_type == fm_core::DiagramType::Timeline
+            && let Some(result) = self.render_timeline(ir, layout)
+        {
+            return result;
+        }
+        if ir.diagram_type == fm_core::DiagramType::Journey
+            && let Some(result) = self.render_journey(ir, layout)
+        {
+            return result;
+        }
+        if ir.diagram_type == fm_core::DiagramType::XyChart
+            && let Some(result) = self.render_xychart(ir, layout)
+        {
+            return result;
+        }
+
+        let layout = &*clusters_anchored_at_origin(layout);
         let (cell_width, cell_height, scale_x, scale_y) =
             self.layout_to_cell_dimensions(&layout.bounds, ir.direction);
 
@@ -91,10 +249,10 @@ impl TermRenderer {
         // Create character buffer.
         let mut buffer = CellBuffer::new(cell_width, cell_height);
 
-        // Render clusters first (background).
+        // Render clusters first (background), enclosing ones before those nested in them.
         if self.config.show_clusters {
-            for cluster_box in &layout.clusters {
-                self.render_cluster_cell(&mut buffer, ir, cluster_box, scale_x, scale_y);
+            for (cluster_box, depth) in clusters_outermost_first(&layout.clusters) {
+                self.render_cluster_cell(&mut buffer, ir, cluster_box, depth, scale_x, scale_y);
             }
         }
 
@@ -120,23 +278,11 @@ impl TermRenderer {
             && let Some(pie_meta) = &ir.pie_meta
             && !pie_meta.slices.is_empty()
         {
-            self::render_pie_cell(&mut buffer, pie_meta, cell_width, cell_height);
-        } else if ir.diagram_type == fm_core::DiagramType::Gantt && ir.gantt_meta.is_some() {
-            render_gantt_cell(&mut buffer, ir, layout, cell_width, cell_height);
-        } else if ir.diagram_type == fm_core::DiagramType::XyChart && ir.xy_chart_meta.is_some() {
-            render_xychart_cell(&mut buffer, ir, cell_width, cell_height);
+            self::render_pie_cell(&mut buffer, ir, pie_meta, cell_width, cell_height);
         } else if ir.diagram_type == fm_core::DiagramType::QuadrantChart
             && ir.quadrant_meta.is_some()
         {
-            render_quadrant_cell(
-                &mut buffer,
-                ir,
-                layout,
-                cell_width,
-                cell_height,
-                scale_x,
-                scale_y,
-            );
+            render_quadrant_cell(&mut buffer, ir, cell_width, cell_height);
         } else {
             // Render nodes (foreground).
             for node_box in &layout.nodes {
@@ -146,9 +292,27 @@ impl TermRenderer {
                 self.render_node_cell(&mut buffer, ir, node_box, scale_x, scale_y);
             }
         }
+        // Heads go over the nodes, in the cell just outside the border the edge ends on.
+        for edge_path in &layout.edges {
+            self.render_edge_heads_cell(&mut buffer, ir, edge_path, scale_x, scale_y);
+        }
+        self.draw_node_notes(&mut buffer, layout, cell_width, scale_x, scale_y);
+
+        let placements =
+            self.edge_label_placements(ir, layout, (cell_width, cell_height), (scale_x, scale_y));
+        for placement in &placements {
+            placement.draw(&mut buffer, cell_width);
+        }
 
-        self.render_generic_diagram_title(&mut buffer.cells, cell_width, ir);
-        let output = buffer.to_output_string();
+        self.render_generic_diagram_title(&mut buffer, ir);
+        let output = self.paint_colors(
+            ir,
+            layout,
+            buffer.to_output_string(),
+            (cell_width, cell_height),
+            (scale_x, scale_y),
+        );
+        let output = self.link_nodes(ir, layout, output, (scale_x, scale_y));
 
         TermRenderResult {
             output,
@@ -158,6 +322,7 @@ impl TermRenderer {
             render_mode: self.config.render_mode,
             node_count: layout.nodes.len(),
             edge_count: layout.edges.len(),
+            summary_only: false,
         }
     }
 
@@ -181,12 +346,15 @@ impl TermRenderer {
         let padding_x = self.config.padding * mult_x;
         let padding_y = self.config.padding * mult_y;
 
-        // Render clusters.
+        // Each kind of element gets its own layer, so where they share a cell only the topmost
+        // one's dots are drawn.
+        canvas.set_layer(CanvasLayer::Clusters);
         if self.config.show_clusters {
-            for cluster_box in &layout.clusters {
+            for (cluster_box, depth) in clusters_outermost_first(&layout.clusters) {
                 self.render_cluster_canvas(
                     &mut canvas,
                     cluster_box,
+                    depth,
                     pixel_scale_x,
                     pixel_scale_y,
                     padding_x,
@@ -196,6 +364,7 @@ impl TermRenderer {
         }
 
         // Render layout bands based on their kind.
+        canvas.set_layer(CanvasLayer::Background);
         for band in &layout.extensions.bands {
             use fm_layout::LayoutBandKind;
             let bx = (band.bounds.x * pixel_scale_x) as isize + padding_x as isize;
@@ -258,6 +427,7 @@ impl TermRenderer {
             }
         }
 
+        canvas.set_layer(CanvasLayer::Overlay);
         for marker in &layout.extensions.sequence_lifecycle_markers {
             match marker.kind {
                 fm_layout::LayoutSequenceLifecycleMarkerKind::Destroy => {
@@ -272,9 +442,11 @@ impl TermRenderer {
         }
 
         // Render edges.
+        canvas.set_layer(CanvasLayer::Edges);
         for edge_path in &layout.edges {
             self.render_edge_canvas(
                 &mut canvas,
+                ir,
                 edge_path,
                 pixel_scale_x,
                 pixel_scale_y,
@@ -284,6 +456,7 @@ impl TermRenderer {
         }
 
         // Render nodes.
+        canvas.set_layer(CanvasLayer::Nodes);
         for node_box in &layout.nodes {
             self.render_node_canvas(
                 &mut canvas,
@@ -319,6 +492,14 @@ impl TermRenderer {
             scale_x,
             scale_y,
         );
+        let output = self.paint_colors(
+            ir,
+            layout,
+            output,
+            (cell_width, cell_height),
+            (scale_x, scale_y),
+        );
+        let output = self.link_nodes(ir, layout, output, (scale_x, scale_y));
 
         TermRenderResult {
             output,
@@ -328,6 +509,73 @@ impl TermRenderer {
             render_mode: self.config.render_mode,
             node_count: layout.nodes.len(),
             edge_count: layout.edges.len(),
+            summary_only: false,
+        }
+    }
+
+    /// Minimum-viable output for viewports too small to draw into: the diagram type and node and
+    /// edge counts, boxed when there is room, never larger than `cols` x `rows`.
+    fn render_summary_card(
+        &self,
+        ir: &MermaidDiagramIr,
+        layout: &DiagramLayout,
+    ) -> TermRenderResult {
+        let (cols, rows) = (self.config.cols, self.config.rows);
+        let node_count = layout.nodes.len();
+        let edge_count = layout.edges.len();
+        let mut buffer = CellBuffer::new(cols, rows);
+
+        let inset = usize::from(cols >= 3 && rows >= 3);
+        if inset == 1 {
+            let glyphs = &self.box_glyphs;
+            for x in 1..cols - 1 {
+                buffer.set(x, 0, glyphs.horizontal);
+                buffer.set(x, rows - 1, glyphs.horizontal);
+            }
+            for y in 1..rows - 1 {
+                buffer.set(0, y, glyphs.vertical);
+                buffer.set(cols - 1, y, glyphs.vertical);
+            }
+            buffer.set(0, 0, glyphs.top_left);
+            buffer.set(cols - 1, 0, glyphs.top_right);
+            buffer.set(0, rows - 1, glyphs.bottom_left);
+            buffer.set(cols - 1, rows - 1, glyphs.bottom_right);
+        }
+        let inner_cols = cols.saturating_sub(inset * 2);
+        let inner_rows = rows.saturating_sub(inset * 2);
+
+        // Counts outrank the diagram type when rows are scarce.
+        let nodes = format!("{node_count} node{}", plural_suffix(node_count));
+        let edges = format!("{edge_count} edge{}", plural_suffix(edge_count));
+        let lines = match inner_rows {
+            0 => Vec::new(),
+            1 => vec![format!("{node_count}n {edge_count}e")],
+            2 => vec![nodes, edges],
+            _ => vec![ir.diagram_type.as_str().to_string(), nodes, edges],
+        };
+        for (row, line) in lines.iter().enumerate() {
+            let clipped: String = if line.chars().count() <= inner_cols {
+                line.clone()
+            } else if inner_cols >= 2 {
+                line.chars()
+                    .take(inner_cols - 1)
+                    .chain(std::iter::once('…'))
+                    .collect()
+            } else {
+                line.chars().take(inner_cols).collect()
+            };
+            buffer.set_string(inset, inset + row, &clipped);
+        }
+
+        TermRenderResult {
+            output: buffer.to_output_string(),
+            width: cols,
+            height: rows,
+            tier: MermaidTier::Compact,
+            render_mode: MermaidRenderMode::CellOnly,
+            node_count,
+            edge_count,
+            summary_only: true,
         }
     }
 
@@ -360,17 +608,21 @@ impl TermRenderer {
             ),
         };
 
-        // Calculate fitted scale factors for the diagram content.
+        // Calculate fitted scale factors for the diagram content, then zoom them. A zoomed-in
+        // diagram keeps the full terminal size and is cropped; a zoomed-out one shrinks.
+        let zoom = self.config.zoom;
         let scale_x = if bounds.width > 0.0 {
-            width as f32 / bounds.width
+            width as f32 / bounds.width * zoom
         } else {
             1.0
         };
         let scale_y = if bounds.height > 0.0 {
-            height as f32 / bounds.height
+            height as f32 / bounds.height * zoom
         } else {
             1.0
         };
+        let width = ((width as f32 * zoom).round() as usize).clamp(1, max_width);
+        let height = ((height as f32 * zoom).round() as usize).clamp(1, max_height);
 
         (
             width.saturating_add(padding_total),
@@ -385,6 +637,7 @@ impl TermRenderer {
         buffer: &mut CellBuffer,
         ir: &MermaidDiagramIr,
         cluster_box: &LayoutClusterBox,
+        depth: usize,
         scale_x: f32,
         scale_y: f32,
     ) {
@@ -393,29 +646,44 @@ impl TermRenderer {
             return;
         }
 
-        let glyphs = &self.cluster_glyphs;
+        let glyphs = ClusterGlyphs::for_depth(self.config.glyph_mode, depth);
+        // Enclosing clusters are drawn first; keep their corners when borders meet.
+        let mut put = |x: usize, y: usize, ch: char| {
+            if !buffer.get(x, y).is_some_and(ClusterGlyphs::is_corner) {
+                buffer.set(x, y, ch);
+            }
+        };
 
-        // Top border.
-        buffer.set(x, y, glyphs.corner_tl);
+        // Top and bottom borders.
         for dx in 1..w - 1 {
-            buffer.set(x + dx, y, glyphs.border_h);
+            put(x + dx, y, glyphs.border_h);
+            put(x + dx, y + h - 1, glyphs.border_h);
         }
-        buffer.set(x + w - 1, y, glyphs.corner_tr);
 
         // Side borders.
         for dy in 1..h - 1 {
-            buffer.set(x, y + dy, glyphs.border_v);
-            buffer.set(x + w - 1, y + dy, glyphs.border_v);
+            put(x, y + dy, glyphs.border_v);
+            put(x + w - 1, y + dy, glyphs.border_v);
         }
 
-        // Bottom border.
-        buffer.set(x, y + h - 1, glyphs.corner_bl);
-        for dx in 1..w - 1 {
-            buffer.set(x + dx, y + h - 1, glyphs.border_h);
-        }
-        buffer.set(x + w - 1, y + h - 1, glyphs.corner_br);
+        put(x, y, glyphs.corner_tl);
+        put(x + w - 1, y, glyphs.corner_tr);
+        put(x, y + h - 1, glyphs.corner_bl);
+        put(x + w - 1, y + h - 1, glyphs.corner_br);
 
-        // Cluster title if available.
+        self.draw_cluster_title(buffer, ir, cluster_box, &glyphs, (x, y, w));
+    }
+
+    /// Write the cluster's title into its top border, centered between the corners and set off
+    /// by the title brackets when there is room for them.
+    fn draw_cluster_title(
+        &self,
+        buffer: &mut CellBuffer,
+        ir: &MermaidDiagramIr,
+        cluster_box: &LayoutClusterBox,
+        glyphs: &ClusterGlyphs,
+        (x, y, w): (usize, usize, usize),
+    ) {
         let title_text = cluster_box.title.as_deref().or_else(|| {
             ir.clusters
                 .get(cluster_box.cluster_index)
@@ -423,12 +691,24 @@ impl TermRenderer {
                 .and_then(|label_id| ir.labels.get(label_id.0))
                 .map(|label| label.text.as_str())
         });
+        let Some(title_text) = title_text else {
+            return;
+        };
 
-        if let Some(title_text) = title_text {
-            let title = self.truncate_label(title_text);
-            let title_x = x + 2;
-            buffer.set_string(title_x, y, &title);
+        // One border cell stays on either side, plus `┤ ` and ` ├` when they fit.
+        let bracketed = w >= 7;
+        let room = w.saturating_sub(if bracketed { 6 } else { 2 });
+        if room == 0 {
+            return;
         }
+        let fitted = self.truncate_label_to(title_text, room.min(self.config.max_label_chars), 1);
+        let text = if bracketed {
+            format!("{} {fitted} {}", glyphs.title_left, glyphs.title_right)
+        } else {
+            fitted
+        };
+        let start = x + (w - display_width(&text)) / 2;
+        buffer.put_str(start, y, &text, x + w - 1);
     }
 
     fn render_edge_cell(
@@ -445,11 +725,11 @@ impl TermRenderer {
 
         let glyphs = &self.edge_glyphs;
 
-        // Get arrow type for this edge.
+        // Get arrow type for this edge, thickened by a wide `linkStyle` stroke.
         let arrow = ir
             .edges
             .get(edge_path.edge_index)
-            .map(|e| e.arrow)
+            .map(styled_arrow)
             .unwrap_or(ArrowType::Arrow);
 
         // Draw line segments.
@@ -458,39 +738,77 @@ impl TermRenderer {
             let (x1, y1) = self.point_to_cells(&window[1], scale_x, scale_y);
             self.draw_line_cell(buffer, x0, y0, x1, y1, glyphs, edge_path.reversed, arrow);
         }
+    }
 
-        // Draw arrowhead at start for double arrows.
-        if matches!(
-            arrow,
-            ArrowType::DoubleArrow | ArrowType::DoubleThickArrow | ArrowType::DoubleDottedArrow
-        ) && let Some(first) = edge_path.points.first()
+    /// Draw an edge's heads one cell back from its endpoints, so they sit outside the node
+    /// borders the endpoints land on.
+    fn render_edge_heads_cell(
+        &self,
+        buffer: &mut CellBuffer,
+        ir: &MermaidDiagramIr,
+        edge_path: &LayoutEdgePath,
+        scale_x: f32,
+        scale_y: f32,
+    ) {
+        if edge_path.points.len() < 2 {
+            return;
+        }
+        let arrow = ir
+            .edges
+            .get(edge_path.edge_index)
+            .map(styled_arrow)
+            .unwrap_or(ArrowType::Arrow);
+        for (x, y, head) in self
+            .edge_heads(edge_path, arrow, scale_x, scale_y, true)
+            .into_iter()
+            .flatten()
         {
-            let (x, y) = self.point_to_cells(first, scale_x, scale_y);
-            if edge_path.points.len() >= 2 {
-                let next = &edge_path.points[1];
-                let (nx, ny) = self.point_to_cells(next, scale_x, scale_y);
-                let arrow_char = self.arrowhead_for_direction(nx, ny, x, y, glyphs, arrow);
-                buffer.set(x, y, arrow_char);
-            }
-        }
-
-        // Draw arrowhead at end.
-        if let Some(last) = edge_path.points.last() {
-            let (x, y) = self.point_to_cells(last, scale_x, scale_y);
-            let arrow_char = if edge_path.points.len() >= 2 {
-                let prev = &edge_path.points[edge_path.points.len() - 2];
-                let (px, py) = self.point_to_cells(prev, scale_x, scale_y);
-                self.arrowhead_for_direction(px, py, x, y, glyphs, arrow)
+            buffer.set(x, y, head);
+        }
+    }
+
+    /// Head glyphs of an edge with at least two points, in cells: one at the start for double
+    /// arrows, one at the end unless the edge is a plain line. With `back_off`, each head moves
+    /// one cell from its endpoint toward the rest of the edge.
+    fn edge_heads(
+        &self,
+        edge_path: &LayoutEdgePath,
+        arrow: ArrowType,
+        scale_x: f32,
+        scale_y: f32,
+        back_off: bool,
+    ) -> [Option<(usize, usize, char)>; 2] {
+        let points = &edge_path.points;
+        let head_at = |tip: &fm_layout::LayoutPoint, from: &fm_layout::LayoutPoint| {
+            let (x, y) = self.point_to_cells(tip, scale_x, scale_y);
+            let (fx, fy) = self.point_to_cells(from, scale_x, scale_y);
+            let (dx, dy) = (x as isize - fx as isize, y as isize - fy as isize);
+            let (x, y) = if back_off && dx.abs().max(dy.abs()) > 1 {
+                if dx.abs() >= dy.abs() {
+                    (x.saturating_add_signed(-dx.signum()), y)
+                } else {
+                    (x, y.saturating_add_signed(-dy.signum()))
+                }
             } else {
-                glyphs.arrow_right
+                (x, y)
             };
-            if !matches!(
-                arrow,
-                ArrowType::Line | ArrowType::ThickLine | ArrowType::DottedLine
-            ) {
-                buffer.set(x, y, arrow_char);
-            }
-        }
+            self.edge_glyphs
+                .head(arrow, dx, dy)
+                .map(|head| (x, y, head))
+        };
+        let double = matches!(
+            arrow,
+            ArrowType::DoubleArrow | ArrowType::DoubleThickArrow | ArrowType::DoubleDottedArrow
+        );
+        let start = if double {
+            head_at(&points[0], &points[1])
+        } else {
+            None
+        };
+        [
+            start,
+            head_at(&points[points.len() - 1], &points[points.len() - 2]),
+        ]
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -505,27 +823,15 @@ impl TermRenderer {
         reversed: bool,
         arrow: ArrowType,
     ) {
-        let line_char = if reversed
-            || matches!(
-                arrow,
-                ArrowType::DottedArrow
-                    | ArrowType::DottedOpenArrow
-                    | ArrowType::HalfArrowTopDotted
-                    | ArrowType::HalfArrowBottomDotted
-                    | ArrowType::HalfArrowTopReverseDotted
-                    | ArrowType::HalfArrowBottomReverseDotted
-                    | ArrowType::StickArrowTopDotted
-                    | ArrowType::StickArrowBottomDotted
-                    | ArrowType::StickArrowTopReverseDotted
-                    | ArrowType::StickArrowBottomReverseDotted
-                    | ArrowType::DottedLine
-                    | ArrowType::DoubleDottedArrow
-            ) {
-            if x0 == x1 {
-                glyphs.dotted_v
-            } else {
-                glyphs.dotted_h
-            }
+        // Back edges are drawn dotted whatever their own style.
+        let stroke = if reversed {
+            EdgeStroke::Dotted
+        } else {
+            EdgeStroke::of(arrow)
+        };
+        let line_char = if stroke != EdgeStroke::Solid {
+            let (horizontal, vertical) = glyphs.lines(stroke);
+            if x0 == x1 { vertical } else { horizontal }
         } else if x0 == x1 {
             glyphs.line_v
         } else if y0 == y1 {
@@ -578,37 +884,6 @@ impl TermRenderer {
         }
     }
 
-    fn arrowhead_for_direction(
-        &self,
-        from_x: usize,
-        from_y: usize,
-        to_x: usize,
-        to_y: usize,
-        glyphs: &EdgeGlyphs,
-        arrow: ArrowType,
-    ) -> char {
-        let dx = to_x as isize - from_x as isize;
-        let dy = to_y as isize - from_y as isize;
-
-        match arrow {
-            ArrowType::Circle => glyphs.circle_head,
-            ArrowType::Cross | ArrowType::DottedCross => glyphs.cross_head,
-            _ => {
-                if dx.abs() > dy.abs() {
-                    if dx > 0 {
-                        glyphs.arrow_right
-                    } else {
-                        glyphs.arrow_left
-                    }
-                } else if dy > 0 {
-                    glyphs.arrow_down
-                } else {
-                    glyphs.arrow_up
-                }
-            }
-        }
-    }
-
     fn render_node_cell(
         &self,
         buffer: &mut CellBuffer,
@@ -630,8 +905,22 @@ impl TermRenderer {
         // Get node shape.
         let shape = ir_node.map(|n| n.shape).unwrap_or(NodeShape::Rect);
 
-        // Draw shape border.
-        self.draw_shape_border(buffer, x, y, w, h, shape);
+        // Draw shape border, preferring a registered custom decorator.
+        let custom_border = ir_node
+            .and_then(|node| self.shape_registry.resolve(node))
+            .and_then(|drawer| drawer.terminal_border(w, h));
+        if let Some(border) = custom_border {
+            for (dx, dy, glyph) in border {
+                if dx < w && dy < h {
+                    buffer.set(x + dx, y + dy, glyph);
+                }
+            }
+        } else {
+            self.draw_shape_border(buffer, x, y, w, h, shape);
+        }
+        if let Some(node) = ir_node {
+            self.draw_node_icon(buffer, node, (x, y, w), usize::MAX);
+        }
 
         // Get label.
         let Some(label) = self.node_display_label(ir, ir_node, &node_box.node_id) else {
@@ -643,7 +932,7 @@ impl TermRenderer {
         let start_y = y + (h.saturating_sub(lines.len())) / 2;
 
         for (i, line) in lines.iter().enumerate() {
-            let label_len = compact_label_width(line);
+            let label_len = display_width(line);
             let label_x = x + (w.saturating_sub(label_len)) / 2;
             buffer.set_string(label_x, start_y + i, line);
         }
@@ -875,10 +1164,12 @@ impl TermRenderer {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn render_cluster_canvas(
         &self,
         canvas: &mut Canvas,
         cluster_box: &LayoutClusterBox,
+        depth: usize,
         scale_x: f32,
         scale_y: f32,
         padding_x: usize,
@@ -889,26 +1180,61 @@ impl TermRenderer {
         let w = (cluster_box.bounds.width * scale_x) as usize;
         let h = (cluster_box.bounds.height * scale_y) as usize;
 
-        if w > 2 && h > 2 {
+        if w <= 2 || h <= 2 {
+            return;
+        }
+        if depth == 0 {
             canvas.draw_rect(x, y, w, h);
+        } else {
+            // Nested clusters get a dashed outline.
+            let (x0, y0) = (x as isize, y as isize);
+            let (x1, y1) = ((x + w - 1) as isize, (y + h - 1) as isize);
+            canvas.draw_dashed_line(x0, y0, x1, y0, 2);
+            canvas.draw_dashed_line(x0, y1, x1, y1, 2);
+            canvas.draw_dashed_line(x0, y0, x0, y1, 2);
+            canvas.draw_dashed_line(x1, y0, x1, y1, 2);
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn render_edge_canvas(
         &self,
         canvas: &mut Canvas,
+        ir: &MermaidDiagramIr,
         edge_path: &LayoutEdgePath,
         scale_x: f32,
         scale_y: f32,
         padding_x: usize,
         padding_y: usize,
     ) {
+        let stroke = ir
+            .edges
+            .get(edge_path.edge_index)
+            .map_or(EdgeStroke::Solid, |edge| EdgeStroke::of(styled_arrow(edge)));
         for window in edge_path.points.windows(2) {
-            let x0 = (window[0].x * scale_x) as isize + padding_x as isize;
-            let y0 = (window[0].y * scale_y) as isize + padding_y as isize;
-            let x1 = (window[1].x * scale_x) as isize + padding_x as isize;
-            let y1 = (window[1].y * scale_y) as isize + padding_y as isize;
-            canvas.draw_line(x0, y0, x1, y1);
+            let (fx0, fy0) = (window[0].x * scale_x, window[0].y * scale_y);
+            let (fx1, fy1) = (window[1].x * scale_x, window[1].y * scale_y);
+            let x0 = fx0 as isize + padding_x as isize;
+            let y0 = fy0 as isize + padding_y as isize;
+            let x1 = fx1 as isize + padding_x as isize;
+            let y1 = fy1 as isize + padding_y as isize;
+            if self.config.smooth_edges && stroke != EdgeStroke::Dotted {
+                // Keep the sub-pixel endpoints the truncating casts above throw away.
+                let (px, py) = (padding_x as f32, padding_y as f32);
+                canvas.draw_smooth_line(
+                    fx0 + px,
+                    fy0 + py,
+                    fx1 + px,
+                    fy1 + py,
+                    stroke == EdgeStroke::Thick,
+                );
+                continue;
+            }
+            match stroke {
+                EdgeStroke::Solid => canvas.draw_line(x0, y0, x1, y1),
+                EdgeStroke::Dotted => canvas.draw_dashed_line(x0, y0, x1, y1, 2),
+                EdgeStroke::Thick => canvas.draw_thick_line(x0, y0, x1, y1),
+            }
         }
     }
 
@@ -935,91 +1261,77 @@ impl TermRenderer {
 
         let shape = ir_node.map(|n| n.shape).unwrap_or(NodeShape::Rect);
 
-        match shape {
+        let left = x as isize;
+        let top = y as isize;
+        let right = (x + w) as isize;
+        let bottom = (y + h) as isize;
+        let mid_x = (x + w / 2) as isize;
+        let mid_y = (y + h / 2) as isize;
+        let inset = (w as f32 * 0.15) as isize;
+
+        // Polygonal shapes are drawn and occluded from their outline; the rest return early.
+        let outline = match shape {
             NodeShape::Circle | NodeShape::DoubleCircle => {
                 let radius = w.min(h) / 2;
                 let cx = x + w / 2;
                 let cy = y + h / 2;
                 canvas.draw_circle(cx as isize, cy as isize, radius as isize);
+                if shape == NodeShape::DoubleCircle && radius > 3 {
+                    canvas.draw_circle(cx as isize, cy as isize, radius as isize - 2);
+                }
+                canvas.occlude_ellipse(cx as f32, cy as f32, radius as f32, radius as f32);
+                return;
             }
             NodeShape::Diamond => {
-                // Draw diamond as four lines.
-                let mid_x = (x + w / 2) as isize;
-                let mid_y = (y + h / 2) as isize;
-                let top = y as isize;
-                let bottom = (y + h) as isize;
-                let left = x as isize;
-                let right = (x + w) as isize;
-                canvas.draw_line(mid_x, top, right, mid_y);
-                canvas.draw_line(right, mid_y, mid_x, bottom);
-                canvas.draw_line(mid_x, bottom, left, mid_y);
-                canvas.draw_line(left, mid_y, mid_x, top);
-            }
-            NodeShape::Parallelogram => {
-                let inset = (w as f32 * 0.15) as isize;
-                let top = y as isize;
-                let bottom = (y + h) as isize;
-                let left = x as isize;
-                let right = (x + w) as isize;
-                canvas.draw_line(left + inset, top, right, top);
-                canvas.draw_line(right, top, right - inset, bottom);
-                canvas.draw_line(right - inset, bottom, left, bottom);
-                canvas.draw_line(left, bottom, left + inset, top);
-            }
-            NodeShape::InvParallelogram => {
-                let inset = (w as f32 * 0.15) as isize;
-                let top = y as isize;
-                let bottom = (y + h) as isize;
-                let left = x as isize;
-                let right = (x + w) as isize;
-                canvas.draw_line(left, top, right - inset, top);
-                canvas.draw_line(right - inset, top, right, bottom);
-                canvas.draw_line(right, bottom, left + inset, bottom);
-                canvas.draw_line(left + inset, bottom, left, top);
-            }
-            NodeShape::Trapezoid => {
-                let inset = (w as f32 * 0.15) as isize;
-                let top = y as isize;
-                let bottom = (y + h) as isize;
-                let left = x as isize;
-                let right = (x + w) as isize;
-                canvas.draw_line(left + inset, top, right - inset, top);
-                canvas.draw_line(right - inset, top, right, bottom);
-                canvas.draw_line(right, bottom, left, bottom);
-                canvas.draw_line(left, bottom, left + inset, top);
-            }
-            NodeShape::InvTrapezoid => {
-                let inset = (w as f32 * 0.15) as isize;
-                let top = y as isize;
-                let bottom = (y + h) as isize;
-                let left = x as isize;
-                let right = (x + w) as isize;
-                canvas.draw_line(left, top, right, top);
-                canvas.draw_line(right, top, right - inset, bottom);
-                canvas.draw_line(right - inset, bottom, left + inset, bottom);
-                canvas.draw_line(left + inset, bottom, left, top);
-            }
-            NodeShape::Hexagon => {
-                let inset = (w as f32 * 0.15) as isize;
-                let top = y as isize;
-                let bottom = (y + h) as isize;
-                let left = x as isize;
-                let right = (x + w) as isize;
-                let mid_y = (y + h / 2) as isize;
-                canvas.draw_line(left + inset, top, right - inset, top);
-                canvas.draw_line(right - inset, top, right, mid_y);
-                canvas.draw_line(right, mid_y, right - inset, bottom);
-                canvas.draw_line(right - inset, bottom, left + inset, bottom);
-                canvas.draw_line(left + inset, bottom, left, mid_y);
-                canvas.draw_line(left, mid_y, left + inset, top);
+                vec![(mid_x, top), (right, mid_y), (mid_x, bottom), (left, mid_y)]
             }
+            NodeShape::Parallelogram => vec![
+                (left + inset, top),
+                (right, top),
+                (right - inset, bottom),
+                (left, bottom),
+            ],
+            NodeShape::InvParallelogram => vec![
+                (left, top),
+                (right - inset, top),
+                (right, bottom),
+                (left + inset, bottom),
+            ],
+            NodeShape::Trapezoid => vec![
+                (left + inset, top),
+                (right - inset, top),
+                (right, bottom),
+                (left, bottom),
+            ],
+            NodeShape::InvTrapezoid => vec![
+                (left, top),
+                (right, top),
+                (right - inset, bottom),
+                (left + inset, bottom),
+            ],
+            NodeShape::Hexagon => vec![
+                (left + inset, top),
+                (right - inset, top),
+                (right, mid_y),
+                (right - inset, bottom),
+                (left + inset, bottom),
+                (left, mid_y),
+            ],
             NodeShape::Rounded | NodeShape::Stadium | NodeShape::Cloud => {
-                // Rounded rectangle: draw rect + round the corners with arcs.
-                canvas.draw_rect(x, y, w.max(1), h.max(1));
+                // Stadiums are capped with half circles, rounded boxes with smaller arcs.
+                let radius = if shape == NodeShape::Stadium {
+                    h / 2
+                } else {
+                    w.min(h) / 4
+                };
+                canvas.draw_rounded_rect(x, y, w.max(1), h.max(1), radius);
+                canvas.occlude_rect(x, y, w.max(1), h.max(1));
+                return;
             }
             NodeShape::Subroutine => {
                 // Double-bordered rectangle.
                 canvas.draw_rect(x, y, w.max(1), h.max(1));
+                canvas.occlude_rect(x, y, w.max(1), h.max(1));
                 if w > 4 {
                     let inner_x = x + 2;
                     canvas.draw_line(
@@ -1036,67 +1348,60 @@ impl TermRenderer {
                         (y + h) as isize,
                     );
                 }
+                return;
             }
             NodeShape::Asymmetric | NodeShape::Tag => {
                 // Flag shape: rect with pointed right side.
-                let top = y as isize;
-                let bottom = (y + h) as isize;
-                let left = x as isize;
-                let right = (x + w) as isize;
-                let mid_y = (y + h / 2) as isize;
                 let point = (w as f32 * 0.2) as isize;
-                canvas.draw_line(left, top, right - point, top);
-                canvas.draw_line(right - point, top, right, mid_y);
-                canvas.draw_line(right, mid_y, right - point, bottom);
-                canvas.draw_line(right - point, bottom, left, bottom);
-                canvas.draw_line(left, bottom, left, top);
-            }
-            NodeShape::Cylinder => {
-                // Database shape: rect with elliptical top.
-                canvas.draw_rect(x, y, w.max(1), h.max(1));
-                // Draw second horizontal line near top to suggest cylinder cap.
-                if h > 3 {
-                    canvas.draw_line(
-                        x as isize,
-                        (y + 2) as isize,
-                        (x + w) as isize,
-                        (y + 2) as isize,
-                    );
-                }
-            }
-            NodeShape::Triangle => {
-                let mid_x = (x + w / 2) as isize;
-                let top = y as isize;
-                let bottom = (y + h) as isize;
-                let left = x as isize;
-                let right = (x + w) as isize;
-                canvas.draw_line(mid_x, top, right, bottom);
-                canvas.draw_line(right, bottom, left, bottom);
-                canvas.draw_line(left, bottom, mid_x, top);
+                vec![
+                    (left, top),
+                    (right - point, top),
+                    (right, mid_y),
+                    (right - point, bottom),
+                    (left, bottom),
+                ]
+            }
+            NodeShape::Cylinder if w > 2 && h > 5 => {
+                // Database shape: elliptical top cap, sides, and the front half of the base.
+                let rx = (w - 1) as f32 / 2.0;
+                let ry = (h as f32 / 8.0).max(1.0);
+                let cx = x as f32 + rx;
+                let cap_y = y as f32 + ry;
+                let base_y = (y + h - 1) as f32 - ry;
+                canvas.draw_ellipse(cx, cap_y, rx, ry);
+                canvas.draw_line(left, cap_y as isize, left, base_y as isize);
+                let side = (x + w - 1) as isize;
+                canvas.draw_line(side, cap_y as isize, side, base_y as isize);
+                canvas.draw_arc(cx, base_y, rx, ry, 0.0, std::f32::consts::PI);
+                canvas.occlude_rect(x, y, w, h);
+                return;
             }
+            NodeShape::Triangle => vec![(mid_x, top), (right, bottom), (left, bottom)],
             NodeShape::Note => {
                 // Rectangle with folded corner.
                 let fold = (w.min(h) as f32 * 0.2) as isize;
-                let top = y as isize;
-                let bottom = (y + h) as isize;
-                let left = x as isize;
-                let right = (x + w) as isize;
-                canvas.draw_line(left, top, right - fold, top);
-                canvas.draw_line(right - fold, top, right, top + fold);
-                canvas.draw_line(right, top + fold, right, bottom);
-                canvas.draw_line(right, bottom, left, bottom);
-                canvas.draw_line(left, bottom, left, top);
+                vec![
+                    (left, top),
+                    (right - fold, top),
+                    (right, top + fold),
+                    (right, bottom),
+                    (left, bottom),
+                ]
             }
             _ => {
                 canvas.draw_rect(x, y, w.max(1), h.max(1));
+                canvas.occlude_rect(x, y, w.max(1), h.max(1));
+                return;
             }
-        }
+        };
+        canvas.draw_polygon(&outline);
+        canvas.occlude_polygon(&outline);
     }
 
     #[allow(clippy::too_many_arguments)]
     fn overlay_labels(
         &self,
-        mut lines: Vec<Vec<char>>,
+        lines: Vec<Vec<char>>,
         ir: &MermaidDiagramIr,
         layout: &DiagramLayout,
         cell_width: usize,
@@ -1106,64 +1411,50 @@ impl TermRenderer {
     ) -> String {
         // `lines` arrives as the canvas's char grid (`Canvas::render_char_grid`) — one row per cell
         // row — instead of a rendered `String` this fn used to re-parse with `lines().chars().collect()`.
-        // Skips a full encode+decode of the whole raster.
-
-        // Pad lines to consistent width.
-        for line in &mut lines {
-            while line.len() < cell_width {
-                line.push(' ');
+        // Skips a full encode+decode of the whole raster. Rows are padded to a consistent width.
+        let width = lines
+            .iter()
+            .map(Vec::len)
+            .max()
+            .unwrap_or(0)
+            .max(cell_width);
+        let height = lines.len().max(cell_height);
+        let mut buffer = CellBuffer::from_rows(lines, width, height);
+
+        // Overlay cluster titles.
+        if self.config.show_clusters {
+            for (cluster_box, depth) in clusters_outermost_first(&layout.clusters) {
+                let (x, y, w, _) = self.bounds_to_cells(&cluster_box.bounds, scale_x, scale_y);
+                let glyphs = ClusterGlyphs::for_depth(self.config.glyph_mode, depth);
+                self.draw_cluster_title(&mut buffer, ir, cluster_box, &glyphs, (x, y, w));
             }
         }
-        while lines.len() < cell_height {
-            lines.push(vec![' '; cell_width]);
-        }
-
-        // Overlay node labels.
-        for node_box in &layout.nodes {
-            let (x, y, w, h) = self.bounds_to_cells(&node_box.bounds, scale_x, scale_y);
-            let ir_node = ir.nodes.get(node_box.node_index);
 
-            if ir_node.is_some_and(is_block_beta_space_node) {
+        // Overlay edge heads.
+        for edge_path in &layout.edges {
+            let Some(edge) = ir.edges.get(edge_path.edge_index) else {
                 continue;
-            }
-
-            // Class diagram nodes with class_meta get three-compartment rendering.
-            if let Some(node) = ir_node
-                && let Some(ref meta) = node.class_meta
-                && (!meta.attributes.is_empty() || !meta.methods.is_empty())
-            {
-                self.overlay_class_compartments(&mut lines, x, y, w, h, ir, node, meta, cell_width);
+            };
+            if edge_path.points.len() < 2 {
                 continue;
             }
-
-            let Some(label) = self.node_display_label(ir, ir_node, &node_box.node_id) else {
-                continue;
-            };
-
-            let label_lines: Vec<&str> = label.lines().collect();
-            let start_y = y + (h.saturating_sub(label_lines.len())) / 2;
-
-            for (i, line) in label_lines.iter().enumerate() {
-                // Iterate the label line's chars directly for both the centering width and the placement —
-                // the previous `chars().collect::<Vec<char>>()` allocated a Vec per label line only to read
-                // its `.len()` and then consume it sequentially. `chars().count()` gives the same width and
-                // `chars().enumerate()` the same `(j, ch)`. Byte-identical, no per-line allocation.
-                let label_len = line.chars().count();
-                let label_x = x + (w.saturating_sub(label_len)) / 2;
-                let label_y = start_y + i;
-
-                if label_y < lines.len() {
-                    for (j, ch) in line.chars().enumerate() {
-                        let col = label_x + j;
-                        if col < cell_width && col < lines[label_y].len() {
-                            lines[label_y][col] = ch;
-                        }
-                    }
+            for (x, y, head) in self
+                .edge_heads(edge_path, styled_arrow(edge), scale_x, scale_y, false)
+                .into_iter()
+                .flatten()
+            {
+                if x < cell_width {
+                    buffer.set(x, y, head);
                 }
             }
         }
 
-        for node_box in &layout.extensions.sequence_mirror_headers {
+        // Overlay node labels.
+        for node_box in layout
+            .nodes
+            .iter()
+            .chain(&layout.extensions.sequence_mirror_headers)
+        {
             let (x, y, w, h) = self.bounds_to_cells(&node_box.bounds, scale_x, scale_y);
             let ir_node = ir.nodes.get(node_box.node_index);
 
@@ -1174,145 +1465,173 @@ impl TermRenderer {
             let Some(label) = self.node_display_label(ir, ir_node, &node_box.node_id) else {
                 continue;
             };
+            if let Some(node) = ir_node {
+                self.draw_node_icon(&mut buffer, node, (x, y, w), cell_width);
+            }
 
             let label_lines: Vec<&str> = label.lines().collect();
             let start_y = y + (h.saturating_sub(label_lines.len())) / 2;
 
             for (i, line) in label_lines.iter().enumerate() {
-                // Iterate the label line's chars directly for both the centering width and the placement —
-                // the previous `chars().collect::<Vec<char>>()` allocated a Vec per label line only to read
-                // its `.len()` and then consume it sequentially. `chars().count()` gives the same width and
-                // `chars().enumerate()` the same `(j, ch)`. Byte-identical, no per-line allocation.
-                let label_len = line.chars().count();
-                let label_x = x + (w.saturating_sub(label_len)) / 2;
-                let label_y = start_y + i;
-
-                if label_y < lines.len() {
-                    for (j, ch) in line.chars().enumerate() {
-                        let col = label_x + j;
-                        if col < cell_width && col < lines[label_y].len() {
-                            lines[label_y][col] = ch;
-                        }
-                    }
-                }
+                let label_x = x + (w.saturating_sub(display_width(line))) / 2;
+                buffer.put_str(label_x, start_y + i, line, cell_width);
             }
         }
+        self.draw_node_notes(&mut buffer, layout, cell_width, scale_x, scale_y);
 
         // Overlay edge labels.
-        for edge_path in &layout.edges {
-            if edge_path.points.len() < 2 {
-                continue;
-            }
-            if let Some(label_id) = ir.edges.get(edge_path.edge_index).and_then(|e| e.label)
-                && let Some(label) = ir.labels.get(label_id.0)
-            {
-                let base_label = self.truncate_label(&label.text);
-                let truncated = if let Some(number) = ir
-                    .sequence_meta
-                    .as_ref()
-                    .and_then(|meta| meta.autonumber_value(edge_path.edge_index))
-                {
-                    format!("{number} {base_label}")
-                } else {
-                    base_label
-                };
-                let label_lines: Vec<&str> = truncated.lines().collect();
-
-                let (mid_x, mid_y) = if edge_path.points.len() == 4 {
-                    let p1 = &edge_path.points[1];
-                    let p2 = &edge_path.points[2];
-                    let px = f32::midpoint(p1.x, p2.x);
-                    let py = f32::midpoint(p1.y, p2.y);
-                    self.point_to_cells(&fm_layout::LayoutPoint { x: px, y: py }, scale_x, scale_y)
-                } else if edge_path.points.len() == 2 {
-                    let p1 = &edge_path.points[0];
-                    let p2 = &edge_path.points[1];
-                    let px = f32::midpoint(p1.x, p2.x);
-                    let py = f32::midpoint(p1.y, p2.y);
-                    self.point_to_cells(&fm_layout::LayoutPoint { x: px, y: py }, scale_x, scale_y)
-                } else {
-                    let mid_idx = edge_path.points.len() / 2;
-                    self.point_to_cells(&edge_path.points[mid_idx], scale_x, scale_y)
-                };
-
-                let start_y = mid_y.saturating_sub(label_lines.len() / 2);
-
-                for (i, line) in label_lines.iter().enumerate() {
-                    // Iterate chars directly (see the node-label loops above): `chars().count()` for the
-                    // centering width, `chars().enumerate()` for placement — no per-line Vec allocation.
-                    let label_len = line.chars().count();
-                    let label_x = mid_x.saturating_sub(label_len / 2);
-                    let label_y = start_y + i;
-
-                    if label_y < lines.len() {
-                        for (j, ch) in line.chars().enumerate() {
-                            let col = label_x + j;
-                            if col < cell_width && col < lines[label_y].len() {
-                                lines[label_y][col] = ch;
-                            }
-                        }
-                    }
-                }
-            }
+        let placements =
+            self.edge_label_placements(ir, layout, (cell_width, cell_height), (scale_x, scale_y));
+        for placement in &placements {
+            placement.draw(&mut buffer, cell_width);
         }
 
-        if let Some(title) = generic_terminal_diagram_title(ir)
-            && let Some(first_line) = lines.first_mut()
-        {
-            let title = self.truncate_label(title);
-            let title_len = title.chars().count().min(cell_width);
-            let start_x = cell_width.saturating_sub(title_len) / 2;
-            for (index, ch) in title.chars().take(title_len).enumerate() {
-                let col = start_x + index;
-                if col < first_line.len() {
-                    first_line[col] = ch;
-                }
-            }
+        self.render_generic_diagram_title(&mut buffer, ir);
+        buffer.write_rows(false)
+    }
+
+    fn render_generic_diagram_title(&self, buffer: &mut CellBuffer, ir: &MermaidDiagramIr) {
+        let Some(title) = generic_terminal_diagram_title(ir) else {
+            return;
+        };
+        if buffer.width == 0 || buffer.height == 0 {
+            return;
         }
 
-        // Single-pass serialization: push every cell char into one pre-sized `String`, with a `'\n'`
-        // between rows. The previous `map(collect::<String>).collect::<Vec>().join("\n")` built one
-        // `String` per row (cell_height allocations) and then RE-COPIED every byte in `join`. Byte-
-        // identical: same chars in row-major order, `'\n'` between rows, no trailing newline. Reserve for
-        // the worst case (every cell a 3-byte U+2800.. braille/box glyph) so no push ever reallocates.
-        let total_chars: usize = lines.iter().map(Vec::len).sum();
-        let mut out = String::with_capacity(total_chars * 3 + lines.len());
-        for (row_index, row) in lines.into_iter().enumerate() {
-            if row_index > 0 {
-                out.push('\n');
+        let title = self.truncate_label_to(title, self.config.max_label_chars.min(buffer.width), 1);
+        let start_x = buffer.width.saturating_sub(display_width(&title)) / 2;
+        buffer.put_str(start_x, 0, &title, buffer.width);
+    }
+
+    /// Color clusters, edges and nodes in a generic cell grid: each element's own `stroke` style
+    /// first, then the palette. Elements outside the focus, if one is set, are drawn faint.
+    /// Leaves `output` untouched when color output is off.
+    fn paint_colors(
+        &self,
+        ir: &MermaidDiagramIr,
+        layout: &DiagramLayout,
+        output: String,
+        (cell_width, cell_height): (usize, usize),
+        (scale_x, scale_y): (f32, f32),
+    ) -> String {
+        if !self.config.color_mode.is_enabled() {
+            return output;
+        }
+        let palette = diagram_palette(ir, self.config.palette);
+        let mut layer = ColorLayer::new(cell_width, cell_height);
+        let focused = focused_nodes(ir, &self.focus_nodes, self.focus_hops);
+        let dimmed = |node_index: usize| {
+            focused
+                .as_ref()
+                .is_some_and(|focused| !focused.contains(&node_index))
+        };
+
+        if self.config.show_clusters {
+            for cluster_box in &layout.clusters {
+                let members = ir
+                    .clusters
+                    .get(cluster_box.cluster_index)
+                    .map_or(&[][..], |cluster| cluster.members.as_slice());
+                layer
+                    .set_dimmed(focused.is_some() && members.iter().all(|member| dimmed(member.0)));
+                let cells = self.bounds_to_cells(&cluster_box.bounds, scale_x, scale_y);
+                layer.paint_border(cells, palette.cluster);
             }
-            for ch in row {
-                out.push(ch);
+        }
+        for edge_path in &layout.edges {
+            let endpoints = ir.edges.get(edge_path.edge_index).and_then(|edge| {
+                Some((
+                    ir.resolve_endpoint_node(edge.from)?.0,
+                    ir.resolve_endpoint_node(edge.to)?.0,
+                ))
+            });
+            layer.set_dimmed(
+                focused.is_some() && endpoints.is_none_or(|(from, to)| dimmed(from) || dimmed(to)),
+            );
+            let color = edge_stroke(ir, edge_path.edge_index).or(palette.edge);
+            for window in edge_path.points.windows(2) {
+                layer.paint_line(
+                    self.point_to_cells(&window[0], scale_x, scale_y),
+                    self.point_to_cells(&window[1], scale_x, scale_y),
+                    color,
+                );
             }
         }
-        out
+        for node_box in layout
+            .nodes
+            .iter()
+            .chain(&layout.extensions.sequence_mirror_headers)
+        {
+            layer.set_dimmed(dimmed(node_box.node_index));
+            let (x, y, w, h) = self.bounds_to_cells(&node_box.bounds, scale_x, scale_y);
+            let color = node_stroke(ir, node_box.node_index).or(palette.node);
+            layer.paint_rect((x, y, w, h), color);
+            layer.paint_rect(
+                (x + 1, y + 1, w.saturating_sub(2), h.saturating_sub(2)),
+                node_text_color(ir, node_box.node_index).or(palette.text),
+            );
+            self.paint_label_bold(&mut layer, ir, node_box, (x, y, w, h));
+        }
+
+        layer.apply(&output, self.config.color_mode)
     }
 
-    fn render_generic_diagram_title(
+    /// Embolden the glyphs of a node label's `**bold**` runs where `render_node_cell` and
+    /// `overlay_labels` placed them. Display glyphs are matched against the markup in order, so a
+    /// wrapped or truncated label only loses the emphasis on the text that was cut.
+    fn paint_label_bold(
         &self,
-        cells: &mut [char],
-        row_width: usize,
+        layer: &mut ColorLayer,
         ir: &MermaidDiagramIr,
+        node_box: &LayoutNodeBox,
+        (x, y, w, h): (usize, usize, usize, usize),
     ) {
-        let Some(title) = generic_terminal_diagram_title(ir) else {
+        let ir_node = ir.nodes.get(node_box.node_index);
+        let Some(segments) = ir_node
+            .and_then(|node| node.label)
+            .and_then(|label_id| ir.label_markup.get(&label_id))
+        else {
             return;
         };
-        if row_width == 0 || cells.len() < row_width {
+        let markup: Vec<(char, bool)> = segments
+            .iter()
+            .flat_map(|segment| match segment {
+                IrLabelSegment::Text { text, bold, .. } => {
+                    text.chars().map(|ch| (ch, *bold)).collect::<Vec<_>>()
+                }
+                IrLabelSegment::LineBreak => vec![('\n', false)],
+            })
+            .collect();
+        if !markup.iter().any(|&(_, bold)| bold) {
             return;
         }
+        let Some(label) = self.node_display_label(ir, ir_node, &node_box.node_id) else {
+            return;
+        };
 
-        let title = self.truncate_label(title);
-        // Iterate the title chars directly (matches the other title path above): `chars().count()` for the
-        // width, `chars().take().enumerate()` for placement — no per-title Vec<char> allocation.
-        let title_len = title.chars().count().min(row_width);
-        let start_x = row_width.saturating_sub(title_len) / 2;
-
-        for (index, ch) in title.chars().take(title_len).enumerate() {
-            cells[start_x + index] = ch;
+        let lines: Vec<&str> = label.lines().collect();
+        let start_y = y + (h.saturating_sub(lines.len())) / 2;
+        let mut cursor = 0;
+        for (row, line) in lines.iter().enumerate() {
+            let start_x = x + (w.saturating_sub(display_width(line))) / 2;
+            for (column, grapheme, _) in grapheme_columns(line) {
+                let Some(first) = grapheme.chars().next() else {
+                    continue;
+                };
+                // Glyphs the label never had, like a truncation `…`, match nothing and are skipped.
+                let Some(offset) = markup[cursor..].iter().position(|&(ch, _)| ch == first) else {
+                    continue;
+                };
+                cursor += offset;
+                if markup[cursor].1 {
+                    layer.paint_bold(start_x + column, start_y + row);
+                }
+                cursor += 1;
+            }
         }
     }
 
-    fn bounds_to_cells(
+    pub(crate) fn bounds_to_cells(
         &self,
         bounds: &fm_layout::LayoutRect,
         scale_x: f32,
@@ -1326,7 +1645,7 @@ impl TermRenderer {
         (x, y, w, h)
     }
 
-    fn point_to_cells(
+    pub(crate) fn point_to_cells(
         &self,
         point: &fm_layout::LayoutPoint,
         scale_x: f32,
@@ -1338,9 +1657,24 @@ impl TermRenderer {
         (x, y)
     }
 
-    fn truncate_label(&self, text: &str) -> String {
-        let max_chars = self.config.max_label_chars.max(1);
-        let max_lines = self.config.max_label_lines.max(1);
+    pub(crate) fn truncate_label(&self, text: &str) -> String {
+        self.truncate_label_to(
+            text,
+            self.config.max_label_chars,
+            self.config.max_label_lines,
+        )
+    }
+
+    /// `text` sanitized and wrapped under the configured wrap mode into at most `max_lines`
+    /// lines of `max_chars` columns, marked with `…` where it was cut (`...` in ASCII glyph mode).
+    pub(crate) fn truncate_label_to(
+        &self,
+        text: &str,
+        max_chars: usize,
+        max_lines: usize,
+    ) -> String {
+        let max_chars = max_chars.max(1);
+        let max_lines = max_lines.max(1);
         let bytes = text.as_bytes();
         let mut previous_space = false;
         let unchanged_short_ascii = bytes.len() <= max_chars
@@ -1366,42 +1700,100 @@ impl TermRenderer {
             })
             .collect();
 
-        let mut lines: Vec<String> = Vec::new();
-        let mut source_lines: Vec<&str> = sanitized.lines().collect();
-        if source_lines.is_empty() {
-            source_lines.push(sanitized.as_str());
+        let marker = if self.config.glyph_mode == MermaidGlyphMode::Ascii {
+            fm_core::WRAP_ELLIPSIS_ASCII
+        } else {
+            fm_core::WRAP_ELLIPSIS
+        };
+        fm_core::wrap_label_with_marker(
+            &sanitized,
+            self.config.wrap_mode,
+            max_chars,
+            max_lines,
+            marker,
+            display_width,
+        )
+        .join("\n")
+    }
+
+    /// Set `node`'s icon into the top border of its `(x, y, width)` box, right of the corner, in
+    /// Unicode glyph mode. Boxes too narrow to keep a border cell on each side go without.
+    fn draw_node_icon(
+        &self,
+        buffer: &mut CellBuffer,
+        node: &fm_core::IrNode,
+        (x, y, w): (usize, usize, usize),
+        limit: usize,
+    ) {
+        if self.config.glyph_mode != MermaidGlyphMode::Unicode {
+            return;
+        }
+        let Some(glyph) = node.icon().and_then(icon_glyph) else {
+            return;
+        };
+        if display_width(glyph) + 3 <= w {
+            buffer.put_str(x + 1, y, glyph, limit.min(x + w - 1));
         }
+    }
 
-        for line in source_lines {
-            if lines.len() >= max_lines {
-                break;
+    /// Node notes as bracketed side boxes, `[ text ]`, joined to their node by a dotted leader
+    /// on the node's centre row.
+    fn draw_node_notes(
+        &self,
+        buffer: &mut CellBuffer,
+        layout: &DiagramLayout,
+        limit: usize,
+        scale_x: f32,
+        scale_y: f32,
+    ) {
+        for note in &layout.extensions.node_notes {
+            let label = self.truncate_label(&note.text);
+            let text_width = label.lines().map(display_width).max().unwrap_or(0);
+            let lines: Vec<String> = label
+                .lines()
+                .map(|line| {
+                    let pad = " ".repeat(text_width - display_width(line));
+                    format!("[ {line}{pad} ]")
+                })
+                .collect();
+            if lines.is_empty() {
+                continue;
             }
-            // Word-wrap long lines at word boundaries.
-            let wrapped = wrap_text(line, max_chars);
-            for wrapped_line in wrapped {
-                if lines.len() >= max_lines {
-                    // Truncate the last line with ellipsis if there's more content.
-                    if let Some(last) = lines.last_mut() {
-                        let chars: Vec<char> = last.chars().collect();
-                        if chars.len() >= max_chars {
-                            *last = format!(
-                                "{}…",
-                                chars[..max_chars.saturating_sub(1)]
-                                    .iter()
-                                    .collect::<String>()
-                            );
-                        }
-                    }
-                    break;
+            let box_width = text_width + 4;
+            let (from_x, row) = self.point_to_cells(&note.leader_from, scale_x, scale_y);
+            let (to_x, _) = self.point_to_cells(&note.leader_to, scale_x, scale_y);
+            let (_, top, _, height) = self.bounds_to_cells(&note.bounds, scale_x, scale_y);
+            let right = note.position != fm_core::NotePosition::LeftOf;
+            let box_x = if right {
+                to_x.max(from_x + 1)
+            } else {
+                to_x.min(from_x.saturating_sub(2))
+                    .saturating_sub(box_width - 1)
+            };
+            // Keep the leader's row inside the box.
+            let top = (top + height.saturating_sub(lines.len()) / 2)
+                .clamp((row + 1).saturating_sub(lines.len()), row);
+
+            let leader = if right {
+                from_x..box_x
+            } else {
+                box_x + box_width..from_x
+            };
+            for x in leader {
+                if buffer
+                    .get(x, row)
+                    .is_some_and(|ch| ch == ' ' || ch == '\u{2800}')
+                {
+                    buffer.set(x, row, self.edge_glyphs.dotted_h);
                 }
-                lines.push(wrapped_line);
+            }
+            for (offset, line) in lines.iter().enumerate() {
+                buffer.put_str(box_x, top + offset, line, limit);
             }
         }
-
-        lines.join("\n")
     }
 
-    fn node_display_label(
+    pub(crate) fn node_display_label(
         &self,
         ir: &MermaidDiagramIr,
         ir_node: Option<&fm_core::IrNode>,
@@ -1420,231 +1812,132 @@ impl TermRenderer {
         )
     }
 
-    /// Render a UML-style three-compartment class box into the character grid.
-    ///
-    /// Layout:
-    /// ```text
-    /// ┌──────────┐
-    /// │ ClassName │  ← header (centered)
-    /// ├──────────┤
-    /// │ +name    │  ← attributes with visibility
-    /// │ -age     │
-    /// ├──────────┤
-    /// │ +eat()   │  ← methods with visibility
-    /// └──────────┘
-    /// ```
-    #[allow(clippy::too_many_arguments)]
-    fn overlay_class_compartments(
+    /// Box outline with a blanked interior, so it hides whatever was drawn beneath it.
+    pub(crate) fn draw_box(
         &self,
-        grid: &mut [Vec<char>],
-        x: usize,
-        y: usize,
-        w: usize,
-        h: usize,
-        ir: &MermaidDiagramIr,
-        node: &fm_core::IrNode,
-        meta: &fm_core::IrClassNodeMeta,
-        grid_width: usize,
+        canvas: &mut GridCanvas,
+        left: usize,
+        top: usize,
+        width: usize,
+        height: usize,
     ) {
-        let inner_w = w.saturating_sub(2); // Width inside borders
         let glyphs = &self.box_glyphs;
-
-        // Helper to write a left-aligned string into the grid at (row, col).
-        let write_text =
-            |grid: &mut [Vec<char>], row: usize, col: usize, text: &str, max_w: usize| {
-                if row >= grid.len() {
-                    return;
-                }
-                for (i, ch) in text.chars().take(max_w).enumerate() {
-                    let c = col + i;
-                    if c < grid_width && c < grid[row].len() {
-                        grid[row][c] = ch;
-                    }
-                }
-            };
-
-        // Helper to draw a horizontal separator.
-        let draw_separator = |grid: &mut [Vec<char>], row: usize| {
-            if row >= grid.len() {
-                return;
-            }
-            if x < grid_width && x < grid[row].len() {
-                grid[row][x] = glyphs.t_right;
-            }
-            for dx in 1..w.saturating_sub(1) {
-                let c = x + dx;
-                if c < grid_width && c < grid[row].len() {
-                    grid[row][c] = glyphs.horizontal;
-                }
-            }
-            let right = x + w.saturating_sub(1);
-            if right < grid_width && right < grid[row].len() {
-                grid[row][right] = glyphs.t_left;
-            }
-        };
-
-        let mut row = y + 1; // Start inside the top border.
-        // Content must stay above the bottom border row.
-        let max_content_row = if h >= 2 { y + h - 1 } else { y + h };
-
-        // Header: class name (centered).
-        let class_name = node
-            .label
-            .and_then(|lid| ir.labels.get(lid.0))
-            .map(|l| l.text.as_str())
-            .unwrap_or(&node.id);
-        let name_text = self.truncate_label(class_name);
-        let name_chars = name_text.chars().count();
-        let name_x = x + 1 + inner_w.saturating_sub(name_chars) / 2;
-        write_text(grid, row, name_x, &name_text, inner_w);
-        row += 1;
-
-        // Separator after header.
-        if row < max_content_row {
-            draw_separator(grid, row);
-            row += 1;
-        }
-
-        // Attributes compartment.
-        for attr in &meta.attributes {
-            if row >= max_content_row {
-                break;
-            }
-            let vis = visibility_char(attr.visibility);
-            let text = format!("{vis}{}", attr.name);
-            write_text(grid, row, x + 1, &text, inner_w);
-            row += 1;
-        }
-
-        // Separator before methods (only if we have both attributes and methods).
-        if !meta.attributes.is_empty() && !meta.methods.is_empty() && row < max_content_row {
-            draw_separator(grid, row);
-            row += 1;
-        }
-
-        // Methods compartment.
-        for method in &meta.methods {
-            if row >= max_content_row {
-                break;
-            }
-            let vis = visibility_char(method.visibility);
-            let suffix = if method.is_abstract {
-                "*"
-            } else if method.is_static {
-                "$"
-            } else {
-                ""
-            };
-            let ret = method
-                .return_type
-                .as_deref()
-                .map(|t| format!(": {t}"))
-                .unwrap_or_default();
-            let text = format!("{vis}{}{suffix}{ret}", method.name);
-            write_text(grid, row, x + 1, &text, inner_w);
-            row += 1;
-        }
-    }
-}
-
-/// Map ClassVisibility to its UML symbol.
-fn visibility_char(vis: fm_core::ClassVisibility) -> char {
-    match vis {
-        fm_core::ClassVisibility::Public => '+',
-        fm_core::ClassVisibility::Private => '-',
-        fm_core::ClassVisibility::Protected => '#',
-        fm_core::ClassVisibility::Package => '~',
-    }
-}
-
-/// Wrap text at word boundaries to fit within `max_width` characters per line.
-///
-/// Uses greedy word-fit: words are placed on the current line until the next
-/// word would exceed the width. A single word wider than the target is placed
-/// on its own line and truncated with ellipsis.
-fn wrap_text(text: &str, max_width: usize) -> Vec<String> {
-    if text.is_empty() {
-        return vec![String::new()];
-    }
-
-    let max_width = max_width.max(1);
-    let mut lines: Vec<String> = Vec::new();
-    let mut current_line = String::new();
-
-    for word in text.split_whitespace() {
-        let word_len = word.chars().count();
-
-        if current_line.is_empty() {
-            // First word on line — always place it, truncate if needed.
-            if word_len <= max_width {
-                current_line.push_str(word);
-            } else {
-                let truncated: String = word.chars().take(max_width.saturating_sub(1)).collect();
-                current_line = format!("{truncated}…");
-            }
-        } else {
-            let current_len = current_line.chars().count();
-            // Check if word fits on current line (+ 1 for space).
-            if current_len + 1 + word_len <= max_width {
-                current_line.push(' ');
-                current_line.push_str(word);
-            } else {
-                // Word doesn't fit — push current line and start new one.
-                lines.push(current_line);
-                if word_len <= max_width {
-                    current_line = word.to_string();
-                } else {
-                    let truncated: String =
-                        word.chars().take(max_width.saturating_sub(1)).collect();
-                    current_line = format!("{truncated}…");
-                }
+        let (right, bottom) = (left + width - 1, top + height - 1);
+        for y in top..=bottom {
+            for x in left..=right {
+                let ch = match (x == left, x == right, y == top, y == bottom) {
+                    (true, _, true, _) => glyphs.top_left,
+                    (_, true, true, _) => glyphs.top_right,
+                    (true, _, _, true) => glyphs.bottom_left,
+                    (_, true, _, true) => glyphs.bottom_right,
+                    (_, _, true, _) | (_, _, _, true) => glyphs.horizontal,
+                    (true, _, _, _) | (_, true, _, _) => glyphs.vertical,
+                    _ => ' ',
+                };
+                canvas.set(x, y, ch);
             }
         }
     }
-
-    if !current_line.is_empty() {
-        lines.push(current_line);
-    }
-
-    if lines.is_empty() {
-        lines.push(String::new());
-    }
-
-    lines
 }
 
 /// Simple character cell buffer for cell-mode rendering.
-struct CellBuffer {
+///
+/// Text is written a grapheme at a time: a double-width grapheme takes its cell plus a
+/// [`WIDE_TAIL`] in the next one, and combining marks are kept with their base cell.
+pub(crate) struct CellBuffer {
     cells: Vec<char>,
     width: usize,
     height: usize,
+    /// Characters after the first of a multi-char grapheme, keyed by cell index.
+    marks: BTreeMap<usize, String>,
 }
 
 impl CellBuffer {
-    fn new(width: usize, height: usize) -> Self {
+    pub(crate) fn new(width: usize, height: usize) -> Self {
         Self {
             cells: vec![' '; width * height],
             width,
             height,
+            marks: BTreeMap::new(),
         }
     }
 
-    fn set(&mut self, x: usize, y: usize, ch: char) {
+    /// Buffer holding `rows`, each padded or cut to `width`.
+    pub(crate) fn from_rows(rows: Vec<Vec<char>>, width: usize, height: usize) -> Self {
+        let mut buffer = Self::new(width, height);
+        for (y, row) in rows.into_iter().take(height).enumerate() {
+            let start = y * width;
+            for (x, ch) in row.into_iter().take(width).enumerate() {
+                buffer.cells[start + x] = ch;
+            }
+        }
+        buffer
+    }
+
+    pub(crate) fn set(&mut self, x: usize, y: usize, ch: char) {
         if x < self.width && y < self.height {
-            self.cells[y * self.width + x] = ch;
+            let index = y * self.width + x;
+            if !self.marks.is_empty() {
+                self.marks.remove(&index);
+            }
+            // Overwriting either half of a double-width grapheme blanks the other half.
+            if x > 0 && self.cells[index] == WIDE_TAIL && ch != WIDE_TAIL {
+                self.cells[index - 1] = ' ';
+                self.marks.remove(&(index - 1));
+            }
+            if x + 1 < self.width && self.cells[index + 1] == WIDE_TAIL {
+                self.cells[index + 1] = ' ';
+            }
+            self.cells[index] = ch;
         }
     }
 
-    fn set_string(&mut self, x: usize, y: usize, s: &str) {
-        for (i, ch) in s.chars().enumerate() {
-            self.set(x + i, y, ch);
+    pub(crate) fn get(&self, x: usize, y: usize) -> Option<char> {
+        (x < self.width && y < self.height).then(|| self.cells[y * self.width + x])
+    }
+
+    /// Write `text` from `(x, y)` a grapheme at a time, stopping before column `limit`.
+    pub(crate) fn put_str(&mut self, x: usize, y: usize, text: &str, limit: usize) {
+        let limit = limit.min(self.width);
+        for (offset, grapheme, width) in grapheme_columns(text) {
+            let col = x + offset;
+            if col + width > limit {
+                break;
+            }
+            let mut chars = grapheme.chars();
+            let Some(first) = chars.next() else {
+                continue;
+            };
+            if width == 0 || y >= self.height {
+                continue;
+            }
+            self.set(col, y, first);
+            if !chars.as_str().is_empty() {
+                self.marks
+                    .insert(y * self.width + col, chars.as_str().to_string());
+            }
+            if width == 2 {
+                self.set(col + 1, y, WIDE_TAIL);
+            }
         }
     }
 
-    fn to_output_string(&self) -> String {
+    fn set_string(&mut self, x: usize, y: usize, s: &str) {
+        self.put_str(x, y, s, usize::MAX);
+    }
+
+    pub(crate) fn to_output_string(&self) -> String {
+        self.write_rows(true)
+    }
+
+    /// Rows joined with `'\n'`, each without trailing whitespace when `trim` is set.
+    pub(crate) fn write_rows(&self, trim: bool) -> String {
+        // Reserve for the worst case (every cell a 3-byte U+2800.. braille/box glyph) so no push
+        // ever reallocates.
         let mut output = String::with_capacity(
             self.cells
                 .len()
+                .saturating_mul(3)
                 .saturating_add(self.height.saturating_sub(1)),
         );
         for y in 0..self.height {
@@ -1653,11 +1946,26 @@ impl CellBuffer {
             }
             let start = y * self.width;
             let row = &self.cells[start..start + self.width];
-            let retained_len = row
-                .iter()
-                .rposition(|ch| !ch.is_whitespace())
-                .map_or(0, |index| index + 1);
-            output.extend(row[..retained_len].iter().copied());
+            let retained_len = if trim {
+                row.iter()
+                    .rposition(|ch| !ch.is_whitespace())
+                    .map_or(0, |index| index + 1)
+            } else {
+                row.len()
+            };
+            if self.marks.is_empty() {
+                output.extend(row[..retained_len].iter().filter(|&&ch| ch != WIDE_TAIL));
+                continue;
+            }
+            for (x, &ch) in row[..retained_len].iter().enumerate() {
+                if ch == WIDE_TAIL {
+                    continue;
+                }
+                output.push(ch);
+                if let Some(marks) = self.marks.get(&(start + x)) {
+                    output.push_str(marks);
+                }
+            }
         }
         output
     }
@@ -1669,22 +1977,80 @@ impl std::fmt::Display for CellBuffer {
             if y > 0 {
                 writeln!(f)?;
             }
-            let start = y * self.width;
-            let end = start + self.width;
-            let line: String = self.cells[start..end].iter().collect();
+            let mut line = String::new();
+            for index in y * self.width..(y + 1) * self.width {
+                if self.cells[index] != WIDE_TAIL {
+                    line.push(self.cells[index]);
+                    line.extend(self.marks.get(&index).map(String::as_str));
+                }
+            }
             write!(f, "{}", line.trim_end())?;
         }
         Ok(())
     }
 }
 
+/// Cell buffer addressed in diagram coordinates, offset by the configured padding. Used by the
+/// diagram-specific renderers that lay out their own grid instead of scaling the layout.
+pub(crate) struct GridCanvas {
+    pub(crate) buffer: CellBuffer,
+    padding: usize,
+}
+
+impl GridCanvas {
+    pub(crate) fn new(width: usize, height: usize, padding: usize) -> Self {
+        Self {
+            buffer: CellBuffer::new(width, height),
+            padding,
+        }
+    }
+
+    pub(crate) fn set(&mut self, x: usize, y: usize, ch: char) {
+        self.buffer.set(x + self.padding, y + self.padding, ch);
+    }
+
+    pub(crate) fn get(&self, x: usize, y: usize) -> Option<char> {
+        self.buffer.get(x + self.padding, y + self.padding)
+    }
+
+    /// Write `text` from `x`, stopping before column `limit`.
+    pub(crate) fn text(&mut self, x: usize, y: usize, text: &str, limit: usize) {
+        self.buffer.put_str(
+            x + self.padding,
+            y + self.padding,
+            text,
+            limit.saturating_add(self.padding),
+        );
+    }
+}
+
+/// Widest line, in terminal columns.
+pub(crate) fn text_width(lines: &[String]) -> usize {
+    lines
+        .iter()
+        .map(|line| display_width(line))
+        .max()
+        .unwrap_or(0)
+}
+
+pub(crate) fn split_lines(text: &str) -> Vec<String> {
+    text.lines().map(str::to_owned).collect()
+}
+
+/// `"s"` unless `count` is exactly one.
+fn plural_suffix(count: usize) -> &'static str {
+    if count == 1 { "" } else { "s" }
+}
+
 /// Render an IR diagram to terminal output with default configuration.
 #[must_use]
 pub fn render_diagram(ir: &MermaidDiagramIr) -> TermRenderResult {
     render_diagram_with_config(ir, &TermRenderConfig::default(), 80, 24)
 }
 
-/// Render an IR diagram to terminal output with custom configuration.
+/// Render an IR diagram to terminal output with custom configuration. With the `Auto` tier,
+/// the diagram's guard report and complexity can degrade the render; see
+/// [`TermRenderConfig::adapted_to`].
 #[must_use]
 pub fn render_diagram_with_config(
     ir: &MermaidDiagramIr,
@@ -1692,8 +2058,11 @@ pub fn render_diagram_with_config(
     cols: usize,
     rows: usize,
 ) -> TermRenderResult {
-    let resolved = ResolvedConfig::resolve(config, cols, rows);
-    let renderer = TermRenderer::new(resolved);
+    let config = config.adapted_to(ir, cols, rows);
+    let resolved = ResolvedConfig::resolve(&config, cols, rows);
+    let renderer = TermRenderer::new(resolved)
+        .with_shape_registry(config.shape_registry.clone())
+        .with_focus(config.focus_nodes.clone(), config.focus_hops);
     renderer.render(ir)
 }
 
@@ -1706,9 +2075,35 @@ pub fn render_diagram_with_layout_and_config(
     cols: usize,
     rows: usize,
 ) -> TermRenderResult {
-    let resolved = ResolvedConfig::resolve(config, cols, rows);
-    let renderer = TermRenderer::new(resolved);
-    renderer.render_layout(ir, layout)
+    let config = config.adapted_to(ir, cols, rows);
+    let resolved = ResolvedConfig::resolve(&config, cols, rows);
+    let renderer = TermRenderer::new(resolved)
+        .with_shape_registry(config.shape_registry.clone())
+        .with_focus(config.focus_nodes.clone(), config.focus_hops);
+    renderer.render_layout(&layout.display_ir(ir), layout)
+}
+
+/// Cluster boxes paired with how many other boxes enclose them, enclosing boxes first.
+fn clusters_outermost_first(clusters: &[LayoutClusterBox]) -> Vec<(&LayoutClusterBox, usize)> {
+    let encloses = |outer: &fm_layout::LayoutRect, inner: &fm_layout::LayoutRect| {
+        outer != inner
+            && outer.x <= inner.x
+            && outer.y <= inner.y
+            && outer.x + outer.width >= inner.x + inner.width
+            && outer.y + outer.height >= inner.y + inner.height
+    };
+    let mut ordered: Vec<_> = clusters
+        .iter()
+        .map(|cluster| {
+            let depth = clusters
+                .iter()
+                .filter(|other| encloses(&other.bounds, &cluster.bounds))
+                .count();
+            (cluster, depth)
+        })
+        .collect();
+    ordered.sort_by_key(|&(_, depth)| depth);
+    ordered
 }
 
 fn is_block_beta_space_node(node: &fm_core::IrNode) -> bool {
@@ -1739,18 +2134,13 @@ fn generic_terminal_diagram_title(ir: &MermaidDiagramIr) -> Option<&str> {
 /// Render a pie chart as an ASCII ellipse with wedge detection and a side legend.
 fn render_pie_cell(
     buffer: &mut CellBuffer,
+    ir: &MermaidDiagramIr,
     pie_meta: &fm_core::IrPieMeta,
     cell_width: usize,
     cell_height: usize,
 ) {
-    use std::f32::consts::PI;
-
     let slices = &pie_meta.slices;
-    let total: f32 = slices
-        .iter()
-        .map(|s| s.value.max(0.0))
-        .sum::<f32>()
-        .max(f32::EPSILON);
+    let pie = fm_layout::chart::layout_pie(ir);
 
     // Reserve space for legend on the right.
     let legend_width = slices
@@ -1779,15 +2169,6 @@ fn render_pie_cell(
 
     let slice_chars: &[char] = &['#', '*', '@', '+', '=', '~', '%', '&'];
 
-    // Build cumulative angle boundaries.
-    let mut boundaries = Vec::with_capacity(slices.len() + 1);
-    boundaries.push(-PI / 2.0);
-    let mut angle = -PI / 2.0;
-    for slice in slices {
-        angle += (slice.value.max(0.0) / total) * 2.0 * PI;
-        boundaries.push(angle);
-    }
-
     // Render pie ellipse pixel-by-pixel.
     for row in 0..chart_height {
         for col in 0..chart_width {
@@ -1796,12 +2177,9 @@ fn render_pie_cell(
             if dx * dx + dy * dy > 1.0 {
                 continue;
             }
-            let cell_angle = (-dy).atan2(dx);
-            // Find which slice this angle belongs to.
-            let slice_idx = boundaries
-                .windows(2)
-                .position(|w| cell_angle >= w[0] && cell_angle < w[1])
-                .unwrap_or(0);
+            // Screen-space angle (y down), the same clockwise-from-12-o'clock convention the
+            // layout's slice angles use.
+            let slice_idx = pie.slice_at_angle(dy.atan2(dx)).unwrap_or(0);
             let ch = slice_chars[slice_idx % slice_chars.len()];
             buffer.set(col, row + 1, ch);
         }
@@ -1809,13 +2187,13 @@ fn render_pie_cell(
 
     // Render legend on the right side.
     let legend_x = chart_width + 2;
-    for (i, slice) in slices.iter().enumerate() {
+    for (i, slice) in pie.slices.iter().enumerate() {
         let row = i + 2;
         if row >= cell_height {
             break;
         }
         let ch = slice_chars[i % slice_chars.len()];
-        let pct = (slice.value.max(0.0) / total) * 100.0;
+        let pct = slice.fraction * 100.0;
         let entry = format!("{ch} {:.0}% {}", pct, slice.label);
         // Truncate by character count (not byte count) to avoid UTF-8 boundary panics.
         let truncated: String = entry.chars().take(legend_width).collect();
@@ -1823,224 +2201,12 @@ fn render_pie_cell(
     }
 }
 
-/// Render gantt task bars in the terminal as horizontal block characters.
-fn render_gantt_cell(
-    buffer: &mut CellBuffer,
-    ir: &MermaidDiagramIr,
-    layout: &DiagramLayout,
-    cell_width: usize,
-    cell_height: usize,
-) {
-    let Some(gantt_meta) = &ir.gantt_meta else {
-        return;
-    };
-
-    let label_width = gantt_meta
-        .sections
-        .iter()
-        .map(|s| s.name.len())
-        .max()
-        .unwrap_or(0)
-        .min(cell_width / 3)
-        .max(8);
-    let bar_area_width = cell_width.saturating_sub(label_width + 3);
-    if bar_area_width < 4 {
-        return;
-    }
-
-    // Large Gantt charts previously scanned every layout node for every task, making terminal rendering
-    // O(tasks * nodes). Build an order-preserving dense index once when it can amortize the allocation.
-    // Only fill an empty slot so duplicate node indexes retain `.find()`'s first-match behavior; sparse
-    // external layouts keep the allocation-free linear path rather than sizing from an arbitrary index.
-    let layout_nodes_by_index = (gantt_meta.tasks.len() >= 16)
-        .then(|| {
-            layout
-                .nodes
-                .iter()
-                .map(|node| node.node_index)
-                .max()
-                .map_or(0, |max_index| max_index.saturating_add(1))
-        })
-        .filter(|lookup_len| *lookup_len <= layout.nodes.len().saturating_mul(2))
-        .map(|lookup_len| {
-            let mut nodes_by_index = vec![None; lookup_len];
-            for node in &layout.nodes {
-                if let Some(slot) = nodes_by_index.get_mut(node.node_index)
-                    && slot.is_none()
-                {
-                    *slot = Some(node);
-                }
-            }
-            nodes_by_index
-        });
-
-    // Title
-    if let Some(title) = &gantt_meta.title {
-        let tx = cell_width.saturating_sub(title.len()) / 2;
-        buffer.set_string(tx, 0, title);
-    }
-
-    let schedule_width = layout.bounds.width.max(1.0);
-    let mut row = 2_usize;
-
-    for (section_idx, section) in gantt_meta.sections.iter().enumerate() {
-        if row >= cell_height {
-            break;
-        }
-        // Section header (truncated by char count for UTF-8 safety).
-        let header: String = section.name.chars().take(label_width).collect();
-        buffer.set_string(0, row, &header);
-        // Separator line
-        for col in label_width + 1..cell_width {
-            buffer.set(col, row, '\u{2500}'); // ─
-        }
-        row += 1;
-
-        // Tasks belonging to this section (matched by section_idx).
-        for task in &gantt_meta.tasks {
-            if task.section_idx != section_idx {
-                continue;
-            }
-            if row >= cell_height {
-                break;
-            }
-            let task_name = ir
-                .nodes
-                .get(task.node.0)
-                .and_then(|node| {
-                    node.label
-                        .and_then(|label_id| ir.labels.get(label_id.0))
-                        .map(|label| label.text.as_str())
-                        .or(Some(node.id.as_str()))
-                })
-                .unwrap_or("task");
-            let task_label: String = task_name.chars().take(label_width).collect();
-            buffer.set_string(0, row, &task_label);
-
-            let bar_origin = label_width + 2;
-            let node_box = match &layout_nodes_by_index {
-                Some(nodes_by_index) => nodes_by_index.get(task.node.0).and_then(|node| *node),
-                None => layout
-                    .nodes
-                    .iter()
-                    .find(|node| node.node_index == task.node.0),
-            };
-            let (bar_start, bar_end) = node_box
-                .map(|node_box| {
-                    let start_ratio =
-                        ((node_box.bounds.x - layout.bounds.x) / schedule_width).clamp(0.0, 1.0);
-                    let end_ratio = ((node_box.bounds.x + node_box.bounds.width - layout.bounds.x)
-                        / schedule_width)
-                        .clamp(start_ratio, 1.0);
-                    let start = bar_origin + (start_ratio * bar_area_width as f32).floor() as usize;
-                    let end = bar_origin + (end_ratio * bar_area_width as f32).ceil() as usize;
-                    (start, end.max(start + 1))
-                })
-                .unwrap_or((
-                    bar_origin,
-                    (bar_origin + bar_area_width / 2).max(bar_origin + 1),
-                ));
-            let bar_char = if matches!(task.task_type, GanttTaskType::Critical) {
-                '\u{2593}' // ▓
-            } else if matches!(task.task_type, GanttTaskType::Done) {
-                '\u{2591}' // ░
-            } else {
-                '\u{2588}' // █
-            };
-            for col in bar_start..bar_end.min(cell_width) {
-                buffer.set(col, row, bar_char);
-            }
-            row += 1;
-        }
-    }
-}
-
-/// Render an XY chart with ASCII axes, category labels, and data bars.
-fn render_xychart_cell(
-    buffer: &mut CellBuffer,
-    ir: &MermaidDiagramIr,
-    cell_width: usize,
-    cell_height: usize,
-) {
-    let Some(xy_meta) = &ir.xy_chart_meta else {
-        return;
-    };
-
-    let label_margin = 8_usize;
-    let chart_left = label_margin + 1;
-    let chart_right = cell_width.saturating_sub(2);
-    let chart_top = 2_usize;
-    let chart_bottom = cell_height.saturating_sub(3);
-    let chart_w = chart_right.saturating_sub(chart_left);
-    let chart_h = chart_bottom.saturating_sub(chart_top);
-
-    if chart_w < 4 || chart_h < 4 {
-        return;
-    }
-
-    // Title
-    if let Some(title) = &xy_meta.title {
-        let tx = cell_width.saturating_sub(title.len()) / 2;
-        buffer.set_string(tx, 0, title);
-    }
-
-    // Y axis (vertical line).
-    for row in chart_top..=chart_bottom {
-        buffer.set(chart_left, row, '\u{2502}'); // │
-    }
-
-    // X axis (horizontal line).
-    for col in chart_left..=chart_right {
-        buffer.set(col, chart_bottom, '\u{2500}'); // ─
-    }
-    buffer.set(chart_left, chart_bottom, '\u{2514}'); // └ corner
-
-    // Category labels along x axis.
-    let categories = &xy_meta.x_axis.categories;
-    if !categories.is_empty() {
-        let cat_spacing = chart_w / categories.len().max(1);
-        for (i, cat) in categories.iter().enumerate() {
-            let x = chart_left + 1 + i * cat_spacing;
-            let label: String = cat.chars().take(cat_spacing.saturating_sub(1)).collect();
-            buffer.set_string(x, chart_bottom + 1, &label);
-        }
-    }
-
-    // Render data series as vertical bar characters.
-    let bar_chars: &[char] = &['\u{2588}', '\u{2593}', '\u{2592}', '\u{2591}']; // █ ▓ ▒ ░
-    for (series_idx, series) in xy_meta.series.iter().enumerate() {
-        let bar_ch = bar_chars[series_idx % bar_chars.len()];
-        let max_val = series
-            .values
-            .iter()
-            .copied()
-            .fold(0.0_f32, f32::max)
-            .max(f32::EPSILON);
-        let val_count = series.values.len().max(1);
-        let bar_spacing = chart_w / val_count;
-
-        for (i, &val) in series.values.iter().enumerate() {
-            let bar_height = ((val / max_val) * chart_h as f32) as usize;
-            let x = chart_left + 1 + i * bar_spacing;
-            for h in 0..bar_height.min(chart_h) {
-                let y = chart_bottom.saturating_sub(1 + h);
-                if y >= chart_top && x < chart_right {
-                    buffer.set(x, y, bar_ch);
-                }
-            }
-        }
-    }
-}
-
 /// Render a quadrant chart with ASCII axes, quadrant labels, and data points.
 fn render_quadrant_cell(
     buffer: &mut CellBuffer,
     ir: &MermaidDiagramIr,
-    layout: &fm_layout::DiagramLayout,
     cell_width: usize,
     cell_height: usize,
-    scale_x: f32,
-    scale_y: f32,
 ) {
     let Some(quad_meta) = &ir.quadrant_meta else {
         return;
@@ -2116,15 +2282,15 @@ fn render_quadrant_cell(
         buffer.set_string(x, margin_top + chart_h + 1, &label);
     }
 
-    // Data points: render from layout node positions.
+    // Data points: place the layout's normalized positions inside this chart's own axes.
     let point_chars: &[char] = &['\u{25cf}', '\u{25cb}', '\u{25c6}', '\u{25a0}']; // ● ○ ◆ ■
-    for (i, node_box) in layout.nodes.iter().enumerate() {
-        let center = node_box.bounds.center();
-        let x = (center.x * scale_x) as usize;
-        let y = (center.y * scale_y) as usize;
-        if x < cell_width && y < cell_height {
-            buffer.set(x, y, point_chars[i % point_chars.len()]);
-        }
+    let quadrant = fm_layout::chart::layout_quadrant(ir);
+    let last_col = (chart_w - 1) as f32;
+    let last_row = (chart_h - 1) as f32;
+    for (i, point) in quadrant.points.iter().enumerate() {
+        let x = margin_left + (point.x * last_col).round() as usize;
+        let y = margin_top + ((1.0 - point.y) * last_row).round() as usize;
+        buffer.set(x, y, point_chars[i % point_chars.len()]);
     }
 }
 
@@ -2133,7 +2299,7 @@ mod tests {
     use super::*;
     use fm_core::{
         DiagramType, GanttDate, GanttTaskType, IrEdge, IrEndpoint, IrGanttMeta, IrGanttSection,
-        IrGanttTask, IrLabel, IrLabelId, IrNode, IrNodeId,
+        IrGanttTask, IrLabel, IrLabelId, IrNode, IrNodeId, MermaidWrapMode,
     };
     use fm_layout::{
         LayoutActivationBar, LayoutClusterBox, LayoutExtensions, LayoutNodeBox, LayoutRect,
@@ -2179,6 +2345,44 @@ mod tests {
         assert!(!result.output.is_empty());
     }
 
+    struct StarBorder;
+
+    impl fm_core::NodeShapeDrawer for StarBorder {
+        fn svg_path(&self, x: f32, y: f32, _width: f32, _height: f32) -> String {
+            format!("M{x},{y} Z")
+        }
+
+        fn terminal_border(
+            &self,
+            width: usize,
+            height: usize,
+        ) -> Option<Vec<(usize, usize, char)>> {
+            let mut cells = Vec::new();
+            for dx in 0..width {
+                cells.push((dx, 0, '*'));
+                cells.push((dx, height.saturating_sub(1), '*'));
+            }
+            Some(cells)
+        }
+    }
+
+    #[test]
+    fn registered_terminal_border_replaces_builtin_box() {
+        let mut ir = sample_ir();
+        ir.nodes[0].custom_shape = Some(String::from("stars"));
+        let config = TermRenderConfig {
+            shape_registry: fm_core::NodeShapeRegistry::new()
+                .with("stars", std::sync::Arc::new(StarBorder)),
+            ..TermRenderConfig::compact()
+        };
+        let custom = render_diagram_with_config(&ir, &config, 80, 24);
+        assert!(custom.output.contains("*****"));
+        assert!(custom.output.contains("Start"));
+
+        let plain = render_diagram_with_config(&ir, &TermRenderConfig::compact(), 80, 24);
+        assert!(!plain.output.contains('*'));
+    }
+
     #[test]
     fn compact_mode_produces_smaller_output() {
         let ir = sample_ir();
@@ -2196,6 +2400,44 @@ mod tests {
         assert!(result.output.contains("Start") || result.output.contains('A'));
     }
 
+    #[test]
+    fn quadrant_points_land_in_their_quadrant() {
+        let mut ir = MermaidDiagramIr::empty(DiagramType::QuadrantChart);
+        ir.nodes.push(IrNode {
+            id: "Hot".to_string(),
+            ..IrNode::default()
+        });
+        ir.quadrant_meta = Some(fm_core::IrQuadrantMeta {
+            points: vec![fm_core::IrQuadrantPoint {
+                label: "Hot".to_string(),
+                x: 0.9,
+                y: 0.9,
+            }],
+            ..fm_core::IrQuadrantMeta::default()
+        });
+
+        let config = TermRenderConfig {
+            render_mode: MermaidRenderMode::CellOnly,
+            ..Default::default()
+        };
+        let result = render_diagram_with_config(&ir, &config, 60, 24);
+        let lines: Vec<Vec<char>> = result
+            .output
+            .lines()
+            .map(|line| line.chars().collect())
+            .collect();
+        let find = |target: char| {
+            lines.iter().enumerate().find_map(|(row, line)| {
+                line.iter()
+                    .position(|&ch| ch == target)
+                    .map(|col| (row, col))
+            })
+        };
+        let (center_row, center_col) = find('\u{253c}').expect("axis cross");
+        let (point_row, point_col) = find('\u{25cf}').expect("data point");
+        assert!(point_row < center_row && point_col > center_col);
+    }
+
     #[test]
     fn tiny_terminal_dimensions_do_not_underflow() {
         let ir = sample_ir();
@@ -2205,6 +2447,43 @@ mod tests {
         assert!(result.height >= 1);
     }
 
+    #[test]
+    fn tiny_viewports_fall_back_to_a_summary_card_that_fits() {
+        let ir = sample_ir();
+        let config = TermRenderConfig::default();
+        for (cols, rows) in [(0, 0), (0, 5), (1, 1), (5, 1), (9, 30), (12, 4), (40, 4)] {
+            let result = render_diagram_with_config(&ir, &config, cols, rows);
+            assert!(result.summary_only, "{cols}x{rows}");
+            assert_eq!((result.width, result.height), (cols, rows));
+            assert!(result.output.lines().count() <= rows);
+            assert!(
+                result
+                    .output
+                    .lines()
+                    .all(|line| line.chars().count() <= cols)
+            );
+            assert_eq!((result.node_count, result.edge_count), (2, 1));
+        }
+
+        let card = render_diagram_with_config(&ir, &config, 14, 4);
+        assert_eq!(
+            card.output,
+            "┌────────────┐\n│2 nodes     │\n│1 edge      │\n└────────────┘"
+        );
+        assert_eq!(
+            render_diagram_with_config(&ir, &TermRenderConfig::compact(), 9, 5).output,
+            "┌───────┐\n│flowch…│\n│2 nodes│\n│1 edge │\n└───────┘"
+        );
+        assert_eq!(card.tier, MermaidTier::Compact);
+        assert_eq!(
+            render_diagram_with_config(&ir, &config, 6, 1).output,
+            "2n 1e"
+        );
+
+        let full = render_diagram_with_config(&ir, &config, 10, 5);
+        assert!(!full.summary_only);
+    }
+
     #[test]
     fn zero_max_label_chars_is_clamped_and_safe() {
         let mut ir = sample_ir();
@@ -2246,134 +2525,170 @@ mod tests {
         assert_eq!(renderer.truncate_label("界面 42"), "界面 42");
     }
 
-    #[inline]
-    fn owned_compact_label_width_reference(line: &str) -> usize {
-        let chars: Vec<char> = line.chars().collect();
-        chars.len()
+    #[test]
+    fn labels_wrap_under_the_configured_wrap_mode() {
+        let truncate = |wrap_mode, max_label_lines, text: &str| {
+            let config = TermRenderConfig {
+                tier: MermaidTier::Rich,
+                max_label_chars: 8,
+                max_label_lines,
+                wrap_mode,
+                ..TermRenderConfig::default()
+            };
+            TermRenderer::new(ResolvedConfig::resolve(&config, 120, 40)).truncate_label(text)
+        };
+        let text = "read configuration";
+        assert_eq!(
+            truncate(MermaidWrapMode::WordChar, 3, text),
+            "read\nconfigur\nation"
+        );
+        assert_eq!(
+            truncate(MermaidWrapMode::WordChar, 2, text),
+            "read\nconfigu…"
+        );
+        assert_eq!(truncate(MermaidWrapMode::Word, 3, text), "read\nconfigu…");
+        assert_eq!(
+            truncate(MermaidWrapMode::Char, 3, text),
+            "read con\nfigurati\non"
+        );
+        assert_eq!(truncate(MermaidWrapMode::None, 3, "界面界面 x"), "界面界…");
     }
 
     #[test]
-    fn compact_label_width_preserves_unicode_scalar_centering() {
-        for line in [
-            "",
-            "Node 42",
-            "界面 → 缓存",
-            "e\u{301}quipe",
-            "🦀 worker",
-            "مرحبا",
-            "🏳️‍🌈",
-        ] {
-            assert_eq!(
-                compact_label_width(line),
-                owned_compact_label_width_reference(line),
-                "Unicode-scalar width changed for {line:?}"
-            );
+    fn ascii_glyph_mode_marks_cut_labels_with_dots() {
+        let config = TermRenderConfig {
+            tier: MermaidTier::Rich,
+            glyph_mode: MermaidGlyphMode::Ascii,
+            max_label_chars: 8,
+            max_label_lines: 1,
+            ..TermRenderConfig::default()
+        };
+        let renderer = TermRenderer::new(ResolvedConfig::resolve(&config, 120, 40));
+        assert_eq!(renderer.truncate_label("read configuration"), "read...");
+    }
+
+    #[test]
+    fn arrow_types_draw_distinct_lines_and_heads_in_cell_mode() {
+        let parsed = fm_parser::parse(
+            "flowchart LR\n  A ==> B\n  C -.-> D\n  E --o F\n  G --x H\n  I --> J",
+        );
+        let output =
+            render_diagram_with_config(&parsed.ir, &TermRenderConfig::compact(), 120, 40).output;
+        for glyph in ['═', '┄', '○', '✕', '▶'] {
+            assert!(output.contains(glyph), "{glyph} missing:\n{output}");
         }
     }
 
     #[test]
-    #[ignore = "release-only same-binary performance probe"]
-    fn compact_label_width_streaming_perf_ab() {
-        use std::hint::black_box;
-        use std::time::Instant;
+    fn wide_link_styles_draw_heavy_lines_and_heads() {
+        let source = "flowchart LR\n  A --> B\n  C --> D";
+        let render = |source: &str| {
+            let parsed = fm_parser::parse(source);
+            render_diagram_with_config(&parsed.ir, &TermRenderConfig::compact(), 120, 40).output
+        };
+        let plain = render(source);
+        assert!(!plain.contains('═') && !plain.contains('▸'), "{plain}");
 
-        const SAMPLE_COUNT: usize = 9;
-        const SWEEPS: usize = 16;
-        const BOX_WIDTH: usize = 48;
-        const BOX_X: usize = 7;
-
-        let patterns = [
-            "Node 0042",
-            "API gateway",
-            "界面 → 缓存",
-            "e\u{301}quipe worker",
-            "Δ-state_17",
-            "🦀 render worker",
-            "مرحبا service",
-            "request\nresponse",
-        ];
-        let labels: Vec<String> = (0..4_096)
-            .map(|index| format!("{}-{index}", patterns[index % patterns.len()]))
-            .collect();
+        let styled = render(&format!(
+            "{source}\n  linkStyle 1 stroke:#f00,stroke-width:4px"
+        ));
+        assert!(styled.contains('═') && styled.contains('▸'), "{styled}");
+        assert!(
+            styled.contains('▶'),
+            "edge 0 keeps its plain head:\n{styled}"
+        );
+    }
 
-        fn centered_cell_stream(
-            labels: &[String],
-            width: impl Fn(&str) -> usize,
-        ) -> Vec<(usize, usize, usize, char)> {
-            let mut cells = Vec::new();
-            for (label_index, label) in labels.iter().enumerate() {
-                for (line_index, line) in label.lines().enumerate() {
-                    let label_x = BOX_X + (BOX_WIDTH.saturating_sub(width(line))) / 2;
-                    cells.extend(
-                        line.chars()
-                            .enumerate()
-                            .map(|(offset, ch)| (label_index, line_index, label_x + offset, ch)),
-                    );
-                }
-            }
-            cells
+    #[test]
+    fn state_notes_draw_as_bracketed_side_boxes_with_leaders() {
+        let parsed = fm_parser::parse(
+            "stateDiagram-v2\n  Idle --> Busy\n  note right of Idle : lock\n  note left of Busy : wait\n",
+        );
+        for config in [TermRenderConfig::compact(), TermRenderConfig::default()] {
+            let output = render_diagram_with_config(&parsed.ir, &config, 120, 40).output;
+            let right = output
+                .lines()
+                .find(|line| line.contains("[ lock ]"))
+                .unwrap_or_else(|| panic!("right note missing:\n{output}"));
+            assert!(right.contains("┄[ lock ]"), "{right:?}");
+            let left = output
+                .lines()
+                .find(|line| line.contains("[ wait ]"))
+                .unwrap_or_else(|| panic!("left note missing:\n{output}"));
+            assert!(left.contains("[ wait ]┄"), "{left:?}");
         }
+    }
 
-        fn measure(labels: &[String], width: impl Fn(&str) -> usize) -> (u128, u64) {
-            let started = Instant::now();
-            let mut digest = 0xcbf2_9ce4_8422_2325_u64;
-            for _ in 0..SWEEPS {
-                for label in labels {
-                    for line in black_box(label.as_str()).lines() {
-                        let label_width = black_box(width(black_box(line)));
-                        let label_x = BOX_X + (BOX_WIDTH.saturating_sub(label_width)) / 2;
-                        digest ^= label_width as u64;
-                        digest = digest.wrapping_mul(0x0000_0100_0000_01b3);
-                        digest ^= label_x as u64;
-                        digest = digest.wrapping_mul(0x0000_0100_0000_01b3);
-                        for (offset, ch) in line.chars().enumerate() {
-                            digest ^= ((label_x + offset) as u64).rotate_left(32)
-                                ^ u64::from(u32::from(ch));
-                            digest = digest.wrapping_mul(0x0000_0100_0000_01b3);
-                        }
-                    }
-                }
-            }
-            (started.elapsed().as_nanos(), black_box(digest))
+    #[test]
+    fn nested_clusters_draw_lighter_borders_and_titles_in_the_top_edge() {
+        let parsed = fm_parser::parse(
+            "flowchart TD\n  subgraph outer [Outer]\n    subgraph inner [Inner]\n      A --> B\n    end\n    C\n  end",
+        );
+        let config = TermRenderConfig {
+            render_mode: MermaidRenderMode::CellOnly,
+            ..TermRenderConfig::default()
+        };
+        let output = render_diagram_with_config(&parsed.ir, &config, 120, 60).output;
+        for title in ["┤ Outer ├", "┤ Inner ├"] {
+            let row = output
+                .lines()
+                .find(|line| line.contains(title))
+                .unwrap_or_else(|| panic!("{title} missing:\n{output}"));
+            assert!(row.contains('┈') || row.contains('·'), "{row:?}");
         }
+        assert!(output.contains('┌') && output.contains('┘'), "{output}");
+        assert!(output.contains('╭') && output.contains('╯'), "{output}");
+    }
 
-        assert_eq!(
-            centered_cell_stream(&labels, owned_compact_label_width_reference),
-            centered_cell_stream(&labels, compact_label_width),
-        );
+    #[test]
+    fn cluster_depth_counts_enclosing_boxes() {
+        let cluster = |cluster_index, x, y, size| LayoutClusterBox {
+            cluster_index,
+            span: Default::default(),
+            title: None,
+            color: None,
+            bounds: LayoutRect {
+                x,
+                y,
+                width: size,
+                height: size,
+            },
+        };
+        let clusters = [
+            cluster(0, 10.0, 10.0, 20.0),
+            cluster(1, 0.0, 0.0, 100.0),
+            cluster(2, 5.0, 5.0, 40.0),
+        ];
+        let depths: Vec<_> = clusters_outermost_first(&clusters)
+            .into_iter()
+            .map(|(cluster, depth)| (cluster.cluster_index, depth))
+            .collect();
+        assert_eq!(depths, [(1, 0), (2, 1), (0, 2)]);
+    }
 
-        let mut baseline_samples = Vec::with_capacity(SAMPLE_COUNT);
-        let mut candidate_samples = Vec::with_capacity(SAMPLE_COUNT);
-        let mut expected_digest = None;
-        for sample in 0..SAMPLE_COUNT {
-            let (baseline, candidate) = if sample % 2 == 0 {
-                (
-                    measure(&labels, owned_compact_label_width_reference),
-                    measure(&labels, compact_label_width),
-                )
-            } else {
-                let candidate = measure(&labels, compact_label_width);
-                let baseline = measure(&labels, owned_compact_label_width_reference);
-                (baseline, candidate)
-            };
-            assert_eq!(candidate.1, baseline.1);
-            assert_eq!(*expected_digest.get_or_insert(baseline.1), baseline.1);
-            baseline_samples.push(baseline.0);
-            candidate_samples.push(candidate.0);
+    #[test]
+    fn labels_are_centered_by_display_width() {
+        for (line, width) in [
+            ("", 0),
+            ("Node 42", 7),
+            ("界面 → 缓存", 11),
+            ("e\u{301}quipe", 6),
+            ("🦀 worker", 9),
+            ("مرحبا", 5),
+            ("🏳️‍🌈", 2),
+        ] {
+            assert_eq!(display_width(line), width, "width changed for {line:?}");
         }
 
-        baseline_samples.sort_unstable();
-        candidate_samples.sort_unstable();
-        let baseline_median = baseline_samples[SAMPLE_COUNT / 2];
-        let candidate_median = candidate_samples[SAMPLE_COUNT / 2];
-        let improvement = (1.0 - candidate_median as f64 / baseline_median as f64) * 100.0;
-
-        eprintln!("baseline_ns={baseline_samples:?}");
-        eprintln!("candidate_ns={candidate_samples:?}");
-        eprintln!(
-            "PERF compact_terminal_label_width baseline_median_ns={baseline_median} candidate_median_ns={candidate_median} improvement_pct={improvement:.3} parity=exact rounds={SAMPLE_COUNT} labels={} sweeps={SWEEPS} digest={:016x}",
-            labels.len(),
-            expected_digest.expect("at least one performance sample")
+        let mut buffer = CellBuffer::new(12, 2);
+        buffer.set_string(0, 0, "│界面 café│");
+        buffer.set_string(0, 1, "│e\u{301}quipe 🦀│");
+        assert_eq!(buffer.to_output_string(), "│界面 café│\n│e\u{301}quipe 🦀│");
+        // Overwriting the right half of a wide character blanks its left half.
+        buffer.set(2, 0, 'x');
+        assert_eq!(
+            buffer.to_output_string().lines().next(),
+            Some("│ x面 café│")
         );
     }
 
@@ -2500,9 +2815,9 @@ mod tests {
             },
         };
 
-        renderer.render_cluster_cell(&mut buffer, &ir, &cluster, 1.0, 1.0);
+        renderer.render_cluster_cell(&mut buffer, &ir, &cluster, 0, 1.0, 1.0);
 
-        assert!(buffer.to_string().contains("Ops"));
+        assert!(buffer.to_string().contains("┤ Ops ├"));
     }
 
     #[test]
@@ -2830,7 +3145,7 @@ mod tests {
                 ..Default::default()
             },
         ];
-        // Exercise the dense node-box lookup used by larger Gantt diagrams.
+        // Undated tasks queue up after the dated ones.
         for index in 2..16 {
             tasks.push(IrGanttTask {
                 node: IrNodeId(index % 2),
@@ -2876,7 +3191,7 @@ mod tests {
                         height: 6.0,
                     },
                 },
-                // A duplicate index proves the dense lookup retains the old linear search's first match.
+                // Rows come from the schedule, so a stray duplicate box adds nothing.
                 LayoutNodeBox {
                     node_index: 0,
                     node_id: "duplicate_build".to_string(),
@@ -2957,6 +3272,32 @@ mod tests {
         assert!(first_line.contains("Shipping History"));
     }
 
+    #[test]
+    fn architecture_icons_sit_in_the_top_border_in_unicode_mode() {
+        let ir = fm_parser::parse(
+            "architecture-beta\n  service db(database)[Database]\n  service web(internet)[Web]",
+        )
+        .ir;
+
+        for config in [TermRenderConfig::compact(), TermRenderConfig::rich()] {
+            let output = render_diagram_with_config(&ir, &config, 80, 24).output;
+            for glyph in ["🗄", "🌐"] {
+                let row = output.lines().find(|line| line.contains(glyph));
+                assert!(row.is_some_and(|row| !row.contains("Database") && !row.contains("Web")));
+            }
+        }
+
+        let ascii = TermRenderConfig {
+            glyph_mode: MermaidGlyphMode::Ascii,
+            ..TermRenderConfig::compact()
+        };
+        assert!(
+            render_diagram_with_config(&ir, &ascii, 80, 24)
+                .output
+                .is_ascii()
+        );
+    }
+
     #[test]
     fn block_beta_space_nodes_are_hidden_in_compact_term_output() {
         let mut ir = MermaidDiagramIr::empty(DiagramType::BlockBeta);
@@ -2991,4 +3332,177 @@ mod tests {
                 .all(|ch| ch.is_whitespace() || ch == '⠀')
         );
     }
+
+    #[test]
+    fn color_modes_paint_nodes_and_edges_from_styles_and_palette() {
+        let parsed = fm_parser::parse(
+            "flowchart LR\n  A[Start] --> B[End]\n  style A stroke:#ff0000\n  linkStyle 0 stroke:#00ff00",
+        );
+        let plain = render_diagram_with_config(&parsed.ir, &TermRenderConfig::rich(), 80, 24);
+        assert!(!plain.output.contains('\x1b'), "{}", plain.output);
+
+        for render_mode in [MermaidRenderMode::CellOnly, MermaidRenderMode::Braille] {
+            let config = TermRenderConfig {
+                render_mode,
+                color_mode: crate::ColorMode::TrueColor,
+                ..TermRenderConfig::rich()
+            };
+            let output = render_diagram_with_config(&parsed.ir, &config, 80, 24).output;
+            // A's own stroke, the edge's linkStyle, and the default palette for B.
+            assert!(output.contains("\x1b[38;2;255;0;0m"), "{output}");
+            assert!(output.contains("\x1b[38;2;0;255;0m"), "{output}");
+            assert!(output.contains("\x1b[38;2;99;102;241m"), "{output}");
+            // Every color is closed again before the end of its line.
+            assert!(output.lines().all(|line| {
+                !line.contains('\x1b')
+                    || line
+                        .rsplit('\x1b')
+                        .next()
+                        .is_some_and(|tail| tail.starts_with("[0m"))
+            }));
+        }
+    }
+
+    #[test]
+    fn class_defs_color_borders_and_labels() {
+        let parsed = fm_parser::parse(
+            "flowchart LR\n  A[Start] --> B[End]\n  classDef default fill:#ff0000,color:#00ff00\n  classDef hot stroke:#0000ff\n  class B hot",
+        );
+        let config = TermRenderConfig {
+            render_mode: MermaidRenderMode::CellOnly,
+            color_mode: crate::ColorMode::TrueColor,
+            ..TermRenderConfig::rich()
+        };
+        let output = render_diagram_with_config(&parsed.ir, &config, 80, 24).output;
+        // Color in effect where `label` is drawn: the last foreground set before it.
+        let color_of = |label: &str| {
+            let before = &output[..output.find(label).expect(label)];
+            let at = before.rfind("\x1b[38;2;").expect("colored label");
+            before[at..]
+                .split('m')
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        };
+        // A takes its border from the default fill, B from its own class stroke.
+        assert!(output.contains("\x1b[38;2;255;0;0m"), "{output}");
+        assert!(output.contains("\x1b[38;2;0;0;255m"), "{output}");
+        assert_eq!(color_of("Start"), "\x1b[38;2;0;255;0", "{output}");
+        assert_eq!(color_of("End"), "\x1b[38;2;0;255;0", "{output}");
+    }
+
+    #[test]
+    fn markdown_bold_labels_render_bold_only_with_color() {
+        let parsed = fm_parser::parse("flowchart LR\n  A[**Loud** quiet] --> B");
+        let plain = render_diagram_with_config(&parsed.ir, &TermRenderConfig::rich(), 80, 24);
+        assert!(plain.output.contains("Loud quiet"), "{}", plain.output);
+        assert!(!plain.output.contains('\x1b'), "{}", plain.output);
+
+        // Whether `label` is drawn bold: SGR 1 set since the last reset before it.
+        let bold = |output: &str, label: &str| {
+            let before = &output[..output.find(label).expect(label)];
+            let since_reset = before.rfind("\x1b[0m").map_or(before, |at| &before[at..]);
+            since_reset.contains("\x1b[1m")
+        };
+        for render_mode in [MermaidRenderMode::CellOnly, MermaidRenderMode::Braille] {
+            let config = TermRenderConfig {
+                render_mode,
+                color_mode: crate::ColorMode::TrueColor,
+                ..TermRenderConfig::rich()
+            };
+            let output = render_diagram_with_config(&parsed.ir, &config, 80, 24).output;
+            assert!(bold(&output, "Loud"), "{output}");
+            assert!(!bold(&output, "quiet"), "{output}");
+        }
+    }
+
+    #[test]
+    fn diagram_theme_selects_the_palette() {
+        let parsed =
+            fm_parser::parse("%%{init: {\"theme\": \"monochrome\"}}%%\nflowchart LR\n  A --> B");
+        let config = TermRenderConfig {
+            color_mode: crate::ColorMode::Ansi256,
+            ..TermRenderConfig::rich()
+        };
+        let output = render_diagram_with_config(&parsed.ir, &config, 80, 24).output;
+        assert!(!output.contains('\x1b'), "{output}");
+    }
+
+    #[test]
+    fn focus_dims_everything_outside_the_neighborhood() {
+        let parsed = fm_parser::parse(
+            "flowchart LR\n  A[Alpha] --> B[Beta]\n  B --> C[Gamma]\n  C --> D[Delta]",
+        );
+        // Whether `label` is drawn faint: SGR 2 set since the last reset before it.
+        let faint = |output: &str, label: &str| {
+            let before = &output[..output.find(label).expect(label)];
+            let since_reset = before.rfind("\x1b[0m").map_or(before, |at| &before[at..]);
+            since_reset.contains("\x1b[2m")
+        };
+        let focus = |hops: usize| {
+            let config = TermRenderConfig {
+                color_mode: crate::ColorMode::TrueColor,
+                focus_nodes: vec!["B".to_string()],
+                focus_hops: hops,
+                ..TermRenderConfig::rich()
+            };
+            render_diagram_with_config(&parsed.ir, &config, 120, 24).output
+        };
+
+        let alone = focus(0);
+        assert!(!faint(&alone, "Beta"), "{alone}");
+        for label in ["Alpha", "Gamma", "Delta"] {
+            assert!(faint(&alone, label), "{label}: {alone}");
+        }
+        let neighborhood = focus(1);
+        for label in ["Alpha", "Beta", "Gamma"] {
+            assert!(!faint(&neighborhood, label), "{label}: {neighborhood}");
+        }
+        assert!(faint(&neighborhood, "Delta"), "{neighborhood}");
+
+        let unknown = TermRenderConfig {
+            color_mode: crate::ColorMode::TrueColor,
+            focus_nodes: vec!["Missing".to_string()],
+            ..TermRenderConfig::rich()
+        };
+        let output = render_diagram_with_config(&parsed.ir, &unknown, 120, 24).output;
+        assert!(!output.contains("\x1b[2m"), "{output}");
+    }
+
+    #[test]
+    fn zoom_magnifies_and_crops_or_shrinks_the_fitted_view() {
+        let parsed = fm_parser::parse("flowchart LR\n  A[Alpha] --> B[Beta] --> C[Gamma]");
+        let render = |zoom: f32| {
+            let config = TermRenderConfig {
+                zoom,
+                ..TermRenderConfig::rich()
+            };
+            render_diagram_with_config(&parsed.ir, &config, 120, 30)
+        };
+
+        let fitted = render(1.0);
+        assert!(fitted.output.contains("Alpha") && fitted.output.contains("Gamma"));
+        let zoomed_in = render(3.0);
+        assert!(zoomed_in.width >= fitted.width);
+        assert!(zoomed_in.output.contains("Alpha"), "{}", zoomed_in.output);
+        assert!(!zoomed_in.output.contains("Gamma"), "{}", zoomed_in.output);
+        let zoomed_out = render(0.5);
+        assert!(zoomed_out.width < fitted.width, "{}", zoomed_out.output);
+    }
+
+    #[test]
+    fn subcell_nodes_keep_their_shape_outlines() {
+        let render = |source: &str| {
+            let parsed = fm_parser::parse(source);
+            render_diagram_with_config(&parsed.ir, &TermRenderConfig::rich(), 120, 30).output
+        };
+        let boxes = render("flowchart LR\n  A[Pill] --> B[Choice] --> C[Store]");
+        let shaped = render("flowchart LR\n  A([Pill]) --> B{Choice} --> C[(Store)]");
+        for label in ["Pill", "Choice", "Store"] {
+            assert!(shaped.contains(label), "{label}: {shaped}");
+        }
+        // Same layout, different outlines.
+        assert_ne!(boxes, shaped);
+        assert_eq!(boxes.lines().count(), shaped.lines().count());
+    }
 }
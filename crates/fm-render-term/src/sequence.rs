@@ -0,0 +1,820 @@
+//! Dedicated terminal renderer for sequence diagrams.
+//!
+//! The generic renderer scales layout coordinates onto the cell grid, which squeezes message rows
+//! together once a diagram is taller than the viewport and draws every message as an ordinary
+//! edge. Sequence diagrams have a grid of their own: one column per participant and a band of rows
+//! per message. This renderer takes the participant order from [`SequenceLayout`] and the order of
+//! messages, fragments and notes from the IR, widens each column gap until the labels that cross it
+//! fit, and draws header boxes, lifelines, arrows, activation bars, `loop`/`alt` frames and notes
+//! with box-drawing characters:
+//!
+//! ```text
+//! ┌───────┐  ┌─────┐
+//! │ Alice │  │ Bob │
+//! └───┬───┘  └──┬──┘
+//!     ┆         ┆
+//!     ┆  Hello  ┆
+//!     ┆────────▶█
+//!     ┆   Hi    █
+//!     ┆◀┄┄┄┄┄┄┄┄█
+//!     ┆         ┆
+//! ```
+//!
+//! Output wider or taller than the viewport is clipped rather than rescaled, so labels stay
+//! legible.
+
+use fm_core::{ArrowType, FragmentKind, LifecycleEventKind, MermaidDiagramIr, NotePosition};
+use fm_layout::DiagramLayout;
+use fm_layout::sequence::SequenceLayout;
+
+use crate::glyphs::is_dotted;
+use crate::renderer::{GridCanvas, TermRenderResult, TermRenderer, split_lines, text_width};
+
+/// Blank columns between neighbouring participant headers.
+const HEADER_GAP: usize = 2;
+
+/// A participant column, left to right.
+struct Column {
+    lines: Vec<String>,
+    /// Header box width, borders included.
+    width: usize,
+    /// The layout repeats the header below the lifeline (`mirrorActors`).
+    mirrored: bool,
+    /// Cell column of the lifeline.
+    center: usize,
+}
+
+impl Column {
+    fn left(&self) -> usize {
+        self.center - self.width / 2
+    }
+
+    fn right(&self) -> usize {
+        self.left() + self.width - 1
+    }
+}
+
+struct Message {
+    edge_index: usize,
+    /// Sending and receiving columns.
+    from: usize,
+    to: usize,
+    arrow: ArrowType,
+    /// Label lines, autonumber first.
+    lines: Vec<String>,
+}
+
+struct Note {
+    after_edge: usize,
+    position: NotePosition,
+    /// Leftmost and rightmost attached columns.
+    first: usize,
+    last: usize,
+    lines: Vec<String>,
+    /// Box width, borders included.
+    width: usize,
+}
+
+/// A `loop`/`alt`/`opt`/... frame around a range of messages.
+struct Frame {
+    tag: String,
+    start_edge: usize,
+    end_edge: usize,
+    /// `else`/`and` branches: first message and bracketed label.
+    branches: Vec<(usize, String)>,
+    /// Number of frames enclosing this one.
+    depth: usize,
+}
+
+/// One band of rows, in drawing order top to bottom.
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    FrameTop(usize),
+    FrameBranch(usize, usize),
+    Message(usize),
+    Note(usize),
+    FrameBottom(usize),
+}
+
+/// An activation bar: column, nesting depth and inclusive row range.
+struct Bar {
+    column: usize,
+    depth: usize,
+    top: usize,
+    bottom: usize,
+}
+
+const fn is_double(arrow: ArrowType) -> bool {
+    matches!(
+        arrow,
+        ArrowType::DoubleArrow | ArrowType::DoubleThickArrow | ArrowType::DoubleDottedArrow
+    )
+}
+
+const fn fragment_tag(kind: FragmentKind) -> &'static str {
+    match kind {
+        FragmentKind::Loop => "loop",
+        FragmentKind::Alt => "alt",
+        FragmentKind::Opt => "opt",
+        FragmentKind::Par => "par",
+        FragmentKind::Critical => "critical",
+        FragmentKind::Break => "break",
+        FragmentKind::Rect => "rect",
+    }
+}
+
+/// Set each column's lifeline position so that headers keep [`HEADER_GAP`] apart and every
+/// message label, self-message loop and note fits beside the lifelines it touches. Returns the
+/// columns needed left of the first lifeline and right of the last one.
+fn place_columns(columns: &mut [Column], messages: &[Message], notes: &[Note]) -> (usize, usize) {
+    let count = columns.len();
+    let mut gaps: Vec<usize> = columns
+        .windows(2)
+        .map(|pair| {
+            let (left, right) = (&pair[0], &pair[1]);
+            (left.width - 1 - left.width / 2) + right.width / 2 + HEADER_GAP + 1
+        })
+        .collect();
+    let mut left_margin = columns[0].width / 2;
+    let last = &columns[count - 1];
+    let mut right_margin = last.width - 1 - last.width / 2;
+
+    // (left column, right column, minimum lifeline distance)
+    let mut spans: Vec<(usize, usize, usize)> = Vec::new();
+    for message in messages {
+        let label = text_width(&message.lines);
+        if message.from == message.to {
+            // The loop takes three columns right of the lifeline, the label starts after a space.
+            let reach = label + 6;
+            if message.from + 1 < count {
+                spans.push((message.from, message.from + 1, reach));
+            } else {
+                right_margin = right_margin.max(reach);
+            }
+        } else {
+            let (lo, hi) = (message.from.min(message.to), message.from.max(message.to));
+            spans.push((lo, hi, label + 4));
+        }
+    }
+    for note in notes {
+        match note.position {
+            NotePosition::RightOf if note.last + 1 < count => {
+                spans.push((note.last, note.last + 1, note.width + 3));
+            }
+            NotePosition::RightOf => right_margin = right_margin.max(note.width + 1),
+            NotePosition::LeftOf if note.first > 0 => {
+                spans.push((note.first - 1, note.first, note.width + 3));
+            }
+            NotePosition::LeftOf => left_margin = left_margin.max(note.width + 1),
+            NotePosition::Over if note.first == note.last => {
+                if note.first == 0 {
+                    left_margin = left_margin.max(note.width / 2);
+                }
+                if note.last + 1 == count {
+                    right_margin = right_margin.max(note.width - 1 - note.width / 2);
+                }
+            }
+            NotePosition::Over => {
+                spans.push((note.first, note.last, note.width.saturating_sub(4)));
+            }
+        }
+    }
+
+    // Widen short spans first so a long span only grows by what its inner gaps still lack.
+    spans.sort_by_key(|&(lo, hi, _)| hi - lo);
+    for (lo, hi, reach) in spans {
+        let current: usize = gaps[lo..hi].iter().sum();
+        if current < reach {
+            gaps[hi - 1] += reach - current;
+        }
+    }
+
+    let mut center = left_margin;
+    for (index, column) in columns.iter_mut().enumerate() {
+        column.center = center;
+        center += gaps.get(index).copied().unwrap_or(0);
+    }
+    (left_margin, right_margin)
+}
+
+impl TermRenderer {
+    /// Draw a sequence diagram on its own participant/message grid. `None` when the layout places
+    /// no participants, leaving the generic renderer to draw whatever decorations it carries.
+    pub(crate) fn render_sequence(
+        &self,
+        ir: &MermaidDiagramIr,
+        layout: &DiagramLayout,
+    ) -> Option<TermRenderResult> {
+        let sequence = SequenceLayout::from_diagram_layout(ir, layout.clone());
+        if sequence.participants.is_empty() {
+            return None;
+        }
+        let mut participants: Vec<_> = sequence.participants.iter().collect();
+        participants.sort_by(|a, b| a.lifeline_x.total_cmp(&b.lifeline_x));
+
+        let mut column_of = vec![None; ir.nodes.len()];
+        let mut columns = Vec::with_capacity(participants.len());
+        for participant in participants {
+            let label = self
+                .node_display_label(
+                    ir,
+                    ir.nodes.get(participant.node_index),
+                    &participant.node_id,
+                )
+                .unwrap_or_else(|| participant.node_id.clone());
+            let lines = split_lines(&label);
+            if let Some(slot) = column_of.get_mut(participant.node_index) {
+                *slot = Some(columns.len());
+            }
+            columns.push(Column {
+                width: (text_width(&lines) + 4).max(5),
+                lines,
+                mirrored: participant.mirror_header.is_some(),
+                center: 0,
+            });
+        }
+        let column = |node: usize| column_of.get(node).copied().flatten();
+
+        let meta = ir.sequence_meta.as_ref();
+        let messages: Vec<Message> = sequence
+            .messages
+            .iter()
+            .filter_map(|message| {
+                let edge = ir.edges.get(message.edge_index)?;
+                let label = edge
+                    .label
+                    .and_then(|label_id| ir.labels.get(label_id.0))
+                    .map(|label| self.truncate_label(&label.text));
+                let number = meta.and_then(|meta| meta.autonumber_value(message.edge_index));
+                let text = match (number, label) {
+                    (Some(number), Some(label)) => format!("{number} {label}"),
+                    (Some(number), None) => number.to_string(),
+                    (None, Some(label)) => label,
+                    (None, None) => String::new(),
+                };
+                Some(Message {
+                    edge_index: message.edge_index,
+                    from: column(message.from)?,
+                    to: column(message.to)?,
+                    arrow: edge.arrow,
+                    lines: split_lines(&text),
+                })
+            })
+            .collect();
+
+        let notes: Vec<Note> = meta
+            .map_or(&[][..], |meta| &meta.notes)
+            .iter()
+            .filter_map(|note| {
+                let mut attached = note
+                    .participants
+                    .iter()
+                    .filter_map(|participant| column(participant.0));
+                let first = attached.next()?;
+                let (first, last) = attached.fold((first, first), |(lo, hi), next| {
+                    (lo.min(next), hi.max(next))
+                });
+                let lines = split_lines(&self.truncate_label(&note.text));
+                Some(Note {
+                    after_edge: note.after_edge,
+                    position: note.position,
+                    first,
+                    last,
+                    width: text_width(&lines) + 4,
+                    lines,
+                })
+            })
+            .collect();
+
+        // `rect` only tints the background in SVG; it gets no frame here.
+        let edge_count = ir.edges.len();
+        let fragments: Vec<_> = meta
+            .map_or(&[][..], |meta| &meta.fragments)
+            .iter()
+            .enumerate()
+            .filter(|(_, fragment)| {
+                fragment.kind != FragmentKind::Rect
+                    && fragment.start_edge <= fragment.end_edge
+                    && fragment.end_edge < edge_count
+            })
+            .collect();
+        // Fragments are recorded as they close, so of two with the same range the later one is
+        // the outer one.
+        let encloses =
+            |(outer_index, outer): (usize, &fm_core::IrSequenceFragment),
+             (inner_index, inner): (usize, &fm_core::IrSequenceFragment)| {
+                outer_index != inner_index
+                    && outer.start_edge <= inner.start_edge
+                    && inner.end_edge <= outer.end_edge
+                    && (outer.end_edge - outer.start_edge > inner.end_edge - inner.start_edge
+                        || outer_index > inner_index)
+            };
+        let frames: Vec<Frame> = fragments
+            .iter()
+            .map(|&(index, fragment)| {
+                let tag = fragment_tag(fragment.kind);
+                Frame {
+                    tag: if fragment.label.is_empty() {
+                        tag.to_string()
+                    } else {
+                        format!("{tag} [{}]", fragment.label)
+                    },
+                    start_edge: fragment.start_edge,
+                    end_edge: fragment.end_edge,
+                    branches: fragment
+                        .alternatives
+                        .iter()
+                        .filter(|branch| {
+                            (fragment.start_edge..=fragment.end_edge).contains(&branch.start_edge)
+                        })
+                        .map(|branch| (branch.start_edge, format!("[{}]", branch.label)))
+                        .collect(),
+                    depth: fragments
+                        .iter()
+                        .filter(|&&outer| encloses(outer, (index, fragment)))
+                        .count(),
+                }
+            })
+            .collect();
+
+        // Order the bands by the message they belong to: frames open before their first message,
+        // notes follow the message they were written after, and frames close after their last
+        // message and any trailing notes. Outer frames open first and close last.
+        let mut steps: Vec<((usize, u8, usize, u8), Step)> = Vec::new();
+        for (index, frame) in frames.iter().enumerate() {
+            steps.push(((frame.start_edge, 0, frame.depth, 0), Step::FrameTop(index)));
+            for (branch, &(start_edge, _)) in frame.branches.iter().enumerate() {
+                steps.push((
+                    (start_edge, 0, frame.depth, 1),
+                    Step::FrameBranch(index, branch),
+                ));
+            }
+            steps.push((
+                (frame.end_edge, 3, usize::MAX - frame.depth, 0),
+                Step::FrameBottom(index),
+            ));
+        }
+        for (index, message) in messages.iter().enumerate() {
+            steps.push(((message.edge_index, 1, 0, 0), Step::Message(index)));
+        }
+        for (index, note) in notes.iter().enumerate() {
+            steps.push(((note.after_edge, 2, index, 0), Step::Note(index)));
+        }
+        steps.sort_by_key(|&(key, _)| key);
+
+        // Columns: the innermost frame keeps one blank column from the content, and participant
+        // groups need one column either side of their headers for the bracket.
+        let groups = meta
+            .filter(|_| self.config.show_clusters)
+            .map_or(&[][..], |meta| &meta.participant_groups);
+        let frame_levels = frames
+            .iter()
+            .map(|frame| frame.depth + 1)
+            .max()
+            .unwrap_or(0);
+        let inset = if frame_levels > 0 {
+            frame_levels + 1
+        } else {
+            usize::from(!groups.is_empty())
+        };
+        let (_, right_margin) = place_columns(&mut columns, &messages, &notes);
+        for column in &mut columns {
+            column.center += inset;
+        }
+        let title_lines = ir
+            .meta
+            .title
+            .as_deref()
+            .map(|title| split_lines(&self.truncate_label(title)))
+            .unwrap_or_default();
+        let last_center = columns.last().map_or(0, |column| column.center);
+        // A frame's tag and branch labels sit between its corners with a space either side.
+        let frame_width = frames
+            .iter()
+            .flat_map(|frame| {
+                std::iter::once(&frame.tag)
+                    .chain(frame.branches.iter().map(|(_, label)| label))
+                    .map(move |text| text.chars().count() + 5 + frame.depth * 2)
+            })
+            .max()
+            .unwrap_or(0);
+        let width = (last_center + right_margin + 1 + inset)
+            .max(frame_width)
+            .max(text_width(&title_lines));
+
+        // Rows.
+        let mut row = 0;
+        let title_top = row;
+        if !title_lines.is_empty() {
+            row += title_lines.len() + 1;
+        }
+        let group_row = row;
+        if !groups.is_empty() {
+            row += 1;
+        }
+        let header_height = columns
+            .iter()
+            .map(|column| column.lines.len())
+            .max()
+            .unwrap_or(0)
+            .max(1)
+            + 2;
+        let header_top = row;
+        row += header_height;
+        let lifeline_top = row;
+        row += 1;
+
+        let mut arrow_rows = vec![None; edge_count];
+        let mut message_rows = vec![0; messages.len()];
+        let mut note_rows = vec![0; notes.len()];
+        let mut frame_rows = vec![(0, 0); frames.len()];
+        let mut branch_rows = Vec::new();
+        for &(_, step) in &steps {
+            match step {
+                Step::FrameTop(frame) => {
+                    frame_rows[frame].0 = row;
+                    row += 1;
+                }
+                Step::FrameBranch(frame, branch) => {
+                    branch_rows.push((frame, branch, row));
+                    row += 1;
+                }
+                Step::Message(index) => {
+                    let message = &messages[index];
+                    message_rows[index] = row;
+                    let label_rows = message.lines.len();
+                    if message.from == message.to {
+                        arrow_rows[message.edge_index] = Some(row + 1);
+                        row += label_rows.max(2);
+                    } else {
+                        arrow_rows[message.edge_index] = Some(row + label_rows);
+                        row += label_rows + 1;
+                    }
+                }
+                Step::Note(index) => {
+                    note_rows[index] = row;
+                    row += notes[index].lines.len() + 2;
+                }
+                Step::FrameBottom(frame) => {
+                    frame_rows[frame].1 = row;
+                    row += 1;
+                }
+            }
+        }
+        row += 1;
+        let footer_top = row;
+        if columns.iter().any(|column| column.mirrored) {
+            row += header_height;
+        }
+        let height = row;
+
+        let last_arrow_row = arrow_rows.iter().rev().find_map(|row| *row);
+        let bars: Vec<Bar> = meta
+            .map_or(&[][..], |meta| &meta.activations)
+            .iter()
+            .filter_map(|activation| {
+                let top = arrow_rows.get(activation.start_edge).copied().flatten()?;
+                let bottom = arrow_rows
+                    .get(activation.end_edge)
+                    .copied()
+                    .flatten()
+                    .or(last_arrow_row)?;
+                Some(Bar {
+                    column: column(activation.participant.0)?,
+                    depth: activation.depth,
+                    top,
+                    bottom: bottom.max(top),
+                })
+            })
+            .collect();
+        // Messages start and end beside the outermost active bar.
+        let bar_reach = |column: usize, row: usize| {
+            bars.iter()
+                .filter(|bar| bar.column == column && (bar.top..=bar.bottom).contains(&row))
+                .map(|bar| bar.depth)
+                .max()
+                .unwrap_or(0)
+        };
+        let mut lifeline_ends = vec![footer_top; columns.len()];
+        let mut destroyed = Vec::new();
+        for event in meta.map_or(&[][..], |meta| &meta.lifecycle_events) {
+            if event.kind != LifecycleEventKind::Destroy {
+                continue;
+            }
+            if let Some(column) = column(event.participant.0)
+                && let Some(row) = arrow_rows.get(event.at_edge).copied().flatten()
+            {
+                lifeline_ends[column] = lifeline_ends[column].min(row);
+                destroyed.push((column, row));
+            }
+        }
+
+        let padding = self.config.padding;
+        let out_width = (width + padding * 2).min(self.config.cols);
+        let out_height = (height + padding * 2).min(self.config.rows);
+        let mut canvas = GridCanvas::new(out_width, out_height, padding);
+        let boxes = &self.box_glyphs;
+        let edges = &self.edge_glyphs;
+
+        // Headers and lifelines.
+        for (index, column) in columns.iter().enumerate() {
+            self.draw_header(&mut canvas, column, header_top, header_height);
+            canvas.set(column.center, header_top + header_height - 1, boxes.t_down);
+            for y in lifeline_top..lifeline_ends[index] {
+                canvas.set(column.center, y, edges.dotted_v);
+            }
+            if column.mirrored {
+                self.draw_header(&mut canvas, column, footer_top, header_height);
+                if lifeline_ends[index] == footer_top {
+                    canvas.set(column.center, footer_top, boxes.t_up);
+                }
+            }
+        }
+
+        for bar in &bars {
+            for y in bar.top..=bar.bottom {
+                canvas.set(columns[bar.column].center + bar.depth, y, boxes.block_full);
+            }
+        }
+
+        // Frames, outermost first. Rules crossing a lifeline become a cross.
+        let mut frame_order: Vec<usize> = (0..frames.len()).collect();
+        frame_order.sort_by_key(|&index| frames[index].depth);
+        let rule = |canvas: &mut GridCanvas, y: usize, left: usize, right: usize, fill: char| {
+            for x in left + 1..right {
+                let ch = if canvas.get(x, y) == Some(edges.dotted_v) {
+                    boxes.cross
+                } else {
+                    fill
+                };
+                canvas.set(x, y, ch);
+            }
+        };
+        for index in frame_order {
+            let frame = &frames[index];
+            let (top, bottom) = frame_rows[index];
+            let (left, right) = (frame.depth, width - 1 - frame.depth);
+            rule(&mut canvas, top, left, right, boxes.horizontal);
+            rule(&mut canvas, bottom, left, right, boxes.horizontal);
+            canvas.set(left, top, boxes.top_left);
+            canvas.set(right, top, boxes.top_right);
+            canvas.set(left, bottom, boxes.bottom_left);
+            canvas.set(right, bottom, boxes.bottom_right);
+            for y in top + 1..bottom {
+                canvas.set(left, y, boxes.vertical);
+                canvas.set(right, y, boxes.vertical);
+            }
+            canvas.text(left + 2, top, &format!(" {} ", frame.tag), right);
+            for &(_, branch, y) in branch_rows.iter().filter(|(owner, ..)| *owner == index) {
+                rule(&mut canvas, y, left, right, edges.dotted_h);
+                canvas.set(left, y, boxes.t_right);
+                canvas.set(right, y, boxes.t_left);
+                canvas.text(
+                    left + 2,
+                    y,
+                    &format!(" {} ", frame.branches[branch].1),
+                    right,
+                );
+            }
+        }
+
+        for (index, message) in messages.iter().enumerate() {
+            let top = message_rows[index];
+            let line = if is_dotted(message.arrow) {
+                edges.dotted_h
+            } else {
+                edges.line_h
+            };
+            let from = columns[message.from].center;
+            if message.from == message.to {
+                // A three-column loop out to the right and back, label beside it.
+                let back = top + 1;
+                let x = from + 1 + bar_reach(message.from, back);
+                canvas.set(x, top, line);
+                canvas.set(x + 1, top, line);
+                canvas.set(x + 2, top, boxes.top_right);
+                canvas.set(
+                    x,
+                    back,
+                    self.message_head(message.arrow, false).unwrap_or(line),
+                );
+                canvas.set(x + 1, back, line);
+                canvas.set(x + 2, back, boxes.bottom_right);
+                for (offset, text) in message.lines.iter().enumerate() {
+                    canvas.text(x + 4, top + offset, text, width);
+                }
+                continue;
+            }
+
+            let y = top + message.lines.len();
+            let to = columns[message.to].center;
+            let rightward = message.to > message.from;
+            let (start, end) = if rightward {
+                (from + 1 + bar_reach(message.from, y), to - 1)
+            } else {
+                (from - 1, to + 1 + bar_reach(message.to, y))
+            };
+            let (lo, hi) = (start.min(end), start.max(end));
+            for x in lo..=hi {
+                canvas.set(x, y, line);
+            }
+            if let Some(head) = self.message_head(message.arrow, rightward) {
+                canvas.set(end, y, head);
+            }
+            if is_double(message.arrow)
+                && let Some(head) = self.message_head(message.arrow, !rightward)
+            {
+                canvas.set(start, y, head);
+            }
+
+            let (left, right) = (from.min(to), from.max(to));
+            let span = right - left - 1;
+            for (offset, text) in message.lines.iter().enumerate() {
+                let x = left + 1 + span.saturating_sub(text.chars().count()) / 2;
+                canvas.text(x, top + offset, text, right);
+            }
+        }
+
+        for (index, note) in notes.iter().enumerate() {
+            let first = columns[note.first].center;
+            let last = columns[note.last].center;
+            let (left, box_width) = match note.position {
+                NotePosition::RightOf => (last + 2, note.width),
+                NotePosition::LeftOf => (first.saturating_sub(note.width + 1), note.width),
+                NotePosition::Over if note.first == note.last => {
+                    (first.saturating_sub(note.width / 2), note.width)
+                }
+                NotePosition::Over => (first - 2, (last - first + 5).max(note.width)),
+            };
+            let top = note_rows[index];
+            self.draw_box(&mut canvas, left, top, box_width, note.lines.len() + 2);
+            for (offset, text) in note.lines.iter().enumerate() {
+                canvas.text(left + 2, top + 1 + offset, text, left + box_width - 1);
+            }
+        }
+
+        for &(column, y) in &destroyed {
+            canvas.set(columns[column].center, y, edges.cross_head);
+        }
+
+        let clusters = &self.cluster_glyphs;
+        for group in groups {
+            let mut members = group
+                .participants
+                .iter()
+                .filter_map(|participant| column(participant.0));
+            let Some(first) = members.next() else {
+                continue;
+            };
+            let (first, last) = members.fold((first, first), |(lo, hi), next| {
+                (lo.min(next), hi.max(next))
+            });
+            let left = columns[first].left() - 1;
+            let right = columns[last].right() + 1;
+            for x in left + 1..right {
+                canvas.set(x, group_row, clusters.border_h);
+            }
+            canvas.set(left, group_row, clusters.corner_tl);
+            canvas.set(right, group_row, clusters.corner_tr);
+            if let Some(label) = split_lines(&self.truncate_label(&group.label)).first() {
+                canvas.text(left + 2, group_row, &format!(" {label} "), right);
+            }
+        }
+
+        for (offset, line) in title_lines.iter().enumerate() {
+            let x = width.saturating_sub(line.chars().count()) / 2;
+            canvas.text(x, title_top + offset, line, width);
+        }
+
+        Some(TermRenderResult {
+            output: canvas.buffer.to_output_string(),
+            width: out_width,
+            height: out_height,
+            tier: self.config.tier,
+            render_mode: self.config.render_mode,
+            node_count: layout.nodes.len(),
+            edge_count: layout.edges.len(),
+            summary_only: false,
+        })
+    }
+
+    /// Participant header box with its label centred.
+    fn draw_header(&self, canvas: &mut GridCanvas, column: &Column, top: usize, height: usize) {
+        self.draw_box(canvas, column.left(), top, column.width, height);
+        let first_row = top + 1 + (height - 2).saturating_sub(column.lines.len()) / 2;
+        for (offset, line) in column.lines.iter().enumerate() {
+            let x = column.left() + 1 + (column.width - 2).saturating_sub(line.chars().count()) / 2;
+            canvas.text(x, first_row + offset, line, column.right());
+        }
+    }
+
+    /// Arrowhead at the receiving end, or `None` for plain lines (`->`, `-->`).
+    fn message_head(&self, arrow: ArrowType, rightward: bool) -> Option<char> {
+        let glyphs = &self.edge_glyphs;
+        match arrow {
+            ArrowType::Line | ArrowType::ThickLine | ArrowType::DottedLine => None,
+            // Asynchronous messages (`-)`) get an open head.
+            ArrowType::OpenArrow | ArrowType::DottedOpenArrow => {
+                Some(if rightward { '>' } else { '<' })
+            }
+            ArrowType::Cross | ArrowType::DottedCross => Some(glyphs.cross_head),
+            ArrowType::Circle => Some(glyphs.circle_head),
+            _ => Some(if rightward {
+                glyphs.arrow_right
+            } else {
+                glyphs.arrow_left
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::TermRenderConfig;
+    use crate::renderer::render_diagram_with_config;
+    use fm_core::MermaidGlyphMode;
+
+    fn render(source: &str, config: &TermRenderConfig) -> String {
+        let parsed = fm_parser::parse(source);
+        render_diagram_with_config(&parsed.ir, config, 160, 60).output
+    }
+
+    fn row_of(output: &str, needle: &str) -> usize {
+        output
+            .lines()
+            .position(|line| line.contains(needle))
+            .unwrap_or_else(|| panic!("{needle:?} missing from\n{output}"))
+    }
+
+    #[test]
+    fn draws_headers_lifelines_messages_and_activation_bars() {
+        let output = render(
+            "sequenceDiagram\n  Alice->>+Bob: Hello\n  Bob-->>-Alice: Hi",
+            &TermRenderConfig::rich(),
+        );
+
+        assert!(output.contains("│ Alice │"), "{output}");
+        assert!(output.contains("│ Bob │"), "{output}");
+        assert!(output.contains('┆'), "{output}");
+        assert!(output.contains('█'), "{output}");
+        // Each label sits on the row above its arrow.
+        let hello = row_of(&output, "Hello");
+        let hi = row_of(&output, "Hi");
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[hello + 1].contains("─▶"), "{output}");
+        assert!(lines[hi + 1].contains("◀┄"), "{output}");
+        assert!(hello < hi);
+    }
+
+    #[test]
+    fn frames_enclose_their_messages_with_branch_separators() {
+        let output = render(
+            "sequenceDiagram\n  Alice->>Bob: ping\n  loop every minute\n    Bob->>Alice: pong\n  end\n  alt ok\n    Alice->>Bob: yes\n  else failure\n    Alice->>Bob: no\n  end",
+            &TermRenderConfig::rich(),
+        );
+
+        let loop_top = row_of(&output, "loop [every minute]");
+        let alt_top = row_of(&output, "alt [ok]");
+        let branch = row_of(&output, "[failure]");
+        assert!(row_of(&output, "ping") < loop_top, "{output}");
+        assert!(loop_top < row_of(&output, "pong"), "{output}");
+        assert!(row_of(&output, "pong") < alt_top, "{output}");
+        assert!(alt_top < row_of(&output, "yes"), "{output}");
+        assert!(row_of(&output, "yes") < branch, "{output}");
+        assert!(branch < row_of(&output, "no"), "{output}");
+    }
+
+    #[test]
+    fn self_messages_and_notes_get_their_own_rows() {
+        let output = render(
+            "sequenceDiagram\n  Alice->>Alice: think\n  Note right of Alice: thinking hard\n  Alice->>Bob: done",
+            &TermRenderConfig::rich(),
+        );
+
+        let think = row_of(&output, "think");
+        let note = row_of(&output, "thinking hard");
+        assert!(think < note && note < row_of(&output, "done"), "{output}");
+        assert!(
+            output
+                .lines()
+                .nth(think)
+                .is_some_and(|line| line.contains('┐'))
+        );
+    }
+
+    #[test]
+    fn ascii_mode_uses_only_ascii() {
+        let config = TermRenderConfig {
+            glyph_mode: MermaidGlyphMode::Ascii,
+            ..TermRenderConfig::rich()
+        };
+        let output = render(
+            "sequenceDiagram\n  Alice->>+Bob: Hello\n  loop retry\n    Bob-->>-Alice: Hi\n  end",
+            &config,
+        );
+
+        assert!(output.is_ascii(), "{output}");
+        assert!(output.contains("| Alice |"), "{output}");
+        assert!(output.contains("loop [retry]"), "{output}");
+    }
+}
@@ -0,0 +1,118 @@
+//! Display width of label text on a terminal grid.
+//!
+//! Text is measured and placed by grapheme cluster rather than by `char`: a base character and
+//! the combining marks after it share one cell, while CJK ideographs, emoji and other characters
+//! [`CharWidthClass`] calls full-width take two.
+
+use fm_core::CharWidthClass;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Stored in the cell right of a double-width grapheme. Never printed: the terminal already
+/// covers that column with the grapheme itself.
+pub(crate) const WIDE_TAIL: char = '\0';
+
+/// Columns `ch` takes on its own: 0 for combining marks, joiners and variation selectors, 2 for
+/// full-width characters, 1 otherwise (ASCII controls included, as labels are sanitized first).
+pub(crate) fn char_width(ch: char) -> usize {
+    if ch.is_ascii() {
+        1
+    } else if is_zero_width(ch) {
+        0
+    } else if CharWidthClass::classify(ch) == CharWidthClass::FullWidth {
+        2
+    } else {
+        1
+    }
+}
+
+/// Columns a grapheme cluster takes: its first character's width, or 2 when any character in
+/// it is full-width (emoji sequences joined with U+200D, keycaps, flags).
+pub(crate) fn grapheme_width(grapheme: &str) -> usize {
+    let mut chars = grapheme.chars();
+    match chars.next() {
+        None => 0,
+        Some(first) => match char_width(first) {
+            0 => 0,
+            _ if chars.any(|ch| char_width(ch) == 2) => 2,
+            width => width,
+        },
+    }
+}
+
+/// Columns `text` takes on screen.
+pub(crate) fn display_width(text: &str) -> usize {
+    if text.is_ascii() {
+        return text.len();
+    }
+    text.graphemes(true).map(grapheme_width).sum()
+}
+
+/// Each grapheme of `text` with the column it starts at and its width.
+pub(crate) fn grapheme_columns(text: &str) -> impl Iterator<Item = (usize, &str, usize)> {
+    let mut column = 0;
+    text.graphemes(true).map(move |grapheme| {
+        let width = grapheme_width(grapheme);
+        let start = column;
+        column += width;
+        (start, grapheme, width)
+    })
+}
+
+const fn is_zero_width(ch: char) -> bool {
+    matches!(
+        ch,
+        '\u{0300}'..='\u{036F}'
+            | '\u{0483}'..='\u{0489}'
+            | '\u{0591}'..='\u{05BD}'
+            | '\u{0610}'..='\u{061A}'
+            | '\u{064B}'..='\u{065F}'
+            | '\u{0E31}'
+            | '\u{0E34}'..='\u{0E3A}'
+            | '\u{0E47}'..='\u{0E4E}'
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{200B}'..='\u{200F}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{302A}'..='\u{302F}'
+            | '\u{3099}'..='\u{309A}'
+            | '\u{FE00}'..='\u{FE0F}'
+            | '\u{FE20}'..='\u{FE2F}'
+            | '\u{FEFF}'
+            | '\u{E0100}'..='\u{E01EF}'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fm_core::MermaidWrapMode;
+
+    #[test]
+    fn widths_follow_graphemes() {
+        assert_eq!(display_width("abc"), 3);
+        assert_eq!(display_width("日本語"), 6);
+        assert_eq!(display_width("cafe\u{301}"), 4);
+        assert_eq!(display_width("🚀 go"), 5);
+        assert_eq!(display_width("👩\u{200D}💻"), 2);
+    }
+
+    #[test]
+    fn truncation_never_splits_a_grapheme() {
+        assert_eq!(fm_core::ellipsize("日本語", 5, display_width), "日本…");
+        assert_eq!(
+            fm_core::ellipsize("e\u{301}e\u{301}", 1, display_width),
+            "…"
+        );
+        assert_eq!(
+            fm_core::wrap_label(
+                "e\u{301}e\u{301}",
+                MermaidWrapMode::Char,
+                1,
+                2,
+                display_width
+            ),
+            ["e\u{301}", "e\u{301}"]
+        );
+        assert_eq!(fm_core::ellipsize("abcd", 3, display_width), "ab…");
+    }
+}
@@ -0,0 +1,360 @@
+//! Dedicated terminal renderers for timelines and user journeys.
+//!
+//! Both read left to right as a band of columns: section names on top, then one column per
+//! timeline period (with its events stacked underneath) or per journey step (with a five-step score
+//! meter and its actors). Columns that don't fit the viewport wrap into further bands below:
+//!
+//! ```text
+//! Social ────────────   Search
+//! 2002       2004       2005
+//! ●──────────●──────────●──────
+//! LinkedIn   Facebook   YouTube
+//!            Google
+//! ```
+
+use fm_core::{IrNodeId, MermaidDiagramIr};
+use fm_layout::DiagramLayout;
+
+use crate::glyphs::TimelineGlyphs;
+use crate::renderer::{GridCanvas, TermRenderResult, TermRenderer};
+
+/// Blank cells between neighbouring columns.
+const COLUMN_GAP: usize = 3;
+/// Highest journey score; the meter has one cell per point.
+const MAX_SCORE: usize = 5;
+
+/// One period or step of the band.
+struct Column {
+    section: Option<usize>,
+    head: String,
+    body: Vec<String>,
+}
+
+impl TermRenderer {
+    /// Draw a timeline as a band of periods with their events stacked underneath. `None` when
+    /// there are no periods.
+    pub(crate) fn render_timeline(
+        &self,
+        ir: &MermaidDiagramIr,
+        layout: &DiagramLayout,
+    ) -> Option<TermRenderResult> {
+        let is_event = |index: usize| {
+            ir.nodes[index]
+                .classes
+                .iter()
+                .any(|class| class == "timeline-event")
+        };
+        let columns: Vec<Column> = (0..ir.nodes.len())
+            .filter(|&index| !is_event(index))
+            .map(|index| Column {
+                section: section_of(ir, index),
+                head: self.cell_text(node_text(ir, index)),
+                body: ir
+                    .edges
+                    .iter()
+                    .filter(|edge| edge.from.resolved_node_id(&ir.ports) == Some(IrNodeId(index)))
+                    .filter_map(|edge| edge.to.resolved_node_id(&ir.ports))
+                    .filter(|event| is_event(event.0))
+                    .map(|event| self.cell_text(node_text(ir, event.0)))
+                    .collect(),
+            })
+            .collect();
+        self.render_band(ir, layout, &columns)
+    }
+
+    /// Draw a user journey as a band of steps, each with a score meter and its actors. `None`
+    /// when there are no steps.
+    pub(crate) fn render_journey(
+        &self,
+        ir: &MermaidDiagramIr,
+        layout: &DiagramLayout,
+    ) -> Option<TermRenderResult> {
+        let glyphs = TimelineGlyphs::for_mode(self.config.glyph_mode);
+        let columns: Vec<Column> = ir
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| {
+                let mut body = Vec::new();
+                // The parser records the score as a `journey-score-N` class.
+                if let Some(score) = node.classes.iter().find_map(|class| {
+                    class
+                        .strip_prefix("journey-score-")
+                        .and_then(|score| score.parse::<usize>().ok())
+                }) {
+                    let filled = score.min(MAX_SCORE);
+                    let mut meter = String::new();
+                    meter.extend(std::iter::repeat_n(glyphs.score_filled, filled));
+                    meter.extend(std::iter::repeat_n(glyphs.score_empty, MAX_SCORE - filled));
+                    body.push(meter);
+                }
+                let actors: Vec<&str> = node
+                    .classes
+                    .iter()
+                    .filter_map(|class| class.strip_prefix("journey-actor-"))
+                    .collect();
+                if !actors.is_empty() {
+                    body.push(self.cell_text(&actors.join(", ")));
+                }
+                Column {
+                    section: section_of(ir, index),
+                    head: self.cell_text(node_text(ir, index)),
+                    body,
+                }
+            })
+            .collect();
+        self.render_band(ir, layout, &columns)
+    }
+
+    /// Lay columns out left to right, wrapping into a new band whenever the next one would run
+    /// past the viewport.
+    fn render_band(
+        &self,
+        ir: &MermaidDiagramIr,
+        layout: &DiagramLayout,
+        columns: &[Column],
+    ) -> Option<TermRenderResult> {
+        if columns.is_empty() {
+            return None;
+        }
+        let glyphs = TimelineGlyphs::for_mode(self.config.glyph_mode);
+        let rule = self.box_glyphs.horizontal;
+        let padding = self.config.padding;
+        let available = self.config.cols.saturating_sub(padding * 2).max(1);
+        let widths: Vec<usize> = columns
+            .iter()
+            .map(|column| {
+                column
+                    .body
+                    .iter()
+                    .chain(std::iter::once(&column.head))
+                    .map(|text| text.chars().count())
+                    .max()
+                    .unwrap_or(0)
+                    .max(1)
+            })
+            .collect();
+
+        // Each band is a run of columns with their x offsets.
+        let mut bands: Vec<Vec<(usize, usize)>> = vec![Vec::new()];
+        let mut x = 0;
+        for (index, &width) in widths.iter().enumerate() {
+            let band = bands.last_mut()?;
+            if !band.is_empty() && x + width > available {
+                bands.push(Vec::new());
+                x = 0;
+            }
+            bands.last_mut()?.push((index, x));
+            x += width + COLUMN_GAP;
+        }
+
+        let title = ir.meta.title.as_deref().map(|title| self.cell_text(title));
+        let has_sections = columns.iter().any(|column| column.section.is_some());
+        let band_height = |band: &[(usize, usize)]| {
+            usize::from(has_sections)
+                + 2
+                + band
+                    .iter()
+                    .map(|&(index, _)| columns[index].body.len())
+                    .max()
+                    .unwrap_or(0)
+        };
+        let top = if title.is_some() { 2 } else { 0 };
+        let height =
+            top + bands.iter().map(|band| band_height(band)).sum::<usize>() + bands.len() - 1;
+        let width = bands
+            .iter()
+            .filter_map(|band| band.last().map(|&(index, x)| x + widths[index]))
+            .max()
+            .unwrap_or(1)
+            .max(title.as_ref().map_or(0, |title| title.chars().count()));
+        let out_width = (width + padding * 2).min(self.config.cols);
+        let out_height = (height + padding * 2).min(self.config.rows);
+        let mut canvas = GridCanvas::new(out_width, out_height, padding);
+        let limit = out_width.saturating_sub(padding * 2);
+
+        if let Some(title) = &title {
+            let x = width.saturating_sub(title.chars().count()) / 2;
+            canvas.text(x, 0, title, limit);
+        }
+
+        let section_names: Vec<String> = ir
+            .clusters
+            .iter()
+            .map(|cluster| {
+                cluster
+                    .title
+                    .and_then(|label_id| ir.labels.get(label_id.0))
+                    .map(|label| self.cell_text(&label.text))
+                    .unwrap_or_default()
+            })
+            .collect();
+        let mut y = top;
+        for band in &bands {
+            let Some(&(last_index, last_x)) = band.last() else {
+                continue;
+            };
+            let band_end = last_x + widths[last_index];
+
+            // A section name starts each run of its columns, ruled out to the next run.
+            if has_sections {
+                let mut start = 0;
+                while let Some(&(index, x)) = band.get(start) {
+                    let section = columns[index].section;
+                    let run = band[start..]
+                        .iter()
+                        .take_while(|&&(other, _)| columns[other].section == section)
+                        .count();
+                    let (end_index, end_x) = band[start + run - 1];
+                    let end = end_x + widths[end_index];
+                    if let Some(name) = section.and_then(|section| section_names.get(section)) {
+                        canvas.text(x, y, name, end.min(limit));
+                        let rule_start = x + name.chars().count() + 1;
+                        for rule_x in rule_start..end.min(limit) {
+                            canvas.set(rule_x, y, rule);
+                        }
+                    }
+                    start += run;
+                }
+                y += 1;
+            }
+
+            for &(index, x) in band {
+                canvas.text(x, y, &columns[index].head, limit);
+            }
+            for axis_x in 0..band_end.min(limit) {
+                canvas.set(axis_x, y + 1, rule);
+            }
+            for &(index, x) in band {
+                canvas.set(x, y + 1, glyphs.marker);
+                for (row, text) in columns[index].body.iter().enumerate() {
+                    canvas.text(x, y + 2 + row, text, limit);
+                }
+            }
+            y += band_height(band) + 1;
+        }
+
+        Some(TermRenderResult {
+            output: canvas.buffer.to_output_string(),
+            width: out_width,
+            height: out_height,
+            tier: self.config.tier,
+            render_mode: self.config.render_mode,
+            node_count: layout.nodes.len(),
+            edge_count: layout.edges.len(),
+            summary_only: false,
+        })
+    }
+}
+
+/// The node's label, or its id when it has none.
+fn node_text(ir: &MermaidDiagramIr, index: usize) -> &str {
+    let node = &ir.nodes[index];
+    node.label
+        .and_then(|label_id| ir.labels.get(label_id.0))
+        .map_or(node.id.as_str(), |label| label.text.trim())
+}
+
+/// Index of the section (cluster) holding the node.
+fn section_of(ir: &MermaidDiagramIr, index: usize) -> Option<usize> {
+    ir.clusters
+        .iter()
+        .position(|cluster| cluster.members.contains(&IrNodeId(index)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::TermRenderConfig;
+    use crate::renderer::render_diagram_with_config;
+    use fm_core::MermaidGlyphMode;
+
+    const SOCIAL: &str = "timeline\n  title History of Social Media\n  section Social\n    2002 : LinkedIn\n    2004 : Facebook : Google\n  section Search\n    2005 : YouTube";
+    const WORKDAY: &str = "journey\n  title My working day\n  section Go to work\n    Make tea: 5: Me\n    Go upstairs: 3: Me\n  section Work\n    Do work: 1: Me, Cat";
+
+    fn render(source: &str, config: &TermRenderConfig, cols: usize) -> String {
+        let parsed = fm_parser::parse(source);
+        render_diagram_with_config(&parsed.ir, config, cols, 40).output
+    }
+
+    fn find(output: &str, needle: &str) -> (usize, usize) {
+        output
+            .lines()
+            .enumerate()
+            .find_map(|(row, line)| {
+                line.find(needle)
+                    .map(|byte| (row, line[..byte].chars().count()))
+            })
+            .unwrap_or_else(|| panic!("{needle:?} missing from\n{output}"))
+    }
+
+    #[test]
+    fn timeline_periods_head_columns_with_their_events_underneath() {
+        let output = render(SOCIAL, &TermRenderConfig::rich(), 120);
+
+        let (period_row, x_2004) = find(&output, "2004");
+        let (_, x_2002) = find(&output, "2002");
+        let (same_row, x_2005) = find(&output, "2005");
+        assert_eq!(period_row, same_row, "{output}");
+        assert!(x_2002 < x_2004 && x_2004 < x_2005, "{output}");
+        // Events stack under their own period, below the axis.
+        let (facebook_row, facebook_x) = find(&output, "Facebook");
+        let (google_row, google_x) = find(&output, "Google");
+        assert_eq!(facebook_x, x_2004, "{output}");
+        assert_eq!(google_x, x_2004, "{output}");
+        assert_eq!(facebook_row, period_row + 2, "{output}");
+        assert_eq!(google_row, facebook_row + 1, "{output}");
+        assert_eq!(output.matches('●').count(), 3, "{output}");
+        // Section names sit above the first period of their run.
+        let (social_row, social_x) = find(&output, "Social ─");
+        assert_eq!((social_row + 1, social_x), (period_row, x_2002), "{output}");
+        assert_eq!(find(&output, "Search").1, x_2005, "{output}");
+        assert!(output.contains("History of Social Media"), "{output}");
+    }
+
+    #[test]
+    fn narrow_viewports_wrap_the_band() {
+        let output = render(SOCIAL, &TermRenderConfig::rich(), 24);
+
+        assert!(
+            find(&output, "2005").0 > find(&output, "Facebook").0,
+            "{output}"
+        );
+        assert_eq!(output.matches('●').count(), 3, "{output}");
+    }
+
+    #[test]
+    fn journey_steps_show_score_meters_and_actors() {
+        let output = render(WORKDAY, &TermRenderConfig::rich(), 120);
+
+        let (tea_row, tea_x) = find(&output, "Make tea");
+        assert_eq!(find(&output, "●●●●●"), (tea_row + 2, tea_x), "{output}");
+        assert_eq!(
+            find(&output, "●●●○○").1,
+            find(&output, "Go upstairs").1,
+            "{output}"
+        );
+        assert_eq!(
+            find(&output, "●○○○○").1,
+            find(&output, "Do work").1,
+            "{output}"
+        );
+        assert!(output.contains("Me, Cat"), "{output}");
+        assert!(output.contains("Go to work"), "{output}");
+        assert!(output.contains("My working day"), "{output}");
+    }
+
+    #[test]
+    fn ascii_mode_uses_only_ascii() {
+        let config = TermRenderConfig {
+            glyph_mode: MermaidGlyphMode::Ascii,
+            ..TermRenderConfig::rich()
+        };
+        let journey = render(WORKDAY, &config, 120);
+        let timeline = render(SOCIAL, &config, 120);
+
+        assert!(journey.is_ascii(), "{journey}");
+        assert!(journey.contains("###--"), "{journey}");
+        assert!(timeline.is_ascii(), "{timeline}");
+        assert!(timeline.contains("Facebook"), "{timeline}");
+    }
+}
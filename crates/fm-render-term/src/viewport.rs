@@ -0,0 +1,332 @@
+//! Panning over diagrams too big for the terminal.
+//!
+//! Instead of shrinking a large diagram into the viewport, the diagram is drawn once onto a
+//! virtual canvas at its natural cell size and a [`Viewport`] window is cut out of it. The
+//! viewport is the same fractional rectangle the minimap draws, so a host can show both and
+//! keep them in step. Cutting is escape-aware: colors and hyperlinks that start left of or
+//! above the window carry into it, and every row ends with them closed.
+
+use fm_core::MermaidDiagramIr;
+use fm_layout::{DiagramLayout, layout_diagram};
+
+use crate::config::TermRenderConfig;
+use crate::minimap::Viewport;
+use crate::renderer::{TermRenderResult, render_diagram_with_layout_and_config};
+use crate::text::char_width;
+
+/// Upper bound on each side of the virtual canvas, in cells.
+const MAX_VIRTUAL_CELLS: usize = 2048;
+
+/// Size of the virtual canvas a viewport pans over, in cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VirtualCanvas {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl VirtualCanvas {
+    /// Furthest `(x, y)` cell offset a `cols` x `rows` window can scroll to.
+    #[must_use]
+    pub const fn max_scroll(&self, cols: usize, rows: usize) -> (usize, usize) {
+        (
+            self.width.saturating_sub(cols),
+            self.height.saturating_sub(rows),
+        )
+    }
+
+    /// Viewport of a `cols` x `rows` window whose top-left cell is `(x, y)`, with the offset
+    /// clamped to [`max_scroll`](Self::max_scroll).
+    #[must_use]
+    pub fn viewport(&self, x: usize, y: usize, cols: usize, rows: usize) -> Viewport {
+        let (max_x, max_y) = self.max_scroll(cols, rows);
+        let width = self.width.max(1) as f32;
+        let height = self.height.max(1) as f32;
+        Viewport {
+            x: x.min(max_x) as f32 / width,
+            y: y.min(max_y) as f32 / height,
+            width: cols.min(self.width) as f32 / width,
+            height: rows.min(self.height) as f32 / height,
+        }
+    }
+
+    /// The cell rectangle `(x, y, width, height)` a viewport covers, never past the canvas.
+    #[must_use]
+    pub fn window(&self, viewport: &Viewport) -> (usize, usize, usize, usize) {
+        let viewport = viewport.normalized();
+        let x = ((viewport.x * self.width as f32).round() as usize).min(self.width);
+        let y = ((viewport.y * self.height as f32).round() as usize).min(self.height);
+        let width = ((viewport.width * self.width as f32).round() as usize).min(self.width - x);
+        let height = ((viewport.height * self.height as f32).round() as usize).min(self.height - y);
+        (x, y, width, height)
+    }
+}
+
+/// Size of the virtual canvas [`render_term_viewport`] pans over.
+#[must_use]
+pub fn term_virtual_canvas(ir: &MermaidDiagramIr, config: &TermRenderConfig) -> VirtualCanvas {
+    let layout = layout_diagram(ir);
+    let canvas = render_virtual_canvas(ir, &layout, config);
+    VirtualCanvas {
+        width: canvas.width,
+        height: canvas.height,
+    }
+}
+
+/// Render the part of a diagram inside `viewport`, with the diagram at its natural size rather
+/// than scaled to fit. The window is at most `max_width` x `max_height` cells.
+#[must_use]
+pub fn render_term_viewport(
+    ir: &MermaidDiagramIr,
+    config: &TermRenderConfig,
+    viewport: Viewport,
+) -> TermRenderResult {
+    let layout = layout_diagram(ir);
+    render_term_viewport_with_layout(ir, &layout, config, viewport)
+}
+
+/// [`render_term_viewport`] with a pre-computed layout.
+#[must_use]
+pub fn render_term_viewport_with_layout(
+    ir: &MermaidDiagramIr,
+    layout: &DiagramLayout,
+    config: &TermRenderConfig,
+    viewport: Viewport,
+) -> TermRenderResult {
+    let canvas = render_virtual_canvas(ir, layout, config);
+    let bounds = VirtualCanvas {
+        width: canvas.width,
+        height: canvas.height,
+    };
+    let (x, y, width, height) = bounds.window(&viewport);
+    let width = width.min(config.max_width);
+    let height = height.min(config.max_height);
+
+    let output = canvas
+        .output
+        .split('\n')
+        .skip(y)
+        .take(height)
+        .map(|line| crop_line(line, x, width))
+        .collect::<Vec<_>>()
+        .join("\n");
+    TermRenderResult {
+        output,
+        width,
+        height,
+        ..canvas
+    }
+}
+
+fn render_virtual_canvas(
+    ir: &MermaidDiagramIr,
+    layout: &DiagramLayout,
+    config: &TermRenderConfig,
+) -> TermRenderResult {
+    let unbounded = TermRenderConfig {
+        max_width: MAX_VIRTUAL_CELLS,
+        max_height: MAX_VIRTUAL_CELLS,
+        ..config.clone()
+    };
+    let mut canvas = render_diagram_with_layout_and_config(
+        ir,
+        layout,
+        &unbounded,
+        MAX_VIRTUAL_CELLS,
+        MAX_VIRTUAL_CELLS,
+    );
+    // Dedicated renderers size themselves to their content; make sure the reported size
+    // covers every row and column actually drawn. Node diagrams are drawn from the canvas
+    // origin but sized with their layout margins, so blank columns past the rightmost drawn
+    // cell are dropped down to the padding.
+    canvas.height = canvas.height.max(canvas.output.split('\n').count());
+    let drawn = canvas
+        .output
+        .split('\n')
+        .map(drawn_width)
+        .max()
+        .unwrap_or(0);
+    canvas.width = if drawn == 0 {
+        canvas.width
+    } else {
+        (drawn + config.padding).min(canvas.width.max(drawn))
+    };
+    canvas
+}
+
+/// An escape sequence at the start of `chars`, after its `ESC`: CSI (`ESC [ … final`) or OSC
+/// (`ESC ] … ESC \`).
+pub(crate) fn take_escape(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> String {
+    let mut sequence = String::from('\x1b');
+    match chars.peek() {
+        Some('[') => {
+            for ch in chars.by_ref() {
+                sequence.push(ch);
+                if ch != '[' && ('@'..='~').contains(&ch) {
+                    break;
+                }
+            }
+        }
+        Some(']') => {
+            while let Some(ch) = chars.next() {
+                sequence.push(ch);
+                if ch == '\x07' {
+                    break;
+                }
+                if ch == '\x1b' && chars.peek() == Some(&'\\') {
+                    sequence.push('\\');
+                    chars.next();
+                    break;
+                }
+            }
+        }
+        _ => {}
+    }
+    sequence
+}
+
+/// Columns `line` takes up on screen, not counting escape sequences.
+pub(crate) fn visible_width(line: &str) -> usize {
+    let mut chars = line.chars().peekable();
+    let mut width = 0;
+    while let Some(ch) = chars.next() {
+        if ch == '\x1b' {
+            take_escape(&mut chars);
+        } else {
+            width += char_width(ch);
+        }
+    }
+    width
+}
+
+/// Columns `line` takes up on screen up to its last non-blank character, not counting escape
+/// sequences. Spaces and empty braille cells are blank.
+fn drawn_width(line: &str) -> usize {
+    let mut chars = line.chars().peekable();
+    let (mut width, mut drawn) = (0, 0);
+    while let Some(ch) = chars.next() {
+        if ch == '\x1b' {
+            take_escape(&mut chars);
+        } else {
+            width += char_width(ch);
+            if ch != ' ' && ch != '\u{2800}' {
+                drawn = width;
+            }
+        }
+    }
+    drawn
+}
+
+/// Columns `start..start + width` of `line`, counted in visible characters. The color and
+/// hyperlink in effect at `start` are re-opened, and whatever is still open at the end closed.
+fn crop_line(line: &str, start: usize, width: usize) -> String {
+    let mut out = String::new();
+    let mut sgr: Option<String> = None;
+    let mut link: Option<String> = None;
+    let mut col = 0;
+    let mut entered = false;
+    let mut chars = line.chars().peekable();
+    while col < start + width {
+        let Some(ch) = chars.next() else {
+            break;
+        };
+        if col >= start && !entered {
+            out.extend(sgr.iter().chain(&link).map(String::as_str));
+            entered = true;
+        }
+        if ch == '\x1b' {
+            let sequence = take_escape(&mut chars);
+            if sequence.starts_with("\x1b]8;") {
+                link = (!sequence.starts_with("\x1b]8;;\x1b")).then(|| sequence.clone());
+            } else if sequence.ends_with('m') {
+                if sequence == "\x1b[0m" || sequence == "\x1b[m" {
+                    sgr = None;
+                } else {
+                    sgr.get_or_insert_default().push_str(&sequence);
+                }
+            }
+            if entered {
+                out.push_str(&sequence);
+            }
+            continue;
+        }
+        if entered {
+            out.push(ch);
+        }
+        col += char_width(ch);
+    }
+    if entered {
+        if sgr.is_some() {
+            out.push_str("\x1b[0m");
+        }
+        if link.is_some() {
+            out.push_str("\x1b]8;;\x1b\\");
+        }
+    }
+    out.truncate(out.trim_end_matches(' ').len());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::ColorMode;
+
+    const CHAIN: &str =
+        "flowchart LR\n  A[Alpha] --> B[Beta] --> C[Gamma] --> D[Delta] --> E[Epsilon] --> F[Zeta]";
+
+    #[test]
+    fn windows_pan_across_the_natural_size_canvas() {
+        let parsed = fm_parser::parse(CHAIN);
+        let config = TermRenderConfig::rich();
+        let canvas = term_virtual_canvas(&parsed.ir, &config);
+        assert!(canvas.width > 40, "{canvas:?}");
+
+        let rows = canvas.height;
+        let (max_x, max_y) = canvas.max_scroll(40, rows);
+        assert_eq!((max_x, max_y), (canvas.width - 40, 0));
+        let left = render_term_viewport(&parsed.ir, &config, canvas.viewport(0, 0, 40, rows));
+        let right = render_term_viewport(&parsed.ir, &config, canvas.viewport(max_x, 0, 40, rows));
+
+        assert_eq!((left.width, left.height), (40, rows));
+        assert!(left.output.lines().all(|line| line.chars().count() <= 40));
+        assert!(left.output.contains("Alpha") && !left.output.contains("Zeta"));
+        assert!(right.output.contains("Zeta") && !right.output.contains("Alpha"));
+        // Scrolling past the end clamps to the last full window.
+        let past = render_term_viewport(&parsed.ir, &config, canvas.viewport(9999, 0, 40, rows));
+        assert_eq!(past.output, right.output);
+    }
+
+    #[test]
+    fn cropping_carries_colors_into_the_window() {
+        assert_eq!(
+            crop_line("\x1b[31mabcdef\x1b[0m", 2, 2),
+            "\x1b[31mcd\x1b[0m"
+        );
+        assert_eq!(
+            crop_line("\x1b]8;;u\x1b\\link\x1b]8;;\x1b\\ plain", 1, 2),
+            "\x1b]8;;u\x1b\\in\x1b]8;;\x1b\\"
+        );
+        assert_eq!(crop_line("ab", 5, 3), "");
+
+        let parsed = fm_parser::parse(CHAIN);
+        let config = TermRenderConfig {
+            color_mode: ColorMode::Ansi256,
+            ..TermRenderConfig::rich()
+        };
+        let canvas = term_virtual_canvas(&parsed.ir, &config);
+        let window = render_term_viewport(
+            &parsed.ir,
+            &config,
+            canvas.viewport(10, 0, 30, canvas.height),
+        );
+        for line in window.output.lines() {
+            assert!(visible_width(line) <= 30, "{line:?}");
+            // Whatever color the row last switched to is reset before it ends.
+            let last_sgr = line.rfind("\x1b[").map(|index| &line[index..]);
+            assert!(
+                last_sgr.is_none_or(|sgr| sgr.starts_with("\x1b[0m")),
+                "{line:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,343 @@
+//! [`ratatui`] widget for embedding diagrams in TUI apps (`ratatui` feature).
+//!
+//! [`DiagramWidget`] draws with the same renderer as [`render_term_with_config`](crate::render_term_with_config)
+//! and copies the resulting cells, colors included, into the frame buffer. Used as a plain
+//! [`Widget`] the diagram is scaled to fit the area. Used as a [`StatefulWidget`] it is drawn at
+//! its natural size and panned by [`DiagramWidgetState::scroll`], like
+//! [`render_term_viewport`](crate::render_term_viewport).
+//!
+//! ```rust,ignore
+//! let mut state = DiagramWidgetState::default();
+//! frame.render_stateful_widget(DiagramWidget::new(&ir), area, &mut state);
+//! // On arrow keys:
+//! state.scroll_by(4, 0);
+//! ```
+
+use fm_core::{MermaidDiagramIr, MermaidTier};
+use fm_layout::{DiagramLayout, layout_diagram};
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::widgets::{StatefulWidget, Widget};
+
+use crate::config::{ResolvedConfig, TermRenderConfig};
+use crate::renderer::{TermRenderer, render_diagram_with_layout_and_config};
+use crate::text::char_width;
+use crate::viewport::{VirtualCanvas, render_term_viewport_with_layout, term_virtual_canvas};
+
+/// A diagram drawn into a ratatui [`Buffer`].
+pub struct DiagramWidget<'a> {
+    ir: &'a MermaidDiagramIr,
+    layout: Option<&'a DiagramLayout>,
+    config: TermRenderConfig,
+}
+
+/// Scroll position, zoom tier and selection of a [`DiagramWidget`], kept by the host between
+/// frames.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiagramWidgetState {
+    /// Top-left cell of the visible window on the natural-size canvas. Clamped to the canvas on
+    /// every render.
+    pub scroll: (usize, usize),
+    /// Fidelity tier overriding the widget config's, e.g. to zoom between compact and rich.
+    pub tier: Option<MermaidTier>,
+    /// IR index of a node whose label is highlighted.
+    pub selected: Option<usize>,
+    /// Size of the canvas at the last render, for scrollbars and key handling.
+    pub canvas: Option<VirtualCanvas>,
+}
+
+impl DiagramWidgetState {
+    /// Move the window by `(dx, dy)` cells; the next render clamps it to the canvas.
+    pub fn scroll_by(&mut self, dx: isize, dy: isize) {
+        self.scroll.0 = self.scroll.0.saturating_add_signed(dx);
+        self.scroll.1 = self.scroll.1.saturating_add_signed(dy);
+    }
+}
+
+impl<'a> DiagramWidget<'a> {
+    #[must_use]
+    pub fn new(ir: &'a MermaidDiagramIr) -> Self {
+        Self {
+            ir,
+            layout: None,
+            config: TermRenderConfig::default(),
+        }
+    }
+
+    /// Draw from a pre-computed layout instead of laying the diagram out on every frame.
+    #[must_use]
+    pub fn with_layout(mut self, layout: &'a DiagramLayout) -> Self {
+        self.layout = Some(layout);
+        self
+    }
+
+    #[must_use]
+    pub fn with_config(mut self, config: TermRenderConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    fn draw(
+        &self,
+        layout: &DiagramLayout,
+        area: Rect,
+        buf: &mut Buffer,
+        state: &mut DiagramWidgetState,
+    ) {
+        let mut config = self.config.clone();
+        if let Some(tier) = state.tier {
+            config.tier = tier;
+        }
+        let (cols, rows) = (usize::from(area.width), usize::from(area.height));
+        config.max_width = cols;
+        config.max_height = rows;
+
+        let canvas = term_virtual_canvas(self.ir, &config);
+        let (max_x, max_y) = canvas.max_scroll(cols, rows);
+        state.scroll = (state.scroll.0.min(max_x), state.scroll.1.min(max_y));
+        state.canvas = Some(canvas);
+        let viewport = canvas.viewport(state.scroll.0, state.scroll.1, cols, rows);
+        let result = render_term_viewport_with_layout(self.ir, layout, &config, viewport);
+
+        write_cells(&result.output, area, buf);
+        let renderer = TermRenderer::new(ResolvedConfig::resolve(&config, cols, rows));
+        let selected = state.selected.and_then(|index| {
+            let node = self.ir.nodes.get(index)?;
+            renderer.node_display_label(self.ir, Some(node), &node.id)
+        });
+        if let Some(label) = selected {
+            // Match the first label row, minus any truncation ellipsis.
+            let first = label.lines().next().unwrap_or_default();
+            highlight(first.trim_end_matches('…'), area, buf);
+        }
+    }
+}
+
+impl Widget for DiagramWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let owned;
+        let layout = match self.layout {
+            Some(layout) => layout,
+            None => {
+                owned = layout_diagram(self.ir);
+                &owned
+            }
+        };
+        let (cols, rows) = (usize::from(area.width), usize::from(area.height));
+        let config = TermRenderConfig {
+            max_width: cols,
+            max_height: rows,
+            ..self.config.clone()
+        };
+        let result = render_diagram_with_layout_and_config(self.ir, layout, &config, cols, rows);
+        write_cells(&result.output, area, buf);
+    }
+}
+
+impl StatefulWidget for DiagramWidget<'_> {
+    type State = DiagramWidgetState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let owned;
+        let layout = match self.layout {
+            Some(layout) => layout,
+            None => {
+                owned = layout_diagram(self.ir);
+                &owned
+            }
+        };
+        self.draw(layout, area, buf, state);
+    }
+}
+
+/// Copy rendered output into `buf`, turning SGR foreground colors into cell styles and skipping
+/// OSC sequences such as hyperlinks. Blank cells are left as the host drew them.
+fn write_cells(output: &str, area: Rect, buf: &mut Buffer) {
+    let mut style = Style::default();
+    for (row, line) in output
+        .split('\n')
+        .take(usize::from(area.height))
+        .enumerate()
+    {
+        let mut col = 0;
+        let mut chars = line.chars().peekable();
+        while let Some(ch) = chars.next() {
+            if ch == '\x1b' {
+                match chars.next() {
+                    Some('[') => {
+                        let mut params = String::new();
+                        for next in chars.by_ref() {
+                            if ('@'..='~').contains(&next) {
+                                if next == 'm' {
+                                    style = apply_sgr(style, &params);
+                                }
+                                break;
+                            }
+                            params.push(next);
+                        }
+                    }
+                    Some(']') => {
+                        while let Some(next) = chars.next() {
+                            if next == '\x07'
+                                || (next == '\x1b' && chars.next_if_eq(&'\\').is_some())
+                            {
+                                break;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            let width = char_width(ch);
+            if width == 0 {
+                continue;
+            }
+            if col + width > usize::from(area.width) {
+                break;
+            }
+            if !(ch.is_whitespace() || ch == '\u{2800}')
+                && let Some(cell) = buf.cell_mut((area.x + col as u16, area.y + row as u16))
+            {
+                cell.set_char(ch).set_style(style);
+            }
+            col += width;
+        }
+        style = Style::default();
+    }
+}
+
+/// Style after an SGR sequence with parameters `params` (`"38;2;r;g;b"`, `"91"`, `"0"`, …).
+fn apply_sgr(style: Style, params: &str) -> Style {
+    let codes: Vec<u8> = params
+        .split(';')
+        .map(|code| code.parse().unwrap_or(0))
+        .collect();
+    match codes.as_slice() {
+        [] | [0] => Style::default(),
+        [38, 2, r, g, b] => style.fg(Color::Rgb(*r, *g, *b)),
+        [38, 5, index] => style.fg(Color::Indexed(*index)),
+        [code @ (30..=37 | 90..=97)] => style.fg(ansi16_color(*code)),
+        _ => style,
+    }
+}
+
+const fn ansi16_color(code: u8) -> Color {
+    match code {
+        30 => Color::Black,
+        31 => Color::Red,
+        32 => Color::Green,
+        33 => Color::Yellow,
+        34 => Color::Blue,
+        35 => Color::Magenta,
+        36 => Color::Cyan,
+        37 => Color::Gray,
+        90 => Color::DarkGray,
+        91 => Color::LightRed,
+        92 => Color::LightGreen,
+        93 => Color::LightYellow,
+        94 => Color::LightBlue,
+        95 => Color::LightMagenta,
+        96 => Color::LightCyan,
+        _ => Color::White,
+    }
+}
+
+/// Reverse the first occurrence of `label` in the drawn area.
+fn highlight(label: &str, area: Rect, buf: &mut Buffer) {
+    let needle: Vec<char> = label.chars().collect();
+    if needle.is_empty() {
+        return;
+    }
+    for y in area.top()..area.bottom() {
+        let row: Vec<char> = (area.left()..area.right())
+            .map(|x| buf[(x, y)].symbol().chars().next().unwrap_or(' '))
+            .collect();
+        if let Some(start) = row
+            .windows(needle.len())
+            .position(|window| window == needle)
+        {
+            for offset in 0..needle.len() {
+                let x = area.x + (start + offset) as u16;
+                buf[(x, y)].modifier.insert(Modifier::REVERSED);
+            }
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::ColorMode;
+
+    const CHAIN: &str =
+        "flowchart LR\n  A[Alpha] --> B[Beta] --> C[Gamma] --> D[Delta] --> E[Epsilon] --> F[Zeta]";
+
+    fn row_text(buf: &Buffer, y: u16) -> String {
+        (0..buf.area.width)
+            .map(|x| buf[(x, y)].symbol().to_string())
+            .collect()
+    }
+
+    fn contains(buf: &Buffer, needle: &str) -> bool {
+        (0..buf.area.height).any(|y| row_text(buf, y).contains(needle))
+    }
+
+    #[test]
+    fn plain_widget_fits_the_diagram_into_the_area() {
+        let parsed = fm_parser::parse(CHAIN);
+        let area = Rect::new(0, 0, 120, 16);
+        let mut buf = Buffer::empty(area);
+        let widget = DiagramWidget::new(&parsed.ir).with_config(TermRenderConfig::rich());
+        Widget::render(widget, area, &mut buf);
+
+        assert!(contains(&buf, "Alpha") && contains(&buf, "Zeta"));
+    }
+
+    #[test]
+    fn stateful_widget_pans_and_clamps_the_scroll_offset() {
+        let parsed = fm_parser::parse(CHAIN);
+        let area = Rect::new(0, 0, 40, 20);
+        let mut state = DiagramWidgetState {
+            scroll: (usize::MAX, 0),
+            ..DiagramWidgetState::default()
+        };
+        let mut buf = Buffer::empty(area);
+        let widget = DiagramWidget::new(&parsed.ir).with_config(TermRenderConfig::rich());
+        StatefulWidget::render(widget, area, &mut buf, &mut state);
+
+        let canvas = state.canvas.expect("canvas size recorded");
+        assert_eq!(state.scroll.0, canvas.max_scroll(40, 20).0);
+        assert!(contains(&buf, "Zeta") && !contains(&buf, "Alpha"));
+    }
+
+    #[test]
+    fn colors_and_selection_become_cell_styles() {
+        let parsed = fm_parser::parse("flowchart LR\n  A[Alpha] --> B[Beta]");
+        let area = Rect::new(0, 0, 60, 16);
+        let mut state = DiagramWidgetState {
+            selected: Some(0),
+            ..DiagramWidgetState::default()
+        };
+        let mut buf = Buffer::empty(area);
+        let widget = DiagramWidget::new(&parsed.ir).with_config(TermRenderConfig {
+            color_mode: ColorMode::TrueColor,
+            ..TermRenderConfig::rich()
+        });
+        StatefulWidget::render(widget, area, &mut buf, &mut state);
+
+        let cells = || (0..area.height).flat_map(|y| (0..area.width).map(move |x| (x, y)));
+        assert!(
+            cells().any(|pos| buf[pos].fg == Color::Rgb(0x63, 0x66, 0xf1)),
+            "palette node color missing"
+        );
+        let reversed: String = cells()
+            .filter(|&pos| buf[pos].modifier.contains(Modifier::REVERSED))
+            .map(|pos| buf[pos].symbol().to_string())
+            .collect();
+        assert_eq!(reversed, "Alpha");
+        assert!(!contains(&buf, "\x1b"));
+    }
+}
@@ -0,0 +1,398 @@
+//! Dedicated terminal renderer for xy charts.
+//!
+//! Values are scaled to the range from [`layout_xychart`], widened to round tick values at the
+//! ends the source leaves open. Bars are block-character columns grouped per category, and lines
+//! are plotted on a Braille sub-cell canvas so slopes stay smooth. Named series get a legend:
+//!
+//! ```text
+//!           Sales
+//! Revenue
+//!  40┤            ⢀⠤
+//!  30┤       ⢀⡠⠔⠊██
+//!  20┤  ⣀⠤⠒⠉  ██  ██
+//!  10┤ ██    ██  ██
+//!   0└──────────────
+//!     jan  feb  mar
+//!
+//! █ Revenue  ⠤ Target
+//! ```
+
+use fm_core::{IrXySeriesKind, MermaidDiagramIr, MermaidGlyphMode, MermaidRenderMode};
+use fm_layout::DiagramLayout;
+use fm_layout::chart::{XyChartLayout, layout_xychart};
+
+use crate::canvas::Canvas;
+use crate::glyphs::XyChartGlyphs;
+use crate::renderer::{GridCanvas, TermRenderResult, TermRenderer};
+
+/// Plot height bounds, in rows.
+const MIN_PLOT_ROWS: usize = 4;
+const MAX_PLOT_ROWS: usize = 16;
+/// Widest a category's band gets, so short series stay compact.
+const MAX_BAND_CELLS: usize = 12;
+/// Category labels get at least this many cells, skipping categories when bands are narrower.
+const MIN_CATEGORY_CELLS: usize = 3;
+/// Braille dots per cell.
+const DOTS_X: usize = 2;
+const DOTS_Y: usize = 4;
+
+impl TermRenderer {
+    /// Draw an xy chart with tick-labelled axes, block bars, Braille lines and a legend for named
+    /// series. `None` when the chart has no series.
+    pub(crate) fn render_xychart(
+        &self,
+        ir: &MermaidDiagramIr,
+        layout: &DiagramLayout,
+    ) -> Option<TermRenderResult> {
+        let meta = ir.xy_chart_meta.as_ref()?;
+        let xy = layout_xychart(ir)?;
+        let glyphs = XyChartGlyphs::for_mode(self.config.glyph_mode);
+        let rules = &self.box_glyphs;
+        let braille = self.config.glyph_mode == MermaidGlyphMode::Unicode
+            && self.config.render_mode != MermaidRenderMode::CellOnly;
+
+        let title = meta
+            .title
+            .as_deref()
+            .or(ir.meta.title.as_deref())
+            .map(|title| self.cell_text(title));
+        let y_label = meta
+            .y_axis
+            .label
+            .as_deref()
+            .map(|label| self.cell_text(label));
+        let x_label = meta
+            .x_axis
+            .label
+            .as_deref()
+            .map(|label| self.cell_text(label));
+        let mut bar_slots = 0;
+        let swatches: Vec<char> = meta
+            .series
+            .iter()
+            .map(|series| match series.kind {
+                IrXySeriesKind::Bar => {
+                    bar_slots += 1;
+                    glyphs.bars[(bar_slots - 1) % glyphs.bars.len()]
+                }
+                IrXySeriesKind::Line | IrXySeriesKind::Area if braille => glyphs.braille_swatch,
+                IrXySeriesKind::Line | IrXySeriesKind::Area => glyphs.point,
+            })
+            .collect();
+        let legend: Vec<String> = meta
+            .series
+            .iter()
+            .zip(&swatches)
+            .filter_map(|(series, swatch)| {
+                let name = series.name.as_deref().filter(|name| !name.is_empty())?;
+                Some(format!("{swatch} {}", self.cell_text(name)))
+            })
+            .collect();
+        let legend = (!legend.is_empty()).then(|| legend.join("  "));
+
+        let padding = self.config.padding;
+        let available_cols = self.config.cols.saturating_sub(padding * 2);
+        let available_rows = self.config.rows.saturating_sub(padding * 2);
+        let top = if title.is_some() { 2 } else { 0 } + usize::from(y_label.is_some());
+        let below = 2 + usize::from(x_label.is_some()) + if legend.is_some() { 2 } else { 0 };
+        let plot_height = available_rows
+            .saturating_sub(top + below)
+            .clamp(MIN_PLOT_ROWS, MAX_PLOT_ROWS);
+
+        // Open ends of the range round out to the next tick, so the axis ends on a labelled value.
+        let step = xy.tick_step(plot_height / 2);
+        let xy = XyChartLayout {
+            y_min: if meta.y_axis.min.is_none() {
+                (xy.y_min / step).floor() * step
+            } else {
+                xy.y_min
+            },
+            y_max: if meta.y_axis.max.is_none() {
+                (xy.y_max / step).ceil() * step
+            } else {
+                xy.y_max
+            },
+            ..xy
+        };
+        let ticks: Vec<(f32, String)> = ((xy.y_min / step).ceil() as i64
+            ..=(xy.y_max / step).floor() as i64)
+            .map(|index| {
+                let value = index as f32 * step;
+                (value, XyChartLayout::tick_label(value))
+            })
+            .collect();
+
+        let label_width = ticks
+            .iter()
+            .map(|(_, label)| label.chars().count())
+            .max()
+            .unwrap_or(0);
+        let axis_x = label_width;
+        let plot_left = axis_x + 1;
+        let category_count = xy.categories.len().max(1);
+        let band =
+            (available_cols.saturating_sub(plot_left) / category_count).clamp(1, MAX_BAND_CELLS);
+        let plot_width = band * category_count;
+        let axis_y = top + plot_height;
+        // Height above the axis, in cells, and the row holding a given height.
+        let height_of = |value: f32| xy.fraction(value) * plot_height as f32;
+        let row_of = |height: f32| axis_y - (height.round() as usize).min(plot_height);
+
+        let width = [
+            plot_left + plot_width,
+            title.as_ref().map_or(0, |text| text.chars().count()),
+            y_label.as_ref().map_or(0, |text| text.chars().count()),
+            legend.as_ref().map_or(0, |text| text.chars().count()),
+        ]
+        .into_iter()
+        .max()
+        .unwrap_or(0);
+        let height = axis_y + below;
+        let out_width = (width + padding * 2).min(self.config.cols);
+        let out_height = (height + padding * 2).min(self.config.rows);
+        let mut canvas = GridCanvas::new(out_width, out_height, padding);
+        let limit = out_width.saturating_sub(padding * 2);
+
+        if let Some(title) = &title {
+            let x = width.saturating_sub(title.chars().count()) / 2;
+            canvas.text(x, 0, title, limit);
+        }
+        if let Some(label) = &y_label {
+            canvas.text(0, top - 1, label, limit);
+        }
+
+        // Axes, with each tick label right-aligned against its row's tick.
+        for y in top..axis_y {
+            canvas.set(axis_x, y, rules.vertical);
+        }
+        for x in plot_left..plot_left + plot_width {
+            canvas.set(x, axis_y, rules.horizontal);
+        }
+        canvas.set(axis_x, axis_y, rules.bottom_left);
+        for (value, label) in &ticks {
+            let y = row_of(height_of(*value));
+            if y < axis_y {
+                canvas.set(axis_x, y, rules.t_left);
+            }
+            canvas.text(axis_x - label.chars().count(), y, label, limit);
+        }
+
+        // Bars fill from the zero line (or the nearest end of the range) to their value.
+        let baseline = height_of(0.0);
+        let bar_width = if band > 1 {
+            ((band - 1) / bar_slots.max(1)).max(1)
+        } else {
+            1
+        };
+        let group_width = (bar_width * bar_slots).min(band);
+        let mut slot = 0;
+        for (series, &fill) in meta.series.iter().zip(&swatches) {
+            if series.kind != IrXySeriesKind::Bar {
+                continue;
+            }
+            for (category, &value) in series.values.iter().enumerate() {
+                let value_height = height_of(value);
+                let from = baseline.min(value_height).round() as usize;
+                let to = baseline.max(value_height).round() as usize;
+                let left =
+                    plot_left + category * band + (band - group_width) / 2 + slot * bar_width;
+                for x in left..(left + bar_width).min(plot_left + plot_width) {
+                    for level in from..to.min(plot_height) {
+                        canvas.set(x, axis_y - 1 - level, fill);
+                    }
+                }
+            }
+            slot += 1;
+        }
+
+        // Lines join the category centres.
+        let center = |category: usize| category * band + band / 2;
+        let lines: Vec<&[f32]> = meta
+            .series
+            .iter()
+            .filter(|series| series.kind != IrXySeriesKind::Bar)
+            .map(|series| series.values.as_slice())
+            .collect();
+        if braille {
+            let mut dots = Canvas::new(plot_width, plot_height, MermaidRenderMode::Braille);
+            let dot_rows = plot_height * DOTS_Y - 1;
+            let dot = |category: usize, value: f32| {
+                (
+                    (center(category) * DOTS_X) as isize,
+                    ((1.0 - xy.fraction(value)) * dot_rows as f32).round() as isize,
+                )
+            };
+            for values in &lines {
+                for (category, &value) in values.iter().enumerate() {
+                    let (x0, y0) = dot(category, value);
+                    let (x1, y1) = values
+                        .get(category + 1)
+                        .map_or((x0, y0), |&next| dot(category + 1, next));
+                    dots.draw_line(x0, y0, x1, y1);
+                }
+            }
+            for (row, cells) in dots.render_char_grid().iter().enumerate() {
+                for (column, &ch) in cells.iter().enumerate() {
+                    if ch != '\u{2800}' {
+                        canvas.set(plot_left + column, top + row, ch);
+                    }
+                }
+            }
+        } else {
+            for values in &lines {
+                for (category, &value) in values.iter().enumerate() {
+                    let next = values.get(category + 1).copied().unwrap_or(value);
+                    let span = if category + 1 < values.len() { band } else { 1 };
+                    for offset in 0..span {
+                        let t = offset as f32 / band as f32;
+                        let height = height_of((next - value).mul_add(t, value));
+                        let y = row_of(height).min(axis_y - 1);
+                        canvas.set(plot_left + center(category) + offset, y, glyphs.point);
+                    }
+                }
+            }
+        }
+
+        // Category labels, centred in their bands; crowded axes label every few categories.
+        let longest = xy
+            .categories
+            .iter()
+            .map(|category| category.chars().count())
+            .max()
+            .unwrap_or(0);
+        let stride = (1..=category_count)
+            .find(|&stride| band * stride > longest.min(MIN_CATEGORY_CELLS))
+            .unwrap_or(category_count);
+        let label_cells = (band * stride).saturating_sub(1).max(1);
+        for (category, name) in xy.categories.iter().enumerate().step_by(stride) {
+            let text: String = name.chars().take(label_cells).collect();
+            let offset = band.saturating_sub(text.chars().count()) / 2;
+            canvas.text(
+                plot_left + category * band + offset,
+                axis_y + 1,
+                &text,
+                limit,
+            );
+        }
+        if let Some(label) = &x_label {
+            let x = plot_left + plot_width.saturating_sub(label.chars().count()) / 2;
+            canvas.text(x, axis_y + 2, label, limit);
+        }
+        if let Some(legend) = &legend {
+            canvas.text(0, height - 1, legend, limit);
+        }
+
+        Some(TermRenderResult {
+            output: canvas.buffer.to_output_string(),
+            width: out_width,
+            height: out_height,
+            tier: self.config.tier,
+            render_mode: self.config.render_mode,
+            node_count: layout.nodes.len(),
+            edge_count: layout.edges.len(),
+            summary_only: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::TermRenderConfig;
+    use crate::renderer::render_diagram_with_config;
+    use fm_core::MermaidGlyphMode;
+
+    const SALES: &str = "xychart-beta\n  title Sales\n  x-axis [jan, feb, mar]\n  y-axis \"Revenue\" 0 --> 100\n  bar Revenue [10, 50, 100]\n  line Target [15, 25, 35]";
+
+    fn render(source: &str, config: &TermRenderConfig) -> String {
+        let parsed = fm_parser::parse(source);
+        render_diagram_with_config(&parsed.ir, config, 80, 30).output
+    }
+
+    /// Column of `needle` in the first line holding it.
+    fn column_of(output: &str, needle: &str) -> usize {
+        output
+            .lines()
+            .find_map(|line| line.find(needle).map(|byte| line[..byte].chars().count()))
+            .unwrap_or_else(|| panic!("{needle:?} missing from\n{output}"))
+    }
+
+    /// Cells holding `glyph` in the given column.
+    fn column_count(output: &str, column: usize, glyph: char) -> usize {
+        output
+            .lines()
+            .filter(|line| line.chars().nth(column) == Some(glyph))
+            .count()
+    }
+
+    #[test]
+    fn bars_rise_in_proportion_over_labelled_categories() {
+        let output = render(
+            "xychart-beta\n  title Sales\n  x-axis [jan, feb, mar]\n  y-axis \"Revenue\" 0 --> 100\n  bar [10, 50, 100]",
+            &TermRenderConfig::rich(),
+        );
+
+        assert!(output.contains("Sales"), "{output}");
+        assert!(output.contains("Revenue"), "{output}");
+        assert!(output.contains("100┤"), "{output}");
+        assert!(output.contains("0└"), "{output}");
+        let heights: Vec<usize> = ["jan", "feb", "mar"]
+            .iter()
+            .map(|category| column_count(&output, column_of(&output, category) + 1, '█'))
+            .collect();
+        assert!(heights[0] > 0 && heights[0] < heights[1], "{output}");
+        assert_eq!(heights[2], heights[1] * 2, "{output}");
+    }
+
+    #[test]
+    fn lines_are_braille_plotted_and_named_series_get_a_legend() {
+        let output = render(SALES, &TermRenderConfig::rich());
+
+        // Braille dots inside the plot, not just in the legend.
+        assert!(
+            output
+                .lines()
+                .filter(|line| line.contains('┤') || line.contains('│'))
+                .any(|line| line
+                    .chars()
+                    .any(|ch| ('\u{2801}'..='\u{28ff}').contains(&ch))),
+            "{output}"
+        );
+        assert!(output.contains("█ Revenue"), "{output}");
+        assert!(output.contains("⠤ Target"), "{output}");
+    }
+
+    #[test]
+    fn open_ranges_round_out_to_the_next_tick() {
+        let output = render(
+            "xychart-beta\n  x-axis [a, b]\n  bar [12, 47]",
+            &TermRenderConfig::rich(),
+        );
+
+        assert!(output.contains("50┤"), "{output}");
+        assert!(!output.contains("47"), "{output}");
+        // Unnamed series leave the legend out, so the category labels end the chart.
+        let last = output
+            .lines()
+            .rfind(|line| !line.trim().is_empty())
+            .unwrap_or_default();
+        assert_eq!(
+            last.split_whitespace().collect::<Vec<_>>(),
+            ["a", "b"],
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn ascii_mode_plots_lines_with_points() {
+        let config = TermRenderConfig {
+            glyph_mode: MermaidGlyphMode::Ascii,
+            ..TermRenderConfig::rich()
+        };
+        let output = render(SALES, &config);
+
+        assert!(output.is_ascii(), "{output}");
+        assert!(output.contains('#'), "{output}");
+        assert!(output.contains("* Target"), "{output}");
+        assert!(output.matches('*').count() > 3, "{output}");
+    }
+}
@@ -186,6 +186,10 @@ struct RuntimeInitConfig {
 #[serde(default, rename_all = "camelCase")]
 struct SvgConfigOverrides {
     responsive: Option<bool>,
+    width: Option<f32>,
+    height: Option<f32>,
+    max_width: Option<f32>,
+    scale: Option<f32>,
     accessible: Option<bool>,
     font_size: Option<f32>,
     padding: Option<f32>,
@@ -195,6 +199,7 @@ struct SvgConfigOverrides {
     theme: Option<String>,
     enable_links: Option<bool>,
     link_mode: Option<String>,
+    layers: Option<bool>,
 }
 
 #[derive(Debug, Clone, Default, Deserialize)]
@@ -318,6 +323,8 @@ pub struct LayoutRuntimeSummary {
     reversed_edge_total_length: f32,
     total_edge_length: f32,
     phase_iterations: usize,
+    /// Whether the layout budget ran out, leaving a partial layout.
+    budget_exceeded: bool,
     /// Whether this layout used the incremental fast path.
     incremental: bool,
     /// Number of nodes that were recomputed (0 = full layout or cache hit).
@@ -341,6 +348,7 @@ impl LayoutRuntimeSummary {
             reversed_edge_total_length: layout.stats.reversed_edge_total_length,
             total_edge_length: layout.stats.total_edge_length,
             phase_iterations: layout.stats.phase_iterations,
+            budget_exceeded: layout.stats.budget_exceeded,
             incremental: traced_layout.trace.incremental.cache_hit
                 || traced_layout
                     .trace
@@ -490,6 +498,19 @@ fn merge_svg_config(
     if let Some(value) = overrides.responsive {
         merged.responsive = value;
     }
+    let positive = |value: Option<f32>| value.filter(|value| value.is_finite() && *value > 0.0);
+    if let Some(value) = positive(overrides.width) {
+        merged.width = Some(value);
+    }
+    if let Some(value) = positive(overrides.height) {
+        merged.height = Some(value);
+    }
+    if let Some(value) = positive(overrides.max_width) {
+        merged.max_width = Some(value);
+    }
+    if let Some(value) = positive(overrides.scale) {
+        merged.scale = value;
+    }
     if let Some(value) = overrides.accessible {
         merged.accessible = value;
     }
@@ -517,6 +538,9 @@ fn merge_svg_config(
     if let Some(value) = overrides.embed_theme_css {
         merged.embed_theme_css = value;
     }
+    if let Some(value) = overrides.layers {
+        merged.layers = value;
+    }
     if let Some(value) = overrides.link_mode.as_deref() {
         merged.link_mode = parse_link_mode(value)?;
     }
@@ -1689,6 +1713,26 @@ mod tests {
         assert_eq!(merged.rounded_corners, base.rounded_corners);
     }
 
+    #[test]
+    fn merge_svg_config_applies_size_overrides() {
+        let base = SvgRenderConfig::default();
+        let overrides = SvgConfigOverrides {
+            width: Some(640.0),
+            max_width: Some(-1.0),
+            scale: Some(2.0),
+            layers: Some(true),
+            ..SvgConfigOverrides::default()
+        };
+
+        let merged = merge_svg_config(&base, &overrides, None).expect("merge should succeed");
+
+        assert_eq!(merged.width, Some(640.0));
+        assert_eq!(merged.height, None);
+        assert_eq!(merged.max_width, None);
+        assert_eq!(merged.scale, 2.0);
+        assert!(merged.layers);
+    }
+
     #[test]
     fn apply_canvas_theme_preset_updates_canvas_colors() {
         let base = CanvasRenderConfig::default();
@@ -899,9 +899,33 @@ function sanitizeFragment(raw) {
   return out.replace(/^-+|-+$/g, "");
 }
 
-function nodeElementId(nodeId, index) {
+function elementIdHash(raw) {
+  let hash = 0x811c9dc5;
+  for (const byte of new TextEncoder().encode(String(raw ?? ""))) {
+    hash = Math.imul(hash ^ byte, 0x01000193) >>> 0;
+  }
+  return hash.toString(16).padStart(8, "0");
+}
+
+function nodeElementId(nodeId) {
   const fragment = sanitizeFragment(nodeId);
-  return fragment ? `fm-node-${fragment}-${index}` : `fm-node-${index}`;
+  const hash = elementIdHash(nodeId);
+  return fragment ? `fm-node-${fragment}-${hash}` : `fm-node-${hash}`;
+}
+
+function userEdgeElementId(userId) {
+  const fragment = sanitizeFragment(userId);
+  const hash = elementIdHash(userId);
+  return fragment ? `fm-edge-${fragment}-${hash}` : `fm-edge-${hash}`;
+}
+
+function endpointNodeId(endpoint, nodes, ports) {
+  let nodeIndex = endpoint?.Node;
+  if (nodeIndex == null && endpoint?.Port != null) {
+    nodeIndex = ports[endpoint.Port]?.node;
+  }
+  const id = nodeIndex == null ? undefined : nodes[nodeIndex]?.id;
+  return typeof id === "string" ? id : "";
 }
 
 function stringifySourceId(value) {
@@ -938,19 +962,39 @@ export function sourceSpans(input) {
       kind: "node",
       index,
       id: sourceId,
-      elementId: nodeElementId(sourceId ?? "", index),
+      elementId: nodeElementId(sourceId ?? ""),
       span,
     });
   });
 
+  const ports = Array.isArray(ir.ports) ? ir.ports : [];
+  const edgeOccurrences = new Map();
   edges.forEach((edge, index) => {
+    // Named edges (`A e1@--> B`) take their id from the name and leave the occurrence
+    // counter alone, like `MermaidDiagramIr::edge_element_ids`.
+    const userId = edge?.extras?.user_id;
+    let elementId;
+    if (typeof userId === "string") {
+      elementId = userEdgeElementId(userId);
+    } else {
+      const fragment = [
+        sanitizeFragment(endpointNodeId(edge?.from, nodes, ports)),
+        sanitizeFragment(endpointNodeId(edge?.to, nodes, ports)),
+      ]
+        .filter(Boolean)
+        .join("-");
+      const occurrence = edgeOccurrences.get(fragment) ?? 0;
+      edgeOccurrences.set(fragment, occurrence + 1);
+      elementId = fragment ? `fm-edge-${fragment}-${occurrence}` : `fm-edge-${occurrence}`;
+    }
     if (!hasKnownSpan(edge?.span)) {
       return;
     }
     records.push({
       kind: "edge",
       index,
-      elementId: `fm-edge-${index}`,
+      id: typeof userId === "string" ? userId : undefined,
+      elementId,
       span: edge.span,
     });
   });